@@ -27,7 +27,9 @@ const COMBINED_FILTER_TYPE_URL: &str =
     "type.googleapis.com/sf.ethereum.transform.v1.CombinedFilter";
 
 use crate::capabilities::NodeCapabilities;
-use crate::data_source::{BlockHandlerFilter, DataSource};
+use crate::data_source::{
+    BlockHandlerFilter, DataSource, MappingContractCreationHandler, TopicFilter,
+};
 use crate::{Chain, Mapping, ENV_VARS};
 
 pub type EventSignature = H256;
@@ -70,6 +72,8 @@ enum LogFilterNode {
 pub struct EthGetLogsFilter {
     pub contracts: Vec<Address>,
     pub event_signatures: Vec<EventSignature>,
+    /// Restrictions on the other topics of the logs
+    pub topics: TopicFilter,
 }
 
 impl EthGetLogsFilter {
@@ -77,6 +81,7 @@ impl EthGetLogsFilter {
         EthGetLogsFilter {
             contracts: vec![address],
             event_signatures: vec![],
+            topics: TopicFilter::default(),
         }
     }
 
@@ -84,6 +89,7 @@ impl EthGetLogsFilter {
         EthGetLogsFilter {
             contracts: vec![],
             event_signatures: vec![event],
+            topics: TopicFilter::default(),
         }
     }
 }
@@ -115,11 +121,12 @@ pub struct TriggerFilter {
     pub(crate) log: EthereumLogFilter,
     pub(crate) call: EthereumCallFilter,
     pub(crate) block: EthereumBlockFilter,
+    pub(crate) contract_creation: EthereumContractCreationFilter,
 }
 
 impl TriggerFilter {
     pub(crate) fn requires_traces(&self) -> bool {
-        !self.call.is_empty() || self.block.requires_traces()
+        !self.call.is_empty() || self.block.requires_traces() || !self.contract_creation.is_empty()
     }
 
     #[cfg(debug_assertions)]
@@ -145,7 +152,11 @@ impl bc::TriggerFilter<Chain> for TriggerFilter {
         self.call
             .extend(EthereumCallFilter::from_data_sources(data_sources.clone()));
         self.block
-            .extend(EthereumBlockFilter::from_data_sources(data_sources));
+            .extend(EthereumBlockFilter::from_data_sources(data_sources.clone()));
+        self.contract_creation
+            .extend(EthereumContractCreationFilter::from_data_sources(
+                data_sources,
+            ));
     }
 
     fn node_capabilities(&self) -> NodeCapabilities {
@@ -168,10 +179,21 @@ impl bc::TriggerFilter<Chain> for TriggerFilter {
 
             self.block
                 .extend(EthereumBlockFilter::from_mapping(&data_source.mapping));
+
+            self.contract_creation
+                .extend(EthereumContractCreationFilter::from_mapping(
+                    &data_source.mapping,
+                ));
         }
     }
 
     fn to_firehose_filter(self) -> Vec<prost_types::Any> {
+        // Firehose can't filter for contract creations, so we need all
+        // blocks in full
+        if !self.contract_creation.is_empty() {
+            return Vec::new();
+        }
+
         let EthereumBlockFilter {
             polling_intervals,
             contract_addresses: _contract_addresses,
@@ -210,6 +232,16 @@ pub struct EthereumLogFilter {
     /// Event sigs with no associated address, matching on all addresses.
     /// Maps to a boolean representing if a trigger requires a transaction receipt.
     wildcard_events: HashMap<EventSignature, bool>,
+
+    /// Restrictions on the other topics of wildcard events. Events without
+    /// an entry match logs with any topics.
+    wildcard_topics: HashMap<EventSignature, TopicFilter>,
+
+    /// Pairs of contract and event whose handlers only match logs with
+    /// certain topics. They are not part of the graph so that their topics
+    /// can be passed to `eth_getLogs`. Maps to a boolean representing if a
+    /// trigger requires a transaction receipt and the topics to match.
+    contract_topics: HashMap<(Address, EventSignature), (bool, TopicFilter)>,
 }
 
 impl From<EthereumLogFilter> for Vec<LogFilter> {
@@ -219,6 +251,7 @@ impl From<EthereumLogFilter> for Vec<LogFilter> {
                 |EthGetLogsFilter {
                      contracts,
                      event_signatures,
+                     topics: _,
                  }| LogFilter {
                     addresses: contracts
                         .iter()
@@ -250,7 +283,15 @@ impl EthereumLogFilter {
                 self.contracts_and_events_graph
                     .all_edges()
                     .any(|(s, t, _)| (s == contract && t == event) || (t == contract && s == event))
-                    || self.wildcard_events.contains_key(sig)
+                    || self
+                        .contract_topics
+                        .get(&(log.address, *sig))
+                        .map_or(false, |(_, topics)| topics.matches(log))
+                    || (self.wildcard_events.contains_key(sig)
+                        && self
+                            .wildcard_topics
+                            .get(sig)
+                            .map_or(true, |topics| topics.matches(log)))
             }
         }
     }
@@ -263,6 +304,10 @@ impl EthereumLogFilter {
     ) -> bool {
         if let Some(true) = self.wildcard_events.get(event_signature) {
             true
+        } else if let Some((true, _)) = contract_address
+            .and_then(|address| self.contract_topics.get(&(*address, *event_signature)))
+        {
+            true
         } else if let Some(address) = contract_address {
             let contract = LogFilterNode::Contract(*address);
             let event = LogFilterNode::Event(*event_signature);
@@ -283,15 +328,19 @@ impl EthereumLogFilter {
                 let event_sig = event_handler.topic0();
                 match ds.address {
                     Some(contract) => {
-                        this.contracts_and_events_graph.add_edge(
-                            LogFilterNode::Contract(contract),
-                            LogFilterNode::Event(event_sig),
+                        this.add_contract_event(
+                            contract,
+                            event_sig,
                             event_handler.receipt,
+                            &event_handler.topics,
                         );
                     }
                     None => {
-                        this.wildcard_events
-                            .insert(event_sig, event_handler.receipt);
+                        this.add_wildcard_event(
+                            event_sig,
+                            event_handler.receipt,
+                            &event_handler.topics,
+                        );
                     }
                 }
             }
@@ -303,12 +352,77 @@ impl EthereumLogFilter {
         let mut this = EthereumLogFilter::default();
         for event_handler in &mapping.event_handlers {
             let signature = event_handler.topic0();
-            this.wildcard_events
-                .insert(signature, event_handler.receipt);
+            this.add_wildcard_event(signature, event_handler.receipt, &event_handler.topics);
         }
         this
     }
 
+    /// Match logs for `event_sig` from `contract`. When several handlers
+    /// match the same event, logs that any of them matches are included
+    fn add_contract_event(
+        &mut self,
+        contract: Address,
+        event_sig: EventSignature,
+        receipt: bool,
+        topics: &TopicFilter,
+    ) {
+        let key = (contract, event_sig);
+        let contract = LogFilterNode::Contract(contract);
+        let event = LogFilterNode::Event(event_sig);
+
+        let graph = &mut self.contracts_and_events_graph;
+        if let Some(requires_receipt) = graph.edge_weight_mut(contract, event) {
+            *requires_receipt |= receipt;
+            return;
+        }
+
+        match self.contract_topics.get_mut(&key) {
+            Some((requires_receipt, existing)) => {
+                *requires_receipt |= receipt;
+                existing.union(topics);
+                if !existing.is_set() {
+                    let (receipt, _) = self.contract_topics.remove(&key).unwrap();
+                    self.contracts_and_events_graph
+                        .add_edge(contract, event, receipt);
+                }
+            }
+            None if topics.is_set() => {
+                self.contract_topics.insert(key, (receipt, topics.clone()));
+            }
+            None => {
+                self.contracts_and_events_graph
+                    .add_edge(contract, event, receipt);
+            }
+        }
+    }
+
+    /// Match logs for `event_sig` from all contracts. When several handlers
+    /// match the same event, logs that any of them matches are included
+    fn add_wildcard_event(
+        &mut self,
+        event_sig: EventSignature,
+        receipt: bool,
+        topics: &TopicFilter,
+    ) {
+        match self.wildcard_events.get_mut(&event_sig) {
+            Some(requires_receipt) => {
+                *requires_receipt |= receipt;
+                if let Some(existing) = self.wildcard_topics.get_mut(&event_sig) {
+                    existing.union(topics);
+                    if !existing.is_set() {
+                        self.wildcard_topics.remove(&event_sig);
+                    }
+                }
+            }
+            None => {
+                self.wildcard_events.insert(event_sig, receipt);
+                if topics.is_set() {
+                    self.wildcard_topics.insert(event_sig, topics.clone());
+                }
+            }
+        }
+    }
+
     /// Extends this log filter with another one.
     pub fn extend(&mut self, other: EthereumLogFilter) {
         if other.is_empty() {
@@ -319,11 +433,25 @@ impl EthereumLogFilter {
         let EthereumLogFilter {
             contracts_and_events_graph,
             wildcard_events,
+            wildcard_topics,
+            contract_topics,
         } = other;
         for (s, t, e) in contracts_and_events_graph.all_edges() {
-            self.contracts_and_events_graph.add_edge(s, t, *e);
+            match (s, t) {
+                (LogFilterNode::Contract(contract), LogFilterNode::Event(event_sig))
+                | (LogFilterNode::Event(event_sig), LogFilterNode::Contract(contract)) => {
+                    self.add_contract_event(contract, event_sig, *e, &TopicFilter::default())
+                }
+                _ => unreachable!("the graph is bipartite"),
+            }
+        }
+        for ((contract, event_sig), (receipt, topics)) in contract_topics {
+            self.add_contract_event(contract, event_sig, receipt, &topics);
+        }
+        for (event_sig, receipt) in wildcard_events {
+            let topics = wildcard_topics.get(&event_sig).cloned().unwrap_or_default();
+            self.add_wildcard_event(event_sig, receipt, &topics);
         }
-        self.wildcard_events.extend(wildcard_events);
     }
 
     /// An empty filter is one that never matches.
@@ -332,8 +460,12 @@ impl EthereumLogFilter {
         let EthereumLogFilter {
             contracts_and_events_graph,
             wildcard_events,
+            wildcard_topics: _,
+            contract_topics,
         } = self;
-        contracts_and_events_graph.edge_count() == 0 && wildcard_events.is_empty()
+        contracts_and_events_graph.edge_count() == 0
+            && wildcard_events.is_empty()
+            && contract_topics.is_empty()
     }
 
     /// Filters for `eth_getLogs` calls. The filters will not return false positives. This attempts
@@ -341,12 +473,36 @@ impl EthereumLogFilter {
     /// broad filters causing the Ethereum endpoint to timeout.
     pub fn eth_get_logs_filters(self) -> impl Iterator<Item = EthGetLogsFilter> {
         // Start with the wildcard event filters.
+        let mut wildcard_topics = self.wildcard_topics;
         let mut filters = self
             .wildcard_events
             .into_keys()
-            .map(EthGetLogsFilter::from_event)
+            .map(|event_sig| {
+                let mut filter = EthGetLogsFilter::from_event(event_sig);
+                filter.topics = wildcard_topics.remove(&event_sig).unwrap_or_default();
+                filter
+            })
             .collect_vec();
 
+        // Events with restricted topics get their own filters, which are
+        // shared by all contracts that match the same topics for the event
+        let mut contracts_by_topics: HashMap<(EventSignature, TopicFilter), Vec<Address>> =
+            HashMap::new();
+        for ((contract, event_sig), (_, topics)) in self.contract_topics {
+            contracts_by_topics
+                .entry((event_sig, topics))
+                .or_default()
+                .push(contract);
+        }
+        for ((event_sig, topics), contracts) in contracts_by_topics {
+            for chunk in contracts.chunks(ENV_VARS.get_logs_max_contracts) {
+                let mut filter = EthGetLogsFilter::from_event(event_sig);
+                filter.contracts = chunk.to_vec();
+                filter.topics = topics.clone();
+                filters.push(filter);
+            }
+        }
+
         // The current algorithm is to repeatedly find the maximum cardinality vertex and turn all
         // of its edges into a filter. This is nice because it is neutral between filtering by
         // contract or by events, if there are many events that appear on only one data source
@@ -406,6 +562,7 @@ impl EthereumLogFilter {
                 LogFilterNode::Contract(address) => Some(address),
                 LogFilterNode::Event(_) => None,
             })
+            .chain(self.contract_topics.keys().map(|(address, _)| *address))
     }
 }
 
@@ -454,6 +611,11 @@ impl Into<Vec<CallToFilter>> for EthereumCallFilter {
 
 impl EthereumCallFilter {
     pub fn matches(&self, call: &EthereumCall) -> bool {
+        // Contract creations are matched by `EthereumContractCreationFilter`
+        if call.creation {
+            return false;
+        }
+
         // Calls returned by Firehose actually contains pure transfers and smart
         // contract calls. If the input is less than 4 bytes, we assume it's a pure transfer
         // and discards those.
@@ -607,6 +769,56 @@ impl From<&EthereumBlockFilter> for EthereumCallFilter {
     }
 }
 
+/// Filters contract creations for the contract creation handlers of data
+/// sources. A creation matches the filter if it matches any of the handlers.
+#[derive(Clone, Debug, Default)]
+pub struct EthereumContractCreationFilter {
+    handlers: HashSet<MappingContractCreationHandler>,
+}
+
+impl EthereumContractCreationFilter {
+    pub fn matches(&self, call: &EthereumCall) -> bool {
+        call.creation && self.handlers.iter().any(|handler| handler.matches(call))
+    }
+
+    pub fn from_mapping(mapping: &Mapping) -> Self {
+        Self {
+            handlers: mapping.contract_creation_handlers.iter().cloned().collect(),
+        }
+    }
+
+    pub fn from_data_sources<'a>(iter: impl IntoIterator<Item = &'a DataSource>) -> Self {
+        Self {
+            handlers: iter
+                .into_iter()
+                .flat_map(|data_source| data_source.mapping.contract_creation_handlers.iter())
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Extends this filter with another one.
+    pub fn extend(&mut self, other: EthereumContractCreationFilter) {
+        self.handlers.extend(other.handlers);
+    }
+
+    /// An empty filter is one that never matches.
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+
+    /// The deployers whose traces need to be requested, or `None` if a
+    /// handler matches contracts from any deployer so that all traces are
+    /// needed.
+    pub fn deployers(&self) -> Option<Vec<Address>> {
+        self.handlers
+            .iter()
+            .map(|handler| handler.deployer)
+            .collect::<Option<HashSet<_>>>()
+            .map(|deployers| deployers.into_iter().collect())
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct EthereumBlockFilter {
     /// Used for polling block handlers, a hashset of (start_block, polling_interval)
@@ -758,6 +970,8 @@ pub enum ProviderStatus {
     GenesisFail,
     VersionTimeout,
     GenesisTimeout,
+    Syncing,
+    BrokenVersion,
 }
 
 impl From<ProviderStatus> for f64 {
@@ -768,16 +982,21 @@ impl From<ProviderStatus> for f64 {
             ProviderStatus::GenesisFail => 2.0,
             ProviderStatus::VersionTimeout => 3.0,
             ProviderStatus::GenesisTimeout => 4.0,
+            ProviderStatus::Syncing => 5.0,
+            ProviderStatus::BrokenVersion => 6.0,
         }
     }
 }
 
-const STATUS_HELP: &str = "0 = ok, 1 = net_version failed, 2 = get genesis failed, 3 = net_version timeout, 4 = get genesis timeout";
+const STATUS_HELP: &str = "0 = ok, 1 = net_version failed, 2 = get genesis failed, 3 = net_version timeout, 4 = get genesis timeout, 5 = syncing, 6 = broken client version";
 #[derive(Debug, Clone)]
 pub struct ProviderEthRpcMetrics {
     request_duration: Box<HistogramVec>,
     errors: Box<CounterVec>,
     status: Box<GaugeVec>,
+    /// Used by the `RequestThrottle` of providers with a request rate limit
+    pub(crate) throttled: Box<CounterVec>,
+    pub(crate) throttled_secs: Box<CounterVec>,
 }
 
 impl ProviderEthRpcMetrics {
@@ -805,10 +1024,26 @@ impl ProviderEthRpcMetrics {
                 vec![String::from("provider")],
             )
             .unwrap();
+        let throttled = registry
+            .new_counter_vec(
+                "eth_rpc_throttled",
+                "Counts eth rpc requests that had to wait for the request rate limit of the provider",
+                vec![String::from("method"), String::from("provider")],
+            )
+            .unwrap();
+        let throttled_secs = registry
+            .new_counter_vec(
+                "eth_rpc_throttled_secs",
+                "Measures the time eth rpc requests waited for the request rate limit of the provider",
+                vec![String::from("method"), String::from("provider")],
+            )
+            .unwrap();
         Self {
             request_duration,
             errors,
             status,
+            throttled,
+            throttled_secs,
         }
     }
 
@@ -833,6 +1068,7 @@ impl ProviderEthRpcMetrics {
 pub struct SubgraphEthRpcMetrics {
     request_duration: GaugeVec,
     errors: CounterVec,
+    normalized_logs: CounterVec,
     deployment: String,
 }
 
@@ -852,9 +1088,17 @@ impl SubgraphEthRpcMetrics {
                 vec!["deployment", "method", "provider"].as_slice(),
             )
             .unwrap();
+        let normalized_logs = registry
+            .global_counter_vec(
+                "deployment_eth_rpc_normalized_logs",
+                "Counts logs from providers that were dropped as duplicates or were out of order",
+                vec!["deployment", "kind"].as_slice(),
+            )
+            .unwrap();
         Self {
             request_duration,
             errors,
+            normalized_logs,
             deployment: subgraph_hash.into(),
         }
     }
@@ -870,6 +1114,14 @@ impl SubgraphEthRpcMetrics {
             .with_label_values(&[&self.deployment, method, provider])
             .inc();
     }
+
+    /// Count logs that had to be normalized; `kind` is either `duplicate`
+    /// or `out_of_order`
+    pub fn add_normalized_logs(&self, kind: &str, count: usize) {
+        self.normalized_logs
+            .with_label_values(&[&self.deployment, kind])
+            .inc_by(count as f64);
+    }
 }
 
 /// Common trait for components that watch and manage access to Ethereum.
@@ -961,6 +1213,16 @@ pub trait EthereumAdapter: Send + Sync + 'static {
         call: EthereumContractCall,
         cache: Arc<dyn EthereumCallCache>,
     ) -> Box<dyn Future<Item = Vec<Token>, Error = EthereumContractCallError> + Send>;
+
+    /// Check whether there is contract code deployed at `address` as of
+    /// `block_ptr`. Results are only cached in memory, never in the call
+    /// cache, since they are not the results of an `eth_call`.
+    fn has_code(
+        &self,
+        logger: &Logger,
+        address: Address,
+        block_ptr: BlockPtr,
+    ) -> Box<dyn Future<Item = bool, Error = EthereumContractCallError> + Send>;
 }
 
 #[cfg(test)]
@@ -968,7 +1230,9 @@ mod tests {
     use crate::adapter::{FunctionSelector, COMBINED_FILTER_TYPE_URL};
 
     use super::{EthereumBlockFilter, LogFilterNode};
-    use super::{EthereumCallFilter, EthereumLogFilter, TriggerFilter};
+    use super::{
+        EthereumCallFilter, EthereumContractCreationFilter, EthereumLogFilter, TriggerFilter,
+    };
 
     use graph::blockchain::TriggerFilter as _;
     use graph::firehose::{CallToFilter, CombinedFilter, LogFilter, MultiLogFilter};
@@ -1072,6 +1336,8 @@ mod tests {
             log: EthereumLogFilter {
                 contracts_and_events_graph: GraphMap::new(),
                 wildcard_events: HashMap::new(),
+                wildcard_topics: HashMap::new(),
+                contract_topics: HashMap::new(),
             },
             call: EthereumCallFilter {
                 contract_addresses_function_signatures: HashMap::from_iter(vec![
@@ -1092,6 +1358,7 @@ mod tests {
                 ]),
                 trigger_every_block: false,
             },
+            contract_creation: EthereumContractCreationFilter::default(),
         };
 
         let expected_call_filters = vec![
@@ -1197,6 +1464,8 @@ mod tests {
             log: EthereumLogFilter {
                 contracts_and_events_graph: GraphMap::new(),
                 wildcard_events: HashMap::new(),
+                wildcard_topics: HashMap::new(),
+                contract_topics: HashMap::new(),
             },
             call: EthereumCallFilter {
                 contract_addresses_function_signatures: HashMap::new(),
@@ -1207,6 +1476,7 @@ mod tests {
                 contract_addresses: HashSet::new(),
                 trigger_every_block: true,
             },
+            contract_creation: EthereumContractCreationFilter::default(),
         };
 
         filter.log.contracts_and_events_graph.add_edge(
@@ -1354,6 +1624,48 @@ mod tests {
         );
     }
 
+    #[test]
+    fn matching_ethereum_contract_creation_filter() {
+        use crate::data_source::MappingContractCreationHandler;
+
+        let creation = |from: Address, input: Vec<u8>| EthereumCall {
+            from,
+            to: address(9),
+            input: bytes(input),
+            creation: true,
+            ..Default::default()
+        };
+
+        let mut filter = EthereumContractCreationFilter {
+            handlers: HashSet::from_iter(vec![MappingContractCreationHandler {
+                handler: "handleDeploy".to_owned(),
+                deployer: Some(address(0)),
+                init_code_prefix: Some(bytes(vec![0x60, 0x80])),
+            }]),
+        };
+
+        assert!(filter.matches(&creation(address(0), vec![0x60, 0x80, 0x60, 0x40])));
+        assert!(!filter.matches(&creation(address(1), vec![0x60, 0x80, 0x60, 0x40])));
+        assert!(!filter.matches(&creation(address(0), vec![0x60, 0x40])));
+        assert_eq!(Some(vec![address(0)]), filter.deployers());
+
+        // Plain calls are never creations, even from the deployer
+        let mut call = creation(address(0), vec![0x60, 0x80, 0x60, 0x40]);
+        call.creation = false;
+        assert!(!filter.matches(&call));
+
+        // Handlers without a deployer require all traces
+        filter.extend(EthereumContractCreationFilter {
+            handlers: HashSet::from_iter(vec![MappingContractCreationHandler {
+                handler: "handleAnyDeploy".to_owned(),
+                deployer: None,
+                init_code_prefix: Some(bytes(vec![0x60, 0x40])),
+            }]),
+        });
+        assert!(filter.matches(&creation(address(1), vec![0x60, 0x40])));
+        assert_eq!(None, filter.deployers());
+    }
+
     #[test]
     fn extending_ethereum_block_filter_no_found() {
         let mut base = EthereumBlockFilter {
@@ -1588,6 +1900,8 @@ fn complete_log_filter() {
             let logs_filters: Vec<_> = EthereumLogFilter {
                 contracts_and_events_graph,
                 wildcard_events: HashMap::new(),
+                wildcard_topics: HashMap::new(),
+                contract_topics: HashMap::new(),
             }
             .eth_get_logs_filters()
             .collect();
@@ -1680,6 +1994,8 @@ fn log_filter_require_transacion_receipt_method() {
     let filter = EthereumLogFilter {
         contracts_and_events_graph,
         wildcard_events,
+        wildcard_topics: HashMap::new(),
+        contract_topics: HashMap::new(),
     };
 
     // connected contracts and events graph
@@ -1711,3 +2027,111 @@ fn log_filter_require_transacion_receipt_method() {
     assert!(!filter.requires_transaction_receipt(&event_signature_b, Some(&alien_contract_address)));
     assert!(!filter.requires_transaction_receipt(&event_signature_c, Some(&alien_contract_address)));
 }
+
+#[test]
+fn log_filter_restricts_topics_of_wildcard_events() {
+    let event = H256::from_low_u64_be(1);
+    let topic = H256::from_low_u64_be;
+    let log = |topics: Vec<H256>| Log {
+        address: Address::from_low_u64_be(2),
+        topics,
+        ..Default::default()
+    };
+
+    let mut filter = EthereumLogFilter::default();
+    filter.add_wildcard_event(
+        event,
+        false,
+        &TopicFilter {
+            topic1: Some(vec![topic(10)]),
+            topic2: Some(vec![topic(20)]),
+            topic3: None,
+        },
+    );
+    assert!(filter.matches(&log(vec![event, topic(10), topic(20)])));
+    assert!(!filter.matches(&log(vec![event, topic(11), topic(20)])));
+    assert!(!filter.matches(&log(vec![event, topic(10)])));
+
+    // A second handler for the same event widens the filter
+    filter.add_wildcard_event(
+        event,
+        true,
+        &TopicFilter {
+            topic1: Some(vec![topic(11)]),
+            topic2: None,
+            topic3: None,
+        },
+    );
+    assert!(filter.matches(&log(vec![event, topic(11), topic(21)])));
+    assert!(!filter.matches(&log(vec![event, topic(12), topic(20)])));
+    assert!(filter.requires_transaction_receipt(&event, None));
+
+    let logs_filters: Vec<_> = filter.clone().eth_get_logs_filters().collect();
+    assert_eq!(1, logs_filters.len());
+    assert!(logs_filters[0].contracts.is_empty());
+    assert_eq!(vec![event], logs_filters[0].event_signatures);
+    assert_eq!(
+        TopicFilter {
+            topic1: Some(vec![topic(10), topic(11)]),
+            topic2: None,
+            topic3: None,
+        },
+        logs_filters[0].topics
+    );
+
+    // A handler without restrictions matches logs with any topics
+    filter.add_wildcard_event(event, false, &TopicFilter::default());
+    assert!(filter.matches(&log(vec![event, topic(12)])));
+    assert!(filter.eth_get_logs_filters().all(|f| !f.topics.is_set()));
+}
+
+#[test]
+fn log_filter_restricts_topics_of_contract_events() {
+    use std::collections::BTreeSet;
+
+    let event = H256::from_low_u64_be(1);
+    let other_event = H256::from_low_u64_be(2);
+    let contract_a = Address::from_low_u64_be(3);
+    let contract_b = Address::from_low_u64_be(4);
+    let topic = H256::from_low_u64_be;
+    let topics = TopicFilter {
+        topic1: None,
+        topic2: Some(vec![topic(10)]),
+        topic3: None,
+    };
+    let log = |address, topics: Vec<H256>| Log {
+        address,
+        topics,
+        ..Default::default()
+    };
+
+    let mut filter = EthereumLogFilter::default();
+    filter.add_contract_event(contract_a, event, false, &topics);
+    filter.add_contract_event(contract_b, event, false, &topics);
+    filter.add_contract_event(contract_a, other_event, false, &TopicFilter::default());
+    assert!(filter.matches(&log(contract_a, vec![event, topic(0), topic(10)])));
+    assert!(!filter.matches(&log(contract_a, vec![event, topic(10), topic(0)])));
+    assert!(filter.matches(&log(contract_a, vec![other_event, topic(0), topic(0)])));
+
+    // Both contracts share one filter that includes the topics
+    let logs_filters: Vec<_> = filter.clone().eth_get_logs_filters().collect();
+    assert_eq!(2, logs_filters.len());
+    let restricted = logs_filters.iter().find(|f| f.topics.is_set()).unwrap();
+    assert_eq!(vec![event], restricted.event_signatures);
+    assert_eq!(
+        BTreeSet::from([contract_a, contract_b]),
+        restricted
+            .contracts
+            .iter()
+            .copied()
+            .collect::<BTreeSet<_>>()
+    );
+    assert_eq!(topics, restricted.topics);
+
+    // A handler without restrictions moves the pair into the graph
+    filter.add_contract_event(contract_a, event, true, &TopicFilter::default());
+    assert!(filter.matches(&log(contract_a, vec![event, topic(10), topic(0)])));
+    assert!(!filter.matches(&log(contract_b, vec![event, topic(10), topic(0)])));
+    assert!(filter.requires_transaction_receipt(&event, Some(&contract_a)));
+    assert!(!filter.requires_transaction_receipt(&event, Some(&contract_b)));
+}
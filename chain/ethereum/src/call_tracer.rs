@@ -0,0 +1,249 @@
+//! Traces for providers that do not support `trace_filter` but can trace a
+//! block with geth's `callTracer` through `debug_traceBlockByHash`.
+//!
+//! The call frames that the tracer returns are turned into traces in the
+//! format of `trace_filter`, so that calls and contract creations are
+//! extracted from them in exactly the same way, down to their
+//! `trace_address`. Frames of calls that failed keep their error and have
+//! no result, just like in `trace_filter`. Block rewards, which
+//! `trace_filter` reports as traces without a transaction, are not part of
+//! call traces; they never lead to calls anyway.
+
+use graph::prelude::{
+    anyhow::{anyhow, Error},
+    serde_json::{self as json, json},
+    web3::types::{Bytes, Trace, H160, H256, U256},
+    BlockNumber, Deserialize,
+};
+
+/// A call frame as returned by the `callTracer`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CallFrame {
+    #[serde(rename = "type")]
+    typ: String,
+    from: H160,
+    #[serde(default)]
+    to: Option<H160>,
+    #[serde(default)]
+    value: Option<U256>,
+    #[serde(default)]
+    gas: U256,
+    #[serde(default)]
+    gas_used: U256,
+    #[serde(default)]
+    input: Bytes,
+    #[serde(default)]
+    output: Option<Bytes>,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    calls: Vec<CallFrame>,
+}
+
+/// The trace of one transaction in the response to
+/// `debug_traceBlockByHash`
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct TransactionTrace {
+    result: CallFrame,
+}
+
+struct Position<'a> {
+    block_number: BlockNumber,
+    block_hash: H256,
+    transaction_position: usize,
+    transaction_hash: &'a H256,
+}
+
+fn push_traces(
+    traces: &mut Vec<Trace>,
+    pos: &Position,
+    trace_address: Vec<usize>,
+    frame: &CallFrame,
+) -> Result<(), Error> {
+    let value = frame.value.unwrap_or_default();
+    let to = frame.to.unwrap_or_default();
+    let (typ, action, result) = match frame.typ.to_ascii_lowercase().as_str() {
+        "create" | "create2" => (
+            "create",
+            json!({
+                "from": frame.from,
+                "value": value,
+                "gas": frame.gas,
+                "init": frame.input,
+            }),
+            json!({
+                "address": to,
+                "code": frame.output.clone().unwrap_or_default(),
+                "gasUsed": frame.gas_used,
+            }),
+        ),
+        "selfdestruct" => (
+            "suicide",
+            json!({
+                "address": frame.from,
+                "refundAddress": to,
+                "balance": value,
+            }),
+            json::Value::Null,
+        ),
+        call_type @ ("call" | "callcode" | "delegatecall" | "staticcall") => (
+            "call",
+            json!({
+                "callType": call_type,
+                "from": frame.from,
+                "to": to,
+                "value": value,
+                "gas": frame.gas,
+                "input": frame.input,
+            }),
+            json!({
+                "gasUsed": frame.gas_used,
+                "output": frame.output.clone().unwrap_or_default(),
+            }),
+        ),
+        typ => return Err(anyhow!("unknown call frame type `{}`", typ)),
+    };
+    let result = match frame.error {
+        Some(_) => json::Value::Null,
+        None => result,
+    };
+
+    let trace = json!({
+        "action": action,
+        "result": result,
+        "error": frame.error,
+        "traceAddress": trace_address,
+        "subtraces": frame.calls.len(),
+        "transactionPosition": pos.transaction_position,
+        "transactionHash": pos.transaction_hash,
+        "blockNumber": pos.block_number,
+        "blockHash": pos.block_hash,
+        "type": typ,
+    });
+    traces.push(json::from_value(trace)?);
+
+    for (i, call) in frame.calls.iter().enumerate() {
+        let mut trace_address = trace_address.clone();
+        trace_address.push(i);
+        push_traces(traces, pos, trace_address, call)?;
+    }
+    Ok(())
+}
+
+/// Turn the call traces of the transactions of a block into traces like
+/// the ones `trace_filter` returns. The traces in `transactions` must be
+/// in the order of the `transaction_hashes` of the block
+pub(crate) fn traces(
+    block_number: BlockNumber,
+    block_hash: H256,
+    transaction_hashes: &[H256],
+    transactions: Vec<TransactionTrace>,
+) -> Result<Vec<Trace>, Error> {
+    if transactions.len() != transaction_hashes.len() {
+        return Err(anyhow!(
+            "debug_traceBlockByHash returned {} traces for block {} with {} transactions",
+            transactions.len(),
+            block_hash,
+            transaction_hashes.len()
+        ));
+    }
+
+    let mut traces = Vec::new();
+    for (transaction_position, (transaction_hash, transaction)) in transaction_hashes
+        .iter()
+        .zip(transactions.iter())
+        .enumerate()
+    {
+        let pos = Position {
+            block_number,
+            block_hash,
+            transaction_position,
+            transaction_hash,
+        };
+        push_traces(&mut traces, &pos, vec![], &transaction.result)?;
+    }
+    Ok(traces)
+}
+
+#[cfg(test)]
+mod tests {
+    use graph::prelude::web3::types::{Action, Res};
+
+    use super::*;
+
+    #[test]
+    fn converts_call_frames() {
+        let frames: Vec<TransactionTrace> = json::from_value(json!([{
+            "txHash": "0x0000000000000000000000000000000000000000000000000000000000000011",
+            "result": {
+                "type": "CALL",
+                "from": "0x0000000000000000000000000000000000000001",
+                "to": "0x0000000000000000000000000000000000000002",
+                "value": "0x0",
+                "gas": "0x100",
+                "gasUsed": "0x80",
+                "input": "0xa9059cbb",
+                "output": "0x01",
+                "calls": [{
+                    "type": "CREATE2",
+                    "from": "0x0000000000000000000000000000000000000002",
+                    "to": "0x0000000000000000000000000000000000000003",
+                    "value": "0x0",
+                    "gas": "0x40",
+                    "gasUsed": "0x20",
+                    "input": "0x6080",
+                    "output": "0x6000"
+                }, {
+                    "type": "STATICCALL",
+                    "from": "0x0000000000000000000000000000000000000002",
+                    "to": "0x0000000000000000000000000000000000000004",
+                    "gas": "0x10",
+                    "gasUsed": "0x10",
+                    "input": "0x70a08231",
+                    "error": "execution reverted"
+                }]
+            }
+        }]))
+        .unwrap();
+        let block_hash = H256::from_low_u64_be(0x22);
+        let tx_hash = H256::from_low_u64_be(0x11);
+
+        let traces = traces(7, block_hash, &[tx_hash], frames).unwrap();
+
+        assert_eq!(3, traces.len());
+        assert!(traces.iter().all(|trace| trace.block_hash == block_hash
+            && trace.block_number == 7
+            && trace.transaction_hash == Some(tx_hash)
+            && trace.transaction_position == Some(0)));
+
+        assert_eq!(Vec::<usize>::new(), traces[0].trace_address);
+        assert_eq!(2, traces[0].subtraces);
+        match (&traces[0].action, &traces[0].result) {
+            (Action::Call(call), Some(Res::Call(res))) => {
+                assert_eq!(H160::from_low_u64_be(2), call.to);
+                assert_eq!(U256::from(0x80), res.gas_used);
+            }
+            _ => panic!("expected a call: {:?}", traces[0]),
+        }
+
+        assert_eq!(vec![0], traces[1].trace_address);
+        match (&traces[1].action, &traces[1].result) {
+            (Action::Create(create), Some(Res::Create(res))) => {
+                assert_eq!(H160::from_low_u64_be(2), create.from);
+                assert_eq!(H160::from_low_u64_be(3), res.address);
+            }
+            _ => panic!("expected a contract creation: {:?}", traces[1]),
+        }
+
+        assert_eq!(vec![1], traces[2].trace_address);
+        assert_eq!(Some("execution reverted".to_string()), traces[2].error);
+        assert!(traces[2].result.is_none());
+    }
+
+    #[test]
+    fn rejects_missing_transactions() {
+        let hashes = [H256::from_low_u64_be(1)];
+        assert!(traces(1, H256::zero(), &hashes, vec![]).is_err());
+    }
+}
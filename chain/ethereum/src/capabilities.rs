@@ -62,7 +62,9 @@ impl graph::blockchain::NodeCapabilities<crate::Chain> for NodeCapabilities {
                     .expect("failed to parse mappings")
             }),
             traces: data_sources.iter().any(|ds| {
-                ds.mapping.has_call_handler() || ds.mapping.has_block_handler_with_call_filter()
+                ds.mapping.has_call_handler()
+                    || ds.mapping.has_block_handler_with_call_filter()
+                    || ds.mapping.has_contract_creation_handler()
             }),
         }
     }
@@ -27,7 +27,7 @@ use graph::{
     components::store::DeploymentLocator,
     firehose,
     prelude::{
-        async_trait, o, serde_json as json, BlockNumber, ChainStore, EthereumBlockWithCalls,
+        async_trait, debug, o, serde_json as json, BlockNumber, ChainStore, EthereumBlockWithCalls,
         Future01CompatExt, Logger, LoggerFactory, NodeId,
     },
 };
@@ -40,8 +40,10 @@ use std::time::Duration;
 use crate::codec::HeaderOnlyBlock;
 use crate::data_source::DataSourceTemplate;
 use crate::data_source::UnresolvedDataSourceTemplate;
+use crate::extraction_pool::ExtractionPool;
 use crate::ingestor::PollingBlockIngestor;
 use crate::network::EthereumNetworkAdapters;
+use crate::throttle::{DeploymentThrottles, RequestThrottle};
 use crate::EthereumAdapter;
 use crate::NodeCapabilities;
 use crate::{
@@ -49,10 +51,10 @@ use crate::{
     codec,
     data_source::{DataSource, UnresolvedDataSource},
     ethereum_adapter::{
-        blocks_with_triggers, get_calls, parse_block_triggers, parse_call_triggers,
-        parse_log_triggers,
+        blocks_with_triggers, blocks_with_triggers_from_cache, get_calls, parse_block_triggers,
+        parse_call_triggers, parse_contract_creation_triggers, parse_log_triggers,
     },
-    SubgraphEthRpcMetrics, TriggerFilter, ENV_VARS,
+    ProviderHealth, SubgraphEthRpcMetrics, TriggerFilter, ENV_VARS,
 };
 use graph::blockchain::block_stream::{
     BlockStream, BlockStreamBuilder, BlockStreamMapper, FirehoseCursor,
@@ -207,6 +209,9 @@ pub struct EthereumAdapterSelector {
     client: Arc<ChainClient<Chain>>,
     registry: Arc<MetricsRegistry>,
     chain_store: Arc<dyn ChainStore>,
+    /// Shared by the triggers adapters of all deployments on this network
+    extraction_pool: Arc<ExtractionPool>,
+    throttles: Arc<DeploymentThrottles>,
 }
 
 impl EthereumAdapterSelector {
@@ -215,12 +220,15 @@ impl EthereumAdapterSelector {
         client: Arc<ChainClient<Chain>>,
         registry: Arc<MetricsRegistry>,
         chain_store: Arc<dyn ChainStore>,
+        throttles: Arc<DeploymentThrottles>,
     ) -> Self {
         Self {
             logger_factory,
             client,
             registry,
             chain_store,
+            extraction_pool: ExtractionPool::from_env(),
+            throttles,
         }
     }
 }
@@ -244,8 +252,10 @@ impl TriggersAdapterSelector<Chain> for EthereumAdapterSelector {
             ethrpc_metrics,
             chain_client: self.client.cheap_clone(),
             chain_store: self.chain_store.cheap_clone(),
+            extraction_pool: self.extraction_pool.cheap_clone(),
             unified_api_version,
             capabilities: *capabilities,
+            throttle: self.throttles.get(&loc.hash),
         };
         Ok(Arc::new(adapter))
     }
@@ -328,6 +338,18 @@ impl Chain {
         };
         adapters.cheapest().unwrap()
     }
+
+    /// The result of the latest health check of each provider of this
+    /// chain, by provider name. Chains that use Firehose have no providers
+    pub fn provider_health(&self) -> Vec<(String, Option<ProviderHealth>)> {
+        match self.client.as_ref() {
+            ChainClient::Firehose(_) => vec![],
+            ChainClient::Rpc(adapters) => adapters
+                .all()
+                .map(|adapter| (adapter.provider().to_string(), adapter.health()))
+                .collect(),
+        }
+    }
 }
 
 #[async_trait]
@@ -504,6 +526,17 @@ impl Blockchain for Chain {
 
         Ok(ingestor)
     }
+
+    fn has_provider_with(&self, capabilities: &NodeCapabilities) -> bool {
+        match self.client.as_ref() {
+            // Firehose provides the same data as an archive node with traces
+            ChainClient::Firehose(_) => true,
+            ChainClient::Rpc(adapters) => adapters
+                .adapters
+                .iter()
+                .any(|adapter| &adapter.capabilities >= capabilities),
+        }
+    }
 }
 
 /// This is used in `EthereumAdapter::triggers_in_block`, called when re-processing a block for
@@ -597,8 +630,11 @@ pub struct TriggersAdapter {
     ethrpc_metrics: Arc<SubgraphEthRpcMetrics>,
     chain_store: Arc<dyn ChainStore>,
     chain_client: Arc<ChainClient<Chain>>,
+    extraction_pool: Arc<ExtractionPool>,
     capabilities: NodeCapabilities,
     unified_api_version: UnifiedMappingApiVersion,
+    /// Limits the requests of the deployment the adapter is for
+    throttle: Option<RequestThrottle>,
 }
 
 #[async_trait]
@@ -609,16 +645,45 @@ impl TriggersAdapterTrait<Chain> for TriggersAdapter {
         to: BlockNumber,
         filter: &TriggerFilter,
     ) -> Result<Vec<BlockWithTriggers<Chain>>, Error> {
-        blocks_with_triggers(
-            self.chain_client.rpc()?.cheapest_with(&self.capabilities)?,
-            self.logger.clone(),
-            self.chain_store.clone(),
-            self.ethrpc_metrics.clone(),
-            from,
-            to,
-            filter,
-            self.unified_api_version.clone(),
-        )
+        RequestThrottle::run(self.throttle.as_ref(), async {
+            if ENV_VARS.scan_cached_blocks && !filter.requires_traces() {
+                if let Some(blocks) = self
+                    .chain_store
+                    .cheap_clone()
+                    .cached_blocks_in_range(from, to)
+                    .await?
+                {
+                    if let Some(blocks) =
+                        blocks_with_triggers_from_cache(&self.logger, blocks, to, filter)?
+                    {
+                        debug!(self.logger, "Found triggers in cached blocks";
+                               "from" => from, "to" => to, "blocks" => blocks.len());
+                        return Ok(blocks);
+                    }
+                }
+            }
+
+            let adapters = self.chain_client.rpc()?;
+            let adapter = adapters.cheapest_with(&self.capabilities)?;
+            let log_adapters = adapters.log_scan_adapters(
+                &adapter,
+                &self.capabilities,
+                ENV_VARS.parallel_log_providers,
+            );
+            blocks_with_triggers(
+                adapter,
+                log_adapters,
+                self.logger.clone(),
+                self.chain_store.clone(),
+                &self.extraction_pool,
+                self.ethrpc_metrics.clone(),
+                from,
+                to,
+                filter,
+                self.unified_api_version.clone(),
+            )
+            .await
+        })
         .await
     }
 
@@ -628,54 +693,66 @@ impl TriggersAdapterTrait<Chain> for TriggersAdapter {
         block: BlockFinality,
         filter: &TriggerFilter,
     ) -> Result<BlockWithTriggers<Chain>, Error> {
-        let block = get_calls(
-            &self.chain_client,
-            logger.clone(),
-            self.ethrpc_metrics.clone(),
-            &self.capabilities,
-            filter.requires_traces(),
-            block,
-        )
-        .await?;
-
-        match &block {
-            BlockFinality::Final(_) => {
-                let adapter = self.chain_client.rpc()?.cheapest_with(&self.capabilities)?;
-                let block_number = block.number() as BlockNumber;
-                let blocks = blocks_with_triggers(
-                    adapter,
-                    logger.clone(),
-                    self.chain_store.clone(),
-                    self.ethrpc_metrics.clone(),
-                    block_number,
-                    block_number,
-                    filter,
-                    self.unified_api_version.clone(),
-                )
-                .await?;
-                assert!(blocks.len() == 1);
-                Ok(blocks.into_iter().next().unwrap())
-            }
-            BlockFinality::NonFinal(full_block) => {
-                let mut triggers = Vec::new();
-                triggers.append(&mut parse_log_triggers(
-                    &filter.log,
-                    &full_block.ethereum_block,
-                ));
-                triggers.append(&mut parse_call_triggers(&filter.call, full_block)?);
-                triggers.append(&mut parse_block_triggers(&filter.block, full_block));
-                Ok(BlockWithTriggers::new(block, triggers, logger))
+        RequestThrottle::run(self.throttle.as_ref(), async {
+            let block = get_calls(
+                &self.chain_client,
+                logger.clone(),
+                self.ethrpc_metrics.clone(),
+                &self.capabilities,
+                filter.requires_traces(),
+                block,
+            )
+            .await?;
+
+            match &block {
+                BlockFinality::Final(_) => {
+                    let adapter = self.chain_client.rpc()?.cheapest_with(&self.capabilities)?;
+                    let block_number = block.number() as BlockNumber;
+                    let blocks = blocks_with_triggers(
+                        adapter,
+                        vec![],
+                        logger.clone(),
+                        self.chain_store.clone(),
+                        &self.extraction_pool,
+                        self.ethrpc_metrics.clone(),
+                        block_number,
+                        block_number,
+                        filter,
+                        self.unified_api_version.clone(),
+                    )
+                    .await?;
+                    assert!(blocks.len() == 1);
+                    Ok(blocks.into_iter().next().unwrap())
+                }
+                BlockFinality::NonFinal(full_block) => {
+                    let mut triggers = Vec::new();
+                    triggers.append(&mut parse_log_triggers(
+                        &filter.log,
+                        &full_block.ethereum_block,
+                    ));
+                    triggers.append(&mut parse_call_triggers(&filter.call, full_block)?);
+                    triggers.append(&mut parse_contract_creation_triggers(
+                        &filter.contract_creation,
+                        full_block,
+                    )?);
+                    triggers.append(&mut parse_block_triggers(&filter.block, full_block));
+                    Ok(BlockWithTriggers::new(block, triggers, logger))
+                }
             }
-        }
+        })
+        .await
     }
 
     async fn is_on_main_chain(&self, ptr: BlockPtr) -> Result<bool, Error> {
-        self.chain_client
-            .rpc()?
-            .cheapest()
-            .ok_or(anyhow!("unable to get adapter for is_on_main_chain"))?
-            .is_on_main_chain(&self.logger, ptr.clone())
-            .await
+        RequestThrottle::run(self.throttle.as_ref(), async {
+            self.chain_client
+                .rpc()?
+                .cheapest()
+                .ok_or(anyhow!("unable to get adapter for is_on_main_chain"))?
+                .is_on_main_chain(&self.logger, ptr.clone())
+                .await
+        })
+        .await
     }
 
     async fn ancestor_block(
@@ -699,33 +776,36 @@ impl TriggersAdapterTrait<Chain> for TriggersAdapter {
     }
 
     async fn parent_ptr(&self, block: &BlockPtr) -> Result<Option<BlockPtr>, Error> {
-        use futures::stream::Stream;
-        use graph::prelude::LightEthereumBlockExt;
-
-        let block = match self.chain_client.as_ref() {
-            ChainClient::Firehose(_) => Some(BlockPtr {
-                hash: BlockHash::from(vec![0xff; 32]),
-                number: block.number.saturating_sub(1),
-            }),
-            ChainClient::Rpc(adapters) => {
-                let blocks = adapters
-                    .cheapest_with(&self.capabilities)?
-                    .load_blocks(
-                        self.logger.cheap_clone(),
-                        self.chain_store.cheap_clone(),
-                        HashSet::from_iter(Some(block.hash_as_h256())),
-                    )
-                    .await
-                    .collect()
-                    .compat()
-                    .await?;
-                assert_eq!(blocks.len(), 1);
-
-                blocks[0].parent_ptr()
-            }
-        };
+        RequestThrottle::run(self.throttle.as_ref(), async {
+            use futures::stream::Stream;
+            use graph::prelude::LightEthereumBlockExt;
+
+            let block = match self.chain_client.as_ref() {
+                ChainClient::Firehose(_) => Some(BlockPtr {
+                    hash: BlockHash::from(vec![0xff; 32]),
+                    number: block.number.saturating_sub(1),
+                }),
+                ChainClient::Rpc(adapters) => {
+                    let blocks = adapters
+                        .cheapest_with(&self.capabilities)?
+                        .load_blocks(
+                            self.logger.cheap_clone(),
+                            self.chain_store.cheap_clone(),
+                            HashSet::from_iter(Some(block.hash_as_h256())),
+                        )
+                        .await
+                        .collect()
+                        .compat()
+                        .await?;
+                    assert_eq!(blocks.len(), 1);
+
+                    blocks[0].parent_ptr()
+                }
+            };
 
-        Ok(block)
+            Ok(block)
+        })
+        .await
     }
 }
 
@@ -73,6 +73,8 @@ impl<'a> TryInto<EthereumCall> for CallAt<'a> {
             block_number: self.block.number as i32,
             transaction_hash: Some(self.trace.hash.try_decode_proto("call transaction hash")?),
             transaction_index: self.trace.index as u64,
+            trace_address: vec![self.call.index as usize],
+            creation: self.call.call_type == CallType::Create as i32,
         })
     }
 }
@@ -21,23 +21,26 @@ use graph::{
         async_trait,
         ethabi::{Address, Contract, Event, Function, LogParam, ParamType, RawLog},
         serde_json, warn,
-        web3::types::{Log, Transaction, H256},
+        web3::types::{Bytes, Log, Transaction, H256, U256},
         BlockNumber, CheapClone, DataSourceTemplateInfo, Deserialize, EthereumCall,
-        LightEthereumBlock, LightEthereumBlockExt, LinkResolver, Logger, TryStreamExt,
+        LightEthereumBlock, LightEthereumBlockExt, LinkResolver, Logger, TryStreamExt, Value,
     },
 };
 
 use graph::data::subgraph::{
     calls_host_fn, DataSourceContext, Source, MIN_SPEC_VERSION, SPEC_VERSION_0_0_8,
+    SPEC_VERSION_1_1_0,
 };
 
 use crate::chain::Chain;
+use crate::event_filter::EventFilter;
 use crate::trigger::{EthereumBlockTriggerType, EthereumTrigger, MappingTrigger};
 
 // The recommended kind is `ethereum`, `ethereum/contract` is accepted for backwards compatibility.
 const ETHEREUM_KINDS: &[&str] = &["ethereum/contract", "ethereum"];
 const EVENT_HANDLER_KIND: &str = "event";
 const CALL_HANDLER_KIND: &str = "call";
+const CONTRACT_CREATION_HANDLER_KIND: &str = "contractCreation";
 const BLOCK_HANDLER_KIND: &str = "block";
 
 /// Runtime representation of a data source.
@@ -89,8 +92,7 @@ impl blockchain::DataSource<Chain> for DataSource {
         })?;
 
         let contract_abi = template
-            .mapping
-            .find_abi(&template.source.abi)
+            .contract_abi(context.as_ref())
             .with_context(|| format!("template `{}`", template.name))?;
 
         Ok(DataSource {
@@ -119,6 +121,7 @@ impl blockchain::DataSource<Chain> for DataSource {
             event_handlers,
             call_handlers,
             block_handlers,
+            contract_creation_handlers,
             ..
         } = &self.mapping;
 
@@ -128,6 +131,9 @@ impl blockchain::DataSource<Chain> for DataSource {
         if !call_handlers.is_empty() {
             kinds.insert(CALL_HANDLER_KIND);
         }
+        if !contract_creation_handlers.is_empty() {
+            kinds.insert(CONTRACT_CREATION_HANDLER_KIND);
+        }
         for handler in block_handlers.iter() {
             kinds.insert(handler.kind());
         }
@@ -135,6 +141,20 @@ impl blockchain::DataSource<Chain> for DataSource {
         kinds
     }
 
+    fn is_batch_handler(&self, handler: &str) -> bool {
+        self.mapping
+            .event_handlers
+            .iter()
+            .any(|event_handler| event_handler.batch && event_handler.handler == handler)
+    }
+
+    fn has_batch_handlers(&self) -> bool {
+        self.mapping
+            .event_handlers
+            .iter()
+            .any(|event_handler| event_handler.batch)
+    }
+
     fn start_block(&self) -> BlockNumber {
         self.start_block
     }
@@ -202,6 +222,7 @@ impl blockchain::DataSource<Chain> for DataSource {
             && mapping.event_handlers == other.mapping.event_handlers
             && mapping.call_handlers == other.mapping.call_handlers
             && mapping.block_handlers == other.mapping.block_handlers
+            && mapping.contract_creation_handlers == other.mapping.contract_creation_handlers
             && context == &other.context
     }
 
@@ -243,7 +264,7 @@ impl blockchain::DataSource<Chain> for DataSource {
 
         let context = context.map(serde_json::from_value).transpose()?;
 
-        let contract_abi = template.mapping.find_abi(&template.source.abi)?;
+        let contract_abi = template.contract_abi(context.as_ref())?;
 
         let address = param.map(|x| H160::from_slice(&x));
         Ok(DataSource {
@@ -326,6 +347,20 @@ impl blockchain::DataSource<Chain> for DataSource {
             errors.push(anyhow!("data source has duplicated block handlers"));
         }
 
+        // An initialization handler is only called at the start block of
+        // the data source, and is therefore never called if it starts later
+        for block_handler in &self.mapping.block_handlers {
+            if block_handler.filter == Some(BlockHandlerFilter::Once)
+                && block_handler.start.is_set()
+            {
+                errors.push(anyhow!(
+                    "block handler `{}` has a `once` filter and can not have a \
+                     `startBlock` or `startTime`",
+                    block_handler.handler
+                ));
+            }
+        }
+
         // Validate that event handlers don't require receipts for API versions lower than 0.0.7
         let api_version = self.api_version();
         if api_version < semver::Version::new(0, 0, 7) {
@@ -340,25 +375,139 @@ impl blockchain::DataSource<Chain> for DataSource {
             }
         }
 
+        // Validate that batch event handlers are only used with API versions
+        // that support them, and that a batch handler is not also used as an
+        // ordinary handler, since the two receive different arguments
+        for event_handler in self.mapping.event_handlers.iter().filter(|h| h.batch) {
+            if api_version < semver::Version::new(0, 0, 7) {
+                errors.push(anyhow!(
+                    "event handler `{}` is a batch handler, but batch handlers are \
+                     only supported for apiVersion >= 0.0.7",
+                    event_handler.handler
+                ));
+            }
+            let handler = event_handler.handler.as_str();
+            let used_unbatched = self
+                .mapping
+                .event_handlers
+                .iter()
+                .any(|h| !h.batch && h.handler == handler)
+                || self
+                    .mapping
+                    .call_handlers
+                    .iter()
+                    .any(|h| h.handler == handler)
+                || self
+                    .mapping
+                    .block_handlers
+                    .iter()
+                    .any(|h| h.handler == handler);
+            if used_unbatched {
+                errors.push(anyhow!(
+                    "handler `{}` is used both as a batch event handler and as \
+                     an ordinary handler",
+                    handler
+                ));
+            }
+        }
+
+        // Contract creation handlers that match every creation on the chain
+        // are almost certainly a mistake and would require all traces
+        for handler in &self.mapping.contract_creation_handlers {
+            if handler.deployer.is_none() && handler.init_code_prefix.is_none() {
+                errors.push(anyhow!(
+                    "contract creation handler `{}` needs a `deployer` or an `initCodePrefix`",
+                    handler.handler
+                ));
+            }
+        }
+
+        // Validate that the events and functions of all handlers exist in
+        // the ABI of the source contract
+        for handler in &self.mapping.event_handlers {
+            if handler
+                .topics
+                .positions()
+                .iter()
+                .any(|values| values.as_ref().map_or(false, |values| values.is_empty()))
+            {
+                errors.push(anyhow!(
+                    "topic filters of handler `{}` must list at least one topic",
+                    handler.handler
+                ));
+            }
+            match self.contract_event_with_signature(&handler.event) {
+                Some(event) => {
+                    if let Some(Err(e)) = handler.filter.as_ref().map(|f| f.validate(event)) {
+                        errors.push(anyhow!(
+                            "invalid filter for handler `{}`: {}",
+                            handler.handler,
+                            e
+                        ));
+                    }
+                }
+                None => {
+                    let signatures = self.contract_abi.contract.events().map(event_signature);
+                    errors.push(anyhow!(
+                        "event `{}` of handler `{}` not found in ABI `{}`{}",
+                        handler.event,
+                        handler.handler,
+                        self.contract_abi.name,
+                        did_you_mean(&handler.event, signatures)
+                    ));
+                }
+            }
+        }
+        for handler in &self.mapping.call_handlers {
+            if self
+                .contract_function_with_signature(&handler.function)
+                .is_none()
+            {
+                let signatures = self
+                    .contract_abi
+                    .contract
+                    .functions()
+                    .filter(|function| is_mutating(function))
+                    .map(function_signature);
+                errors.push(anyhow!(
+                    "function `{}` of handler `{}` not found in ABI `{}`{}",
+                    handler.function,
+                    handler.handler,
+                    self.contract_abi.name,
+                    did_you_mean(&handler.function, signatures)
+                ));
+            }
+        }
+
         errors
     }
 
+    fn lint(&self) -> Vec<String> {
+        let mut warnings = vec![];
+
+        // ABIs other than the one for the source contract are only needed
+        // for `ethereum.call`
+        let makes_calls = self.mapping.requires_archive().unwrap_or(true);
+        if !makes_calls {
+            for abi in &self.mapping.abis {
+                if abi.name != self.contract_abi.name {
+                    warnings.push(format!(
+                        "ABI `{}` is not used since the mapping does not make any contract calls",
+                        abi.name
+                    ));
+                }
+            }
+        }
+
+        warnings
+    }
+
     fn api_version(&self) -> semver::Version {
         self.mapping.api_version.clone()
     }
 
     fn min_spec_version(&self) -> semver::Version {
-        self.mapping
-            .block_handlers
-            .iter()
-            .fold(MIN_SPEC_VERSION, |mut min, handler| {
-                min = match handler.filter {
-                    Some(BlockHandlerFilter::Polling { every: _ }) => SPEC_VERSION_0_0_8,
-                    Some(BlockHandlerFilter::Once) => SPEC_VERSION_0_0_8,
-                    _ => min,
-                };
-                min
-            })
+        self.mapping.min_spec_version()
     }
 
     fn runtime(&self) -> Option<Arc<Vec<u8>>> {
@@ -397,7 +546,7 @@ impl DataSource {
         })
     }
 
-    fn handlers_for_log(&self, log: &Log) -> Vec<MappingEventHandler> {
+    fn handlers_for_log(&self, log: &Log, block: &LightEthereumBlock) -> Vec<MappingEventHandler> {
         // Get signature from the log
         let topic0 = match log.topics.get(0) {
             Some(topic0) => topic0,
@@ -408,12 +557,20 @@ impl DataSource {
         self.mapping
             .event_handlers
             .iter()
-            .filter(|handler| *topic0 == handler.topic0())
+            .filter(|handler| {
+                *topic0 == handler.topic0()
+                    && handler.topics.matches(log)
+                    && handler.start.has_started(block)
+            })
             .cloned()
             .collect::<Vec<_>>()
     }
 
-    fn handler_for_call(&self, call: &EthereumCall) -> Result<Option<MappingCallHandler>, Error> {
+    fn handler_for_call(
+        &self,
+        call: &EthereumCall,
+        block: &LightEthereumBlock,
+    ) -> Result<Option<MappingCallHandler>, Error> {
         // First four bytes of the input for the call are the first four
         // bytes of hash of the function signature
         ensure!(
@@ -427,6 +584,7 @@ impl DataSource {
             .mapping
             .call_handlers
             .iter()
+            .filter(|handler| handler.start.has_started(block))
             .find(move |handler| {
                 let fhash = keccak256(handler.function.as_bytes());
                 let actual_method_id = [fhash[0], fhash[1], fhash[2], fhash[3]];
@@ -438,24 +596,24 @@ impl DataSource {
     fn handler_for_block(
         &self,
         trigger_type: &EthereumBlockTriggerType,
-        block: BlockNumber,
+        block: &LightEthereumBlock,
     ) -> Option<MappingBlockHandler> {
+        let handlers = self
+            .mapping
+            .block_handlers
+            .iter()
+            .filter(|handler| handler.start.has_started(block));
+        let block = block.number();
         match trigger_type {
             // Start matches only initialization handlers with a `once` filter
-            EthereumBlockTriggerType::Start => self
-                .mapping
-                .block_handlers
-                .iter()
+            EthereumBlockTriggerType::Start => handlers
                 .find(move |handler| match handler.filter {
                     Some(BlockHandlerFilter::Once) => block == self.start_block,
                     _ => false,
                 })
                 .cloned(),
             // End matches all handlers without a filter or with a `polling` filter
-            EthereumBlockTriggerType::End => self
-                .mapping
-                .block_handlers
-                .iter()
+            EthereumBlockTriggerType::End => handlers
                 .find(move |handler| match handler.filter {
                     Some(BlockHandlerFilter::Polling { every }) => {
                         let start_block = self.start_block;
@@ -466,10 +624,7 @@ impl DataSource {
                     _ => false,
                 })
                 .cloned(),
-            EthereumBlockTriggerType::WithCallTo(_address) => self
-                .mapping
-                .block_handlers
-                .iter()
+            EthereumBlockTriggerType::WithCallTo(_address) => handlers
                 .find(move |handler| handler.filter == Some(BlockHandlerFilter::Call))
                 .cloned(),
         }
@@ -481,65 +636,6 @@ impl DataSource {
     /// 2. There are no equal matches, but there is exactly one event that equals `signature` if all
     ///    `indexed` modifiers are removed from the parameters.
     fn contract_event_with_signature(&self, signature: &str) -> Option<&Event> {
-        // Returns an `Event(uint256,address)` signature for an event, without `indexed` hints.
-        fn ambiguous_event_signature(event: &Event) -> String {
-            format!(
-                "{}({})",
-                event.name,
-                event
-                    .inputs
-                    .iter()
-                    .map(|input| event_param_type_signature(&input.kind))
-                    .collect::<Vec<_>>()
-                    .join(",")
-            )
-        }
-
-        // Returns an `Event(indexed uint256,address)` type signature for an event.
-        fn event_signature(event: &Event) -> String {
-            format!(
-                "{}({})",
-                event.name,
-                event
-                    .inputs
-                    .iter()
-                    .map(|input| format!(
-                        "{}{}",
-                        if input.indexed { "indexed " } else { "" },
-                        event_param_type_signature(&input.kind)
-                    ))
-                    .collect::<Vec<_>>()
-                    .join(",")
-            )
-        }
-
-        // Returns the signature of an event parameter type (e.g. `uint256`).
-        fn event_param_type_signature(kind: &ParamType) -> String {
-            use ParamType::*;
-
-            match kind {
-                Address => "address".into(),
-                Bytes => "bytes".into(),
-                Int(size) => format!("int{}", size),
-                Uint(size) => format!("uint{}", size),
-                Bool => "bool".into(),
-                String => "string".into(),
-                Array(inner) => format!("{}[]", event_param_type_signature(inner)),
-                FixedBytes(size) => format!("bytes{}", size),
-                FixedArray(inner, size) => {
-                    format!("{}[{}]", event_param_type_signature(inner), size)
-                }
-                Tuple(components) => format!(
-                    "({})",
-                    components
-                        .iter()
-                        .map(event_param_type_signature)
-                        .collect::<Vec<_>>()
-                        .join(",")
-                ),
-            }
-        }
-
         self.contract_abi
             .contract
             .events()
@@ -547,33 +643,24 @@ impl DataSource {
             .or_else(|| {
                 // Fallback for subgraphs that don't use `indexed` in event signatures yet:
                 //
-                // If there is only one event variant with this name and if its signature
-                // without `indexed` matches the event signature from the manifest, we
-                // can safely assume that the event is a match, we don't need to force
-                // the subgraph to add `indexed`.
-
-                // Extract the event name; if there is no '(' in the signature,
-                // `event_name` will be empty and not match any events, so that's ok
-                let parens = signature.find('(').unwrap_or(0);
-                let event_name = &signature[0..parens];
-
-                let matching_events = self
+                // If there is exactly one event whose signature without `indexed`
+                // matches the event signature from the manifest, we can safely assume
+                // that the event is a match, we don't need to force the subgraph to
+                // add `indexed`. Overloaded events with different parameter types can
+                // be told apart this way, too, since their signatures differ even
+                // without `indexed`; overloads that only differ in which parameters
+                // are indexed have to be written with `indexed` in the manifest.
+                let mut matching_events = self
                     .contract_abi
                     .contract
                     .events()
-                    .filter(|event| event.name == event_name)
-                    .collect::<Vec<_>>();
+                    .filter(|event| ambiguous_event_signature(event) == signature);
 
-                // Only match the event signature without `indexed` if there is
-                // only a single event variant
-                if matching_events.len() == 1
-                    && ambiguous_event_signature(matching_events[0]) == signature
-                {
-                    Some(matching_events[0])
-                } else {
-                    // More than one event variant or the signature
-                    // still doesn't match, even if we ignore `indexed` hints
-                    None
+                match (matching_events.next(), matching_events.next()) {
+                    (Some(event), None) => Some(event),
+                    // No event matches, even if we ignore `indexed` hints, or
+                    // more than one does
+                    _ => None,
                 }
             })
     }
@@ -582,25 +669,8 @@ impl DataSource {
         self.contract_abi
             .contract
             .functions()
-            .filter(|function| match function.state_mutability {
-                StateMutability::Payable | StateMutability::NonPayable => true,
-                StateMutability::Pure | StateMutability::View => false,
-            })
-            .find(|function| {
-                // Construct the argument function signature:
-                // `address,uint256,bool`
-                let mut arguments = function
-                    .inputs
-                    .iter()
-                    .map(|input| format!("{}", input.kind))
-                    .collect::<Vec<String>>()
-                    .join(",");
-                // `address,uint256,bool)
-                arguments.push(')');
-                // `operation(address,uint256,bool)`
-                let actual_signature = vec![function.name.clone(), arguments].join("(");
-                target_signature == actual_signature
-            })
+            .filter(|function| is_mutating(function))
+            .find(|function| target_signature == function_signature(function))
     }
 
     fn matches_trigger_address(&self, trigger: &EthereumTrigger) -> bool {
@@ -634,7 +704,7 @@ impl DataSource {
 
         match trigger {
             EthereumTrigger::Block(_, trigger_type) => {
-                let handler = match self.handler_for_block(trigger_type, block.number()) {
+                let handler = match self.handler_for_block(trigger_type, block) {
                     Some(handler) => handler,
                     None => return Ok(None),
                 };
@@ -649,7 +719,7 @@ impl DataSource {
             EthereumTrigger::Log(log_ref) => {
                 let log = Arc::new(log_ref.log().clone());
                 let receipt = log_ref.receipt();
-                let potential_handlers = self.handlers_for_log(&log);
+                let potential_handlers = self.handlers_for_log(&log, block);
 
                 // Map event handlers to (event handler, event ABI) pairs; fail if there are
                 // handlers that don't exist in the contract ABI
@@ -701,6 +771,13 @@ impl DataSource {
                             .ok()
                             .map(|params| (event_handler, params))
                     })
+                    // Skip handlers whose filter rules out this event
+                    .filter(|(event_handler, params)| {
+                        event_handler
+                            .filter
+                            .as_ref()
+                            .map_or(true, |filter| filter.matches(params))
+                    })
                     .collect::<Vec<_>>();
 
                 if matching_handlers.is_empty() {
@@ -756,9 +833,43 @@ impl DataSource {
                     logging_extras,
                 )))
             }
+            EthereumTrigger::Call(call) if call.creation => {
+                let handler = match self
+                    .mapping
+                    .contract_creation_handlers
+                    .iter()
+                    .find(|handler| handler.matches(call))
+                {
+                    Some(handler) => handler,
+                    None => return Ok(None),
+                };
+
+                let transaction = Arc::new(
+                    block
+                        .transaction_for_call(call)
+                        .context("Found no transaction for contract creation")?,
+                );
+                let logging_extras = Arc::new(o! {
+                    "contract" => format!("{}", &call.to),
+                    "deployer" => format!("{}", &call.from),
+                    "transaction" => format!("{}", &transaction.hash),
+                });
+                Ok(Some(TriggerWithHandler::<Chain>::new_with_logging_extras(
+                    MappingTrigger::Call {
+                        block: block.cheap_clone(),
+                        transaction,
+                        call: call.cheap_clone(),
+                        inputs: vec![],
+                        outputs: vec![],
+                    },
+                    handler.handler.clone(),
+                    block.block_ptr(),
+                    logging_extras,
+                )))
+            }
             EthereumTrigger::Call(call) => {
                 // Identify the call handler for this call
-                let handler = match self.handler_for_call(call)? {
+                let handler = match self.handler_for_call(call, block)? {
                     Some(handler) => handler,
                     None => return Ok(None),
                 };
@@ -959,6 +1070,34 @@ impl blockchain::UnresolvedDataSourceTemplate<Chain> for UnresolvedDataSourceTem
     }
 }
 
+impl DataSourceTemplate {
+    /// The ABI for the contract of a data source created from this template
+    /// with `context`. If the template has `abiFromContext`, the context
+    /// entry with that name selects the ABI; without such an entry, or
+    /// without `abiFromContext`, the ABI is `source.abi`
+    pub fn contract_abi(
+        &self,
+        context: Option<&DataSourceContext>,
+    ) -> Result<Arc<MappingABI>, Error> {
+        let name = match &self.source.abi_from_context {
+            None => &self.source.abi,
+            Some(key) => match context.and_then(|context| context.get(key)) {
+                None => &self.source.abi,
+                Some(Value::String(name)) => name,
+                Some(value) => {
+                    return Err(anyhow!(
+                        "the context entry `{}` that selects the ABI must be a string, \
+                         but it is `{}`",
+                        key,
+                        value
+                    ))
+                }
+            },
+        };
+        self.mapping.find_abi(name)
+    }
+}
+
 impl blockchain::DataSourceTemplate<Chain> for DataSourceTemplate {
     fn name(&self) -> &str {
         &self.name
@@ -968,6 +1107,14 @@ impl blockchain::DataSourceTemplate<Chain> for DataSourceTemplate {
         self.mapping.api_version.clone()
     }
 
+    fn min_spec_version(&self) -> semver::Version {
+        let min = self.mapping.min_spec_version();
+        if self.source.abi_from_context.is_some() {
+            return min.max(SPEC_VERSION_1_1_0);
+        }
+        min
+    }
+
     fn runtime(&self) -> Option<Arc<Vec<u8>>> {
         Some(self.mapping.runtime.cheap_clone())
     }
@@ -995,6 +1142,8 @@ pub struct UnresolvedMapping {
     pub call_handlers: Vec<MappingCallHandler>,
     #[serde(default)]
     pub event_handlers: Vec<MappingEventHandler>,
+    #[serde(default)]
+    pub contract_creation_handlers: Vec<MappingContractCreationHandler>,
     pub file: Link,
 }
 
@@ -1008,6 +1157,7 @@ pub struct Mapping {
     pub block_handlers: Vec<MappingBlockHandler>,
     pub call_handlers: Vec<MappingCallHandler>,
     pub event_handlers: Vec<MappingEventHandler>,
+    pub contract_creation_handlers: Vec<MappingContractCreationHandler>,
     pub runtime: Arc<Vec<u8>>,
     pub link: Link,
 }
@@ -1027,6 +1177,44 @@ impl Mapping {
             .any(|handler| matches!(handler.filter, Some(BlockHandlerFilter::Call)))
     }
 
+    pub fn has_contract_creation_handler(&self) -> bool {
+        !self.contract_creation_handlers.is_empty()
+    }
+
+    /// The lowest spec version that supports all the features the
+    /// handlers of this mapping use
+    fn min_spec_version(&self) -> semver::Version {
+        let min = self
+            .block_handlers
+            .iter()
+            .fold(MIN_SPEC_VERSION, |mut min, handler| {
+                min = match handler.filter {
+                    Some(BlockHandlerFilter::Polling { every: _ }) => SPEC_VERSION_0_0_8,
+                    Some(BlockHandlerFilter::Once) => SPEC_VERSION_0_0_8,
+                    _ => min,
+                };
+                min
+            });
+
+        let has_handler_start = self
+            .block_handlers
+            .iter()
+            .map(|handler| &handler.start)
+            .chain(self.call_handlers.iter().map(|handler| &handler.start))
+            .chain(self.event_handlers.iter().map(|handler| &handler.start))
+            .any(HandlerStart::is_set);
+        let has_batch_handler = self.event_handlers.iter().any(|handler| handler.batch);
+        let has_event_filter = self
+            .event_handlers
+            .iter()
+            .any(|handler| handler.filter.is_some() || handler.topics.is_set());
+        if has_handler_start || has_batch_handler || has_event_filter {
+            return SPEC_VERSION_1_1_0;
+        }
+
+        min
+    }
+
     pub fn find_abi(&self, abi_name: &str) -> Result<Arc<MappingABI>, Error> {
         Ok(self
             .abis
@@ -1052,6 +1240,7 @@ impl UnresolvedMapping {
             block_handlers,
             call_handlers,
             event_handlers,
+            contract_creation_handlers,
             file: link,
         } = self;
 
@@ -1084,6 +1273,7 @@ impl UnresolvedMapping {
             block_handlers: block_handlers.clone(),
             call_handlers: call_handlers.clone(),
             event_handlers: event_handlers.clone(),
+            contract_creation_handlers: contract_creation_handlers.clone(),
             runtime,
             link,
         })
@@ -1122,10 +1312,41 @@ impl UnresolvedMappingABI {
     }
 }
 
+/// When a handler starts to be called, independently of the start of its
+/// data source. A handler that a new version of a subgraph adds can use
+/// this to only process blocks from the point where it was introduced when
+/// the new version is grafted onto an older one or resynced
+#[derive(Clone, Debug, Default, Hash, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HandlerStart {
+    /// The first block for which the handler is called
+    pub start_block: Option<BlockNumber>,
+    /// The handler is only called for blocks with at least this timestamp,
+    /// in seconds since the epoch
+    pub start_time: Option<u64>,
+}
+
+impl HandlerStart {
+    /// Whether the handler is called for `block`
+    pub fn has_started(&self, block: &LightEthereumBlock) -> bool {
+        self.start_block
+            .map_or(true, |start_block| block.number() >= start_block)
+            && self
+                .start_time
+                .map_or(true, |start_time| block.timestamp >= U256::from(start_time))
+    }
+
+    fn is_set(&self) -> bool {
+        self.start_block.is_some() || self.start_time.is_some()
+    }
+}
+
 #[derive(Clone, Debug, Hash, Eq, PartialEq, Deserialize)]
 pub struct MappingBlockHandler {
     pub handler: String,
     pub filter: Option<BlockHandlerFilter>,
+    #[serde(flatten)]
+    pub start: HandlerStart,
 }
 
 impl MappingBlockHandler {
@@ -1157,6 +1378,8 @@ pub enum BlockHandlerFilter {
 pub struct MappingCallHandler {
     pub function: String,
     pub handler: String,
+    #[serde(flatten)]
+    pub start: HandlerStart,
 }
 
 #[derive(Clone, Debug, Hash, Eq, PartialEq, Deserialize)]
@@ -1166,6 +1389,99 @@ pub struct MappingEventHandler {
     pub handler: String,
     #[serde(default)]
     pub receipt: bool,
+    /// Batch handlers are called once per block with all of the block's
+    /// matching events, after all other handlers for the block
+    #[serde(default)]
+    pub batch: bool,
+    /// Only call the handler for events whose parameters pass this filter
+    #[serde(default)]
+    pub filter: Option<EventFilter>,
+    #[serde(flatten)]
+    pub topics: TopicFilter,
+    #[serde(flatten)]
+    pub start: HandlerStart,
+}
+
+/// Restrictions on the topics after `topic0` of the logs that an event
+/// handler is called for. A log matches if its topic in each restricted
+/// position is one of the values listed for that position; positions
+/// without values match any topic. These restrictions are also passed to
+/// `eth_getLogs`
+#[derive(Clone, Debug, Default, Hash, Eq, PartialEq, Deserialize)]
+pub struct TopicFilter {
+    pub topic1: Option<Vec<H256>>,
+    pub topic2: Option<Vec<H256>>,
+    pub topic3: Option<Vec<H256>>,
+}
+
+impl TopicFilter {
+    fn positions(&self) -> [&Option<Vec<H256>>; 3] {
+        [&self.topic1, &self.topic2, &self.topic3]
+    }
+
+    pub fn matches(&self, log: &Log) -> bool {
+        self.positions()
+            .iter()
+            .enumerate()
+            .all(|(i, values)| match values {
+                None => true,
+                Some(values) => log
+                    .topics
+                    .get(i + 1)
+                    .map_or(false, |topic| values.contains(topic)),
+            })
+    }
+
+    /// Whether this filter restricts any of the topics
+    pub fn is_set(&self) -> bool {
+        self.positions().iter().any(|values| values.is_some())
+    }
+
+    /// Widen this filter so that it also matches all logs that `other`
+    /// matches
+    pub fn union(&mut self, other: &TopicFilter) {
+        fn union(values: &mut Option<Vec<H256>>, other: &Option<Vec<H256>>) {
+            match (values.as_mut(), other) {
+                (Some(values), Some(other)) => {
+                    for value in other {
+                        if !values.contains(value) {
+                            values.push(*value);
+                        }
+                    }
+                }
+                _ => *values = None,
+            }
+        }
+
+        union(&mut self.topic1, &other.topic1);
+        union(&mut self.topic2, &other.topic2);
+        union(&mut self.topic3, &other.topic3);
+    }
+}
+
+/// A handler for the creation of contracts that no factory announces. It is
+/// called with an `ethereum.Call` whose `to` is the address of the new
+/// contract and whose `from` is the deployer.
+#[derive(Clone, Debug, Hash, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MappingContractCreationHandler {
+    pub handler: String,
+    /// Only match contracts created by this address; for contracts created
+    /// with CREATE2 through a factory contract, this is the factory
+    pub deployer: Option<Address>,
+    /// Only match contracts whose init code starts with these bytes
+    pub init_code_prefix: Option<Bytes>,
+}
+
+impl MappingContractCreationHandler {
+    pub fn matches(&self, call: &EthereumCall) -> bool {
+        call.creation
+            && self.deployer.map_or(true, |deployer| deployer == call.from)
+            && self
+                .init_code_prefix
+                .as_ref()
+                .map_or(true, |prefix| call.input.0.starts_with(&prefix.0))
+    }
 }
 
 impl MappingEventHandler {
@@ -1175,6 +1491,119 @@ impl MappingEventHandler {
     }
 }
 
+// Returns an `Event(uint256,address)` signature for an event, without `indexed` hints.
+fn ambiguous_event_signature(event: &Event) -> String {
+    format!(
+        "{}({})",
+        event.name,
+        event
+            .inputs
+            .iter()
+            .map(|input| event_param_type_signature(&input.kind))
+            .collect::<Vec<_>>()
+            .join(",")
+    )
+}
+
+// Returns an `Event(indexed uint256,address)` type signature for an event.
+fn event_signature(event: &Event) -> String {
+    format!(
+        "{}({})",
+        event.name,
+        event
+            .inputs
+            .iter()
+            .map(|input| format!(
+                "{}{}",
+                if input.indexed { "indexed " } else { "" },
+                event_param_type_signature(&input.kind)
+            ))
+            .collect::<Vec<_>>()
+            .join(",")
+    )
+}
+
+// Returns the signature of an event parameter type (e.g. `uint256`).
+fn event_param_type_signature(kind: &ParamType) -> String {
+    use ParamType::*;
+
+    match kind {
+        Address => "address".into(),
+        Bytes => "bytes".into(),
+        Int(size) => format!("int{}", size),
+        Uint(size) => format!("uint{}", size),
+        Bool => "bool".into(),
+        String => "string".into(),
+        Array(inner) => format!("{}[]", event_param_type_signature(inner)),
+        FixedBytes(size) => format!("bytes{}", size),
+        FixedArray(inner, size) => {
+            format!("{}[{}]", event_param_type_signature(inner), size)
+        }
+        Tuple(components) => format!(
+            "({})",
+            components
+                .iter()
+                .map(event_param_type_signature)
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+    }
+}
+
+// Returns an `operation(address,uint256,bool)` signature for a function.
+fn function_signature(function: &Function) -> String {
+    // Construct the argument function signature:
+    // `address,uint256,bool`
+    let mut arguments = function
+        .inputs
+        .iter()
+        .map(|input| format!("{}", input.kind))
+        .collect::<Vec<String>>()
+        .join(",");
+    // `address,uint256,bool)
+    arguments.push(')');
+    // `operation(address,uint256,bool)`
+    vec![function.name.clone(), arguments].join("(")
+}
+
+// Only functions that can change state are called in transactions, and
+// therefore show up in call handlers.
+fn is_mutating(function: &Function) -> bool {
+    match function.state_mutability {
+        StateMutability::Payable | StateMutability::NonPayable => true,
+        StateMutability::Pure | StateMutability::View => false,
+    }
+}
+
+// Returns a `, did you mean `...`?` suggestion with the candidate that is
+// closest to `signature`, or an empty string if there are no candidates.
+fn did_you_mean(signature: &str, candidates: impl Iterator<Item = String>) -> String {
+    candidates
+        .min_by_key(|candidate| edit_distance(signature, candidate))
+        .map(|candidate| format!(", did you mean `{}`?", candidate))
+        .unwrap_or_default()
+}
+
+// The Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                prev
+            } else {
+                1 + prev.min(row[j]).min(cur)
+            };
+            prev = cur;
+        }
+    }
+    row[b.len()]
+}
+
 /// Hashes a string to a H256 hash.
 fn string_to_h256(s: &str) -> H256 {
     let mut result = [0u8; 32];
@@ -1190,6 +1619,273 @@ fn string_to_h256(s: &str) -> H256 {
 }
 
 #[derive(Clone, Debug, Default, Hash, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TemplateSource {
     pub abi: String,
+    /// The name of an entry in the creation context of data sources that
+    /// names the ABI to use instead of `abi`, so that one template can
+    /// handle contracts with different interfaces
+    #[serde(default)]
+    pub abi_from_context: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use graph::blockchain::DataSource as _;
+    use graph::prelude::ethabi::Contract;
+
+    use graph::data::subgraph::DataSourceContext;
+    use graph::prelude::Value;
+
+    use super::{
+        did_you_mean, edit_distance, DataSource, DataSourceTemplate, HandlerStart,
+        LightEthereumBlock, Mapping, MappingABI, MappingCallHandler, MappingEventHandler,
+        TemplateSource, TopicFilter,
+    };
+
+    const OVERLOADED_ABI: &str = r#"[
+        {"type": "event", "name": "Transfer", "anonymous": false, "inputs": [
+            {"name": "to", "type": "address", "indexed": true},
+            {"name": "value", "type": "uint256", "indexed": false}]},
+        {"type": "event", "name": "Transfer", "anonymous": false, "inputs": [
+            {"name": "from", "type": "address", "indexed": true},
+            {"name": "to", "type": "address", "indexed": true},
+            {"name": "value", "type": "uint256", "indexed": false}]},
+        {"type": "function", "name": "transfer", "stateMutability": "nonpayable", "inputs": [
+            {"name": "to", "type": "address"},
+            {"name": "value", "type": "uint256"}], "outputs": []},
+        {"type": "function", "name": "transfer", "stateMutability": "nonpayable", "inputs": [
+            {"name": "to", "type": "address"},
+            {"name": "value", "type": "uint256"},
+            {"name": "data", "type": "bytes"}], "outputs": []}
+    ]"#;
+
+    fn data_source(events: &[&str], functions: &[&str]) -> DataSource {
+        let contract_abi = Arc::new(MappingABI {
+            name: "Token".to_string(),
+            contract: Contract::load(OVERLOADED_ABI.as_bytes()).unwrap(),
+        });
+        let event_handlers = events
+            .iter()
+            .enumerate()
+            .map(|(i, event)| MappingEventHandler {
+                event: event.to_string(),
+                topic0: None,
+                handler: format!("handleEvent{}", i),
+                receipt: false,
+                batch: false,
+                filter: None,
+                topics: TopicFilter::default(),
+                start: HandlerStart::default(),
+            })
+            .collect();
+        let call_handlers = functions
+            .iter()
+            .enumerate()
+            .map(|(i, function)| MappingCallHandler {
+                function: function.to_string(),
+                handler: format!("handleCall{}", i),
+                start: HandlerStart::default(),
+            })
+            .collect();
+        DataSource {
+            kind: "ethereum/contract".to_string(),
+            network: Some("mainnet".to_string()),
+            name: "Token".to_string(),
+            manifest_idx: 0,
+            address: Some(Default::default()),
+            start_block: 0,
+            end_block: None,
+            mapping: Mapping {
+                kind: "ethereum/events".to_string(),
+                api_version: semver::Version::new(0, 0, 7),
+                language: "wasm/assemblyscript".to_string(),
+                entities: vec![],
+                abis: vec![contract_abi.clone()],
+                block_handlers: vec![],
+                call_handlers,
+                event_handlers,
+                contract_creation_handlers: vec![],
+                runtime: Arc::new(vec![]),
+                link: "link".into(),
+            },
+            context: Arc::new(None),
+            creation_block: None,
+            contract_abi,
+        }
+    }
+
+    #[test]
+    fn overloaded_signatures() {
+        let ds = data_source(
+            &[
+                "Transfer(indexed address,uint256)",
+                "Transfer(address,address,uint256)",
+            ],
+            &[
+                "transfer(address,uint256)",
+                "transfer(address,uint256,bytes)",
+            ],
+        );
+        assert!(ds.validate().is_empty());
+
+        // Each handler resolves to its own overload, whether or not it
+        // uses `indexed`
+        let event = ds
+            .contract_event_with_signature("Transfer(indexed address,uint256)")
+            .unwrap();
+        assert_eq!(2, event.inputs.len());
+        let event = ds
+            .contract_event_with_signature("Transfer(address,address,uint256)")
+            .unwrap();
+        assert_eq!(3, event.inputs.len());
+
+        let function = ds
+            .contract_function_with_signature("transfer(address,uint256,bytes)")
+            .unwrap();
+        assert_eq!(3, function.inputs.len());
+
+        // Handlers are routed by the exact topic and selector
+        let topics: Vec<_> = ds
+            .mapping
+            .event_handlers
+            .iter()
+            .map(|handler| handler.topic0())
+            .collect();
+        assert_ne!(topics[0], topics[1]);
+
+        // An event that is not in the ABI is rejected with a suggestion
+        let ds = data_source(&["Transfer(address)"], &[]);
+        let errors = ds.validate();
+        assert_eq!(1, errors.len());
+        assert!(errors[0]
+            .to_string()
+            .contains("did you mean `Transfer(indexed address,uint256)`?"));
+    }
+
+    #[test]
+    fn batch_handlers() {
+        let mut ds = data_source(
+            &[
+                "Transfer(indexed address,uint256)",
+                "Transfer(address,address,uint256)",
+            ],
+            &[],
+        );
+        assert!(!ds.has_batch_handlers());
+
+        ds.mapping.event_handlers[0].batch = true;
+        assert!(ds.validate().is_empty());
+        assert!(ds.has_batch_handlers());
+        assert!(ds.is_batch_handler("handleEvent0"));
+        assert!(!ds.is_batch_handler("handleEvent1"));
+
+        // A handler can't be both a batch and an ordinary handler
+        let mut mixed = ds.clone();
+        mixed.mapping.event_handlers[1].handler = "handleEvent0".to_string();
+        let errors = mixed.validate();
+        assert_eq!(1, errors.len());
+        assert!(errors[0].to_string().contains("both as a batch"));
+
+        // Batch handlers need apiVersion 0.0.7
+        ds.mapping.api_version = semver::Version::new(0, 0, 6);
+        let errors = ds.validate();
+        assert_eq!(1, errors.len());
+        assert!(errors[0].to_string().contains("apiVersion >= 0.0.7"));
+    }
+
+    #[test]
+    fn event_filters() {
+        let mut ds = data_source(&["Transfer(address,address,uint256)"], &[]);
+        ds.mapping.event_handlers[0].filter = Some("value > 0 && to != 0x0".parse().unwrap());
+        assert!(ds.validate().is_empty());
+
+        ds.mapping.event_handlers[0].filter = Some("amount > 0".parse().unwrap());
+        let errors = ds.validate();
+        assert_eq!(1, errors.len());
+        assert!(errors[0]
+            .to_string()
+            .contains("invalid filter for handler `handleEvent0`"));
+    }
+
+    #[test]
+    fn abi_from_context() {
+        let mut mapping = data_source(&[], &[]).mapping;
+        mapping.abis.push(Arc::new(MappingABI {
+            name: "TokenV2".to_string(),
+            contract: Contract::load(OVERLOADED_ABI.as_bytes()).unwrap(),
+        }));
+        let template = DataSourceTemplate {
+            kind: "ethereum/contract".to_string(),
+            network: Some("mainnet".to_string()),
+            name: "Token".to_string(),
+            manifest_idx: 0,
+            source: TemplateSource {
+                abi: "Token".to_string(),
+                abi_from_context: Some("abi".to_string()),
+            },
+            mapping,
+        };
+        let context = |value: Value| {
+            DataSourceContext::from(std::collections::HashMap::from([("abi".into(), value)]))
+        };
+
+        let abi = |context: Option<DataSourceContext>| {
+            template
+                .contract_abi(context.as_ref())
+                .map(|abi| abi.name.clone())
+        };
+        assert_eq!("Token", abi(None).unwrap());
+        assert_eq!("Token", abi(Some(DataSourceContext::new())).unwrap());
+        assert_eq!(
+            "TokenV2",
+            abi(Some(context(Value::String("TokenV2".to_string())))).unwrap()
+        );
+        assert!(abi(Some(context(Value::String("Pool".to_string())))).is_err());
+        assert!(abi(Some(context(Value::Int(2)))).is_err());
+    }
+
+    #[test]
+    fn suggest_closest_signature() {
+        assert_eq!(0, edit_distance("Transfer(address)", "Transfer(address)"));
+        assert_eq!(3, edit_distance("kitten", "sitting"));
+
+        let candidates = || {
+            vec![
+                "Approval(indexed address,indexed address,uint256)".to_string(),
+                "Transfer(indexed address,indexed address,uint256)".to_string(),
+            ]
+            .into_iter()
+        };
+        assert_eq!(
+            ", did you mean `Transfer(indexed address,indexed address,uint256)`?",
+            did_you_mean("Transfer(address,address,uint256)", candidates())
+        );
+        assert_eq!("", did_you_mean("Transfer()", std::iter::empty()));
+    }
+
+    #[test]
+    fn handler_start() {
+        use graph::prelude::serde_yaml;
+        use graph::prelude::web3::types::{U256, U64};
+
+        let handler: MappingEventHandler = serde_yaml::from_str(
+            "event: Transfer(address,uint256)\nhandler: handleTransfer\nstartBlock: 100\nstartTime: 1700000000",
+        )
+        .unwrap();
+        assert_eq!(Some(100), handler.start.start_block);
+        assert_eq!(Some(1700000000), handler.start.start_time);
+
+        let block = |number: u64, timestamp: u64| LightEthereumBlock {
+            number: Some(U64::from(number)),
+            timestamp: U256::from(timestamp),
+            ..Default::default()
+        };
+        assert!(!handler.start.has_started(&block(99, 1800000000)));
+        assert!(!handler.start.has_started(&block(100, 1600000000)));
+        assert!(handler.start.has_started(&block(100, 1700000000)));
+        assert!(HandlerStart::default().has_started(&block(0, 0)));
+    }
 }
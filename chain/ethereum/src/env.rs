@@ -1,7 +1,9 @@
 use envconfig::Envconfig;
 use graph::env::EnvVarBoolean;
 use graph::prelude::{envconfig, lazy_static, BlockNumber};
+use std::collections::HashMap;
 use std::fmt;
+use std::str::FromStr;
 use std::time::Duration;
 
 lazy_static! {
@@ -33,6 +35,106 @@ pub struct EnvVars {
     /// Set by the environment variable `ETHEREUM_BLOCK_BATCH_SIZE`. The
     /// default value is 10 blocks.
     pub block_batch_size: usize,
+    /// The maximum number of providers that a scan for logs over a block
+    /// range is split across. Each provider scans a contiguous part of the
+    /// range concurrently with the others. A value of 1 disables this and
+    /// scans the whole range with one provider.
+    ///
+    /// Set by the environment variable
+    /// `GRAPH_ETHEREUM_PARALLEL_LOG_PROVIDERS`. The default value is 1.
+    pub parallel_log_providers: usize,
+    /// The smallest number of blocks that a provider is asked to scan for
+    /// logs when a scan is split across providers; smaller ranges are not
+    /// worth splitting up.
+    ///
+    /// Set by the environment variable
+    /// `GRAPH_ETHEREUM_PARALLEL_LOG_MIN_RANGE`. The default value is 100
+    /// blocks.
+    pub parallel_log_min_range: BlockNumber,
+    /// Whether to find the triggers for a block range in the blocks and
+    /// transaction receipts that the chain store already has instead of
+    /// asking an Ethereum node, as long as the store has all blocks in the
+    /// range and the subgraph does not need traces.
+    ///
+    /// Set by the environment variable
+    /// `GRAPH_ETHEREUM_SCAN_CACHED_BLOCKS`. Off by default.
+    pub scan_cached_blocks: bool,
+    /// Whether the block ingestor subscribes to `newHeads` when its
+    /// provider is connected over a WebSocket, so that it looks for a new
+    /// chain head as soon as the provider announces one instead of waiting
+    /// for the next poll. Polling takes over whenever the subscription
+    /// fails.
+    ///
+    /// Set by the environment variable
+    /// `GRAPH_ETHEREUM_SUBSCRIBE_NEW_HEADS`. Off by default.
+    pub subscribe_new_heads: bool,
+    /// The number of requests in a row that have to fail before a provider
+    /// is put into a cooldown, during which other providers for the same
+    /// network are used, as long as there are any. A value of 0 disables
+    /// cooldowns.
+    ///
+    /// Set by the environment variable
+    /// `GRAPH_ETHEREUM_PROVIDER_COOLDOWN_ERRORS`. The default value is 5.
+    pub provider_cooldown_errors: u64,
+    /// How long a provider stays in a cooldown after the last request to it
+    /// failed.
+    ///
+    /// Set by the environment variable `GRAPH_ETHEREUM_PROVIDER_COOLDOWN`
+    /// (expressed in seconds). The default value is 30 seconds.
+    pub provider_cooldown: Duration,
+    /// How often to ask each provider for its client version and whether it
+    /// is still syncing. A value of 0 turns these checks off.
+    ///
+    /// Set by the environment variable
+    /// `GRAPH_ETHEREUM_PROVIDER_CHECK_INTERVAL` (expressed in seconds). The
+    /// default value is 300 seconds.
+    pub provider_check_interval: Duration,
+    /// Client versions that are known to return wrong data; a provider whose
+    /// client version contains one of these strings is reported as broken.
+    ///
+    /// Set by the environment variable
+    /// `GRAPH_ETHEREUM_BROKEN_CLIENT_VERSIONS`, separated by `;`.
+    pub broken_client_versions: Vec<String>,
+    /// Whether to stop using providers that are syncing or run a broken
+    /// client version as long as other providers for the same network are
+    /// healthy.
+    ///
+    /// Set by the environment variable
+    /// `GRAPH_ETHEREUM_QUARANTINE_UNHEALTHY_PROVIDERS`. Off by default.
+    pub quarantine_unhealthy_providers: bool,
+    /// The maximum number of log scans that all deployments on one network
+    /// run at the same time.
+    ///
+    /// Set by the environment variable
+    /// `GRAPH_ETHEREUM_EXTRACTION_MAX_LOG_SCANS`. The default value is 16.
+    pub extraction_max_log_scans: usize,
+    /// The maximum number of trace scans that all deployments on one
+    /// network run at the same time.
+    ///
+    /// Set by the environment variable
+    /// `GRAPH_ETHEREUM_EXTRACTION_MAX_TRACE_SCANS`. The default value is 8.
+    pub extraction_max_trace_scans: usize,
+    /// The maximum number of times that all deployments on one network
+    /// fetch the transaction receipts for a block range at the same time.
+    ///
+    /// Set by the environment variable
+    /// `GRAPH_ETHEREUM_EXTRACTION_MAX_RECEIPT_FETCHES`. The default value is
+    /// 16.
+    pub extraction_max_receipt_fetches: usize,
+    /// The maximum number of JSON-RPC requests per second that one
+    /// deployment may make. Unlimited if not set or 0.
+    ///
+    /// Set by the environment variable
+    /// `GRAPH_ETHEREUM_DEPLOYMENT_MAX_RPC_RATE`.
+    pub deployment_max_rpc_rate: Option<f64>,
+    /// Overrides of `deployment_max_rpc_rate` for individual deployments,
+    /// keyed by deployment hash. A rate of 0 makes the deployment
+    /// unlimited.
+    ///
+    /// Set by the environment variable
+    /// `GRAPH_ETHEREUM_DEPLOYMENT_MAX_RPC_RATES` as a comma separated list
+    /// of `<deployment hash>=<rate>` entries.
+    pub deployment_max_rpc_rates: HashMap<String, f64>,
     /// Maximum number of blocks to request in each chunk.
     ///
     /// Set by the environment variable `GRAPH_ETHEREUM_MAX_BLOCK_RANGE_SIZE`.
@@ -84,6 +186,12 @@ pub struct EnvVars {
     /// This is a comma separated list of chain ids for which the gas field will not be set
     /// when calling `eth_call`.
     pub eth_call_no_gas: Vec<String>,
+    /// Whether mappings can use the `ethereum.tokenName`,
+    /// `ethereum.tokenSymbol`, `ethereum.tokenDecimals` and
+    /// `ethereum.tokenURI` host functions.
+    ///
+    /// Set by the flag `GRAPH_ETHEREUM_TOKEN_HOST_FNS`. Off by default.
+    pub token_host_fns: bool,
 }
 
 // This does not print any values avoid accidentally leaking any sensitive env vars
@@ -97,6 +205,15 @@ impl EnvVars {
     pub fn from_env() -> Result<Self, envconfig::Error> {
         Ok(Inner::init_from_env()?.into())
     }
+
+    /// The maximum number of RPC requests per second for `deployment`, or
+    /// `None` if the deployment is not limited
+    pub fn deployment_max_rpc_rate(&self, deployment: &str) -> Option<f64> {
+        match self.deployment_max_rpc_rates.get(deployment) {
+            Some(rate) => Some(*rate).filter(|rate| *rate > 0.0),
+            None => self.deployment_max_rpc_rate,
+        }
+    }
 }
 
 impl From<Inner> for EnvVars {
@@ -112,6 +229,25 @@ impl From<Inner> for EnvVars {
             trace_stream_step_size: x.trace_stream_step_size,
             max_event_only_range: x.max_event_only_range,
             block_batch_size: x.block_batch_size,
+            parallel_log_providers: x.parallel_log_providers,
+            parallel_log_min_range: x.parallel_log_min_range,
+            scan_cached_blocks: x.scan_cached_blocks,
+            subscribe_new_heads: x.subscribe_new_heads,
+            provider_cooldown_errors: x.provider_cooldown_errors,
+            provider_cooldown: Duration::from_secs(x.provider_cooldown_in_secs),
+            provider_check_interval: Duration::from_secs(x.provider_check_interval_in_secs),
+            broken_client_versions: x
+                .broken_client_versions
+                .split(';')
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect(),
+            quarantine_unhealthy_providers: x.quarantine_unhealthy_providers,
+            extraction_max_log_scans: x.extraction_max_log_scans,
+            extraction_max_trace_scans: x.extraction_max_trace_scans,
+            extraction_max_receipt_fetches: x.extraction_max_receipt_fetches,
+            deployment_max_rpc_rate: x.deployment_max_rpc_rate.filter(|rate| *rate > 0.0),
+            deployment_max_rpc_rates: x.deployment_max_rpc_rates.0,
             max_block_range_size: x.max_block_range_size,
             json_rpc_timeout: Duration::from_secs(x.json_rpc_timeout_in_secs),
             request_retries: x.request_retries,
@@ -130,6 +266,7 @@ impl From<Inner> for EnvVars {
                 .filter(|s| !s.is_empty())
                 .map(str::to_string)
                 .collect(),
+            token_host_fns: x.token_host_fns.0,
         }
     }
 }
@@ -153,6 +290,37 @@ struct Inner {
     max_event_only_range: BlockNumber,
     #[envconfig(from = "ETHEREUM_BLOCK_BATCH_SIZE", default = "10")]
     block_batch_size: usize,
+    #[envconfig(from = "GRAPH_ETHEREUM_PARALLEL_LOG_PROVIDERS", default = "1")]
+    parallel_log_providers: usize,
+    #[envconfig(from = "GRAPH_ETHEREUM_PARALLEL_LOG_MIN_RANGE", default = "100")]
+    parallel_log_min_range: BlockNumber,
+    #[envconfig(from = "GRAPH_ETHEREUM_SCAN_CACHED_BLOCKS", default = "false")]
+    scan_cached_blocks: bool,
+    #[envconfig(from = "GRAPH_ETHEREUM_SUBSCRIBE_NEW_HEADS", default = "false")]
+    subscribe_new_heads: bool,
+    #[envconfig(from = "GRAPH_ETHEREUM_PROVIDER_COOLDOWN_ERRORS", default = "5")]
+    provider_cooldown_errors: u64,
+    #[envconfig(from = "GRAPH_ETHEREUM_PROVIDER_COOLDOWN", default = "30")]
+    provider_cooldown_in_secs: u64,
+    #[envconfig(from = "GRAPH_ETHEREUM_PROVIDER_CHECK_INTERVAL", default = "300")]
+    provider_check_interval_in_secs: u64,
+    #[envconfig(from = "GRAPH_ETHEREUM_BROKEN_CLIENT_VERSIONS", default = "")]
+    broken_client_versions: String,
+    #[envconfig(
+        from = "GRAPH_ETHEREUM_QUARANTINE_UNHEALTHY_PROVIDERS",
+        default = "false"
+    )]
+    quarantine_unhealthy_providers: bool,
+    #[envconfig(from = "GRAPH_ETHEREUM_EXTRACTION_MAX_LOG_SCANS", default = "16")]
+    extraction_max_log_scans: usize,
+    #[envconfig(from = "GRAPH_ETHEREUM_EXTRACTION_MAX_TRACE_SCANS", default = "8")]
+    extraction_max_trace_scans: usize,
+    #[envconfig(from = "GRAPH_ETHEREUM_EXTRACTION_MAX_RECEIPT_FETCHES", default = "16")]
+    extraction_max_receipt_fetches: usize,
+    #[envconfig(from = "GRAPH_ETHEREUM_DEPLOYMENT_MAX_RPC_RATE")]
+    deployment_max_rpc_rate: Option<f64>,
+    #[envconfig(from = "GRAPH_ETHEREUM_DEPLOYMENT_MAX_RPC_RATES", default = "")]
+    deployment_max_rpc_rates: DeploymentRates,
     #[envconfig(from = "GRAPH_ETHEREUM_MAX_BLOCK_RANGE_SIZE", default = "2000")]
     max_block_range_size: BlockNumber,
     #[envconfig(from = "GRAPH_ETHEREUM_JSON_RPC_TIMEOUT", default = "180")]
@@ -177,4 +345,53 @@ struct Inner {
     genesis_block_number: u64,
     #[envconfig(from = "GRAPH_ETH_CALL_NO_GAS", default = "421613")]
     eth_call_no_gas: String,
+    #[envconfig(from = "GRAPH_ETHEREUM_TOKEN_HOST_FNS", default = "false")]
+    token_host_fns: EnvVarBoolean,
+}
+
+/// A comma separated list of `<deployment hash>=<rate>` entries
+#[derive(Clone, Debug)]
+struct DeploymentRates(HashMap<String, f64>);
+
+impl FromStr for DeploymentRates {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(|entry| {
+                entry
+                    .split_once('=')
+                    .and_then(|(hash, rate)| {
+                        let rate = rate.trim().parse::<f64>().ok()?;
+                        Some((hash.trim().to_string(), rate))
+                    })
+                    .ok_or_else(|| {
+                        format!(
+                            "invalid entry `{}`, expected `<deployment hash>=<rate>`",
+                            entry
+                        )
+                    })
+            })
+            .collect::<Result<_, _>>()
+            .map(DeploymentRates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DeploymentRates;
+
+    #[test]
+    fn parse_deployment_rates() {
+        let rates: DeploymentRates = "".parse().unwrap();
+        assert!(rates.0.is_empty());
+
+        let rates: DeploymentRates = "QmA=10, QmB = 0.5,".parse().unwrap();
+        assert_eq!(Some(&10.0), rates.0.get("QmA"));
+        assert_eq!(Some(&0.5), rates.0.get("QmB"));
+
+        assert!("QmA".parse::<DeploymentRates>().is_err());
+        assert!("QmA=fast".parse::<DeploymentRates>().is_err());
+    }
 }
@@ -7,10 +7,13 @@ use graph::blockchain::ChainIdentifier;
 use graph::components::transaction_receipt::LightTransactionReceipt;
 use graph::data::subgraph::UnifiedMappingApiVersion;
 use graph::data::subgraph::API_VERSION_0_0_7;
+use graph::prelude::chrono::{DateTime, Utc};
 use graph::prelude::ethabi::ParamType;
 use graph::prelude::ethabi::Token;
 use graph::prelude::tokio::try_join;
 use graph::slog::o;
+use graph::stable_hash::crypto_stable_hash;
+use graph::util::herd_cache::HerdCache;
 use graph::{
     blockchain::{block_stream::BlockWithTriggers, BlockPtr, IngestorError},
     prelude::{
@@ -22,43 +25,288 @@ use graph::{
             self,
             types::{
                 Address, BlockId, BlockNumber as Web3BlockNumber, Bytes, CallRequest, Filter,
-                FilterBuilder, Log, Transaction, TransactionReceipt, H256,
+                FilterBuilder, Log, Transaction, TransactionReceipt, H256, U256, U64,
             },
         },
-        BlockNumber, ChainStore, CheapClone, DynTryFuture, Error, EthereumCallCache, Logger,
-        TimeoutError, TryFutureExt,
+        BlockNumber, ChainStore, CheapClone, Deserialize, DynTryFuture, Error, EthereumCallCache,
+        Logger, Serialize, TimeoutError, TryFutureExt,
     },
 };
 use graph::{
     components::ethereum::*,
     prelude::web3::api::Web3,
     prelude::web3::transports::Batch,
-    prelude::web3::types::{Trace, TraceFilter, TraceFilterBuilder, H160},
+    prelude::web3::types::{Action, Res, Trace, TraceFilter, TraceFilterBuilder, H160},
 };
 use itertools::Itertools;
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::TryFrom;
+use std::fmt;
 use std::iter::FromIterator;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicI32, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::Instant;
 
 use crate::adapter::ProviderStatus;
+use crate::call_tracer;
 use crate::chain::BlockFinality;
+use crate::extraction_pool::{ExtractionKind, ExtractionPool};
 use crate::trigger::LogRef;
 use crate::Chain;
 use crate::NodeCapabilities;
 use crate::{
     adapter::{
         EthGetLogsFilter, EthereumAdapter as EthereumAdapterTrait, EthereumBlockFilter,
-        EthereumCallFilter, EthereumContractCall, EthereumContractCallError, EthereumLogFilter,
-        ProviderEthRpcMetrics, SubgraphEthRpcMetrics,
+        EthereumCallFilter, EthereumContractCall, EthereumContractCallError,
+        EthereumContractCreationFilter, EthereumLogFilter, ProviderEthRpcMetrics,
+        SubgraphEthRpcMetrics,
     },
     transport::Transport,
     trigger::{EthereumBlockTriggerType, EthereumTrigger},
     TriggerFilter, ENV_VARS,
 };
 
+/// The result of a request that is shared by everybody who makes the same
+/// request while it is in flight
+type SharedResult<T, E> = Arc<Result<T, E>>;
+
+/// Errors that are handed to everybody who waits for the same request.
+/// Most of them can't be cloned; a shared error keeps everything about the
+/// original that callers act on
+trait ShareError {
+    fn share(&self) -> Self;
+}
+
+impl ShareError for TimeoutError<web3::error::Error> {
+    fn share(&self) -> Self {
+        match self {
+            TimeoutError::Inner(e) => TimeoutError::Inner(e.clone()),
+            TimeoutError::Elapsed => TimeoutError::Elapsed,
+        }
+    }
+}
+
+impl ShareError for IngestorError {
+    fn share(&self) -> Self {
+        match self {
+            IngestorError::BlockUnavailable(block_hash) => {
+                IngestorError::BlockUnavailable(*block_hash)
+            }
+            IngestorError::ReceiptUnavailable(block_hash, transaction_hash) => {
+                IngestorError::ReceiptUnavailable(*block_hash, *transaction_hash)
+            }
+            IngestorError::Unknown(e) => IngestorError::Unknown(anyhow!("{:#}", e)),
+        }
+    }
+}
+
+impl ShareError for Error {
+    fn share(&self) -> Self {
+        anyhow!("{:#}", self)
+    }
+}
+
+fn shared_result<T: Clone, E: ShareError>(res: SharedResult<T, E>) -> Result<T, E> {
+    Arc::try_unwrap(res).unwrap_or_else(|res| match res.as_ref() {
+        Ok(value) => Ok(value.clone()),
+        Err(e) => Err(e.share()),
+    })
+}
+
+/// The addresses by which `trace_filter` narrows down traces. Empty
+/// address lists request all traces
+#[derive(Clone, Debug)]
+enum TraceAddresses {
+    To(Vec<H160>),
+    From(Vec<H160>),
+}
+
+impl TraceAddresses {
+    fn is_empty(&self) -> bool {
+        match self {
+            TraceAddresses::To(addrs) | TraceAddresses::From(addrs) => addrs.is_empty(),
+        }
+    }
+
+    /// Whether `trace_filter` would return `trace` for these addresses
+    fn matches(&self, trace: &Trace) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        match (self, &trace.action) {
+            (TraceAddresses::To(addrs), Action::Call(call)) => addrs.contains(&call.to),
+            (TraceAddresses::To(addrs), Action::Create(_)) => match &trace.result {
+                Some(Res::Create(result)) => addrs.contains(&result.address),
+                _ => false,
+            },
+            (TraceAddresses::From(addrs), Action::Call(call)) => addrs.contains(&call.from),
+            (TraceAddresses::From(addrs), Action::Create(create)) => addrs.contains(&create.from),
+            _ => false,
+        }
+    }
+}
+
+/// Requests to a provider that are currently in flight. Deployments on the
+/// same network often make the same request at the same time, for example,
+/// when they all index the same block. Identical requests are only sent to
+/// the provider once, and everybody waits for the same response
+#[derive(Clone)]
+struct InFlight {
+    logs: HerdCache<SharedResult<Vec<Log>, TimeoutError<web3::error::Error>>>,
+    blocks: HerdCache<SharedResult<Arc<LightEthereumBlock>, Error>>,
+    receipts: HerdCache<SharedResult<Arc<TransactionReceipt>, IngestorError>>,
+}
+
+impl InFlight {
+    fn new(provider: &str) -> Self {
+        InFlight {
+            logs: HerdCache::new(format!("eth_{}_logs_in_flight", provider)),
+            blocks: HerdCache::new(format!("eth_{}_blocks_in_flight", provider)),
+            receipts: HerdCache::new(format!("eth_{}_receipts_in_flight", provider)),
+        }
+    }
+}
+
+impl CheapClone for InFlight {}
+
+impl fmt::Debug for InFlight {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InFlight").finish_non_exhaustive()
+    }
+}
+
+/// Remembers which addresses had code at which block for `has_code`. The
+/// results are not real `eth_call` results and must therefore never end up
+/// in the call cache, which is persistent and shared by all deployments.
+/// Instead, they are kept in memory, and the cache is simply emptied once
+/// it holds `CAPACITY` entries
+#[derive(Debug, Default)]
+struct CodeCache {
+    entries: Mutex<HashMap<(Address, H256), bool>>,
+}
+
+impl CodeCache {
+    const CAPACITY: usize = 10_000;
+
+    fn get(&self, address: Address, block_hash: H256) -> Option<bool> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(&(address, block_hash))
+            .copied()
+    }
+
+    fn set(&self, address: Address, block_hash: H256, has_code: bool) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= Self::CAPACITY {
+            entries.clear();
+        }
+        entries.insert((address, block_hash), has_code);
+    }
+}
+
+/// The largest step, i.e., one less than the number of blocks, that
+/// `eth_getLogs` requests to a provider use. It is lowered whenever the
+/// provider rejects a block range as too large, so that later scans don't
+/// keep retrying ranges it will not serve. Since such limits are often
+/// temporary, it doubles again after every `RECOVERY_SUCCESSES` successful
+/// requests that used the full step, up to the configured `ceiling`
+#[derive(Debug)]
+struct LogStepLimit {
+    step: AtomicI32,
+    successes: AtomicU32,
+    ceiling: BlockNumber,
+}
+
+impl LogStepLimit {
+    const RECOVERY_SUCCESSES: u32 = 10;
+
+    fn new(max_block_range: Option<BlockNumber>) -> Self {
+        let ceiling = max_block_range.map_or(BlockNumber::MAX, |range| range.max(1) - 1);
+        LogStepLimit {
+            step: AtomicI32::new(ceiling),
+            successes: AtomicU32::new(0),
+            ceiling,
+        }
+    }
+
+    fn get(&self) -> BlockNumber {
+        self.step.load(Ordering::SeqCst)
+    }
+
+    /// Don't use a step larger than `step` from now on. Returns whether
+    /// that lowered the limit
+    fn lower(&self, step: BlockNumber) -> bool {
+        self.successes.store(0, Ordering::SeqCst);
+        self.step.fetch_min(step, Ordering::SeqCst) > step
+    }
+
+    /// Record that a request with `step` succeeded. Returns the new limit
+    /// if that raised it
+    fn succeeded(&self, step: BlockNumber) -> Option<BlockNumber> {
+        let limit = self.get();
+        if step < limit || limit >= self.ceiling {
+            return None;
+        }
+        if self.successes.fetch_add(1, Ordering::SeqCst) + 1 < Self::RECOVERY_SUCCESSES {
+            return None;
+        }
+        self.successes.store(0, Ordering::SeqCst);
+        let raised = limit.saturating_mul(2).saturating_add(1).min(self.ceiling);
+        self.step
+            .compare_exchange(limit, raised, Ordering::SeqCst, Ordering::SeqCst)
+            .ok()
+            .map(|_| raised)
+    }
+}
+
+/// Limits for the requests sent to one provider. Public endpoints differ
+/// widely in what they accept, and limits that are not set fall back to
+/// the global settings from the environment
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ProviderLimits {
+    /// The largest number of blocks that one `eth_getLogs` request covers
+    pub max_block_range: Option<BlockNumber>,
+    /// The largest number of requests in one JSON-RPC batch
+    pub max_batch_size: Option<usize>,
+    /// How many seconds to wait for a response before retrying a request;
+    /// defaults to `GRAPH_ETHEREUM_JSON_RPC_TIMEOUT`
+    pub timeout_secs: Option<u64>,
+    /// The largest number of requests that are sent to the provider at the
+    /// same time
+    pub max_concurrent_requests: Option<usize>,
+    /// The largest number of requests per second, on average, that are
+    /// sent to the provider
+    pub max_requests_per_second: Option<u32>,
+}
+
+impl ProviderLimits {
+    fn timeout_secs(&self) -> u64 {
+        self.timeout_secs
+            .unwrap_or_else(|| ENV_VARS.json_rpc_timeout.as_secs())
+    }
+}
+
+/// What the latest check of a provider found out about it
+#[derive(Clone, Debug)]
+pub struct ProviderHealth {
+    /// The client version the provider reported, if it reported one
+    pub client_version: Option<String>,
+    /// Whether the provider said that it is still syncing
+    pub syncing: bool,
+    /// Whether the client version is one of
+    /// `GRAPH_ETHEREUM_BROKEN_CLIENT_VERSIONS`
+    pub broken_version: bool,
+    pub checked_at: DateTime<Utc>,
+}
+
+impl ProviderHealth {
+    pub fn is_healthy(&self) -> bool {
+        !self.syncing && !self.broken_version
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct EthereumAdapter {
     logger: Logger,
@@ -67,6 +315,15 @@ pub struct EthereumAdapter {
     metrics: Arc<ProviderEthRpcMetrics>,
     supports_eip_1898: bool,
     call_only: bool,
+    /// Whether to get traces with `debug_traceBlockByHash` instead of
+    /// `trace_filter`
+    debug_traces: bool,
+    max_log_step: Arc<LogStepLimit>,
+    in_flight: InFlight,
+    code_cache: Arc<CodeCache>,
+    limits: ProviderLimits,
+    /// The result of the latest health check, if there was one
+    health: Arc<RwLock<Option<ProviderHealth>>>,
 }
 
 impl CheapClone for EthereumAdapter {
@@ -78,6 +335,12 @@ impl CheapClone for EthereumAdapter {
             metrics: self.metrics.cheap_clone(),
             supports_eip_1898: self.supports_eip_1898,
             call_only: self.call_only,
+            debug_traces: self.debug_traces,
+            max_log_step: self.max_log_step.cheap_clone(),
+            in_flight: self.in_flight.cheap_clone(),
+            code_cache: self.code_cache.cheap_clone(),
+            limits: self.limits,
+            health: self.health.cheap_clone(),
         }
     }
 }
@@ -87,6 +350,19 @@ impl EthereumAdapter {
         self.call_only
     }
 
+    /// Get traces from this provider by tracing blocks with
+    /// `debug_traceBlockByHash` and geth's `callTracer` instead of with
+    /// `trace_filter`, for providers that do not support the latter
+    pub fn with_debug_traces(mut self, debug_traces: bool) -> Self {
+        self.debug_traces = debug_traces;
+        self
+    }
+
+    /// The URL of the WebSocket this adapter talks to, if it uses one
+    pub fn ws_url(&self) -> Option<&str> {
+        self.web3.transport().ws_url()
+    }
+
     pub async fn new(
         logger: Logger,
         provider: String,
@@ -94,6 +370,7 @@ impl EthereumAdapter {
         provider_metrics: Arc<ProviderEthRpcMetrics>,
         supports_eip_1898: bool,
         call_only: bool,
+        limits: ProviderLimits,
     ) -> Self {
         let web3 = Arc::new(Web3::new(transport));
 
@@ -106,6 +383,7 @@ impl EthereumAdapter {
             .map(|s| s.contains("TestRPC"))
             .unwrap_or(false);
 
+        let in_flight = InFlight::new(&provider);
         EthereumAdapter {
             logger,
             provider,
@@ -113,6 +391,123 @@ impl EthereumAdapter {
             metrics: provider_metrics,
             supports_eip_1898: supports_eip_1898 && !is_ganache,
             call_only,
+            debug_traces: false,
+            max_log_step: Arc::new(LogStepLimit::new(limits.max_block_range)),
+            in_flight,
+            code_cache: Arc::new(CodeCache::default()),
+            limits,
+            health: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// The result of the latest health check of this provider
+    pub fn health(&self) -> Option<ProviderHealth> {
+        self.health.read().unwrap().clone()
+    }
+
+    /// Whether the latest health check found a problem with this provider
+    pub fn is_unhealthy(&self) -> bool {
+        self.health
+            .read()
+            .unwrap()
+            .as_ref()
+            .map_or(false, |health| !health.is_healthy())
+    }
+
+    /// Ask the provider for its client version and whether it is syncing,
+    /// and remember the result. Providers that are syncing or run a
+    /// broken client version are reported as warnings
+    pub async fn check_health(&self) -> ProviderHealth {
+        self.check_health_against(&ENV_VARS.broken_client_versions)
+            .await
+    }
+
+    /// Like `check_health`, but with the given list of broken client
+    /// versions
+    async fn check_health_against(&self, broken_versions: &[String]) -> ProviderHealth {
+        let client_version = match self.web3.web3().client_version().await {
+            Ok(version) => Some(version),
+            Err(e) => {
+                debug!(self.logger, "Failed to get client version"; "error" => e.to_string());
+                None
+            }
+        };
+        let syncing = match self.web3.eth().syncing().await {
+            Ok(web3::types::SyncState::Syncing(_)) => true,
+            Ok(web3::types::SyncState::NotSyncing) => false,
+            Err(e) => {
+                debug!(self.logger, "Failed to get sync state"; "error" => e.to_string());
+                false
+            }
+        };
+        let broken_version = client_version.as_ref().map_or(false, |version| {
+            broken_versions
+                .iter()
+                .any(|broken| version.contains(broken.as_str()))
+        });
+        let health = ProviderHealth {
+            client_version,
+            syncing,
+            broken_version,
+            checked_at: Utc::now(),
+        };
+
+        let version = health.client_version.as_deref().unwrap_or("unknown");
+        if health.syncing {
+            warn!(self.logger, "Provider is still syncing and may return incomplete data";
+                  "provider" => &self.provider, "client_version" => version);
+            self.metrics
+                .set_status(ProviderStatus::Syncing, &self.provider);
+        } else if health.broken_version {
+            warn!(self.logger, "Provider runs a client version that is known to be broken";
+                  "provider" => &self.provider, "client_version" => version);
+            self.metrics
+                .set_status(ProviderStatus::BrokenVersion, &self.provider);
+        } else if self.is_unhealthy() {
+            info!(self.logger, "Provider is healthy again";
+                  "provider" => &self.provider, "client_version" => version);
+            self.metrics
+                .set_status(ProviderStatus::Working, &self.provider);
+        }
+
+        *self.health.write().unwrap() = Some(health.clone());
+        health
+    }
+
+    /// Check the health of this provider every
+    /// `GRAPH_ETHEREUM_PROVIDER_CHECK_INTERVAL` in the background
+    pub fn start_health_checks(&self) {
+        let interval = ENV_VARS.provider_check_interval;
+        if interval.is_zero() {
+            return;
+        }
+        let adapter = self.cheap_clone();
+        graph::spawn(async move {
+            loop {
+                adapter.check_health().await;
+                graph::prelude::tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    fn max_log_step(&self) -> BlockNumber {
+        self.max_log_step.get()
+    }
+
+    /// Remember that `eth_getLogs` requests to this provider should not use
+    /// a step larger than `step`
+    fn lower_max_log_step(&self, logger: &Logger, step: BlockNumber) {
+        if self.max_log_step.lower(step) {
+            info!(logger, "Lowering maximum block range for eth_getLogs requests";
+                  "provider" => &self.provider, "max_range" => step + 1);
+        }
+    }
+
+    /// Remember that an `eth_getLogs` request with `step` succeeded
+    fn log_step_succeeded(&self, logger: &Logger, step: BlockNumber) {
+        if let Some(step) = self.max_log_step.succeeded(step) {
+            info!(logger, "Raising maximum block range for eth_getLogs requests";
+                  "provider" => &self.provider, "max_range" => step.saturating_add(1));
         }
     }
 
@@ -122,27 +517,43 @@ impl EthereumAdapter {
         subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
         from: BlockNumber,
         to: BlockNumber,
-        addresses: Vec<H160>,
+        addresses: TraceAddresses,
     ) -> Result<Vec<Trace>, Error> {
         assert!(!self.call_only);
 
+        if self.debug_traces {
+            // Trace several blocks at once; `buffered` keeps the traces in
+            // block order
+            let eth = self.clone();
+            let traces: Vec<Vec<Trace>> = futures03::stream::iter(from..=to)
+                .map(|number| {
+                    eth.clone()
+                        .block_call_traces(logger.clone(), subgraph_metrics.clone(), number)
+                })
+                .buffered(ENV_VARS.block_batch_size)
+                .try_collect()
+                .await?;
+            return Ok(traces
+                .into_iter()
+                .flatten()
+                .filter(|trace| addresses.matches(trace))
+                .collect());
+        }
+
         let eth = self.clone();
         let retry_log_message =
             format!("trace_filter RPC call for block range: [{}..{}]", from, to);
         retry(retry_log_message, &logger)
             .limit(ENV_VARS.request_retries)
-            .timeout_secs(ENV_VARS.json_rpc_timeout.as_secs())
+            .timeout_secs(self.limits.timeout_secs())
             .run(move || {
-                let trace_filter: TraceFilter = match addresses.len() {
-                    0 => TraceFilterBuilder::default()
-                        .from_block(from.into())
-                        .to_block(to.into())
-                        .build(),
-                    _ => TraceFilterBuilder::default()
-                        .from_block(from.into())
-                        .to_block(to.into())
-                        .to_address(addresses.clone())
-                        .build(),
+                let builder = TraceFilterBuilder::default()
+                    .from_block(from.into())
+                    .to_block(to.into());
+                let trace_filter: TraceFilter = match &addresses {
+                    addrs if addrs.is_empty() => builder.build(),
+                    TraceAddresses::To(addrs) => builder.to_address(addrs.clone()).build(),
+                    TraceAddresses::From(addrs) => builder.from_address(addrs.clone()).build(),
                 };
 
                 let eth = eth.cheap_clone();
@@ -209,6 +620,83 @@ impl EthereumAdapter {
             .await
     }
 
+    /// The traces of block `number`, made from the call traces of its
+    /// transactions; see `call_tracer`
+    async fn block_call_traces(
+        self,
+        logger: Logger,
+        subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
+        number: BlockNumber,
+    ) -> Result<Vec<Trace>, Error> {
+        let eth = self.clone();
+        let retry_log_message = format!("debug_traceBlockByHash RPC call for block {}", number);
+        retry(retry_log_message, &logger)
+            .limit(ENV_VARS.request_retries)
+            .timeout_secs(self.limits.timeout_secs())
+            .run(move || {
+                let eth = eth.cheap_clone();
+                let logger = logger.clone();
+                let subgraph_metrics = subgraph_metrics.clone();
+                let provider_metrics = eth.metrics.clone();
+                let provider = eth.provider.clone();
+
+                async move {
+                    let start = Instant::now();
+                    let result: Result<_, Error> = async {
+                        let block = eth
+                            .web3
+                            .eth()
+                            .block(BlockId::Number(Web3BlockNumber::Number(U64::from(
+                                number as u64,
+                            ))))
+                            .await?
+                            .ok_or_else(|| {
+                                anyhow!("Ethereum node could not find block {}", number)
+                            })?;
+                        let hash = block
+                            .hash
+                            .ok_or_else(|| anyhow!("block {} has no hash", number))?;
+                        let transactions = web3::Transport::execute(
+                            eth.web3.transport(),
+                            "debug_traceBlockByHash",
+                            vec![
+                                json::to_value(hash)?,
+                                json::json!({ "tracer": "callTracer" }),
+                            ],
+                        )
+                        .await?;
+                        call_tracer::traces(
+                            number,
+                            hash,
+                            &block.transactions,
+                            json::from_value(transactions)?,
+                        )
+                    }
+                    .await;
+
+                    let elapsed = start.elapsed().as_secs_f64();
+                    provider_metrics.observe_request(elapsed, "debug_traceBlockByHash", &provider);
+                    subgraph_metrics.observe_request(elapsed, "debug_traceBlockByHash", &provider);
+                    if let Err(e) = &result {
+                        provider_metrics.add_error("debug_traceBlockByHash", &provider);
+                        subgraph_metrics.add_error("debug_traceBlockByHash", &provider);
+                        debug!(logger, "Error tracing block {} error = {:#}", number, e);
+                    }
+                    result
+                }
+            })
+            .map_err(move |e| {
+                e.into_inner().unwrap_or_else(move || {
+                    anyhow!(
+                        "Ethereum node took too long to respond to debug_traceBlockByHash \
+                         (block {})",
+                        number
+                    )
+                })
+            })
+            .await
+    }
+
     async fn logs_with_sigs(
         &self,
         logger: Logger,
@@ -230,7 +718,7 @@ impl EthereumAdapter {
                     .any(|f| e.to_string().contains(f)),
             })
             .limit(ENV_VARS.request_retries)
-            .timeout_secs(ENV_VARS.json_rpc_timeout.as_secs())
+            .timeout_secs(self.limits.timeout_secs())
             .run(move || {
                 let eth_adapter = eth_adapter.cheap_clone();
                 let subgraph_metrics = subgraph_metrics.clone();
@@ -246,7 +734,12 @@ impl EthereumAdapter {
                         .from_block(from.into())
                         .to_block(to.into())
                         .address(filter.contracts.clone())
-                        .topics(Some(filter.event_signatures.clone()), None, None, None)
+                        .topics(
+                            Some(filter.event_signatures.clone()),
+                            filter.topics.topic1.clone(),
+                            filter.topics.topic2.clone(),
+                            filter.topics.topic3.clone(),
+                        )
                         .build();
 
                     // Request logs from client
@@ -264,13 +757,80 @@ impl EthereumAdapter {
             .await
     }
 
+    /// Like `logs_with_sigs`, but identical requests that are in flight at
+    /// the same time, e.g., for different deployments, share one
+    /// `eth_getLogs` call. Deployments that wait for the call of another
+    /// deployment record it in their metrics with the time they waited
+    async fn shared_logs_with_sigs(
+        &self,
+        logger: Logger,
+        subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
+        from: BlockNumber,
+        to: BlockNumber,
+        filter: Arc<EthGetLogsFilter>,
+        too_many_logs_fingerprints: &'static [&'static str],
+    ) -> Result<Vec<Log>, TimeoutError<web3::error::Error>> {
+        let start = Instant::now();
+        let key = logs_key(from, to, &filter);
+        let eth = self.cheap_clone();
+        let call_logger = logger.cheap_clone();
+        let call_metrics = subgraph_metrics.cheap_clone();
+        let call = async move {
+            let res = eth
+                .logs_with_sigs(
+                    call_logger,
+                    call_metrics,
+                    from,
+                    to,
+                    filter,
+                    too_many_logs_fingerprints,
+                )
+                .await;
+            Arc::new(res)
+        };
+        let (res, cached) = self.in_flight.logs.cached_query(key, call, &logger).await;
+        if cached {
+            let elapsed = start.elapsed().as_secs_f64();
+            subgraph_metrics.observe_request(elapsed, "eth_getLogs", &self.provider);
+            if res.is_err() {
+                subgraph_metrics.add_error("eth_getLogs", &self.provider);
+            }
+        }
+        shared_result(res)
+    }
+
+    /// Fetch the receipt for a transaction; identical requests that are in
+    /// flight at the same time share one call
+    async fn shared_transaction_receipt(
+        &self,
+        transaction_hash: H256,
+        block_hash: H256,
+        logger: Logger,
+    ) -> Result<Arc<TransactionReceipt>, IngestorError> {
+        let key = crypto_stable_hash(&format!("{:?}:{:?}", block_hash, transaction_hash));
+        let call = fetch_transaction_receipt_with_retry(
+            self.web3.cheap_clone(),
+            transaction_hash,
+            block_hash,
+            logger.cheap_clone(),
+            self.limits,
+        )
+        .map(Arc::new);
+        let (res, _) = self
+            .in_flight
+            .receipts
+            .cached_query(key, call, &logger)
+            .await;
+        shared_result(res)
+    }
+
     fn trace_stream(
         self,
         logger: &Logger,
         subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
         from: BlockNumber,
         to: BlockNumber,
-        addresses: Vec<H160>,
+        addresses: TraceAddresses,
     ) -> impl Stream<Item = Trace, Error = Error> + Send {
         if from > to {
             panic!(
@@ -325,13 +885,26 @@ impl EthereumAdapter {
         to: BlockNumber,
         filter: EthGetLogsFilter,
     ) -> DynTryFuture<'static, Vec<Log>, Error> {
-        // Codes returned by Ethereum node providers if an eth_getLogs request is too heavy.
-        // The first one is for Infura when it hits the log limit, the rest for Alchemy timeouts.
+        // Codes and messages returned by Ethereum node providers if an eth_getLogs request is
+        // too heavy. The first one is for Infura when it hits the log limit, the next two for
+        // Alchemy timeouts, followed by the messages other providers use for result and range
+        // limits. The last one is our own timeout for requests that take too long.
         const TOO_MANY_LOGS_FINGERPRINTS: &[&str] = &[
             "ServerError(-32005)",
             "503 Service Unavailable",
             "ServerError(-32000)",
+            "query returned more than",
+            "response size exceeded",
+            "response too large",
+            "exceed maximum block range",
+            "block range is too wide",
+            "Timeout elapsed",
         ];
+        // The errors that say that the provider does not serve ranges this large. Only these
+        // lower the step for later scans; the others might just be caused by a busy provider
+        // or by this filter matching many logs
+        const RANGE_TOO_LARGE_FINGERPRINTS: &[&str] =
+            &["exceed maximum block range", "block range is too wide"];
 
         if from > to {
             panic!(
@@ -349,6 +922,8 @@ impl EthereumAdapter {
             false => to - from,
             true => (to - from).min(ENV_VARS.max_event_only_range - 1),
         };
+        // Don't bother with ranges that this provider has rejected before
+        let step = step.min(self.max_log_step());
 
         // Typically this will loop only once and fetch the entire range in one request. But if the
         // node returns an error that signifies the request is to heavy to process, the range will
@@ -370,7 +945,7 @@ impl EthereumAdapter {
                     "Requesting logs for blocks [{}, {}], {}", start, end, filter
                 );
                 let res = eth
-                    .logs_with_sigs(
+                    .shared_logs_with_sigs(
                         logger.cheap_clone(),
                         subgraph_metrics.cheap_clone(),
                         start,
@@ -381,9 +956,8 @@ impl EthereumAdapter {
                     .await;
 
                 match res {
-                    Err(e) => {
-                        let string_err = e.to_string();
-
+                    Err(err) => {
+                        let string_err = err.to_string();
                         // If the step is already 0, the request is too heavy even for a single
                         // block. We hope this never happens, but if it does, make sure to error.
                         if TOO_MANY_LOGS_FINGERPRINTS
@@ -396,13 +970,22 @@ impl EthereumAdapter {
                             let new_step = step / 10;
                             debug!(logger, "Reducing block range size to scan for events";
                                                "new_size" => new_step + 1);
+                            if RANGE_TOO_LARGE_FINGERPRINTS
+                                .iter()
+                                .any(|f| string_err.contains(f))
+                            {
+                                eth.lower_max_log_step(&logger, new_step);
+                            }
                             Ok(Some((vec![], (start, new_step))))
                         } else {
                             warn!(logger, "Unexpected RPC error"; "error" => &string_err);
                             Err(anyhow!("{}", string_err))
                         }
                     }
-                    Ok(logs) => Ok(Some((logs, (end + 1, step)))),
+                    Ok(logs) => {
+                        eth.log_step_succeeded(&logger, step);
+                        Ok(Some((logs, (end + 1, step))))
+                    }
                 }
             }
         })
@@ -435,7 +1018,7 @@ impl EthereumAdapter {
                 Err(_) => true,
             })
             .limit(ENV_VARS.request_retries)
-            .timeout_secs(ENV_VARS.json_rpc_timeout.as_secs())
+            .timeout_secs(self.limits.timeout_secs())
             .run(move || {
                 let call_data = call_data.clone();
                 let web3 = web3.cheap_clone();
@@ -569,6 +1152,38 @@ impl EthereumAdapter {
             .compat()
     }
 
+    /// Fetch the code deployed at `address` as of `block_ptr` with `eth_getCode`.
+    fn code(
+        &self,
+        logger: &Logger,
+        address: Address,
+        block_ptr: BlockPtr,
+    ) -> impl Future<Item = Bytes, Error = EthereumContractCallError> + Send {
+        let web3 = self.web3.clone();
+        let logger = Logger::new(logger, o!("provider" => self.provider.clone()));
+
+        // `eth_getCode` only takes a block number, not a hash. A reorg while
+        // the request is in flight is caught by the usual reorg handling since
+        // the block being processed will be reverted.
+        let block_id = Web3BlockNumber::Number(block_ptr.number.into());
+        let retry_log_message = format!("eth_getCode RPC call for block {}", block_ptr);
+        retry(retry_log_message, &logger)
+            .limit(ENV_VARS.request_retries)
+            .timeout_secs(self.limits.timeout_secs())
+            .run(move || {
+                let web3 = web3.cheap_clone();
+                async move {
+                    web3.eth()
+                        .code(address, Some(block_id))
+                        .await
+                        .map_err(EthereumContractCallError::Web3Error)
+                }
+            })
+            .map_err(|e| e.into_inner().unwrap_or(EthereumContractCallError::Timeout))
+            .boxed()
+            .compat()
+    }
+
     /// Request blocks by hash through JSON-RPC.
     fn load_blocks_rpc(
         &self,
@@ -576,12 +1191,16 @@ impl EthereumAdapter {
         ids: Vec<H256>,
     ) -> impl Stream<Item = Arc<LightEthereumBlock>, Error = Error> + Send {
         let web3 = self.web3.clone();
+        let in_flight = self.in_flight.cheap_clone();
+        let timeout_secs = self.limits.timeout_secs();
 
         stream::iter_ok::<_, Error>(ids.into_iter().map(move |hash| {
             let web3 = web3.clone();
-            retry(format!("load block {}", hash), &logger)
+            let in_flight = in_flight.cheap_clone();
+            let logger = logger.cheap_clone();
+            let call = retry(format!("load block {}", hash), &logger)
                 .limit(ENV_VARS.request_retries)
-                .timeout_secs(ENV_VARS.json_rpc_timeout.as_secs())
+                .timeout_secs(timeout_secs)
                 .run(move || {
                     Box::pin(web3.eth().block_with_txs(BlockId::Hash(hash)))
                         .compat()
@@ -593,9 +1212,17 @@ impl EthereumAdapter {
                         })
                         .compat()
                 })
-                .boxed()
-                .compat()
-                .from_err()
+                .map(|res| Arc::new(res.map_err(Error::from)));
+
+            // Deployments that need the same block at the same time share
+            // the request for it
+            async move {
+                let key = crypto_stable_hash(&format!("{:?}", hash));
+                let (res, _) = in_flight.blocks.cached_query(key, call, &logger).await;
+                shared_result(res)
+            }
+            .boxed()
+            .compat()
         }))
         .buffered(ENV_VARS.block_batch_size)
     }
@@ -609,12 +1236,13 @@ impl EthereumAdapter {
         block_nums: Vec<BlockNumber>,
     ) -> impl Stream<Item = BlockPtr, Error = Error> + Send {
         let web3 = self.web3.clone();
+        let timeout_secs = self.limits.timeout_secs();
 
         stream::iter_ok::<_, Error>(block_nums.into_iter().map(move |block_num| {
             let web3 = web3.clone();
             retry(format!("load block ptr {}", block_num), &logger)
                 .no_limit()
-                .timeout_secs(ENV_VARS.json_rpc_timeout.as_secs())
+                .timeout_secs(timeout_secs)
                 .run(move || {
                     let web3 = web3.clone();
                     async move {
@@ -724,15 +1352,51 @@ impl EthereumAdapter {
         }
 
         Box::new(
-            eth.trace_stream(logger, subgraph_metrics, from, to, addresses)
+            eth.trace_stream(
+                logger,
+                subgraph_metrics,
+                from,
+                to,
+                TraceAddresses::To(addresses),
+            )
+            .filter_map(|trace| EthereumCall::try_from_trace(&trace))
+            .filter(move |call| {
+                // `trace_filter` can only filter by calls `to` an address and
+                // a block range. Since subgraphs are subscribing to calls
+                // for a specific contract function an additional filter needs
+                // to be applied
+                call_filter.matches(call)
+            }),
+        )
+    }
+
+    pub(crate) fn contract_creations_in_block_range<'a>(
+        &self,
+        logger: &Logger,
+        subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
+        from: BlockNumber,
+        to: BlockNumber,
+        creation_filter: &'a EthereumContractCreationFilter,
+    ) -> Box<dyn Stream<Item = EthereumCall, Error = Error> + Send + 'a> {
+        if creation_filter.is_empty() {
+            return Box::new(stream::empty());
+        }
+
+        // Handlers that only match on the init code need to look at all
+        // traces since there is no way to filter them on the node
+        let addresses = creation_filter.deployers().unwrap_or_default();
+
+        Box::new(
+            self.clone()
+                .trace_stream(
+                    logger,
+                    subgraph_metrics,
+                    from,
+                    to,
+                    TraceAddresses::From(addresses),
+                )
                 .filter_map(|trace| EthereumCall::try_from_trace(&trace))
-                .filter(move |call| {
-                    // `trace_filter` can only filter by calls `to` an address and
-                    // a block range. Since subgraphs are subscribing to calls
-                    // for a specific contract function an additional filter needs
-                    // to be applied
-                    call_filter.matches(call)
-                }),
+                .filter(move |call| creation_filter.matches(call)),
         )
     }
 
@@ -814,14 +1478,13 @@ impl EthereumAdapter {
         block_hash: H256,
     ) -> Result<Vec<EthereumCall>, Error> {
         let eth = self.clone();
-        let addresses = Vec::new();
         let traces = eth
             .trace_stream(
                 logger,
                 subgraph_metrics.clone(),
                 block_number,
                 block_number,
-                addresses,
+                TraceAddresses::To(vec![]),
             )
             .collect()
             .compat()
@@ -829,8 +1492,10 @@ impl EthereumAdapter {
 
         // `trace_stream` returns all of the traces for the block, and this
         // includes a trace for the block reward which every block should have.
-        // If there are no traces something has gone wrong.
-        if traces.is_empty() {
+        // If there are no traces something has gone wrong. Call traces do
+        // not have a trace for the block reward, so that blocks without
+        // transactions have no traces
+        if traces.is_empty() && !self.debug_traces {
             return Err(anyhow!(
                 "Trace stream returned no traces for block: number = `{}`, hash = `{}`",
                 block_number,
@@ -842,7 +1507,11 @@ impl EthereumAdapter {
         // all the traces for the block, we need to ensure that the
         // block hash for the traces is equal to the desired block hash.
         // Assume all traces are for the same block.
-        if traces.iter().nth(0).unwrap().block_hash != block_hash {
+        if traces
+            .iter()
+            .nth(0)
+            .map_or(false, |trace| trace.block_hash != block_hash)
+        {
             return Err(anyhow!(
                 "Trace stream returned traces for an unexpected block: \
                          number = `{}`, hash = `{}`",
@@ -884,13 +1553,100 @@ impl EthereumAdapter {
         Box::new(self.load_block_ptrs_rpc(logger, blocks).collect())
     }
 
+    /// Execute `call` and return the raw, undecoded output, going through
+    /// `cache` first. An empty response is reported as a revert.
+    pub(crate) fn raw_contract_call(
+        &self,
+        logger: &Logger,
+        call: EthereumContractCall,
+        cache: Arc<dyn EthereumCallCache>,
+    ) -> Box<dyn Future<Item = Vec<u8>, Error = EthereumContractCallError> + Send> {
+        // Emit custom error for type mismatches.
+        for (token, kind) in call
+            .args
+            .iter()
+            .zip(call.function.inputs.iter().map(|p| &p.kind))
+        {
+            if !token.type_check(kind) {
+                return Box::new(future::err(EthereumContractCallError::TypeError(
+                    token.clone(),
+                    kind.clone(),
+                )));
+            }
+        }
+
+        // Encode the call parameters according to the ABI
+        let call_data = match call.function.encode_input(&call.args) {
+            Ok(data) => data,
+            Err(e) => return Box::new(future::err(EthereumContractCallError::EncodingError(e))),
+        };
+
+        debug!(logger, "eth_call";
+            "address" => hex::encode(call.address),
+            "data" => hex::encode(&call_data),
+            "block_hash" => call.block_ptr.hash_hex(),
+            "block_number" => call.block_ptr.block_number()
+        );
+
+        // Check if we have it cached, if not do the call and cache.
+        Box::new(
+            match cache
+                .get_call(call.address, &call_data, call.block_ptr.clone())
+                .map_err(|e| error!(logger, "call cache get error"; "error" => e.to_string()))
+                .ok()
+                .flatten()
+            {
+                Some(result) => {
+                    Box::new(future::ok(result)) as Box<dyn Future<Item = _, Error = _> + Send>
+                }
+                None => {
+                    let cache = cache.clone();
+                    let call = call.clone();
+                    let logger = logger.clone();
+                    Box::new(
+                        self.call(
+                            logger.clone(),
+                            call.address,
+                            Bytes(call_data.clone()),
+                            call.block_ptr.clone(),
+                            call.gas,
+                        )
+                        .map(move |result| {
+                            // Don't block handler execution on writing to the cache.
+                            let for_cache = result.0.clone();
+                            let _ = graph::spawn_blocking_allow_panic(move || {
+                                cache
+                                    .set_call(call.address, &call_data, call.block_ptr, &for_cache)
+                                    .map_err(|e| {
+                                        error!(logger, "call cache set error";
+                                                   "error" => e.to_string())
+                                    })
+                            });
+                            result.0
+                        }),
+                    )
+                }
+            }
+            .and_then(|output| {
+                if output.is_empty() {
+                    // We got a `0x` response. For old Geth, this can mean a revert. It can also be
+                    // that the contract actually returned an empty response. A view call is meant
+                    // to return something, so we treat empty responses the same as reverts.
+                    Err(EthereumContractCallError::Revert("empty response".into()))
+                } else {
+                    Ok(output)
+                }
+            }),
+        )
+    }
+
     pub async fn chain_id(&self) -> Result<u64, Error> {
         let logger = self.logger.clone();
         let web3 = self.web3.clone();
         u64::try_from(
             retry("chain_id RPC call", &logger)
                 .no_limit()
-                .timeout_secs(ENV_VARS.json_rpc_timeout.as_secs())
+                .timeout_secs(self.limits.timeout_secs())
                 .run(move || {
                     let web3 = web3.cheap_clone();
                     async move { web3.eth().chain_id().await }
@@ -994,7 +1750,7 @@ impl EthereumAdapterTrait for EthereumAdapter {
         Box::new(
             retry("eth_getBlockByNumber(latest) no txs RPC call", logger)
                 .no_limit()
-                .timeout_secs(ENV_VARS.json_rpc_timeout.as_secs())
+                .timeout_secs(self.limits.timeout_secs())
                 .run(move || {
                     let web3 = web3.cheap_clone();
                     async move {
@@ -1028,7 +1784,7 @@ impl EthereumAdapterTrait for EthereumAdapter {
         Box::new(
             retry("eth_getBlockByNumber(latest) with txs RPC call", logger)
                 .no_limit()
-                .timeout_secs(ENV_VARS.json_rpc_timeout.as_secs())
+                .timeout_secs(self.limits.timeout_secs())
                 .run(move || {
                     let web3 = web3.cheap_clone();
                     async move {
@@ -1085,7 +1841,7 @@ impl EthereumAdapterTrait for EthereumAdapter {
         Box::new(
             retry(retry_log_message, &logger)
                 .limit(ENV_VARS.request_retries)
-                .timeout_secs(ENV_VARS.json_rpc_timeout.as_secs())
+                .timeout_secs(self.limits.timeout_secs())
                 .run(move || {
                     Box::pin(web3.eth().block_with_txs(BlockId::Hash(block_hash)))
                         .compat()
@@ -1116,7 +1872,7 @@ impl EthereumAdapterTrait for EthereumAdapter {
         Box::new(
             retry(retry_log_message, &logger)
                 .no_limit()
-                .timeout_secs(ENV_VARS.json_rpc_timeout.as_secs())
+                .timeout_secs(self.limits.timeout_secs())
                 .run(move || {
                     let web3 = web3.cheap_clone();
                     async move {
@@ -1159,9 +1915,11 @@ impl EthereumAdapterTrait for EthereumAdapter {
             })));
         }
         let hashes: Vec<_> = block.transactions.iter().map(|txn| txn.hash).collect();
+        let limits = self.limits;
         let receipts_future = if ENV_VARS.fetch_receipts_in_batches {
             // Deprecated batching retrieval of transaction receipts.
-            fetch_transaction_receipts_in_batch_with_retry(web3, hashes, block_hash, logger).boxed()
+            fetch_transaction_receipts_in_batch_with_retry(web3, hashes, block_hash, logger, limits)
+                .boxed()
         } else {
             let hash_stream = graph::tokio_stream::iter(hashes);
             let receipt_stream = graph::tokio_stream::StreamExt::map(hash_stream, move |tx_hash| {
@@ -1170,6 +1928,7 @@ impl EthereumAdapterTrait for EthereumAdapter {
                     tx_hash,
                     block_hash,
                     logger.cheap_clone(),
+                    limits,
                 )
             })
             .buffered(ENV_VARS.block_ingestor_max_concurrent_json_rpc_calls);
@@ -1223,7 +1982,7 @@ impl EthereumAdapterTrait for EthereumAdapter {
         Box::new(
             retry(retry_log_message, logger)
                 .no_limit()
-                .timeout_secs(ENV_VARS.json_rpc_timeout.as_secs())
+                .timeout_secs(self.limits.timeout_secs())
                 .run(move || {
                     let web3 = web3.cheap_clone();
                     async move {
@@ -1253,90 +2012,44 @@ impl EthereumAdapterTrait for EthereumAdapter {
         call: EthereumContractCall,
         cache: Arc<dyn EthereumCallCache>,
     ) -> Box<dyn Future<Item = Vec<Token>, Error = EthereumContractCallError> + Send> {
-        // Emit custom error for type mismatches.
-        for (token, kind) in call
-            .args
-            .iter()
-            .zip(call.function.inputs.iter().map(|p| &p.kind))
-        {
-            if !token.type_check(kind) {
-                return Box::new(future::err(EthereumContractCallError::TypeError(
-                    token.clone(),
-                    kind.clone(),
-                )));
-            }
-        }
-
-        // Encode the call parameters according to the ABI
-        let call_data = match call.function.encode_input(&call.args) {
-            Ok(data) => data,
-            Err(e) => return Box::new(future::err(EthereumContractCallError::EncodingError(e))),
-        };
-
-        debug!(logger, "eth_call";
-            "address" => hex::encode(call.address),
-            "data" => hex::encode(&call_data),
-            "block_hash" => call.block_ptr.hash_hex(),
-            "block_number" => call.block_ptr.block_number()
-        );
-
-        // Check if we have it cached, if not do the call and cache.
+        let function = call.function.clone();
         Box::new(
-            match cache
-                .get_call(call.address, &call_data, call.block_ptr.clone())
-                .map_err(|e| error!(logger, "call cache get error"; "error" => e.to_string()))
-                .ok()
-                .flatten()
-            {
-                Some(result) => {
-                    Box::new(future::ok(result)) as Box<dyn Future<Item = _, Error = _> + Send>
-                }
-                None => {
-                    let cache = cache.clone();
-                    let call = call.clone();
-                    let logger = logger.clone();
-                    Box::new(
-                        self.call(
-                            logger.clone(),
-                            call.address,
-                            Bytes(call_data.clone()),
-                            call.block_ptr.clone(),
-                            call.gas,
-                        )
-                        .map(move |result| {
-                            // Don't block handler execution on writing to the cache.
-                            let for_cache = result.0.clone();
-                            let _ = graph::spawn_blocking_allow_panic(move || {
-                                cache
-                                    .set_call(call.address, &call_data, call.block_ptr, &for_cache)
-                                    .map_err(|e| {
-                                        error!(logger, "call cache set error";
-                                                   "error" => e.to_string())
-                                    })
-                            });
-                            result.0
-                        }),
-                    )
-                }
-            }
-            // Decode the return values according to the ABI
-            .and_then(move |output| {
-                if output.is_empty() {
-                    // We got a `0x` response. For old Geth, this can mean a revert. It can also be
-                    // that the contract actually returned an empty response. A view call is meant
-                    // to return something, so we treat empty responses the same as reverts.
-                    Err(EthereumContractCallError::Revert("empty response".into()))
-                } else {
+            self.raw_contract_call(logger, call, cache)
+                // Decode the return values according to the ABI
+                .and_then(move |output| {
                     // Decode failures are reverts. The reasoning is that if Solidity fails to
                     // decode an argument, that's a revert, so the same goes for the output.
-                    call.function.decode_output(&output).map_err(|e| {
+                    function.decode_output(&output).map_err(|e| {
                         EthereumContractCallError::Revert(format!("failed to decode output: {}", e))
                     })
-                }
-            }),
+                }),
         )
     }
 
+    fn has_code(
+        &self,
+        logger: &Logger,
+        address: Address,
+        block_ptr: BlockPtr,
+    ) -> Box<dyn Future<Item = bool, Error = EthereumContractCallError> + Send> {
+        debug!(logger, "eth_getCode";
+            "address" => hex::encode(address),
+            "block_hash" => block_ptr.hash_hex(),
+            "block_number" => block_ptr.block_number()
+        );
+
+        let block_hash = block_ptr.hash_as_h256();
+        if let Some(has_code) = self.code_cache.get(address, block_hash) {
+            return Box::new(future::ok(has_code));
+        }
+        let code_cache = self.code_cache.cheap_clone();
+        Box::new(self.code(logger, address, block_ptr).map(move |code| {
+            let has_code = !code.0.is_empty();
+            code_cache.set(address, block_hash, has_code);
+            has_code
+        }))
+    }
+
     /// Load Ethereum blocks in bulk, returning results as they come back as a Stream.
     async fn load_blocks(
         &self,
@@ -1404,8 +2117,10 @@ impl EthereumAdapterTrait for EthereumAdapter {
 /// node is aware of.
 pub(crate) async fn blocks_with_triggers(
     adapter: Arc<EthereumAdapter>,
+    log_adapters: Vec<Arc<EthereumAdapter>>,
     logger: Logger,
     chain_store: Arc<dyn ChainStore>,
+    extraction_pool: &ExtractionPool,
     subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
     from: BlockNumber,
     to: BlockNumber,
@@ -1450,7 +2165,9 @@ pub(crate) async fn blocks_with_triggers(
     if !filter.log.is_empty() {
         let logs_future = get_logs_and_transactions(
             &eth,
+            log_adapters,
             &logger,
+            extraction_pool,
             subgraph_metrics.clone(),
             from,
             to,
@@ -1467,11 +2184,33 @@ pub(crate) async fn blocks_with_triggers(
             .map(Arc::new)
             .map(EthereumTrigger::Call)
             .collect()
-            .compat()
+            .compat();
+        let calls_future = extraction_pool
+            .run(ExtractionKind::Traces, calls_future)
             .boxed();
         trigger_futs.push(calls_future)
     }
 
+    // Scan for contract creations
+    if !filter.contract_creation.is_empty() {
+        let creations_future = eth
+            .contract_creations_in_block_range(
+                &logger,
+                subgraph_metrics.clone(),
+                from,
+                to,
+                &filter.contract_creation,
+            )
+            .map(Arc::new)
+            .map(EthereumTrigger::Call)
+            .collect()
+            .compat();
+        let creations_future = extraction_pool
+            .run(ExtractionKind::Traces, creations_future)
+            .boxed();
+        trigger_futs.push(creations_future)
+    }
+
     if !filter.block.contract_addresses.is_empty() {
         // To determine which blocks include a call to addresses
         // in the block filter, transform the `block_filter` into
@@ -1485,7 +2224,9 @@ pub(crate) async fn blocks_with_triggers(
                 )
             })
             .collect()
-            .compat()
+            .compat();
+        let block_future = extraction_pool
+            .run(ExtractionKind::Traces, block_future)
             .boxed();
         trigger_futs.push(block_future)
     }
@@ -1579,6 +2320,55 @@ pub(crate) async fn blocks_with_triggers(
     Ok(blocks)
 }
 
+/// Find the triggers in `blocks`, the blocks from `from` to `to` with
+/// their transaction receipts as the chain store has them, without asking
+/// an Ethereum node. Return `None` if any of the blocks is missing its
+/// receipts, or if `filter` requires traces since the chain store has none
+pub(crate) fn blocks_with_triggers_from_cache(
+    logger: &Logger,
+    blocks: Vec<json::Value>,
+    to: BlockNumber,
+    filter: &TriggerFilter,
+) -> Result<Option<Vec<BlockWithTriggers<crate::Chain>>>, Error> {
+    if filter.requires_traces() {
+        return Ok(None);
+    }
+
+    let mut result = vec![];
+    for data in blocks {
+        let ethereum_block: EthereumBlock = match json::from_value(data) {
+            Ok(block) => block,
+            // Light blocks without receipts
+            Err(_) => return Ok(None),
+        };
+        if ethereum_block.transaction_receipts.len() != ethereum_block.block.transactions.len() {
+            return Ok(None);
+        }
+
+        let block = EthereumBlockWithCalls {
+            ethereum_block,
+            calls: Some(vec![]),
+        };
+        let mut triggers: Vec<_> = parse_log_triggers(&filter.log, &block.ethereum_block)
+            .into_iter()
+            .filter(|trigger| match trigger {
+                EthereumTrigger::Log(log) => filter.log.matches(log.log()),
+                _ => true,
+            })
+            .collect();
+        triggers.append(&mut parse_block_triggers(&filter.block, &block));
+
+        if !triggers.is_empty() || block.ethereum_block.block.number() == to {
+            result.push(BlockWithTriggers::new(
+                BlockFinality::NonFinal(block),
+                triggers,
+                logger,
+            ));
+        }
+    }
+    Ok(Some(result))
+}
+
 pub(crate) async fn get_calls(
     client: &Arc<ChainClient<Chain>>,
     logger: Logger,
@@ -1667,6 +2457,31 @@ pub(crate) fn parse_call_triggers(
     }
 }
 
+pub(crate) fn parse_contract_creation_triggers(
+    creation_filter: &EthereumContractCreationFilter,
+    block: &EthereumBlockWithCalls,
+) -> anyhow::Result<Vec<EthereumTrigger>> {
+    if creation_filter.is_empty() {
+        return Ok(vec![]);
+    }
+
+    match &block.calls {
+        Some(calls) => calls
+            .iter()
+            .filter(move |call| creation_filter.matches(call))
+            .map(
+                move |call| match block.transaction_for_call_succeeded(call) {
+                    Ok(true) => Ok(Some(EthereumTrigger::Call(Arc::new(call.clone())))),
+                    Ok(false) => Ok(None),
+                    Err(e) => Err(e),
+                },
+            )
+            .filter_map_ok(|some_trigger| some_trigger)
+            .collect(),
+        None => Ok(vec![]),
+    }
+}
+
 /// This method does not parse block triggers with `once` filters.
 /// This is because it is to be run before any other triggers are run.
 /// So we have `parse_initialization_triggers` for that.
@@ -1893,29 +2708,40 @@ async fn filter_call_triggers_from_unsuccessful_transactions(
     Ok(block)
 }
 
-/// Deprecated. Wraps the [`fetch_transaction_receipts_in_batch`] in a retry loop.
+/// Deprecated. Wraps the [`fetch_transaction_receipts_in_batch`] in a retry loop. The
+/// receipts are requested in batches of at most `limits.max_batch_size`.
 async fn fetch_transaction_receipts_in_batch_with_retry(
     web3: Arc<Web3<Transport>>,
     hashes: Vec<H256>,
     block_hash: H256,
     logger: Logger,
+    limits: ProviderLimits,
 ) -> Result<Vec<Arc<TransactionReceipt>>, IngestorError> {
-    let retry_log_message = format!(
-        "batch eth_getTransactionReceipt RPC call for block {:?}",
-        block_hash
-    );
-    retry(retry_log_message, &logger)
-        .limit(ENV_VARS.request_retries)
-        .no_logging()
-        .timeout_secs(ENV_VARS.json_rpc_timeout.as_secs())
-        .run(move || {
-            let web3 = web3.cheap_clone();
-            let hashes = hashes.clone();
-            let logger = logger.cheap_clone();
-            fetch_transaction_receipts_in_batch(web3, hashes, block_hash, logger).boxed()
-        })
-        .await
-        .map_err(|_timeout| anyhow!(block_hash).into())
+    let batch_size = limits.max_batch_size.unwrap_or(hashes.len()).max(1);
+    let mut receipts = Vec::with_capacity(hashes.len());
+    for chunk in hashes.chunks(batch_size) {
+        let retry_log_message = format!(
+            "batch eth_getTransactionReceipt RPC call for block {:?}",
+            block_hash
+        );
+        let web3 = web3.cheap_clone();
+        let hashes = chunk.to_vec();
+        let logger = logger.cheap_clone();
+        let batch = retry(retry_log_message, &logger)
+            .limit(ENV_VARS.request_retries)
+            .no_logging()
+            .timeout_secs(limits.timeout_secs())
+            .run(move || {
+                let web3 = web3.cheap_clone();
+                let hashes = hashes.clone();
+                let logger = logger.cheap_clone();
+                fetch_transaction_receipts_in_batch(web3, hashes, block_hash, logger).boxed()
+            })
+            .await
+            .map_err(|_timeout| IngestorError::from(anyhow!(block_hash)))?;
+        receipts.extend(batch);
+    }
+    Ok(receipts)
 }
 
 /// Deprecated. Attempts to fetch multiple transaction receipts in a batching contex.
@@ -1954,6 +2780,7 @@ async fn fetch_transaction_receipt_with_retry(
     transaction_hash: H256,
     block_hash: H256,
     logger: Logger,
+    limits: ProviderLimits,
 ) -> Result<Arc<TransactionReceipt>, IngestorError> {
     let logger = logger.cheap_clone();
     let retry_log_message = format!(
@@ -1962,7 +2789,7 @@ async fn fetch_transaction_receipt_with_retry(
     );
     retry(retry_log_message, &logger)
         .limit(ENV_VARS.request_retries)
-        .timeout_secs(ENV_VARS.json_rpc_timeout.as_secs())
+        .timeout_secs(limits.timeout_secs())
         .run(move || web3.eth().transaction_receipt(transaction_hash).boxed())
         .await
         .map_err(|_timeout| anyhow!(block_hash).into())
@@ -2027,10 +2854,108 @@ fn resolve_transaction_receipt(
     }
 }
 
+/// Splits the block range `[from, to]` into at most `parts` contiguous
+/// ranges of roughly equal size, none of them smaller than `min_size`
+/// blocks, unless the whole range is smaller than that.
+fn split_block_range(
+    from: BlockNumber,
+    to: BlockNumber,
+    parts: usize,
+    min_size: BlockNumber,
+) -> Vec<(BlockNumber, BlockNumber)> {
+    let len = to - from + 1;
+    let parts = (parts as BlockNumber).min(len / min_size.max(1)).max(1);
+    let size = (len + parts - 1) / parts;
+
+    (0..parts)
+        .map(|i| from + i * size)
+        .take_while(|start| *start <= to)
+        .map(|start| (start, (start + size - 1).min(to)))
+        .collect()
+}
+
+/// Scans `[from, to]` for logs by splitting the range across `adapters`
+/// and scanning each part concurrently with a different adapter. Each
+/// adapter reduces its request size on its own if its provider finds the
+/// requests too heavy. The parts are disjoint, so the results can simply be
+/// concatenated.
+async fn logs_across_adapters(
+    adapters: Vec<Arc<EthereumAdapter>>,
+    logger: &Logger,
+    subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
+    from: BlockNumber,
+    to: BlockNumber,
+    log_filter: EthereumLogFilter,
+) -> Result<Vec<Log>, Error> {
+    let ranges = split_block_range(from, to, adapters.len(), ENV_VARS.parallel_log_min_range);
+    if ranges.len() > 1 {
+        debug!(logger, "Splitting scan for logs across providers";
+               "from" => from, "to" => to, "providers" => ranges.len());
+    }
+
+    let scans = ranges
+        .into_iter()
+        .zip(adapters)
+        .map(|((start, end), adapter)| {
+            adapter.logs_in_block_range(
+                logger,
+                subgraph_metrics.cheap_clone(),
+                start,
+                end,
+                log_filter.clone(),
+            )
+        });
+    let logs = futures03::future::try_join_all(scans).await?;
+    Ok(logs.into_iter().flatten().collect())
+}
+
+/// Some providers return the same log more than once or return logs out of
+/// order for large ranges. Sorts `logs` by block and log index and drops
+/// duplicates so that the triggers don't depend on such quirks. Logs with
+/// the same block hash, transaction hash and log index are duplicates.
+fn normalize_logs(
+    logger: &Logger,
+    subgraph_metrics: &SubgraphEthRpcMetrics,
+    mut logs: Vec<Log>,
+) -> Vec<Log> {
+    fn position(log: &Log) -> (Option<U64>, Option<U256>) {
+        (log.block_number, log.log_index)
+    }
+
+    let out_of_order = logs
+        .windows(2)
+        .filter(|pair| position(&pair[0]) > position(&pair[1]))
+        .count();
+    if out_of_order > 0 {
+        // The sort is stable, logs without a position keep their order
+        logs.sort_by_key(position);
+    }
+
+    let len = logs.len();
+    let mut seen = HashSet::new();
+    logs.retain(|log| match (log.transaction_hash, log.log_index) {
+        (Some(tx_hash), Some(log_index)) => seen.insert((log.block_hash, tx_hash, log_index)),
+        _ => true,
+    });
+    let duplicates = len - logs.len();
+
+    if out_of_order > 0 || duplicates > 0 {
+        debug!(logger, "Normalized logs returned by provider";
+               "out_of_order" => out_of_order, "duplicates" => duplicates);
+        subgraph_metrics.add_normalized_logs("out_of_order", out_of_order);
+        subgraph_metrics.add_normalized_logs("duplicate", duplicates);
+    }
+    logs
+}
+
 /// Retrieves logs and the associated transaction receipts, if required by the [`EthereumLogFilter`].
+/// When `log_adapters` contains more than one adapter, the scan for logs is
+/// split across them; otherwise, `adapter` is used.
 async fn get_logs_and_transactions(
     adapter: &Arc<EthereumAdapter>,
+    log_adapters: Vec<Arc<EthereumAdapter>>,
     logger: &Logger,
+    extraction_pool: &ExtractionPool,
     subgraph_metrics: Arc<SubgraphEthRpcMetrics>,
     from: BlockNumber,
     to: BlockNumber,
@@ -2038,15 +2963,32 @@ async fn get_logs_and_transactions(
     unified_api_version: &UnifiedMappingApiVersion,
 ) -> Result<Vec<EthereumTrigger>, anyhow::Error> {
     // Obtain logs externally
-    let logs = adapter
-        .logs_in_block_range(
-            logger,
-            subgraph_metrics.cheap_clone(),
-            from,
-            to,
-            log_filter.clone(),
-        )
+    let logs = extraction_pool
+        .run(ExtractionKind::Logs, async {
+            if log_adapters.len() > 1 {
+                logs_across_adapters(
+                    log_adapters,
+                    logger,
+                    subgraph_metrics.cheap_clone(),
+                    from,
+                    to,
+                    log_filter.clone(),
+                )
+                .await
+            } else {
+                adapter
+                    .logs_in_block_range(
+                        logger,
+                        subgraph_metrics.cheap_clone(),
+                        from,
+                        to,
+                        log_filter.clone(),
+                    )
+                    .await
+            }
+        })
         .await?;
+    let logs = normalize_logs(logger, &subgraph_metrics, logs);
 
     // Not all logs have associated transaction hashes, nor do all triggers require them.
     // We also restrict receipts retrieval for some api versions.
@@ -2078,13 +3020,17 @@ async fn get_logs_and_transactions(
         );
 
     // Obtain receipts externally
-    let transaction_receipts_by_hash = get_transaction_receipts_for_transaction_hashes(
-        adapter,
-        &transaction_hashes_by_block,
-        subgraph_metrics,
-        logger.cheap_clone(),
-    )
-    .await?;
+    let transaction_receipts_by_hash = extraction_pool
+        .run(
+            ExtractionKind::Receipts,
+            get_transaction_receipts_for_transaction_hashes(
+                adapter,
+                &transaction_hashes_by_block,
+                subgraph_metrics,
+                logger.cheap_clone(),
+            ),
+        )
+        .await?;
 
     // Associate each log with its receipt, when possible
     let mut log_triggers = Vec::new();
@@ -2122,12 +3068,10 @@ async fn get_transaction_receipts_for_transaction_hashes(
     // Request transaction receipts concurrently
     let receipt_futures = FuturesUnordered::new();
 
-    let web3 = Arc::clone(&adapter.web3);
     for (block_hash, transaction_hashes) in transaction_hashes_by_block {
         for transaction_hash in transaction_hashes {
             unique_transaction_hashes.insert(transaction_hash);
-            let receipt_future = fetch_transaction_receipt_with_retry(
-                web3.cheap_clone(),
+            let receipt_future = adapter.shared_transaction_receipt(
                 *transaction_hash,
                 *block_hash,
                 logger.cheap_clone(),
@@ -2179,18 +3123,47 @@ async fn get_transaction_receipts_for_transaction_hashes(
     Ok(receipts_by_hash)
 }
 
+/// The key under which an `eth_getLogs` request for `filter` is shared
+/// with identical requests that are in flight. It has to cover everything
+/// that goes into the request, or requests that return different logs end
+/// up sharing one response
+fn logs_key(from: BlockNumber, to: BlockNumber, filter: &EthGetLogsFilter) -> [u8; 32] {
+    crypto_stable_hash(&format!(
+        "{}:{}:{:?}:{:?}:{:?}",
+        from, to, filter.contracts, filter.event_signatures, filter.topics
+    ))
+}
+
 #[cfg(test)]
 mod tests {
 
+    use crate::adapter::EthGetLogsFilter;
+    use crate::data_source::TopicFilter;
     use crate::trigger::{EthereumBlockTriggerType, EthereumTrigger};
 
-    use super::{parse_block_triggers, EthereumBlock, EthereumBlockFilter, EthereumBlockWithCalls};
-    use graph::blockchain::BlockPtr;
+    use super::{
+        blocks_with_triggers_from_cache, logs_key, normalize_logs, parse_block_triggers,
+        split_block_range, CodeCache, EthereumAdapter, EthereumBlock, EthereumBlockFilter,
+        EthereumBlockWithCalls, LogStepLimit, ProviderLimits, TraceAddresses,
+    };
+    use crate::adapter::{ProviderEthRpcMetrics, SubgraphEthRpcMetrics};
+    use crate::transport::Fixtures;
+    use crate::{Transport, TransportBackend, TriggerFilter};
+    use graph::blockchain::{BlockPtr, IngestorError};
+    use graph::log::logger;
     use graph::prelude::ethabi::ethereum_types::U64;
-    use graph::prelude::web3::types::{Address, Block, Bytes, H256};
-    use graph::prelude::EthereumCall;
+    use graph::prelude::futures03::future::{join, join_all};
+    use graph::prelude::tokio::{self, sync::Semaphore};
+    use graph::prelude::web3::types::{Action, Address, Block, Bytes, Log, H256, U256};
+    use graph::prelude::{
+        serde_json, web3, BlockNumber, CheapClone, EthereumCall, MetricsRegistry, TimeoutError,
+    };
+    use jsonrpc_core::types::{Call, Params};
     use std::collections::HashSet;
+    use std::future::Future;
     use std::iter::FromIterator;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Arc;
 
     #[test]
@@ -2231,6 +3204,50 @@ mod tests {
         );
     }
 
+    #[test]
+    fn triggers_from_cached_blocks() {
+        let ethereum_block = |number: u8| EthereumBlock {
+            block: Arc::new(Block {
+                hash: Some(hash(number)),
+                number: Some(U64::from(number)),
+                ..Default::default()
+            }),
+            transaction_receipts: vec![],
+        };
+        let blocks = (1..=3)
+            .map(|number| graph::prelude::serde_json::to_value(ethereum_block(number)).unwrap())
+            .collect::<Vec<_>>();
+
+        // Only the last block is returned if nothing matches
+        let filter = TriggerFilter::default();
+        let found = blocks_with_triggers_from_cache(&logger(true), blocks.clone(), 3, &filter)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            vec![3],
+            found.iter().map(|b| b.ptr().number).collect::<Vec<_>>()
+        );
+
+        let mut filter = TriggerFilter::default();
+        filter.block.trigger_every_block = true;
+        let found = blocks_with_triggers_from_cache(&logger(true), blocks, 3, &filter)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            vec![1, 2, 3],
+            found.iter().map(|b| b.ptr().number).collect::<Vec<_>>()
+        );
+        assert!(found.iter().all(|b| b.trigger_count() == 2));
+
+        // Light blocks do not have receipts
+        let light = graph::prelude::serde_json::to_value(ethereum_block(1).block).unwrap();
+        assert!(
+            blocks_with_triggers_from_cache(&logger(true), vec![light], 1, &filter)
+                .unwrap()
+                .is_none()
+        );
+    }
+
     #[test]
     fn parse_block_triggers_specific_call_not_found() {
         let block = EthereumBlockWithCalls {
@@ -2298,6 +3315,60 @@ mod tests {
         );
     }
 
+    #[test]
+    fn split_block_ranges() {
+        // Ranges are split evenly, with the last range taking what's left
+        assert_eq!(
+            vec![(0, 2), (3, 5), (6, 8), (9, 9)],
+            split_block_range(0, 9, 4, 1)
+        );
+        assert_eq!(
+            vec![(10, 13), (14, 17), (18, 19)],
+            split_block_range(10, 19, 3, 1)
+        );
+        // No range is smaller than the minimum size
+        assert_eq!(vec![(0, 4), (5, 9)], split_block_range(0, 9, 4, 5));
+        // Ranges smaller than the minimum size are not split
+        assert_eq!(vec![(0, 9)], split_block_range(0, 9, 4, 100));
+        assert_eq!(vec![(7, 7)], split_block_range(7, 7, 3, 1));
+    }
+
+    #[test]
+    fn normalize_provider_logs() {
+        let log = |block: u64, tx: u8, index: u64| Log {
+            block_hash: Some(hash(block as u8)),
+            block_number: Some(U64::from(block)),
+            transaction_hash: Some(hash(tx)),
+            log_index: Some(U256::from(index)),
+            ..Default::default()
+        };
+        let positions = |logs: Vec<Log>| {
+            logs.into_iter()
+                .map(|log| (log.block_number.unwrap(), log.log_index.unwrap()))
+                .collect::<Vec<_>>()
+        };
+        let logger = logger(false);
+        let metrics = SubgraphEthRpcMetrics::new(Arc::new(MetricsRegistry::mock()), "test");
+
+        let logs = vec![
+            log(2, 3, 0),
+            log(1, 1, 1),
+            log(1, 1, 0),
+            log(1, 1, 1),
+            log(2, 3, 0),
+        ];
+        let expected = vec![
+            (U64::from(1), U256::from(0)),
+            (U64::from(1), U256::from(1)),
+            (U64::from(2), U256::from(0)),
+        ];
+        assert_eq!(expected, positions(normalize_logs(&logger, &metrics, logs)));
+
+        // Logs that are already normalized are left alone
+        let logs = vec![log(1, 1, 0), log(1, 2, 1), log(2, 3, 0)];
+        assert_eq!(expected, positions(normalize_logs(&logger, &metrics, logs)));
+    }
+
     fn address(id: u64) -> Address {
         Address::from_low_u64_be(id)
     }
@@ -2309,4 +3380,443 @@ mod tests {
     fn bytes(value: Vec<u8>) -> Bytes {
         Bytes::from(value)
     }
+
+    #[test]
+    fn log_step_limit_lowers_and_recovers() {
+        let limit = LogStepLimit::new(Some(2000));
+        assert_eq!(1999, limit.get());
+
+        assert!(limit.lower(199));
+        assert_eq!(199, limit.get());
+        // Lowering to a larger step does nothing
+        assert!(!limit.lower(1000));
+        assert_eq!(199, limit.get());
+
+        // Requests that didn't use the full step don't count towards
+        // raising the limit
+        for _ in 0..LogStepLimit::RECOVERY_SUCCESSES {
+            assert_eq!(None, limit.succeeded(10));
+        }
+        assert_eq!(199, limit.get());
+
+        for _ in 1..LogStepLimit::RECOVERY_SUCCESSES {
+            assert_eq!(None, limit.succeeded(199));
+        }
+        assert_eq!(Some(399), limit.succeeded(199));
+        assert_eq!(399, limit.get());
+
+        // Another failure resets the count of successes
+        for _ in 1..LogStepLimit::RECOVERY_SUCCESSES {
+            assert_eq!(None, limit.succeeded(399));
+        }
+        assert!(limit.lower(39));
+        assert_eq!(None, limit.succeeded(39));
+        assert_eq!(39, limit.get());
+
+        // The limit never grows beyond the configured range
+        for _ in 0..10 * LogStepLimit::RECOVERY_SUCCESSES {
+            limit.succeeded(limit.get());
+        }
+        assert_eq!(1999, limit.get());
+
+        let unlimited = LogStepLimit::new(None);
+        assert!(unlimited.lower(99));
+        for _ in 0..40 * LogStepLimit::RECOVERY_SUCCESSES {
+            unlimited.succeeded(unlimited.get());
+        }
+        assert_eq!(BlockNumber::MAX, unlimited.get());
+    }
+
+    #[test]
+    fn code_cache_is_bounded() {
+        let cache = CodeCache::default();
+        assert_eq!(None, cache.get(address(1), hash(1)));
+
+        cache.set(address(1), hash(1), true);
+        cache.set(address(2), hash(1), false);
+        assert_eq!(Some(true), cache.get(address(1), hash(1)));
+        assert_eq!(Some(false), cache.get(address(2), hash(1)));
+        // Results are per block
+        assert_eq!(None, cache.get(address(1), hash(2)));
+
+        // Once the cache is full, it starts over
+        for i in 0..CodeCache::CAPACITY {
+            cache.set(address(3), H256::from_low_u64_be(i as u64), true);
+        }
+        assert_eq!(None, cache.get(address(1), hash(1)));
+        let last = H256::from_low_u64_be(CodeCache::CAPACITY as u64 - 1);
+        assert_eq!(Some(true), cache.get(address(3), last));
+    }
+
+    #[test]
+    fn logs_key_covers_topics() {
+        let filter = |topic1: Option<Vec<H256>>| EthGetLogsFilter {
+            contracts: vec![address(1)],
+            event_signatures: vec![hash(2)],
+            topics: TopicFilter {
+                topic1,
+                topic2: None,
+                topic3: None,
+            },
+        };
+
+        let unrestricted = logs_key(1, 10, &filter(None));
+        let restricted = logs_key(1, 10, &filter(Some(vec![hash(3)])));
+        let other = logs_key(1, 10, &filter(Some(vec![hash(4)])));
+        assert_ne!(unrestricted, restricted);
+        assert_ne!(restricted, other);
+        assert_eq!(restricted, logs_key(1, 10, &filter(Some(vec![hash(3)]))));
+        assert_ne!(restricted, logs_key(1, 11, &filter(Some(vec![hash(3)]))));
+    }
+
+    /// A backend that answers requests for `method` with `response`, but
+    /// only once the gate is opened. Other requests fail right away
+    #[derive(Debug)]
+    struct GatedBackend {
+        method: &'static str,
+        response: Result<serde_json::Value, web3::error::Error>,
+        calls: AtomicUsize,
+        gate: Arc<Semaphore>,
+    }
+
+    impl GatedBackend {
+        fn new(
+            method: &'static str,
+            response: Result<serde_json::Value, web3::error::Error>,
+        ) -> Arc<Self> {
+            Arc::new(GatedBackend {
+                method,
+                response,
+                calls: AtomicUsize::new(0),
+                gate: Arc::new(Semaphore::new(0)),
+            })
+        }
+
+        /// How often `method` was requested
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+
+        /// Run `requests` at the same time and let the provider answer
+        /// once all of them were started
+        async fn run<F: Future>(&self, requests: Vec<F>) -> Vec<F::Output> {
+            let open = async {
+                // All requests have been polled once by the time the first
+                // one reaches the provider
+                while self.calls() == 0 {
+                    tokio::task::yield_now().await;
+                }
+                self.gate.add_permits(1000);
+            };
+            let (results, ()) = join(join_all(requests), open).await;
+            results
+        }
+    }
+
+    impl TransportBackend for GatedBackend {
+        fn send(
+            &self,
+            request: Call,
+        ) -> Pin<Box<dyn Future<Output = Result<serde_json::Value, web3::error::Error>> + Send>>
+        {
+            match request {
+                Call::MethodCall(call) if call.method == self.method => {
+                    self.calls.fetch_add(1, Ordering::SeqCst);
+                    let gate = self.gate.cheap_clone();
+                    let response = self.response.clone();
+                    Box::pin(async move {
+                        let _permit = gate.acquire().await.unwrap();
+                        response
+                    })
+                }
+                _ => Box::pin(std::future::ready(Err(web3::error::Error::Unreachable))),
+            }
+        }
+    }
+
+    async fn gated_adapter(
+        registry: &Arc<MetricsRegistry>,
+        backend: &Arc<GatedBackend>,
+    ) -> EthereumAdapter {
+        EthereumAdapter::new(
+            logger(false),
+            "provider".to_string(),
+            Transport::new_custom(backend.cheap_clone()),
+            Arc::new(ProviderEthRpcMetrics::new(registry.cheap_clone())),
+            true,
+            false,
+            ProviderLimits::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn shared_receipt_requests_keep_their_error() {
+        let registry = Arc::new(MetricsRegistry::mock());
+        // The provider does not know the receipt
+        let backend = GatedBackend::new("eth_getTransactionReceipt", Ok(serde_json::Value::Null));
+        let adapter = gated_adapter(&registry, &backend).await;
+
+        let requests = (0..3)
+            .map(|_| adapter.shared_transaction_receipt(hash(1), hash(2), logger(false)))
+            .collect();
+        let results = backend.run(requests).await;
+
+        assert_eq!(1, backend.calls());
+        assert_eq!(3, results.len());
+        for res in results {
+            match res {
+                Err(IngestorError::ReceiptUnavailable(block_hash, transaction_hash)) => {
+                    assert_eq!(hash(2), block_hash);
+                    assert_eq!(hash(1), transaction_hash);
+                }
+                res => panic!("unexpected result {:?}", res),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn shared_logs_requests_count_for_every_deployment() {
+        let registry = Arc::new(MetricsRegistry::mock());
+        let error = web3::error::Error::Rpc(jsonrpc_core::Error {
+            code: jsonrpc_core::ErrorCode::ServerError(-32000),
+            message: "query returned more than 10000 results".to_string(),
+            data: None,
+        });
+        let backend = GatedBackend::new("eth_getLogs", Err(error));
+        let adapter = gated_adapter(&registry, &backend).await;
+        let filter = Arc::new(EthGetLogsFilter {
+            contracts: vec![address(1)],
+            event_signatures: vec![hash(2)],
+            topics: TopicFilter {
+                topic1: None,
+                topic2: None,
+                topic3: None,
+            },
+        });
+
+        let deployments = ["QmLeader", "QmFollower"];
+        let requests = deployments
+            .iter()
+            .map(|deployment| {
+                let metrics = Arc::new(SubgraphEthRpcMetrics::new(
+                    registry.cheap_clone(),
+                    deployment,
+                ));
+                adapter.shared_logs_with_sigs(
+                    logger(false),
+                    metrics,
+                    1,
+                    10,
+                    filter.cheap_clone(),
+                    &["query returned more than"],
+                )
+            })
+            .collect();
+        let results = backend.run(requests).await;
+
+        assert_eq!(1, backend.calls());
+        for res in results {
+            match res {
+                Err(TimeoutError::Inner(e)) => {
+                    assert!(e.to_string().contains("query returned more than"))
+                }
+                res => panic!("unexpected result {:?}", res),
+            }
+        }
+        // Every deployment has the failed request in its metrics
+        let errors = registry
+            .global_counter_vec(
+                "deployment_eth_rpc_errors",
+                "Counts eth rpc request errors for a subgraph deployment",
+                &["deployment", "method", "provider"],
+            )
+            .unwrap();
+        for deployment in deployments {
+            let count = errors
+                .with_label_values(&[deployment, "eth_getLogs", "provider"])
+                .get();
+            assert_eq!(1.0, count, "errors of {}", deployment);
+        }
+    }
+
+    /// A backend for tracing blocks with `debug_traceBlockByHash`. Block
+    /// `n` has one transaction, which calls `address(n)`. Responses take a
+    /// little while so that concurrent requests overlap
+    #[derive(Debug, Default)]
+    struct TraceBackend {
+        running: Arc<AtomicUsize>,
+        max_running: Arc<AtomicUsize>,
+    }
+
+    impl TransportBackend for TraceBackend {
+        fn send(
+            &self,
+            request: Call,
+        ) -> Pin<Box<dyn Future<Output = Result<serde_json::Value, web3::error::Error>> + Send>>
+        {
+            let response = match request {
+                Call::MethodCall(call) => match (call.method.as_str(), call.params) {
+                    ("eth_getBlockByNumber", Params::Array(params)) => {
+                        let number: U64 = serde_json::from_value(params[0].clone()).unwrap();
+                        let block = Block {
+                            hash: Some(hash(number.as_u64() as u8)),
+                            number: Some(number),
+                            transactions: vec![hash(0)],
+                            ..Default::default()
+                        };
+                        Ok(serde_json::to_value(block).unwrap())
+                    }
+                    ("debug_traceBlockByHash", Params::Array(params)) => {
+                        let block_hash: H256 = serde_json::from_value(params[0].clone()).unwrap();
+                        let to = address(block_hash.as_bytes()[0] as u64);
+                        Ok(serde_json::json!([{
+                            "result": { "type": "CALL", "from": address(1), "to": to, "input": "0x" }
+                        }]))
+                    }
+                    _ => Err(web3::error::Error::Unreachable),
+                },
+                _ => Err(web3::error::Error::Unreachable),
+            };
+            let running = self.running.cheap_clone();
+            let max_running = self.max_running.cheap_clone();
+            Box::pin(async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                for _ in 0..10 {
+                    tokio::task::yield_now().await;
+                }
+                running.fetch_sub(1, Ordering::SeqCst);
+                response
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn debug_traces_are_fetched_concurrently_in_block_order() {
+        let registry = Arc::new(MetricsRegistry::mock());
+        let backend = Arc::new(TraceBackend::default());
+        let adapter = EthereumAdapter::new(
+            logger(false),
+            "provider".to_string(),
+            Transport::new_custom(backend.cheap_clone()),
+            Arc::new(ProviderEthRpcMetrics::new(registry.cheap_clone())),
+            true,
+            false,
+            ProviderLimits::default(),
+        )
+        .await
+        .with_debug_traces(true);
+        let metrics = Arc::new(SubgraphEthRpcMetrics::new(registry, "QmTraces"));
+
+        let traces = adapter
+            .clone()
+            .traces(
+                logger(false),
+                metrics.cheap_clone(),
+                1,
+                25,
+                TraceAddresses::To(vec![]),
+            )
+            .await
+            .unwrap();
+
+        let numbers: Vec<_> = traces.iter().map(|trace| trace.block_number).collect();
+        assert_eq!((1..=25).collect::<Vec<u64>>(), numbers);
+        let max_running = backend.max_running.load(Ordering::SeqCst);
+        assert!(max_running > 1, "blocks were traced one at a time");
+        assert!(max_running <= crate::ENV_VARS.block_batch_size);
+
+        // Traces are filtered like `trace_filter` would filter them
+        let traces = adapter
+            .traces(
+                logger(false),
+                metrics,
+                1,
+                25,
+                TraceAddresses::To(vec![address(3), address(7)]),
+            )
+            .await
+            .unwrap();
+        let targets: Vec<_> = traces
+            .iter()
+            .map(|trace| match &trace.action {
+                Action::Call(call) => call.to,
+                action => panic!("unexpected action {:?}", action),
+            })
+            .collect();
+        assert_eq!(vec![address(3), address(7)], targets);
+    }
+
+    /// An adapter whose provider reports `client_version` and answers
+    /// `eth_syncing` with `sync_state`
+    async fn health_check_adapter(
+        provider_metrics: &Arc<ProviderEthRpcMetrics>,
+        client_version: &str,
+        sync_state: &str,
+    ) -> EthereumAdapter {
+        let fixtures = Fixtures::from_json(&format!(
+            r#"[{{ "method": "web3_clientVersion", "result": "{}" }},
+                {{ "method": "eth_syncing", "result": {} }}]"#,
+            client_version, sync_state
+        ))
+        .unwrap();
+        EthereumAdapter::new(
+            logger(false),
+            "provider".to_string(),
+            Transport::new_custom(Arc::new(fixtures)),
+            provider_metrics.cheap_clone(),
+            true,
+            false,
+            ProviderLimits::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn check_health_finds_syncing_and_broken_providers() {
+        const SYNCING: &str =
+            r#"{ "startingBlock": "0x0", "currentBlock": "0x5", "highestBlock": "0x10" }"#;
+
+        let registry = Arc::new(MetricsRegistry::mock());
+        let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(registry));
+        let broken = vec!["Geth/v1.13.0".to_string()];
+
+        let adapter = health_check_adapter(&provider_metrics, "Geth/v1.13.5", "false").await;
+        assert!(adapter.health().is_none());
+        let health = adapter.check_health_against(&broken).await;
+        assert_eq!(Some("Geth/v1.13.5"), health.client_version.as_deref());
+        assert!(health.is_healthy());
+        assert!(!adapter.is_unhealthy());
+
+        let adapter = health_check_adapter(&provider_metrics, "Geth/v1.13.5", SYNCING).await;
+        let health = adapter.check_health_against(&broken).await;
+        assert!(health.syncing);
+        assert!(!health.broken_version);
+        assert!(adapter.is_unhealthy());
+
+        // Broken versions match on a prefix of the client version
+        let adapter = health_check_adapter(&provider_metrics, "Geth/v1.13.0-stable", "false").await;
+        let health = adapter.check_health_against(&broken).await;
+        assert!(!health.syncing);
+        assert!(health.broken_version);
+        assert!(adapter.is_unhealthy());
+        assert!(adapter.health().is_some());
+
+        // A provider that can't answer the checks is not reported
+        let fixtures = Fixtures::from_json("[]").unwrap();
+        let adapter = EthereumAdapter::new(
+            logger(false),
+            "provider".to_string(),
+            Transport::new_custom(Arc::new(fixtures)),
+            provider_metrics.cheap_clone(),
+            true,
+            false,
+            ProviderLimits::default(),
+        )
+        .await;
+        let health = adapter.check_health_against(&broken).await;
+        assert_eq!(None, health.client_version);
+        assert!(health.is_healthy());
+    }
 }
@@ -0,0 +1,425 @@
+//! Filters on the parameters of an event that event handlers can declare in
+//! the manifest, e.g. `filter: "amount > 0 && to != 0x0"`. They are checked
+//! before the handler is called so that events the handler would ignore
+//! never cost a WASM invocation.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error};
+use graph::prelude::ethabi::{Event, EventParam, LogParam, ParamType, Token};
+use graph::prelude::{web3::types::U256, BigInt};
+use serde::Deserialize;
+
+/// A conjunction of conditions on event parameters. An event matches the
+/// filter if it satisfies all of the conditions.
+#[derive(Clone, Debug, Hash, Eq, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct EventFilter {
+    conditions: Vec<Condition>,
+}
+
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+struct Condition {
+    param: String,
+    op: Op,
+    value: Literal,
+}
+
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+enum Literal {
+    Number { negative: bool, magnitude: U256 },
+    Hex(Vec<u8>),
+    Bool(bool),
+    String(String),
+}
+
+impl EventFilter {
+    /// Checks that the filter only refers to parameters of `event` and
+    /// that every condition makes sense for the type of its parameter.
+    pub fn validate(&self, event: &Event) -> Result<(), Error> {
+        for cond in &self.conditions {
+            let input = event
+                .inputs
+                .iter()
+                .find(|input| input.name == cond.param)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "filter refers to parameter `{}` which event `{}` does not have",
+                        cond.param,
+                        event.name
+                    )
+                })?;
+            cond.validate(input)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the decoded `params` of an event satisfy all conditions. A
+    /// condition on a parameter that is missing or has a type the
+    /// condition can't be checked against is never satisfied.
+    pub fn matches(&self, params: &[LogParam]) -> bool {
+        self.conditions.iter().all(|cond| {
+            params
+                .iter()
+                .find(|param| param.name == cond.param)
+                .map_or(false, |param| cond.matches(&param.value))
+        })
+    }
+}
+
+impl Condition {
+    fn validate(&self, input: &EventParam) -> Result<(), Error> {
+        let kind = &input.kind;
+
+        // Logs only contain the hash of indexed parameters of a dynamic
+        // type, and a condition on them could never be satisfied
+        let hashed = matches!(
+            kind,
+            ParamType::String
+                | ParamType::Bytes
+                | ParamType::Array(_)
+                | ParamType::FixedArray(_, _)
+                | ParamType::Tuple(_)
+        );
+        if input.indexed && hashed {
+            return Err(anyhow!(
+                "filter `{}` can not be applied to parameter `{}` of type `{}` since it is indexed \
+                 and logs only contain its hash",
+                self,
+                self.param,
+                kind
+            ));
+        }
+
+        let ordered = matches!(self.op, Op::Lt | Op::Le | Op::Gt | Op::Ge);
+        let compatible = match (kind, &self.value) {
+            (ParamType::Uint(_), Literal::Number { negative, .. }) => !negative,
+            (ParamType::Int(_) | ParamType::Uint(_), Literal::Number { .. })
+            | (ParamType::Int(_) | ParamType::Uint(_), Literal::Hex(_)) => true,
+            (ParamType::Address, Literal::Hex(bytes)) => !ordered && bytes.len() <= 20,
+            (ParamType::FixedBytes(_) | ParamType::Bytes, Literal::Hex(_))
+            | (ParamType::Bool, Literal::Bool(_))
+            | (ParamType::String, Literal::String(_)) => !ordered,
+            _ => false,
+        };
+        if compatible {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "filter `{}` can not be applied to parameter `{}` of type `{}`",
+                self,
+                self.param,
+                kind
+            ))
+        }
+    }
+
+    fn matches(&self, token: &Token) -> bool {
+        let ordering = match self.compare(token) {
+            Some(ordering) => ordering,
+            None => return false,
+        };
+        match self.op {
+            Op::Eq => ordering == Ordering::Equal,
+            Op::Ne => ordering != Ordering::Equal,
+            Op::Lt => ordering == Ordering::Less,
+            Op::Le => ordering != Ordering::Greater,
+            Op::Gt => ordering == Ordering::Greater,
+            Op::Ge => ordering != Ordering::Less,
+        }
+    }
+
+    /// Compares `token` to the literal of this condition, or returns
+    /// `None` if they can't be compared
+    fn compare(&self, token: &Token) -> Option<Ordering> {
+        match (token, &self.value) {
+            (Token::Uint(n), value) => Some(BigInt::from_unsigned_u256(n).cmp(&value.as_number()?)),
+            (Token::Int(n), value) => Some(BigInt::from_signed_u256(n).cmp(&value.as_number()?)),
+            (Token::Address(address), Literal::Hex(bytes)) if bytes.len() <= 20 => {
+                let mut padded = [0u8; 20];
+                padded[20 - bytes.len()..].copy_from_slice(bytes);
+                Some(address.as_bytes().cmp(&padded[..]))
+            }
+            (Token::FixedBytes(actual), Literal::Hex(bytes))
+            | (Token::Bytes(actual), Literal::Hex(bytes)) => Some(actual.cmp(bytes)),
+            (Token::Bool(actual), Literal::Bool(b)) => Some(actual.cmp(b)),
+            (Token::String(actual), Literal::String(s)) => Some(actual.cmp(s)),
+            _ => None,
+        }
+    }
+}
+
+impl Literal {
+    fn as_number(&self) -> Option<BigInt> {
+        match self {
+            Literal::Number {
+                negative,
+                magnitude,
+            } => {
+                let n = BigInt::from_unsigned_u256(magnitude);
+                Some(if *negative { BigInt::from(0) - n } else { n })
+            }
+            Literal::Hex(bytes) if bytes.len() <= 32 => {
+                Some(BigInt::from_unsigned_u256(&U256::from_big_endian(bytes)))
+            }
+            _ => None,
+        }
+    }
+
+    fn parse(s: &str) -> Result<Literal, Error> {
+        if s.is_empty() {
+            return Err(anyhow!("expected a value"));
+        }
+        if s == "true" || s == "false" {
+            return Ok(Literal::Bool(s == "true"));
+        }
+        if let Some(s) = s.strip_prefix('"') {
+            return s
+                .strip_suffix('"')
+                .map(|s| Literal::String(s.to_owned()))
+                .ok_or_else(|| anyhow!("unterminated string `\"{}`", s));
+        }
+        if let Some(hex) = s.strip_prefix("0x") {
+            let hex = if hex.len() % 2 == 1 {
+                format!("0{}", hex)
+            } else {
+                hex.to_owned()
+            };
+            return hex::decode(hex)
+                .map(Literal::Hex)
+                .map_err(|e| anyhow!("invalid hex value `{}`: {}", s, e));
+        }
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, s),
+        };
+        let magnitude = U256::from_dec_str(digits).map_err(|_| {
+            anyhow!(
+                "invalid value `{}`, expected a number, a hex value, a quoted string, `true` or `false`",
+                s
+            )
+        })?;
+        Ok(Literal::Number {
+            negative,
+            magnitude,
+        })
+    }
+}
+
+impl FromStr for EventFilter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const OPS: [(&str, Op); 6] = [
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ];
+
+        let mut conditions = vec![];
+        let mut rest = s.trim_start();
+        loop {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let (param, tail) = rest.split_at(end);
+            if param.is_empty() {
+                return Err(anyhow!("invalid filter `{}`: expected a parameter name", s));
+            }
+
+            let tail = tail.trim_start();
+            let (op, tail) = OPS
+                .iter()
+                .find_map(|(token, op)| tail.strip_prefix(token).map(|tail| (*op, tail)))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "invalid filter `{}`: expected one of ==, !=, <, <=, >, >= after `{}`",
+                        s,
+                        param
+                    )
+                })?;
+
+            // A string literal extends to the closing quote, anything else
+            // to the next whitespace
+            let tail = tail.trim_start();
+            let end = if tail.starts_with('"') {
+                tail[1..].find('"').map_or(tail.len(), |pos| pos + 2)
+            } else {
+                tail.find(char::is_whitespace).unwrap_or(tail.len())
+            };
+            let (value, tail) = tail.split_at(end);
+            let value =
+                Literal::parse(value).map_err(|e| anyhow!("invalid filter `{}`: {}", s, e))?;
+
+            conditions.push(Condition {
+                param: param.to_owned(),
+                op,
+                value,
+            });
+
+            rest = tail.trim_start();
+            if rest.is_empty() {
+                break;
+            }
+            rest = rest
+                .strip_prefix("&&")
+                .ok_or_else(|| anyhow!("invalid filter `{}`: expected `&&` before `{}`", s, rest))?
+                .trim_start();
+        }
+
+        Ok(EventFilter { conditions })
+    }
+}
+
+impl TryFrom<String> for EventFilter {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self.op {
+            Op::Eq => "==",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+        };
+        write!(f, "{} {} ", self.param, op)?;
+        match &self.value {
+            Literal::Number {
+                negative,
+                magnitude,
+            } => write!(f, "{}{}", if *negative { "-" } else { "" }, magnitude),
+            Literal::Hex(bytes) => write!(f, "0x{}", hex::encode(bytes)),
+            Literal::Bool(b) => write!(f, "{}", b),
+            Literal::String(s) => write!(f, "\"{}\"", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use graph::prelude::ethabi::{Address, EventParam, LogParam, ParamType, Token};
+    use graph::prelude::web3::types::U256;
+
+    use super::EventFilter;
+
+    fn transfer(amount: u64, to: Address) -> Vec<LogParam> {
+        vec![
+            LogParam {
+                name: "to".to_string(),
+                value: Token::Address(to),
+            },
+            LogParam {
+                name: "amount".to_string(),
+                value: Token::Uint(U256::from(amount)),
+            },
+        ]
+    }
+
+    #[test]
+    fn parse_and_match() {
+        let filter: EventFilter = "amount > 0 && to != 0x0".parse().unwrap();
+        assert!(filter.matches(&transfer(1, Address::from_low_u64_be(1))));
+        assert!(!filter.matches(&transfer(0, Address::from_low_u64_be(1))));
+        assert!(!filter.matches(&transfer(1, Address::zero())));
+
+        let filter: EventFilter = "amount<=10".parse().unwrap();
+        assert!(filter.matches(&transfer(10, Address::zero())));
+        assert!(!filter.matches(&transfer(11, Address::zero())));
+
+        // Conditions on missing parameters never match
+        let filter: EventFilter = "from == 0x0".parse().unwrap();
+        assert!(!filter.matches(&transfer(1, Address::zero())));
+
+        let filter: EventFilter = r#"name == "a b""#.parse().unwrap();
+        let params = vec![LogParam {
+            name: "name".to_string(),
+            value: Token::String("a b".to_string()),
+        }];
+        assert!(filter.matches(&params));
+
+        let filter: EventFilter = "delta < -5".parse().unwrap();
+        let params = vec![LogParam {
+            name: "delta".to_string(),
+            value: Token::Int(U256::MAX),
+        }];
+        assert!(!filter.matches(&params));
+        let params = vec![LogParam {
+            name: "delta".to_string(),
+            value: Token::Int(U256::MAX - 5),
+        }];
+        assert!(filter.matches(&params));
+
+        for invalid in [
+            "",
+            "amount",
+            "amount >",
+            "amount > 0 to == 0x0",
+            "amount ~ 1",
+        ] {
+            assert!(invalid.parse::<EventFilter>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn validate() {
+        let event = graph::prelude::ethabi::Event {
+            name: "Transfer".to_string(),
+            inputs: vec![
+                EventParam {
+                    name: "to".to_string(),
+                    kind: ParamType::Address,
+                    indexed: true,
+                },
+                EventParam {
+                    name: "amount".to_string(),
+                    kind: ParamType::Uint(256),
+                    indexed: false,
+                },
+                EventParam {
+                    name: "memo".to_string(),
+                    kind: ParamType::String,
+                    indexed: true,
+                },
+                EventParam {
+                    name: "data".to_string(),
+                    kind: ParamType::Bytes,
+                    indexed: true,
+                },
+            ],
+            anonymous: false,
+        };
+
+        let valid = |s: &str| s.parse::<EventFilter>().unwrap().validate(&event).is_ok();
+        assert!(valid("amount > 0 && to != 0x0"));
+        assert!(valid("amount >= 0x10"));
+        assert!(!valid("from == 0x0"));
+        assert!(!valid("to > 0x0"));
+        assert!(!valid("amount > -1"));
+        assert!(!valid(r#"amount == "1""#));
+        // Indexed parameters of a dynamic type are only available as hashes
+        assert!(!valid(r#"memo == "hello""#));
+        assert!(!valid("data == 0x01"));
+    }
+}
@@ -0,0 +1,116 @@
+//! Limits on how much trigger extraction all deployments on one network can
+//! do at the same time.
+//!
+//! Extracting triggers for a block range means scanning for logs, fetching
+//! traces, and fetching transaction receipts. A deployment that backfills
+//! aggressively can issue a lot of these requests, and without a limit that
+//! is shared across deployments it would use up the capacity of the
+//! network's providers and make every other deployment on the network wait.
+//! Each kind of work has its own limit so that, e.g., slow trace requests do
+//! not hold up log scans. Waiting for a slot is first-come-first-served, so
+//! every deployment gets its turn.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use graph::prelude::tokio::sync::Semaphore;
+
+use crate::ENV_VARS;
+
+/// The kinds of work the pool limits
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExtractionKind {
+    /// Scanning a block range for logs with `eth_getLogs`
+    Logs,
+    /// Fetching traces for a block range with `trace_filter`
+    Traces,
+    /// Fetching the receipts for the transactions of a set of logs
+    Receipts,
+}
+
+/// The trigger extraction pool of one network, shared by all deployments
+/// that index that network
+pub struct ExtractionPool {
+    logs: Semaphore,
+    traces: Semaphore,
+    receipts: Semaphore,
+}
+
+impl ExtractionPool {
+    /// Create a pool that runs at most `logs` log scans, `traces` trace
+    /// scans and `receipts` receipt fetches at the same time. Limits of 0
+    /// are treated as 1
+    pub fn new(logs: usize, traces: usize, receipts: usize) -> Self {
+        ExtractionPool {
+            logs: Semaphore::new(logs.max(1)),
+            traces: Semaphore::new(traces.max(1)),
+            receipts: Semaphore::new(receipts.max(1)),
+        }
+    }
+
+    /// Create a pool with the limits from the environment
+    pub fn from_env() -> Arc<Self> {
+        Arc::new(Self::new(
+            ENV_VARS.extraction_max_log_scans,
+            ENV_VARS.extraction_max_trace_scans,
+            ENV_VARS.extraction_max_receipt_fetches,
+        ))
+    }
+
+    fn semaphore(&self, kind: ExtractionKind) -> &Semaphore {
+        match kind {
+            ExtractionKind::Logs => &self.logs,
+            ExtractionKind::Traces => &self.traces,
+            ExtractionKind::Receipts => &self.receipts,
+        }
+    }
+
+    /// Wait for a free slot for work of `kind` and run `work` while
+    /// holding it
+    pub async fn run<F: Future>(&self, kind: ExtractionKind, work: F) -> F::Output {
+        // Unwrap: the semaphore is never closed
+        let _permit = self.semaphore(kind).acquire().await.unwrap();
+        work.await
+    }
+
+    /// The number of slots for work of `kind` that are currently free
+    pub fn available(&self, kind: ExtractionKind) -> usize {
+        self.semaphore(kind).available_permits()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use graph::prelude::tokio;
+    use graph::prelude::tokio::sync::oneshot;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn limits_are_per_kind() {
+        let pool = Arc::new(ExtractionPool::new(1, 2, 0));
+        assert_eq!(1, pool.available(ExtractionKind::Logs));
+        assert_eq!(2, pool.available(ExtractionKind::Traces));
+        assert_eq!(1, pool.available(ExtractionKind::Receipts));
+
+        let (send, recv) = oneshot::channel::<()>();
+        let pool2 = pool.clone();
+        let scan = tokio::spawn(async move {
+            pool2
+                .run(ExtractionKind::Logs, async move { recv.await.unwrap() })
+                .await
+        });
+        while pool.available(ExtractionKind::Logs) > 0 {
+            tokio::task::yield_now().await;
+        }
+
+        // A log scan in progress does not hold up other kinds of work
+        pool.run(ExtractionKind::Traces, async {}).await;
+        pool.run(ExtractionKind::Receipts, async {}).await;
+        assert_eq!(0, pool.available(ExtractionKind::Logs));
+
+        send.send(()).unwrap();
+        scan.await.unwrap();
+        assert_eq!(1, pool.available(ExtractionKind::Logs));
+    }
+}
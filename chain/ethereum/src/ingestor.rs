@@ -2,12 +2,158 @@ use crate::{chain::BlockFinality, EthereumAdapter, EthereumAdapterTrait, ENV_VAR
 use graph::{
     blockchain::{BlockHash, BlockIngestor, BlockPtr, IngestorError},
     cheap_clone::CheapClone,
+    futures03::{
+        future::BoxFuture,
+        stream::{BoxStream, StreamExt},
+        FutureExt,
+    },
     prelude::{
-        async_trait, error, ethabi::ethereum_types::H256, info, tokio, trace, warn, ChainStore,
-        Error, EthereumBlockWithCalls, Future01CompatExt, LogCode, Logger,
+        async_trait, error,
+        ethabi::ethereum_types::H256,
+        info,
+        tokio::{self, task::JoinHandle},
+        trace, warn,
+        web3::{api::SubscriptionStream, transports::WebSocket, types::BlockHeader, Web3},
+        ChainStore, Error, EthereumBlockWithCalls, Future01CompatExt, LogCode, Logger,
     },
 };
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// How long a `newHeads` subscription can go without a notification
+/// before we assume that the socket is gone and subscribe again. Until
+/// then, we poll every `polling_interval` as if there was no subscription
+const NEW_HEADS_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long to wait after a `newHeads` subscription failed before trying
+/// to subscribe again
+const NEW_HEADS_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The notifications of a `newHeads` subscription; we only care that
+/// there was a new head, not what it was
+type NewHeads = BoxStream<'static, Result<(), String>>;
+
+type Subscribe = Arc<dyn Fn() -> BoxFuture<'static, Result<NewHeads, String>> + Send + Sync>;
+
+/// A subscription to `newHeads` that the ingestor uses to learn about new
+/// blocks sooner than polling would. The notifications only tell the
+/// ingestor to look for a new chain head; the blocks themselves are still
+/// fetched and checked by `do_poll`, which ignores heads that it has
+/// already seen or that are behind the chain head, so that blocks reach
+/// the store in the same order as with polling. Subscribing happens in the
+/// background so that a provider that does not respond never holds up
+/// polling
+struct NewHeadsSubscription {
+    subscribe: Subscribe,
+    heads: Option<NewHeads>,
+    /// When we last heard from `heads`
+    last_head: Instant,
+    /// The attempt to subscribe that is under way
+    pending: Option<JoinHandle<Result<NewHeads, String>>>,
+    last_attempt: Option<Instant>,
+}
+
+impl NewHeadsSubscription {
+    fn new(url: String) -> Self {
+        Self::with_subscribe(Arc::new(move || {
+            let url = url.clone();
+            async move {
+                let ws = WebSocket::new(&url).await.map_err(|e| e.to_string())?;
+                let heads: SubscriptionStream<WebSocket, BlockHeader> = Web3::new(ws)
+                    .eth_subscribe()
+                    .subscribe_new_heads()
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let heads: NewHeads = heads
+                    .map(|head| head.map(|_| ()).map_err(|e| e.to_string()))
+                    .boxed();
+                Ok::<_, String>(heads)
+            }
+            .boxed()
+        }))
+    }
+
+    fn with_subscribe(subscribe: Subscribe) -> Self {
+        NewHeadsSubscription {
+            subscribe,
+            heads: None,
+            last_head: Instant::now(),
+            pending: None,
+            last_attempt: None,
+        }
+    }
+
+    /// Start subscribing in the background if we have no subscription and
+    /// are not waiting for one, and pick up the subscription once that is
+    /// done
+    fn resubscribe(&mut self, logger: &Logger) {
+        if let Some(res) = self
+            .pending
+            .as_mut()
+            .and_then(|pending| pending.now_or_never())
+        {
+            self.pending = None;
+            match res.map_err(|e| e.to_string()).and_then(|res| res) {
+                Ok(heads) => {
+                    info!(logger, "Subscribed to new heads");
+                    self.heads = Some(heads);
+                    self.last_head = Instant::now();
+                }
+                Err(e) => {
+                    warn!(logger, "Failed to subscribe to new heads, polling instead";
+                          "error" => e);
+                }
+            }
+        }
+
+        if self.heads.is_none()
+            && self.pending.is_none()
+            && self
+                .last_attempt
+                .map_or(true, |last| last.elapsed() >= NEW_HEADS_RETRY_INTERVAL)
+        {
+            self.last_attempt = Some(Instant::now());
+            let subscribe = (self.subscribe)();
+            self.pending = Some(graph::spawn(async move {
+                tokio::time::timeout(NEW_HEADS_TIMEOUT, subscribe)
+                    .await
+                    .map_err(|_| "timed out".to_string())?
+            }));
+        }
+    }
+
+    /// Wait until it is time to look for a new chain head. That is when
+    /// the provider announces a new head, but never longer than
+    /// `polling_interval`
+    async fn wait(&mut self, logger: &Logger, polling_interval: Duration) {
+        self.resubscribe(logger);
+
+        let heads = match self.heads.as_mut() {
+            Some(heads) => heads,
+            None => return tokio::time::sleep(polling_interval).await,
+        };
+
+        let error = match tokio::time::timeout(polling_interval, heads.next()).await {
+            Ok(Some(Ok(()))) => {
+                self.last_head = Instant::now();
+                // Several blocks might have been announced since the last
+                // poll; one poll takes care of all of them
+                while let Some(Some(Ok(()))) = heads.next().now_or_never() {}
+                return;
+            }
+            Ok(Some(Err(e))) => e,
+            Ok(None) => "the subscription ended".to_string(),
+            Err(_) if self.last_head.elapsed() < NEW_HEADS_TIMEOUT => return,
+            Err(_) => format!("no new head in {} seconds", NEW_HEADS_TIMEOUT.as_secs()),
+        };
+        warn!(logger, "Lost subscription to new heads, polling instead";
+              "error" => error);
+        self.heads = None;
+        self.last_attempt = Some(Instant::now());
+    }
+}
 
 pub struct PollingBlockIngestor {
     logger: Logger,
@@ -204,6 +350,14 @@ impl PollingBlockIngestor {
 #[async_trait]
 impl BlockIngestor for PollingBlockIngestor {
     async fn run(self: Box<Self>) {
+        let mut new_heads = if ENV_VARS.subscribe_new_heads {
+            self.eth_adapter
+                .ws_url()
+                .map(|url| NewHeadsSubscription::new(url.to_string()))
+        } else {
+            None
+        };
+
         loop {
             match self.do_poll().await {
                 // Some polls will fail due to transient issues
@@ -220,7 +374,10 @@ impl BlockIngestor for PollingBlockIngestor {
                 self.cleanup_cached_blocks()
             }
 
-            tokio::time::sleep(self.polling_interval).await;
+            match new_heads.as_mut() {
+                Some(new_heads) => new_heads.wait(&self.logger, self.polling_interval).await,
+                None => tokio::time::sleep(self.polling_interval).await,
+            }
         }
     }
 
@@ -228,3 +385,62 @@ impl BlockIngestor for PollingBlockIngestor {
         self.network_name.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use graph::futures03::{channel::mpsc, future};
+    use graph::log::logger;
+
+    use super::*;
+
+    const POLLING_INTERVAL: Duration = Duration::from_millis(50);
+
+    /// Wait once and return how long that took
+    async fn wait(new_heads: &mut NewHeadsSubscription) -> Duration {
+        let start = Instant::now();
+        new_heads.wait(&logger(false), POLLING_INTERVAL).await;
+        start.elapsed()
+    }
+
+    #[tokio::test]
+    async fn polls_while_subscribing_hangs() {
+        let mut new_heads = NewHeadsSubscription::with_subscribe(Arc::new(|| {
+            future::pending::<Result<NewHeads, String>>().boxed()
+        }));
+        for _ in 0..3 {
+            assert!(wait(&mut new_heads).await < NEW_HEADS_TIMEOUT / 10);
+        }
+        assert!(new_heads.heads.is_none());
+    }
+
+    #[tokio::test]
+    async fn wakes_up_for_new_heads() {
+        let (sender, receiver) = mpsc::unbounded();
+        let receiver = std::sync::Mutex::new(Some(receiver));
+        let mut new_heads = NewHeadsSubscription::with_subscribe(Arc::new(move || {
+            let heads = receiver.lock().unwrap().take().unwrap();
+            future::ok::<NewHeads, String>(heads.boxed()).boxed()
+        }));
+
+        // The subscription is set up in the background
+        assert!(wait(&mut new_heads).await >= POLLING_INTERVAL);
+        tokio::time::sleep(POLLING_INTERVAL).await;
+
+        // A new head ends the wait right away; so do several new heads
+        sender.unbounded_send(Ok(())).unwrap();
+        assert!(wait(&mut new_heads).await < POLLING_INTERVAL);
+        sender.unbounded_send(Ok(())).unwrap();
+        sender.unbounded_send(Ok(())).unwrap();
+        assert!(wait(&mut new_heads).await < POLLING_INTERVAL);
+
+        // A silent subscription does not hold up polling, and we keep it
+        assert!(wait(&mut new_heads).await < NEW_HEADS_TIMEOUT / 10);
+        assert!(new_heads.heads.is_some());
+
+        // Once the subscription ends, we go back to polling
+        drop(sender);
+        wait(&mut new_heads).await;
+        assert!(new_heads.heads.is_none());
+        assert!(wait(&mut new_heads).await >= POLLING_INTERVAL);
+    }
+}
@@ -1,22 +1,29 @@
 mod adapter;
+mod call_tracer;
 mod capabilities;
 pub mod codec;
 mod data_source;
 mod env;
 mod ethereum_adapter;
+mod event_filter;
+mod extraction_pool;
 mod ingestor;
 pub mod runtime;
+mod throttle;
 mod transport;
 
 pub use self::capabilities::NodeCapabilities;
-pub use self::ethereum_adapter::EthereumAdapter;
+pub use self::ethereum_adapter::{EthereumAdapter, ProviderHealth, ProviderLimits};
+pub use self::extraction_pool::{ExtractionKind, ExtractionPool};
 pub use self::runtime::RuntimeAdapter;
-pub use self::transport::Transport;
-pub use env::ENV_VARS;
+pub use self::throttle::{DeploymentThrottles, RequestThrottle};
+pub use self::transport::{Fixtures, Transport, TransportBackend};
+pub use env::{EnvVars, ENV_VARS};
 
 // ETHDEP: These concrete types should probably not be exposed.
 pub use data_source::{
-    BlockHandlerFilter, DataSource, DataSourceTemplate, Mapping, MappingABI, TemplateSource,
+    BlockHandlerFilter, DataSource, DataSourceTemplate, Mapping, MappingABI, MappingBlockHandler,
+    MappingEventHandler, TemplateSource,
 };
 
 pub mod chain;
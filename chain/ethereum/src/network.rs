@@ -7,13 +7,15 @@ use graph::prelude::rand::{self, Rng};
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 pub use graph::impl_slog_value;
 use graph::prelude::Error;
 
 use crate::adapter::EthereumAdapter as _;
 use crate::capabilities::NodeCapabilities;
-use crate::EthereumAdapter;
+use crate::ethereum_adapter::ProviderHealth;
+use crate::{EthereumAdapter, ENV_VARS};
 
 pub const DEFAULT_ADAPTER_ERROR_RETEST_PERCENT: f64 = 0.2;
 
@@ -41,9 +43,31 @@ impl EthereumNetworkAdapter {
     pub fn current_error_count(&self) -> u64 {
         self.endpoint_metrics.get_count(&self.provider().into())
     }
+
+    /// Whether this adapter has failed so often recently that other
+    /// adapters should be used instead of it while it recovers
+    fn is_cooling_down(&self, min_errors: u64, cooldown: Duration) -> bool {
+        min_errors > 0
+            && self.current_error_count() >= min_errors
+            && self
+                .endpoint_metrics
+                .since_last_failure(&self.provider().into())
+                .map_or(false, |since| since < cooldown)
+    }
+
+    /// Whether this adapter should not be used while other adapters are
+    /// healthy because it is syncing or runs a broken client version
+    fn is_quarantined(&self, quarantine: bool) -> bool {
+        quarantine && self.adapter.is_unhealthy()
+    }
+
     pub fn provider(&self) -> &str {
         self.adapter.provider()
     }
+
+    pub fn health(&self) -> Option<ProviderHealth> {
+        self.adapter.health()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -79,6 +103,47 @@ impl EthereumNetworkAdapters {
     pub fn all_cheapest_with(
         &self,
         required_capabilities: &NodeCapabilities,
+    ) -> impl Iterator<Item = &EthereumNetworkAdapter> + '_ {
+        self.all_cheapest_with_quarantine(
+            required_capabilities,
+            ENV_VARS.quarantine_unhealthy_providers,
+        )
+    }
+
+    /// Like `all_cheapest_with`, but only avoids unhealthy adapters if
+    /// `quarantine` is set
+    fn all_cheapest_with_quarantine(
+        &self,
+        required_capabilities: &NodeCapabilities,
+        quarantine: bool,
+    ) -> impl Iterator<Item = &EthereumNetworkAdapter> + '_ {
+        let mut available: Vec<_> = self.cheapest_candidates(required_capabilities).collect();
+        // Quarantined adapters are only used if there is no other choice
+        if !available
+            .iter()
+            .all(|adapter| adapter.is_quarantined(quarantine))
+        {
+            available.retain(|adapter| !adapter.is_quarantined(quarantine));
+        }
+        let cooling_down = |adapter: &&EthereumNetworkAdapter| {
+            adapter.is_cooling_down(
+                ENV_VARS.provider_cooldown_errors,
+                ENV_VARS.provider_cooldown,
+            )
+        };
+        // Adapters that are cooling down are only used if there is no
+        // other choice
+        let all_cooling_down = available.iter().all(cooling_down);
+        available
+            .into_iter()
+            .filter(move |adapter| all_cooling_down || !cooling_down(adapter))
+    }
+
+    /// Like `all_cheapest_with`, but including adapters that are cooling
+    /// down after errors
+    fn cheapest_candidates(
+        &self,
+        required_capabilities: &NodeCapabilities,
     ) -> impl Iterator<Item = &EthereumNetworkAdapter> + '_ {
         let cheapest_sufficient_capability = self
             .adapters
@@ -123,6 +188,36 @@ impl EthereumNetworkAdapters {
         ))
     }
 
+    /// The adapters that a scan for logs can be split across: `adapter`
+    /// comes first, followed by up to `max - 1` other adapters with the
+    /// same capabilities, preferring the ones with the fewest errors
+    pub fn log_scan_adapters(
+        &self,
+        adapter: &Arc<EthereumAdapter>,
+        required_capabilities: &NodeCapabilities,
+        max: usize,
+    ) -> Vec<Arc<EthereumAdapter>> {
+        let mut others: Vec<_> = self
+            .all_cheapest_with(required_capabilities)
+            .filter(|other| other.provider() != adapter.provider())
+            .collect();
+        others.sort_by_key(|other| other.current_error_count());
+
+        std::iter::once(adapter.cheap_clone())
+            .chain(
+                others
+                    .into_iter()
+                    .take(max.saturating_sub(1))
+                    .map(|other| other.adapter.cheap_clone()),
+            )
+            .collect()
+    }
+
+    /// All adapters, including the ones that are only used for calls
+    pub fn all(&self) -> impl Iterator<Item = &EthereumNetworkAdapter> + '_ {
+        self.adapters.iter().chain(self.call_only_adapters.iter())
+    }
+
     pub fn cheapest(&self) -> Option<Arc<EthereumAdapter>> {
         // EthereumAdapters are sorted by their NodeCapabilities when the EthereumNetworks
         // struct is instantiated so they do not need to be sorted here
@@ -281,8 +376,10 @@ mod tests {
     use std::sync::Arc;
     use uuid::Uuid;
 
+    use crate::transport::Fixtures;
     use crate::{
-        EthereumAdapter, EthereumAdapterTrait, EthereumNetworks, ProviderEthRpcMetrics, Transport,
+        EthereumAdapter, EthereumAdapterTrait, EthereumNetworks, ProviderEthRpcMetrics,
+        ProviderLimits, Transport, ENV_VARS,
     };
 
     use super::{EthereumNetworkAdapter, EthereumNetworkAdapters, NodeCapabilities};
@@ -364,6 +461,7 @@ mod tests {
                 provider_metrics.clone(),
                 true,
                 true,
+                ProviderLimits::default(),
             )
             .await,
         );
@@ -376,6 +474,7 @@ mod tests {
                 provider_metrics.clone(),
                 true,
                 false,
+                ProviderLimits::default(),
             )
             .await,
         );
@@ -470,6 +569,7 @@ mod tests {
                 provider_metrics.clone(),
                 true,
                 true,
+                ProviderLimits::default(),
             )
             .await,
         );
@@ -482,6 +582,7 @@ mod tests {
                 provider_metrics.clone(),
                 true,
                 false,
+                ProviderLimits::default(),
             )
             .await,
         );
@@ -541,6 +642,7 @@ mod tests {
                 provider_metrics.clone(),
                 true,
                 true,
+                ProviderLimits::default(),
             )
             .await,
         );
@@ -553,6 +655,7 @@ mod tests {
                 provider_metrics.clone(),
                 true,
                 false,
+                ProviderLimits::default(),
             )
             .await,
         );
@@ -610,6 +713,7 @@ mod tests {
                 provider_metrics.clone(),
                 true,
                 false,
+                ProviderLimits::default(),
             )
             .await,
         );
@@ -830,6 +934,129 @@ mod tests {
         assert!(res.is_err(), "{:?}", res);
     }
 
+    #[tokio::test]
+    async fn eth_adapter_selection_cooldown() {
+        let logger = Logger::root(Discard, o!());
+        let failing_provider = Uuid::new_v4().to_string();
+        let other_provider = Uuid::new_v4().to_string();
+
+        let mock_registry = Arc::new(MetricsRegistry::mock());
+        let metrics = Arc::new(EndpointMetrics::new(
+            logger,
+            &[failing_provider.clone(), other_provider.clone()],
+            mock_registry.clone(),
+        ));
+        let logger = graph::log::logger(true);
+        let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(mock_registry.clone()));
+        let capabilities = NodeCapabilities {
+            archive: true,
+            traces: false,
+        };
+
+        // Always retesting would pick the provider with the most errors
+        // unless it is cooling down
+        let mut adapters = EthereumNetworkAdapters::new(Some(1f64));
+        for provider in [&failing_provider, &other_provider] {
+            adapters.adapters.push(EthereumNetworkAdapter {
+                endpoint_metrics: metrics.clone(),
+                capabilities,
+                adapter: fake_adapter(&logger, provider, &provider_metrics, &metrics, false).await,
+                limit: SubgraphLimit::Unlimited,
+            });
+        }
+        for _ in 0..ENV_VARS.provider_cooldown_errors {
+            metrics.report_for_test(&Provider::from(failing_provider.clone()), false);
+        }
+        metrics.report_for_test(&Provider::from(other_provider.clone()), false);
+        assert_eq!(
+            adapters.cheapest_with(&capabilities).unwrap().provider(),
+            other_provider
+        );
+
+        // When all adapters are cooling down, they are all used again
+        for _ in 0..ENV_VARS.provider_cooldown_errors {
+            metrics.report_for_test(&Provider::from(other_provider.clone()), false);
+        }
+        assert_eq!(adapters.all_cheapest_with(&capabilities).count(), 2);
+    }
+
+    #[tokio::test]
+    async fn eth_adapter_selection_quarantine() {
+        let logger = Logger::root(Discard, o!());
+        let syncing_provider = Uuid::new_v4().to_string();
+        let healthy_provider = Uuid::new_v4().to_string();
+
+        let mock_registry = Arc::new(MetricsRegistry::mock());
+        let metrics = Arc::new(EndpointMetrics::new(
+            logger,
+            &[syncing_provider.clone(), healthy_provider.clone()],
+            mock_registry.clone(),
+        ));
+        let logger = graph::log::logger(true);
+        let provider_metrics = Arc::new(ProviderEthRpcMetrics::new(mock_registry.clone()));
+        let capabilities = NodeCapabilities {
+            archive: true,
+            traces: false,
+        };
+
+        let mut adapters = EthereumNetworkAdapters::new(Some(0f64));
+        for (provider, sync_state) in [
+            (
+                &syncing_provider,
+                r#"{ "startingBlock": "0x0", "currentBlock": "0x5", "highestBlock": "0x10" }"#,
+            ),
+            (&healthy_provider, "false"),
+        ] {
+            let fixtures = Fixtures::from_json(&format!(
+                r#"[{{ "method": "web3_clientVersion", "result": "Geth/v1.13.5" }},
+                    {{ "method": "eth_syncing", "result": {} }}]"#,
+                sync_state
+            ))
+            .unwrap();
+            let adapter = EthereumAdapter::new(
+                logger.clone(),
+                provider.to_string(),
+                Transport::new_custom(Arc::new(fixtures)),
+                provider_metrics.clone(),
+                true,
+                false,
+                ProviderLimits::default(),
+            )
+            .await;
+            adapter.check_health().await;
+            adapters.adapters.push(EthereumNetworkAdapter {
+                endpoint_metrics: metrics.clone(),
+                capabilities,
+                adapter: Arc::new(adapter),
+                limit: SubgraphLimit::Unlimited,
+            });
+        }
+
+        // Unhealthy adapters are only avoided when quarantining is on
+        assert_eq!(
+            adapters
+                .all_cheapest_with_quarantine(&capabilities, false)
+                .count(),
+            2
+        );
+        let selected: Vec<_> = adapters
+            .all_cheapest_with_quarantine(&capabilities, true)
+            .map(|adapter| adapter.provider())
+            .collect();
+        assert_eq!(selected, vec![healthy_provider.as_str()]);
+
+        // When all adapters are unhealthy, they are all used
+        adapters
+            .adapters
+            .retain(|adapter| adapter.provider() == syncing_provider);
+        assert_eq!(
+            adapters
+                .all_cheapest_with_quarantine(&capabilities, true)
+                .count(),
+            1
+        );
+    }
+
     async fn fake_adapter(
         logger: &Logger,
         provider: &str,
@@ -852,6 +1079,7 @@ mod tests {
                 provider_metrics.clone(),
                 true,
                 call_only,
+                ProviderLimits::default(),
             )
             .await,
         )
@@ -1,4 +1,5 @@
 use super::runtime_adapter::UnresolvedContractCall;
+use super::token::TokenUriCall;
 use crate::trigger::{
     EthereumBlockData, EthereumCallData, EthereumEventData, EthereumTransactionData,
 };
@@ -176,6 +177,31 @@ impl FromAscObj<AscUnresolvedContractCall> for UnresolvedContractCall {
     }
 }
 
+#[repr(C)]
+#[derive(AscType)]
+pub struct AscTokenUriCall {
+    pub token_address: AscPtr<AscAddress>,
+    pub token_id: AscPtr<AscBigInt>,
+}
+
+impl AscIndexId for AscTokenUriCall {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::EthereumTokenUriCall;
+}
+
+impl FromAscObj<AscTokenUriCall> for TokenUriCall {
+    fn from_asc_obj<H: AscHeap + ?Sized>(
+        asc_call: AscTokenUriCall,
+        heap: &H,
+        gas: &GasCounter,
+        depth: usize,
+    ) -> Result<Self, DeterministicHostError> {
+        Ok(TokenUriCall {
+            token_address: asc_get(heap, asc_call.token_address, gas, depth)?,
+            token_id: asc_get(heap, asc_call.token_id, gas, depth)?,
+        })
+    }
+}
+
 #[repr(C)]
 #[derive(AscType)]
 pub(crate) struct AscEthereumBlock {
@@ -365,6 +391,44 @@ impl AscIndexId for AscEthereumEvent_0_0_7<AscEthereumTransaction_0_0_6, AscEthe
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::EthereumEvent;
 }
 
+/// The events passed to a batch event handler.
+pub(crate) struct AscEthereumEventArray_0_0_7(
+    Array<AscPtr<AscEthereumEvent_0_0_7<AscEthereumTransaction_0_0_6, AscEthereumBlock_0_0_6>>>,
+);
+
+impl AscType for AscEthereumEventArray_0_0_7 {
+    fn to_asc_bytes(&self) -> Result<Vec<u8>, DeterministicHostError> {
+        self.0.to_asc_bytes()
+    }
+
+    fn from_asc_bytes(
+        asc_obj: &[u8],
+        api_version: &Version,
+    ) -> Result<Self, DeterministicHostError> {
+        Ok(Self(Array::from_asc_bytes(asc_obj, api_version)?))
+    }
+}
+
+impl ToAscObj<AscEthereumEventArray_0_0_7>
+    for Vec<(EthereumEventData, Option<&TransactionReceipt>)>
+{
+    fn to_asc_obj<H: AscHeap + ?Sized>(
+        &self,
+        heap: &mut H,
+        gas: &GasCounter,
+    ) -> Result<AscEthereumEventArray_0_0_7, HostExportError> {
+        let events = self
+            .iter()
+            .map(|event| asc_new(heap, event, gas))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(AscEthereumEventArray_0_0_7(Array::new(&events, heap, gas)?))
+    }
+}
+
+impl AscIndexId for AscEthereumEventArray_0_0_7 {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::ArrayEthereumEvent;
+}
+
 #[repr(C)]
 #[derive(AscType)]
 pub(crate) struct AscLogParam {
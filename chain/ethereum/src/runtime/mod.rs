@@ -2,3 +2,4 @@ pub use runtime_adapter::RuntimeAdapter;
 
 pub mod abi;
 pub mod runtime_adapter;
+pub mod token;
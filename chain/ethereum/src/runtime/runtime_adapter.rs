@@ -3,8 +3,8 @@ use std::{sync::Arc, time::Instant};
 use crate::data_source::MappingABI;
 use crate::{
     capabilities::NodeCapabilities, network::EthereumNetworkAdapters, Chain, DataSource,
-    EthereumAdapter, EthereumAdapterTrait, EthereumContractCall, EthereumContractCallError,
-    ENV_VARS,
+    DeploymentThrottles, EthereumAdapter, EthereumAdapterTrait, EthereumContractCall,
+    EthereumContractCallError, RequestThrottle, ENV_VARS,
 };
 use anyhow::{Context, Error};
 use blockchain::HostFn;
@@ -23,9 +23,10 @@ use graph::{
     semver::Version,
     slog::{info, trace, Logger},
 };
-use graph_runtime_wasm::asc_abi::class::{AscEnumArray, EthereumValueKind};
+use graph_runtime_wasm::asc_abi::class::{AscAddress, AscEnumArray, EthereumValueKind};
 
 use super::abi::{AscUnresolvedContractCall, AscUnresolvedContractCall_0_0_4};
+use super::token;
 
 /// Gas limit for `eth_call`. The value of 50_000_000 is a protocol-wide parameter so this
 /// should be changed only for debugging purposes and never on an indexer in the network. This
@@ -51,6 +52,7 @@ pub struct RuntimeAdapter {
     pub eth_adapters: Arc<EthereumNetworkAdapters>,
     pub call_cache: Arc<dyn EthereumCallCache>,
     pub chain_identifier: Arc<ChainIdentifier>,
+    pub throttles: Arc<DeploymentThrottles>,
 }
 
 impl blockchain::RuntimeAdapter<Chain> for RuntimeAdapter {
@@ -91,7 +93,143 @@ impl blockchain::RuntimeAdapter<Chain> for RuntimeAdapter {
             }),
         };
 
-        Ok(vec![ethereum_call])
+        let eth_adapters = self.eth_adapters.cheap_clone();
+        let ethereum_has_code = HostFn {
+            name: "ethereum.hasCode",
+            func: Arc::new(move |ctx, wasm_ptr| {
+                let eth_adapter = eth_adapters.call_or_cheapest(Some(&NodeCapabilities {
+                    archive,
+                    traces: false,
+                }))?;
+                ethereum_has_code(&eth_adapter, ctx, wasm_ptr).map(|has_code| has_code as u32)
+            }),
+        };
+
+        let mut host_fns = vec![ethereum_call, ethereum_has_code];
+        if ENV_VARS.token_host_fns {
+            host_fns.extend(token_host_fns(
+                self.eth_adapters.cheap_clone(),
+                self.call_cache.cheap_clone(),
+                archive,
+                eth_call_gas,
+            ));
+        }
+
+        Ok(host_fns
+            .into_iter()
+            .map(|host_fn| throttled(host_fn, self.throttles.cheap_clone()))
+            .collect())
+    }
+}
+
+/// Make the requests that `host_fn` sends count against the request
+/// budget of the deployment that calls it
+fn throttled(host_fn: HostFn, throttles: Arc<DeploymentThrottles>) -> HostFn {
+    let HostFn { name, func } = host_fn;
+    HostFn {
+        name,
+        func: Arc::new(move |ctx, wasm_ptr| {
+            let throttle = throttles.get(ctx.deployment);
+            RequestThrottle::run_blocking(throttle.as_ref(), || func(ctx, wasm_ptr))
+        }),
+    }
+}
+
+/// The host fns from the `token` module, which are only available when
+/// `GRAPH_ETHEREUM_TOKEN_HOST_FNS` is set.
+fn token_host_fns(
+    eth_adapters: Arc<EthereumNetworkAdapters>,
+    call_cache: Arc<dyn EthereumCallCache>,
+    archive: bool,
+    eth_call_gas: Option<u32>,
+) -> Vec<HostFn> {
+    type TokenFn = fn(
+        &EthereumAdapter,
+        Arc<dyn EthereumCallCache>,
+        HostFnCtx<'_>,
+        u32,
+        Option<u32>,
+    ) -> Result<u32, HostExportError>;
+
+    let fns: [(&'static str, TokenFn); 4] = [
+        ("ethereum.tokenName", |adapter, cache, ctx, ptr, gas| {
+            token::ethereum_token_name(adapter, cache, ctx, ptr, gas).map(|ptr| ptr.wasm_ptr())
+        }),
+        ("ethereum.tokenSymbol", |adapter, cache, ctx, ptr, gas| {
+            token::ethereum_token_symbol(adapter, cache, ctx, ptr, gas).map(|ptr| ptr.wasm_ptr())
+        }),
+        ("ethereum.tokenDecimals", |adapter, cache, ctx, ptr, gas| {
+            token::ethereum_token_decimals(adapter, cache, ctx, ptr, gas)
+                .map(|decimals| decimals as u32)
+        }),
+        ("ethereum.tokenURI", |adapter, cache, ctx, ptr, gas| {
+            token::ethereum_token_uri(adapter, cache, ctx, ptr, gas).map(|ptr| ptr.wasm_ptr())
+        }),
+    ];
+
+    fns.into_iter()
+        .map(|(name, token_fn)| {
+            let eth_adapters = eth_adapters.cheap_clone();
+            let call_cache = call_cache.cheap_clone();
+            HostFn {
+                name,
+                func: Arc::new(move |ctx, wasm_ptr| {
+                    let eth_adapter = eth_adapters.call_or_cheapest(Some(&NodeCapabilities {
+                        archive,
+                        traces: false,
+                    }))?;
+                    token_fn(
+                        &eth_adapter,
+                        call_cache.cheap_clone(),
+                        ctx,
+                        wasm_ptr,
+                        eth_call_gas,
+                    )
+                }),
+            }
+        })
+        .collect()
+}
+
+/// function ethereum.hasCode(address: Address): boolean
+fn ethereum_has_code(
+    eth_adapter: &EthereumAdapter,
+    ctx: HostFnCtx<'_>,
+    wasm_ptr: u32,
+) -> Result<bool, HostExportError> {
+    ctx.gas
+        .consume_host_fn_with_metrics(ETHEREUM_CALL, "ethereum_has_code")?;
+
+    let address: Address = asc_get::<_, AscAddress, _>(ctx.heap, wasm_ptr.into(), &ctx.gas, 0)?;
+
+    let result = graph::block_on(
+        eth_adapter
+            .has_code(&ctx.logger, address, ctx.block_ptr.cheap_clone())
+            .compat(),
+    );
+    match result {
+        Ok(has_code) => Ok(has_code),
+
+        // As for `ethereum.call`, errors from the Ethereum node could be due
+        // to the block no longer being on the main chain.
+        Err(EthereumContractCallError::Web3Error(e)) => {
+            Err(HostExportError::PossibleReorg(anyhow::anyhow!(
+                "Ethereum node returned an error when checking code of address {}: {}",
+                address,
+                e
+            )))
+        }
+        Err(EthereumContractCallError::Timeout) => {
+            Err(HostExportError::PossibleReorg(anyhow::anyhow!(
+                "Ethereum node did not respond when checking code of address {}",
+                address
+            )))
+        }
+        Err(e) => Err(HostExportError::Unknown(anyhow::anyhow!(
+            "Failed to check code of address {}: {}",
+            address,
+            e
+        ))),
     }
 }
 
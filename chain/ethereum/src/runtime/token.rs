@@ -0,0 +1,349 @@
+//! Host functions that read the metadata of ERC-20, ERC-721 and ERC-1155
+//! tokens. Many token contracts deviate from the standards, for example by
+//! returning `bytes32` instead of `string` from `name()` or by not
+//! implementing optional methods at all; these functions deal with that so
+//! that mappings get either a usable value or `null`.
+
+use std::sync::Arc;
+
+use graph::{
+    blockchain::{BlockPtr, HostFnCtx},
+    cheap_clone::CheapClone,
+    prelude::{
+        ethabi::{self, Address, Contract, Function, ParamType, Token},
+        lazy_static, serde_json,
+        web3::types::U256,
+        BigInt, EthereumCallCache, Future01CompatExt,
+    },
+    runtime::{asc_get, asc_new, AscPtr, HostExportError},
+    slog::{info, Logger},
+};
+use graph_runtime_wasm::asc_abi::class::{AscAddress, AscString};
+
+use super::abi::AscTokenUriCall;
+use super::runtime_adapter::ETHEREUM_CALL;
+use crate::{EthereumAdapter, EthereumContractCall, EthereumContractCallError};
+
+lazy_static! {
+    /// The metadata methods of ERC-20, ERC-721 and ERC-1155. Only the inputs
+    /// are used; outputs are decoded by hand to tolerate non-standard
+    /// return types.
+    static ref TOKEN_METADATA: Contract = serde_json::from_value(serde_json::json!([
+        { "type": "function", "name": "name", "inputs": [], "outputs": [{ "name": "", "type": "string" }], "stateMutability": "view" },
+        { "type": "function", "name": "symbol", "inputs": [], "outputs": [{ "name": "", "type": "string" }], "stateMutability": "view" },
+        { "type": "function", "name": "decimals", "inputs": [], "outputs": [{ "name": "", "type": "uint8" }], "stateMutability": "view" },
+        { "type": "function", "name": "tokenURI", "inputs": [{ "name": "tokenId", "type": "uint256" }], "outputs": [{ "name": "", "type": "string" }], "stateMutability": "view" },
+        { "type": "function", "name": "uri", "inputs": [{ "name": "id", "type": "uint256" }], "outputs": [{ "name": "", "type": "string" }], "stateMutability": "view" }
+    ]))
+    .expect("token metadata ABI is valid");
+}
+
+fn metadata_function(name: &str) -> &'static Function {
+    TOKEN_METADATA
+        .function(name)
+        .expect("token metadata ABI contains all metadata functions")
+}
+
+#[derive(Clone, Debug)]
+pub struct TokenUriCall {
+    pub token_address: Address,
+    pub token_id: BigInt,
+}
+
+/// function ethereum.tokenName(address: Address): string | null
+pub(crate) fn ethereum_token_name(
+    eth_adapter: &EthereumAdapter,
+    call_cache: Arc<dyn EthereumCallCache>,
+    ctx: HostFnCtx<'_>,
+    wasm_ptr: u32,
+    eth_call_gas: Option<u32>,
+) -> Result<AscPtr<AscString>, HostExportError> {
+    token_string(
+        eth_adapter,
+        call_cache,
+        ctx,
+        wasm_ptr,
+        eth_call_gas,
+        "name",
+        "ethereum_token_name",
+    )
+}
+
+/// function ethereum.tokenSymbol(address: Address): string | null
+pub(crate) fn ethereum_token_symbol(
+    eth_adapter: &EthereumAdapter,
+    call_cache: Arc<dyn EthereumCallCache>,
+    ctx: HostFnCtx<'_>,
+    wasm_ptr: u32,
+    eth_call_gas: Option<u32>,
+) -> Result<AscPtr<AscString>, HostExportError> {
+    token_string(
+        eth_adapter,
+        call_cache,
+        ctx,
+        wasm_ptr,
+        eth_call_gas,
+        "symbol",
+        "ethereum_token_symbol",
+    )
+}
+
+fn token_string(
+    eth_adapter: &EthereumAdapter,
+    call_cache: Arc<dyn EthereumCallCache>,
+    ctx: HostFnCtx<'_>,
+    wasm_ptr: u32,
+    eth_call_gas: Option<u32>,
+    method: &str,
+    metric: &str,
+) -> Result<AscPtr<AscString>, HostExportError> {
+    ctx.gas
+        .consume_host_fn_with_metrics(ETHEREUM_CALL, metric)?;
+
+    let address: Address = asc_get::<_, AscAddress, _>(ctx.heap, wasm_ptr.into(), &ctx.gas, 0)?;
+
+    let output = token_call(
+        eth_adapter,
+        call_cache,
+        &ctx.logger,
+        &ctx.block_ptr,
+        address,
+        metadata_function(method),
+        vec![],
+        eth_call_gas,
+    )?;
+    match output.as_deref().and_then(decode_string) {
+        Some(value) => Ok(asc_new(ctx.heap, value.as_str(), &ctx.gas)?),
+        None => Ok(AscPtr::null()),
+    }
+}
+
+/// function ethereum.tokenDecimals(address: Address): i32
+///
+/// Returns -1 if the token does not report a usable number of decimals.
+pub(crate) fn ethereum_token_decimals(
+    eth_adapter: &EthereumAdapter,
+    call_cache: Arc<dyn EthereumCallCache>,
+    ctx: HostFnCtx<'_>,
+    wasm_ptr: u32,
+    eth_call_gas: Option<u32>,
+) -> Result<i32, HostExportError> {
+    ctx.gas
+        .consume_host_fn_with_metrics(ETHEREUM_CALL, "ethereum_token_decimals")?;
+
+    let address: Address = asc_get::<_, AscAddress, _>(ctx.heap, wasm_ptr.into(), &ctx.gas, 0)?;
+
+    let output = token_call(
+        eth_adapter,
+        call_cache,
+        &ctx.logger,
+        &ctx.block_ptr,
+        address,
+        metadata_function("decimals"),
+        vec![],
+        eth_call_gas,
+    )?;
+    Ok(output
+        .as_deref()
+        .and_then(decode_decimals)
+        .map_or(-1, i32::from))
+}
+
+/// function ethereum.tokenURI(call: TokenURICall): string | null
+///
+/// Tries the ERC-721 `tokenURI(uint256)` first and falls back to the
+/// ERC-1155 `uri(uint256)`, in which case the `{id}` placeholder is
+/// replaced with the token id.
+pub(crate) fn ethereum_token_uri(
+    eth_adapter: &EthereumAdapter,
+    call_cache: Arc<dyn EthereumCallCache>,
+    ctx: HostFnCtx<'_>,
+    wasm_ptr: u32,
+    eth_call_gas: Option<u32>,
+) -> Result<AscPtr<AscString>, HostExportError> {
+    ctx.gas
+        .consume_host_fn_with_metrics(ETHEREUM_CALL, "ethereum_token_uri")?;
+
+    let call: TokenUriCall =
+        asc_get::<_, AscTokenUriCall, _>(ctx.heap, wasm_ptr.into(), &ctx.gas, 0)?;
+    if call.token_id < BigInt::from(0) || call.token_id.bits() > 256 {
+        return Err(HostExportError::Deterministic(anyhow::anyhow!(
+            "token id {} passed to ethereum.tokenURI is not a uint256",
+            call.token_id
+        )));
+    }
+    let token_id = call.token_id.to_unsigned_u256();
+
+    let erc721 = token_call(
+        eth_adapter,
+        call_cache.cheap_clone(),
+        &ctx.logger,
+        &ctx.block_ptr,
+        call.token_address,
+        metadata_function("tokenURI"),
+        vec![Token::Uint(token_id)],
+        eth_call_gas,
+    )?;
+    let uri = match erc721.as_deref().and_then(decode_string) {
+        Some(uri) => Some(uri),
+        None => token_call(
+            eth_adapter,
+            call_cache,
+            &ctx.logger,
+            &ctx.block_ptr,
+            call.token_address,
+            metadata_function("uri"),
+            vec![Token::Uint(token_id)],
+            eth_call_gas,
+        )?
+        .as_deref()
+        .and_then(decode_string)
+        .map(|uri| expand_erc1155_uri(&uri, token_id)),
+    };
+    match uri {
+        Some(uri) => Ok(asc_new(ctx.heap, uri.as_str(), &ctx.gas)?),
+        None => Ok(AscPtr::null()),
+    }
+}
+
+/// Call a metadata method through the call cache and return its raw output.
+/// Returns `Ok(None)` if the call was reverted, which includes the method
+/// not being implemented.
+fn token_call(
+    eth_adapter: &EthereumAdapter,
+    call_cache: Arc<dyn EthereumCallCache>,
+    logger: &Logger,
+    block_ptr: &BlockPtr,
+    address: Address,
+    function: &Function,
+    args: Vec<Token>,
+    eth_call_gas: Option<u32>,
+) -> Result<Option<Vec<u8>>, HostExportError> {
+    let call = EthereumContractCall {
+        address,
+        block_ptr: block_ptr.cheap_clone(),
+        function: function.clone(),
+        args,
+        gas: eth_call_gas,
+    };
+
+    let result = graph::block_on(
+        eth_adapter
+            .raw_contract_call(logger, call, call_cache)
+            .compat(),
+    );
+    match result {
+        Ok(output) => Ok(Some(output)),
+        Err(EthereumContractCallError::Revert(reason)) => {
+            info!(logger, "Token metadata call reverted";
+                  "address" => address.to_string(),
+                  "function" => &function.name,
+                  "reason" => reason);
+            Ok(None)
+        }
+
+        // As for `ethereum.call`, errors from the Ethereum node could be due
+        // to the block no longer being on the main chain.
+        Err(EthereumContractCallError::Web3Error(e)) => {
+            Err(HostExportError::PossibleReorg(anyhow::anyhow!(
+                "Ethereum node returned an error when calling `{}` of token {}: {}",
+                function.name,
+                address,
+                e
+            )))
+        }
+        Err(EthereumContractCallError::Timeout) => {
+            Err(HostExportError::PossibleReorg(anyhow::anyhow!(
+                "Ethereum node did not respond when calling `{}` of token {}",
+                function.name,
+                address
+            )))
+        }
+        Err(e) => Err(HostExportError::Unknown(anyhow::anyhow!(
+            "Failed to call `{}` of token {}: {}",
+            function.name,
+            address,
+            e
+        ))),
+    }
+}
+
+/// Decode the output of a method that should return a `string`. Some
+/// tokens, most prominently MKR, return a `bytes32` that holds the string
+/// padded with zero bytes instead. Returns `None` if the output is neither.
+fn decode_string(output: &[u8]) -> Option<String> {
+    match ethabi::decode(&[ParamType::String], output) {
+        Ok(mut tokens) => tokens.pop().and_then(Token::into_string),
+        Err(_) if output.len() == 32 => {
+            let len = output.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+            String::from_utf8(output[..len].to_vec()).ok()
+        }
+        Err(_) => None,
+    }
+}
+
+/// Decode the output of `decimals()`. The standard return type is `uint8`,
+/// but some tokens return a `uint256`; values that don't fit into a `uint8`
+/// are treated as unusable.
+fn decode_decimals(output: &[u8]) -> Option<u8> {
+    if output.len() < 32 {
+        return None;
+    }
+    let decimals = U256::from_big_endian(&output[..32]);
+    if decimals > U256::from(u8::MAX) {
+        return None;
+    }
+    Some(decimals.low_u32() as u8)
+}
+
+/// Substitute the ERC-1155 `{id}` placeholder with the token id as 64
+/// lowercase hex digits.
+fn expand_erc1155_uri(uri: &str, token_id: U256) -> String {
+    uri.replace("{id}", &format!("{:064x}", token_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use graph::prelude::{ethabi, web3::types::U256};
+
+    use super::{decode_decimals, decode_string, expand_erc1155_uri};
+
+    #[test]
+    fn decode_string_handles_string_and_bytes32() {
+        let standard = ethabi::encode(&[ethabi::Token::String("Wrapped Ether".to_string())]);
+        assert_eq!(Some("Wrapped Ether".to_string()), decode_string(&standard));
+
+        let mut bytes32 = [0u8; 32];
+        bytes32[..5].copy_from_slice(b"Maker");
+        assert_eq!(Some("Maker".to_string()), decode_string(&bytes32));
+
+        let mut invalid = [0u8; 32];
+        invalid[0] = 0xff;
+        assert_eq!(None, decode_string(&invalid));
+
+        assert_eq!(None, decode_string(&[1, 2, 3]));
+    }
+
+    #[test]
+    fn decode_decimals_rejects_large_values() {
+        let eighteen = ethabi::encode(&[ethabi::Token::Uint(U256::from(18))]);
+        assert_eq!(Some(18), decode_decimals(&eighteen));
+
+        let too_large = ethabi::encode(&[ethabi::Token::Uint(U256::from(256))]);
+        assert_eq!(None, decode_decimals(&too_large));
+
+        assert_eq!(None, decode_decimals(&[18]));
+    }
+
+    #[test]
+    fn expand_erc1155_uri_substitutes_id() {
+        assert_eq!(
+            "https://token-cdn-domain/000000000000000000000000000000000000000000000000000000000004cce0.json",
+            expand_erc1155_uri("https://token-cdn-domain/{id}.json", U256::from(314592))
+        );
+        assert_eq!(
+            "ipfs://abc",
+            expand_erc1155_uri("ipfs://abc", U256::from(1))
+        );
+    }
+}
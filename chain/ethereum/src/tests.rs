@@ -1,12 +1,20 @@
 use std::sync::Arc;
+use MaybeUninit;
 
 use graph::{
-    blockchain::{block_stream::BlockWithTriggers, BlockPtr},
+    anyhow::anyhow,
+    blockchain::{block_stream::BlockWithTriggers, BlockPtr, TriggerData as _},
+    data::subgraph::API_VERSION_0_0_6,
     prelude::{
         web3::types::{Address, Bytes, Log, H160, H256, U64},
         EthereumCall, LightEthereumBlock,
     },
+    runtime::{
+        gas::GasCounter, AscHeap, DeterministicHostError, HostExportError, IndexForAscTypeId,
+    },
+    semver::Version,
     slog::{self, o, Logger},
+    util::mem::init_slice,
 };
 
 use crate::{
@@ -208,3 +216,218 @@ fn test_trigger_dedup() {
         vec![log1, log2, call1, log3, call2, call3, block2, block1]
     );
 }
+
+#[test]
+fn test_trigger_ordering_across_kinds() {
+    let ptr = BlockPtr::from((H256::zero(), 1u64));
+    let block = |kind| EthereumTrigger::Block(ptr.clone(), kind);
+    let start = block(EthereumBlockTriggerType::Start);
+    let end = block(EthereumBlockTriggerType::End);
+    let call_to1 = block(EthereumBlockTriggerType::WithCallTo(
+        Address::from_low_u64_be(1),
+    ));
+    let call_to2 = block(EthereumBlockTriggerType::WithCallTo(
+        Address::from_low_u64_be(2),
+    ));
+
+    let call = |tx_index: u64, trace_address: Vec<usize>| {
+        let mut call = EthereumCall::default();
+        call.transaction_index = tx_index;
+        call.trace_address = trace_address;
+        EthereumTrigger::Call(Arc::new(call))
+    };
+    let call1 = call(1, vec![]);
+    let call2 = call(1, vec![0]);
+    let call3 = call(1, vec![0, 0]);
+    let call4 = call(1, vec![1]);
+    let call5 = call(2, vec![]);
+
+    let log = |tx_index: u64, log_index: u64| {
+        EthereumTrigger::Log(LogRef::FullLog(
+            Arc::new(Log {
+                address: H160::default(),
+                topics: vec![],
+                data: Bytes::default(),
+                block_hash: Some(H256::zero()),
+                block_number: Some(U64::one()),
+                transaction_hash: Some(H256::from_low_u64_be(tx_index)),
+                transaction_index: Some(tx_index.into()),
+                log_index: Some(log_index.into()),
+                transaction_log_index: Some(log_index.into()),
+                log_type: Some("".into()),
+                removed: Some(false),
+            }),
+            None,
+        ))
+    };
+    let log1 = log(0, 0);
+    let log2 = log(1, 1);
+    let log3 = log(1, 2);
+    let log4 = log(2, 3);
+
+    let expected = vec![
+        start, log1, log2, log3, call1, call2, call3, call4, log4, call5, call_to1, call_to2, end,
+    ];
+
+    // The order in which deployments with spec version 1.0.0 process
+    // triggers does not depend on the order in which they were collected
+    let logger = Logger::root(slog::Discard, o!());
+    let mut b: LightEthereumBlock = Default::default();
+    b.number = Some(Default::default());
+    b.hash = Some(Default::default());
+    let b = Arc::new(b);
+    for triggers in [
+        expected.clone(),
+        expected.iter().rev().cloned().collect(),
+        expected
+            .iter()
+            .skip(5)
+            .chain(expected.iter().take(5))
+            .cloned()
+            .collect(),
+    ] {
+        let mut triggers = BlockWithTriggers::<crate::Chain>::new(
+            BlockFinality::Final(b.clone()),
+            triggers,
+            &logger,
+        )
+        .trigger_data;
+        triggers.sort_by(|a, b| a.cmp_position(b));
+        assert_eq!(triggers, expected);
+    }
+}
+
+/// A heap that puts objects one after the other into a vector of bytes
+struct TestHeap {
+    memory: Vec<u8>,
+}
+
+impl AscHeap for TestHeap {
+    fn raw_new(&mut self, bytes: &[u8], _gas: &GasCounter) -> Result<u32, DeterministicHostError> {
+        self.memory.extend_from_slice(bytes);
+        Ok((self.memory.len() - bytes.len()) as u32)
+    }
+
+    fn read<'a>(
+        &self,
+        offset: u32,
+        buffer: &'a mut [MaybeUninit<u8>],
+        _gas: &GasCounter,
+    ) -> Result<&'a mut [u8], DeterministicHostError> {
+        let start = offset as usize;
+        let src = self
+            .memory
+            .get(start..start + buffer.len())
+            .ok_or_else(|| {
+                anyhow!(
+                    "read of {} bytes at {} is out of bounds",
+                    buffer.len(),
+                    start
+                )
+            })?;
+        Ok(init_slice(src, buffer))
+    }
+
+    fn read_u32(&self, offset: u32, gas: &GasCounter) -> Result<u32, DeterministicHostError> {
+        let mut data = [MaybeUninit::<u8>::uninit(); 4];
+        let bytes = self.read(offset, &mut data, gas)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn api_version(&self) -> Version {
+        API_VERSION_0_0_6.clone()
+    }
+
+    fn asc_type_id(&mut self, type_id_index: IndexForAscTypeId) -> Result<u32, HostExportError> {
+        Ok(type_id_index as u32)
+    }
+}
+
+#[test]
+fn test_mapping_triggers_share_block() {
+    use graph::components::metrics::gas::GasMetrics;
+    use graph::prelude::web3::types::{Transaction, U256};
+    use graph::prelude::BigInt;
+    use graph::runtime::{asc_get, AscPtr};
+    use graph_runtime_wasm::module::ToAscPtr;
+
+    use crate::runtime::abi::{
+        AscEthereumBlock_0_0_6, AscEthereumCall_0_0_3, AscEthereumEvent,
+        AscEthereumTransaction_0_0_6,
+    };
+    use crate::trigger::MappingTrigger;
+
+    type Event = AscEthereumEvent<AscEthereumTransaction_0_0_6, AscEthereumBlock_0_0_6>;
+    type Call = AscEthereumCall_0_0_3<AscEthereumTransaction_0_0_6, AscEthereumBlock_0_0_6>;
+
+    /// The hash, parent hash, number and timestamp of the block that a
+    /// handler for `trigger` receives
+    fn handler_block(trigger: MappingTrigger) -> (H256, H256, BigInt, BigInt) {
+        let mut heap = TestHeap { memory: vec![] };
+        let gas = GasCounter::new(GasMetrics::mock());
+
+        let is_log = matches!(trigger, MappingTrigger::Log { .. });
+        let is_call = matches!(trigger, MappingTrigger::Call { .. });
+        let ptr = trigger.to_asc_ptr(&mut heap, &gas).unwrap().wasm_ptr();
+        let block: AscPtr<AscEthereumBlock_0_0_6> = if is_log {
+            AscPtr::<Event>::new(ptr)
+                .read_ptr(&heap, &gas)
+                .unwrap()
+                .block
+        } else if is_call {
+            AscPtr::<Call>::new(ptr)
+                .read_ptr(&heap, &gas)
+                .unwrap()
+                .block
+        } else {
+            AscPtr::new(ptr)
+        };
+
+        let block = block.read_ptr(&heap, &gas).unwrap();
+        (
+            asc_get(&heap, block.hash, &gas, 0).unwrap(),
+            asc_get(&heap, block.parent_hash, &gas, 0).unwrap(),
+            asc_get(&heap, block.number, &gas, 0).unwrap(),
+            asc_get(&heap, block.timestamp, &gas, 0).unwrap(),
+        )
+    }
+
+    let block = Arc::new(LightEthereumBlock {
+        hash: Some(H256::from_low_u64_be(2)),
+        parent_hash: H256::from_low_u64_be(1),
+        number: Some(U64::from(2)),
+        timestamp: U256::from(1_700_000_000),
+        ..Default::default()
+    });
+    let transaction = Arc::new(Transaction::default());
+
+    let triggers = vec![
+        MappingTrigger::Log {
+            block: block.clone(),
+            transaction: transaction.clone(),
+            log: Arc::new(Log::default()),
+            params: vec![],
+            receipt: None,
+        },
+        MappingTrigger::Call {
+            block: block.clone(),
+            transaction,
+            call: Arc::new(EthereumCall::default()),
+            inputs: vec![],
+            outputs: vec![],
+        },
+        MappingTrigger::Block { block },
+    ];
+
+    for trigger in triggers {
+        assert_eq!(
+            (
+                H256::from_low_u64_be(2),
+                H256::from_low_u64_be(1),
+                BigInt::from(2u64),
+                BigInt::from(1_700_000_000u64),
+            ),
+            handler_block(trigger)
+        );
+    }
+}
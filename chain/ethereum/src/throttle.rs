@@ -0,0 +1,247 @@
+//! Limits on how many requests per second are sent to Ethereum providers.
+//!
+//! A provider is limited with `max_requests_per_second` in its `limits`;
+//! `Transport::RateLimited` makes every request to it wait for its
+//! `RequestThrottle`. A deployment is limited with
+//! `GRAPH_ETHEREUM_DEPLOYMENT_MAX_RPC_RATE(S)`. Since providers are shared
+//! by all deployments on a chain, the throttle of a deployment is not part
+//! of a transport; instead, requests that are made inside
+//! `RequestThrottle::run` wait for it, no matter which transport sends them.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use graph::prelude::{tokio, CounterVec, DeploymentHash, MetricsRegistry};
+use graph::util::rate_limit::RateLimiter;
+
+use crate::adapter::ProviderEthRpcMetrics;
+use crate::ENV_VARS;
+
+tokio::task_local! {
+    /// The throttle of the deployment on whose behalf the current task
+    /// sends requests
+    static DEPLOYMENT_THROTTLE: RequestThrottle;
+}
+
+/// Makes requests wait so that they stay within a request rate, and
+/// records how many requests had to wait and for how long
+#[derive(Clone)]
+pub struct RequestThrottle {
+    limiter: Arc<RateLimiter>,
+    throttled: CounterVec,
+    throttled_secs: CounterVec,
+    /// The provider or deployment the throttle is for
+    label: String,
+}
+
+impl RequestThrottle {
+    /// A throttle that sends at most `max_requests` per second to
+    /// `provider`
+    pub fn for_provider(
+        metrics: &ProviderEthRpcMetrics,
+        provider: &str,
+        max_requests: u32,
+    ) -> Self {
+        Self {
+            limiter: Arc::new(RateLimiter::new(max_requests.max(1) as f64)),
+            throttled: (*metrics.throttled).clone(),
+            throttled_secs: (*metrics.throttled_secs).clone(),
+            label: provider.to_string(),
+        }
+    }
+
+    fn for_deployment(registry: &MetricsRegistry, deployment: &str, rate: f64) -> Self {
+        let throttled = registry
+            .global_counter_vec(
+                "deployment_eth_rpc_throttled",
+                "Counts eth rpc requests of a subgraph deployment that had to wait because the deployment was over its RPC budget",
+                &["method", "deployment"],
+            )
+            .unwrap();
+        let throttled_secs = registry
+            .global_counter_vec(
+                "deployment_eth_rpc_throttled_secs",
+                "Measures the time eth rpc requests of a subgraph deployment waited because the deployment was over its RPC budget",
+                &["method", "deployment"],
+            )
+            .unwrap();
+        Self {
+            limiter: Arc::new(RateLimiter::new(rate)),
+            throttled,
+            throttled_secs,
+            label: deployment.to_string(),
+        }
+    }
+
+    /// Wait until a request for `method` may be sent
+    pub async fn wait(&self, method: &str) {
+        let waited = self.limiter.wait().await;
+        if !waited.is_zero() {
+            self.throttled
+                .with_label_values(&[method, &self.label])
+                .inc();
+            self.throttled_secs
+                .with_label_values(&[method, &self.label])
+                .inc_by(waited.as_secs_f64());
+        }
+    }
+
+    /// Run `fut` so that every request it sends to a provider also waits
+    /// for `throttle`. Runs `fut` unchanged if `throttle` is `None`
+    pub async fn run<F: Future>(throttle: Option<&RequestThrottle>, fut: F) -> F::Output {
+        match throttle {
+            Some(throttle) => DEPLOYMENT_THROTTLE.scope(throttle.clone(), fut).await,
+            None => fut.await,
+        }
+    }
+
+    /// Like `run`, but for code that blocks on its requests, like host
+    /// functions
+    pub fn run_blocking<R>(throttle: Option<&RequestThrottle>, f: impl FnOnce() -> R) -> R {
+        match throttle {
+            Some(throttle) => DEPLOYMENT_THROTTLE.sync_scope(throttle.clone(), f),
+            None => f(),
+        }
+    }
+
+    /// The throttle of the deployment for which the current task sends
+    /// requests, if there is one
+    pub(crate) fn current() -> Option<RequestThrottle> {
+        DEPLOYMENT_THROTTLE
+            .try_with(|throttle| throttle.clone())
+            .ok()
+    }
+}
+
+impl fmt::Debug for RequestThrottle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestThrottle")
+            .field("rate", &self.limiter.rate())
+            .field("label", &self.label)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The throttles of the deployments on one chain. The block stream and the
+/// mappings of a deployment get their throttle from here so that their
+/// requests count against the same budget
+pub struct DeploymentThrottles {
+    registry: Arc<MetricsRegistry>,
+    throttles: Mutex<HashMap<DeploymentHash, Option<RequestThrottle>>>,
+}
+
+impl DeploymentThrottles {
+    pub fn new(registry: Arc<MetricsRegistry>) -> Self {
+        Self {
+            registry,
+            throttles: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The throttle for `deployment`, or `None` if the requests of the
+    /// deployment are not limited
+    pub fn get(&self, deployment: &DeploymentHash) -> Option<RequestThrottle> {
+        let mut throttles = self.throttles.lock().unwrap();
+        if let Some(throttle) = throttles.get(deployment) {
+            return throttle.clone();
+        }
+        let throttle = ENV_VARS
+            .deployment_max_rpc_rate(deployment)
+            .map(|rate| RequestThrottle::for_deployment(&self.registry, deployment, rate));
+        throttles.insert(deployment.clone(), throttle.clone());
+        throttle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use graph::prelude::{tokio, web3::api::Web3, CheapClone, MetricsRegistry};
+
+    use super::RequestThrottle;
+    use crate::adapter::ProviderEthRpcMetrics;
+    use crate::transport::{Fixtures, Transport};
+
+    #[tokio::test]
+    async fn provider_throttle_limits_requests() {
+        let registry = Arc::new(MetricsRegistry::mock());
+        let provider_metrics = ProviderEthRpcMetrics::new(registry);
+        let fixtures =
+            Fixtures::from_json(r#"[{ "method": "eth_blockNumber", "result": "0x10" }]"#).unwrap();
+        let throttle = RequestThrottle::for_provider(&provider_metrics, "provider", 5);
+        let web3 = Web3::new(Transport::new_custom(Arc::new(fixtures)).with_throttle(throttle));
+        let throttled = |provider: &str| {
+            provider_metrics
+                .throttled
+                .with_label_values(&["eth_blockNumber", provider])
+                .get()
+        };
+
+        // The burst of 5 requests goes through right away, the next one
+        // has to wait and is counted for the provider
+        for _ in 0..5 {
+            web3.eth().block_number().await.unwrap();
+        }
+        assert_eq!(0.0, throttled("provider"));
+        web3.eth().block_number().await.unwrap();
+        assert_eq!(1.0, throttled("provider"));
+        assert!(
+            provider_metrics
+                .throttled_secs
+                .with_label_values(&["eth_blockNumber", "provider"])
+                .get()
+                > 0.0
+        );
+        assert_eq!(0.0, throttled("other"));
+    }
+
+    #[tokio::test]
+    async fn deployment_throttle_counts_requests_once() {
+        let registry = Arc::new(MetricsRegistry::mock());
+        let provider_metrics = ProviderEthRpcMetrics::new(registry.cheap_clone());
+        let fixtures =
+            Fixtures::from_json(r#"[{ "method": "eth_blockNumber", "result": "0x10" }]"#).unwrap();
+        // Nested transports must not make a request wait twice
+        let transport = Transport::new_custom(Arc::new(fixtures))
+            .with_throttle(RequestThrottle::for_provider(
+                &provider_metrics,
+                "provider",
+                1000,
+            ))
+            .with_max_concurrent_requests(4);
+        let web3 = Web3::new(transport);
+
+        let throttle = RequestThrottle::for_deployment(&registry, "QmDeployment", 10.0);
+        let throttled = || {
+            throttle
+                .throttled
+                .with_label_values(&["eth_blockNumber", "QmDeployment"])
+                .get()
+        };
+
+        // The burst of 10 requests goes through right away, the next one
+        // has to wait
+        RequestThrottle::run(Some(&throttle), async {
+            for _ in 0..10 {
+                web3.eth().block_number().await.unwrap();
+            }
+        })
+        .await;
+        assert_eq!(0.0, throttled());
+        // The throttle applies even though web3 sent the request before
+        // `run` was called
+        RequestThrottle::run(Some(&throttle), web3.eth().block_number())
+            .await
+            .unwrap();
+        assert_eq!(1.0, throttled());
+
+        // Requests outside of `run` are not limited by the deployment
+        for _ in 0..10 {
+            web3.eth().block_number().await.unwrap();
+        }
+        assert_eq!(1.0, throttled());
+    }
+}
@@ -1,13 +1,33 @@
 use graph::endpoint::{EndpointMetrics, Provider, RequestLabels};
-use jsonrpc_core::types::Call;
+use jsonrpc_core::types::{Call, Params};
 use jsonrpc_core::Value;
 
 use web3::transports::{http, ipc, ws};
 use web3::RequestId;
 
+use graph::prelude::tokio::sync::Semaphore;
 use graph::prelude::*;
 use graph::url::Url;
+use std::fmt;
 use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::throttle::RequestThrottle;
+
+type RequestFuture = Pin<Box<dyn Future<Output = Result<Value, web3::error::Error>> + Send>>;
+type BatchFuture = Box<
+    dyn Future<Output = Result<Vec<Result<Value, web3::error::Error>>, web3::error::Error>>
+        + Send
+        + Unpin,
+>;
+
+/// A backend for JSON-RPC requests other than the ones that web3 provides,
+/// for example, a proxy that speaks a different protocol, or recorded
+/// responses for tests.
+pub trait TransportBackend: fmt::Debug + Send + Sync + 'static {
+    /// Send a single request and return its result
+    fn send(&self, request: Call) -> RequestFuture;
+}
 
 /// Abstraction over the different web3 transports.
 #[derive(Clone, Debug)]
@@ -18,7 +38,28 @@ pub enum Transport {
         provider: Provider,
     },
     IPC(ipc::Ipc),
-    WS(ws::WebSocket),
+    WS {
+        ws: ws::WebSocket,
+        /// The URL the socket is connected to, so that others can open
+        /// their own connection to it
+        url: String,
+    },
+    Custom {
+        backend: Arc<dyn TransportBackend>,
+        next_id: Arc<AtomicUsize>,
+    },
+    /// Another transport that has at most as many requests in flight as
+    /// `permits` allows; a batch counts as one request.
+    Limited {
+        transport: Box<Transport>,
+        permits: Arc<Semaphore>,
+    },
+    /// Another transport that sends at most as many requests per second
+    /// as `throttle` allows; a batch counts as one request.
+    RateLimited {
+        transport: Box<Transport>,
+        throttle: RequestThrottle,
+    },
 }
 
 impl Transport {
@@ -35,10 +76,24 @@ impl Transport {
     pub async fn new_ws(ws: &str) -> Self {
         ws::WebSocket::new(ws)
             .await
-            .map(Transport::WS)
+            .map(|socket| Transport::WS {
+                ws: socket,
+                url: ws.to_string(),
+            })
             .expect("Failed to connect to Ethereum WS")
     }
 
+    /// The URL of the WebSocket this transport uses, if it uses one
+    pub fn ws_url(&self) -> Option<&str> {
+        match self {
+            Transport::WS { url, .. } => Some(url.as_str()),
+            Transport::Limited { transport, .. } | Transport::RateLimited { transport, .. } => {
+                transport.ws_url()
+            }
+            Transport::RPC { .. } | Transport::IPC(_) | Transport::Custom { .. } => None,
+        }
+    }
+
     /// Creates a JSON-RPC over HTTP transport.
     ///
     /// Note: JSON-RPC over HTTP doesn't always support subscribing to new
@@ -61,10 +116,42 @@ impl Transport {
             provider: provider.as_ref().into(),
         }
     }
+
+    /// Creates a transport that sends requests to `backend`.
+    pub fn new_custom(backend: Arc<dyn TransportBackend>) -> Self {
+        Transport::Custom {
+            backend,
+            next_id: Arc::new(AtomicUsize::new(1)),
+        }
+    }
+
+    /// Limits this transport to sending at most `max_requests` requests at
+    /// the same time. Requests beyond that wait until earlier ones finish.
+    pub fn with_max_concurrent_requests(self, max_requests: usize) -> Self {
+        Transport::Limited {
+            transport: Box::new(self),
+            permits: Arc::new(Semaphore::new(max_requests.max(1))),
+        }
+    }
+
+    /// Limits this transport to sending requests at the rate `throttle`
+    /// allows. Requests beyond that wait for their turn.
+    pub fn with_throttle(self, throttle: RequestThrottle) -> Self {
+        Transport::RateLimited {
+            transport: Box::new(self),
+            throttle,
+        }
+    }
+
+    /// Creates a transport that answers requests with the responses
+    /// recorded in the fixture file at `path`; see [`Fixtures`].
+    pub fn new_fixture(path: &str) -> Result<Self, Error> {
+        Ok(Self::new_custom(Arc::new(Fixtures::load(path)?)))
+    }
 }
 
 impl web3::Transport for Transport {
-    type Out = Pin<Box<dyn Future<Output = Result<Value, web3::error::Error>> + Send + 'static>>;
+    type Out = RequestFuture;
 
     fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
         match self {
@@ -74,11 +161,35 @@ impl web3::Transport for Transport {
                 provider: _,
             } => client.prepare(method, params),
             Transport::IPC(ipc) => ipc.prepare(method, params),
-            Transport::WS(ws) => ws.prepare(method, params),
+            Transport::WS { ws, .. } => ws.prepare(method, params),
+            Transport::Custom { next_id, .. } => {
+                let id = next_id.fetch_add(1, Ordering::SeqCst);
+                (id, web3::helpers::build_request(id, method, params))
+            }
+            Transport::Limited { transport, .. } | Transport::RateLimited { transport, .. } => {
+                web3::Transport::prepare(transport.as_ref(), method, params)
+            }
         }
     }
 
     fn send(&self, id: RequestId, request: Call) -> Self::Out {
+        // The throttle is looked up when the request is polled since web3
+        // sends requests when it creates the future for them
+        let transport = self.clone();
+        Box::pin(async move {
+            if let Some(throttle) = RequestThrottle::current() {
+                throttle.wait(method_name(&request)).await;
+            }
+            transport.send_request(id, request).await
+        })
+    }
+}
+
+impl Transport {
+    /// Send `request` without waiting for the throttle of the deployment
+    /// that makes it; nested transports are sent requests this way so that
+    /// they are only counted once
+    fn send_request(&self, id: RequestId, request: Call) -> RequestFuture {
         match self {
             Transport::RPC {
                 client,
@@ -87,10 +198,7 @@ impl web3::Transport for Transport {
             } => {
                 let metrics = metrics.cheap_clone();
                 let client = client.clone();
-                let method = match request {
-                    Call::MethodCall(ref m) => m.method.as_str(),
-                    _ => "unknown",
-                };
+                let method = method_name(&request);
 
                 let labels = RequestLabels {
                     provider: provider.clone(),
@@ -111,22 +219,60 @@ impl web3::Transport for Transport {
                 Box::pin(out)
             }
             Transport::IPC(ipc) => Box::pin(ipc.send(id, request)),
-            Transport::WS(ws) => Box::pin(ws.send(id, request)),
+            Transport::WS { ws, .. } => Box::pin(ws.send(id, request)),
+            Transport::Custom { backend, .. } => backend.send(request),
+            Transport::Limited { transport, permits } => {
+                let transport = transport.clone();
+                let permits = permits.cheap_clone();
+                Box::pin(async move {
+                    // The semaphore is never closed
+                    let _permit = permits.acquire_owned().await;
+                    transport.send_request(id, request).await
+                })
+            }
+            Transport::RateLimited {
+                transport,
+                throttle,
+            } => {
+                let transport = transport.clone();
+                let throttle = throttle.clone();
+                Box::pin(async move {
+                    throttle.wait(method_name(&request)).await;
+                    transport.send_request(id, request).await
+                })
+            }
         }
     }
 }
 
+fn method_name(request: &Call) -> &str {
+    match request {
+        Call::MethodCall(m) => m.method.as_str(),
+        _ => "unknown",
+    }
+}
+
 impl web3::BatchTransport for Transport {
-    type Batch = Box<
-        dyn Future<Output = Result<Vec<Result<Value, web3::error::Error>>, web3::error::Error>>
-            + Send
-            + Unpin,
-    >;
+    type Batch = BatchFuture;
 
     fn send_batch<T>(&self, requests: T) -> Self::Batch
     where
         T: IntoIterator<Item = (RequestId, Call)>,
     {
+        let transport = self.clone();
+        let requests = requests.into_iter().collect::<Vec<_>>();
+        Box::new(Box::pin(async move {
+            if let Some(throttle) = RequestThrottle::current() {
+                throttle.wait("batch").await;
+            }
+            transport.send_batch_requests(requests).await
+        }))
+    }
+}
+
+impl Transport {
+    /// Like `send_request`, but for a batch
+    fn send_batch_requests(&self, requests: Vec<(RequestId, Call)>) -> BatchFuture {
         match self {
             Transport::RPC {
                 client,
@@ -134,7 +280,136 @@ impl web3::BatchTransport for Transport {
                 provider: _,
             } => Box::new(client.send_batch(requests)),
             Transport::IPC(ipc) => Box::new(ipc.send_batch(requests)),
-            Transport::WS(ws) => Box::new(ws.send_batch(requests)),
+            Transport::WS { ws, .. } => Box::new(ws.send_batch(requests)),
+            Transport::Custom { backend, .. } => {
+                let responses = requests
+                    .into_iter()
+                    .map(|(_, request)| backend.send(request))
+                    .collect::<Vec<_>>();
+                Box::new(futures03::future::join_all(responses).map(Ok))
+            }
+            Transport::Limited { transport, permits } => {
+                let transport = transport.clone();
+                let permits = permits.cheap_clone();
+                Box::new(Box::pin(async move {
+                    // The semaphore is never closed
+                    let _permit = permits.acquire_owned().await;
+                    transport.send_batch_requests(requests).await
+                }))
+            }
+            Transport::RateLimited {
+                transport,
+                throttle,
+            } => {
+                let transport = transport.clone();
+                let throttle = throttle.clone();
+                Box::new(Box::pin(async move {
+                    throttle.wait("batch").await;
+                    transport.send_batch_requests(requests).await
+                }))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Fixture {
+    method: String,
+    #[serde(default)]
+    params: Value,
+    #[serde(default)]
+    result: Value,
+    #[serde(default)]
+    error: Option<jsonrpc_core::Error>,
+}
+
+/// A backend that answers requests with recorded responses, mostly useful
+/// for tests. The fixtures are a JSON array of objects with the `method`
+/// and `params` of a request, and either the `result` or the `error` that
+/// should be returned for it. Requests without a matching fixture fail.
+#[derive(Debug)]
+pub struct Fixtures {
+    fixtures: Vec<Fixture>,
+}
+
+impl Fixtures {
+    pub fn load(path: &str) -> Result<Self, Error> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("failed to read fixtures from {}: {}", path, e))?;
+        Self::from_json(&json)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let fixtures = serde_json::from_str(json)?;
+        Ok(Fixtures { fixtures })
+    }
+
+    fn respond(&self, method: &str, params: Value) -> Result<Value, web3::error::Error> {
+        let fixture = self
+            .fixtures
+            .iter()
+            .find(|fixture| {
+                // Fixtures can leave out empty parameters
+                let fixture_params = match &fixture.params {
+                    Value::Null => Value::Array(vec![]),
+                    params => params.clone(),
+                };
+                fixture.method == method && fixture_params == params
+            })
+            .ok_or_else(|| {
+                web3::error::Error::Rpc(jsonrpc_core::Error {
+                    code: jsonrpc_core::ErrorCode::InternalError,
+                    message: format!("no fixture for {} with params {}", method, params),
+                    data: None,
+                })
+            })?;
+
+        match &fixture.error {
+            Some(error) => Err(web3::error::Error::Rpc(error.clone())),
+            None => Ok(fixture.result.clone()),
         }
     }
 }
+
+impl TransportBackend for Fixtures {
+    fn send(&self, request: Call) -> RequestFuture {
+        let response = match request {
+            Call::MethodCall(call) => {
+                let params = match call.params {
+                    Params::None => Value::Array(vec![]),
+                    Params::Array(params) => Value::Array(params),
+                    Params::Map(params) => Value::Object(params),
+                };
+                self.respond(&call.method, params)
+            }
+            Call::Notification(_) | Call::Invalid { .. } => Err(web3::error::Error::Rpc(
+                jsonrpc_core::Error::invalid_request(),
+            )),
+        };
+        Box::pin(std::future::ready(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use graph::prelude::{tokio, web3::api::Web3};
+    use std::sync::Arc;
+
+    use super::{Fixtures, Transport};
+
+    #[tokio::test]
+    async fn fixture_transport() {
+        let fixtures = Fixtures::from_json(
+            r#"[
+                { "method": "eth_blockNumber", "result": "0x10" },
+                { "method": "net_version", "error": { "code": -32000, "message": "boom" } }
+            ]"#,
+        )
+        .unwrap();
+        let web3 = Web3::new(Transport::new_custom(Arc::new(fixtures)));
+
+        assert_eq!(16, web3.eth().block_number().await.unwrap().as_u64());
+        assert!(web3.net().version().await.is_err());
+        assert!(web3.eth().gas_price().await.is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use graph::anyhow::{anyhow, Error};
 use graph::blockchain::MappingTriggerTrait;
 use graph::blockchain::TriggerData;
 use graph::data::subgraph::API_VERSION_0_0_2;
@@ -25,7 +26,6 @@ use graph::runtime::AscPtr;
 use graph::runtime::HostExportError;
 use graph::semver::Version;
 use graph_runtime_wasm::module::ToAscPtr;
-use std::ops::Deref;
 use std::{cmp::Ordering, sync::Arc};
 
 use crate::runtime::abi::AscEthereumBlock;
@@ -33,6 +33,7 @@ use crate::runtime::abi::AscEthereumBlock_0_0_6;
 use crate::runtime::abi::AscEthereumCall;
 use crate::runtime::abi::AscEthereumCall_0_0_3;
 use crate::runtime::abi::AscEthereumEvent;
+use crate::runtime::abi::AscEthereumEventArray_0_0_7;
 use crate::runtime::abi::AscEthereumEvent_0_0_7;
 use crate::runtime::abi::AscEthereumTransaction_0_0_1;
 use crate::runtime::abi::AscEthereumTransaction_0_0_2;
@@ -41,6 +42,10 @@ use crate::runtime::abi::AscEthereumTransaction_0_0_6;
 // ETHDEP: This should be defined in only one place.
 type LightEthereumBlock = Block<Transaction>;
 
+/// The trigger that is passed to a handler. Every kind of trigger carries
+/// the full block it belongs to, and handlers of all kinds receive it as
+/// the same `ethereum.Block` with its number, hash, parent hash and
+/// timestamp so that mappings never need to request them separately.
 pub enum MappingTrigger {
     Log {
         block: Arc<LightEthereumBlock>,
@@ -59,6 +64,24 @@ pub enum MappingTrigger {
     Block {
         block: Arc<LightEthereumBlock>,
     },
+    /// All events of a block that matched a batch event handler, in the
+    /// order in which they were emitted. Each entry is a `Log` trigger, and
+    /// there is at least one.
+    Logs {
+        logs: Vec<MappingTrigger>,
+    },
+}
+
+impl MappingTrigger {
+    /// The block of this trigger
+    pub fn block(&self) -> &Arc<LightEthereumBlock> {
+        match self {
+            MappingTrigger::Log { block, .. }
+            | MappingTrigger::Call { block, .. }
+            | MappingTrigger::Block { block } => block,
+            MappingTrigger::Logs { logs } => logs[0].block(),
+        }
+    }
 }
 
 impl MappingTriggerTrait for MappingTrigger {
@@ -67,6 +90,9 @@ impl MappingTriggerTrait for MappingTrigger {
             MappingTrigger::Log { log, .. } => log.transaction_hash,
             MappingTrigger::Call { call, .. } => call.transaction_hash,
             MappingTrigger::Block { .. } => None,
+            MappingTrigger::Logs { logs, .. } => {
+                return format!("batch of {} events", logs.len());
+            }
         };
 
         match transaction_id {
@@ -74,6 +100,28 @@ impl MappingTriggerTrait for MappingTrigger {
             None => String::new(),
         }
     }
+
+    fn transaction_hash(&self) -> Option<String> {
+        let transaction_id = match self {
+            MappingTrigger::Log { log, .. } => log.transaction_hash,
+            MappingTrigger::Call { call, .. } => call.transaction_hash,
+            MappingTrigger::Block { .. } | MappingTrigger::Logs { .. } => None,
+        };
+        transaction_id.map(|tx_hash| format!("0x{:x}", tx_hash))
+    }
+
+    fn batch(triggers: Vec<Self>) -> Result<Self, Error> {
+        if triggers.is_empty() {
+            return Err(anyhow!("a batch needs at least one trigger"));
+        }
+        if let Some(trigger) = triggers
+            .iter()
+            .find(|trigger| !matches!(trigger, MappingTrigger::Log { .. }))
+        {
+            return Err(anyhow!("only events can be batched, but got {:?}", trigger));
+        }
+        Ok(MappingTrigger::Logs { logs: triggers })
+    }
 }
 
 // Logging the block is too verbose, so this strips the block from the trigger for Debug.
@@ -93,6 +141,9 @@ impl std::fmt::Debug for MappingTrigger {
                 _outputs: Vec<LogParam>,
             },
             Block,
+            Logs {
+                _logs: Vec<Arc<Log>>,
+            },
         }
 
         let trigger_without_block = match self {
@@ -120,6 +171,15 @@ impl std::fmt::Debug for MappingTrigger {
                 _outputs: outputs.clone(),
             },
             MappingTrigger::Block { block: _ } => MappingTriggerWithoutBlock::Block,
+            MappingTrigger::Logs { logs } => MappingTriggerWithoutBlock::Logs {
+                _logs: logs
+                    .iter()
+                    .filter_map(|trigger| match trigger {
+                        MappingTrigger::Log { log, .. } => Some(log.cheap_clone()),
+                        _ => None,
+                    })
+                    .collect(),
+            },
         };
 
         write!(f, "{:?}", trigger_without_block)
@@ -141,15 +201,8 @@ impl ToAscPtr for MappingTrigger {
                 receipt,
             } => {
                 let api_version = heap.api_version();
-                let ethereum_event_data = EthereumEventData {
-                    block: EthereumBlockData::from(block.as_ref()),
-                    transaction: EthereumTransactionData::from(transaction.deref()),
-                    address: log.address,
-                    log_index: log.log_index.unwrap_or(U256::zero()),
-                    transaction_log_index: log.log_index.unwrap_or(U256::zero()),
-                    log_type: log.log_type.clone(),
-                    params,
-                };
+                let ethereum_event_data =
+                    EthereumEventData::new(&block, &transaction, &log, params);
                 if api_version >= API_VERSION_0_0_7 {
                     asc_new::<
                         AscEthereumEvent_0_0_7<
@@ -190,14 +243,7 @@ impl ToAscPtr for MappingTrigger {
                 inputs,
                 outputs,
             } => {
-                let call = EthereumCallData {
-                    to: call.to,
-                    from: call.from,
-                    block: EthereumBlockData::from(block.as_ref()),
-                    transaction: EthereumTransactionData::from(transaction.deref()),
-                    inputs,
-                    outputs,
-                };
+                let call = EthereumCallData::new(&block, &transaction, &call, inputs, outputs);
                 if heap.api_version() >= Version::new(0, 0, 6) {
                     asc_new::<
                         AscEthereumCall_0_0_3<AscEthereumTransaction_0_0_6, AscEthereumBlock_0_0_6>,
@@ -224,6 +270,35 @@ impl ToAscPtr for MappingTrigger {
                     asc_new::<AscEthereumBlock, _, _>(heap, &block, gas)?.erase()
                 }
             }
+            MappingTrigger::Logs { logs } => {
+                // Manifest validation only allows batch handlers for these versions
+                if heap.api_version() < API_VERSION_0_0_7 {
+                    return Err(HostExportError::Deterministic(anyhow!(
+                        "batch event handlers require apiVersion >= 0.0.7"
+                    )));
+                }
+                let (events, receipts): (Vec<_>, Vec<_>) = logs
+                    .into_iter()
+                    .filter_map(|trigger| match trigger {
+                        MappingTrigger::Log {
+                            block,
+                            transaction,
+                            log,
+                            params,
+                            receipt,
+                        } => Some((
+                            EthereumEventData::new(&block, &transaction, &log, params),
+                            receipt,
+                        )),
+                        _ => None,
+                    })
+                    .unzip();
+                let events: Vec<_> = events
+                    .into_iter()
+                    .zip(receipts.iter().map(|receipt| receipt.as_deref()))
+                    .collect();
+                asc_new::<AscEthereumEventArray_0_0_7, _, _>(heap, &events, gas)?.erase()
+            }
         })
     }
 }
@@ -332,6 +407,9 @@ impl EthereumTrigger {
             EthereumTrigger::Block(_, EthereumBlockTriggerType::WithCallTo(address)) => {
                 Some(address)
             }
+            // The address of a newly created contract can't be known in
+            // advance, creation handlers match on the deployer instead.
+            EthereumTrigger::Call(call) if call.creation => None,
             EthereumTrigger::Call(call) => Some(&call.to),
             EthereumTrigger::Log(log_ref) => Some(&log_ref.address()),
             // Unfiltered block triggers match any data source address.
@@ -341,6 +419,58 @@ impl EthereumTrigger {
     }
 }
 
+/// The position of a trigger among the triggers of its block. Deployments
+/// with spec version 1.0.0 or later process triggers in the order of their
+/// positions, which is:
+///
+/// 1. block triggers for `EthereumBlockTriggerType::Start`
+/// 2. events and calls, ordered by the index of their transaction; within a
+///    transaction, all events (by log index) come before all calls (by
+///    trace address)
+/// 3. block triggers for `EthereumBlockTriggerType::WithCallTo`, ordered by
+///    the address that was called
+/// 4. block triggers for `EthereumBlockTriggerType::End`
+///
+/// Unlike the order given by `Ord`, it does not depend on how the triggers
+/// were fetched, so that all nodes process the triggers of a block in the
+/// same order.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum TriggerPosition<'a> {
+    Start,
+    Transaction {
+        index: Option<u64>,
+        trigger: TransactionTriggerPosition<'a>,
+    },
+    WithCallTo(&'a Address),
+    End,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum TransactionTriggerPosition<'a> {
+    Log(Option<U256>),
+    Call(&'a [usize]),
+}
+
+impl EthereumTrigger {
+    fn position(&self) -> TriggerPosition<'_> {
+        match self {
+            EthereumTrigger::Block(_, EthereumBlockTriggerType::Start) => TriggerPosition::Start,
+            EthereumTrigger::Block(_, EthereumBlockTriggerType::WithCallTo(address)) => {
+                TriggerPosition::WithCallTo(address)
+            }
+            EthereumTrigger::Block(_, EthereumBlockTriggerType::End) => TriggerPosition::End,
+            EthereumTrigger::Log(log) => TriggerPosition::Transaction {
+                index: log.transaction_index().map(|index| index.as_u64()),
+                trigger: TransactionTriggerPosition::Log(log.log_index()),
+            },
+            EthereumTrigger::Call(call) => TriggerPosition::Transaction {
+                index: Some(call.transaction_index),
+                trigger: TransactionTriggerPosition::Call(&call.trace_address),
+            },
+        }
+    }
+}
+
 impl Ord for EthereumTrigger {
     fn cmp(&self, other: &Self) -> Ordering {
         match (self, other) {
@@ -410,13 +540,17 @@ impl TriggerData for EthereumTrigger {
         }
     }
 
+    fn cmp_position(&self, other: &Self) -> Ordering {
+        self.position().cmp(&other.position())
+    }
+
     fn address_match(&self) -> Option<&[u8]> {
         self.address().map(|address| address.as_bytes())
     }
 }
 
 /// Ethereum block data.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct EthereumBlockData {
     pub hash: H256,
     pub parent_hash: H256,
@@ -502,6 +636,25 @@ pub struct EthereumEventData {
     pub params: Vec<LogParam>,
 }
 
+impl EthereumEventData {
+    fn new(
+        block: &LightEthereumBlock,
+        transaction: &Transaction,
+        log: &Log,
+        params: Vec<LogParam>,
+    ) -> Self {
+        EthereumEventData {
+            block: EthereumBlockData::from(block),
+            transaction: EthereumTransactionData::from(transaction),
+            address: log.address,
+            log_index: log.log_index.unwrap_or(U256::zero()),
+            transaction_log_index: log.log_index.unwrap_or(U256::zero()),
+            log_type: log.log_type.clone(),
+            params,
+        }
+    }
+}
+
 /// An Ethereum call executed within a transaction within a block to a contract address.
 #[derive(Debug, Clone)]
 pub struct EthereumCallData {
@@ -512,3 +665,22 @@ pub struct EthereumCallData {
     pub inputs: Vec<LogParam>,
     pub outputs: Vec<LogParam>,
 }
+
+impl EthereumCallData {
+    fn new(
+        block: &LightEthereumBlock,
+        transaction: &Transaction,
+        call: &EthereumCall,
+        inputs: Vec<LogParam>,
+        outputs: Vec<LogParam>,
+    ) -> Self {
+        EthereumCallData {
+            to: call.to,
+            from: call.from,
+            block: EthereumBlockData::from(block),
+            transaction: EthereumTransactionData::from(transaction),
+            inputs,
+            outputs,
+        }
+    }
+}
@@ -5,6 +5,8 @@ mod subgraph;
 
 pub use crate::link_resolver::LinkResolver;
 pub use crate::subgraph::{
-    SubgraphAssignmentProvider, SubgraphInstanceManager, SubgraphRegistrar, SubgraphRunner,
-    SubgraphTriggerProcessor,
+    BlockReplay, SubgraphAssignmentProvider, SubgraphInstanceManager, SubgraphRegistrar,
+    SubgraphRunner, SubgraphTriggerProcessor,
 };
+
+pub use crate::subgraph::testing;
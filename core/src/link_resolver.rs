@@ -229,12 +229,16 @@ impl LinkResolverTrait for LinkResolver {
         restrict_file_size(&link.link, size, max_file_size)?;
 
         let link = link.link.clone();
+        let timeout = self.timeout;
         let data = retry_policy(self.retry, "ipfs.getBlock", logger)
             .run(move || {
                 let link = link.clone();
                 let client = client.clone();
                 async move {
-                    let data = client.get_block(link.clone()).await?.to_vec();
+                    let data = client
+                        .get_block(link.clone(), Some(timeout))
+                        .await?
+                        .to_vec();
                     Result::<Vec<u8>, reqwest::Error>::Ok(data)
                 }
             })
@@ -10,6 +10,7 @@ use graph::{
         store::BlockNumber,
         subgraph::{RuntimeHost, RuntimeHostBuilder},
     },
+    data_source::DataSource,
 };
 
 /// This structure maintains a partition of the hosts by address, for faster trigger matching. This
@@ -35,9 +36,10 @@ impl<C: Blockchain, T: RuntimeHostBuilder<C>> OnchainHosts<C, T> {
         &self.hosts
     }
 
-    pub fn contains(&self, other: &Arc<T::Host>) -> bool {
+    /// Whether there is a host for a duplicate of `data_source`
+    pub fn contains(&self, data_source: &DataSource<C>) -> bool {
         // Narrow down the host list by address, as an optimization.
-        let hosts = match other.data_source().address() {
+        let hosts = match data_source.address() {
             Some(address) => self.hosts_by_address.get(address.as_slice()),
             None => Some(&self.hosts_without_address),
         };
@@ -45,7 +47,7 @@ impl<C: Blockchain, T: RuntimeHostBuilder<C>> OnchainHosts<C, T> {
         hosts
             .into_iter()
             .flatten()
-            .any(|idx| &self.hosts[*idx] == other)
+            .any(|idx| self.hosts[*idx].data_source().is_duplicate_of(data_source))
     }
 
     pub fn last(&self) -> Option<&Arc<T::Host>> {
@@ -148,14 +150,18 @@ impl<C: Blockchain, T: RuntimeHostBuilder<C>> OffchainHosts<C, T> {
         self.by_block.values().flatten()
     }
 
-    pub fn contains(&self, other: &Arc<T::Host>) -> bool {
+    /// Whether there is a host for a duplicate of `data_source`
+    pub fn contains(&self, data_source: &DataSource<C>) -> bool {
         // Narrow down the host list by address, as an optimization.
-        let hosts = match other.data_source().address() {
+        let hosts = match data_source.address() {
             Some(address) => self.by_address.get(address.as_slice()),
             None => Some(&self.wildcard_address),
         };
 
-        hosts.into_iter().flatten().any(|host| host == other)
+        hosts
+            .into_iter()
+            .flatten()
+            .any(|host| host.data_source().is_duplicate_of(data_source))
     }
 
     pub fn push(&mut self, host: Arc<T::Host>) {
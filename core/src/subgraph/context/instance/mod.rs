@@ -11,6 +11,8 @@ use graph::{
     prelude::*,
 };
 use hosts::{OffchainHosts, OnchainHosts};
+
+use crate::subgraph::error::DataSourceLimitExceeded;
 use std::collections::HashMap;
 
 pub(super) struct SubgraphInstance<C: Blockchain, T: RuntimeHostBuilder<C>> {
@@ -36,6 +38,10 @@ pub(super) struct SubgraphInstance<C: Blockchain, T: RuntimeHostBuilder<C>> {
 
     /// This manages the sequence of causality regions for the subgraph.
     causality_region_seq: CausalityRegionSeq,
+
+    /// The most data sources with a host that the subgraph may have. Set from
+    /// `GRAPH_SUBGRAPH_MAX_DATA_SOURCES`.
+    max_data_sources: usize,
 }
 
 impl<T, C> SubgraphInstance<C, T>
@@ -84,9 +90,14 @@ where
             templates,
             host_metrics,
             causality_region_seq,
+            max_data_sources: ENV_VARS.subgraph_max_data_sources,
         }
     }
 
+    pub(super) fn set_max_data_sources(&mut self, max_data_sources: usize) {
+        self.max_data_sources = max_data_sources;
+    }
+
     // If `data_source.runtime()` is `None`, returns `Ok(None)`.
     fn new_host(
         &mut self,
@@ -130,18 +141,25 @@ where
         logger: &Logger,
         data_source: DataSource<C>,
     ) -> Result<Option<Arc<T::Host>>, Error> {
+        // Factories often announce the same contract more than once. Check
+        // for duplicates before building a host since that is much more
+        // expensive than the check.
+        let is_onchain = data_source.is_onchain();
+        let is_duplicate = match is_onchain {
+            true => self.onchain_hosts.contains(&data_source),
+            false => self.offchain_hosts.contains(&data_source),
+        };
+        if is_duplicate {
+            return Ok(None);
+        }
+
         // Protect against creating more than the allowed maximum number of data sources
-        if self.hosts_len() >= ENV_VARS.subgraph_max_data_sources {
-            anyhow::bail!(
-                "Limit of {} data sources per subgraph exceeded",
-                ENV_VARS.subgraph_max_data_sources,
-            );
+        if self.hosts_len() >= self.max_data_sources {
+            return Err(DataSourceLimitExceeded(self.max_data_sources).into());
         }
 
-        let is_onchain = data_source.is_onchain();
         let Some(host) = self.new_host(logger.clone(), data_source)? else { return Ok(None) };
 
-        // Check for duplicates and add the host.
         if is_onchain {
             // `onchain_hosts` will remain ordered by the creation block.
             // See also 8f1bca33-d3b7-4035-affc-fd6161a12448.
@@ -152,31 +170,23 @@ where
                     <= host.data_source().creation_block(),
             );
 
-            if self.onchain_hosts.contains(&host) {
-                Ok(None)
-            } else {
-                self.onchain_hosts.push(host.cheap_clone());
-                Ok(Some(host))
-            }
+            self.onchain_hosts.push(host.cheap_clone());
         } else {
-            if self.offchain_hosts.contains(&host) {
-                Ok(None)
-            } else {
-                self.offchain_hosts.push(host.cheap_clone());
-                Ok(Some(host))
-            }
+            self.offchain_hosts.push(host.cheap_clone());
         }
+        Ok(Some(host))
     }
 
     /// Reverts any DataSources that have been added from the block forwards (inclusively)
     /// This function also reverts the done_at status if it was 'done' on this block or later.
-    /// It only returns the offchain::Source because we don't currently need to know which
-    /// DataSources were removed, the source is used so that the offchain DDS can be found again.
+    /// It returns the number of onchain hosts that were removed and the offchain::Source of
+    /// the offchain DataSources that are no longer done; the source is used so that the
+    /// offchain DDS can be found again.
     pub(super) fn revert_data_sources(
         &mut self,
         reverted_block: BlockNumber,
-    ) -> Vec<offchain::Source> {
-        self.revert_onchain_hosts(reverted_block);
+    ) -> (usize, Vec<offchain::Source>) {
+        let reverted_onchain = self.revert_onchain_hosts(reverted_block);
         self.offchain_hosts.remove_ge_block(reverted_block);
 
         // Any File DataSources (Dynamic Data Sources), will have their own causality region
@@ -184,23 +194,27 @@ where
         // Regardless of the sequence logic, if the current causality region is ONCHAIN then there are
         // no others and therefore the remaining code is a noop and we can just stop here.
         if self.causality_region_seq.0 == CausalityRegion::ONCHAIN {
-            return vec![];
+            return (reverted_onchain, vec![]);
         }
 
-        self.offchain_hosts
+        let sources = self
+            .offchain_hosts
             .all()
             .filter(|host| matches!(host.done_at(), Some(done_at) if done_at >= reverted_block))
             .map(|host| {
                 host.set_done_at(None);
                 host.data_source().as_offchain().unwrap().source.clone()
             })
-            .collect()
+            .collect();
+        (reverted_onchain, sources)
     }
 
     /// Because onchain hosts are ordered, removing them based on creation block is cheap and simple.
-    fn revert_onchain_hosts(&mut self, reverted_block: BlockNumber) {
+    /// Returns the number of hosts that were removed.
+    fn revert_onchain_hosts(&mut self, reverted_block: BlockNumber) -> usize {
         // `onchain_hosts` is ordered by the creation block.
         // See also 8f1bca33-d3b7-4035-affc-fd6161a12448.
+        let mut reverted = 0;
         while self
             .onchain_hosts
             .last()
@@ -208,7 +222,9 @@ where
             .is_some()
         {
             self.onchain_hosts.pop();
+            reverted += 1;
         }
+        reverted
     }
 
     /// Returns all hosts which match the trigger's address.
@@ -231,6 +247,15 @@ where
         self.causality_region_seq.next_val()
     }
 
+    pub(super) fn set_causality_region_seq(&mut self, seq: CausalityRegionSeq) {
+        self.causality_region_seq = seq;
+    }
+
+    /// All onchain hosts, in the order in which their data sources were created.
+    pub fn onchain_hosts(&self) -> &[Arc<T::Host>] {
+        self.onchain_hosts.hosts()
+    }
+
     pub fn hosts_len(&self) -> usize {
         self.onchain_hosts.len() + self.offchain_hosts.len()
     }
@@ -239,3 +264,106 @@ where
         self.onchain_hosts.hosts().first()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use graph::prelude::{tokio, web3::types::Address};
+
+    use crate::subgraph::error::DataSourceLimitExceeded;
+    use crate::subgraph::testing::{
+        block_data_source, block_ptr, MockRuntimeHostBuilder, TestInstance,
+    };
+
+    #[tokio::test]
+    async fn data_source_limit_is_enforced() {
+        let data_sources = vec![
+            block_data_source("first", "handleBlock"),
+            block_data_source("second", "handleBlock"),
+        ];
+        let mut instance = TestInstance::new(
+            "type Thing @entity { id: ID! }",
+            data_sources,
+            MockRuntimeHostBuilder::default(),
+        )
+        .unwrap()
+        .with_max_data_sources(3);
+        assert_eq!(2, instance.hosts_len());
+
+        assert!(instance
+            .add_data_source(block_data_source("third", "handleBlock"))
+            .unwrap());
+        let err = instance
+            .add_data_source(block_data_source("fourth", "handleBlock"))
+            .unwrap_err();
+        assert!(err.is::<DataSourceLimitExceeded>());
+        assert_eq!(
+            "Limit of 3 data sources per subgraph exceeded",
+            err.to_string()
+        );
+        assert_eq!(3, instance.hosts_len());
+
+        // Duplicates do not count against the limit
+        assert!(!instance
+            .add_data_source(block_data_source("third", "handleBlock"))
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn duplicate_data_sources_are_skipped() {
+        let host_builder = MockRuntimeHostBuilder::default();
+        let mut instance = TestInstance::new(
+            "type Thing @entity { id: ID! }",
+            vec![block_data_source("first", "handleBlock")],
+            host_builder.clone(),
+        )
+        .unwrap();
+        assert_eq!(1, host_builder.builds());
+
+        // Duplicates can differ in their creation block, and no host is
+        // built for them
+        let mut duplicate = block_data_source("first", "handleBlock");
+        duplicate.creation_block = Some(7);
+        assert!(!instance.add_data_source(duplicate).unwrap());
+        assert_eq!(1, instance.hosts_len());
+        assert_eq!(1, host_builder.builds());
+
+        // The same data source for another address is not a duplicate
+        let mut other = block_data_source("first", "handleBlock");
+        other.address = Some(Address::from_low_u64_be(1));
+        other.creation_block = Some(7);
+        assert!(instance.add_data_source(other.clone()).unwrap());
+        assert_eq!(2, instance.hosts_len());
+        assert_eq!(2, host_builder.builds());
+
+        assert!(!instance.add_data_source(other).unwrap());
+        assert_eq!(2, instance.hosts_len());
+        assert_eq!(2, host_builder.builds());
+    }
+
+    #[tokio::test]
+    async fn revert_counts_removed_data_sources() {
+        let mut instance = TestInstance::new(
+            "type Thing @entity { id: ID! }",
+            vec![block_data_source("static", "handleBlock")],
+            MockRuntimeHostBuilder::default(),
+        )
+        .unwrap();
+        for block in [2, 3] {
+            let mut data_source = block_data_source(&format!("created{}", block), "handleBlock");
+            data_source.creation_block = Some(block);
+            assert!(instance.add_data_source(data_source).unwrap());
+        }
+        assert_eq!(3, instance.hosts_len());
+
+        // Only data sources created after the block we revert to are
+        // removed; the runner rebuilds its filter when there are any
+        assert_eq!(1, instance.revert(block_ptr(2)).await.unwrap());
+        assert_eq!(2, instance.hosts_len());
+        assert_eq!(0, instance.revert(block_ptr(2)).await.unwrap());
+        assert_eq!(2, instance.hosts_len());
+
+        // Data sources from the manifest are never removed
+        assert_eq!(1, instance.revert(block_ptr(0)).await.unwrap());
+        assert_eq!(1, instance.hosts_len());
+    }
+}
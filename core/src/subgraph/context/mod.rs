@@ -1,4 +1,5 @@
 mod instance;
+mod parallel;
 
 use crate::polling_monitor::{
     spawn_monitor, ArweaveService, IpfsService, PollingMonitor, PollingMonitorMetrics,
@@ -15,7 +16,8 @@ use graph::{
     data_source::{
         causality_region::CausalityRegionSeq,
         offchain::{self, Base64},
-        CausalityRegion, DataSource, DataSourceTemplate, TriggerData,
+        CausalityRegion, DataSource, DataSourceTemplate, MappingTrigger, TriggerData,
+        TriggerWithHandler,
     },
     ipfs_client::CidFile,
     prelude::{
@@ -30,6 +32,8 @@ use std::sync::{Arc, RwLock};
 use std::{collections::HashMap, time::Instant};
 
 use self::instance::SubgraphInstance;
+use self::parallel::DataSourceGroups;
+pub use self::parallel::ParallelOutcome;
 
 #[derive(Clone, Debug)]
 pub struct SubgraphKeepAlive {
@@ -74,6 +78,7 @@ where
     pub offchain_monitor: OffchainMonitor,
     pub filter: Option<C::TriggerFilter>,
     trigger_processor: Box<dyn TriggerProcessor<C, T>>,
+    data_source_groups: DataSourceGroups,
 }
 
 impl<C: Blockchain, T: RuntimeHostBuilder<C>> IndexingContext<C, T> {
@@ -99,6 +104,7 @@ impl<C: Blockchain, T: RuntimeHostBuilder<C>> IndexingContext<C, T> {
             offchain_monitor,
             filter: None,
             trigger_processor,
+            data_source_groups: DataSourceGroups::default(),
         }
     }
 
@@ -216,21 +222,153 @@ impl<C: Blockchain, T: RuntimeHostBuilder<C>> IndexingContext<C, T> {
             .await
     }
 
+    /// Calls the batch handlers of all hosts with the triggers of this block
+    /// that matched them. This must be called after all of the block's
+    /// `triggers` have been passed to `process_trigger`.
+    pub async fn process_batch_handlers(
+        &self,
+        logger: &Logger,
+        block: &Arc<C::Block>,
+        triggers: &[TriggerData<C>],
+        state: BlockState<C>,
+        proof_of_indexing: &SharedProofOfIndexing,
+        causality_region: &str,
+        debug_fork: &Option<Arc<dyn SubgraphFork>>,
+        subgraph_metrics: &Arc<SubgraphInstanceMetrics>,
+        instrument: bool,
+    ) -> Result<BlockState<C>, MappingError> {
+        self.process_batch_handlers_in_hosts(
+            logger,
+            self.instance.onchain_hosts(),
+            block,
+            triggers,
+            state,
+            proof_of_indexing,
+            causality_region,
+            debug_fork,
+            subgraph_metrics,
+            instrument,
+        )
+        .await
+    }
+
+    /// Like `process_batch_handlers`, but only for the given `hosts`.
+    ///
+    /// Each batch handler is called once with all the triggers it matched,
+    /// in the order of `triggers`. Handlers are called in the order in which
+    /// their hosts were created and, within a host, in the order in which
+    /// they first matched a trigger. Handlers that matched nothing are not
+    /// called.
+    pub async fn process_batch_handlers_in_hosts(
+        &self,
+        logger: &Logger,
+        hosts: &[Arc<T::Host>],
+        block: &Arc<C::Block>,
+        triggers: &[TriggerData<C>],
+        mut state: BlockState<C>,
+        proof_of_indexing: &SharedProofOfIndexing,
+        causality_region: &str,
+        debug_fork: &Option<Arc<dyn SubgraphFork>>,
+        subgraph_metrics: &Arc<SubgraphInstanceMetrics>,
+        instrument: bool,
+    ) -> Result<BlockState<C>, MappingError> {
+        let mut batches: Vec<(&T::Host, Vec<TriggerWithHandler<MappingTrigger<C>>>)> = vec![];
+
+        {
+            let _section = subgraph_metrics.stopwatch.start_section("match_and_decode");
+
+            for host in hosts
+                .iter()
+                .filter(|host| host.data_source().has_batch_handlers())
+            {
+                // Batches for this host start here
+                let batch_start = batches.len();
+                for trigger in triggers {
+                    let mapping_trigger = match host.match_and_decode(trigger, block, logger)? {
+                        Some(mapping_trigger) => mapping_trigger,
+                        None => continue,
+                    };
+                    let handler = mapping_trigger.handler_name();
+                    if !host.data_source().is_batch_handler(handler) {
+                        continue;
+                    }
+
+                    match batches[batch_start..]
+                        .iter_mut()
+                        .find(|(_, batch)| batch[0].handler_name() == handler)
+                    {
+                        Some((_, batch)) => batch.push(mapping_trigger),
+                        None => batches.push((host.as_ref(), vec![mapping_trigger])),
+                    }
+                }
+            }
+        }
+
+        if batches.is_empty() {
+            return Ok(state);
+        }
+
+        for (host, batch) in batches {
+            let error_count = state.deterministic_errors.len();
+
+            if let Some(proof_of_indexing) = proof_of_indexing {
+                proof_of_indexing
+                    .borrow_mut()
+                    .start_handler(causality_region);
+            }
+
+            let mapping_trigger = TriggerWithHandler::batch(batch)?;
+            let start = Instant::now();
+            state = host
+                .process_mapping_trigger(
+                    logger,
+                    mapping_trigger.block_ptr(),
+                    mapping_trigger,
+                    state,
+                    proof_of_indexing.cheap_clone(),
+                    debug_fork,
+                    instrument,
+                )
+                .await?;
+            let elapsed = start.elapsed().as_secs_f64();
+            subgraph_metrics.observe_trigger_processing_duration(elapsed);
+
+            if let Some(proof_of_indexing) = proof_of_indexing {
+                if state.deterministic_errors.len() != error_count {
+                    assert!(state.deterministic_errors.len() == error_count + 1);
+
+                    // If a deterministic error has happened, write a new
+                    // ProofOfIndexingEvent::DeterministicError to the SharedProofOfIndexing.
+                    proof_of_indexing
+                        .borrow_mut()
+                        .write_deterministic_error(logger, causality_region);
+                }
+            }
+        }
+
+        Ok(state)
+    }
+
     /// Removes data sources hosts with a creation block greater or equal to `reverted_block`, so
     /// that they are no longer candidates for `process_trigger`.
     ///
-    /// This does not currently affect the `offchain_monitor` or the `filter`, so they will continue
-    /// to include data sources that have been reverted. This is not ideal for performance, but it
-    /// does not affect correctness since triggers that have no matching host will be ignored by
+    /// This does not affect the `offchain_monitor`, so it will continue to include data sources
+    /// that have been reverted. This is not ideal for performance, but it does not affect
+    /// correctness since triggers that have no matching host will be ignored by
     /// `process_trigger`.
     ///
     /// File data sources that have been marked not done during this process will get re-queued
-    pub fn revert_data_sources(&mut self, reverted_block: BlockNumber) -> Result<(), Error> {
-        let removed = self.instance.revert_data_sources(reverted_block);
+    ///
+    /// Returns the number of onchain data sources that were removed. The caller needs to
+    /// rebuild the filter if that is not zero so that the block stream stops matching
+    /// triggers for contracts that don't exist on the canonical chain.
+    pub fn revert_data_sources(&mut self, reverted_block: BlockNumber) -> Result<usize, Error> {
+        let (reverted_onchain, removed) = self.instance.revert_data_sources(reverted_block);
 
         removed
             .into_iter()
-            .try_for_each(|source| self.offchain_monitor.add_source(source))
+            .try_for_each(|source| self.offchain_monitor.add_source(source))?;
+        Ok(reverted_onchain)
     }
 
     pub fn add_dynamic_data_source(
@@ -259,10 +397,21 @@ impl<C: Blockchain, T: RuntimeHostBuilder<C>> IndexingContext<C, T> {
         self.instance.causality_region_next_value()
     }
 
+    /// Continue the sequence of causality regions from `seq`, e.g., when
+    /// another node has created causality regions in the meantime
+    pub fn set_causality_region_seq(&mut self, seq: CausalityRegionSeq) {
+        self.instance.set_causality_region_seq(seq)
+    }
+
     pub fn hosts_len(&self) -> usize {
         self.instance.hosts_len()
     }
 
+    /// Override `GRAPH_SUBGRAPH_MAX_DATA_SOURCES` for this subgraph
+    pub fn set_max_data_sources(&mut self, max_data_sources: usize) {
+        self.instance.set_max_data_sources(max_data_sources)
+    }
+
     pub fn onchain_data_sources(&self) -> impl Iterator<Item = &C::DataSource> + Clone {
         self.instance.onchain_data_sources()
     }
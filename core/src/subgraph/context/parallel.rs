@@ -0,0 +1,451 @@
+//! Processing the triggers of a block with several groups of data sources
+//! at the same time.
+//!
+//! Handlers of data sources that never use entities of the same type can
+//! not see each other's changes, and it therefore does not matter in which
+//! order they run. Each group of such data sources processes its triggers
+//! with its own `BlockState`, and the block states are merged in manifest
+//! order afterwards. Which data sources use the same entity types is not
+//! known up front since mappings are not restricted to the entities that
+//! the manifest declares for them; processing is therefore speculative:
+//! every group keeps track of the entity types it reads and writes, and if
+//! two groups turn out to share a type, the results are thrown away, the
+//! block is processed sequentially and the two groups are merged for all
+//! later blocks. The results are also thrown away if any group fails: the
+//! error that sequential processing reports may come from an earlier
+//! trigger in another group, or the failing handler may have depended on
+//! changes from another group.
+//!
+//! The proof of indexing depends on the order of entity changes. Groups
+//! record their PoI events, and the events are written to the block's PoI
+//! in the order in which sequential processing would have produced them.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use atomic_refcell::AtomicRefCell;
+use graph::{
+    blockchain::Blockchain,
+    components::{
+        store::{SubgraphFork, WritableStore},
+        subgraph::{MappingError, RecordedEvents, RuntimeHost as _, SharedProofOfIndexing},
+    },
+    data_source::TriggerData,
+    prelude::{
+        debug,
+        futures03::{self, StreamExt},
+        BlockState, Entity, RuntimeHostBuilder, SubgraphInstanceMetrics,
+    },
+    schema::{EntityKey, EntityType},
+    slog::Logger,
+    util::lfu_cache::LfuCache,
+};
+
+use super::IndexingContext;
+
+/// Which data sources have to process triggers in the same group because
+/// their handlers use entities of the same type. Data sources are
+/// identified by their name so that all data sources created from the same
+/// template are in the same group. Every data source starts out in a group
+/// of its own
+#[derive(Default)]
+pub struct DataSourceGroups {
+    groups: HashMap<String, usize>,
+    next: usize,
+}
+
+impl DataSourceGroups {
+    fn group(&mut self, name: &str) -> usize {
+        if let Some(group) = self.groups.get(name) {
+            return *group;
+        }
+        let group = self.next;
+        self.next += 1;
+        self.groups.insert(name.to_owned(), group);
+        group
+    }
+
+    /// Put all data sources that are in the same group as the data source
+    /// `a` or the data source `b` into one group
+    fn join(&mut self, a: &str, b: &str) {
+        let (a, b) = (self.group(a), self.group(b));
+        let (keep, merged) = (a.min(b), a.max(b));
+        for group in self.groups.values_mut() {
+            if *group == merged {
+                *group = keep;
+            }
+        }
+    }
+}
+
+/// The result of `IndexingContext::process_triggers_in_parallel`
+pub enum ParallelOutcome<C: Blockchain> {
+    /// The triggers were processed, and the block state is the same as
+    /// the one that processing them sequentially would have produced
+    Processed(BlockState<C>),
+    /// The triggers have to be processed sequentially. Holds the entities
+    /// that were loaded from the store so they can be used for that
+    Sequential(LfuCache<EntityKey, Option<Entity>>),
+}
+
+impl<C: Blockchain, T: RuntimeHostBuilder<C>> IndexingContext<C, T> {
+    /// Process `triggers` with up to `parallelism` groups of data sources
+    /// at the same time, if that is possible without changing the outcome.
+    /// Batch handlers are not called; that is left to
+    /// `process_batch_handlers` just like for sequential processing
+    pub async fn process_triggers_in_parallel(
+        &mut self,
+        logger: &Logger,
+        block: &Arc<C::Block>,
+        triggers: &[TriggerData<C>],
+        store: &Arc<dyn WritableStore>,
+        entity_lfu_cache: LfuCache<EntityKey, Option<Entity>>,
+        proof_of_indexing: &SharedProofOfIndexing,
+        causality_region: &str,
+        debug_fork: &Option<Arc<dyn SubgraphFork>>,
+        subgraph_metrics: &Arc<SubgraphInstanceMetrics>,
+        instrument: bool,
+        parallelism: usize,
+    ) -> Result<ParallelOutcome<C>, MappingError> {
+        if triggers
+            .iter()
+            .any(|trigger| matches!(trigger, TriggerData::Offchain(_)))
+        {
+            return Ok(ParallelOutcome::Sequential(entity_lfu_cache));
+        }
+
+        let instance = &self.instance;
+        let groups = &mut self.data_source_groups;
+
+        // The position of each group in the manifest is the position of
+        // its first host
+        let mut positions = HashMap::new();
+        for (position, host) in instance.onchain_hosts().iter().enumerate() {
+            positions
+                .entry(groups.group(host.data_source().name()))
+                .or_insert(position);
+        }
+
+        // The work for each group, by position: the hosts that need to
+        // process each trigger, identified by the index of the trigger and
+        // the position of the host among the hosts for that trigger
+        let mut work = BTreeMap::new();
+        for (index, trigger) in triggers.iter().enumerate() {
+            for (rank, host) in instance.hosts_for_trigger(trigger).enumerate() {
+                let group = groups.group(host.data_source().name());
+                let position = positions.get(&group).copied().unwrap_or(usize::MAX);
+                work.entry((position, group))
+                    .or_insert_with(Vec::new)
+                    .push((index, rank, host));
+            }
+        }
+        if work.len() < 2 {
+            return Ok(ParallelOutcome::Sequential(entity_lfu_cache));
+        }
+
+        let mut entity_lfu_cache = Some(entity_lfu_cache);
+        let trigger_processor = &self.trigger_processor;
+        // A data source from each group to identify the group by
+        let members: Vec<_> = work
+            .values()
+            .map(|work| work[0].2.data_source().name().to_owned())
+            .collect();
+        let runs = work.into_values().map(|work| {
+            let mut state =
+                BlockState::<C>::new(store.clone(), entity_lfu_cache.take().unwrap_or_default());
+            state.entity_cache.track_entity_types();
+            let recorder = proof_of_indexing
+                .as_ref()
+                .map(|poi| Arc::new(AtomicRefCell::new(poi.borrow().recorder())));
+
+            async move {
+                let mut recorded: Vec<((usize, usize), RecordedEvents)> = vec![];
+                for (index, rank, host) in work {
+                    let trigger = &triggers[index];
+                    state = trigger_processor
+                        .process_trigger(
+                            logger,
+                            Box::new(std::iter::once(host)),
+                            block,
+                            trigger,
+                            state,
+                            &recorder,
+                            causality_region,
+                            debug_fork,
+                            subgraph_metrics,
+                            instrument,
+                        )
+                        .await
+                        .map_err(move |mut e| {
+                            let error_context = trigger.error_context();
+                            if !error_context.is_empty() {
+                                e = e.context(error_context);
+                            }
+                            e.context("failed to process trigger".to_string())
+                        })?;
+                    if let Some(recorder) = &recorder {
+                        recorded.push(((index, rank), recorder.borrow_mut().take_recorded()));
+                    }
+                }
+                Ok::<_, MappingError>((state, recorded))
+            }
+        });
+        let mut failed = false;
+        let results: Vec<_> = futures03::stream::iter(runs)
+            .buffered(parallelism)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .zip(members.iter())
+            .filter_map(|(result, member)| match result {
+                Ok(result) => Some((member, result)),
+                Err(e) => {
+                    debug!(logger, "Processing triggers in parallel failed, processing them sequentially";
+                        "data_source" => member,
+                        "error" => format!("{:?}", e));
+                    failed = true;
+                    None
+                }
+            })
+            .collect();
+
+        // Check that the groups really were independent. The groups that
+        // succeeded are checked even if another one failed so that shared
+        // entity types are detected as early as possible
+        let mut sequential = failed;
+        let mut users: HashMap<&EntityType, &str> = HashMap::new();
+        let mut conflicts = vec![];
+        for (member, (state, _)) in &results {
+            let member: &str = member;
+            if state.has_errors()
+                || state.has_created_data_sources()
+                || state.entity_cache.generated_ids()
+            {
+                sequential = true;
+            }
+            for entity_type in state.entity_cache.entity_types().into_iter().flatten() {
+                match users.get(entity_type) {
+                    Some(user) if *user != member => conflicts.push((*user, member)),
+                    Some(_) => {}
+                    None => {
+                        users.insert(entity_type, member);
+                    }
+                }
+            }
+        }
+        if !conflicts.is_empty() {
+            debug!(logger, "Data sources share entity types, processing them together";
+                "conflicts" => conflicts.len());
+            for (a, b) in conflicts {
+                self.data_source_groups.join(a, b);
+            }
+            sequential = true;
+        }
+
+        if sequential {
+            let mut current = LfuCache::new();
+            for (_, (state, _)) in results {
+                current.extend(state.entity_cache.into_current());
+            }
+            return Ok(ParallelOutcome::Sequential(current));
+        }
+
+        let mut results = results.into_iter().map(|(_, result)| result);
+        // Unwrap: there are at least two groups
+        let (mut block_state, mut recorded) = results.next().unwrap();
+        for (state, events) in results {
+            block_state.extend(state);
+            recorded.extend(events);
+        }
+
+        if let Some(proof_of_indexing) = proof_of_indexing {
+            let mut proof_of_indexing = proof_of_indexing.borrow_mut();
+            recorded.sort_by_key(|(key, _)| *key);
+            let mut started = None;
+            for ((index, _), events) in recorded {
+                if started != Some(index) && events.started_handler() {
+                    proof_of_indexing.start_handler(causality_region);
+                    started = Some(index);
+                }
+                proof_of_indexing.replay(logger, events);
+            }
+        }
+
+        Ok(ParallelOutcome::Processed(block_state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use graph::components::subgraph::MappingError;
+    use graph::prelude::{anyhow::anyhow, tokio, BlockNumber, Value};
+    use graph_chain_ethereum::Chain;
+
+    use super::DataSourceGroups;
+    use crate::subgraph::testing::{
+        block, block_data_source, BlockOutcome, MockRuntimeHostBuilder, TestInstance,
+    };
+
+    const SCHEMA: &str = "
+        type Token @entity { id: ID!, block: Int! }
+        type Transfer @entity { id: ID!, block: Int! }
+        type Counter @entity { id: ID!, count: Int! }";
+
+    /// `tokens` creates a token in every block. `transfers` records a
+    /// transfer in every block, and reads the token of the block in blocks
+    /// from `reads_from` on; since it can only see the token when it runs
+    /// after `tokens`, it fails when its group runs on its own. `counter`
+    /// fails in block `fails_at`, no matter how triggers are processed
+    fn host_builder(
+        reads_from: BlockNumber,
+        fails_at: BlockNumber,
+    ) -> MockRuntimeHostBuilder<Chain> {
+        MockRuntimeHostBuilder::default()
+            .with_handler("tokens", "handleBlock", |ctx, _| {
+                let block = ctx.block;
+                ctx.set(
+                    "Token",
+                    &format!("t-{}", block),
+                    vec![("block", Value::Int(block))],
+                )
+            })
+            .with_handler("transfers", "handleBlock", move |ctx, _| {
+                let block = ctx.block;
+                if block >= reads_from && ctx.get("Token", &format!("t-{}", block))?.is_none() {
+                    return Err(MappingError::Unknown(anyhow!("missing token")));
+                }
+                ctx.set(
+                    "Transfer",
+                    &format!("x-{}", block),
+                    vec![("block", Value::Int(block))],
+                )
+            })
+            .with_handler("counter", "handleBlock", move |ctx, _| {
+                if ctx.block == fails_at {
+                    return Err(MappingError::Unknown(anyhow!("counter failed")));
+                }
+                let count = ctx
+                    .get("Counter", "c")?
+                    .and_then(|counter| counter.get("count").and_then(|count| count.as_int()))
+                    .unwrap_or(0);
+                ctx.set("Counter", "c", vec![("count", Value::Int(count + 1))])
+            })
+    }
+
+    /// Process blocks `0..blocks` of the subgraph with the given
+    /// parallelism and return the outcome of each block, with the entity
+    /// modifications in a deterministic order
+    async fn run(
+        parallelism: usize,
+        blocks: BlockNumber,
+        reads_from: BlockNumber,
+        fails_at: BlockNumber,
+    ) -> Vec<Result<BlockOutcome, MappingError>> {
+        let data_sources = ["tokens", "transfers", "counter"]
+            .into_iter()
+            .map(|name| block_data_source(name, "handleBlock"))
+            .collect();
+        let mut instance =
+            TestInstance::new(SCHEMA, data_sources, host_builder(reads_from, fails_at))
+                .unwrap()
+                .with_parallelism(parallelism);
+
+        let mut outcomes = vec![];
+        for number in 0..blocks {
+            let outcome = instance
+                .process_block(&block(number))
+                .await
+                .map(|mut outcome| {
+                    outcome.modifications.sort_by(|a, b| a.key().cmp(b.key()));
+                    outcome
+                });
+            let failed = outcome.is_err();
+            outcomes.push(outcome);
+            if failed {
+                break;
+            }
+        }
+        outcomes
+    }
+
+    fn assert_same(
+        sequential: &[Result<BlockOutcome, MappingError>],
+        parallel: &[Result<BlockOutcome, MappingError>],
+    ) {
+        assert_eq!(sequential.len(), parallel.len());
+        for (sequential, parallel) in sequential.iter().zip(parallel) {
+            match (sequential, parallel) {
+                (Ok(sequential), Ok(parallel)) => {
+                    assert_eq!(sequential.modifications, parallel.modifications);
+                    assert_eq!(sequential.poi, parallel.poi);
+                }
+                (Err(_), Err(_)) => {}
+                (sequential, parallel) => panic!(
+                    "sequential processing gave {:?}, parallel processing {:?}",
+                    sequential, parallel
+                ),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn parallel_processing_matches_sequential_processing() {
+        let sequential = run(1, 4, BlockNumber::MAX, BlockNumber::MAX).await;
+        let parallel = run(4, 4, BlockNumber::MAX, BlockNumber::MAX).await;
+
+        assert_same(&sequential, &parallel);
+        assert!(sequential
+            .iter()
+            .all(|outcome| !outcome.as_ref().unwrap().parallel));
+        assert!(parallel
+            .iter()
+            .all(|outcome| outcome.as_ref().unwrap().parallel));
+    }
+
+    #[tokio::test]
+    async fn failing_groups_are_processed_sequentially() {
+        // `transfers` fails in its own group from block 2 on, but not when
+        // it is processed after `tokens`
+        let sequential = run(1, 4, 2, BlockNumber::MAX).await;
+        let parallel = run(4, 4, 2, BlockNumber::MAX).await;
+
+        assert_same(&sequential, &parallel);
+        let processed_in_parallel: Vec<_> = parallel
+            .iter()
+            .map(|outcome| outcome.as_ref().unwrap().parallel)
+            .collect();
+        assert_eq!(vec![true, true, false, false], processed_in_parallel);
+
+        // `counter` fails in block 2, and sequential processing reports
+        // that error
+        let sequential = run(1, 4, BlockNumber::MAX, 2).await;
+        let parallel = run(4, 4, BlockNumber::MAX, 2).await;
+
+        assert_same(&sequential, &parallel);
+        assert_eq!(3, parallel.len());
+        assert!(parallel[2].is_err());
+    }
+
+    #[test]
+    fn joins_groups() {
+        let mut groups = DataSourceGroups::default();
+        let (a, b, c) = (groups.group("a"), groups.group("b"), groups.group("c"));
+        assert_eq!(
+            3,
+            [a, b, c]
+                .iter()
+                .collect::<std::collections::HashSet<_>>()
+                .len()
+        );
+        assert_eq!(a, groups.group("a"));
+
+        groups.join("c", "b");
+        assert_eq!(b, groups.group("c"));
+        assert_ne!(a, groups.group("c"));
+
+        // Joining with any member of a group joins the whole group
+        groups.join("c", "a");
+        assert_eq!(a, groups.group("b"));
+        assert_eq!(a, groups.group("c"));
+    }
+}
@@ -26,3 +26,9 @@ impl From<StoreError> for BlockProcessingError {
         BlockProcessingError::Unknown(e.into())
     }
 }
+
+/// A subgraph tried to have more data sources than
+/// `GRAPH_SUBGRAPH_MAX_DATA_SOURCES` allows
+#[derive(thiserror::Error, Debug)]
+#[error("Limit of {0} data sources per subgraph exceeded")]
+pub struct DataSourceLimitExceeded(pub usize);
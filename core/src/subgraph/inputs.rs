@@ -25,6 +25,10 @@ pub struct IndexingInputs<C: Blockchain> {
     pub unified_api_version: UnifiedMappingApiVersion,
     pub static_filters: bool,
     pub poi_version: ProofOfIndexingVersion,
+    /// Whether to process the triggers of a block in the order that
+    /// `TriggerData::cmp_position` gives them, which deployments with spec
+    /// version 1.0.0 or later do
+    pub total_trigger_order: bool,
     pub network: String,
 
     /// Whether to instrument trigger processing and log additional,
@@ -48,6 +52,7 @@ impl<C: Blockchain> IndexingInputs<C> {
             unified_api_version,
             static_filters,
             poi_version,
+            total_trigger_order,
             network,
             instrument,
         } = self;
@@ -65,6 +70,7 @@ impl<C: Blockchain> IndexingInputs<C> {
             unified_api_version: unified_api_version.clone(),
             static_filters: *static_filters,
             poi_version: *poi_version,
+            total_trigger_order: *total_trigger_order,
             network: network.clone(),
             instrument: *instrument,
         }
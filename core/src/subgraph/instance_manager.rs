@@ -7,9 +7,10 @@ use std::collections::BTreeSet;
 use crate::subgraph::runner::SubgraphRunner;
 use graph::blockchain::block_stream::BlockStreamMetrics;
 use graph::blockchain::{Blockchain, BlockchainKind, DataSource, NodeCapabilities};
+use graph::components::link_resolver::VerifyingLinkResolver;
 use graph::components::metrics::gas::GasMetrics;
 use graph::components::subgraph::ProofOfIndexingVersion;
-use graph::data::subgraph::{UnresolvedSubgraphManifest, SPEC_VERSION_0_0_6};
+use graph::data::subgraph::{UnresolvedSubgraphManifest, SPEC_VERSION_0_0_6, SPEC_VERSION_1_0_0};
 use graph::data_source::causality_region::CausalityRegionSeq;
 use graph::env::EnvVars;
 use graph::prelude::{SubgraphInstanceManager as SubgraphInstanceManagerTrait, *};
@@ -19,7 +20,18 @@ use graph_runtime_wasm::RuntimeHostBuilder;
 use tokio::task;
 
 use super::context::OffchainMonitor;
-use super::SubgraphTriggerProcessor;
+use super::{BlockReplay, SubgraphTriggerProcessor};
+
+/// The kind of store a runner uses
+#[derive(Clone)]
+enum StoreMode {
+    /// A store that indexes the deployment
+    Write,
+    /// The store of a standby instance on the given node
+    Standby(NodeId),
+    /// A store for replaying the given block
+    Replay(BlockNumber),
+}
 
 #[derive(Clone)]
 pub struct SubgraphInstanceManager<S: SubgraphStore> {
@@ -42,22 +54,67 @@ impl<S: SubgraphStore> SubgraphInstanceManagerTrait for SubgraphInstanceManager<
         loc: DeploymentLocator,
         manifest: serde_yaml::Mapping,
         stop_block: Option<BlockNumber>,
+    ) {
+        self.start_instance(loc, manifest, stop_block, None).await
+    }
+
+    async fn start_standby(
+        self: Arc<Self>,
+        loc: DeploymentLocator,
+        manifest: serde_yaml::Mapping,
+        node: NodeId,
+    ) {
+        self.start_instance(loc, manifest, None, Some(node)).await
+    }
+
+    async fn stop_subgraph(&self, loc: DeploymentLocator) {
+        let logger = self.logger_factory.subgraph_logger(&loc);
+
+        // Stop the block stream first so that the writer does not receive
+        // any more blocks while it writes the ones it has
+        self.instances.remove(&loc.id);
+
+        match self.subgraph_store.stop_subgraph(&loc).await {
+            Ok(()) => debug!(logger, "Stopped subgraph writer"),
+            Err(err) => {
+                error!(logger, "Error stopping subgraph writer"; "error" => format!("{:#}", err))
+            }
+        }
+
+        info!(logger, "Stopped subgraph");
+    }
+}
+
+impl<S: SubgraphStore> SubgraphInstanceManager<S> {
+    /// Start the deployment, or a standby instance of it if `standby` is
+    /// the node on which that instance runs
+    async fn start_instance(
+        self: Arc<Self>,
+        loc: DeploymentLocator,
+        manifest: serde_yaml::Mapping,
+        stop_block: Option<BlockNumber>,
+        standby: Option<NodeId>,
     ) {
         let logger = self.logger_factory.subgraph_logger(&loc);
         let err_logger = logger.clone();
         let instance_manager = self.cheap_clone();
+        let mode = match standby {
+            Some(node) => StoreMode::Standby(node),
+            None => StoreMode::Write,
+        };
 
         let subgraph_start_future = async move {
             match BlockchainKind::from_manifest(&manifest)? {
                 BlockchainKind::Arweave => {
                     let runner = instance_manager
-                        .build_subgraph_runner::<graph_chain_arweave::Chain>(
+                        .build_runner::<graph_chain_arweave::Chain>(
                             logger.clone(),
                             self.env_vars.cheap_clone(),
                             loc.clone(),
                             manifest,
                             stop_block,
                             Box::new(SubgraphTriggerProcessor {}),
+                            mode.clone(),
                         )
                         .await?;
 
@@ -65,13 +122,14 @@ impl<S: SubgraphStore> SubgraphInstanceManagerTrait for SubgraphInstanceManager<
                 }
                 BlockchainKind::Ethereum => {
                     let runner = instance_manager
-                        .build_subgraph_runner::<graph_chain_ethereum::Chain>(
+                        .build_runner::<graph_chain_ethereum::Chain>(
                             logger.clone(),
                             self.env_vars.cheap_clone(),
                             loc.clone(),
                             manifest,
                             stop_block,
                             Box::new(SubgraphTriggerProcessor {}),
+                            mode.clone(),
                         )
                         .await?;
 
@@ -79,13 +137,14 @@ impl<S: SubgraphStore> SubgraphInstanceManagerTrait for SubgraphInstanceManager<
                 }
                 BlockchainKind::Near => {
                     let runner = instance_manager
-                        .build_subgraph_runner::<graph_chain_near::Chain>(
+                        .build_runner::<graph_chain_near::Chain>(
                             logger.clone(),
                             self.env_vars.cheap_clone(),
                             loc.clone(),
                             manifest,
                             stop_block,
                             Box::new(SubgraphTriggerProcessor {}),
+                            mode.clone(),
                         )
                         .await?;
 
@@ -93,13 +152,14 @@ impl<S: SubgraphStore> SubgraphInstanceManagerTrait for SubgraphInstanceManager<
                 }
                 BlockchainKind::Cosmos => {
                     let runner = instance_manager
-                        .build_subgraph_runner::<graph_chain_cosmos::Chain>(
+                        .build_runner::<graph_chain_cosmos::Chain>(
                             logger.clone(),
                             self.env_vars.cheap_clone(),
                             loc.clone(),
                             manifest,
                             stop_block,
                             Box::new(SubgraphTriggerProcessor {}),
+                            mode.clone(),
                         )
                         .await?;
 
@@ -107,13 +167,14 @@ impl<S: SubgraphStore> SubgraphInstanceManagerTrait for SubgraphInstanceManager<
                 }
                 BlockchainKind::Substreams => {
                     let runner = instance_manager
-                        .build_subgraph_runner::<graph_chain_substreams::Chain>(
+                        .build_runner::<graph_chain_substreams::Chain>(
                             logger.clone(),
                             self.env_vars.cheap_clone(),
                             loc.cheap_clone(),
                             manifest,
                             stop_block,
                             Box::new(graph_chain_substreams::TriggerProcessor::new(loc.clone())),
+                            mode.clone(),
                         )
                         .await?;
 
@@ -121,13 +182,14 @@ impl<S: SubgraphStore> SubgraphInstanceManagerTrait for SubgraphInstanceManager<
                 }
                 BlockchainKind::Starknet => {
                     let runner = instance_manager
-                        .build_subgraph_runner::<graph_chain_starknet::Chain>(
+                        .build_runner::<graph_chain_starknet::Chain>(
                             logger.clone(),
                             self.env_vars.cheap_clone(),
                             loc.clone(),
                             manifest,
                             stop_block,
                             Box::new(SubgraphTriggerProcessor {}),
+                            mode.clone(),
                         )
                         .await?;
 
@@ -154,23 +216,6 @@ impl<S: SubgraphStore> SubgraphInstanceManagerTrait for SubgraphInstanceManager<
         });
     }
 
-    async fn stop_subgraph(&self, loc: DeploymentLocator) {
-        let logger = self.logger_factory.subgraph_logger(&loc);
-
-        match self.subgraph_store.stop_subgraph(&loc).await {
-            Ok(()) => debug!(logger, "Stopped subgraph writer"),
-            Err(err) => {
-                error!(logger, "Error stopping subgraph writer"; "error" => format!("{:#}", err))
-            }
-        }
-
-        self.instances.remove(&loc.id);
-
-        info!(logger, "Stopped subgraph");
-    }
-}
-
-impl<S: SubgraphStore> SubgraphInstanceManager<S> {
     pub fn new(
         logger_factory: &LoggerFactory,
         env_vars: Arc<EnvVars>,
@@ -209,6 +254,68 @@ impl<S: SubgraphStore> SubgraphInstanceManager<S> {
         stop_block: Option<BlockNumber>,
         tp: Box<dyn TriggerProcessor<C, RuntimeHostBuilder<C>>>,
     ) -> anyhow::Result<SubgraphRunner<C, RuntimeHostBuilder<C>>>
+    where
+        C: Blockchain,
+        <C as Blockchain>::MappingTrigger: ToAscPtr,
+    {
+        self.build_runner(
+            logger,
+            env_vars,
+            deployment,
+            manifest,
+            stop_block,
+            tp,
+            StoreMode::Write,
+        )
+        .await
+    }
+
+    /// Run the handlers of the deployment for `block` against the state of
+    /// the deployment as of the block before it, without writing anything
+    /// to the store. The deployment does not need to be assigned to this
+    /// node; if it is being indexed elsewhere, that is not disturbed
+    pub async fn replay_block<C>(
+        &self,
+        logger: Logger,
+        env_vars: Arc<EnvVars>,
+        deployment: DeploymentLocator,
+        manifest: serde_yaml::Mapping,
+        block: BlockNumber,
+        tp: Box<dyn TriggerProcessor<C, RuntimeHostBuilder<C>>>,
+    ) -> anyhow::Result<BlockReplay>
+    where
+        C: Blockchain,
+        <C as Blockchain>::MappingTrigger: ToAscPtr,
+    {
+        let runner = self
+            .build_runner(
+                logger,
+                env_vars,
+                deployment,
+                manifest,
+                None,
+                tp,
+                StoreMode::Replay(block),
+            )
+            .await?;
+        runner.replay_block(block).await
+    }
+
+    /// Build the runner for the deployment. For a standby instance, the
+    /// runner reads everything it needs from the store, but does not
+    /// change anything in the store until it has taken over the
+    /// deployment. A runner for replaying a block never changes anything
+    /// in the store
+    async fn build_runner<C>(
+        &self,
+        logger: Logger,
+        env_vars: Arc<EnvVars>,
+        deployment: DeploymentLocator,
+        manifest: serde_yaml::Mapping,
+        stop_block: Option<BlockNumber>,
+        tp: Box<dyn TriggerProcessor<C, RuntimeHostBuilder<C>>>,
+        mode: StoreMode,
+    ) -> anyhow::Result<SubgraphRunner<C, RuntimeHostBuilder<C>>>
     where
         C: Blockchain,
         <C as Blockchain>::MappingTrigger: ToAscPtr,
@@ -219,17 +326,23 @@ impl<S: SubgraphStore> SubgraphInstanceManager<S> {
         let raw_yaml = serde_yaml::to_string(&manifest).unwrap();
         let manifest = UnresolvedSubgraphManifest::parse(deployment.hash.cheap_clone(), manifest)?;
 
-        // Allow for infinite retries for subgraph definition files.
-        let link_resolver = Arc::from(self.link_resolver.with_retries());
-
-        // Make sure the `raw_yaml` is present on both this subgraph and the graft base.
-        self.subgraph_store
-            .set_manifest_raw_yaml(&deployment.hash, raw_yaml)
-            .await?;
-        if let Some(graft) = &manifest.graft {
+        // Allow for infinite retries for subgraph definition files, and
+        // check them against their CIDs like we do when deploying
+        let verifying_resolver = VerifyingLinkResolver::new(self.link_resolver.cheap_clone());
+        let link_resolver: Arc<dyn LinkResolver> = Arc::from(verifying_resolver.with_retries());
+
+        // Make sure the `raw_yaml` is present on both this subgraph and the
+        // graft base. The node that indexes the deployment takes care of
+        // that when we only read from the store
+        let writes = matches!(mode, StoreMode::Write);
+        if writes {
+            self.subgraph_store
+                .set_manifest_raw_yaml(&deployment.hash, raw_yaml)
+                .await?;
+        }
+        if let Some(graft) = manifest.graft.as_ref().filter(|_| writes) {
             if self.subgraph_store.is_deployed(&graft.base)? {
-                let file_bytes = self
-                    .link_resolver
+                let file_bytes = verifying_resolver
                     .cat(&logger, &graft.base.to_ipfs_link())
                     .await?;
                 let yaml = String::from_utf8(file_bytes)?;
@@ -267,30 +380,46 @@ impl<S: SubgraphStore> SubgraphInstanceManager<S> {
             );
         }
 
-        let store = self
-            .subgraph_store
-            .cheap_clone()
-            .writable(
-                logger.clone(),
-                deployment.id,
-                Arc::new(manifest.template_idx_and_name().collect()),
-            )
-            .await?;
-
-        // Create deployment features from the manifest
-        // Write it to the database
-        let deployment_features = manifest.deployment_features();
-        self.subgraph_store
-            .create_subgraph_features(deployment_features)?;
+        let manifest_idx_and_name = Arc::new(manifest.template_idx_and_name().collect());
+        let store = match mode {
+            StoreMode::Write => {
+                let store = self
+                    .subgraph_store
+                    .cheap_clone()
+                    .writable(logger.clone(), deployment.id, manifest_idx_and_name)
+                    .await?;
 
-        // Start the subgraph deployment before reading dynamic data
-        // sources; if the subgraph is a graft or a copy, starting it will
-        // do the copying and dynamic data sources won't show up until after
-        // that is done
-        store.start_subgraph_deployment(&logger).await?;
+                // Create deployment features from the manifest
+                // Write it to the database
+                let deployment_features = manifest.deployment_features();
+                self.subgraph_store
+                    .create_subgraph_features(deployment_features)?;
+
+                // Start the subgraph deployment before reading dynamic data
+                // sources; if the subgraph is a graft or a copy, starting it will
+                // do the copying and dynamic data sources won't show up until after
+                // that is done
+                store.start_subgraph_deployment(&logger).await?;
+                store
+            }
+            StoreMode::Standby(node) => {
+                info!(logger, "Building standby instance"; "node_id" => node.to_string());
+                self.subgraph_store
+                    .cheap_clone()
+                    .standby_writable(logger.clone(), deployment.id, manifest_idx_and_name, node)
+                    .await?
+            }
+            StoreMode::Replay(block) => {
+                info!(logger, "Building instance to replay block"; "block_number" => block);
+                self.subgraph_store
+                    .cheap_clone()
+                    .replay_writable(logger.clone(), deployment.id, manifest_idx_and_name, block)
+                    .await?
+            }
+        };
 
         let dynamic_data_sources =
-            load_dynamic_data_sources(store.clone(), logger.clone(), &manifest)
+            load_dynamic_data_sources(store.clone(), logger.clone(), &manifest.templates)
                 .await
                 .context("Failed to load dynamic data sources")?;
 
@@ -402,6 +531,7 @@ impl<S: SubgraphStore> SubgraphInstanceManager<S> {
         } else {
             ProofOfIndexingVersion::Legacy
         };
+        let total_trigger_order = manifest.spec_version.ge(&SPEC_VERSION_1_0_0);
 
         let causality_region_seq =
             CausalityRegionSeq::from_current(store.causality_region_curr_val().await?);
@@ -422,6 +552,7 @@ impl<S: SubgraphStore> SubgraphInstanceManager<S> {
             unified_api_version,
             static_filters: self.static_filters,
             poi_version,
+            total_trigger_order,
             network,
             instrument,
         };
@@ -2,15 +2,18 @@ use std::time::Instant;
 
 use graph::blockchain::Blockchain;
 use graph::components::store::WritableStore;
-use graph::data_source::DataSource;
+use graph::data_source::{DataSource, DataSourceTemplate};
 use graph::prelude::*;
 
 pub async fn load_dynamic_data_sources<C: Blockchain>(
     store: Arc<dyn WritableStore>,
     logger: Logger,
-    manifest: &SubgraphManifest<C>,
+    templates: &[DataSourceTemplate<C>],
 ) -> Result<Vec<DataSource<C>>, Error> {
-    let manifest_idx_and_name = manifest.template_idx_and_name().collect();
+    let manifest_idx_and_name = templates
+        .iter()
+        .map(|template| (template.manifest_idx(), template.name().to_owned()))
+        .collect();
     let start_time = Instant::now();
 
     let mut data_sources: Vec<DataSource<C>> = vec![];
@@ -19,8 +22,7 @@ pub async fn load_dynamic_data_sources<C: Blockchain>(
         .load_dynamic_data_sources(manifest_idx_and_name)
         .await?
     {
-        let template = manifest
-            .templates
+        let template = templates
             .iter()
             .find(|template| template.manifest_idx() == stored.manifest_idx)
             .ok_or_else(|| anyhow!("no template with idx `{}` was found", stored.manifest_idx))?;
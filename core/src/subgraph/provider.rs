@@ -1,44 +1,56 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::sync::Mutex;
 
 use async_trait::async_trait;
 
 use graph::{
     components::store::{DeploymentId, DeploymentLocator},
+    data::subgraph::parse_raw_manifest,
     prelude::{SubgraphAssignmentProvider as SubgraphAssignmentProviderTrait, *},
 };
 
 #[derive(Debug)]
 struct DeploymentRegistry {
-    subgraphs_deployed: Arc<Mutex<HashSet<DeploymentId>>>,
+    subgraphs_deployed: Arc<Mutex<HashMap<DeploymentId, DeploymentLocator>>>,
     subgraph_metrics: Arc<SubgraphCountMetric>,
 }
 
 impl DeploymentRegistry {
     fn new(subgraph_metrics: Arc<SubgraphCountMetric>) -> Self {
         Self {
-            subgraphs_deployed: Arc::new(Mutex::new(HashSet::new())),
+            subgraphs_deployed: Arc::new(Mutex::new(HashMap::new())),
             subgraph_metrics,
         }
     }
 
-    fn insert(&self, id: DeploymentId) -> bool {
-        if !self.subgraphs_deployed.lock().unwrap().insert(id) {
+    fn insert(&self, loc: &DeploymentLocator) -> bool {
+        let mut deployed = self.subgraphs_deployed.lock().unwrap();
+        if deployed.contains_key(&loc.id) {
             return false;
         }
+        deployed.insert(loc.id, loc.clone());
 
         self.subgraph_metrics.deployment_count.inc();
         true
     }
 
     fn remove(&self, id: &DeploymentId) -> bool {
-        if !self.subgraphs_deployed.lock().unwrap().remove(id) {
+        if self.subgraphs_deployed.lock().unwrap().remove(id).is_none() {
             return false;
         }
 
         self.subgraph_metrics.deployment_count.dec();
         true
     }
+
+    fn locators(&self) -> Vec<DeploymentLocator> {
+        self.subgraphs_deployed
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect()
+    }
 }
 
 pub struct SubgraphAssignmentProvider<I> {
@@ -68,17 +80,30 @@ impl<I: SubgraphInstanceManager> SubgraphAssignmentProvider<I> {
     }
 }
 
-#[async_trait]
-impl<I: SubgraphInstanceManager> SubgraphAssignmentProviderTrait for SubgraphAssignmentProvider<I> {
-    async fn start(
+impl<I: SubgraphInstanceManager> SubgraphAssignmentProvider<I> {
+    /// Stop all deployments that run on this node, which writes the blocks
+    /// they have already processed to the store. Used when the node shuts
+    /// down
+    pub async fn stop_all(&self) {
+        let stops = self
+            .deployment_registry
+            .locators()
+            .into_iter()
+            .map(|loc| self.stop(loc));
+        futures03::future::join_all(stops).await;
+    }
+
+    /// Start the deployment, or a standby instance of it on `standby`
+    async fn start_instance(
         &self,
         loc: DeploymentLocator,
         stop_block: Option<BlockNumber>,
+        standby: Option<NodeId>,
     ) -> Result<(), SubgraphAssignmentProviderError> {
         let logger = self.logger_factory.subgraph_logger(&loc);
 
         // If subgraph ID already in set
-        if !self.deployment_registry.insert(loc.id) {
+        if !self.deployment_registry.insert(&loc) {
             info!(logger, "Subgraph deployment is already running");
 
             return Err(SubgraphAssignmentProviderError::AlreadyRunning(
@@ -92,16 +117,45 @@ impl<I: SubgraphInstanceManager> SubgraphAssignmentProviderTrait for SubgraphAss
             .await
             .map_err(SubgraphAssignmentProviderError::ResolveError)?;
 
-        let raw: serde_yaml::Mapping = serde_yaml::from_slice(&file_bytes)
+        let raw: serde_yaml::Mapping = parse_raw_manifest(&file_bytes)
             .map_err(|e| SubgraphAssignmentProviderError::ResolveError(e.into()))?;
 
-        self.instance_manager
-            .cheap_clone()
-            .start_subgraph(loc, raw, stop_block)
-            .await;
+        match standby {
+            None => {
+                self.instance_manager
+                    .cheap_clone()
+                    .start_subgraph(loc, raw, stop_block)
+                    .await
+            }
+            Some(node) => {
+                self.instance_manager
+                    .cheap_clone()
+                    .start_standby(loc, raw, node)
+                    .await
+            }
+        }
 
         Ok(())
     }
+}
+
+#[async_trait]
+impl<I: SubgraphInstanceManager> SubgraphAssignmentProviderTrait for SubgraphAssignmentProvider<I> {
+    async fn start(
+        &self,
+        loc: DeploymentLocator,
+        stop_block: Option<BlockNumber>,
+    ) -> Result<(), SubgraphAssignmentProviderError> {
+        self.start_instance(loc, stop_block, None).await
+    }
+
+    async fn start_standby(
+        &self,
+        loc: DeploymentLocator,
+        node: NodeId,
+    ) -> Result<(), SubgraphAssignmentProviderError> {
+        self.start_instance(loc, None, Some(node)).await
+    }
 
     async fn stop(
         &self,
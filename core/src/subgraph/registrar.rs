@@ -1,14 +1,16 @@
-use std::collections::HashSet;
-use std::time::Instant;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use graph::blockchain::Blockchain;
 use graph::blockchain::BlockchainKind;
 use graph::blockchain::BlockchainMap;
+use graph::blockchain::NodeCapabilities as _;
+use graph::components::link_resolver::VerifyingLinkResolver;
 use graph::components::store::{DeploymentId, DeploymentLocator, SubscriptionManager};
 use graph::components::subgraph::Settings;
-use graph::data::subgraph::schema::DeploymentCreate;
-use graph::data::subgraph::Graft;
+use graph::data::subgraph::schema::{DeploymentCreate, DeploymentMetadata, DeploymentPlacement};
+use graph::data::subgraph::{parse_raw_manifest, Graft, SubgraphManifestWarning};
 use graph::prelude::{
     CreateSubgraphResult, SubgraphAssignmentProvider as SubgraphAssignmentProviderTrait,
     SubgraphRegistrar as SubgraphRegistrarTrait, *,
@@ -196,11 +198,35 @@ where
                                             })))
                                         } else {
                                             // Ensure it is removed from this node
-                                            debug!(logger, "Deployment assignee is not this node, broadcasting remove event"; "assigned_to" => assigned, "node_id" => &node_id);
-                                            Box::new(stream::once(Ok(AssignmentEvent::Remove {
-                                                deployment,
+                                            debug!(logger, "Deployment assignee is not this node, broadcasting remove event"; "assigned_to" => &assigned, "node_id" => &node_id);
+                                            let remove = AssignmentEvent::Remove {
+                                                deployment: deployment.clone(),
                                                 node_id: node_id.clone(),
-                                            })))
+                                            };
+
+                                            // If this node keeps a standby instance of the
+                                            // deployment, start it afresh. Whatever this node
+                                            // was running for the deployment so far might not
+                                            // be a standby instance
+                                            let standby = match store.standby_node(&deployment) {
+                                                Ok(standby) => standby,
+                                                Err(e) => {
+                                                    error!(logger, "Failed to get standby node"; "error" => e.to_string());
+                                                    None
+                                                }
+                                            };
+                                            if !is_paused && standby.as_ref() == Some(&node_id) {
+                                                debug!(logger, "This node keeps a standby instance, broadcasting standby event"; "assigned_to" => &assigned, "node_id" => &node_id);
+                                                Box::new(stream::iter_ok(vec![
+                                                    remove,
+                                                    AssignmentEvent::Standby {
+                                                        deployment,
+                                                        node_id: node_id.clone(),
+                                                    },
+                                                ]))
+                                            } else {
+                                                Box::new(stream::once(Ok(remove)))
+                                            }
                                         }
                                     } else {
                                         // Was added/updated, but is now gone.
@@ -229,32 +255,78 @@ where
         let logger = self.logger.clone();
         let node_id = self.node_id.clone();
 
-        future::result(self.store.active_assignments(&self.node_id))
+        let assignments = self
+            .store
+            .active_assignments(&self.node_id)
+            .and_then(|active| {
+                self.store
+                    .standby_assignments(&self.node_id)
+                    .map(|standby| (active, standby))
+            });
+        let store = self.store.clone();
+        future::result(assignments)
             .map_err(|e| anyhow!("Error querying subgraph assignments: {}", e))
-            .and_then(move |deployments| {
-                // This operation should finish only after all subgraphs are
-                // started. We wait for the spawned tasks to complete by giving
-                // each a `sender` and waiting for all of them to be dropped, so
-                // the receiver terminates without receiving anything.
+            .and_then(move |(deployments, standby_deployments)| {
                 let deployments = HashSet::<DeploymentLocator>::from_iter(deployments);
                 let deployments_len = deployments.len();
-                let (sender, receiver) = futures01::sync::mpsc::channel::<()>(1);
-                for id in deployments {
-                    let sender = sender.clone();
-                    let logger = logger.clone();
-
-                    graph::spawn(
-                        start_subgraph(id, provider.clone(), logger).map(move |()| drop(sender)),
-                    );
-                }
-                drop(sender);
-                receiver.collect().then(move |_| {
+
+                let deployments: Vec<_> = deployments.into_iter().collect();
+                let blocks_behind = store.blocks_behind(&deployments).unwrap_or_else(|e| {
+                    warn!(logger, "Failed to get how far behind subgraphs are";
+                                  "error" => e.to_string());
+                    HashMap::new()
+                });
+                let starts =
+                    start_order(deployments, standby_deployments, &blocks_behind, &node_id);
+
+                // This operation should finish only after all subgraphs are
+                // started
+                let limit = match ENV_VARS.subgraph_max_concurrent_starts {
+                    0 => None,
+                    limit => Some(limit),
+                };
+                async move {
+                    futures03::stream::iter(starts)
+                        .for_each_concurrent(limit, |(loc, standby)| {
+                            let provider = provider.clone();
+                            let logger = logger.clone();
+                            async move {
+                                // Run each start on its own task so that starts
+                                // that block do not hold up the others
+                                let start = start_subgraph(loc, standby, provider, logger);
+                                let _ = graph::spawn(start).await;
+                            }
+                        })
+                        .await;
                     info!(logger, "Started all assigned subgraphs";
                                   "count" => deployments_len, "node_id" => &node_id);
-                    future::ok(())
-                })
+                    Ok::<_, Error>(())
+                }
+                .boxed()
+                .compat()
             })
     }
+
+    /// The chain store for `network`, whatever kind of chain it is
+    fn chain_store(&self, network: &str) -> Option<Arc<dyn ChainStore>> {
+        fn chain_store<C: Blockchain>(
+            chains: &BlockchainMap,
+            network: &str,
+        ) -> Option<Arc<dyn ChainStore>> {
+            chains
+                .get::<C>(network.to_string())
+                .ok()
+                .map(|chain| chain.chain_store())
+        }
+
+        let chains = self.chains.as_ref();
+        chain_store::<graph_chain_ethereum::Chain>(chains, network)
+            .or_else(|| chain_store::<graph_chain_near::Chain>(chains, network))
+            .or_else(|| chain_store::<graph_chain_cosmos::Chain>(chains, network))
+            .or_else(|| chain_store::<graph_chain_arweave::Chain>(chains, network))
+            .or_else(|| chain_store::<graph_chain_substreams::Chain>(chains, network))
+            .or_else(|| chain_store::<graph_chain_starknet::Chain>(chains, network))
+    }
 }
 
 #[async_trait]
@@ -284,7 +356,9 @@ where
         start_block_override: Option<BlockPtr>,
         graft_block_override: Option<BlockPtr>,
         history_blocks: Option<i32>,
-    ) -> Result<DeploymentLocator, SubgraphRegistrarError> {
+        metadata: DeploymentMetadata,
+        placement: DeploymentPlacement,
+    ) -> Result<CreateSubgraphVersionResult, SubgraphRegistrarError> {
         // We don't have a location for the subgraph yet; that will be
         // assigned when we deploy for real. For logging purposes, make up a
         // fake locator
@@ -292,9 +366,12 @@ where
             .logger_factory
             .subgraph_logger(&DeploymentLocator::new(DeploymentId(0), hash.clone()));
 
+        // Check every file we fetch against its CID and remember the files
+        // so that we can record them with the deployment
+        let resolver = Arc::new(VerifyingLinkResolver::new(self.resolver.cheap_clone()));
+
         let raw: serde_yaml::Mapping = {
-            let file_bytes = self
-                .resolver
+            let file_bytes = resolver
                 .cat(&logger, &hash.to_ipfs_link())
                 .await
                 .map_err(|e| {
@@ -303,7 +380,7 @@ where
                     )
                 })?;
 
-            serde_yaml::from_slice(&file_bytes)
+            parse_raw_manifest(&file_bytes)
                 .map_err(|e| SubgraphRegistrarError::ResolveError(e.into()))?
         };
 
@@ -315,7 +392,7 @@ where
         let history_blocks =
             history_blocks.or(self.settings.for_name(&name).map(|c| c.history_blocks));
 
-        let deployment_locator = match kind {
+        let result = match kind {
             BlockchainKind::Arweave => {
                 create_subgraph_version::<graph_chain_arweave::Chain, _>(
                     &logger,
@@ -329,8 +406,10 @@ where
                     node_id,
                     debug_fork,
                     self.version_switching_mode,
-                    &self.resolver,
+                    &resolver,
                     history_blocks,
+                    metadata,
+                    placement,
                 )
                 .await?
             }
@@ -347,8 +426,10 @@ where
                     node_id,
                     debug_fork,
                     self.version_switching_mode,
-                    &self.resolver,
+                    &resolver,
                     history_blocks,
+                    metadata,
+                    placement,
                 )
                 .await?
             }
@@ -365,8 +446,10 @@ where
                     node_id,
                     debug_fork,
                     self.version_switching_mode,
-                    &self.resolver,
+                    &resolver,
                     history_blocks,
+                    metadata,
+                    placement,
                 )
                 .await?
             }
@@ -383,8 +466,10 @@ where
                     node_id,
                     debug_fork,
                     self.version_switching_mode,
-                    &self.resolver,
+                    &resolver,
                     history_blocks,
+                    metadata,
+                    placement,
                 )
                 .await?
             }
@@ -401,8 +486,10 @@ where
                     node_id,
                     debug_fork,
                     self.version_switching_mode,
-                    &self.resolver,
+                    &resolver,
                     history_blocks,
+                    metadata,
+                    placement,
                 )
                 .await?
             }
@@ -419,8 +506,10 @@ where
                     node_id,
                     debug_fork,
                     self.version_switching_mode,
-                    &self.resolver,
+                    &resolver,
                     history_blocks,
+                    metadata,
+                    placement,
                 )
                 .await?
             }
@@ -433,7 +522,7 @@ where
             "subgraph_hash" => hash.to_string(),
         );
 
-        Ok(deployment_locator)
+        Ok(result)
     }
 
     async fn remove_subgraph(&self, name: SubgraphName) -> Result<(), SubgraphRegistrarError> {
@@ -461,6 +550,119 @@ where
 
         Ok(())
     }
+
+    async fn pause_subgraph(&self, hash: &DeploymentHash) -> Result<(), SubgraphRegistrarError> {
+        let locator = self.store.active_locator(hash)?;
+        let deployment =
+            locator.ok_or_else(|| SubgraphRegistrarError::DeploymentNotFound(hash.to_string()))?;
+
+        self.store.pause_subgraph(&deployment)?;
+
+        Ok(())
+    }
+
+    async fn resume_subgraph(&self, hash: &DeploymentHash) -> Result<(), SubgraphRegistrarError> {
+        let locator = self.store.active_locator(hash)?;
+        let deployment =
+            locator.ok_or_else(|| SubgraphRegistrarError::DeploymentNotFound(hash.to_string()))?;
+
+        self.store.resume_subgraph(&deployment)?;
+
+        Ok(())
+    }
+
+    async fn prune_subgraph(
+        &self,
+        hash: &DeploymentHash,
+        history_blocks: Option<BlockNumber>,
+    ) -> Result<(), SubgraphRegistrarError> {
+        let locator = self.store.active_locator(hash)?;
+        let deployment =
+            locator.ok_or_else(|| SubgraphRegistrarError::DeploymentNotFound(hash.to_string()))?;
+
+        self.store.prune_subgraph(&deployment, history_blocks)?;
+
+        Ok(())
+    }
+
+    async fn rewind_subgraph(
+        &self,
+        hash: &DeploymentHash,
+        block_ptr: BlockPtr,
+    ) -> Result<(), SubgraphRegistrarError> {
+        /// How long we wait for the node that indexes the deployment to
+        /// stop it after we paused it
+        const STOP_TIMEOUT: Duration = Duration::from_secs(60);
+        const STOP_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+        let locator = self.store.active_locator(hash)?;
+        let deployment =
+            locator.ok_or_else(|| SubgraphRegistrarError::DeploymentNotFound(hash.to_string()))?;
+
+        let network = self.store.network_name(&deployment)?;
+        let chain_store = self.chain_store(&network).ok_or_else(|| {
+            SubgraphRegistrarError::NetworkNotSupported(anyhow!(
+                "network {} is not supported by this node",
+                network
+            ))
+        })?;
+        if !chain_store.check_block_ptr(&block_ptr).await? {
+            return Err(SubgraphRegistrarError::Unknown(anyhow!(
+                "the chain {} does not have a block with hash {}",
+                network,
+                block_ptr.hash
+            )));
+        }
+
+        let paused = matches!(self.store.assignment_status(&deployment)?, Some((_, true)));
+        if !paused {
+            self.store.pause_subgraph(&deployment)?;
+        }
+
+        // The node that indexes the deployment releases its lock on it
+        // once it has seen that the deployment was paused
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        let res = loop {
+            match self.store.rewind_subgraph(&deployment, block_ptr.clone()) {
+                Err(StoreError::DeploymentLocked(_)) if Instant::now() < deadline => {
+                    tokio::time::sleep(STOP_CHECK_INTERVAL).await
+                }
+                res => break res,
+            }
+        };
+
+        // Restart indexing from the block we rewound to, unless the
+        // deployment was paused before
+        if !paused {
+            self.store.resume_subgraph(&deployment)?;
+        }
+        res?;
+
+        Ok(())
+    }
+}
+
+/// The order in which to start `deployments` and the `standby` instances
+/// that `node_id` keeps: the deployments that are closest to the chain
+/// head come first, then the ones for which that is not known, and
+/// standby instances after all deployments
+fn start_order(
+    mut deployments: Vec<DeploymentLocator>,
+    standby: Vec<DeploymentLocator>,
+    blocks_behind: &HashMap<DeploymentId, BlockNumber>,
+    node_id: &NodeId,
+) -> Vec<(DeploymentLocator, Option<NodeId>)> {
+    deployments.sort_by_key(|loc| {
+        blocks_behind
+            .get(&loc.id)
+            .copied()
+            .unwrap_or(BlockNumber::MAX)
+    });
+    deployments
+        .into_iter()
+        .map(|loc| (loc, None))
+        .chain(standby.into_iter().map(|loc| (loc, Some(node_id.clone()))))
+        .collect()
 }
 
 async fn handle_assignment_event(
@@ -477,7 +679,14 @@ async fn handle_assignment_event(
             deployment,
             node_id: _,
         } => {
-            start_subgraph(deployment, provider.clone(), logger).await;
+            start_subgraph(deployment, None, provider.clone(), logger).await;
+            Ok(())
+        }
+        AssignmentEvent::Standby {
+            deployment,
+            node_id,
+        } => {
+            start_subgraph(deployment, Some(node_id), provider.clone(), logger).await;
             Ok(())
         }
         AssignmentEvent::Remove {
@@ -490,18 +699,23 @@ async fn handle_assignment_event(
     }
 }
 
+/// Start the deployment, or a standby instance of it if `standby` is set
 async fn start_subgraph(
     deployment: DeploymentLocator,
+    standby: Option<NodeId>,
     provider: Arc<impl SubgraphAssignmentProviderTrait>,
     logger: Logger,
 ) {
     let logger = logger
         .new(o!("subgraph_id" => deployment.hash.to_string(), "sgd" => deployment.id.to_string()));
 
-    trace!(logger, "Start subgraph");
+    trace!(logger, "Start subgraph"; "standby" => standby.is_some());
 
     let start_time = Instant::now();
-    let result = provider.start(deployment.clone(), None).await;
+    let result = match standby {
+        None => provider.start(deployment.clone(), None).await,
+        Some(node) => provider.start_standby(deployment.clone(), node).await,
+    };
 
     debug!(
         logger,
@@ -585,14 +799,17 @@ async fn create_subgraph_version<C: Blockchain, S: SubgraphStore>(
     node_id: NodeId,
     debug_fork: Option<DeploymentHash>,
     version_switching_mode: SubgraphVersionSwitchingMode,
-    resolver: &Arc<dyn LinkResolver>,
+    resolver: &Arc<VerifyingLinkResolver>,
     history_blocks: Option<i32>,
-) -> Result<DeploymentLocator, SubgraphRegistrarError> {
+    metadata: DeploymentMetadata,
+    placement: DeploymentPlacement,
+) -> Result<CreateSubgraphVersionResult, SubgraphRegistrarError> {
     let raw_string = serde_yaml::to_string(&raw).unwrap();
+    let link_resolver: Arc<dyn LinkResolver> = resolver.cheap_clone();
     let unvalidated = UnvalidatedSubgraphManifest::<C>::resolve(
         deployment.clone(),
         raw,
-        resolver,
+        &link_resolver,
         logger,
         ENV_VARS.max_spec_version.clone(),
     )
@@ -622,6 +839,28 @@ async fn create_subgraph_version<C: Blockchain, S: SubgraphStore>(
         .map_err(SubgraphRegistrarError::NetworkNotSupported)?
         .cheap_clone();
 
+    let mut warnings = manifest.lint();
+    let onchain_data_sources: Vec<_> = manifest
+        .data_sources
+        .iter()
+        .filter_map(|ds| ds.as_onchain())
+        .cloned()
+        .collect();
+    let required_capabilities = C::NodeCapabilities::from_data_sources(&onchain_data_sources);
+    if !chain.has_provider_with(&required_capabilities) {
+        warnings.push(SubgraphManifestWarning::new(
+            None,
+            format!(
+                "no provider for network `{}` has the capabilities `{}` that the subgraph \
+                 requires, it will not be able to start indexing",
+                network_name, required_capabilities
+            ),
+        ));
+    }
+    for warning in &warnings {
+        warn!(logger, "Subgraph manifest warning"; "warning" => warning.to_string());
+    }
+
     let logger = logger.clone();
     let store = store.clone();
     let deployment_store = store.clone();
@@ -686,12 +925,15 @@ async fn create_subgraph_version<C: Blockchain, S: SubgraphStore>(
     let mut deployment = DeploymentCreate::new(raw_string, &manifest, start_block)
         .graft(base_block)
         .debug(debug_fork)
+        .metadata(metadata)
+        .placement(placement)
+        .files(resolver.files())
         .entities_with_causality_region(needs_causality_region);
     if let Some(history_blocks) = history_blocks {
         deployment = deployment.with_history_blocks(history_blocks);
     }
 
-    deployment_store
+    let deployment = deployment_store
         .create_subgraph_deployment(
             name,
             &manifest.schema,
@@ -700,5 +942,53 @@ async fn create_subgraph_version<C: Blockchain, S: SubgraphStore>(
             network_name,
             version_switching_mode,
         )
-        .map_err(SubgraphRegistrarError::SubgraphDeploymentError)
+        .map_err(SubgraphRegistrarError::SubgraphDeploymentError)?;
+
+    Ok(CreateSubgraphVersionResult {
+        deployment,
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use graph::components::store::{DeploymentId, DeploymentLocator};
+    use graph::prelude::{DeploymentHash, NodeId};
+
+    use super::start_order;
+
+    fn loc(id: i32) -> DeploymentLocator {
+        let hash = DeploymentHash::new(format!("Qm{}", id)).unwrap();
+        DeploymentLocator::new(DeploymentId::new(id), hash)
+    }
+
+    #[test]
+    fn deployments_closest_to_head_start_first() {
+        let node_id = NodeId::new("standby").unwrap();
+        let blocks_behind =
+            HashMap::from_iter([(DeploymentId::new(1), 500), (DeploymentId::new(2), 3)]);
+
+        let order = start_order(
+            vec![loc(1), loc(2), loc(3)],
+            vec![loc(4)],
+            &blocks_behind,
+            &node_id,
+        )
+        .into_iter()
+        .map(|(loc, standby)| (loc.id, standby))
+        .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (DeploymentId::new(2), None),
+                (DeploymentId::new(1), None),
+                // Deployments for which we do not know how far behind they
+                // are come after all others, standby instances last
+                (DeploymentId::new(3), None),
+                (DeploymentId::new(4), Some(node_id)),
+            ],
+            order
+        );
+    }
 }
@@ -1,6 +1,7 @@
-use crate::subgraph::context::IndexingContext;
-use crate::subgraph::error::BlockProcessingError;
+use crate::subgraph::context::{IndexingContext, ParallelOutcome};
+use crate::subgraph::error::{BlockProcessingError, DataSourceLimitExceeded};
 use crate::subgraph::inputs::IndexingInputs;
+use crate::subgraph::loader::load_dynamic_data_sources;
 use crate::subgraph::state::IndexingState;
 use crate::subgraph::stream::new_block_stream;
 use atomic_refcell::AtomicRefCell;
@@ -17,7 +18,8 @@ use graph::data::subgraph::{
     SubgraphFeature,
 };
 use graph::data_source::{
-    offchain, CausalityRegion, DataSource, DataSourceCreationError, DataSourceTemplate, TriggerData,
+    causality_region::CausalityRegionSeq, offchain, CausalityRegion, DataSource,
+    DataSourceCreationError, DataSourceTemplate, TriggerData,
 };
 use graph::env::EnvVars;
 use graph::prelude::*;
@@ -28,13 +30,25 @@ use std::time::{Duration, Instant};
 
 const MINUTE: Duration = Duration::from_secs(60);
 
-const SKIP_PTR_UPDATES_THRESHOLD: Duration = Duration::from_secs(60 * 5);
 const HANDLE_REVERT_SECTION_NAME: &str = "handle_revert";
 const PROCESS_BLOCK_SECTION_NAME: &str = "process_block";
 const PROCESS_WASM_BLOCK_SECTION_NAME: &str = "process_wasm_block";
 const PROCESS_TRIGGERS_SECTION_NAME: &str = "process_triggers";
 const HANDLE_CREATED_DS_SECTION_NAME: &str = "handle_new_data_sources";
 
+/// What running the handlers of a deployment for one block produced,
+/// without any of it having been written to the store
+#[derive(Debug, Default)]
+pub struct BlockReplay {
+    /// The block that was replayed, `None` if the block has no triggers
+    /// for the deployment
+    pub block_ptr: Option<BlockPtr>,
+    /// The entity operations the handlers performed
+    pub modifications: Vec<EntityModification>,
+    /// The deterministic errors the handlers ran into
+    pub errors: Vec<SubgraphError>,
+}
+
 pub struct SubgraphRunner<C, T>
 where
     C: Blockchain,
@@ -88,14 +102,15 @@ where
     /// be removed. The same thing also applies to the block cache.
     /// This function must be called before continuing to process in order to avoid
     /// duplicated host insertion and POI issues with dirty entity changes.
-    fn revert_state_to(&mut self, block_number: BlockNumber) -> Result<(), Error> {
+    /// Returns the number of onchain data sources that were created after
+    /// `block_number` and have been removed
+    fn revert_state_to(&mut self, block_number: BlockNumber) -> Result<usize, Error> {
         self.state.entity_lfu_cache = LfuCache::new();
 
         // 1. Revert all hosts(created by DDS) at a block higher than `block_number`.
         // 2. Unmark any offchain data sources that were marked done on the blocks being removed.
         // When no offchain datasources are present, 2. should be a noop.
-        self.ctx.revert_data_sources(block_number + 1)?;
-        Ok(())
+        self.ctx.revert_data_sources(block_number + 1)
     }
 
     #[cfg(debug_assertions)]
@@ -108,6 +123,14 @@ where
         self.run_inner(break_on_restart).await
     }
 
+    /// Put the triggers of a block in the order in which this deployment
+    /// processes them
+    fn order_triggers(&self, triggers: &mut [C::TriggerData]) {
+        if self.inputs.total_trigger_order {
+            triggers.sort_by(graph::blockchain::TriggerData::cmp_position);
+        }
+    }
+
     fn build_filter(&self) -> C::TriggerFilter {
         let current_ptr = self.inputs.store.block_ptr();
         let static_filters =
@@ -163,7 +186,147 @@ where
         self.run_inner(false).await.map(|_| ())
     }
 
+    /// Run the handlers for the triggers in block `number` and return what
+    /// they did. The runner must use a store that reads the state of the
+    /// deployment as of the block before `number`; nothing is written to
+    /// the store, and offchain triggers are not processed. What the
+    /// mappings log goes to the logger of the runner as usual
+    pub async fn replay_block(mut self, number: BlockNumber) -> Result<BlockReplay, Error> {
+        let filter = self.build_filter();
+        let block = self
+            .inputs
+            .triggers_adapter
+            .scan_triggers(number, number, &filter)
+            .await?
+            .into_iter()
+            .find(|block| block.block.number() == number);
+        let block = match block {
+            Some(block) => block,
+            None => {
+                info!(self.logger, "Block has no triggers for the deployment";
+                    "block_number" => number);
+                return Ok(BlockReplay::default());
+            }
+        };
+
+        let mut triggers = block.trigger_data;
+        self.order_triggers(&mut triggers);
+        let block = Arc::new(block.block);
+        let block_ptr = block.ptr();
+
+        let logger = self.logger.new(o!(
+                "block_number" => format!("{:?}", block_ptr.number),
+                "block_hash" => format!("{}", block_ptr.hash)
+        ));
+        info!(logger, "Replaying block"; "triggers" => triggers.len());
+
+        let causality_region = PoICausalityRegion::from_network(&self.inputs.network);
+        let block_state = self
+            .process_triggers(
+                &None,
+                &block,
+                triggers.into_iter().map(TriggerData::Onchain),
+                &causality_region,
+            )
+            .await
+            .map_err(|e| match e {
+                MappingError::PossibleReorg(e) | MappingError::Unknown(e) => e,
+            })?;
+
+        let block_state = match self
+            .process_created_data_sources(
+                &logger,
+                &block,
+                &block_ptr,
+                &FirehoseCursor::None,
+                block_state,
+                &None,
+                &causality_region,
+            )
+            .await
+        {
+            Ok(block_state) => block_state,
+            Err(BlockProcessingError::Deterministic(e)) => {
+                return Ok(BlockReplay {
+                    block_ptr: Some(block_ptr),
+                    modifications: vec![],
+                    errors: vec![e],
+                })
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let modifications = block_state
+            .entity_cache
+            .as_modifications(block_ptr.number)?
+            .modifications;
+
+        Ok(BlockReplay {
+            block_ptr: Some(block_ptr),
+            modifications,
+            errors: block_state.deterministic_errors,
+        })
+    }
+
+    /// A standby instance waits here until it can take over writing to the
+    /// deployment. The node that wrote to the deployment until then has
+    /// most likely made progress since the standby instance was built, and
+    /// everything that the instance loaded from the store is reloaded.
+    ///
+    /// Returns `false` if the subgraph was stopped while waiting
+    async fn take_over(&mut self) -> Result<bool, Error> {
+        let canceler = CancelGuard::new();
+        let take_over = self
+            .inputs
+            .store
+            .cheap_clone()
+            .take_over()
+            .cancelable(&canceler, || Err(StoreError::Canceled));
+        // Stopping the subgraph drops the guard, which cancels the wait
+        self.ctx
+            .instances
+            .insert(self.inputs.deployment.id, canceler);
+
+        let store = match take_over.await {
+            Ok(Some(store)) => store,
+            Ok(None) => return Ok(true),
+            Err(StoreError::Canceled) => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+
+        info!(self.logger, "Taking over the deployment");
+        store.start_subgraph_deployment(&self.logger).await?;
+
+        let dynamic_data_sources = load_dynamic_data_sources(
+            store.cheap_clone(),
+            self.logger.cheap_clone(),
+            self.ctx.templates(),
+        )
+        .await
+        .context("Failed to load dynamic data sources")?;
+        // Forget all dynamic data sources and add the ones that are in the
+        // store now; the ones we already knew about are not built again
+        self.revert_state_to(0)?;
+        for data_source in dynamic_data_sources {
+            self.ctx
+                .add_dynamic_data_source(&self.logger, data_source)?;
+        }
+        self.ctx
+            .set_causality_region_seq(CausalityRegionSeq::from_current(
+                store.causality_region_curr_val().await?,
+            ));
+
+        self.inputs = Arc::new(self.inputs.with_store(store));
+        self.state.synced = self.inputs.store.is_deployment_synced().await?;
+        Ok(true)
+    }
+
     async fn run_inner(mut self, break_on_restart: bool) -> Result<Self, Error> {
+        if !self.take_over().await? {
+            info!(self.logger, "Stopping standby instance");
+            return Ok(self);
+        }
+
         // If a subgraph failed for deterministic reasons, before start indexing, we first
         // revert the deployment head. It should lead to the same result since the error was
         // deterministic.
@@ -268,7 +431,8 @@ where
         block: BlockWithTriggers<C>,
         firehose_cursor: FirehoseCursor,
     ) -> Result<Action, BlockProcessingError> {
-        let triggers = block.trigger_data;
+        let mut triggers = block.trigger_data;
+        self.order_triggers(&mut triggers);
         let block = Arc::new(block.block);
         let block_ptr = block.ptr();
 
@@ -353,103 +517,17 @@ where
                 .stopwatch
                 .start_section(HANDLE_CREATED_DS_SECTION_NAME);
 
-            // This loop will:
-            // 1. Instantiate created data sources.
-            // 2. Process those data sources for the current block.
-            // Until no data sources are created or MAX_DATA_SOURCES is hit.
-
-            // Note that this algorithm processes data sources spawned on the same block _breadth
-            // first_ on the tree implied by the parent-child relationship between data sources. Only a
-            // very contrived subgraph would be able to observe this.
-            while block_state.has_created_data_sources() {
-                // Instantiate dynamic data sources, removing them from the block state.
-                let (data_sources, runtime_hosts) =
-                    self.create_dynamic_data_sources(block_state.drain_created_data_sources())?;
-
-                let filter = C::TriggerFilter::from_data_sources(
-                    data_sources.iter().filter_map(DataSource::as_onchain),
-                );
-
-                let block: Arc<C::Block> = if self.inputs.chain.is_refetch_block_required() {
-                    let cur = firehose_cursor.clone();
-                    let log = logger.cheap_clone();
-                    let chain = self.inputs.chain.cheap_clone();
-                    Arc::new(
-                        retry(
-                            "refetch firehose block after dynamic datasource was added",
-                            &logger,
-                        )
-                        .limit(5)
-                        .no_timeout()
-                        .run(move || {
-                            let cur = cur.clone();
-                            let log = log.cheap_clone();
-                            let chain = chain.cheap_clone();
-                            async move { chain.refetch_firehose_block(&log, cur).await }
-                        })
-                        .await?,
-                    )
-                } else {
-                    block.cheap_clone()
-                };
-
-                // Reprocess the triggers from this block that match the new data sources
-                let block_with_triggers = self
-                    .inputs
-                    .triggers_adapter
-                    .triggers_in_block(&logger, block.as_ref().clone(), &filter)
-                    .await?;
-
-                let triggers = block_with_triggers.trigger_data;
-
-                if triggers.len() == 1 {
-                    info!(
-                        &logger,
-                        "1 trigger found in this block for the new data sources"
-                    );
-                } else if triggers.len() > 1 {
-                    info!(
-                        &logger,
-                        "{} triggers found in this block for the new data sources",
-                        triggers.len()
-                    );
-                }
-
-                // Add entity operations for the new data sources to the block state
-                // and add runtimes for the data sources to the subgraph instance.
-                self.persist_dynamic_data_sources(&mut block_state, data_sources);
-
-                // Process the triggers in each host in the same order the
-                // corresponding data sources have been created.
-                for trigger in triggers {
-                    block_state = self
-                        .ctx
-                        .process_trigger_in_hosts(
-                            &logger,
-                            Box::new(runtime_hosts.iter().map(|host| host.as_ref())),
-                            &block,
-                            &TriggerData::Onchain(trigger),
-                            block_state,
-                            &proof_of_indexing,
-                            &causality_region,
-                            &self.inputs.debug_fork,
-                            &self.metrics.subgraph,
-                            self.inputs.instrument,
-                        )
-                        .await
-                        .map_err(|e| {
-                            // This treats a `PossibleReorg` as an ordinary error which will fail the subgraph.
-                            // This can cause an unnecessary subgraph failure, to fix it we need to figure out a
-                            // way to revert the effect of `create_dynamic_data_sources` so we may return a
-                            // clean context as in b21fa73b-6453-4340-99fb-1a78ec62efb1.
-                            match e {
-                                MappingError::PossibleReorg(e) | MappingError::Unknown(e) => {
-                                    BlockProcessingError::Unknown(e)
-                                }
-                            }
-                        })?;
-                }
-            }
+            block_state = self
+                .process_created_data_sources(
+                    &logger,
+                    &block,
+                    &block_ptr,
+                    &firehose_cursor,
+                    block_state,
+                    &proof_of_indexing,
+                    &causality_region,
+                )
+                .await?;
         }
 
         let has_errors = block_state.has_errors();
@@ -603,6 +681,159 @@ where
         }
     }
 
+    /// Instantiate the data sources that processing `block` created and
+    /// process the triggers in `block` for them, repeating until no more
+    /// data sources get created
+    async fn process_created_data_sources(
+        &mut self,
+        logger: &Logger,
+        block: &Arc<C::Block>,
+        block_ptr: &BlockPtr,
+        firehose_cursor: &FirehoseCursor,
+        mut block_state: BlockState<C>,
+        proof_of_indexing: &SharedProofOfIndexing,
+        causality_region: &str,
+    ) -> Result<BlockState<C>, BlockProcessingError> {
+        // This loop will:
+        // 1. Instantiate created data sources.
+        // 2. Process those data sources for the current block.
+        // Until no data sources are created or MAX_DATA_SOURCES is hit.
+
+        // Note that this algorithm processes data sources spawned on the same block _breadth
+        // first_ on the tree implied by the parent-child relationship between data sources. Only a
+        // very contrived subgraph would be able to observe this.
+        while block_state.has_created_data_sources() {
+            // Instantiate dynamic data sources, removing them from the block state.
+            let (data_sources, runtime_hosts) =
+                match self.create_dynamic_data_sources(block_state.drain_created_data_sources()) {
+                    Ok(created) => created,
+                    // Every node hits the limit at the same data source, and
+                    // failing the block means that nothing it did is committed
+                    Err(e) if e.is::<DataSourceLimitExceeded>() => {
+                        return Err(BlockProcessingError::Deterministic(SubgraphError {
+                            subgraph_id: self.inputs.deployment.hash.clone(),
+                            message: e.to_string(),
+                            block_ptr: Some(block_ptr.clone()),
+                            handler: None,
+                            data_source: None,
+                            transaction_hash: None,
+                            deterministic: true,
+                        }));
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+
+            let filter = C::TriggerFilter::from_data_sources(
+                data_sources.iter().filter_map(DataSource::as_onchain),
+            );
+
+            let block: Arc<C::Block> = if self.inputs.chain.is_refetch_block_required() {
+                let cur = firehose_cursor.clone();
+                let log = logger.cheap_clone();
+                let chain = self.inputs.chain.cheap_clone();
+                Arc::new(
+                    retry(
+                        "refetch firehose block after dynamic datasource was added",
+                        logger,
+                    )
+                    .limit(5)
+                    .no_timeout()
+                    .run(move || {
+                        let cur = cur.clone();
+                        let log = log.cheap_clone();
+                        let chain = chain.cheap_clone();
+                        async move { chain.refetch_firehose_block(&log, cur).await }
+                    })
+                    .await?,
+                )
+            } else {
+                block.cheap_clone()
+            };
+
+            // Reprocess the triggers from this block that match the new data sources
+            let block_with_triggers = self
+                .inputs
+                .triggers_adapter
+                .triggers_in_block(logger, block.as_ref().clone(), &filter)
+                .await?;
+
+            let mut triggers = block_with_triggers.trigger_data;
+            self.order_triggers(&mut triggers);
+
+            if triggers.len() == 1 {
+                info!(
+                    logger,
+                    "1 trigger found in this block for the new data sources"
+                );
+            } else if triggers.len() > 1 {
+                info!(
+                    logger,
+                    "{} triggers found in this block for the new data sources",
+                    triggers.len()
+                );
+            }
+
+            // Add entity operations for the new data sources to the block state
+            // and add runtimes for the data sources to the subgraph instance.
+            self.persist_dynamic_data_sources(&mut block_state, data_sources);
+
+            // Process the triggers in each host in the same order the
+            // corresponding data sources have been created.
+            let triggers: Vec<_> = triggers.into_iter().map(TriggerData::Onchain).collect();
+            for trigger in &triggers {
+                block_state = self
+                    .ctx
+                    .process_trigger_in_hosts(
+                        logger,
+                        Box::new(runtime_hosts.iter().map(|host| host.as_ref())),
+                        &block,
+                        trigger,
+                        block_state,
+                        proof_of_indexing,
+                        causality_region,
+                        &self.inputs.debug_fork,
+                        &self.metrics.subgraph,
+                        self.inputs.instrument,
+                    )
+                    .await
+                    .map_err(|e| {
+                        // This treats a `PossibleReorg` as an ordinary error which will fail the subgraph.
+                        // This can cause an unnecessary subgraph failure, to fix it we need to figure out a
+                        // way to revert the effect of `create_dynamic_data_sources` so we may return a
+                        // clean context as in b21fa73b-6453-4340-99fb-1a78ec62efb1.
+                        match e {
+                            MappingError::PossibleReorg(e) | MappingError::Unknown(e) => {
+                                BlockProcessingError::Unknown(e)
+                            }
+                        }
+                    })?;
+            }
+
+            block_state = self
+                .ctx
+                .process_batch_handlers_in_hosts(
+                    logger,
+                    &runtime_hosts,
+                    &block,
+                    &triggers,
+                    block_state,
+                    proof_of_indexing,
+                    causality_region,
+                    &self.inputs.debug_fork,
+                    &self.metrics.subgraph,
+                    self.inputs.instrument,
+                )
+                .await
+                .map_err(|e| match e {
+                    MappingError::PossibleReorg(e) | MappingError::Unknown(e) => {
+                        BlockProcessingError::Unknown(e)
+                    }
+                })?;
+        }
+
+        Ok(block_state)
+    }
+
     async fn process_triggers(
         &mut self,
         proof_of_indexing: &SharedProofOfIndexing,
@@ -610,18 +841,51 @@ where
         triggers: impl Iterator<Item = TriggerData<C>>,
         causality_region: &str,
     ) -> Result<BlockState<C>, MappingError> {
-        let mut block_state = BlockState::new(
-            self.inputs.store.clone(),
-            std::mem::take(&mut self.state.entity_lfu_cache),
-        );
+        let triggers: Vec<_> = triggers.collect();
 
-        for trigger in triggers {
+        let mut entity_lfu_cache = std::mem::take(&mut self.state.entity_lfu_cache);
+        if ENV_VARS.subgraph_trigger_parallelism > 1 {
+            match self
+                .ctx
+                .process_triggers_in_parallel(
+                    &self.logger,
+                    block,
+                    &triggers,
+                    &self.inputs.store,
+                    entity_lfu_cache,
+                    proof_of_indexing,
+                    causality_region,
+                    &self.inputs.debug_fork,
+                    &self.metrics.subgraph,
+                    self.inputs.instrument,
+                    ENV_VARS.subgraph_trigger_parallelism,
+                )
+                .await?
+            {
+                ParallelOutcome::Processed(block_state) => {
+                    return self
+                        .process_batch_handlers(
+                            proof_of_indexing,
+                            block,
+                            &triggers,
+                            block_state,
+                            causality_region,
+                        )
+                        .await
+                }
+                ParallelOutcome::Sequential(cache) => entity_lfu_cache = cache,
+            }
+        }
+
+        let mut block_state = BlockState::new(self.inputs.store.clone(), entity_lfu_cache);
+
+        for trigger in &triggers {
             block_state = self
                 .ctx
                 .process_trigger(
                     &self.logger,
                     block,
-                    &trigger,
+                    trigger,
                     block_state,
                     proof_of_indexing,
                     causality_region,
@@ -638,7 +902,40 @@ where
                     e.context("failed to process trigger".to_string())
                 })?;
         }
-        Ok(block_state)
+
+        self.process_batch_handlers(
+            proof_of_indexing,
+            block,
+            &triggers,
+            block_state,
+            causality_region,
+        )
+        .await
+    }
+
+    /// Batch handlers run after all other handlers of the block
+    async fn process_batch_handlers(
+        &self,
+        proof_of_indexing: &SharedProofOfIndexing,
+        block: &Arc<C::Block>,
+        triggers: &[TriggerData<C>],
+        block_state: BlockState<C>,
+        causality_region: &str,
+    ) -> Result<BlockState<C>, MappingError> {
+        self.ctx
+            .process_batch_handlers(
+                &self.logger,
+                block,
+                triggers,
+                block_state,
+                proof_of_indexing,
+                causality_region,
+                &self.inputs.debug_fork,
+                &self.metrics.subgraph,
+                self.inputs.instrument,
+            )
+            .await
+            .map_err(|e| e.context("failed to process batch handlers".to_string()))
     }
     async fn process_wasm_block(
         &mut self,
@@ -711,10 +1008,10 @@ where
                     runtime_hosts.push(host);
                 }
                 None => {
-                    warn!(
+                    info!(
                         self.logger,
-                        "no runtime host created, there is already a runtime host instantiated for \
-                        this data source";
+                        "Skipping duplicate data source, there is already a runtime host for \
+                        a data source from the same template with the same address and context";
                         "name" => &data_source.name(),
                         "address" => &data_source.address()
                         .map(hex::encode)
@@ -807,9 +1104,11 @@ where
 
                 let error = SubgraphError {
                     subgraph_id: self.inputs.deployment.hash.clone(),
-                    message,
+                    message: SubgraphError::truncate_message(message),
                     block_ptr: Some(block_ptr),
                     handler: None,
+                    data_source: None,
+                    transaction_hash: None,
                     deterministic,
                 };
 
@@ -1286,7 +1585,7 @@ where
         }
 
         if block.trigger_count() == 0
-            && self.state.skip_ptr_updates_timer.elapsed() <= SKIP_PTR_UPDATES_THRESHOLD
+            && self.state.skip_ptr_updates_timer.elapsed() <= ENV_VARS.subgraph_ptr_update_interval
             && !self.state.synced
             && !close_to_chain_head(
                 &block_ptr,
@@ -1346,9 +1645,16 @@ where
             .deployment_head
             .set(subgraph_ptr.number as f64);
 
-        self.revert_state_to(revert_to_ptr.number)?;
+        let reverted_data_sources = self.revert_state_to(revert_to_ptr.number)?;
+        if reverted_data_sources > 0 {
+            info!(&self.logger, "Removed data sources created in reverted blocks";
+                  "count" => reverted_data_sources);
+        }
 
-        let needs_restart: bool = self.needs_restart(revert_to_ptr, subgraph_ptr);
+        // The filter of the block stream still includes the data sources that were removed
+        // and needs to be rebuilt, just like it is when data sources are created.
+        let needs_restart: bool = self.needs_restart(revert_to_ptr, subgraph_ptr)
+            || (reverted_data_sources > 0 && !self.inputs.static_filters);
 
         let action = if needs_restart {
             Action::Restart
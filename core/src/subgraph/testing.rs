@@ -0,0 +1,1169 @@
+//! Running subgraph instances in tests without a WASM runtime, a database
+//! or an Ethereum node.
+//!
+//! `MockRuntimeHostBuilder` builds hosts whose handlers are Rust closures
+//! instead of mappings, and `InMemoryStore` is a `WritableStore` that keeps
+//! entities in memory. A `TestInstance` uses both to run blocks with
+//! synthetic triggers through the same trigger processing as the
+//! `SubgraphRunner`, and returns the entity modifications and the proof of
+//! indexing for each block so that tests can make assertions about them.
+//!
+//! Handlers write entities through a `HandlerContext`, which also records
+//! the changes in the proof of indexing the same way that the host exports
+//! of the WASM runtime do.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use atomic_refcell::AtomicRefCell;
+use futures01::sync::mpsc;
+use graph::components::{
+    link_resolver::{ArweaveClient, FileSizeLimit},
+    metrics::gas::GasMetrics,
+};
+use graph::{
+    blockchain::{
+        block_stream::{BlockWithTriggers, FirehoseCursor},
+        Block, Blockchain,
+    },
+    components::{
+        store::{
+            DeploymentCursorTracker, DerivedEntityQuery, GetScope, ReadStore,
+            StoredDynamicDataSource, SubgraphFork, UnfailOutcome, WritableStore,
+        },
+        subgraph::{
+            HostMetrics, MappingError, PoICausalityRegion, ProofOfIndexing, ProofOfIndexingEvent,
+            ProofOfIndexingVersion, SharedProofOfIndexing,
+        },
+    },
+    data::subgraph::schema::{SubgraphError, SubgraphHealth},
+    data::subgraph::{SubgraphManifest, SPEC_VERSION_0_0_9},
+    data::value::Word,
+    data_source::{
+        causality_region::CausalityRegionSeq, CausalityRegion, DataSource, DataSourceTemplate,
+        MappingTrigger, TriggerData, TriggerWithHandler,
+    },
+    ipfs_client::IpfsClient,
+    prelude::{
+        ethabi, web3::types::H256, BlockNumber, BlockPtr, BlockState, CheapClone, DeploymentHash,
+        Entity, EntityModification, LightEthereumBlock, Link, Logger, MetricsRegistry, RuntimeHost,
+        RuntimeHostBuilder, StopwatchMetrics, StoreError, SubgraphCountMetric,
+        SubgraphInstanceMetrics, Value, ENV_VARS,
+    },
+    schema::{EntityKey, EntityType, InputSchema},
+    semver::Version,
+    slog::{self, o},
+    util::lfu_cache::LfuCache,
+};
+use graph_chain_ethereum::{
+    chain::BlockFinality,
+    trigger::{EthereumBlockTriggerType, EthereumTrigger},
+    Chain, Mapping, MappingABI, MappingBlockHandler,
+};
+
+use crate::polling_monitor::{arweave_service, ipfs_service};
+use crate::subgraph::context::{
+    IndexingContext, OffchainMonitor, ParallelOutcome, SubgraphKeepAlive,
+};
+use crate::subgraph::SubgraphTriggerProcessor;
+
+/// A handler of a mock host. It is called with the trigger that matched
+/// the handler
+pub type Handler<C> = Arc<
+    dyn Fn(&mut HandlerContext<'_, C>, &MappingTrigger<C>) -> Result<(), MappingError>
+        + Send
+        + Sync,
+>;
+
+/// The handlers of all data sources, by data source name and handler name
+type Handlers<C> = HashMap<(String, String), Handler<C>>;
+
+/// Builds hosts that call Rust closures instead of WASM mappings
+pub struct MockRuntimeHostBuilder<C: Blockchain> {
+    handlers: Arc<Handlers<C>>,
+    /// How many hosts this builder and its clones have built
+    builds: Arc<AtomicUsize>,
+}
+
+impl<C: Blockchain> Clone for MockRuntimeHostBuilder<C> {
+    fn clone(&self) -> Self {
+        Self {
+            handlers: self.handlers.cheap_clone(),
+            builds: self.builds.cheap_clone(),
+        }
+    }
+}
+
+impl<C: Blockchain> Default for MockRuntimeHostBuilder<C> {
+    fn default() -> Self {
+        Self {
+            handlers: Arc::new(HashMap::new()),
+            builds: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+impl<C: Blockchain> MockRuntimeHostBuilder<C> {
+    /// Call `f` for the handler `handler` of the data source `data_source`
+    pub fn with_handler<F>(mut self, data_source: &str, handler: &str, f: F) -> Self
+    where
+        F: Fn(&mut HandlerContext<'_, C>, &MappingTrigger<C>) -> Result<(), MappingError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        Arc::make_mut(&mut self.handlers)
+            .insert((data_source.to_owned(), handler.to_owned()), Arc::new(f));
+        self
+    }
+
+    /// How many hosts this builder and its clones have built so far
+    pub fn builds(&self) -> usize {
+        self.builds.load(Ordering::SeqCst)
+    }
+}
+
+impl<C: Blockchain> RuntimeHostBuilder<C> for MockRuntimeHostBuilder<C> {
+    type Host = MockRuntimeHost<C>;
+    type Req = ();
+
+    fn build(
+        &self,
+        network_name: String,
+        _subgraph_id: DeploymentHash,
+        data_source: DataSource<C>,
+        _top_level_templates: Arc<Vec<DataSourceTemplate<C>>>,
+        _mapping_request_sender: mpsc::Sender<()>,
+        _metrics: Arc<HostMetrics>,
+    ) -> Result<Self::Host, anyhow::Error> {
+        self.builds.fetch_add(1, Ordering::SeqCst);
+        Ok(MockRuntimeHost {
+            data_source,
+            causality_region: PoICausalityRegion::from_network(&network_name),
+            handlers: self.handlers.cheap_clone(),
+        })
+    }
+
+    fn spawn_mapping(
+        _raw_module: &[u8],
+        _logger: Logger,
+        _subgraph_id: DeploymentHash,
+        _metrics: Arc<HostMetrics>,
+    ) -> Result<mpsc::Sender<()>, anyhow::Error> {
+        // Mock hosts never send requests to a mapping
+        let (sender, _) = mpsc::channel(0);
+        Ok(sender)
+    }
+}
+
+/// A host built by `MockRuntimeHostBuilder`
+pub struct MockRuntimeHost<C: Blockchain> {
+    data_source: DataSource<C>,
+    causality_region: String,
+    handlers: Arc<Handlers<C>>,
+}
+
+#[async_trait]
+impl<C: Blockchain> RuntimeHost<C> for MockRuntimeHost<C> {
+    fn data_source(&self) -> &DataSource<C> {
+        &self.data_source
+    }
+
+    fn match_and_decode(
+        &self,
+        trigger: &TriggerData<C>,
+        block: &Arc<C::Block>,
+        logger: &Logger,
+    ) -> Result<Option<TriggerWithHandler<MappingTrigger<C>>>, anyhow::Error> {
+        self.data_source.match_and_decode(trigger, block, logger)
+    }
+
+    async fn process_block(
+        &self,
+        _logger: &Logger,
+        _block_ptr: BlockPtr,
+        _block_data: Box<[u8]>,
+        handler: String,
+        _state: BlockState<C>,
+        _proof_of_indexing: SharedProofOfIndexing,
+        _debug_fork: &Option<Arc<dyn SubgraphFork>>,
+        _instrument: bool,
+    ) -> Result<BlockState<C>, MappingError> {
+        Err(MappingError::Unknown(anyhow!(
+            "mock hosts can not process raw blocks (handler `{}`)",
+            handler
+        )))
+    }
+
+    async fn process_mapping_trigger(
+        &self,
+        logger: &Logger,
+        block_ptr: BlockPtr,
+        trigger: TriggerWithHandler<MappingTrigger<C>>,
+        mut state: BlockState<C>,
+        proof_of_indexing: SharedProofOfIndexing,
+        _debug_fork: &Option<Arc<dyn SubgraphFork>>,
+        _instrument: bool,
+    ) -> Result<BlockState<C>, MappingError> {
+        let name = self.data_source.name();
+        let handler = self
+            .handlers
+            .get(&(name.to_owned(), trigger.handler_name().to_owned()))
+            .ok_or_else(|| {
+                MappingError::Unknown(anyhow!(
+                    "data source `{}` has no handler `{}`",
+                    name,
+                    trigger.handler_name()
+                ))
+            })?;
+
+        state.enter_handler(trigger.handler_name());
+        let mut ctx = HandlerContext {
+            state: &mut state,
+            block: block_ptr.number,
+            proof_of_indexing,
+            causality_region: &self.causality_region,
+            logger,
+        };
+        handler(&mut ctx, &trigger.trigger)?;
+        state.exit_handler();
+        Ok(state)
+    }
+
+    fn creation_block_number(&self) -> Option<BlockNumber> {
+        self.data_source.creation_block()
+    }
+
+    fn done_at(&self) -> Option<BlockNumber> {
+        match &self.data_source {
+            DataSource::Onchain(_) => None,
+            DataSource::Offchain(ds) => ds.done_at(),
+        }
+    }
+
+    fn set_done_at(&self, block: Option<BlockNumber>) {
+        match &self.data_source {
+            DataSource::Onchain(_) => {}
+            DataSource::Offchain(ds) => ds.set_done_at(block),
+        }
+    }
+}
+
+impl<C: Blockchain> PartialEq for MockRuntimeHost<C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.data_source.is_duplicate_of(&other.data_source)
+    }
+}
+
+/// What handlers of mock hosts can do. Entity changes are recorded in the
+/// proof of indexing just like the `store.set` and `store.remove` host
+/// exports do it
+pub struct HandlerContext<'a, C: Blockchain> {
+    pub state: &'a mut BlockState<C>,
+    pub block: BlockNumber,
+    proof_of_indexing: SharedProofOfIndexing,
+    causality_region: &'a str,
+    logger: &'a Logger,
+}
+
+impl<'a, C: Blockchain> HandlerContext<'a, C> {
+    fn key(&self, entity_type: &str, id: &str) -> Result<EntityKey, MappingError> {
+        let entity_type = self.state.entity_cache.schema.entity_type(entity_type)?;
+        Ok(entity_type.parse_key(id)?)
+    }
+
+    fn write_poi_event(&self, event: &ProofOfIndexingEvent) {
+        if let Some(proof_of_indexing) = &self.proof_of_indexing {
+            proof_of_indexing
+                .borrow_mut()
+                .write(self.logger, self.causality_region, event);
+        }
+    }
+
+    pub fn get(&mut self, entity_type: &str, id: &str) -> Result<Option<Entity>, MappingError> {
+        let key = self.key(entity_type, id)?;
+        let entity = self
+            .state
+            .entity_cache
+            .get(&key, GetScope::Store)
+            .map_err(|e| anyhow!(e))?;
+        Ok(entity.map(|entity| entity.into_owned()))
+    }
+
+    /// Set the entity with the given `id` to `data`
+    pub fn set(
+        &mut self,
+        entity_type: &str,
+        id: &str,
+        data: Vec<(&str, Value)>,
+    ) -> Result<(), MappingError> {
+        let key = self.key(entity_type, id)?;
+        let data = data
+            .into_iter()
+            .map(|(field, value)| (Word::from(field), value))
+            .chain(std::iter::once((
+                Word::from("id"),
+                Value::from(key.entity_id.clone()),
+            )));
+        let entity = self
+            .state
+            .entity_cache
+            .make_entity(data)
+            .map_err(|e| anyhow!(e))?;
+        self.write_poi_event(&ProofOfIndexingEvent::SetEntity {
+            entity_type,
+            id,
+            data: &entity,
+        });
+        self.state.entity_cache.set(key, entity)?;
+        Ok(())
+    }
+
+    pub fn remove(&mut self, entity_type: &str, id: &str) -> Result<(), MappingError> {
+        let key = self.key(entity_type, id)?;
+        self.write_poi_event(&ProofOfIndexingEvent::RemoveEntity { entity_type, id });
+        self.state.entity_cache.remove(key);
+        Ok(())
+    }
+}
+
+/// A `WritableStore` that keeps the entities of one deployment in memory
+pub struct InMemoryStore {
+    schema: InputSchema,
+    inner: Mutex<InMemoryInner>,
+}
+
+#[derive(Default)]
+struct InMemoryInner {
+    entities: BTreeMap<EntityKey, Entity>,
+    block_ptr: Option<BlockPtr>,
+    /// For each block that was written, the previous versions of the
+    /// entities that the block changed, so that the block can be reverted
+    history: Vec<(BlockPtr, Vec<(EntityKey, Option<Entity>)>)>,
+    data_sources: Vec<StoredDynamicDataSource>,
+    health: Option<SubgraphHealth>,
+}
+
+impl InMemoryStore {
+    pub fn new(schema: InputSchema) -> Self {
+        Self {
+            schema,
+            inner: Mutex::new(InMemoryInner::default()),
+        }
+    }
+
+    /// All entities in the store
+    pub fn entities(&self) -> BTreeMap<EntityKey, Entity> {
+        self.inner.lock().unwrap().entities.clone()
+    }
+}
+
+impl ReadStore for InMemoryStore {
+    fn get(&self, key: &EntityKey) -> Result<Option<Entity>, StoreError> {
+        Ok(self.inner.lock().unwrap().entities.get(key).cloned())
+    }
+
+    fn get_many(
+        &self,
+        keys: BTreeSet<EntityKey>,
+    ) -> Result<BTreeMap<EntityKey, Entity>, StoreError> {
+        let inner = self.inner.lock().unwrap();
+        Ok(keys
+            .into_iter()
+            .filter_map(|key| {
+                inner
+                    .entities
+                    .get(&key)
+                    .cloned()
+                    .map(|entity| (key, entity))
+            })
+            .collect())
+    }
+
+    fn get_derived(
+        &self,
+        query: &DerivedEntityQuery,
+    ) -> Result<BTreeMap<EntityKey, Entity>, StoreError> {
+        let value = Value::from(query.value.clone());
+        let inner = self.inner.lock().unwrap();
+        Ok(inner
+            .entities
+            .iter()
+            .filter(|(key, entity)| {
+                key.entity_type == query.entity_type
+                    && key.causality_region == query.causality_region
+                    && entity.get(query.entity_field.as_str()) == Some(&value)
+            })
+            .map(|(key, entity)| (key.clone(), entity.clone()))
+            .collect())
+    }
+
+    fn max_timestamp(&self, entity_type: &EntityType) -> Result<Option<i64>, StoreError> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner
+            .entities
+            .iter()
+            .filter(|(key, _)| &key.entity_type == entity_type)
+            .filter_map(|(_, entity)| match entity.get("timestamp") {
+                Some(Value::Int8(timestamp)) => Some(*timestamp),
+                _ => None,
+            })
+            .max())
+    }
+
+    fn input_schema(&self) -> InputSchema {
+        self.schema.cheap_clone()
+    }
+}
+
+impl DeploymentCursorTracker for InMemoryStore {
+    fn input_schema(&self) -> InputSchema {
+        self.schema.cheap_clone()
+    }
+
+    fn block_ptr(&self) -> Option<BlockPtr> {
+        self.inner.lock().unwrap().block_ptr.clone()
+    }
+
+    fn firehose_cursor(&self) -> FirehoseCursor {
+        FirehoseCursor::None
+    }
+}
+
+#[async_trait]
+impl WritableStore for InMemoryStore {
+    async fn start_subgraph_deployment(&self, _logger: &Logger) -> Result<(), StoreError> {
+        Ok(())
+    }
+
+    async fn revert_block_operations(
+        &self,
+        block_ptr_to: BlockPtr,
+        _firehose_cursor: FirehoseCursor,
+    ) -> Result<(), StoreError> {
+        let mut inner = self.inner.lock().unwrap();
+        while inner
+            .history
+            .last()
+            .map_or(false, |(ptr, _)| ptr.number > block_ptr_to.number)
+        {
+            let (_, previous) = inner.history.pop().unwrap();
+            for (key, entity) in previous.into_iter().rev() {
+                match entity {
+                    Some(entity) => inner.entities.insert(key, entity),
+                    None => inner.entities.remove(&key),
+                };
+            }
+        }
+        inner.block_ptr = Some(block_ptr_to);
+        Ok(())
+    }
+
+    async fn unfail_deterministic_error(
+        &self,
+        _current_ptr: &BlockPtr,
+        _parent_ptr: &BlockPtr,
+    ) -> Result<UnfailOutcome, StoreError> {
+        Ok(UnfailOutcome::Noop)
+    }
+
+    fn unfail_non_deterministic_error(
+        &self,
+        _current_ptr: &BlockPtr,
+    ) -> Result<UnfailOutcome, StoreError> {
+        Ok(UnfailOutcome::Noop)
+    }
+
+    async fn fail_subgraph(&self, _error: SubgraphError) -> Result<(), StoreError> {
+        self.inner.lock().unwrap().health = Some(SubgraphHealth::Failed);
+        Ok(())
+    }
+
+    async fn supports_proof_of_indexing(&self) -> Result<bool, StoreError> {
+        Ok(true)
+    }
+
+    async fn transact_block_operations(
+        &self,
+        block_ptr_to: BlockPtr,
+        _firehose_cursor: FirehoseCursor,
+        mods: Vec<EntityModification>,
+        _stopwatch: &StopwatchMetrics,
+        data_sources: Vec<StoredDynamicDataSource>,
+        deterministic_errors: Vec<SubgraphError>,
+        _offchain_to_remove: Vec<StoredDynamicDataSource>,
+        _is_non_fatal_errors_active: bool,
+    ) -> Result<(), StoreError> {
+        let mut inner = self.inner.lock().unwrap();
+        let mut previous = Vec::with_capacity(mods.len());
+        for modification in mods {
+            let (key, entity) = match modification {
+                EntityModification::Insert { key, data, .. }
+                | EntityModification::Overwrite { key, data, .. } => (key, Some(data)),
+                EntityModification::Remove { key, .. } => (key, None),
+            };
+            let old = match entity {
+                Some(entity) => inner.entities.insert(key.clone(), entity),
+                None => inner.entities.remove(&key),
+            };
+            previous.push((key, old));
+        }
+        inner.history.push((block_ptr_to.clone(), previous));
+        inner.data_sources.extend(data_sources);
+        if !deterministic_errors.is_empty() {
+            inner.health = Some(SubgraphHealth::Unhealthy);
+        }
+        inner.block_ptr = Some(block_ptr_to);
+        Ok(())
+    }
+
+    fn deployment_synced(&self) -> Result<(), StoreError> {
+        Ok(())
+    }
+
+    async fn is_deployment_synced(&self) -> Result<bool, StoreError> {
+        Ok(false)
+    }
+
+    fn unassign_subgraph(&self) -> Result<(), StoreError> {
+        Ok(())
+    }
+
+    async fn load_dynamic_data_sources(
+        &self,
+        _manifest_idx_and_name: Vec<(u32, String)>,
+    ) -> Result<Vec<StoredDynamicDataSource>, StoreError> {
+        Ok(self.inner.lock().unwrap().data_sources.clone())
+    }
+
+    async fn causality_region_curr_val(&self) -> Result<Option<CausalityRegion>, StoreError> {
+        Ok(None)
+    }
+
+    fn shard(&self) -> &str {
+        "memory"
+    }
+
+    async fn health(&self) -> Result<SubgraphHealth, StoreError> {
+        Ok(self
+            .inner
+            .lock()
+            .unwrap()
+            .health
+            .unwrap_or(SubgraphHealth::Healthy))
+    }
+
+    async fn flush(&self) -> Result<(), StoreError> {
+        Ok(())
+    }
+
+    async fn restart(self: Arc<Self>) -> Result<Option<Arc<dyn WritableStore>>, StoreError> {
+        Ok(None)
+    }
+
+    async fn take_over(self: Arc<Self>) -> Result<Option<Arc<dyn WritableStore>>, StoreError> {
+        Ok(None)
+    }
+}
+
+/// The result of processing a block with a `TestInstance`
+#[derive(Debug)]
+pub struct BlockOutcome {
+    /// The entity changes that were written for the block
+    pub modifications: Vec<EntityModification>,
+    /// The digest of the proof of indexing of the block for each causality
+    /// region, sorted by causality region
+    pub poi: Vec<(String, Vec<u8>)>,
+    /// Whether the triggers of the block were processed in parallel
+    pub parallel: bool,
+}
+
+/// An Ethereum subgraph whose data sources are handled by a
+/// `MockRuntimeHostBuilder`, writing to an `InMemoryStore`
+pub struct TestInstance {
+    logger: Logger,
+    ctx: IndexingContext<Chain, MockRuntimeHostBuilder<Chain>>,
+    store: Arc<InMemoryStore>,
+    writable: Arc<dyn WritableStore>,
+    network: String,
+    metrics: Arc<SubgraphInstanceMetrics>,
+    stopwatch: StopwatchMetrics,
+    entity_lfu_cache: LfuCache<EntityKey, Option<Entity>>,
+    parallelism: usize,
+}
+
+impl TestInstance {
+    /// A subgraph with the given `schema` and `data_sources`, in the order
+    /// in which they are declared in the manifest. Must be called from
+    /// within a tokio runtime
+    pub fn new(
+        schema: &str,
+        data_sources: Vec<graph_chain_ethereum::DataSource>,
+        host_builder: MockRuntimeHostBuilder<Chain>,
+    ) -> Result<Self, anyhow::Error> {
+        let logger = Logger::root(slog::Discard, o!());
+        let id = DeploymentHash::new("testInstance").unwrap();
+        let registry = Arc::new(MetricsRegistry::mock());
+        let schema = InputSchema::parse(schema, id.clone())?;
+        let network = "mainnet".to_owned();
+
+        let manifest = SubgraphManifest::<Chain> {
+            id: id.clone(),
+            spec_version: SPEC_VERSION_0_0_9,
+            features: Default::default(),
+            description: None,
+            repository: None,
+            schema: schema.cheap_clone(),
+            data_sources: data_sources
+                .iter()
+                .cloned()
+                .map(DataSource::Onchain)
+                .collect(),
+            graft: None,
+            templates: vec![],
+            chain: PhantomData,
+            indexer_hints: None,
+        };
+
+        let stopwatch = StopwatchMetrics::new(
+            logger.clone(),
+            id.clone(),
+            "process",
+            registry.cheap_clone(),
+            "memory".to_owned(),
+        );
+        let host_metrics = Arc::new(HostMetrics::new(
+            registry.cheap_clone(),
+            id.as_str(),
+            stopwatch.cheap_clone(),
+            GasMetrics::new(id.clone(), registry.cheap_clone()),
+        ));
+        let metrics = Arc::new(SubgraphInstanceMetrics::new(
+            registry.cheap_clone(),
+            id.as_str(),
+            stopwatch.cheap_clone(),
+        ));
+
+        // File data sources are not supported, but the context needs a
+        // monitor for them
+        let offchain_monitor = OffchainMonitor::new(
+            logger.clone(),
+            registry.cheap_clone(),
+            &id,
+            ipfs_service(
+                IpfsClient::localhost(),
+                ENV_VARS.mappings.max_ipfs_file_bytes as u64,
+                ENV_VARS.mappings.ipfs_timeout,
+                ENV_VARS.mappings.ipfs_request_limit,
+            ),
+            arweave_service(
+                Arc::new(ArweaveClient::default()),
+                ENV_VARS.mappings.ipfs_timeout,
+                ENV_VARS.mappings.ipfs_request_limit,
+                FileSizeLimit::Unlimited,
+            ),
+        );
+
+        let mut ctx = IndexingContext::new(
+            manifest,
+            host_builder,
+            host_metrics,
+            CausalityRegionSeq::from_current(None),
+            SubgraphKeepAlive::new(Arc::new(SubgraphCountMetric::new(registry))),
+            offchain_monitor,
+            Box::new(SubgraphTriggerProcessor {}),
+        );
+        for data_source in data_sources {
+            ctx.add_dynamic_data_source(&logger, DataSource::Onchain(data_source))?;
+        }
+
+        let store = Arc::new(InMemoryStore::new(schema));
+        Ok(TestInstance {
+            logger,
+            ctx,
+            writable: store.cheap_clone(),
+            store,
+            network,
+            metrics,
+            stopwatch,
+            entity_lfu_cache: LfuCache::new(),
+            parallelism: 1,
+        })
+    }
+
+    /// Process the triggers of each block with up to `parallelism` groups
+    /// of data sources at the same time, like the runner does when
+    /// `GRAPH_SUBGRAPH_TRIGGER_PARALLELISM` is set
+    pub fn with_parallelism(mut self, parallelism: usize) -> Self {
+        self.parallelism = parallelism;
+        self
+    }
+
+    /// Allow at most `max` data sources instead of
+    /// `GRAPH_SUBGRAPH_MAX_DATA_SOURCES`
+    pub fn with_max_data_sources(mut self, max: usize) -> Self {
+        self.ctx.set_max_data_sources(max);
+        self
+    }
+
+    pub fn store(&self) -> &Arc<InMemoryStore> {
+        &self.store
+    }
+
+    /// Add `data_source` the way the runner adds a dynamic data source.
+    /// Returns whether a host was created for it
+    pub fn add_data_source(
+        &mut self,
+        data_source: graph_chain_ethereum::DataSource,
+    ) -> Result<bool, anyhow::Error> {
+        let host = self
+            .ctx
+            .add_dynamic_data_source(&self.logger, DataSource::Onchain(data_source))?;
+        Ok(host.is_some())
+    }
+
+    pub fn hosts_len(&self) -> usize {
+        self.ctx.hosts_len()
+    }
+
+    /// Process the triggers of `block` and write the resulting entity
+    /// changes to the store
+    pub async fn process_block(
+        &mut self,
+        block: &BlockWithTriggers<Chain>,
+    ) -> Result<BlockOutcome, MappingError> {
+        let triggers: Vec<_> = block
+            .trigger_data
+            .iter()
+            .cloned()
+            .map(TriggerData::Onchain)
+            .collect();
+        let block = Arc::new(block.block.clone());
+        let block_ptr = block.ptr();
+        let proof_of_indexing = Some(Arc::new(AtomicRefCell::new(ProofOfIndexing::new(
+            block_ptr.number,
+            ProofOfIndexingVersion::Fast,
+        ))));
+        let causality_region = PoICausalityRegion::from_network(&self.network);
+
+        let mut entity_lfu_cache = std::mem::take(&mut self.entity_lfu_cache);
+        let mut state = None;
+        if self.parallelism > 1 {
+            match self
+                .ctx
+                .process_triggers_in_parallel(
+                    &self.logger,
+                    &block,
+                    &triggers,
+                    &self.writable,
+                    entity_lfu_cache,
+                    &proof_of_indexing,
+                    &causality_region,
+                    &None,
+                    &self.metrics,
+                    false,
+                    self.parallelism,
+                )
+                .await?
+            {
+                ParallelOutcome::Processed(block_state) => {
+                    entity_lfu_cache = LfuCache::new();
+                    state = Some(block_state);
+                }
+                ParallelOutcome::Sequential(cache) => entity_lfu_cache = cache,
+            }
+        }
+        let parallel = state.is_some();
+
+        let state = match state {
+            Some(state) => state,
+            None => {
+                let mut state = BlockState::new(self.writable.cheap_clone(), entity_lfu_cache);
+                for trigger in &triggers {
+                    state = self
+                        .ctx
+                        .process_trigger(
+                            &self.logger,
+                            &block,
+                            trigger,
+                            state,
+                            &proof_of_indexing,
+                            &causality_region,
+                            &None,
+                            &self.metrics,
+                            false,
+                        )
+                        .await?;
+                }
+                state
+            }
+        };
+        let state = self
+            .ctx
+            .process_batch_handlers(
+                &self.logger,
+                &block,
+                &triggers,
+                state,
+                &proof_of_indexing,
+                &causality_region,
+                &None,
+                &self.metrics,
+                false,
+            )
+            .await?;
+
+        let proof_of_indexing = Arc::try_unwrap(proof_of_indexing.unwrap())
+            .unwrap()
+            .into_inner();
+        let mut poi: Vec<_> = proof_of_indexing
+            .take()
+            .into_iter()
+            .map(|(causality_region, stream)| (causality_region.to_string(), stream.pause(None)))
+            .collect();
+        poi.sort();
+
+        let BlockState {
+            entity_cache,
+            deterministic_errors,
+            persisted_data_sources,
+            ..
+        } = state;
+        let modifications = entity_cache
+            .as_modifications(block_ptr.number)
+            .map_err(|e| anyhow!(e))?;
+        self.entity_lfu_cache = modifications.entity_lfu_cache;
+        self.writable
+            .transact_block_operations(
+                block_ptr,
+                FirehoseCursor::None,
+                modifications.modifications.clone(),
+                &self.stopwatch,
+                persisted_data_sources,
+                deterministic_errors,
+                vec![],
+                false,
+            )
+            .await
+            .map_err(|e| anyhow!(e))?;
+
+        Ok(BlockOutcome {
+            modifications: modifications.modifications,
+            poi,
+            parallel,
+        })
+    }
+
+    /// Revert the store to `block_ptr`, like the runner does for a reorg.
+    /// Returns how many data sources created after `block_ptr` were removed
+    pub async fn revert(&mut self, block_ptr: BlockPtr) -> Result<usize, anyhow::Error> {
+        self.writable
+            .revert_block_operations(block_ptr.clone(), FirehoseCursor::None)
+            .await?;
+        let reverted = self.ctx.revert_data_sources(block_ptr.number + 1)?;
+        self.entity_lfu_cache = LfuCache::new();
+        Ok(reverted)
+    }
+}
+
+/// An Ethereum data source named `name` that handles every block with the
+/// handler `handler`
+pub fn block_data_source(name: &str, handler: &str) -> graph_chain_ethereum::DataSource {
+    graph_chain_ethereum::DataSource {
+        kind: "ethereum/contract".to_owned(),
+        network: Some("mainnet".to_owned()),
+        name: name.to_owned(),
+        manifest_idx: 0,
+        address: None,
+        start_block: 0,
+        end_block: None,
+        mapping: Mapping {
+            kind: "ethereum/events".to_owned(),
+            api_version: Version::new(0, 0, 7),
+            language: "wasm/assemblyscript".to_owned(),
+            entities: vec![],
+            abis: vec![],
+            block_handlers: vec![MappingBlockHandler {
+                handler: handler.to_owned(),
+                filter: None,
+                start: Default::default(),
+            }],
+            call_handlers: vec![],
+            event_handlers: vec![],
+            contract_creation_handlers: vec![],
+            runtime: Arc::new(name.as_bytes().to_vec()),
+            link: Link {
+                link: name.to_owned(),
+            },
+        },
+        context: Arc::new(None),
+        creation_block: None,
+        contract_abi: Arc::new(MappingABI {
+            name: name.to_owned(),
+            contract: ethabi::Contract::load("[]".as_bytes()).unwrap(),
+        }),
+    }
+}
+
+/// The pointer of the test block with number `number`
+pub fn block_ptr(number: BlockNumber) -> BlockPtr {
+    BlockPtr {
+        hash: H256::from_low_u64_be(number as u64 + 1).into(),
+        number,
+    }
+}
+
+/// A block with number `number` that only has a trigger for block handlers
+pub fn block(number: BlockNumber) -> BlockWithTriggers<Chain> {
+    let ptr = block_ptr(number);
+    let parent_hash = match number {
+        0 => H256::zero(),
+        n => H256::from_slice(block_ptr(n - 1).hash.as_slice()),
+    };
+    BlockWithTriggers {
+        block: BlockFinality::Final(Arc::new(LightEthereumBlock {
+            hash: Some(H256::from_slice(ptr.hash.as_slice())),
+            number: Some((number as u64).into()),
+            parent_hash,
+            ..Default::default()
+        })),
+        trigger_data: vec![EthereumTrigger::Block(ptr, EthereumBlockTriggerType::End)],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use graph::prelude::tokio;
+    use graph::prelude::web3::types::{Bytes, Log, Transaction, U256, U64};
+    use graph_chain_ethereum::{trigger::LogRef, MappingEventHandler};
+
+    use super::*;
+
+    const SCHEMA: &str = "
+        type Counter @entity { id: ID!, count: Int! }
+        type Log @entity { id: ID!, block: Int! }";
+
+    fn host_builder() -> MockRuntimeHostBuilder<Chain> {
+        MockRuntimeHostBuilder::default()
+            .with_handler("counter", "handleBlock", |ctx, _| {
+                let count = match ctx.get("Counter", "c")? {
+                    Some(counter) => counter.get("count").and_then(|v| v.as_int()).unwrap_or(0),
+                    None => 0,
+                };
+                ctx.set("Counter", "c", vec![("count", Value::Int(count + 1))])
+            })
+            .with_handler("log", "handleBlock", |ctx, _| {
+                let id = format!("log-{}", ctx.block);
+                let block = ctx.block;
+                ctx.set("Log", &id, vec![("block", Value::Int(block))])?;
+                if block == 2 {
+                    ctx.remove("Log", "log-0")?;
+                }
+                Ok(())
+            })
+    }
+
+    #[tokio::test]
+    async fn processes_blocks() {
+        let mut instance = TestInstance::new(
+            SCHEMA,
+            vec![
+                block_data_source("counter", "handleBlock"),
+                block_data_source("log", "handleBlock"),
+            ],
+            host_builder(),
+        )
+        .unwrap();
+
+        for number in 0..3 {
+            let outcome = instance.process_block(&block(number)).await.unwrap();
+            assert!(!outcome.parallel);
+            assert_eq!(1, outcome.poi.len());
+        }
+
+        let entities = instance.store().entities();
+        let ids: Vec<_> = entities
+            .keys()
+            .map(|key| format!("{}[{}]", key.entity_type, key.entity_id))
+            .collect();
+        assert_eq!(vec!["Counter[c]", "Log[log-1]", "Log[log-2]"], ids);
+        let counter = entities.values().next().unwrap();
+        assert_eq!(Some(&Value::Int(3)), counter.get("count"));
+        assert_eq!(Some(block_ptr(2)), instance.store().block_ptr());
+
+        instance.revert(block_ptr(0)).await.unwrap();
+        let entities = instance.store().entities();
+        assert_eq!(2, entities.len());
+        let counter = entities.values().next().unwrap();
+        assert_eq!(Some(&Value::Int(1)), counter.get("count"));
+        assert_eq!(Some(block_ptr(0)), instance.store().block_ptr());
+    }
+
+    #[tokio::test]
+    async fn handler_errors_are_returned() {
+        let host_builder =
+            MockRuntimeHostBuilder::default().with_handler("fails", "handleBlock", |_, _| {
+                Err(MappingError::Unknown(anyhow!("boom")))
+            });
+        let mut instance = TestInstance::new(
+            SCHEMA,
+            vec![block_data_source("fails", "handleBlock")],
+            host_builder,
+        )
+        .unwrap();
+
+        assert!(instance.process_block(&block(0)).await.is_err());
+        assert_eq!(None, instance.store().block_ptr());
+    }
+
+    const PING_ABI: &str = r#"[{"type": "event", "name": "Ping", "anonymous": false,
+        "inputs": [{"name": "n", "type": "uint256", "indexed": false}]}]"#;
+
+    /// A data source named `name` that handles the event `Ping(uint256)`
+    /// with `handler`
+    fn ping_data_source(
+        name: &str,
+        handler: &str,
+        batch: bool,
+    ) -> graph_chain_ethereum::DataSource {
+        let mut data_source = block_data_source(name, handler);
+        data_source.mapping.block_handlers = vec![];
+        data_source.mapping.event_handlers = vec![MappingEventHandler {
+            event: "Ping(uint256)".to_owned(),
+            topic0: None,
+            handler: handler.to_owned(),
+            receipt: false,
+            batch,
+            filter: None,
+            topics: Default::default(),
+            start: Default::default(),
+        }];
+        data_source.contract_abi = Arc::new(MappingABI {
+            name: name.to_owned(),
+            contract: ethabi::Contract::load(PING_ABI.as_bytes()).unwrap(),
+        });
+        data_source
+    }
+
+    /// A block with number `number` that emits `Ping(n)` for each of `pings`
+    fn ping_block(number: BlockNumber, pings: &[u64]) -> BlockWithTriggers<Chain> {
+        let ptr = block_ptr(number);
+        let hash = H256::from_slice(ptr.hash.as_slice());
+        let transaction = Transaction {
+            hash: H256::from_low_u64_be(1000 + number as u64),
+            block_hash: Some(hash),
+            ..Default::default()
+        };
+        let topic0 = ethabi::Contract::load(PING_ABI.as_bytes())
+            .unwrap()
+            .event("Ping")
+            .unwrap()
+            .signature();
+        let trigger_data = pings
+            .iter()
+            .enumerate()
+            .map(|(index, n)| {
+                let log = Log {
+                    address: Default::default(),
+                    topics: vec![topic0],
+                    data: Bytes(ethabi::encode(&[ethabi::Token::Uint(U256::from(*n))])),
+                    block_hash: Some(hash),
+                    block_number: Some((number as u64).into()),
+                    transaction_hash: Some(transaction.hash),
+                    transaction_index: Some(U64::zero()),
+                    log_index: Some(index.into()),
+                    transaction_log_index: Some(index.into()),
+                    log_type: None,
+                    removed: Some(false),
+                };
+                EthereumTrigger::Log(LogRef::FullLog(Arc::new(log), None))
+            })
+            .collect();
+        let mut block = block(number);
+        block.block = BlockFinality::Final(Arc::new(LightEthereumBlock {
+            hash: Some(hash),
+            number: Some((number as u64).into()),
+            transactions: vec![transaction],
+            ..Default::default()
+        }));
+        block.trigger_data = trigger_data;
+        block
+    }
+
+    #[tokio::test]
+    async fn batch_handlers_run_after_other_handlers() {
+        let host_builder = MockRuntimeHostBuilder::default()
+            .with_handler("single", "handlePing", |ctx, _| {
+                let count = match ctx.get("Counter", "pings")? {
+                    Some(counter) => counter.get("count").and_then(|v| v.as_int()).unwrap_or(0),
+                    None => 0,
+                };
+                ctx.set("Counter", "pings", vec![("count", Value::Int(count + 1))])
+            })
+            .with_handler("batch", "handlePings", |ctx, trigger| {
+                let logs = match trigger {
+                    MappingTrigger::Onchain(
+                        graph_chain_ethereum::trigger::MappingTrigger::Logs { logs },
+                    ) => logs.len() as i32,
+                    trigger => {
+                        return Err(MappingError::Unknown(anyhow!(
+                            "expected a batch of events but got {:?}",
+                            trigger
+                        )))
+                    }
+                };
+                // All ordinary handlers for the block have run already
+                let seen = ctx
+                    .get("Counter", "pings")?
+                    .and_then(|counter| counter.get("count").and_then(|v| v.as_int()))
+                    .unwrap_or(0);
+                ctx.set("Counter", "batch", vec![("count", Value::Int(logs))])?;
+                ctx.set("Counter", "seen", vec![("count", Value::Int(seen))])
+            });
+
+        // The batch data source comes first, so its handler would run first
+        // if it was called for each event
+        let mut instance = TestInstance::new(
+            SCHEMA,
+            vec![
+                ping_data_source("batch", "handlePings", true),
+                ping_data_source("single", "handlePing", false),
+            ],
+            host_builder,
+        )
+        .unwrap();
+
+        instance
+            .process_block(&ping_block(0, &[1, 2, 3]))
+            .await
+            .unwrap();
+
+        let counts: Vec<_> = instance
+            .store()
+            .entities()
+            .into_iter()
+            .map(|(key, counter)| {
+                (
+                    key.entity_id.to_string(),
+                    counter.get("count").and_then(|v| v.as_int()).unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("batch".to_string(), 3),
+                ("pings".to_string(), 3),
+                ("seen".to_string(), 3)
+            ],
+            counts
+        );
+
+        // Blocks without matching events do not call the batch handler
+        instance.process_block(&ping_block(1, &[])).await.unwrap();
+        assert_eq!(3, instance.store().entities().len());
+    }
+}
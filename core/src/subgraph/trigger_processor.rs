@@ -48,6 +48,15 @@ where
                     None => continue,
                 };
 
+                // Batch handlers are called once per block with all their
+                // triggers, see `IndexingContext::process_batch_handlers`.
+                if host
+                    .data_source()
+                    .is_batch_handler(mapping_trigger.handler_name())
+                {
+                    continue;
+                }
+
                 host_mapping.push((host, mapping_trigger));
             }
         }
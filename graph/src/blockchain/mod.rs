@@ -23,7 +23,7 @@ use crate::{
     },
     data::subgraph::{UnifiedMappingApiVersion, MIN_SPEC_VERSION},
     data_source,
-    prelude::DataSourceContext,
+    prelude::{DataSourceContext, DeploymentHash},
     runtime::{gas::GasCounter, AscHeap, HostExportError},
 };
 use crate::{
@@ -40,6 +40,7 @@ use serde::{Deserialize, Serialize};
 use slog::Logger;
 use std::{
     any::Any,
+    cmp::Ordering,
     collections::{HashMap, HashSet},
     fmt::{self, Debug},
     str::FromStr,
@@ -205,6 +206,13 @@ pub trait Blockchain: Debug + Sized + Send + Sync + Unpin + 'static {
     fn chain_client(&self) -> Arc<ChainClient<Self>>;
 
     fn block_ingestor(&self) -> anyhow::Result<Box<dyn BlockIngestor>>;
+
+    /// Whether any of the providers for this chain has the `capabilities`.
+    /// Only used to warn about subgraphs that can't be indexed with the
+    /// configured providers when they are deployed.
+    fn has_provider_with(&self, _capabilities: &Self::NodeCapabilities) -> bool {
+        true
+    }
 }
 
 #[derive(Error, Debug)]
@@ -274,6 +282,19 @@ pub trait DataSource<C: Blockchain>: 'static + Sized + Send + Sync + Clone {
 
     fn handler_kinds(&self) -> HashSet<&str>;
 
+    /// Whether `handler` is a batch handler. Batch handlers are not called
+    /// for each trigger they match, but once per block with all of the
+    /// block's matching triggers, after all other handlers for the block
+    /// have run.
+    fn is_batch_handler(&self, _handler: &str) -> bool {
+        false
+    }
+
+    /// Whether this data source has any batch handlers.
+    fn has_batch_handlers(&self) -> bool {
+        false
+    }
+
     /// Checks if `trigger` matches this data source, and if so decodes it into a `MappingTrigger`.
     /// A return of `Ok(None)` mean the trigger does not match.
     ///
@@ -299,6 +320,12 @@ pub trait DataSource<C: Blockchain>: 'static + Sized + Send + Sync + Clone {
     /// Used as part of manifest validation. If there are no errors, return an empty vector.
     fn validate(&self) -> Vec<Error>;
 
+    /// Used as part of manifest linting when a subgraph is deployed. Returns
+    /// warnings about things that are allowed but most likely a mistake.
+    fn lint(&self) -> Vec<String> {
+        vec![]
+    }
+
     fn has_expired(&self, block: BlockNumber) -> bool {
         self.end_block()
             .map_or(false, |end_block| block > end_block)
@@ -319,6 +346,11 @@ pub trait UnresolvedDataSourceTemplate<C: Blockchain>:
 
 pub trait DataSourceTemplate<C: Blockchain>: Send + Sync + Debug {
     fn api_version(&self) -> semver::Version;
+
+    fn min_spec_version(&self) -> semver::Version {
+        MIN_SPEC_VERSION
+    }
+
     fn runtime(&self) -> Option<Arc<Vec<u8>>>;
     fn name(&self) -> &str;
     fn manifest_idx(&self) -> u32;
@@ -342,6 +374,17 @@ pub trait TriggerData {
     /// For example an useful return is: `"block #<N> (<hash>), transaction <tx_hash>".
     fn error_context(&self) -> String;
 
+    /// Compare two triggers of the same block by the total order in which
+    /// deployments with spec version 1.0.0 or later process them. Triggers
+    /// that compare equal keep the order that `Ord` gave them, which is all
+    /// that chains that do not override this get
+    fn cmp_position(&self, _other: &Self) -> Ordering
+    where
+        Self: Sized,
+    {
+        Ordering::Equal
+    }
+
     /// If this trigger can only possibly match data sources with a specific address, then it can be
     /// returned here for improved trigger matching performance, which helps subgraphs with many
     /// data sources. But this optimization is not required, so returning `None` is always correct.
@@ -355,9 +398,27 @@ pub trait MappingTriggerTrait {
     /// If there is an error when processing this trigger, this will called to add relevant context.
     /// For example an useful return is: `"block #<N> (<hash>), transaction <tx_hash>".
     fn error_context(&self) -> String;
+
+    /// The hash of the transaction this trigger comes from, if any. It is
+    /// recorded with errors to make it easier to find what caused them.
+    fn transaction_hash(&self) -> Option<String> {
+        None
+    }
+
+    /// Combines the triggers that matched a batch handler in one block
+    /// into a single trigger that passes all of them to the handler. The
+    /// triggers are in the order in which they occurred in the block.
+    fn batch(_triggers: Vec<Self>) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        Err(anyhow!("batch handlers are not supported on this chain"))
+    }
 }
 
 pub struct HostFnCtx<'a> {
+    /// The deployment whose mapping called the host fn
+    pub deployment: &'a DeploymentHash,
     pub logger: Logger,
     pub block_ptr: BlockPtr,
     pub heap: &'a mut dyn AscHeap,
@@ -415,7 +415,10 @@ where
 
             // In principle this block should be in the store, but we have seen this error for deep
             // reorgs in ropsten.
-            let head_ancestor_opt = self.adapter.ancestor_block(head_ptr, offset).await?;
+            let head_ancestor_opt = self
+                .adapter
+                .ancestor_block(head_ptr.clone(), offset)
+                .await?;
 
             match head_ancestor_opt {
                 None => {
@@ -433,13 +436,34 @@ where
                         // The subgraph ptr is an ancestor of the head block.
                         // We cannot use an RPC call here to find the first interesting block
                         // due to the race conditions previously mentioned,
-                        // so instead we will advance the subgraph ptr by one block.
+                        // so instead we will advance the subgraph ptr by the next few
+                        // blocks from the block store, one block unless configured otherwise.
                         // Note that head_ancestor is a child of subgraph_ptr.
-                        let block = self
-                            .adapter
-                            .triggers_in_block(&self.logger, head_ancestor, &self.filter)
-                            .await?;
-                        Ok(ReconciliationStep::ProcessDescendantBlocks(vec![block], 1))
+                        let range_size = ENV_VARS.near_head_block_range_size.min(offset + 1);
+                        let mut blocks = vec![head_ancestor];
+                        for offset in (offset + 1 - range_size..offset).rev() {
+                            match self
+                                .adapter
+                                .ancestor_block(head_ptr.clone(), offset)
+                                .await?
+                            {
+                                Some(block) => blocks.push(block),
+                                None => break,
+                            }
+                        }
+                        let mut next_blocks = Vec::with_capacity(blocks.len());
+                        for block in linked_descendants(&subgraph_ptr, blocks) {
+                            let block = self
+                                .adapter
+                                .triggers_in_block(&self.logger, block, &self.filter)
+                                .await?;
+                            next_blocks.push(block);
+                        }
+                        let range_size = next_blocks.len() as BlockNumber;
+                        Ok(ReconciliationStep::ProcessDescendantBlocks(
+                            next_blocks,
+                            range_size,
+                        ))
                     } else {
                         let parent = self.parent_ptr(&subgraph_ptr, "nonfinal").await?;
 
@@ -613,3 +637,78 @@ impl<C: Blockchain> Stream for PollingBlockStream<C> {
         result
     }
 }
+
+/// The blocks from the start of `blocks` that form a chain starting with a
+/// child of `ptr`. Since the block store can change while we look up
+/// blocks, later blocks need not be descendants of earlier ones
+fn linked_descendants<B: Block>(ptr: &BlockPtr, blocks: Vec<B>) -> Vec<B> {
+    let mut parent = ptr.hash.clone();
+    blocks
+        .into_iter()
+        .take_while(|block| {
+            let linked = block.parent_hash().as_ref() == Some(&parent);
+            parent = block.hash();
+            linked
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blockchain::{Block, BlockHash, BlockPtr};
+
+    use super::linked_descendants;
+
+    struct TestBlock {
+        ptr: BlockPtr,
+        parent: BlockPtr,
+    }
+
+    impl Block for TestBlock {
+        fn ptr(&self) -> BlockPtr {
+            self.ptr.clone()
+        }
+
+        fn parent_ptr(&self) -> Option<BlockPtr> {
+            Some(self.parent.clone())
+        }
+    }
+
+    fn ptr(number: i32, fork: u8) -> BlockPtr {
+        BlockPtr::new(BlockHash::from(vec![number as u8, fork]), number)
+    }
+
+    fn block(number: i32, fork: u8, parent_fork: u8) -> TestBlock {
+        TestBlock {
+            ptr: ptr(number, fork),
+            parent: ptr(number - 1, parent_fork),
+        }
+    }
+
+    fn numbers(blocks: Vec<TestBlock>) -> Vec<i32> {
+        blocks.iter().map(|block| block.number()).collect()
+    }
+
+    #[test]
+    fn descendants_stop_at_the_first_unlinked_block() {
+        let start = ptr(10, 0);
+
+        let blocks = vec![block(11, 0, 0), block(12, 0, 0), block(13, 0, 0)];
+        assert_eq!(
+            vec![11, 12, 13],
+            numbers(linked_descendants(&start, blocks))
+        );
+
+        // Block 13 is on a different fork than block 12
+        let blocks = vec![block(11, 0, 0), block(12, 0, 0), block(13, 1, 1)];
+        assert_eq!(vec![11, 12], numbers(linked_descendants(&start, blocks)));
+
+        // Block 12 comes after a fork, and block 13 is its child; neither
+        // may be processed since block 12 is not a child of block 11
+        let blocks = vec![block(11, 0, 0), block(12, 1, 1), block(13, 1, 1)];
+        assert_eq!(vec![11], numbers(linked_descendants(&start, blocks)));
+
+        let blocks = vec![block(11, 1, 1)];
+        assert!(linked_descendants(&start, blocks).is_empty());
+    }
+}
@@ -113,6 +113,15 @@ pub struct EthereumCall {
     pub block_hash: H256,
     pub transaction_hash: Option<H256>,
     pub transaction_index: u64,
+    /// The position of the call in the call tree of its transaction;
+    /// comparing these lexicographically orders calls by execution. For
+    /// RPC traces this is the trace address, for Firehose blocks it is the
+    /// index of the call in the transaction.
+    pub trace_address: Vec<usize>,
+    /// Whether this is the creation of a contract. For creations, `to` is
+    /// the address of the new contract, `input` its init code and `output`
+    /// its runtime code.
+    pub creation: bool,
 }
 
 impl EthereumCall {
@@ -122,33 +131,49 @@ impl EthereumCall {
         if trace.error.is_some() {
             return None;
         }
-        // We are only interested in traces from CALLs
-        let call = match &trace.action {
-            // Contract to contract value transfers compile to the CALL opcode
-            // and have no input. Call handlers are for triggering on explicit method calls right now.
-            Action::Call(call) if call.input.0.len() >= 4 => call,
-            _ => return None,
-        };
-        let (output, gas_used) = match &trace.result {
-            Some(Res::Call(result)) => (result.output.clone(), result.gas_used),
-            _ => return None,
-        };
-
         // The only traces without transactions are those from Parity block reward contracts, we
         // don't support triggering on that.
         let transaction_index = trace.transaction_position? as u64;
 
+        // We are only interested in traces from CALLs and contract creations
+        let (from, to, value, input, output, gas_used, creation) =
+            match (&trace.action, &trace.result) {
+                // Contract to contract value transfers compile to the CALL opcode
+                // and have no input. Call handlers are for triggering on explicit method calls right now.
+                (Action::Call(call), Some(Res::Call(result))) if call.input.0.len() >= 4 => (
+                    call.from,
+                    call.to,
+                    call.value,
+                    call.input.clone(),
+                    result.output.clone(),
+                    result.gas_used,
+                    false,
+                ),
+                (Action::Create(create), Some(Res::Create(result))) => (
+                    create.from,
+                    result.address,
+                    create.value,
+                    create.init.clone(),
+                    result.code.clone(),
+                    result.gas_used,
+                    true,
+                ),
+                _ => return None,
+            };
+
         Some(EthereumCall {
-            from: call.from,
-            to: call.to,
-            value: call.value,
+            from,
+            to,
+            value,
             gas_used,
-            input: call.input.clone(),
+            input,
             output,
             block_number: trace.block_number as BlockNumber,
             block_hash: trace.block_hash,
             transaction_hash: trace.transaction_hash,
             transaction_index,
+            trace_address: trace.trace_address.clone(),
+            creation,
         })
     }
 }
@@ -3,16 +3,25 @@ use std::time::Duration;
 
 use async_trait::async_trait;
 use futures03::prelude::Stream;
+use lazy_static::lazy_static;
 use reqwest::Client;
 use serde_json::Value;
 use slog::{debug, Logger};
 use thiserror::Error;
 
 use crate::cheap_clone::CheapClone;
+use crate::data::subgraph::schema::ManifestFile;
 use crate::data::subgraph::Link;
 use crate::data_source::offchain::Base64;
+use crate::env::ENV_VARS;
+use crate::ipfs_dag::{cat_verified, verify_block};
 use crate::prelude::Error;
+use crate::util::lfu_cache::LfuCache;
+use cid::Cid;
+use std::collections::BTreeMap;
 use std::fmt::Debug;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 
 /// The values that `json_stream` returns. The struct contains the deserialized
 /// JSON value from the input stream, together with the line number from which
@@ -47,6 +56,99 @@ pub trait LinkResolver: Send + Sync + 'static + Debug {
     async fn json_stream(&self, logger: &Logger, link: &Link) -> Result<JsonValueStream, Error>;
 }
 
+lazy_static! {
+    /// The files that `VerifyingLinkResolver` read and verified, keyed by
+    /// their link. The contents of a CID never change, and all resolvers
+    /// therefore share them
+    static ref VERIFIED_FILES: Mutex<LfuCache<String, Vec<u8>>> = Mutex::new(LfuCache::new());
+}
+
+/// A `LinkResolver` that checks that the contents of every file it
+/// fetches hash to the CID in the file's link, so that a misbehaving IPFS
+/// node or gateway can not substitute different contents for a file. It
+/// remembers which files it fetched and how large they were
+#[derive(Debug, Clone)]
+pub struct VerifyingLinkResolver {
+    inner: Arc<dyn LinkResolver>,
+    files: Arc<Mutex<BTreeMap<String, u64>>>,
+}
+
+impl VerifyingLinkResolver {
+    pub fn new(inner: Arc<dyn LinkResolver>) -> Self {
+        Self {
+            inner,
+            files: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    /// All files that were fetched with `cat` so far, ordered by their link
+    pub fn files(&self) -> Vec<ManifestFile> {
+        self.files
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(link, size)| ManifestFile {
+                link: link.clone(),
+                size: *size,
+            })
+            .collect()
+    }
+
+    fn wrap(&self, inner: Box<dyn LinkResolver>) -> Box<dyn LinkResolver> {
+        Box::new(Self {
+            inner: inner.into(),
+            files: self.files.cheap_clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl LinkResolver for VerifyingLinkResolver {
+    fn with_timeout(&self, timeout: Duration) -> Box<dyn LinkResolver> {
+        self.wrap(self.inner.with_timeout(timeout))
+    }
+
+    fn with_retries(&self) -> Box<dyn LinkResolver> {
+        self.wrap(self.inner.with_retries())
+    }
+
+    async fn cat(&self, logger: &Logger, link: &Link) -> Result<Vec<u8>, Error> {
+        let cached = VERIFIED_FILES.lock().unwrap().get(&link.link).cloned();
+        let data = match cached {
+            Some(data) => data,
+            None => {
+                let max_size = ENV_VARS.mappings.max_ipfs_file_bytes;
+                let data = cat_verified(self.inner.as_ref(), logger, link, max_size).await?;
+                // Only cache files that are not too large, and keep the
+                // cache about as large as that of the `IpfsResolver`
+                let max_file_size = ENV_VARS.mappings.max_ipfs_cache_file_size;
+                if data.len() <= max_file_size {
+                    let mut cache = VERIFIED_FILES.lock().unwrap();
+                    cache.insert(link.link.clone(), data.clone());
+                    cache.evict(ENV_VARS.mappings.max_ipfs_cache_size as usize * max_file_size);
+                }
+                data
+            }
+        };
+        self.files
+            .lock()
+            .unwrap()
+            .insert(link.link.clone(), data.len() as u64);
+        Ok(data)
+    }
+
+    async fn get_block(&self, logger: &Logger, link: &Link) -> Result<Vec<u8>, Error> {
+        let cid = Cid::from_str(link.link.trim_start_matches("/ipfs/"))?;
+        let data = self.inner.get_block(logger, link).await?;
+        verify_block(&cid, &data)?;
+        Ok(data)
+    }
+
+    async fn json_stream(&self, logger: &Logger, link: &Link) -> Result<JsonValueStream, Error> {
+        self.inner.json_stream(logger, link).await
+    }
+}
+
 #[derive(Debug)]
 pub struct ArweaveClient {
     base_url: url::Url,
@@ -6,3 +6,6 @@ pub mod subscription;
 
 /// Component for the index node server.
 pub mod index_node;
+
+/// Readiness state of the node for health probes.
+pub mod readiness;
@@ -0,0 +1,36 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::endpoint::EndpointMetrics;
+
+/// Node-wide state that the `/ready` endpoint reports on, in addition to
+/// store connectivity. The node marks itself initialized once it has
+/// started all its servers; before that, it should not receive traffic.
+#[derive(Debug)]
+pub struct Readiness {
+    initialized: AtomicBool,
+    endpoint_metrics: Arc<EndpointMetrics>,
+}
+
+impl Readiness {
+    pub fn new(endpoint_metrics: Arc<EndpointMetrics>) -> Self {
+        Readiness {
+            initialized: AtomicBool::new(false),
+            endpoint_metrics,
+        }
+    }
+
+    pub fn mark_initialized(&self) {
+        self.initialized.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.initialized.load(Ordering::SeqCst)
+    }
+
+    /// Whether at least one of the configured providers is usable; see
+    /// [`EndpointMetrics::has_healthy_provider`]
+    pub fn providers_healthy(&self) -> bool {
+        self.endpoint_metrics.has_healthy_provider()
+    }
+}
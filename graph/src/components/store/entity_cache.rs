@@ -1,14 +1,18 @@
 use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt::{self, Debug};
+use std::str::FromStr;
 use std::sync::Arc;
 
 use crate::components::store::write::EntityModification;
 use crate::components::store::{self as s, Entity, EntityOperation};
-use crate::data::store::{EntityValidationError, Id, IdType, IntoEntityIterator};
+use crate::data::store::{EntityValidationError, Id, IdType, IntoEntityIterator, Value};
+use crate::data::value::Word;
+use crate::data_source::CausalityRegion;
 use crate::prelude::ENV_VARS;
-use crate::schema::{EntityKey, InputSchema};
+use crate::schema::{EntityKey, EntityType, InputSchema};
 use crate::util::intern::Error as InternError;
 use crate::util::lfu_cache::{EvictStats, LfuCache};
 
@@ -22,6 +26,62 @@ pub enum GetScope {
     InBlock,
 }
 
+/// How the `EntityCache` reacts when a handler sets a field of an entity
+/// to a different value than an earlier handler in the same block did,
+/// without reading the entity first. The result of such writes depends on
+/// the order in which the handlers run, which is a frequent source of
+/// nondeterminism bugs in mappings
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WriteConflictMode {
+    /// Do not track writes at all
+    Off,
+    /// Log a warning for each conflicting write
+    Warn,
+    /// Fail the handler that caused the conflict with a non-deterministic
+    /// error. Since the mode is a setting of the node, the failure must
+    /// not become part of the state of the deployment
+    Strict,
+}
+
+impl FromStr for WriteConflictMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(WriteConflictMode::Off),
+            "warn" => Ok(WriteConflictMode::Warn),
+            "strict" => Ok(WriteConflictMode::Strict),
+            _ => Err(format!("invalid write conflict mode: {:?}", s)),
+        }
+    }
+}
+
+/// A field that the currently executing handler set to a different value
+/// than the handler `previous_handler` did earlier in the block
+#[derive(Clone, Debug, PartialEq)]
+pub struct WriteConflict {
+    pub key: EntityKey,
+    pub field: Word,
+    pub handler: Word,
+    pub previous_handler: Word,
+}
+
+impl fmt::Display for WriteConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "handler `{}` overwrote field `{}` of {}[{}] that handler `{}` set \
+             earlier in the same block without reading the entity first; \
+             the resulting value depends on the order in which handlers run",
+            self.handler,
+            self.field,
+            self.key.entity_type,
+            self.key.entity_id,
+            self.previous_handler
+        )
+    }
+}
+
 /// A representation of entity operations that can be accumulated.
 #[derive(Debug, Clone)]
 enum EntityOp {
@@ -61,6 +121,49 @@ impl EntityOp {
     }
 }
 
+/// The accumulated change to one entity in a form that can be saved
+/// outside of the `EntityCache`, e.g., on disk, and be put back with
+/// `EntityCache::restore_updates`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedEntityOp {
+    entity_type: String,
+    entity_id: Value,
+    causality_region: CausalityRegion,
+    op: SavedOp,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SavedOp {
+    Remove,
+    Update(Vec<(String, Value)>),
+    Overwrite(Vec<(String, Value)>),
+}
+
+impl SavedEntityOp {
+    fn new(key: &EntityKey, op: &EntityOp) -> Self {
+        let data = |entity: &Entity| {
+            entity
+                .clone()
+                .sorted()
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect()
+        };
+        let op = match op {
+            EntityOp::Remove => SavedOp::Remove,
+            EntityOp::Update(entity) => SavedOp::Update(data(entity)),
+            EntityOp::Overwrite(entity) => SavedOp::Overwrite(data(entity)),
+        };
+        SavedEntityOp {
+            entity_type: key.entity_type.to_string(),
+            entity_id: key.entity_id.clone().into(),
+            causality_region: key.causality_region,
+            op,
+        }
+    }
+}
+
 /// A cache for entities from the store that provides the basic functionality
 /// needed for the store interactions in the host exports. This struct tracks
 /// how entities are modified, and caches all entities looked up from the
@@ -85,6 +188,27 @@ pub struct EntityCache {
     // Marks whether updates should go in `handler_updates`.
     in_handler: bool,
 
+    /// Whether writes are tracked to detect conflicts between handlers;
+    /// the remaining fields are only maintained if this is not `Off`
+    write_conflict_mode: WriteConflictMode,
+
+    /// The name of the currently executing handler
+    handler: Option<Word>,
+
+    /// The handler that last changed each entity during this block
+    writers: HashMap<EntityKey, Word>,
+
+    /// The entities that the currently executing handler has read
+    handler_reads: HashSet<EntityKey>,
+
+    /// The types of all entities that were read or changed through this
+    /// cache; only maintained after `track_entity_types` was called
+    entity_types: Option<HashSet<EntityType>>,
+
+    /// The largest timestamps of timeseries in the store, i.e., before
+    /// this block, as far as they were looked up
+    max_timestamps: HashMap<EntityType, Option<i64>>,
+
     /// The store is only used to read entities.
     pub store: Arc<dyn s::ReadStore>,
 
@@ -120,6 +244,12 @@ impl EntityCache {
             updates: HashMap::new(),
             handler_updates: HashMap::new(),
             in_handler: false,
+            write_conflict_mode: ENV_VARS.mappings.entity_write_conflicts,
+            handler: None,
+            writers: HashMap::new(),
+            handler_reads: HashSet::new(),
+            entity_types: None,
+            max_timestamps: HashMap::new(),
             schema: store.input_schema(),
             store,
             seq: 0,
@@ -143,21 +273,93 @@ impl EntityCache {
             updates: HashMap::new(),
             handler_updates: HashMap::new(),
             in_handler: false,
+            write_conflict_mode: ENV_VARS.mappings.entity_write_conflicts,
+            handler: None,
+            writers: HashMap::new(),
+            handler_reads: HashSet::new(),
+            entity_types: None,
+            max_timestamps: HashMap::new(),
             schema: store.input_schema(),
             store,
             seq: 0,
         }
     }
 
-    pub(crate) fn enter_handler(&mut self) {
+    /// Change how conflicting writes from different handlers are treated.
+    /// The default is taken from `GRAPH_ENTITY_WRITE_CONFLICTS`
+    pub fn set_write_conflict_mode(&mut self, mode: WriteConflictMode) {
+        self.write_conflict_mode = mode;
+    }
+
+    pub fn write_conflict_mode(&self) -> WriteConflictMode {
+        self.write_conflict_mode
+    }
+
+    /// Start keeping track of the types of the entities that are read or
+    /// changed through this cache, see `entity_types`
+    pub fn track_entity_types(&mut self) {
+        self.entity_types.get_or_insert_with(HashSet::new);
+    }
+
+    /// The types of the entities that were read or changed since
+    /// `track_entity_types` was called, or `None` if it was not called
+    pub fn entity_types(&self) -> Option<&HashSet<EntityType>> {
+        self.entity_types.as_ref()
+    }
+
+    fn touch(&mut self, entity_type: &EntityType) {
+        if let Some(entity_types) = &mut self.entity_types {
+            if !entity_types.contains(entity_type) {
+                entity_types.insert(entity_type.clone());
+            }
+        }
+    }
+
+    /// The largest `timestamp` of the data points of the timeseries
+    /// `entity_type` that were written before the current block
+    pub fn max_timestamp(&mut self, entity_type: &EntityType) -> Result<Option<i64>, StoreError> {
+        if let Some(timestamp) = self.max_timestamps.get(entity_type) {
+            return Ok(*timestamp);
+        }
+        let timestamp = self.store.max_timestamp(entity_type)?;
+        self.max_timestamps.insert(entity_type.clone(), timestamp);
+        Ok(timestamp)
+    }
+
+    /// Whether `generate_id` has been called on this cache
+    pub fn generated_ids(&self) -> bool {
+        self.seq > 0
+    }
+
+    /// Discard all changes and return the entities that were loaded from
+    /// the store, so that they can be used for a new cache
+    pub fn into_current(self) -> LfuCache<EntityKey, Option<Entity>> {
+        self.current
+    }
+
+    fn tracks_writes(&self) -> bool {
+        self.write_conflict_mode != WriteConflictMode::Off
+    }
+
+    pub(crate) fn enter_handler(&mut self, handler: &str) {
         assert!(!self.in_handler);
         self.in_handler = true;
+        if self.tracks_writes() {
+            self.handler = Some(Word::from(handler));
+        }
     }
 
     pub(crate) fn exit_handler(&mut self) {
         assert!(self.in_handler);
         self.in_handler = false;
 
+        if let Some(handler) = self.handler.take() {
+            for key in self.handler_updates.keys() {
+                self.writers.insert(key.clone(), handler.clone());
+            }
+            self.handler_reads.clear();
+        }
+
         // Apply all handler updates to the main `updates`.
         let handler_updates = Vec::from_iter(self.handler_updates.drain());
         for (key, op) in handler_updates {
@@ -168,14 +370,75 @@ impl EntityCache {
     pub(crate) fn exit_handler_and_discard_changes(&mut self) {
         assert!(self.in_handler);
         self.in_handler = false;
+        self.handler = None;
+        self.handler_reads.clear();
         self.handler_updates.clear();
     }
 
+    /// Find the fields that the currently executing handler set without
+    /// reading the entity first, and that a different handler set to a
+    /// different value earlier in this block. Always returns an empty list
+    /// unless write conflict detection is turned on
+    pub fn write_conflicts(&self) -> Vec<WriteConflict> {
+        let handler = match (&self.handler, self.in_handler) {
+            (Some(handler), true) => handler,
+            _ => return vec![],
+        };
+
+        let mut conflicts = Vec::new();
+        for (key, op) in &self.handler_updates {
+            if self.handler_reads.contains(key) {
+                continue;
+            }
+            let previous_handler = match self.writers.get(key) {
+                Some(previous_handler) if previous_handler != handler => previous_handler,
+                _ => continue,
+            };
+            let (previous, current) = match (self.updates.get(key), op) {
+                (
+                    Some(EntityOp::Update(previous) | EntityOp::Overwrite(previous)),
+                    EntityOp::Update(current) | EntityOp::Overwrite(current),
+                ) => (previous, current),
+                _ => continue,
+            };
+            for (field, value) in current.sorted_ref() {
+                match previous.get(field) {
+                    Some(prev_value) if prev_value != value => {
+                        conflicts.push(WriteConflict {
+                            key: key.clone(),
+                            field: Word::from(field),
+                            handler: handler.clone(),
+                            previous_handler: previous_handler.clone(),
+                        });
+                    }
+                    _ => {}
+                }
+            }
+        }
+        // Make the order of conflicts independent of the order of the
+        // `handler_updates` map
+        conflicts.sort_by(|a, b| (&a.key, &a.field).cmp(&(&b.key, &b.field)));
+        conflicts
+    }
+
     pub fn get(
         &mut self,
         key: &EntityKey,
         scope: GetScope,
     ) -> Result<Option<Cow<Entity>>, StoreError> {
+        if self.in_handler && self.tracks_writes() {
+            self.handler_reads.insert(key.clone());
+        }
+        self.lookup(key, scope)
+    }
+
+    fn lookup(
+        &mut self,
+        key: &EntityKey,
+        scope: GetScope,
+    ) -> Result<Option<Cow<Entity>>, StoreError> {
+        self.touch(&key.entity_type);
+
         // Get the current entity, apply any updates from `updates`, then
         // from `handler_updates`.
         let mut entity: Option<Cow<Entity>> = match scope {
@@ -208,11 +471,46 @@ impl EntityCache {
         Ok(entity)
     }
 
+    /// Get the entities for all `keys` as they are visible to the current
+    /// handler, loading the ones that are not in the cache yet from the
+    /// store with a single query. Entities that do not exist are omitted
+    /// from the result; the result is in the same order as `keys`
+    pub fn get_many(&mut self, keys: &[EntityKey]) -> Result<Vec<Entity>, StoreError> {
+        self.load_missing(keys)?;
+
+        let mut entities = Vec::with_capacity(keys.len());
+        for key in keys {
+            if let Some(entity) = self.get(key, GetScope::Store)? {
+                entities.push(entity.into_owned());
+            }
+        }
+        Ok(entities)
+    }
+
+    /// Load all `keys` that are not in `self.current` yet from the store
+    /// with a single query, remembering the ones that do not exist, too
+    fn load_missing(&mut self, keys: &[EntityKey]) -> Result<(), StoreError> {
+        let missing: BTreeSet<_> = keys
+            .iter()
+            .filter(|key| !self.current.contains_key(key))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            let mut found = self.store.get_many(missing.clone())?;
+            for key in missing {
+                let entity = found.remove(&key);
+                self.current.insert(key, entity);
+            }
+        }
+        Ok(())
+    }
+
     pub fn load_related(
         &mut self,
         eref: &LoadRelatedRequest,
     ) -> Result<Vec<Entity>, anyhow::Error> {
         let (entity_type, field) = self.schema.get_field_related(eref)?;
+        self.touch(&entity_type);
 
         let query = DerivedEntityQuery {
             entity_type,
@@ -334,7 +632,11 @@ impl EntityCache {
     /// with existing data. The entity will be validated against the
     /// subgraph schema, and any errors will result in an `Err` being
     /// returned.
-    pub fn set(&mut self, key: EntityKey, entity: Entity) -> Result<(), anyhow::Error> {
+    pub fn set(&mut self, key: EntityKey, mut entity: Entity) -> Result<(), anyhow::Error> {
+        self.check_interface_uniqueness(&key)?;
+
+        normalize_join_table_lists(&key, &mut entity)?;
+
         // check the validate for derived fields
         let is_valid = entity.validate(&key).is_ok();
 
@@ -344,7 +646,7 @@ impl EntityCache {
         // lookup in the database and check again with an entity that merges
         // the existing entity with the changes
         if !is_valid {
-            let entity = self.get(&key, GetScope::Store)?.ok_or_else(|| {
+            let entity = self.lookup(&key, GetScope::Store)?.ok_or_else(|| {
                 anyhow!(
                     "Failed to read entity {}[{}] back from cache",
                     key.entity_type,
@@ -357,6 +659,44 @@ impl EntityCache {
         Ok(())
     }
 
+    /// Check that creating the entity for `key` does not reuse the id of
+    /// an entity of another type that shares an interface with it. Queries
+    /// for the interface could not tell these entities apart. Since this
+    /// only depends on the entities that exist at this block, a conflict is
+    /// a deterministic error
+    fn check_interface_uniqueness(&mut self, key: &EntityKey) -> Result<(), StoreError> {
+        let others = key.entity_type.share_interfaces()?;
+        if others.is_empty() {
+            return Ok(());
+        }
+
+        // Load the entity and all the entities it could conflict with in
+        // one query; entities that do not exist are remembered in the
+        // cache so that later checks for the same id are free
+        let keys: Vec<_> = std::iter::once(key.clone())
+            .chain(
+                others
+                    .iter()
+                    .map(|other| other.key_in(key.entity_id.clone(), key.causality_region)),
+            )
+            .collect();
+        self.load_missing(&keys)?;
+
+        if self.lookup(key, GetScope::Store)?.is_some() {
+            return Ok(());
+        }
+        for (other, other_key) in others.iter().zip(&keys[1..]) {
+            if self.lookup(other_key, GetScope::Store)?.is_some() {
+                return Err(StoreError::ConflictingId(
+                    key.entity_type.to_string(),
+                    key.entity_id.to_string(),
+                    other.to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
     pub fn append(&mut self, operations: Vec<EntityOperation>) {
         assert!(!self.in_handler);
 
@@ -374,6 +714,7 @@ impl EntityCache {
 
     fn entity_op(&mut self, key: EntityKey, op: EntityOp) {
         use std::collections::hash_map::Entry;
+        self.touch(&key.entity_type);
         let updates = match self.in_handler {
             true => &mut self.handler_updates,
             false => &mut self.updates,
@@ -387,10 +728,50 @@ impl EntityCache {
         }
     }
 
+    /// Return the changes that have been made so far in a form that can be
+    /// saved and later be put back into a cache with `restore_updates`
+    pub fn saved_updates(&self) -> Vec<SavedEntityOp> {
+        assert!(!self.in_handler);
+
+        self.updates
+            .iter()
+            .map(|(key, op)| SavedEntityOp::new(key, op))
+            .collect()
+    }
+
+    /// Apply changes that were saved with `saved_updates` to this cache as
+    /// if they had been made with `set` and `remove`
+    pub fn restore_updates(&mut self, saved: Vec<SavedEntityOp>) -> Result<(), anyhow::Error> {
+        assert!(!self.in_handler);
+
+        for saved in saved {
+            let entity_type = self.schema.entity_type(saved.entity_type.as_str())?;
+            let id = Id::try_from(saved.entity_id)?;
+            let key = entity_type.key_in(id, saved.causality_region);
+            let make = |data: Vec<(String, Value)>| {
+                self.make_entity(
+                    data.into_iter()
+                        .map(|(name, value)| (Word::from(name), value)),
+                )
+            };
+            let op = match saved.op {
+                SavedOp::Remove => EntityOp::Remove,
+                SavedOp::Update(data) => EntityOp::Update(make(data)?),
+                SavedOp::Overwrite(data) => EntityOp::Overwrite(make(data)?),
+            };
+            self.entity_op(key, op);
+        }
+        Ok(())
+    }
+
     pub(crate) fn extend(&mut self, other: EntityCache) {
         assert!(!other.in_handler);
 
         self.current.extend(other.current);
+        self.writers.extend(other.writers);
+        if let (Some(entity_types), Some(other)) = (&mut self.entity_types, other.entity_types) {
+            entity_types.extend(other);
+        }
         for (key, op) in other.updates {
             self.entity_op(key, op);
         }
@@ -506,3 +887,42 @@ impl EntityCache {
         })
     }
 }
+
+/// The lists of `@joinTable` fields behave like sets, and the store reads
+/// them back ordered by id. Sort and dedupe them when they are set so that
+/// mappings see the same list no matter whether it comes from the cache or
+/// from the store
+fn normalize_join_table_lists(key: &EntityKey, entity: &mut Entity) -> Result<(), StoreError> {
+    let object_type = match key.entity_type.object_type() {
+        Some(object_type) => object_type,
+        None => return Ok(()),
+    };
+
+    let mut lists = Vec::new();
+    for field in object_type
+        .fields
+        .iter()
+        .filter(|field| field.is_join_table)
+    {
+        let values = match entity.get(field.name.as_str()) {
+            Some(Value::List(values)) => values,
+            _ => continue,
+        };
+        // Values that are not ids are reported by entity validation
+        let mut ids = match values
+            .iter()
+            .cloned()
+            .map(Id::try_from)
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(ids) => ids,
+            Err(_) => continue,
+        };
+        ids.sort();
+        ids.dedup();
+        let values = ids.into_iter().map(Value::from).collect();
+        lists.push((field.name.clone(), Value::List(values)));
+    }
+    entity.merge_iter(lists)?;
+    Ok(())
+}
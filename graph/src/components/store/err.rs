@@ -38,6 +38,16 @@ pub enum StoreError {
          there are most likely two (or more) nodes indexing this subgraph"
     )]
     DuplicateBlockProcessing(DeploymentHash, BlockNumber),
+    #[error(
+        "subgraph `{0}` is already being written by another graph-node instance; \
+         there are most likely two (or more) nodes indexing this subgraph"
+    )]
+    DeploymentLocked(DeploymentHash),
+    #[error(
+        "lost the writer lock for subgraph `{0}`; another graph-node instance \
+         might be writing to it"
+    )]
+    WriterLockLost(DeploymentHash),
     /// An internal error where we expected the application logic to enforce
     /// some constraint, e.g., that subgraph names are unique, but found that
     /// constraint to not hold
@@ -107,6 +117,8 @@ impl Clone for StoreError {
             Self::DuplicateBlockProcessing(arg0, arg1) => {
                 Self::DuplicateBlockProcessing(arg0.clone(), arg1.clone())
             }
+            Self::DeploymentLocked(arg0) => Self::DeploymentLocked(arg0.clone()),
+            Self::WriterLockLost(arg0) => Self::WriterLockLost(arg0.clone()),
             Self::ConstraintViolation(arg0) => Self::ConstraintViolation(arg0.clone()),
             Self::DeploymentNotFound(arg0) => Self::DeploymentNotFound(arg0.clone()),
             Self::UnknownShard(arg0) => Self::UnknownShard(arg0.clone()),
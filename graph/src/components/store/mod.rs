@@ -2,8 +2,11 @@ mod entity_cache;
 mod err;
 mod traits;
 pub mod write;
+mod write_stats;
 
-pub use entity_cache::{EntityCache, GetScope, ModificationsAndCache};
+pub use entity_cache::{
+    EntityCache, GetScope, ModificationsAndCache, SavedEntityOp, WriteConflict, WriteConflictMode,
+};
 use futures03::future::{FutureExt, TryFutureExt};
 use slog::{trace, Logger};
 
@@ -13,6 +16,7 @@ use itertools::Itertools;
 use strum_macros::Display;
 pub use traits::*;
 pub use write::Batch;
+pub use write_stats::{DeploymentWriteStats, HotEntity, WriteStats, WRITE_STATS};
 
 use futures::stream::poll_fn;
 use futures::{Async, Poll, Stream};
@@ -23,12 +27,13 @@ use std::fmt;
 use std::fmt::Display;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, RwLock};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crate::blockchain::{Block, BlockHash, BlockPtr};
 use crate::cheap_clone::CheapClone;
 use crate::components::store::write::EntityModification;
 use crate::constraint_violation;
+use crate::data::query::RunningQuery;
 use crate::data::store::scalar::Bytes;
 use crate::data::store::{Id, IdList, Value};
 use crate::data::value::Word;
@@ -321,6 +326,9 @@ pub enum ParentLink {
     /// vector contains the id of the child of the parent with id
     /// `EntityWindow.ids[i]`
     Scalar(IdList),
+    /// The parent stores the ids of its children in the join table for
+    /// the parent's field with the given name
+    Join(Word),
 }
 
 /// How many children a parent can have when the child stores
@@ -448,6 +456,15 @@ pub struct EntityQuery {
 
     pub trace: bool,
 
+    /// When the query has to be done. The store limits how long the SQL
+    /// statements for the query may run so that the database stops
+    /// working on them once the deadline has passed
+    pub deadline: Option<Instant>,
+
+    /// The GraphQL query this query is part of, if any. Cancelling it also
+    /// cancels the SQL statement that runs this query
+    pub running: Option<Arc<RunningQuery>>,
+
     _force_use_of_new: (),
 }
 
@@ -467,6 +484,8 @@ impl EntityQuery {
             logger: None,
             query_id: None,
             trace: false,
+            deadline: None,
+            running: None,
             _force_use_of_new: (),
         }
     }
@@ -496,6 +515,11 @@ impl EntityQuery {
         self
     }
 
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
     pub fn simplify(mut self) -> Self {
         // If there is one window, with one id, in a direct relation to the
         // entities, we can simplify the query by changing the filter and
@@ -1035,6 +1059,10 @@ impl ReadStore for EmptyStore {
         Ok(BTreeMap::new())
     }
 
+    fn max_timestamp(&self, _entity_type: &EntityType) -> Result<Option<i64>, StoreError> {
+        Ok(None)
+    }
+
     fn input_schema(&self) -> InputSchema {
         self.schema.cheap_clone()
     }
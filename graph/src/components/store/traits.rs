@@ -1,5 +1,6 @@
-use anyhow::Error;
+use anyhow::{anyhow, Error};
 use async_trait::async_trait;
+use std::collections::HashMap;
 use web3::types::{Address, H256};
 
 use super::*;
@@ -15,6 +16,7 @@ use crate::data::subgraph::{status, DeploymentFeatures};
 use crate::data::{query::QueryTarget, subgraph::schema::*};
 use crate::prelude::{DeploymentState, NodeId, QueryExecutionError, SubgraphName};
 use crate::schema::{ApiSchema, InputSchema};
+use crate::util::jobs::JobStatus;
 
 pub trait SubscriptionManager: Send + Sync + 'static {
     /// Subscribe to changes for specific subgraphs and entities.
@@ -106,6 +108,40 @@ pub trait SubgraphStore: Send + Sync + 'static {
         node_id: &NodeId,
     ) -> Result<(), StoreError>;
 
+    /// Pause indexing of `deployment`. The deployment stays assigned to its
+    /// node, and is marked as paused so that it stays paused when nodes
+    /// restart. If there is no assignment for the deployment, report an
+    /// error.
+    fn pause_subgraph(&self, deployment: &DeploymentLocator) -> Result<(), StoreError>;
+
+    /// Resume indexing of a paused `deployment` from the last block it
+    /// processed. If there is no assignment for the deployment, report an
+    /// error.
+    fn resume_subgraph(&self, deployment: &DeploymentLocator) -> Result<(), StoreError>;
+
+    /// Start pruning the history of `deployment` in the background. If
+    /// `history_blocks` is given, the deployment keeps that many blocks of
+    /// history from now on; otherwise, it is pruned to the amount of
+    /// history it is already configured to keep
+    fn prune_subgraph(
+        &self,
+        deployment: &DeploymentLocator,
+        history_blocks: Option<BlockNumber>,
+    ) -> Result<(), StoreError>;
+
+    /// Revert all changes that `deployment` made after `block_ptr`, so
+    /// that it continues indexing with the block after it. Fails with
+    /// `StoreError::DeploymentLocked` while a node is indexing the
+    /// deployment
+    fn rewind_subgraph(
+        &self,
+        deployment: &DeploymentLocator,
+        block_ptr: BlockPtr,
+    ) -> Result<(), StoreError>;
+
+    /// The name of the network that `deployment` indexes
+    fn network_name(&self, deployment: &DeploymentLocator) -> Result<String, StoreError>;
+
     fn assigned_node(&self, deployment: &DeploymentLocator) -> Result<Option<NodeId>, StoreError>;
 
     /// Returns Option<(node_id,is_paused)> where `node_id` is the node that
@@ -122,6 +158,14 @@ pub trait SubgraphStore: Send + Sync + 'static {
     /// Returns assignments that are not paused
     fn active_assignments(&self, node: &NodeId) -> Result<Vec<DeploymentLocator>, StoreError>;
 
+    /// Returns the deployments that are not paused and for which `node`
+    /// should keep a standby instance
+    fn standby_assignments(&self, node: &NodeId) -> Result<Vec<DeploymentLocator>, StoreError>;
+
+    /// Returns the node that keeps a standby instance of the deployment,
+    /// if there is one
+    fn standby_node(&self, deployment: &DeploymentLocator) -> Result<Option<NodeId>, StoreError>;
+
     /// Return `true` if a subgraph `name` exists, regardless of whether the
     /// subgraph has any deployments attached to it
     fn subgraph_exists(&self, name: &SubgraphName) -> Result<bool, StoreError>;
@@ -177,6 +221,32 @@ pub trait SubgraphStore: Send + Sync + 'static {
         manifest_idx_and_name: Arc<Vec<(u32, String)>>,
     ) -> Result<Arc<dyn WritableStore>, StoreError>;
 
+    /// Return a `WritableStore` for a standby instance of the deployment
+    /// that runs on `node`. The store reads the state of the deployment
+    /// like the one returned by `writable`, but refuses to write anything.
+    /// The standby instance starts writing once `take_over` on the
+    /// returned store has handed it a store that can write.
+    async fn standby_writable(
+        self: Arc<Self>,
+        logger: Logger,
+        deployment: DeploymentId,
+        manifest_idx_and_name: Arc<Vec<(u32, String)>>,
+        node: NodeId,
+    ) -> Result<Arc<dyn WritableStore>, StoreError>;
+
+    /// Return a `WritableStore` for replaying `block` of the deployment.
+    /// The store reads the state of the deployment as of the block before
+    /// `block` and refuses to write anything. It is an error if the
+    /// deployment has not processed `block` yet or if its history for
+    /// `block` has been pruned
+    async fn replay_writable(
+        self: Arc<Self>,
+        logger: Logger,
+        deployment: DeploymentId,
+        manifest_idx_and_name: Arc<Vec<(u32, String)>>,
+        block: BlockNumber,
+    ) -> Result<Arc<dyn WritableStore>, StoreError>;
+
     /// Initiate a graceful shutdown of the writable that a previous call to
     /// `writable` might have started
     async fn stop_subgraph(&self, deployment: &DeploymentLocator) -> Result<(), StoreError>;
@@ -187,6 +257,13 @@ pub trait SubgraphStore: Send + Sync + 'static {
     /// being set up
     async fn least_block_ptr(&self, id: &DeploymentHash) -> Result<Option<BlockPtr>, StoreError>;
 
+    /// How many blocks each of `deployments` is behind the head of its
+    /// chain. Deployments for which that is not known are left out
+    fn blocks_behind(
+        &self,
+        deployments: &[DeploymentLocator],
+    ) -> Result<HashMap<DeploymentId, BlockNumber>, StoreError>;
+
     async fn is_healthy(&self, id: &DeploymentHash) -> Result<bool, StoreError>;
 
     /// Find all deployment locators for the subgraph with the given hash.
@@ -225,6 +302,10 @@ pub trait ReadStore: Send + Sync + 'static {
         query_derived: &DerivedEntityQuery,
     ) -> Result<BTreeMap<EntityKey, Entity>, StoreError>;
 
+    /// The largest `timestamp` of the data points of the timeseries
+    /// `entity_type` as of the latest block, or `None` if there are none
+    fn max_timestamp(&self, entity_type: &EntityType) -> Result<Option<i64>, StoreError>;
+
     fn input_schema(&self) -> InputSchema;
 }
 
@@ -248,6 +329,10 @@ impl<T: ?Sized + ReadStore> ReadStore for Arc<T> {
         (**self).get_derived(entity_derived)
     }
 
+    fn max_timestamp(&self, entity_type: &EntityType) -> Result<Option<i64>, StoreError> {
+        (**self).max_timestamp(entity_type)
+    }
+
     fn input_schema(&self) -> InputSchema {
         (**self).input_schema()
     }
@@ -374,6 +459,14 @@ pub trait WritableStore: ReadStore + DeploymentCursorTracker {
     /// anymore, as it will continue to produce errors for any write
     /// requests, and instead, the returned `WritableStore` should be used.
     async fn restart(self: Arc<Self>) -> Result<Option<Arc<dyn WritableStore>>, StoreError>;
+
+    /// Wait until the standby instance that uses this store can take over
+    /// writing to the deployment, i.e., until no other node writes to it
+    /// anymore, and return the store that it should use from then on.
+    ///
+    /// This call returns `None` right away if `self` is not the store of
+    /// a standby instance.
+    async fn take_over(self: Arc<Self>) -> Result<Option<Arc<dyn WritableStore>>, StoreError>;
 }
 
 #[async_trait]
@@ -471,6 +564,18 @@ pub trait ChainStore: Send + Sync + 'static {
         offset: BlockNumber,
     ) -> Result<Option<serde_json::Value>, Error>;
 
+    /// Return the data of the blocks on the main chain with numbers from
+    /// `from` to `to`, in ascending order, as they were stored, or `None`
+    /// if the store does not have all of them. The main chain is the one
+    /// that ends in the current chain head; if the store can not connect
+    /// the block with number `to` to the chain head, the result is also
+    /// `None`
+    async fn cached_blocks_in_range(
+        self: Arc<Self>,
+        from: BlockNumber,
+        to: BlockNumber,
+    ) -> Result<Option<Vec<serde_json::Value>>, Error>;
+
     /// Remove old blocks from the cache we maintain in the database and
     /// return a pair containing the number of the oldest block retained
     /// and the number of blocks deleted.
@@ -497,6 +602,21 @@ pub trait ChainStore: Send + Sync + 'static {
         hash: &BlockHash,
     ) -> Result<Option<(String, BlockNumber, Option<u64>)>, StoreError>;
 
+    /// Check that the block with hash `ptr.hash` has number `ptr.number`.
+    /// Returns `false` if this chain store does not know that block
+    async fn check_block_ptr(&self, ptr: &BlockPtr) -> Result<bool, Error> {
+        match self.block_number(&ptr.hash).await? {
+            Some((_, number, _)) if number != ptr.number => Err(anyhow!(
+                "the block with hash {} has number {}, not {}",
+                ptr.hash,
+                number,
+                ptr.number
+            )),
+            Some(_) => Ok(true),
+            None => Ok(false),
+        }
+    }
+
     /// Tries to retrieve all transactions receipts for a given block.
     async fn transaction_receipts_in_block(
         &self,
@@ -521,6 +641,16 @@ pub trait EthereumCallCache: Send + Sync + 'static {
     /// update the last access time of the returned cached calls.
     fn get_calls_in_block(&self, block: BlockPtr) -> Result<Vec<CachedEthereumCall>, Error>;
 
+    /// Returns the cache entry for the provided Ethereum call, if present.
+    /// Like `get_calls_in_block`, this method does *not* update the last
+    /// access time of the cached call.
+    fn get_cached_call(
+        &self,
+        contract_address: ethabi::Address,
+        encoded_call: &[u8],
+        block: BlockPtr,
+    ) -> Result<Option<CachedEthereumCall>, Error>;
+
     /// Stores the provided Ethereum call in the cache.
     fn set_call(
         &self,
@@ -612,6 +742,18 @@ pub trait StatusStore: Send + Sync + 'static {
         deployment_hash: &str,
     ) -> Result<Vec<(String, String)>, StoreError>;
 
+    /// Returns (name, deployment hash, version) of the current and pending
+    /// versions of all subgraphs, where version is either `current` or
+    /// `pending`. Used by the GraphiQL deployment selector
+    fn subgraph_versions(&self) -> Result<Vec<(String, String, String)>, StoreError>;
+
+    /// Check that the store can reach its primary database. Used by the
+    /// `/ready` endpoint
+    fn check_connection(&self) -> Result<(), StoreError>;
+
+    /// The status of the background jobs of all nodes
+    fn job_statuses(&self) -> Result<Vec<JobStatus>, StoreError>;
+
     /// A value of None indicates that the table is not available. Re-deploying
     /// the subgraph fixes this. It is undesirable to force everything to
     /// re-sync from scratch, so existing deployments will continue without a
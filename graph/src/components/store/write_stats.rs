@@ -0,0 +1,275 @@
+//! Statistics about how often the deployments in this process write to
+//! each entity type and to individual entities.
+//!
+//! Writes are counted per entity type exactly. Writes to individual
+//! entities are sampled: only every `GRAPH_STORE_WRITE_STATS_SAMPLE_RATE`th
+//! write is counted, and only a limited number of entities is tracked per
+//! deployment; when that limit is reached, the entity with the fewest
+//! writes makes room for the new one and passes its count on to it. That
+//! way, entities that are written all the time, like a global entity that
+//! every handler updates, always show up among the hottest entities even
+//! though their counts are estimates. Statistics cover the writes since the
+//! deployment was started in this process and are not adjusted when blocks
+//! are reverted; they are forgotten when the deployment is stopped.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use lazy_static::lazy_static;
+
+use crate::data::store::Id;
+use crate::env::ENV_VARS;
+use crate::prelude::DeploymentHash;
+use crate::schema::EntityType;
+
+use super::write::EntityModification;
+
+/// How many entities to track per deployment
+const HOT_ENTITY_CAPACITY: usize = 1000;
+
+lazy_static! {
+    /// The write statistics of all deployments that write in this process
+    pub static ref WRITE_STATS: WriteStats = WriteStats::default();
+}
+
+#[derive(Default)]
+pub struct WriteStats {
+    deployments: Mutex<HashMap<DeploymentHash, Arc<DeploymentWriteStats>>>,
+}
+
+impl WriteStats {
+    /// The statistics for `deployment`, which are created if they do not
+    /// exist yet
+    pub fn for_deployment(&self, deployment: &DeploymentHash) -> Arc<DeploymentWriteStats> {
+        let mut deployments = self.deployments.lock().unwrap();
+        match deployments.get(deployment) {
+            Some(stats) => stats.clone(),
+            None => {
+                let stats = Arc::new(DeploymentWriteStats::new(
+                    ENV_VARS.store.write_stats_sample_rate,
+                ));
+                deployments.insert(deployment.clone(), stats.clone());
+                stats
+            }
+        }
+    }
+
+    /// The statistics for `deployment` if it has written anything in this
+    /// process
+    pub fn get(&self, deployment: &DeploymentHash) -> Option<Arc<DeploymentWriteStats>> {
+        self.deployments.lock().unwrap().get(deployment).cloned()
+    }
+
+    /// Forget the statistics for `deployment`, e.g., because it is not
+    /// indexed in this process anymore
+    pub fn remove(&self, deployment: &DeploymentHash) {
+        self.deployments.lock().unwrap().remove(deployment);
+    }
+}
+
+/// An entity that gets written a lot
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HotEntity {
+    pub entity_type: EntityType,
+    pub id: Id,
+    /// The estimated number of writes to the entity
+    pub writes: u64,
+}
+
+type EntityRef = (EntityType, Id);
+
+/// The sampled writes of the tracked entities. `coldest` contains the same
+/// entries as `writes`, ordered so that the entity with the fewest writes
+/// can be found without looking at all of them
+#[derive(Default)]
+struct Entities {
+    writes: HashMap<EntityRef, u64>,
+    coldest: BTreeSet<(u64, EntityRef)>,
+}
+
+impl Entities {
+    fn record(&mut self, entry: EntityRef) {
+        if let Some(writes) = self.writes.get_mut(&entry) {
+            self.coldest.remove(&(*writes, entry.clone()));
+            *writes += 1;
+            self.coldest.insert((*writes, entry));
+            return;
+        }
+        let mut writes = 1;
+        if self.writes.len() >= HOT_ENTITY_CAPACITY {
+            if let Some((coldest_writes, coldest)) = self.coldest.pop_first() {
+                self.writes.remove(&coldest);
+                writes += coldest_writes;
+            }
+        }
+        self.writes.insert(entry.clone(), writes);
+        self.coldest.insert((writes, entry));
+    }
+}
+
+pub struct DeploymentWriteStats {
+    sample_rate: u64,
+    /// The number of writes that were seen, used for sampling
+    seen: AtomicU64,
+    entity_types: Mutex<HashMap<EntityType, u64>>,
+    entities: Mutex<Entities>,
+}
+
+impl DeploymentWriteStats {
+    /// Create statistics that count every `sample_rate`th write to an
+    /// entity. A `sample_rate` of 0 turns off tracking of entities
+    pub fn new(sample_rate: usize) -> Self {
+        DeploymentWriteStats {
+            sample_rate: sample_rate as u64,
+            seen: AtomicU64::new(0),
+            entity_types: Mutex::new(HashMap::new()),
+            entities: Mutex::new(Entities::default()),
+        }
+    }
+
+    /// Count the writes in `mods`
+    pub fn record<'a>(&self, mods: impl IntoIterator<Item = &'a EntityModification>) {
+        let mut entity_types = self.entity_types.lock().unwrap();
+        let mut entities = self.entities.lock().unwrap();
+        for emod in mods {
+            let key = emod.key();
+            *entity_types.entry(key.entity_type.clone()).or_default() += 1;
+
+            if self.sample_rate == 0
+                || self.seen.fetch_add(1, Ordering::Relaxed) % self.sample_rate != 0
+            {
+                continue;
+            }
+            entities.record((key.entity_type.clone(), key.entity_id.clone()));
+        }
+    }
+
+    /// The number of writes to each entity type, the most written type
+    /// first
+    pub fn entity_types(&self) -> Vec<(EntityType, u64)> {
+        let mut types: Vec<_> = self
+            .entity_types
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(entity_type, writes)| (entity_type.clone(), *writes))
+            .collect();
+        types.sort_by(|(a, a_writes), (b, b_writes)| b_writes.cmp(a_writes).then(a.cmp(b)));
+        types
+    }
+
+    /// The `first` entities with the most writes, the hottest one first
+    pub fn hottest(&self, first: usize) -> Vec<HotEntity> {
+        let mut entities: Vec<_> = self
+            .entities
+            .lock()
+            .unwrap()
+            .writes
+            .iter()
+            .map(|((entity_type, id), writes)| HotEntity {
+                entity_type: entity_type.clone(),
+                id: id.clone(),
+                writes: writes * self.sample_rate,
+            })
+            .collect();
+        entities.sort_by(|a, b| {
+            b.writes
+                .cmp(&a.writes)
+                .then_with(|| a.entity_type.cmp(&b.entity_type))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        entities.truncate(first);
+        entities
+    }
+
+    pub fn sample_rate(&self) -> u64 {
+        self.sample_rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::store::write::EntityModification;
+    use crate::entity;
+    use crate::schema::InputSchema;
+
+    use super::*;
+
+    #[test]
+    fn finds_hot_entities() {
+        let schema = InputSchema::raw(
+            "type Bundle @entity { id: ID! } type Swap @entity { id: ID! }",
+            "hotEntities",
+        );
+        let bundle = schema.entity_type("Bundle").unwrap();
+        let swap = schema.entity_type("Swap").unwrap();
+
+        let stats = DeploymentWriteStats::new(1);
+        for block in 0..10 {
+            let id = format!("swap-{}", block);
+            let mods = vec![
+                EntityModification::Overwrite {
+                    key: bundle.parse_key("1").unwrap(),
+                    data: entity! { schema => id: "1" },
+                    block,
+                    end: None,
+                },
+                EntityModification::Insert {
+                    key: swap.parse_key(id.as_str()).unwrap(),
+                    data: entity! { schema => id: id.as_str() },
+                    block,
+                    end: None,
+                },
+            ];
+            stats.record(&mods);
+        }
+
+        assert_eq!(vec![(bundle.clone(), 10), (swap, 10)], stats.entity_types());
+        let hottest = stats.hottest(2);
+        assert_eq!(2, hottest.len());
+        assert_eq!(bundle, hottest[0].entity_type);
+        assert_eq!("1", hottest[0].id.to_string());
+        assert_eq!(10, hottest[0].writes);
+        assert_eq!(1, hottest[1].writes);
+    }
+
+    #[test]
+    fn evicts_the_coldest_entity() {
+        let schema = InputSchema::raw("type Swap @entity { id: ID! }", "hotEntities");
+        let swap = schema.entity_type("Swap").unwrap();
+        let write = |id: &str| EntityModification::Insert {
+            key: swap.parse_key(id).unwrap(),
+            data: entity! { schema => id: id },
+            block: 1,
+            end: None,
+        };
+
+        let stats = DeploymentWriteStats::new(1);
+        let mods: Vec<_> = (0..HOT_ENTITY_CAPACITY)
+            .map(|i| write(&format!("swap-{}", i)))
+            .collect();
+        stats.record(&mods);
+        stats.record(&[write("swap-7"), write("swap-7")]);
+
+        // The new entity takes over the count of one that was written once
+        stats.record(&[write("new")]);
+        let hottest = stats.hottest(HOT_ENTITY_CAPACITY + 1);
+        assert_eq!(HOT_ENTITY_CAPACITY, hottest.len());
+        assert_eq!("swap-7", hottest[0].id.to_string());
+        assert_eq!(3, hottest[0].writes);
+        assert_eq!("new", hottest[1].id.to_string());
+        assert_eq!(2, hottest[1].writes);
+        assert_eq!(1, hottest[2].writes);
+    }
+
+    #[test]
+    fn forgets_removed_deployments() {
+        let stats = WriteStats::default();
+        let deployment = DeploymentHash::new("QmWriteStats").unwrap();
+        stats.for_deployment(&deployment);
+        assert!(stats.get(&deployment).is_some());
+        stats.remove(&deployment);
+        assert!(stats.get(&deployment).is_none());
+    }
+}
@@ -0,0 +1,228 @@
+//! Statistics about how often the handlers of the deployments in this
+//! process are called and how long they take.
+//!
+//! For each handler, the number of invocations and their total time are
+//! counted, and the slowest invocations are kept together with the block
+//! they processed. Statistics cover the invocations since the process
+//! started and are not adjusted when blocks are reverted. When
+//! `GRAPH_HANDLER_STATS_LOG_INTERVAL` is set, each deployment periodically
+//! logs a summary of its most expensive handlers.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+use slog::{info, Logger};
+
+use crate::env::ENV_VARS;
+use crate::prelude::BlockNumber;
+
+/// How many of the slowest invocations to keep per handler
+const SLOWEST_INVOCATIONS: usize = 5;
+
+/// How many handlers the periodic summary lists
+const SUMMARY_HANDLERS: usize = 5;
+
+lazy_static! {
+    /// The handler statistics of all deployments that index in this process
+    pub static ref HANDLER_STATS: HandlerStats = HandlerStats::default();
+}
+
+#[derive(Default)]
+pub struct HandlerStats {
+    deployments: Mutex<HashMap<String, Arc<DeploymentHandlerStats>>>,
+}
+
+impl HandlerStats {
+    /// The statistics for `deployment`, which are created if they do not
+    /// exist yet
+    pub fn for_deployment(&self, deployment: &str) -> Arc<DeploymentHandlerStats> {
+        let mut deployments = self.deployments.lock().unwrap();
+        match deployments.get(deployment) {
+            Some(stats) => stats.clone(),
+            None => {
+                let stats = Arc::new(DeploymentHandlerStats::new(
+                    ENV_VARS.mappings.handler_stats_log_interval,
+                ));
+                deployments.insert(deployment.to_string(), stats.clone());
+                stats
+            }
+        }
+    }
+
+    /// The statistics for `deployment` if it has been indexed in this
+    /// process
+    pub fn get(&self, deployment: &str) -> Option<Arc<DeploymentHandlerStats>> {
+        self.deployments.lock().unwrap().get(deployment).cloned()
+    }
+}
+
+/// One invocation of a handler
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HandlerInvocation {
+    pub block: BlockNumber,
+    pub elapsed: Duration,
+}
+
+/// What we know about the invocations of one handler
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HandlerStat {
+    pub data_source: String,
+    pub handler: String,
+    pub invocations: u64,
+    pub total: Duration,
+    /// The slowest invocations, the slowest one first
+    pub slowest: Vec<HandlerInvocation>,
+}
+
+impl HandlerStat {
+    fn new(data_source: &str, handler: &str) -> Self {
+        HandlerStat {
+            data_source: data_source.to_string(),
+            handler: handler.to_string(),
+            invocations: 0,
+            total: Duration::ZERO,
+            slowest: Vec::new(),
+        }
+    }
+
+    fn record(&mut self, block: BlockNumber, elapsed: Duration) {
+        self.invocations += 1;
+        self.total += elapsed;
+
+        if self.slowest.len() == SLOWEST_INVOCATIONS
+            && self
+                .slowest
+                .last()
+                .map_or(false, |inv| inv.elapsed >= elapsed)
+        {
+            return;
+        }
+        let pos = self
+            .slowest
+            .iter()
+            .position(|inv| inv.elapsed < elapsed)
+            .unwrap_or(self.slowest.len());
+        self.slowest
+            .insert(pos, HandlerInvocation { block, elapsed });
+        self.slowest.truncate(SLOWEST_INVOCATIONS);
+    }
+}
+
+struct Inner {
+    /// Keyed by data source and handler name
+    handlers: HashMap<(String, String), HandlerStat>,
+    last_summary: Instant,
+}
+
+pub struct DeploymentHandlerStats {
+    /// How often to log a summary; `None` turns the summary off
+    log_interval: Option<Duration>,
+    inner: Mutex<Inner>,
+}
+
+impl DeploymentHandlerStats {
+    pub fn new(log_interval: Option<Duration>) -> Self {
+        DeploymentHandlerStats {
+            log_interval,
+            inner: Mutex::new(Inner {
+                handlers: HashMap::new(),
+                last_summary: Instant::now(),
+            }),
+        }
+    }
+
+    /// Count an invocation of `handler` of `data_source` for `block` that
+    /// took `elapsed`, and log a summary if one is due
+    pub fn record(
+        &self,
+        logger: &Logger,
+        data_source: &str,
+        handler: &str,
+        block: BlockNumber,
+        elapsed: Duration,
+    ) {
+        let mut inner = self.inner.lock().unwrap();
+        let key = (data_source.to_string(), handler.to_string());
+        inner
+            .handlers
+            .entry(key)
+            .or_insert_with(|| HandlerStat::new(data_source, handler))
+            .record(block, elapsed);
+
+        if let Some(interval) = self.log_interval {
+            if inner.last_summary.elapsed() >= interval {
+                inner.last_summary = Instant::now();
+                let summary = Self::sorted(&inner.handlers)
+                    .iter()
+                    .take(SUMMARY_HANDLERS)
+                    .map(|stat| {
+                        format!(
+                            "{}.{}: {} calls, {}ms",
+                            stat.data_source,
+                            stat.handler,
+                            stat.invocations,
+                            stat.total.as_millis()
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                info!(logger, "Handler statistics"; "handlers" => summary);
+            }
+        }
+    }
+
+    fn sorted(handlers: &HashMap<(String, String), HandlerStat>) -> Vec<HandlerStat> {
+        let mut stats: Vec<_> = handlers.values().cloned().collect();
+        stats.sort_by(|a, b| {
+            b.total
+                .cmp(&a.total)
+                .then_with(|| a.data_source.cmp(&b.data_source))
+                .then_with(|| a.handler.cmp(&b.handler))
+        });
+        stats
+    }
+
+    /// The statistics of all handlers, the one that took the most time
+    /// first
+    pub fn handlers(&self) -> Vec<HandlerStat> {
+        Self::sorted(&self.inner.lock().unwrap().handlers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::log::discard;
+
+    use super::*;
+
+    #[test]
+    fn tracks_handlers() {
+        let logger = discard();
+        let ms = Duration::from_millis;
+
+        let stats = DeploymentHandlerStats::new(None);
+        for block in 0..10 {
+            stats.record(&logger, "Token", "handleTransfer", block, ms(block as u64));
+            stats.record(&logger, "Token", "handleApproval", block, ms(1));
+        }
+
+        let handlers = stats.handlers();
+        assert_eq!(2, handlers.len());
+        assert_eq!("handleTransfer", handlers[0].handler);
+        assert_eq!(10, handlers[0].invocations);
+        assert_eq!(ms(45), handlers[0].total);
+        assert_eq!(
+            vec![9, 8, 7, 6, 5],
+            handlers[0]
+                .slowest
+                .iter()
+                .map(|inv| inv.block)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!("handleApproval", handlers[1].handler);
+        assert_eq!(ms(10), handlers[1].total);
+        assert_eq!(SLOWEST_INVOCATIONS, handlers[1].slowest.len());
+    }
+}
@@ -6,14 +6,15 @@ use anyhow::Error;
 use async_trait::async_trait;
 use futures::sync::mpsc;
 
+use crate::blockchain::Blockchain;
 use crate::components::metrics::gas::GasMetrics;
 use crate::components::store::SubgraphFork;
+use crate::components::subgraph::{DeploymentHandlerStats, SharedProofOfIndexing, HANDLER_STATS};
 use crate::data_source::{
     DataSource, DataSourceTemplate, MappingTrigger, TriggerData, TriggerWithHandler,
 };
 use crate::prelude::*;
 use crate::runtime::HostExportError;
-use crate::{blockchain::Blockchain, components::subgraph::SharedProofOfIndexing};
 
 #[derive(Debug)]
 pub enum MappingError {
@@ -103,6 +104,7 @@ pub struct HostMetrics {
     eth_call_execution_time: Box<HistogramVec>,
     pub gas_metrics: GasMetrics,
     pub stopwatch: StopwatchMetrics,
+    pub handler_stats: Arc<DeploymentHandlerStats>,
 }
 
 impl HostMetrics {
@@ -150,6 +152,7 @@ impl HostMetrics {
             stopwatch,
             gas_metrics,
             eth_call_execution_time,
+            handler_stats: HANDLER_STATS.for_deployment(subgraph),
         }
     }
 
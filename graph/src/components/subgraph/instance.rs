@@ -95,10 +95,10 @@ impl<C: Blockchain> BlockState<C> {
         std::mem::take(&mut self.created_data_sources)
     }
 
-    pub fn enter_handler(&mut self) {
+    pub fn enter_handler(&mut self, handler: &str) {
         assert!(!self.in_handler);
         self.in_handler = true;
-        self.entity_cache.enter_handler()
+        self.entity_cache.enter_handler(handler)
     }
 
     pub fn exit_handler(&mut self) {
@@ -1,4 +1,4 @@
-use crate::prelude::BlockNumber;
+use crate::prelude::{BlockNumber, NodeId};
 use std::sync::Arc;
 
 use crate::components::store::DeploymentLocator;
@@ -16,5 +16,14 @@ pub trait SubgraphInstanceManager: Send + Sync + 'static {
         manifest: serde_yaml::Mapping,
         stop_block: Option<BlockNumber>,
     );
+    /// Start a standby instance of the deployment on `node`. The instance
+    /// is fully built, but only starts indexing once the node that indexes
+    /// the deployment stops writing to it
+    async fn start_standby(
+        self: Arc<Self>,
+        deployment: DeploymentLocator,
+        manifest: serde_yaml::Mapping,
+        node: NodeId,
+    );
     async fn stop_subgraph(&self, deployment: DeploymentLocator);
 }
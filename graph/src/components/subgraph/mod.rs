@@ -1,3 +1,4 @@
+mod handler_stats;
 mod host;
 mod instance;
 mod instance_manager;
@@ -8,12 +9,15 @@ mod settings;
 
 pub use crate::prelude::Entity;
 
+pub use self::handler_stats::{
+    DeploymentHandlerStats, HandlerInvocation, HandlerStat, HandlerStats, HANDLER_STATS,
+};
 pub use self::host::{HostMetrics, MappingError, RuntimeHost, RuntimeHostBuilder};
 pub use self::instance::{BlockState, DataSourceTemplateInfo};
 pub use self::instance_manager::SubgraphInstanceManager;
 pub use self::proof_of_indexing::{
     PoICausalityRegion, ProofOfIndexing, ProofOfIndexingEvent, ProofOfIndexingFinisher,
-    ProofOfIndexingVersion, SharedProofOfIndexing,
+    ProofOfIndexingVersion, RecordedEvents, SharedProofOfIndexing,
 };
 pub use self::provider::SubgraphAssignmentProvider;
 pub use self::registrar::{SubgraphRegistrar, SubgraphVersionSwitchingMode};
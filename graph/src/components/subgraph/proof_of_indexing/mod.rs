@@ -3,7 +3,7 @@ mod online;
 mod reference;
 
 pub use event::ProofOfIndexingEvent;
-pub use online::{ProofOfIndexing, ProofOfIndexingFinisher};
+pub use online::{ProofOfIndexing, ProofOfIndexingFinisher, RecordedEvents};
 pub use reference::PoICausalityRegion;
 
 use atomic_refcell::AtomicRefCell;
@@ -82,20 +82,33 @@ mod tests {
 
             for block_i in 0..block_count {
                 let mut stream = ProofOfIndexing::new(block_i.try_into().unwrap(), version);
+                // The same events, recorded and then replayed, must lead
+                // to the same PoI
+                let mut replayed = ProofOfIndexing::new(block_i.try_into().unwrap(), version);
+                let mut recorder = replayed.recorder();
 
                 for (name, region) in case.data.causality_regions.iter() {
                     let block = &region.blocks[block_i];
 
                     for evt in block.events.iter() {
                         stream.write(&logger, name, evt);
+                        recorder.write(&logger, name, evt);
                     }
                 }
+                replayed.replay(&logger, recorder.take_recorded());
+                let mut replayed = replayed.take();
 
                 for (name, region) in stream.take() {
                     let prev = db.get(&name);
                     let update = region.pause(prev.map(|v| &v[..]));
+                    let replayed_update = replayed
+                        .remove(&name)
+                        .expect("replayed events for the causality region")
+                        .pause(prev.map(|v| &v[..]));
+                    assert_eq!(update, replayed_update, "case: {}", case.name);
                     db.insert(name, update);
                 }
+                assert!(replayed.is_empty(), "case: {}", case.name);
             }
 
             let block_number = (block_count - 1) as u64;
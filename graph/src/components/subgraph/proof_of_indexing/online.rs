@@ -6,7 +6,7 @@ use super::{ProofOfIndexingEvent, ProofOfIndexingVersion};
 use crate::{
     blockchain::BlockPtr,
     data::store::Id,
-    prelude::{debug, BlockNumber, DeploymentHash, Logger, ENV_VARS},
+    prelude::{debug, BlockNumber, DeploymentHash, Entity, Logger, ENV_VARS},
     util::stable_hash_glue::AsBytes,
 };
 use stable_hash::{fast::FastStableHasher, FieldAddress, StableHash, StableHasher};
@@ -168,6 +168,44 @@ pub struct ProofOfIndexing {
     /// state with other data sources. This may also give us some freedom to change
     /// the order of triggers in the future.
     per_causality_region: HashMap<Id, BlockEventStream>,
+    /// If this is set, events are collected here instead of being hashed
+    /// so that they can be written to another `ProofOfIndexing` later,
+    /// see `ProofOfIndexing::recorder`
+    recorded: Option<Vec<RecordedEvent>>,
+}
+
+/// What was done to a recording `ProofOfIndexing`
+enum RecordedEvent {
+    StartHandler {
+        causality_region: String,
+    },
+    RemoveEntity {
+        causality_region: String,
+        entity_type: String,
+        id: String,
+    },
+    SetEntity {
+        causality_region: String,
+        entity_type: String,
+        id: String,
+        data: Entity,
+    },
+    DeterministicError {
+        causality_region: String,
+    },
+}
+
+/// The events that a recording `ProofOfIndexing` collected, in the order
+/// in which they happened
+pub struct RecordedEvents(Vec<RecordedEvent>);
+
+impl RecordedEvents {
+    /// Whether a handler was started while these events were recorded
+    pub fn started_handler(&self) -> bool {
+        self.0
+            .iter()
+            .any(|event| matches!(event, RecordedEvent::StartHandler { .. }))
+    }
 }
 
 impl fmt::Debug for ProofOfIndexing {
@@ -182,12 +220,86 @@ impl ProofOfIndexing {
             version,
             block_number,
             per_causality_region: HashMap::new(),
+            recorded: None,
+        }
+    }
+
+    /// A `ProofOfIndexing` for the same block that does not hash events
+    /// but only records them. The recorded events can then be written to
+    /// this `ProofOfIndexing` with `replay`, which makes it possible to
+    /// run handlers in a different order than the one in which their
+    /// events need to be hashed
+    pub fn recorder(&self) -> Self {
+        Self {
+            version: self.version,
+            block_number: self.block_number,
+            per_causality_region: HashMap::new(),
+            recorded: Some(Vec::new()),
+        }
+    }
+
+    /// Return the events recorded since the last call and start recording
+    /// afresh. Returns no events if this is not a recording
+    /// `ProofOfIndexing`
+    pub fn take_recorded(&mut self) -> RecordedEvents {
+        RecordedEvents(
+            self.recorded
+                .as_mut()
+                .map(std::mem::take)
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Write events that were recorded with a recorder of this
+    /// `ProofOfIndexing`. Starting handlers is left to the caller since
+    /// handlers that process the same trigger only start once
+    pub fn replay(&mut self, logger: &Logger, events: RecordedEvents) {
+        for event in events.0 {
+            match event {
+                RecordedEvent::StartHandler { .. } => {}
+                RecordedEvent::RemoveEntity {
+                    causality_region,
+                    entity_type,
+                    id,
+                } => self.write(
+                    logger,
+                    &causality_region,
+                    &ProofOfIndexingEvent::RemoveEntity {
+                        entity_type: &entity_type,
+                        id: &id,
+                    },
+                ),
+                RecordedEvent::SetEntity {
+                    causality_region,
+                    entity_type,
+                    id,
+                    data,
+                } => self.write(
+                    logger,
+                    &causality_region,
+                    &ProofOfIndexingEvent::SetEntity {
+                        entity_type: &entity_type,
+                        id: &id,
+                        data: &data,
+                    },
+                ),
+                RecordedEvent::DeterministicError { causality_region } => {
+                    self.write_deterministic_error(logger, &causality_region)
+                }
+            }
         }
     }
 }
 
 impl ProofOfIndexing {
     pub fn write_deterministic_error(&mut self, logger: &Logger, causality_region: &str) {
+        if let Some(recorded) = &mut self.recorded {
+            recorded.push(RecordedEvent::DeterministicError {
+                causality_region: causality_region.to_owned(),
+            });
+            return;
+        }
+
         let redacted_events = self.with_causality_region(causality_region, |entry| {
             entry.vec_length - entry.handler_start
         });
@@ -206,6 +318,33 @@ impl ProofOfIndexing {
         causality_region: &str,
         event: &ProofOfIndexingEvent<'_>,
     ) {
+        if let Some(recorded) = &mut self.recorded {
+            let causality_region = causality_region.to_owned();
+            match event {
+                ProofOfIndexingEvent::RemoveEntity { entity_type, id } => {
+                    recorded.push(RecordedEvent::RemoveEntity {
+                        causality_region,
+                        entity_type: entity_type.to_string(),
+                        id: id.to_string(),
+                    })
+                }
+                ProofOfIndexingEvent::SetEntity {
+                    entity_type,
+                    id,
+                    data,
+                } => recorded.push(RecordedEvent::SetEntity {
+                    causality_region,
+                    entity_type: entity_type.to_string(),
+                    id: id.to_string(),
+                    data: (*data).clone(),
+                }),
+                ProofOfIndexingEvent::DeterministicError { .. } => {
+                    recorded.push(RecordedEvent::DeterministicError { causality_region })
+                }
+            }
+            return;
+        }
+
         if ENV_VARS.log_poi_events {
             debug!(
                 logger,
@@ -220,6 +359,12 @@ impl ProofOfIndexing {
     }
 
     pub fn start_handler(&mut self, causality_region: &str) {
+        if let Some(recorded) = &mut self.recorded {
+            recorded.push(RecordedEvent::StartHandler {
+                causality_region: causality_region.to_owned(),
+            });
+            return;
+        }
         self.with_causality_region(causality_region, |entry| entry.start_handler())
     }
 
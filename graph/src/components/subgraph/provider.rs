@@ -10,6 +10,13 @@ pub trait SubgraphAssignmentProvider: Send + Sync + 'static {
         deployment: DeploymentLocator,
         stop_block: Option<BlockNumber>,
     ) -> Result<(), SubgraphAssignmentProviderError>;
+    /// Start a standby instance of the deployment on `node`; it is stopped
+    /// with `stop` like any other instance
+    async fn start_standby(
+        &self,
+        deployment: DeploymentLocator,
+        node: NodeId,
+    ) -> Result<(), SubgraphAssignmentProviderError>;
     async fn stop(
         &self,
         deployment: DeploymentLocator,
@@ -2,7 +2,10 @@ use std::str::FromStr;
 
 use async_trait::async_trait;
 
-use crate::{components::store::DeploymentLocator, prelude::*};
+use crate::{
+    data::subgraph::schema::{DeploymentMetadata, DeploymentPlacement},
+    prelude::*,
+};
 
 #[derive(Clone, Copy, Debug)]
 pub enum SubgraphVersionSwitchingMode {
@@ -45,7 +48,9 @@ pub trait SubgraphRegistrar: Send + Sync + 'static {
         start_block_block: Option<BlockPtr>,
         graft_block_override: Option<BlockPtr>,
         history_blocks: Option<i32>,
-    ) -> Result<DeploymentLocator, SubgraphRegistrarError>;
+        metadata: DeploymentMetadata,
+        placement: DeploymentPlacement,
+    ) -> Result<CreateSubgraphVersionResult, SubgraphRegistrarError>;
 
     async fn remove_subgraph(&self, name: SubgraphName) -> Result<(), SubgraphRegistrarError>;
 
@@ -54,4 +59,29 @@ pub trait SubgraphRegistrar: Send + Sync + 'static {
         hash: &DeploymentHash,
         node_id: &NodeId,
     ) -> Result<(), SubgraphRegistrarError>;
+
+    /// Stop indexing the deployment `hash` without removing it. It stays
+    /// paused until it is resumed, even across restarts
+    async fn pause_subgraph(&self, hash: &DeploymentHash) -> Result<(), SubgraphRegistrarError>;
+
+    /// Resume indexing the paused deployment `hash` from the last block it
+    /// processed
+    async fn resume_subgraph(&self, hash: &DeploymentHash) -> Result<(), SubgraphRegistrarError>;
+
+    /// Start pruning the history of the deployment `hash` in the
+    /// background, optionally changing how many blocks of history it keeps
+    async fn prune_subgraph(
+        &self,
+        hash: &DeploymentHash,
+        history_blocks: Option<BlockNumber>,
+    ) -> Result<(), SubgraphRegistrarError>;
+
+    /// Revert the deployment `hash` to `block_ptr` and let it index again
+    /// from there. A deployment that is running is paused while it is
+    /// rewound
+    async fn rewind_subgraph(
+        &self,
+        hash: &DeploymentHash,
+        block_ptr: BlockPtr,
+    ) -> Result<(), SubgraphRegistrarError>;
 }
@@ -4,6 +4,7 @@ use std::convert::TryFrom;
 use std::str::FromStr;
 
 use crate::blockchain::BlockHash;
+use crate::data::store::scalar;
 use crate::data::value::Object;
 use crate::prelude::{r, BigInt};
 use web3::types::H160;
@@ -102,6 +103,16 @@ impl TryFromValue for BlockHash {
     }
 }
 
+impl TryFromValue for scalar::Bytes {
+    fn try_from_value(value: &r::Value) -> Result<Self, Error> {
+        match value {
+            r::Value::String(s) => scalar::Bytes::from_str(s)
+                .map_err(|e| anyhow!("Cannot parse Bytes value from string `{}`: {}", s, e)),
+            _ => Err(anyhow!("Cannot parse value into Bytes: {:?}", value)),
+        }
+    }
+}
+
 impl TryFromValue for BigInt {
     fn try_from_value(value: &r::Value) -> Result<Self, Error> {
         match value {
@@ -8,6 +8,7 @@ use std::string::FromUtf8Error;
 use std::sync::Arc;
 
 use crate::data::subgraph::*;
+use crate::env::ENV_VARS;
 use crate::prelude::q;
 use crate::{components::store::StoreError, prelude::CacheWeight};
 
@@ -55,6 +56,7 @@ pub enum QueryExecutionError {
     EntityParseError(String),
     StoreError(CloneableAnyhowError),
     Timeout,
+    Cancelled,
     EmptySelectionSet(String),
     AmbiguousDerivedFromResult(Pos, String, String, String),
     Unimplemented(String),
@@ -77,9 +79,132 @@ pub enum QueryExecutionError {
     DeploymentNotFound(String),
     IdMissing,
     IdNotString,
+    /// The field can only be queried with a valid access token
+    AccessDenied(String),
+}
+
+/// A stable, machine-readable classification of query errors that clients
+/// receive as `extensions.code` of each error if `GRAPH_GRAPHQL_ERROR_CODES`
+/// is set
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueryErrorCode {
+    /// The query is malformed or does not fit the schema; sending it again
+    /// will not help
+    BadQuery,
+    DeploymentNotFound,
+    /// The deployment failed before reaching the block the query asked for
+    IndexingFailed,
+    /// The database could not be reached or ran out of connections; the
+    /// query can be retried
+    StoreUnavailable,
+    TimedOut,
+    TooExpensive,
+    Throttled,
+    Cancelled,
+    /// The chain was reorganized while the query ran; the query can be
+    /// retried right away
+    ChainReorganized,
+    /// The query asked for something that requires an access token
+    AccessDenied,
+    Internal,
+}
+
+impl QueryErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        use QueryErrorCode::*;
+        match self {
+            BadQuery => "BAD_QUERY",
+            DeploymentNotFound => "DEPLOYMENT_NOT_FOUND",
+            IndexingFailed => "INDEXING_FAILED",
+            StoreUnavailable => "STORE_UNAVAILABLE",
+            TimedOut => "TIMED_OUT",
+            TooExpensive => "TOO_EXPENSIVE",
+            Throttled => "THROTTLED",
+            Cancelled => "CANCELLED",
+            ChainReorganized => "CHAIN_REORGANIZED",
+            AccessDenied => "ACCESS_DENIED",
+            Internal => "INTERNAL_ERROR",
+        }
+    }
+}
+
+impl CloneableAnyhowError {
+    /// Only errors that say that the database can not be reached right now
+    /// are worth retrying
+    fn code(&self) -> QueryErrorCode {
+        match self.0.downcast_ref::<StoreError>() {
+            Some(StoreError::DatabaseUnavailable) | Some(StoreError::DatabaseDisabled) => {
+                QueryErrorCode::StoreUnavailable
+            }
+            Some(StoreError::Canceled) => QueryErrorCode::Cancelled,
+            _ => QueryErrorCode::Internal,
+        }
+    }
 }
 
 impl QueryExecutionError {
+    pub fn code(&self) -> QueryErrorCode {
+        use self::QueryExecutionError::*;
+        match self {
+            OperationNameRequired
+            | OperationNotFound(_)
+            | NotSupported(_)
+            | NoRootSubscriptionObjectType
+            | InvalidArgumentError(_, _, _)
+            | MissingArgumentError(_, _)
+            | ValidationError(_, _)
+            | InvalidVariableTypeError(_, _)
+            | MissingVariableError(_, _)
+            | OrderByNotSupportedError(_, _)
+            | OrderByNotSupportedForType(_)
+            | FilterNotSupportedError(_, _)
+            | UnknownField(_, _, _)
+            | EmptyQuery
+            | MultipleSubscriptionFields
+            | RangeArgumentsError(_, _, _)
+            | InvalidFilterError
+            | EntityFieldError(_, _)
+            | ListTypesError(_, _)
+            | ListFilterError(_)
+            | ChildFilterNestingNotSupportedError(_, _)
+            | ValueParseError(_, _)
+            | AttributeTypeError(_, _)
+            | EmptySelectionSet(_)
+            | Unimplemented(_)
+            | EnumCoercionError(_, _, _, _, _)
+            | ScalarCoercionError(_, _, _, _)
+            | CyclicalFragment(_)
+            | UndefinedFragment(_)
+            | FulltextQueryRequiresFilter
+            | FulltextQueryInvalidSyntax(_) => QueryErrorCode::BadQuery,
+            SubgraphDeploymentIdError(_) | DeploymentNotFound(_) => {
+                QueryErrorCode::DeploymentNotFound
+            }
+            StoreError(e) => e.code(),
+            Timeout => QueryErrorCode::TimedOut,
+            TooComplex(_, _) | TooDeep(_) | TooExpensive | ResultTooBig(_, _) => {
+                QueryErrorCode::TooExpensive
+            }
+            Throttled => QueryErrorCode::Throttled,
+            Cancelled => QueryErrorCode::Cancelled,
+            DeploymentReverted => QueryErrorCode::ChainReorganized,
+            AccessDenied(_) => QueryErrorCode::AccessDenied,
+            NonNullError(_, _)
+            | ListValueError(_, _)
+            | NamedTypeError(_)
+            | AbstractTypeError(_)
+            | EntityParseError(_)
+            | AmbiguousDerivedFromResult(_, _, _, _)
+            | Panic(_)
+            | EventStreamError
+            | SubgraphManifestResolveError(_)
+            | InvalidSubgraphManifest
+            | IdMissing
+            | IdNotString
+            | ResolveEntitiesError(_) => QueryErrorCode::Internal,
+        }
+    }
+
     pub fn is_attestable(&self) -> bool {
         use self::QueryExecutionError::*;
         match self {
@@ -122,6 +247,7 @@ impl QueryExecutionError {
             | EntityParseError(_)
             | StoreError(_)
             | Timeout
+            | Cancelled
             | AmbiguousDerivedFromResult(_, _, _, _)
             | TooComplex(_, _)
             | TooDeep(_)
@@ -136,7 +262,8 @@ impl QueryExecutionError {
             | ResultTooBig(_, _)
             | DeploymentNotFound(_)
             | IdMissing
-            | IdNotString => false,
+            | IdNotString
+            | AccessDenied(_) => false,
         }
     }
 }
@@ -247,6 +374,7 @@ impl fmt::Display for QueryExecutionError {
                 write!(f, "Store error: {}", e.0)
             }
             Timeout => write!(f, "Query timed out"),
+            Cancelled => write!(f, "Query was cancelled by the operator of this node"),
             EmptySelectionSet(entity_type) => {
                 write!(f, "Selection set for type `{}` is empty", entity_type)
             }
@@ -286,6 +414,7 @@ impl fmt::Display for QueryExecutionError {
             DeploymentNotFound(id_or_name) => write!(f, "deployment `{}` does not exist", id_or_name),
             IdMissing => write!(f, "entity is missing an `id` attribute"),
             IdNotString => write!(f, "entity `id` attribute is not a string"),
+            AccessDenied(field) => write!(f, "`{}` requires a valid access token", field),
         }
     }
 }
@@ -357,6 +486,14 @@ impl QueryError {
             QueryError::IndexingError => false,
         }
     }
+
+    pub fn code(&self) -> QueryErrorCode {
+        match self {
+            QueryError::EncodingError(_) | QueryError::ParseError(_) => QueryErrorCode::BadQuery,
+            QueryError::ExecutionError(err) => err.code(),
+            QueryError::IndexingError => QueryErrorCode::IndexingFailed,
+        }
+    }
 }
 
 impl From<FromUtf8Error> for QueryError {
@@ -405,7 +542,7 @@ impl Serialize for QueryError {
     {
         use self::QueryExecutionError::*;
 
-        let mut map = serializer.serialize_map(Some(1))?;
+        let mut map = serializer.serialize_map(None)?;
 
         let msg = match self {
             // Serialize parse errors with their location (line, column) to make it easier
@@ -465,6 +602,11 @@ impl Serialize for QueryError {
         };
 
         map.serialize_entry("message", msg.as_str())?;
+        if ENV_VARS.graphql.error_codes {
+            let mut extensions = HashMap::new();
+            extensions.insert("code", self.code().as_str());
+            map.serialize_entry("extensions", &extensions)?;
+        }
         map.end()
     }
 }
@@ -475,3 +617,66 @@ impl CacheWeight for QueryError {
         0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_codes() {
+        assert_eq!(
+            "TIMED_OUT",
+            QueryError::from(QueryExecutionError::Timeout)
+                .code()
+                .as_str()
+        );
+        assert_eq!(
+            QueryErrorCode::BadQuery,
+            QueryError::from(QueryExecutionError::EmptyQuery).code()
+        );
+        assert_eq!(
+            QueryErrorCode::DeploymentNotFound,
+            QueryError::from(QueryExecutionError::from(StoreError::DeploymentNotFound(
+                "QmNone".to_string()
+            )))
+            .code()
+        );
+        assert_eq!(
+            QueryErrorCode::StoreUnavailable,
+            QueryError::from(QueryExecutionError::from(StoreError::DatabaseUnavailable)).code()
+        );
+        assert_eq!(
+            QueryErrorCode::IndexingFailed,
+            QueryError::IndexingError.code()
+        );
+    }
+
+    #[test]
+    fn only_connection_errors_are_store_unavailable() {
+        let code = |e: StoreError| QueryError::from(QueryExecutionError::from(e)).code();
+
+        assert_eq!(
+            QueryErrorCode::StoreUnavailable,
+            code(StoreError::DatabaseDisabled)
+        );
+        assert_eq!(QueryErrorCode::Cancelled, code(StoreError::Canceled));
+        // Retrying these would only fail again
+        assert_eq!(
+            QueryErrorCode::Internal,
+            code(StoreError::QueryExecutionError(
+                "canceling statement due to statement timeout".to_string()
+            ))
+        );
+        assert_eq!(
+            QueryErrorCode::Internal,
+            code(StoreError::UnknownField("name".to_string()))
+        );
+        assert_eq!(
+            QueryErrorCode::Internal,
+            QueryError::from(QueryExecutionError::ResolveEntitiesError(
+                "syntax error".to_string()
+            ))
+            .code()
+        );
+    }
+}
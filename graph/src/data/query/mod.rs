@@ -2,10 +2,16 @@ mod cache_status;
 mod error;
 mod query;
 mod result;
+mod running;
+mod slow;
 mod trace;
 
 pub use self::cache_status::CacheStatus;
-pub use self::error::{QueryError, QueryExecutionError};
+pub use self::error::{QueryError, QueryErrorCode, QueryExecutionError};
 pub use self::query::{Query, QueryTarget, QueryVariables};
 pub use self::result::{QueryResult, QueryResults};
+pub use self::running::{
+    BackendGuard, CancelBackend, RunningQueries, RunningQuery, RunningQueryGuard, RUNNING_QUERIES,
+};
+pub use self::slow::{QueryTiming, SlowQueries, SlowQuery, SLOW_QUERIES};
 pub use self::trace::Trace;
@@ -133,6 +133,9 @@ pub struct Query {
     pub query_text: Arc<String>,
     pub variables_text: Arc<String>,
     pub trace: bool,
+    /// Who sent the query, if known; only used to show operators which
+    /// queries are running
+    pub client: Option<String>,
     _force_use_of_new: (),
 }
 
@@ -142,6 +145,7 @@ impl Query {
 
         let (query_text, variables_text) = if trace
             || ENV_VARS.log_gql_timing()
+            || ENV_VARS.graphql.slow_query_threshold.is_some()
             || (ENV_VARS.graphql.enable_validations && ENV_VARS.graphql.silent_graphql_validations)
         {
             (
@@ -161,7 +165,13 @@ impl Query {
             query_text: Arc::new(query_text),
             variables_text: Arc::new(variables_text),
             trace,
+            client: None,
             _force_use_of_new: (),
         }
     }
+
+    pub fn with_client(mut self, client: Option<String>) -> Self {
+        self.client = client;
+        self
+    }
 }
@@ -47,15 +47,24 @@ pub type Data = Object;
 /// A collection of query results that is serialized as a single result.
 pub struct QueryResults {
     results: Vec<Arc<QueryResult>>,
+    /// The complexity of the query; only set when it should be reported
+    /// in the `extensions` of the response
+    complexity: Option<u64>,
 }
 
 impl QueryResults {
     pub fn empty() -> Self {
         QueryResults {
             results: Vec::new(),
+            complexity: None,
         }
     }
 
+    /// Report `complexity` in the `extensions` of the response
+    pub fn set_complexity(&mut self, complexity: u64) {
+        self.complexity = Some(complexity);
+    }
+
     pub fn first(&self) -> Option<&Arc<QueryResult>> {
         self.results.first()
     }
@@ -103,6 +112,9 @@ impl Serialize for QueryResults {
         if first_trace.is_some() {
             len += 1;
         }
+        if self.complexity.is_some() {
+            len += 1;
+        }
         let mut state = serializer.serialize_struct("QueryResults", len)?;
 
         // Serialize data.
@@ -141,6 +153,15 @@ impl Serialize for QueryResults {
         if let Some(trace) = first_trace {
             state.serialize_field("trace", trace)?;
         }
+
+        if let Some(complexity) = self.complexity {
+            #[derive(Serialize)]
+            struct Extensions {
+                complexity: u64,
+            }
+
+            state.serialize_field("extensions", &Extensions { complexity })?;
+        }
         state.end()
     }
 }
@@ -149,6 +170,7 @@ impl From<Data> for QueryResults {
     fn from(x: Data) -> Self {
         QueryResults {
             results: vec![Arc::new(x.into())],
+            complexity: None,
         }
     }
 }
@@ -157,13 +179,17 @@ impl From<QueryResult> for QueryResults {
     fn from(x: QueryResult) -> Self {
         QueryResults {
             results: vec![Arc::new(x)],
+            complexity: None,
         }
     }
 }
 
 impl From<Arc<QueryResult>> for QueryResults {
     fn from(x: Arc<QueryResult>) -> Self {
-        QueryResults { results: vec![x] }
+        QueryResults {
+            results: vec![x],
+            complexity: None,
+        }
     }
 }
 
@@ -171,6 +197,7 @@ impl From<QueryExecutionError> for QueryResults {
     fn from(x: QueryExecutionError) -> Self {
         QueryResults {
             results: vec![Arc::new(x.into())],
+            complexity: None,
         }
     }
 }
@@ -179,6 +206,7 @@ impl From<Vec<QueryExecutionError>> for QueryResults {
     fn from(x: Vec<QueryExecutionError>) -> Self {
         QueryResults {
             results: vec![Arc::new(x.into())],
+            complexity: None,
         }
     }
 }
@@ -189,13 +217,17 @@ impl QueryResults {
     }
 
     pub fn as_http_response<T: From<String>>(&self) -> http::Response<T> {
-        let status_code = http::StatusCode::OK;
-
         let json =
             serde_json::to_string(self).expect("Failed to serialize GraphQL response to JSON");
 
+        self.http_response_builder().body(T::from(json)).unwrap()
+    }
+
+    /// A builder for the HTTP response for these results that has all the
+    /// headers set, for callers that produce the body themselves
+    pub fn http_response_builder(&self) -> http::response::Builder {
         http::Response::builder()
-            .status(status_code)
+            .status(http::StatusCode::OK)
             .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
             .header(ACCESS_CONTROL_ALLOW_HEADERS, "Content-Type, User-Agent")
             .header(ACCESS_CONTROL_ALLOW_METHODS, "GET, OPTIONS, POST")
@@ -204,8 +236,6 @@ impl QueryResults {
                 "Graph-Attestable",
                 self.results.iter().all(|r| r.is_attestable()).to_string(),
             )
-            .body(T::from(json))
-            .unwrap()
     }
 }
 
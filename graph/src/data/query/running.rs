@@ -0,0 +1,205 @@
+//! Keep track of the GraphQL queries that are running in this process so
+//! that operators can see what a query node is busy with and cancel a
+//! query that runs away without restarting the node.
+//!
+//! Queries are registered when their execution starts and stay registered
+//! until the `RunningQueryGuard` that registering returned is dropped.
+//! Cancelling a query sets a flag that execution checks in the same places
+//! where it checks whether the query has timed out. If the query is
+//! waiting for the database at that moment, the SQL statement it is
+//! waiting for is cancelled, too.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+
+use crate::prelude::DeploymentHash;
+
+lazy_static! {
+    /// The queries that are running in this process
+    pub static ref RUNNING_QUERIES: RunningQueries = RunningQueries::default();
+}
+
+/// Cancels what the database backend with the given process id is doing
+pub type CancelBackend = Arc<dyn Fn(i32) + Send + Sync>;
+
+/// The database backend that runs a SQL statement for a query
+struct Backend {
+    pid: i32,
+    cancel: CancelBackend,
+}
+
+/// A query that is currently running
+pub struct RunningQuery {
+    pub id: u64,
+    pub deployment: DeploymentHash,
+    pub shape_hash: u64,
+    pub started: Instant,
+    /// Who sent the query, as far as we know; for HTTP queries, this is
+    /// the `User-Agent` of the request
+    pub client: Option<String>,
+    cancelled: AtomicBool,
+    backend: Mutex<Option<Backend>>,
+}
+
+impl RunningQuery {
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Note that the database backend with process id `pid` is running a
+    /// SQL statement for this query until the returned guard is dropped.
+    /// Cancelling the query in the meantime calls `cancel` with `pid`
+    pub fn in_backend(&self, pid: i32, cancel: CancelBackend) -> BackendGuard<'_> {
+        *self.backend.lock().unwrap() = Some(Backend { pid, cancel });
+        BackendGuard { query: self }
+    }
+
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        // Hold the lock while cancelling so that the backend can not go
+        // on to run statements for another query in the meantime
+        if let Some(backend) = self.backend.lock().unwrap().as_ref() {
+            (backend.cancel)(backend.pid);
+        }
+    }
+}
+
+impl fmt::Debug for RunningQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RunningQuery")
+            .field("id", &self.id)
+            .field("deployment", &self.deployment)
+            .field("shape_hash", &self.shape_hash)
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+/// Clears the backend of a query when the SQL statement it ran is done
+pub struct BackendGuard<'a> {
+    query: &'a RunningQuery,
+}
+
+impl Drop for BackendGuard<'_> {
+    fn drop(&mut self) {
+        *self.query.backend.lock().unwrap() = None;
+    }
+}
+
+#[derive(Default)]
+pub struct RunningQueries {
+    queries: Mutex<HashMap<u64, Arc<RunningQuery>>>,
+    next_id: AtomicU64,
+}
+
+impl RunningQueries {
+    /// Register a query that is about to start running. The query is
+    /// removed from the list of running queries when the returned guard
+    /// is dropped
+    pub fn register(
+        &self,
+        deployment: DeploymentHash,
+        shape_hash: u64,
+        client: Option<String>,
+    ) -> RunningQueryGuard<'_> {
+        let query = Arc::new(RunningQuery {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst),
+            deployment,
+            shape_hash,
+            started: Instant::now(),
+            client,
+            cancelled: AtomicBool::new(false),
+            backend: Mutex::new(None),
+        });
+        self.queries.lock().unwrap().insert(query.id, query.clone());
+        RunningQueryGuard {
+            queries: self,
+            query,
+        }
+    }
+
+    /// All running queries, the ones that have been running the longest
+    /// first
+    pub fn list(&self) -> Vec<Arc<RunningQuery>> {
+        let mut queries: Vec<_> = self.queries.lock().unwrap().values().cloned().collect();
+        queries.sort_by_key(|query| (query.started, query.id));
+        queries
+    }
+
+    /// Cancel the query with the given `id`. Return `false` if there is no
+    /// such query
+    pub fn cancel(&self, id: u64) -> bool {
+        let query = self.queries.lock().unwrap().get(&id).cloned();
+        match query {
+            Some(query) => {
+                query.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Removes a query from the running queries when it is dropped
+pub struct RunningQueryGuard<'a> {
+    queries: &'a RunningQueries,
+    query: Arc<RunningQuery>,
+}
+
+impl RunningQueryGuard<'_> {
+    pub fn query(&self) -> &Arc<RunningQuery> {
+        &self.query
+    }
+}
+
+impl Drop for RunningQueryGuard<'_> {
+    fn drop(&mut self) {
+        self.queries.queries.lock().unwrap().remove(&self.query.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn register_and_cancel() {
+        let queries = RunningQueries::default();
+        let hash = DeploymentHash::new("QmRunning").unwrap();
+
+        let first = queries.register(hash.clone(), 1, Some("curl".to_string()));
+        let second = queries.register(hash, 2, None);
+        let ids: Vec<_> = queries.list().iter().map(|query| query.id).collect();
+        assert_eq!(vec![first.query().id, second.query().id], ids);
+
+        assert!(queries.cancel(second.query().id));
+        assert!(second.query().is_cancelled());
+        assert!(!first.query().is_cancelled());
+
+        let cancelled = Arc::new(Mutex::new(Vec::new()));
+        let cancel: CancelBackend = {
+            let cancelled = cancelled.clone();
+            Arc::new(move |pid| cancelled.lock().unwrap().push(pid))
+        };
+        let backend = first.query().in_backend(42, cancel.clone());
+        drop(backend);
+        let backend = first.query().in_backend(17, cancel);
+        assert!(queries.cancel(first.query().id));
+        assert_eq!(vec![17], *cancelled.lock().unwrap());
+
+        let id = first.query().id;
+        drop(backend);
+        drop(first);
+        assert_eq!(1, queries.list().len());
+        assert!(!queries.cancel(id));
+    }
+}
@@ -0,0 +1,169 @@
+//! Keep track of the GraphQL queries that took longer than
+//! `GRAPH_GRAPHQL_SLOW_QUERY_MS` so that operators can find the queries
+//! that cost the most.
+//!
+//! Slow queries are grouped by deployment and by the shape hash of the
+//! query, which ignores the values of arguments; queries that only differ
+//! in their arguments or variables therefore end up in the same group.
+//! Only a limited number of groups are kept; when that limit is reached,
+//! the group with the least total time is dropped to make room.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+
+use crate::prelude::{BlockNumber, DeploymentHash};
+
+/// The maximum number of groups of slow queries that are kept
+const MAX_SLOW_QUERIES: usize = 1000;
+
+lazy_static! {
+    /// The slow queries of this process
+    pub static ref SLOW_QUERIES: SlowQueries = SlowQueries::new(MAX_SLOW_QUERIES);
+}
+
+/// How long the phases of a query took
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueryTiming {
+    /// Validating the query and preparing it for execution
+    pub prepare: Duration,
+    /// Waiting for the query to be admitted and for the deployment to
+    /// reach the blocks the query asks for
+    pub wait: Duration,
+    /// Executing the query against the store
+    pub execute: Duration,
+}
+
+impl QueryTiming {
+    pub fn total(&self) -> Duration {
+        self.prepare + self.wait + self.execute
+    }
+}
+
+/// The slow queries of a deployment that have the same shape
+#[derive(Clone, Debug)]
+pub struct SlowQuery {
+    pub deployment: DeploymentHash,
+    pub shape_hash: u64,
+    /// The text of the latest slow query with this shape
+    pub query: Arc<String>,
+    /// The hash of the variables of the latest slow query with this shape
+    pub variables_hash: u64,
+    /// The block at which the latest slow query with this shape ran
+    pub block: BlockNumber,
+    pub count: u64,
+    pub total: Duration,
+    /// The timing of the slowest query with this shape
+    pub max: QueryTiming,
+}
+
+pub struct SlowQueries {
+    max_entries: usize,
+    queries: Mutex<HashMap<(DeploymentHash, u64), SlowQuery>>,
+}
+
+impl SlowQueries {
+    pub fn new(max_entries: usize) -> Self {
+        SlowQueries {
+            max_entries,
+            queries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record a query that took longer than the slow query threshold
+    pub fn record(
+        &self,
+        deployment: &DeploymentHash,
+        shape_hash: u64,
+        query: &Arc<String>,
+        variables_hash: u64,
+        block: BlockNumber,
+        timing: QueryTiming,
+    ) {
+        let mut queries = self.queries.lock().unwrap();
+        let key = (deployment.clone(), shape_hash);
+        if !queries.contains_key(&key) && queries.len() >= self.max_entries {
+            let cheapest = queries
+                .iter()
+                .min_by_key(|(_, query)| query.total)
+                .map(|(key, _)| key.clone());
+            if let Some(cheapest) = cheapest {
+                queries.remove(&cheapest);
+            }
+        }
+
+        let entry = queries.entry(key).or_insert_with(|| SlowQuery {
+            deployment: deployment.clone(),
+            shape_hash,
+            query: query.clone(),
+            variables_hash,
+            block,
+            count: 0,
+            total: Duration::ZERO,
+            max: QueryTiming::default(),
+        });
+        entry.query = query.clone();
+        entry.variables_hash = variables_hash;
+        entry.block = block;
+        entry.count += 1;
+        entry.total += timing.total();
+        if timing.total() > entry.max.total() {
+            entry.max = timing;
+        }
+    }
+
+    /// The `first` groups of slow queries that took the most time in
+    /// total, the most expensive one first
+    pub fn top(&self, first: usize) -> Vec<SlowQuery> {
+        let mut queries: Vec<_> = self.queries.lock().unwrap().values().cloned().collect();
+        queries.sort_by(|a, b| {
+            b.total
+                .cmp(&a.total)
+                .then_with(|| a.shape_hash.cmp(&b.shape_hash))
+        });
+        queries.truncate(first);
+        queries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_and_evicts() {
+        let queries = SlowQueries::new(2);
+        let hash = DeploymentHash::new("QmSlow").unwrap();
+        let text = Arc::new("{ things { id } }".to_string());
+        let timing = |ms| QueryTiming {
+            execute: Duration::from_millis(ms),
+            ..Default::default()
+        };
+
+        queries.record(&hash, 1, &text, 7, 10, timing(100));
+        queries.record(&hash, 1, &text, 8, 11, timing(300));
+        queries.record(&hash, 2, &text, 7, 10, timing(50));
+
+        let top = queries.top(10);
+        assert_eq!(
+            vec![1, 2],
+            top.iter().map(|q| q.shape_hash).collect::<Vec<_>>()
+        );
+        assert_eq!(2, top[0].count);
+        assert_eq!(Duration::from_millis(400), top[0].total);
+        assert_eq!(timing(300), top[0].max);
+        assert_eq!(8, top[0].variables_hash);
+        assert_eq!(11, top[0].block);
+
+        // Shape 2 took the least time and makes room for shape 3
+        queries.record(&hash, 3, &text, 7, 10, timing(200));
+        let top = queries.top(10);
+        assert_eq!(
+            vec![1, 3],
+            top.iter().map(|q| q.shape_hash).collect::<Vec<_>>()
+        );
+        assert_eq!(1, queries.top(1).len());
+    }
+}
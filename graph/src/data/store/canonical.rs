@@ -0,0 +1,124 @@
+//! A canonical JSON form of entities that makes it possible to hash and
+//! compare the data of a deployment without relying on the proof of
+//! indexing or on the database it is stored in.
+//!
+//! Every entity becomes one line of JSON without any whitespace. The line
+//! is an object with the attributes of the entity and an additional
+//! attribute `__typename` with the name of the entity type. Keys appear in
+//! the byte order of their names, and attributes that are `null` are left
+//! out. Values are normalized so that equal values always look the same:
+//!
+//! - `String` values are JSON strings
+//! - `Int` values are JSON numbers
+//! - `Int8` and `BigInt` values are JSON strings with the decimal value,
+//!   since JSON numbers can not represent them exactly everywhere
+//! - `BigDecimal` values are JSON strings with the normalized decimal value,
+//!   i.e., without trailing zeros after the decimal point
+//! - `Boolean` values are `true` or `false`
+//! - `Bytes` values are JSON strings with the lowercase hex value and a
+//!   `0x` prefix
+//! - lists are JSON arrays of values in the same form; `null` elements
+//!   are kept
+//!
+//! Any change to this format must change `CANONICAL_VERSION`.
+
+use std::fmt::Write as _;
+
+use super::{Entity, Value};
+
+/// The version of the canonical format
+pub const CANONICAL_VERSION: u32 = 1;
+
+fn write_str(out: &mut String, s: &str) {
+    // Unwrap: serializing a string can not fail
+    out.push_str(&serde_json::to_string(s).unwrap());
+}
+
+fn write_value(out: &mut String, value: &Value) {
+    // Unwraps: writing to a `String` can not fail
+    match value {
+        Value::String(s) => write_str(out, s),
+        Value::Int(i) => write!(out, "{}", i).unwrap(),
+        Value::Int8(i) => write!(out, "\"{}\"", i).unwrap(),
+        Value::BigInt(i) => write!(out, "\"{}\"", i).unwrap(),
+        Value::BigDecimal(d) => write!(out, "\"{}\"", d.normalized()).unwrap(),
+        Value::Bool(b) => write!(out, "{}", b).unwrap(),
+        Value::Bytes(b) => write!(out, "\"{}\"", b).unwrap(),
+        Value::Null => out.push_str("null"),
+        Value::List(values) => {
+            out.push('[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(out, value);
+            }
+            out.push(']');
+        }
+    }
+}
+
+/// The canonical form of `entity`, an entity of type `entity_type`, as a
+/// single line of JSON without a trailing newline
+pub fn canonical_json(entity_type: &str, entity: &Entity) -> String {
+    let mut attrs: Vec<_> = entity
+        .sorted_ref()
+        .into_iter()
+        .filter(|(key, value)| !value.is_null() && *key != "__typename")
+        .collect();
+    attrs.push(("__typename", &Value::Null));
+    attrs.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut out = String::from("{");
+    for (i, (key, value)) in attrs.into_iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_str(&mut out, key);
+        out.push(':');
+        if key == "__typename" {
+            write_str(&mut out, entity_type);
+        } else {
+            write_value(&mut out, value);
+        }
+    }
+    out.push('}');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::data::store::scalar::{BigDecimal, BigInt, Bytes};
+    use crate::entity;
+    use crate::schema::InputSchema;
+
+    use super::*;
+
+    #[test]
+    fn canonical_form() {
+        let schema = InputSchema::raw(
+            "type Thing @entity { id: ID!, name: String, count: Int!, big: BigInt!,
+                                  price: BigDecimal!, flag: Boolean!, data: Bytes!,
+                                  tags: [String!]!, nothing: String }",
+            "canonical",
+        );
+        let entity = entity! { schema =>
+            id: "one",
+            name: "say \"hi\"",
+            count: 7,
+            big: BigInt::from(12345678901234567890u64),
+            price: BigDecimal::from_str("1.500").unwrap(),
+            flag: true,
+            data: Bytes::from_str("0xCAFE").unwrap(),
+            tags: vec!["a", "b"],
+            nothing: Value::Null,
+        };
+
+        assert_eq!(
+            r#"{"__typename":"Thing","big":"12345678901234567890","count":7,"data":"0xcafe","flag":true,"id":"one","name":"say \"hi\"","price":"1.5","tags":["a","b"]}"#,
+            canonical_json("Thing", &entity)
+        );
+    }
+}
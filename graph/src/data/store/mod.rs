@@ -29,6 +29,9 @@ pub use id::{Id, IdList, IdRef, IdType};
 /// Custom scalars in GraphQL.
 pub mod scalar;
 
+/// A canonical JSON form of entities
+pub mod canonical;
+
 // Ethereum compatibility.
 pub mod ethereum;
 
@@ -121,6 +124,11 @@ pub enum AssignmentEvent {
         deployment: DeploymentLocator,
         node_id: NodeId,
     },
+    /// The node `node_id` should keep a standby instance of the deployment
+    Standby {
+        deployment: DeploymentLocator,
+        node_id: NodeId,
+    },
 }
 
 impl AssignmentEvent {
@@ -128,6 +136,7 @@ impl AssignmentEvent {
         match self {
             AssignmentEvent::Add { node_id, .. } => node_id,
             AssignmentEvent::Remove { node_id, .. } => node_id,
+            AssignmentEvent::Standby { node_id, .. } => node_id,
         }
     }
 }
@@ -677,6 +686,15 @@ pub enum EntityValidationError {
         actual_type: String,
     },
 
+    #[error("Entity {entity}[{entity_id}]: the value `{value}` for field `{field}` is not a valid value for enum {enum_type}")]
+    InvalidEnumValue {
+        entity: String,
+        entity_id: String,
+        value: String,
+        field: String,
+        enum_type: String,
+    },
+
     #[error("Entity {entity}[{entity_id}]: missing value for non-nullable field `{field}`")]
     MissingValueForNonNullableField {
         entity: String,
@@ -684,6 +702,13 @@ pub enum EntityValidationError {
         field: String,
     },
 
+    #[error("Entity {entity}[{entity_id}]: the id does not have the format required by @idFormat: {reason}")]
+    InvalidIdFormat {
+        entity: String,
+        entity_id: String,
+        reason: String,
+    },
+
     #[error("Entity {entity}[{entity_id}]: field `{field}` is derived and cannot be set")]
     CannotSetDerivedField {
         entity: String,
@@ -863,6 +888,15 @@ impl Entity {
             }
         })?;
 
+        object_type
+            .id_format
+            .check(&key.entity_id)
+            .map_err(|reason| EntityValidationError::InvalidIdFormat {
+                entity: key.entity_type.to_string(),
+                entity_id: key.entity_id.to_string(),
+                reason,
+            })?;
+
         for field in object_type.fields.iter() {
             match (self.get(&field.name), field.is_derived) {
                 (Some(value), false) => {
@@ -899,6 +933,26 @@ impl Entity {
                             actual_type: value.type_name().to_string(),
                         });
                     }
+                    let base_type = field.field_type.get_base_type();
+                    if let Some(enum_values) = key.entity_type.enum_values(base_type) {
+                        let elts = match value {
+                            Value::List(elts) => elts.as_slice(),
+                            value => std::slice::from_ref(value),
+                        };
+                        for elt in elts {
+                            if let Value::String(s) = elt {
+                                if !enum_values.contains(s) {
+                                    return Err(EntityValidationError::InvalidEnumValue {
+                                        entity: key.entity_type.to_string(),
+                                        entity_id: key.entity_id.to_string(),
+                                        value: s.clone(),
+                                        field: field.name.to_string(),
+                                        enum_type: base_type.to_string(),
+                                    });
+                                }
+                            }
+                        }
+                    }
                 }
                 (None, false) => {
                     if field.field_type.is_non_null() {
@@ -1123,6 +1177,53 @@ fn entity_validation() {
         thing,
         "Entity Thing[t8]: field `cruft` is derived and cannot be set",
     );
+
+    let mut thing = make_thing("t9");
+    thing.set("favorite_color", "green").unwrap();
+    check(
+        thing,
+        "Entity Thing[t9]: the value `green` for field `favorite_color` \
+         is not a valid value for enum Color",
+    );
+}
+
+#[test]
+fn entity_id_format_validation() {
+    use crate::schema::InputSchema;
+
+    let schema = InputSchema::parse(
+        "type Account @entity { id: ID! @idFormat(lowercaseHex: true, maxLength: 8) }",
+        DeploymentHash::new("idFormat").unwrap(),
+    )
+    .unwrap();
+    let account = schema.entity_type("Account").unwrap();
+
+    let check = |id: &str| {
+        let entity = entity! { schema => id: id };
+        entity
+            .validate(&account.parse_key(id).unwrap())
+            .map_err(|e| e.to_string())
+    };
+
+    assert!(check("0xab12").is_ok());
+    assert!(check("alice").is_ok());
+    assert_eq!(
+        Err(
+            "Entity Account[0xAB12]: the id does not have the format required by @idFormat: \
+             an id that starts with `0x` must be followed by lowercase hex digits"
+                .to_string()
+        ),
+        check("0xAB12")
+    );
+    assert!(check("0Xab12").is_err());
+    assert_eq!(
+        Err(
+            "Entity Account[0x0123456789]: the id does not have the format required by \
+             @idFormat: the id has 12 characters but can have at most 8"
+                .to_string()
+        ),
+        check("0x0123456789")
+    );
 }
 
 #[test]
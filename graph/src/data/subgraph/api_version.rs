@@ -44,6 +44,15 @@ pub const SPEC_VERSION_0_0_9: Version = Version::new(0, 0, 9);
 // Enables `indexerHints` feature.
 pub const SPEC_VERSION_0_1_0: Version = Version::new(0, 1, 0);
 
+/// Processes the triggers of a block in an order that does not depend on
+/// how they were fetched, see `TriggerData::cmp_position`.
+pub const SPEC_VERSION_1_0_0: Version = Version::new(1, 0, 0);
+
+/// Enables `startBlock` and `startTime` on the handlers of Ethereum data
+/// sources, batch event handlers, filters on the parameters and topics of
+/// events and `abiFromContext` for data source templates.
+pub const SPEC_VERSION_1_1_0: Version = Version::new(1, 1, 0);
+
 pub const MIN_SPEC_VERSION: Version = Version::new(0, 0, 2);
 
 #[derive(Clone, PartialEq, Debug)]
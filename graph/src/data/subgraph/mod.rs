@@ -21,7 +21,7 @@ use slog::Logger;
 use stable_hash::{FieldAddress, StableHash};
 use stable_hash_legacy::SequenceNumber;
 use std::{
-    collections::{BTreeSet, HashMap, HashSet},
+    collections::{BTreeSet, HashMap},
     marker::PhantomData,
 };
 use thiserror::Error;
@@ -30,10 +30,10 @@ use web3::types::Address;
 
 use crate::{
     bail,
-    blockchain::{BlockPtr, Blockchain, DataSource as _},
+    blockchain::{BlockPtr, Blockchain, DataSource as _, DataSourceTemplate as _},
     components::{
         link_resolver::LinkResolver,
-        store::{StoreError, SubgraphStore},
+        store::{DeploymentLocator, StoreError, SubgraphStore},
     },
     data::{
         graphql::TryFromValue, query::QueryExecutionError,
@@ -44,7 +44,7 @@ use crate::{
         UnresolvedDataSourceTemplate,
     },
     ensure,
-    prelude::{r, CheapClone, Value, ENV_VARS},
+    prelude::{r, CheapClone, Value, BLOCK_NUMBER_MAX, ENV_VARS},
     schema::{InputSchema, SchemaValidationError},
 };
 
@@ -267,6 +267,42 @@ pub struct CreateSubgraphResult {
     pub id: String,
 }
 
+/// Result of creating a subgraph version in the registrar.
+pub struct CreateSubgraphVersionResult {
+    /// The deployment that the new version points to.
+    pub deployment: DeploymentLocator,
+    /// Warnings from linting the manifest of the deployment.
+    pub warnings: Vec<SubgraphManifestWarning>,
+}
+
+/// Something in a subgraph manifest that does not prevent deploying the
+/// subgraph, but is most likely a mistake.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubgraphManifestWarning {
+    /// The name of the data source the warning is about, if any.
+    pub data_source: Option<String>,
+    pub message: String,
+}
+
+impl SubgraphManifestWarning {
+    pub fn new(data_source: Option<&str>, message: impl Into<String>) -> Self {
+        Self {
+            data_source: data_source.map(str::to_owned),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for SubgraphManifestWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.data_source {
+            Some(ds) => write!(f, "data source {}: {}", ds, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum SubgraphRegistrarError {
     #[error("subgraph resolve error: {0}")]
@@ -361,6 +397,16 @@ pub enum SubgraphManifestResolveError {
     ResolveError(#[from] anyhow::Error),
 }
 
+/// Parse the raw YAML of a subgraph manifest. Anchors and aliases are
+/// expanded by the YAML parser, and merge keys (`<<: *alias`) are applied
+/// here, so that the resulting mapping, which is also what gets stored as
+/// the `raw_yaml` of the deployment, is fully expanded.
+pub fn parse_raw_manifest(bytes: &[u8]) -> Result<serde_yaml::Mapping, serde_yaml::Error> {
+    let mut value: serde_yaml::Value = serde_yaml::from_slice(bytes)?;
+    value.apply_merge()?;
+    serde_yaml::from_value(value)
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DataSourceContext(HashMap<Word, Value>);
 
@@ -369,6 +415,10 @@ impl DataSourceContext {
         Self(HashMap::new())
     }
 
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.0.get(&Word::from(key))
+    }
+
     // This collects the entries into an ordered vector so that it can be iterated deterministically.
     pub fn sorted(self) -> Vec<(Word, Value)> {
         let mut v: Vec<_> = self.0.into_iter().collect();
@@ -549,6 +599,81 @@ pub struct BaseSubgraphManifest<C, S, D, T> {
 #[serde(rename_all = "camelCase")]
 pub struct IndexerHints {
     pub history_blocks: Option<BlockNumber>,
+    pub prune: Option<Prune>,
+}
+
+impl IndexerHints {
+    /// The number of blocks of history these hints ask for, if they ask
+    /// for a limit at all
+    pub fn history_blocks(&self) -> Option<BlockNumber> {
+        match self.prune {
+            Some(prune) => Some(prune.history_blocks()),
+            None => self.history_blocks,
+        }
+    }
+}
+
+/// How much history a subgraph wants the node to retain, given as
+/// `prune: auto | never | <blocks>` in the manifest's `indexerHints`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Prune {
+    /// Retain as little history as the node is configured to allow
+    Auto,
+    /// Retain all history
+    Never,
+    /// Retain this many blocks of history
+    Blocks(BlockNumber),
+}
+
+impl Prune {
+    pub fn history_blocks(&self) -> BlockNumber {
+        match self {
+            Prune::Auto => ENV_VARS.min_history_blocks,
+            Prune::Never => BLOCK_NUMBER_MAX,
+            Prune::Blocks(blocks) => (*blocks).max(ENV_VARS.min_history_blocks),
+        }
+    }
+}
+
+impl<'de> de::Deserialize<'de> for Prune {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct PruneVisitor;
+
+        impl<'de> de::Visitor<'de> for PruneVisitor {
+            type Value = Prune;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("'auto', 'never', or a positive number of blocks")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Prune, E> {
+                match value {
+                    "auto" => Ok(Prune::Auto),
+                    "never" => Ok(Prune::Never),
+                    _ => Err(E::invalid_value(de::Unexpected::Str(value), &self)),
+                }
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Prune, E> {
+                match BlockNumber::try_from(value) {
+                    Ok(blocks) if blocks > 0 => Ok(Prune::Blocks(blocks)),
+                    _ => Err(E::invalid_value(de::Unexpected::Signed(value), &self)),
+                }
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Prune, E> {
+                match BlockNumber::try_from(value) {
+                    Ok(blocks) if blocks > 0 => Ok(Prune::Blocks(blocks)),
+                    _ => Err(E::invalid_value(de::Unexpected::Unsigned(value), &self)),
+                }
+            }
+        }
+
+        deserializer.deserialize_any(PruneVisitor)
+    }
 }
 
 /// SubgraphManifest with IPFS links unresolved
@@ -684,7 +809,34 @@ impl<C: Blockchain> SubgraphManifest<C> {
     pub fn history_blocks(&self) -> Option<BlockNumber> {
         self.indexer_hints
             .as_ref()
-            .and_then(|hints| hints.history_blocks)
+            .and_then(|hints| hints.history_blocks())
+    }
+
+    /// Look for things in the manifest that are allowed but most likely a
+    /// mistake. Unlike validation errors, warnings do not prevent the
+    /// subgraph from being deployed.
+    pub fn lint(&self) -> Vec<SubgraphManifestWarning> {
+        let mut warnings = vec![];
+
+        if self.spec_version < SPEC_VERSION_0_0_4 {
+            warnings.push(SubgraphManifestWarning::new(
+                None,
+                format!(
+                    "specVersion {} is deprecated, use specVersion {} or later",
+                    self.spec_version, SPEC_VERSION_0_0_4
+                ),
+            ));
+        }
+
+        for ds in &self.data_sources {
+            warnings.extend(
+                ds.lint()
+                    .into_iter()
+                    .map(|message| SubgraphManifestWarning::new(Some(ds.name()), message)),
+            );
+        }
+
+        warnings
     }
 
     pub fn api_versions(&self) -> impl Iterator<Item = semver::Version> + '_ {
@@ -701,12 +853,14 @@ impl<C: Blockchain> SubgraphManifest<C> {
             .map(|v| v.version().map(|v| v.to_string()))
             .flatten();
 
+        // Kinds are collected into sorted sets so that the status API
+        // reports them in a stable order
         let handler_kinds = self
             .data_sources
             .iter()
             .map(|ds| ds.handler_kinds())
             .flatten()
-            .collect::<HashSet<_>>();
+            .collect::<BTreeSet<_>>();
 
         let features: Vec<String> = self
             .features
@@ -720,7 +874,7 @@ impl<C: Blockchain> SubgraphManifest<C> {
             .data_sources
             .iter()
             .map(|ds| ds.kind().to_string())
-            .collect::<HashSet<_>>();
+            .collect::<BTreeSet<_>>();
 
         let data_source_template_kinds = self
             .templates
@@ -879,6 +1033,14 @@ impl<C: Blockchain> UnresolvedSubgraphManifest<C> {
             );
         }
 
+        if let Some(IndexerHints {
+            history_blocks: Some(_),
+            prune: Some(_),
+        }) = &indexer_hints
+        {
+            bail!("`indexerHints` can not set both `historyBlocks` and `prune`");
+        }
+
         // Check the min_spec_version of each data source against the spec version of the subgraph
         let min_spec_version_mismatch = data_sources
             .iter()
@@ -894,6 +1056,21 @@ impl<C: Blockchain> UnresolvedSubgraphManifest<C> {
             );
         }
 
+        let min_spec_version_mismatch = templates
+            .iter()
+            .filter_map(|template| template.as_onchain())
+            .find(|template| spec_version < template.min_spec_version());
+
+        if let Some(min_spec_version_mismatch) = min_spec_version_mismatch {
+            bail!(
+                "Subgraph `{}` uses spec version {}, but data source template `{}` requires at least version {}",
+                id,
+                spec_version,
+                min_spec_version_mismatch.name(),
+                min_spec_version_mismatch.min_spec_version()
+            );
+        }
+
         Ok(SubgraphManifest {
             id,
             spec_version,
@@ -955,6 +1132,23 @@ impl DeploymentState {
         }
         Ok(())
     }
+
+    /// The latest block that the subgraph has processed that is at least
+    /// `ENV_VARS.reorg_threshold` blocks behind its latest block. Since
+    /// the chain head is never behind the subgraph, a reorg can not revert
+    /// this or any earlier block. Returns `None` if the subgraph has no
+    /// data for such a block
+    pub fn final_block_number(&self) -> Option<BlockNumber> {
+        let number = self
+            .latest_block
+            .number
+            .saturating_sub(ENV_VARS.reorg_threshold);
+        if number < self.earliest_block_number || number < 0 {
+            None
+        } else {
+            Some(number)
+        }
+    }
 }
 
 fn display_vector(input: &[impl std::fmt::Display]) -> impl std::fmt::Display {
@@ -1012,3 +1206,40 @@ fn test_display_vector() {
         format!("{}", manifest_validation_error)
     )
 }
+
+#[test]
+fn test_parse_raw_manifest_expands_merge_keys() {
+    use serde_yaml::Value;
+
+    const MANIFEST: &str = "
+specVersion: 0.0.4
+dataSources:
+  - &token
+    kind: ethereum/contract
+    name: TokenA
+    network: mainnet
+    source:
+      address: '0x0000000000000000000000000000000000000001'
+      abi: Token
+  - <<: *token
+    name: TokenB
+";
+
+    let raw = parse_raw_manifest(MANIFEST.as_bytes()).unwrap();
+    let data_sources = raw
+        .get(&Value::from("dataSources"))
+        .and_then(Value::as_sequence)
+        .unwrap();
+    assert_eq!(2, data_sources.len());
+
+    let b = &data_sources[1];
+    assert_eq!(Some("TokenB"), b["name"].as_str());
+    assert_eq!(Some("mainnet"), b["network"].as_str());
+    assert_eq!(Some("Token"), b["source"]["abi"].as_str());
+    assert!(b.get("<<").is_none());
+
+    // The expanded manifest no longer mentions anchors or merge keys
+    let yaml = serde_yaml::to_string(&raw).unwrap();
+    assert!(!yaml.contains("<<"));
+    assert!(!yaml.contains('&'));
+}
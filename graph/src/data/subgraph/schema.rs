@@ -98,6 +98,27 @@ impl TryFromValue for SubgraphHealth {
     }
 }
 
+/// Metadata for a deployment that is passed in when the subgraph is
+/// deployed rather than read from its manifest
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct DeploymentMetadata {
+    /// A label for this version of the subgraph, like `v1.2.0`
+    pub version_label: Option<String>,
+    /// If set, overrides the `description` from the manifest
+    pub description: Option<String>,
+    /// If set, overrides the `repository` from the manifest
+    pub repository: Option<String>,
+}
+
+/// Where to put a new deployment instead of where the deployment rules
+/// would put it. The shard and node must still be allowed by the rule that
+/// matches the deployment
+#[derive(Clone, Debug, Default)]
+pub struct DeploymentPlacement {
+    pub shard: Option<String>,
+    pub node: Option<NodeId>,
+}
+
 /// The deployment data that is needed to create a deployment
 pub struct DeploymentCreate {
     pub manifest: SubgraphManifestEntity,
@@ -106,6 +127,7 @@ pub struct DeploymentCreate {
     pub graft_block: Option<BlockPtr>,
     pub debug_fork: Option<DeploymentHash>,
     pub history_blocks: Option<i32>,
+    pub placement: DeploymentPlacement,
 }
 
 impl DeploymentCreate {
@@ -121,6 +143,7 @@ impl DeploymentCreate {
             graft_block: None,
             debug_fork: None,
             history_blocks: None,
+            placement: DeploymentPlacement::default(),
         }
     }
 
@@ -137,11 +160,37 @@ impl DeploymentCreate {
         self
     }
 
+    pub fn metadata(mut self, metadata: DeploymentMetadata) -> Self {
+        let DeploymentMetadata {
+            version_label,
+            description,
+            repository,
+        } = metadata;
+        self.manifest.version_label = version_label;
+        if description.is_some() {
+            self.manifest.description = description;
+        }
+        if repository.is_some() {
+            self.manifest.repository = repository;
+        }
+        self
+    }
+
+    pub fn placement(mut self, placement: DeploymentPlacement) -> Self {
+        self.placement = placement;
+        self
+    }
+
     pub fn debug(mut self, fork: Option<DeploymentHash>) -> Self {
         self.debug_fork = fork;
         self
     }
 
+    pub fn files(mut self, files: Vec<ManifestFile>) -> Self {
+        self.manifest.files = files;
+        self
+    }
+
     pub fn entities_with_causality_region(
         mut self,
         entities_with_causality_region: BTreeSet<EntityType>,
@@ -154,6 +203,14 @@ impl DeploymentCreate {
 
 /// The representation of a subgraph deployment when reading an existing
 /// deployment
+/// A file that was fetched while resolving the manifest of a deployment;
+/// `link` contains the CID of the file
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestFile {
+    pub link: String,
+    pub size: u64,
+}
+
 #[derive(Debug)]
 pub struct SubgraphDeploymentEntity {
     pub manifest: SubgraphManifestEntity,
@@ -180,11 +237,15 @@ pub struct SubgraphManifestEntity {
     pub spec_version: String,
     pub description: Option<String>,
     pub repository: Option<String>,
+    pub version_label: Option<String>,
     pub features: Vec<String>,
     pub schema: String,
     pub raw_yaml: Option<String>,
     pub entities_with_causality_region: Vec<EntityType>,
     pub history_blocks: BlockNumber,
+    /// The files that were fetched and verified when the manifest was
+    /// resolved
+    pub files: Vec<ManifestFile>,
 }
 
 impl SubgraphManifestEntity {
@@ -197,11 +258,13 @@ impl SubgraphManifestEntity {
             spec_version: manifest.spec_version.to_string(),
             description: manifest.description.clone(),
             repository: manifest.repository.clone(),
+            version_label: None,
             features: manifest.features.iter().map(|f| f.to_string()).collect(),
             schema: manifest.schema.document_string(),
             raw_yaml: Some(raw_yaml),
             entities_with_causality_region,
             history_blocks: BLOCK_NUMBER_MAX,
+            files: Vec::new(),
         }
     }
 
@@ -244,17 +307,52 @@ pub struct SubgraphError {
     pub message: String,
     pub block_ptr: Option<BlockPtr>,
     pub handler: Option<String>,
+    /// The name of the data source whose handler caused the error
+    pub data_source: Option<String>,
+    /// The hash of the transaction that triggered the failing handler, for
+    /// triggers that come from a transaction
+    pub transaction_hash: Option<String>,
 
     // `true` if we are certain the error is deterministic. If in doubt, this is `false`.
     pub deterministic: bool,
 }
 
+impl SubgraphError {
+    /// Truncate `message` to at most `GRAPH_MAX_ERROR_MESSAGE_LENGTH`
+    /// bytes so that errors with a huge message, like a deep host stack,
+    /// can still be stored and reported
+    pub fn truncate_message(message: String) -> String {
+        Self::truncate_message_to(message, ENV_VARS.mappings.max_error_message_length)
+    }
+
+    fn truncate_message_to(mut message: String, max_len: usize) -> String {
+        const ELLIPSIS: &str = "...";
+
+        if message.len() <= max_len {
+            return message;
+        }
+        let mut len = max_len.saturating_sub(ELLIPSIS.len());
+        while !message.is_char_boundary(len) {
+            len -= 1;
+        }
+        message.truncate(len);
+        message.push_str(ELLIPSIS);
+        message
+    }
+}
+
 impl Display for SubgraphError {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         write!(f, "{}", self.message)?;
         if let Some(handler) = &self.handler {
             write!(f, " in handler `{}`", handler)?;
         }
+        if let Some(data_source) = &self.data_source {
+            write!(f, " of data source `{}`", data_source)?;
+        }
+        if let Some(transaction_hash) = &self.transaction_hash {
+            write!(f, " for transaction {}", transaction_hash)?;
+        }
         if let Some(block_ptr) = &self.block_ptr {
             write!(f, " at block {}", block_ptr)?;
         }
@@ -267,7 +365,9 @@ impl_stable_hash!(SubgraphError {
     message,
     block_ptr,
     handler,
-    deterministic
+    deterministic,
+    data_source,
+    transaction_hash
 });
 
 pub fn generate_entity_id() -> String {
@@ -282,3 +382,26 @@ pub fn generate_entity_id() -> String {
     // and without spending bits on a version identifier.
     hex::encode(id_bytes)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::SubgraphError;
+
+    #[test]
+    fn truncate_message() {
+        let truncate = |message: &str, max_len| {
+            SubgraphError::truncate_message_to(message.to_string(), max_len)
+        };
+
+        assert_eq!("short", truncate("short", 10));
+        assert_eq!("exactly 10", truncate("exactly 10", 10));
+        assert_eq!("too lo...", truncate("too long by far", 9));
+
+        // 'ü' takes two bytes, and the cut at 6 would split it
+        let message = "abcdeü and more";
+        let truncated = truncate(message, 9);
+        assert_eq!("abcde...", truncated);
+        assert!(truncated.len() <= 9);
+        assert_eq!("abcdeü...", truncate(message, 10));
+    }
+}
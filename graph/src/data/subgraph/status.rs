@@ -16,6 +16,9 @@ pub enum Filter {
     Deployments(Vec<String>),
     /// Get the status of all deployments with the given ids
     DeploymentIds(Vec<DeploymentId>),
+    /// Get the status of active deployments in the order of their IPFS
+    /// hashes, skipping the first `skip` and returning at most `first`
+    Page { skip: usize, first: Option<usize> },
 }
 
 /// Light wrapper around `EthereumBlockPointer` that is compatible with GraphQL values.
@@ -31,6 +34,10 @@ impl EthereumBlock {
         self.0
     }
 
+    pub fn ptr(&self) -> &BlockPtr {
+        &self.0
+    }
+
     pub fn number(&self) -> i32 {
         self.0.number
     }
@@ -67,6 +74,17 @@ pub struct ChainInfo {
     pub latest_block: Option<EthereumBlock>,
 }
 
+impl ChainInfo {
+    /// How many blocks the subgraph is behind the chain head, or `None` if
+    /// either the chain head or the subgraph's latest block is not known
+    pub fn blocks_behind(&self) -> Option<BlockNumber> {
+        match (&self.chain_head_block, &self.latest_block) {
+            (Some(head), Some(latest)) => Some((head.number() - latest.number()).max(0)),
+            _ => None,
+        }
+    }
+}
+
 impl IntoValue for ChainInfo {
     fn into_value(self) -> r::Value {
         let ChainInfo {
@@ -110,10 +128,20 @@ pub struct Info {
 
     pub entity_count: u64,
 
+    /// The approximate size of the deployment's tables and indexes in
+    /// bytes, if it is known
+    pub storage_bytes: Option<u64>,
+
     /// ID of the Graph Node that the subgraph is indexed by.
     pub node: Option<String>,
 
     pub history_blocks: i32,
+
+    /// Metadata passed in when the deployment was created, or taken from
+    /// its manifest
+    pub version_label: Option<String>,
+    pub description: Option<String>,
+    pub repository: Option<String>,
 }
 
 impl IntoValue for Info {
@@ -123,6 +151,7 @@ impl IntoValue for Info {
             subgraph,
             chains,
             entity_count,
+            storage_bytes,
             fatal_error,
             health,
             paused,
@@ -130,6 +159,9 @@ impl IntoValue for Info {
             non_fatal_errors,
             synced,
             history_blocks,
+            version_label,
+            description,
+            repository,
         } = self;
 
         fn subgraph_error_to_value(subgraph_error: SubgraphError) -> r::Value {
@@ -138,6 +170,8 @@ impl IntoValue for Info {
                 message,
                 block_ptr,
                 handler,
+                data_source,
+                transaction_hash,
                 deterministic,
             } = subgraph_error;
 
@@ -146,6 +180,8 @@ impl IntoValue for Info {
                 subgraphId: subgraph_id.to_string(),
                 message: message,
                 handler: handler,
+                dataSource: data_source,
+                transactionHash: transaction_hash,
                 block: object! {
                     __typename: "Block",
                     number: block_ptr.as_ref().map(|x| x.number),
@@ -171,8 +207,12 @@ impl IntoValue for Info {
             nonFatalErrors: non_fatal_errors,
             chains: chains.into_iter().map(|chain| chain.into_value()).collect::<Vec<_>>(),
             entityCount: format!("{}", entity_count),
+            storageBytes: storage_bytes.map(|bytes| format!("{}", bytes)),
             node: node,
             historyBlocks: history_blocks,
+            versionLabel: version_label,
+            description: description,
+            repository: repository,
         }
     }
 }
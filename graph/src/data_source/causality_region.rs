@@ -5,6 +5,7 @@ use diesel::{
     types::{FromSql, ToSql},
     FromSqlRow,
 };
+use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::io;
 
@@ -21,7 +22,10 @@ use crate::components::subgraph::Entity;
 /// This necessary for determinism because offchain data sources don't have a deterministic order of
 /// execution, for example an IPFS file may become available at any point in time. The isolation
 /// rules make the indexing result reproducible, given a set of available files.
-#[derive(Debug, Copy, Clone, PartialEq, Eq, FromSqlRow, Hash, PartialOrd, Ord)]
+#[derive(
+    Debug, Copy, Clone, PartialEq, Eq, FromSqlRow, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(transparent)]
 pub struct CausalityRegion(i32);
 
 impl fmt::Display for CausalityRegion {
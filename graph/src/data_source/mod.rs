@@ -19,7 +19,7 @@ use crate::{
     prelude::{CheapClone as _, DataSourceContext},
     schema::{EntityType, InputSchema},
 };
-use anyhow::Error;
+use anyhow::{anyhow, Error};
 use semver::Version;
 use serde::{de::IntoDeserializer as _, Deserialize, Deserializer};
 use slog::{Logger, SendSyncRefUnwindSafeKV};
@@ -185,6 +185,20 @@ impl<C: Blockchain> DataSource<C> {
         }
     }
 
+    pub fn is_batch_handler(&self, handler: &str) -> bool {
+        match self {
+            Self::Onchain(ds) => ds.is_batch_handler(handler),
+            Self::Offchain(_) => false,
+        }
+    }
+
+    pub fn has_batch_handlers(&self) -> bool {
+        match self {
+            Self::Onchain(ds) => ds.has_batch_handlers(),
+            Self::Offchain(_) => false,
+        }
+    }
+
     pub fn match_and_decode(
         &self,
         trigger: &TriggerData<C>,
@@ -242,6 +256,23 @@ impl<C: Blockchain> DataSource<C> {
         }
     }
 
+    pub fn lint(&self) -> Vec<String> {
+        match self {
+            Self::Onchain(ds) => {
+                let mut warnings = ds.lint();
+                if ds.address().is_some() && ds.start_block() == 0 {
+                    warnings.push(
+                        "data source has no `startBlock`, indexing will start at the \
+                         genesis block"
+                            .to_string(),
+                    );
+                }
+                warnings
+            }
+            Self::Offchain(_) => vec![],
+        }
+    }
+
     pub fn causality_region(&self) -> CausalityRegion {
         match self {
             Self::Onchain(_) => CausalityRegion::ONCHAIN,
@@ -438,6 +469,31 @@ impl<T> TriggerWithHandler<T> {
     }
 }
 
+impl<C: Blockchain> TriggerWithHandler<MappingTrigger<C>> {
+    /// Combines `triggers`, which must all be for the same batch handler,
+    /// into one trigger for that handler. See `MappingTriggerTrait::batch`.
+    pub fn batch(triggers: Vec<Self>) -> Result<Self, Error> {
+        let first = triggers
+            .first()
+            .ok_or_else(|| anyhow!("a batch needs at least one trigger"))?;
+        let handler = first.handler.clone();
+        let block_ptr = first.block_ptr.clone();
+        let triggers = triggers
+            .into_iter()
+            .map(|trigger| match trigger.trigger {
+                MappingTrigger::Onchain(trigger) => Ok(trigger),
+                MappingTrigger::Offchain(_) => Err(anyhow!("offchain triggers can not be batched")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let trigger = C::MappingTrigger::batch(triggers)?;
+        Ok(TriggerWithHandler::new(
+            MappingTrigger::Onchain(trigger),
+            handler,
+            block_ptr,
+        ))
+    }
+}
+
 #[derive(Debug)]
 pub enum TriggerData<C: Blockchain> {
     Onchain(C::TriggerData),
@@ -466,6 +522,13 @@ impl<C: Blockchain> MappingTrigger<C> {
             Self::Offchain(_) => None, // TODO: Add error context for offchain triggers
         }
     }
+
+    pub fn transaction_hash(&self) -> Option<String> {
+        match self {
+            Self::Onchain(trigger) => trigger.transaction_hash(),
+            Self::Offchain(_) => None,
+        }
+    }
 }
 
 macro_rules! clone_data_source {
@@ -4,6 +4,7 @@ use std::{
         atomic::{AtomicU64, Ordering},
         Arc,
     },
+    time::{Duration, Instant},
 };
 
 use prometheus::IntCounterVec;
@@ -14,7 +15,17 @@ use crate::{components::metrics::MetricsRegistry, data::value::Word};
 /// ProviderCount is the underlying structure to keep the count,
 /// we require that all the hosts are known ahead of time, this way we can
 /// avoid locking since we don't need to modify the entire struture.
-type ProviderCount = Arc<HashMap<Provider, AtomicU64>>;
+type ProviderCount = Arc<HashMap<Provider, ProviderState>>;
+
+#[derive(Debug, Default)]
+struct ProviderState {
+    /// The number of requests that failed since the last request that
+    /// succeeded
+    errors: AtomicU64,
+    /// When the last request failed, as milliseconds since
+    /// `EndpointMetrics::start` plus one; 0 if no request has failed yet
+    last_failure: AtomicU64,
+}
 
 /// Provider represents label of the underlying endpoint.
 pub type Provider = Word;
@@ -67,6 +78,7 @@ pub struct EndpointMetrics {
     logger: Logger,
     providers: ProviderCount,
     counter: Box<IntCounterVec>,
+    start: Instant,
 }
 
 impl std::fmt::Debug for EndpointMetrics {
@@ -84,7 +96,7 @@ impl EndpointMetrics {
         let providers = Arc::new(HashMap::from_iter(
             providers
                 .iter()
-                .map(|h| (Provider::from(h.as_ref()), AtomicU64::new(0))),
+                .map(|h| (Provider::from(h.as_ref()), ProviderState::default())),
         ));
 
         let counter = registry
@@ -99,6 +111,7 @@ impl EndpointMetrics {
             logger,
             providers,
             counter,
+            start: Instant::now(),
         }
     }
 
@@ -131,8 +144,8 @@ impl EndpointMetrics {
 
     pub fn success(&self, labels: &RequestLabels) {
         match self.providers.get(&labels.provider) {
-            Some(count) => {
-                count.store(0, Ordering::Relaxed);
+            Some(state) => {
+                state.errors.store(0, Ordering::Relaxed);
             }
             None => warn!(
                 &self.logger,
@@ -145,8 +158,10 @@ impl EndpointMetrics {
 
     pub fn failure(&self, labels: &RequestLabels) {
         match self.providers.get(&labels.provider) {
-            Some(count) => {
-                count.fetch_add(1, Ordering::Relaxed);
+            Some(state) => {
+                state.errors.fetch_add(1, Ordering::Relaxed);
+                let now = self.start.elapsed().as_millis() as u64 + 1;
+                state.last_failure.store(now, Ordering::Relaxed);
             }
             None => warn!(
                 &self.logger,
@@ -164,9 +179,31 @@ impl EndpointMetrics {
     pub fn get_count(&self, provider: &Provider) -> u64 {
         self.providers
             .get(provider)
-            .map(|c| c.load(Ordering::Relaxed))
+            .map(|state| state.errors.load(Ordering::Relaxed))
             .unwrap_or(0)
     }
+
+    /// Returns how long ago the most recent failed request to `provider`
+    /// happened, or `None` if no request to it has failed yet
+    pub fn since_last_failure(&self, provider: &Provider) -> Option<Duration> {
+        let last_failure = self
+            .providers
+            .get(provider)
+            .map(|state| state.last_failure.load(Ordering::Relaxed))
+            .filter(|last_failure| *last_failure > 0)?;
+        let last_failure = self.start + Duration::from_millis(last_failure - 1);
+        Some(last_failure.elapsed())
+    }
+
+    /// Returns `true` if there are no providers, or if at least one
+    /// provider's most recent request succeeded
+    pub fn has_healthy_provider(&self) -> bool {
+        self.providers.is_empty()
+            || self
+                .providers
+                .values()
+                .any(|state| state.errors.load(Ordering::Relaxed) == 0)
+    }
 }
 
 #[cfg(test)]
@@ -197,5 +234,27 @@ mod test {
         assert_eq!(metrics.get_count(&a), 1);
         assert_eq!(metrics.get_count(&b), 2);
         assert_eq!(metrics.get_count(&c), 0);
+
+        assert!(metrics.since_last_failure(&a).is_some());
+        assert!(metrics.since_last_failure(&c).is_none());
+    }
+
+    #[tokio::test]
+    async fn healthy_provider() {
+        let (a, b): (Provider, Provider) = ("a".into(), "b".into());
+        let hosts: &[&str] = &[&a, &b];
+        let logger = Logger::root(Discard, o!());
+
+        let metrics = EndpointMetrics::new(logger, hosts, Arc::new(MetricsRegistry::mock()));
+        assert!(metrics.has_healthy_provider());
+
+        metrics.report_for_test(&a, false);
+        assert!(metrics.has_healthy_provider());
+        metrics.report_for_test(&b, false);
+        assert!(!metrics.has_healthy_provider());
+        metrics.report_for_test(&b, true);
+        assert!(metrics.has_healthy_provider());
+
+        assert!(EndpointMetrics::mock().has_healthy_provider());
     }
 }
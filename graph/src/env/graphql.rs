@@ -56,9 +56,22 @@ pub struct EnvVarsGraphQl {
     /// Set by the environment variable `GRAPH_GRAPHQL_QUERY_TIMEOUT` (expressed in
     /// seconds). No default value is provided.
     pub query_timeout: Option<Duration>,
+    /// Set by the environment variable `GRAPH_GRAPHQL_BLOCK_WAIT_TIMEOUT`
+    /// (expressed in seconds). No default value is provided.
+    pub block_wait_timeout: Option<Duration>,
+    /// Queries that take longer than this are logged as slow queries and
+    /// show up in the `slowQueries` of the index node status API.
+    ///
+    /// Set by the environment variable `GRAPH_GRAPHQL_SLOW_QUERY_MS`
+    /// (expressed in milliseconds). No default value is provided.
+    pub slow_query_threshold: Option<Duration>,
     /// Set by the environment variable `GRAPH_GRAPHQL_MAX_COMPLEXITY`. No
     /// default value is provided.
     pub max_complexity: Option<u64>,
+    /// Set by the environment variable `GRAPH_GRAPHQL_FILTER_COMPLEXITY`.
+    /// How much each condition in the `where` filter of a collection field
+    /// adds to the complexity of a query. The default value is 0.
+    pub filter_complexity: u64,
     /// Set by the environment variable `GRAPH_GRAPHQL_MAX_DEPTH`. The default
     /// value is 255.
     pub max_depth: u8,
@@ -94,6 +107,18 @@ pub struct EnvVarsGraphQl {
     /// header `X-GraphTraceQuery` set to this value will include a trace of
     /// the SQL queries that were run.
     pub query_trace_token: String,
+    /// Set by the flag `GRAPH_GRAPHQL_ERROR_CODES`. Off by default.
+    /// Include a machine-readable `code` in the `extensions` of every
+    /// GraphQL error
+    pub error_codes: bool,
+    /// Set by the flag `GRAPH_GRAPHQL_DISABLE_RESPONSE_COMPRESSION`. Off by
+    /// default. Send query results uncompressed even if the client accepts
+    /// compressed responses
+    pub disable_response_compression: bool,
+    /// Set by the flag `GRAPH_GRAPHQL_LIST_SUBGRAPHS`. Off by default.
+    /// Serve the names and deployments of all subgraphs at `/subgraphs` on
+    /// the query port, which GraphiQL uses to switch between subgraphs
+    pub list_subgraphs: bool,
 }
 
 // This does not print any values avoid accidentally leaking any sensitive env vars
@@ -127,7 +152,10 @@ impl From<InnerGraphQl> for EnvVarsGraphQl {
             query_cache_max_mem: x.query_cache_max_mem_in_mb.0 * 1000 * 1000,
             query_cache_stale_period: x.query_cache_stale_period,
             query_timeout: x.query_timeout_in_secs.map(Duration::from_secs),
+            block_wait_timeout: x.block_wait_timeout_in_secs.map(Duration::from_secs),
+            slow_query_threshold: x.slow_query_threshold_in_ms.map(Duration::from_millis),
             max_complexity: x.max_complexity.map(|x| x.0),
+            filter_complexity: x.filter_complexity,
             max_depth: x.max_depth.0,
             max_first: x.max_first,
             max_skip: x.max_skip.0,
@@ -138,6 +166,9 @@ impl From<InnerGraphQl> for EnvVarsGraphQl {
             disable_bool_filters: x.disable_bool_filters.0,
             disable_child_sorting: x.disable_child_sorting.0,
             query_trace_token: x.query_trace_token,
+            error_codes: x.error_codes.0,
+            disable_response_compression: x.disable_response_compression.0,
+            list_subgraphs: x.list_subgraphs.0,
         }
     }
 }
@@ -165,8 +196,14 @@ pub struct InnerGraphQl {
     query_cache_stale_period: u64,
     #[envconfig(from = "GRAPH_GRAPHQL_QUERY_TIMEOUT")]
     query_timeout_in_secs: Option<u64>,
+    #[envconfig(from = "GRAPH_GRAPHQL_BLOCK_WAIT_TIMEOUT")]
+    block_wait_timeout_in_secs: Option<u64>,
+    #[envconfig(from = "GRAPH_GRAPHQL_SLOW_QUERY_MS")]
+    slow_query_threshold_in_ms: Option<u64>,
     #[envconfig(from = "GRAPH_GRAPHQL_MAX_COMPLEXITY")]
     max_complexity: Option<NoUnderscores<u64>>,
+    #[envconfig(from = "GRAPH_GRAPHQL_FILTER_COMPLEXITY", default = "0")]
+    filter_complexity: u64,
     #[envconfig(from = "GRAPH_GRAPHQL_MAX_DEPTH", default = "")]
     max_depth: WithDefaultUsize<u8, { u8::MAX as usize }>,
     #[envconfig(from = "GRAPH_GRAPHQL_MAX_FIRST", default = "1000")]
@@ -187,4 +224,10 @@ pub struct InnerGraphQl {
     pub disable_child_sorting: EnvVarBoolean,
     #[envconfig(from = "GRAPH_GRAPHQL_TRACE_TOKEN", default = "")]
     query_trace_token: String,
+    #[envconfig(from = "GRAPH_GRAPHQL_ERROR_CODES", default = "false")]
+    pub error_codes: EnvVarBoolean,
+    #[envconfig(from = "GRAPH_GRAPHQL_DISABLE_RESPONSE_COMPRESSION", default = "false")]
+    pub disable_response_compression: EnvVarBoolean,
+    #[envconfig(from = "GRAPH_GRAPHQL_LIST_SUBGRAPHS", default = "false")]
+    pub list_subgraphs: EnvVarBoolean,
 }
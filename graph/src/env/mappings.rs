@@ -1,6 +1,8 @@
 use std::fmt;
+use std::path::PathBuf;
 
 use super::*;
+use crate::components::store::WriteConflictMode;
 
 #[derive(Clone)]
 pub struct EnvVarsMapping {
@@ -15,17 +17,36 @@ pub struct EnvVarsMapping {
     /// Set by the environment variable `GRAPH_ENTITY_CACHE_SIZE` (expressed in
     /// kilobytes). The default value is 10 megabytes.
     pub entity_cache_size: usize,
+    /// How to treat a handler that sets an entity field to a different
+    /// value than another handler did earlier in the same block, without
+    /// reading the entity first.
+    ///
+    /// Set by the environment variable `GRAPH_ENTITY_WRITE_CONFLICTS`
+    /// (`off`, `warn` or `strict`). The default value is `off`.
+    pub entity_write_conflicts: WriteConflictMode,
     /// Set by the environment variable `GRAPH_MAX_API_VERSION`. The default
     /// value is `0.0.8`.
     pub max_api_version: Version,
     /// Set by the environment variable `GRAPH_MAPPING_HANDLER_TIMEOUT`
     /// (expressed in seconds). No default is provided.
     pub timeout: Option<Duration>,
+    /// How often each deployment logs a summary of the handlers that took
+    /// the most time.
+    ///
+    /// Set by the environment variable `GRAPH_HANDLER_STATS_LOG_INTERVAL`
+    /// (expressed in seconds). Summaries are not logged if this is not set.
+    pub handler_stats_log_interval: Option<Duration>,
     /// Maximum stack size for the WASM runtime.
     ///
     /// Set by the environment variable `GRAPH_RUNTIME_MAX_STACK_SIZE`
     /// (expressed in bytes). The default value is 512KiB.
     pub max_stack_size: usize,
+    /// Maximum length of the message of a subgraph error that is stored and
+    /// reported through the status API; longer messages are truncated.
+    ///
+    /// Set by the environment variable `GRAPH_MAX_ERROR_MESSAGE_LENGTH`
+    /// (expressed in bytes). The default value is 10000.
+    pub max_error_message_length: usize,
 
     /// Set by the environment variable `GRAPH_MAX_IPFS_CACHE_FILE_SIZE`
     /// (expressed in bytes). The default value is 1MiB.
@@ -43,6 +64,18 @@ pub struct EnvVarsMapping {
     /// Set by the environment variable `GRAPH_MAX_IPFS_MAP_FILE_SIZE_LIMIT`
     /// (expressed in bytes). The default value is 256MiB.
     pub max_ipfs_map_file_size: usize,
+    /// The directory in which `ipfs.map` saves its progress so that it can
+    /// pick up where it left off when the node restarts while a file is
+    /// being processed. Progress is not saved if this is not set.
+    ///
+    /// Set by the environment variable `GRAPH_IPFS_MAP_CHECKPOINT_DIR`.
+    pub ipfs_map_checkpoint_dir: Option<PathBuf>,
+    /// How many JSON values `ipfs.map` processes between saving its
+    /// progress.
+    ///
+    /// Set by the environment variable
+    /// `GRAPH_IPFS_MAP_CHECKPOINT_INTERVAL`. The default value is 10000.
+    pub ipfs_map_checkpoint_interval: usize,
     /// Sets the `ipfs.cat` file size limit.
     ///
     /// Set by the environment variable `GRAPH_MAX_IPFS_FILE_BYTES` (expressed in
@@ -71,15 +104,22 @@ impl From<InnerMappingHandlers> for EnvVarsMapping {
         Self {
             entity_cache_dead_weight: x.entity_cache_dead_weight.0,
             entity_cache_size: x.entity_cache_size_in_kb * 1000,
+            entity_write_conflicts: x.entity_write_conflicts,
 
             max_api_version: x.max_api_version,
             timeout: x.mapping_handler_timeout_in_secs.map(Duration::from_secs),
+            handler_stats_log_interval: x
+                .handler_stats_log_interval_in_secs
+                .map(Duration::from_secs),
             max_stack_size: x.runtime_max_stack_size.0 .0,
+            max_error_message_length: x.max_error_message_length,
 
             max_ipfs_cache_file_size: x.max_ipfs_cache_file_size.0,
             max_ipfs_cache_size: x.max_ipfs_cache_size,
             ipfs_timeout: Duration::from_secs(x.ipfs_timeout_in_secs),
             max_ipfs_map_file_size: x.max_ipfs_map_file_size.0,
+            ipfs_map_checkpoint_dir: x.ipfs_map_checkpoint_dir,
+            ipfs_map_checkpoint_interval: x.ipfs_map_checkpoint_interval.max(1),
             max_ipfs_file_bytes: x.max_ipfs_file_bytes.0,
             ipfs_request_limit: x.ipfs_request_limit,
             allow_non_deterministic_ipfs: x.allow_non_deterministic_ipfs.0,
@@ -93,12 +133,18 @@ pub struct InnerMappingHandlers {
     entity_cache_dead_weight: EnvVarBoolean,
     #[envconfig(from = "GRAPH_ENTITY_CACHE_SIZE", default = "10000")]
     entity_cache_size_in_kb: usize,
+    #[envconfig(from = "GRAPH_ENTITY_WRITE_CONFLICTS", default = "off")]
+    entity_write_conflicts: WriteConflictMode,
     #[envconfig(from = "GRAPH_MAX_API_VERSION", default = "0.0.8")]
     max_api_version: Version,
     #[envconfig(from = "GRAPH_MAPPING_HANDLER_TIMEOUT")]
     mapping_handler_timeout_in_secs: Option<u64>,
+    #[envconfig(from = "GRAPH_HANDLER_STATS_LOG_INTERVAL")]
+    handler_stats_log_interval_in_secs: Option<u64>,
     #[envconfig(from = "GRAPH_RUNTIME_MAX_STACK_SIZE", default = "")]
     runtime_max_stack_size: WithDefaultUsize<NoUnderscores<usize>, { 512 * 1024 }>,
+    #[envconfig(from = "GRAPH_MAX_ERROR_MESSAGE_LENGTH", default = "10000")]
+    max_error_message_length: usize,
 
     // IPFS.
     #[envconfig(from = "GRAPH_MAX_IPFS_CACHE_FILE_SIZE", default = "")]
@@ -109,6 +155,10 @@ pub struct InnerMappingHandlers {
     ipfs_timeout_in_secs: u64,
     #[envconfig(from = "GRAPH_MAX_IPFS_MAP_FILE_SIZE", default = "")]
     max_ipfs_map_file_size: WithDefaultUsize<usize, { 256 * 1024 * 1024 }>,
+    #[envconfig(from = "GRAPH_IPFS_MAP_CHECKPOINT_DIR")]
+    ipfs_map_checkpoint_dir: Option<PathBuf>,
+    #[envconfig(from = "GRAPH_IPFS_MAP_CHECKPOINT_INTERVAL", default = "10000")]
+    ipfs_map_checkpoint_interval: usize,
     #[envconfig(from = "GRAPH_MAX_IPFS_FILE_BYTES", default = "")]
     max_ipfs_file_bytes: WithDefaultUsize<usize, { 25 * 1024 * 1024 }>,
     #[envconfig(from = "GRAPH_IPFS_REQUEST_LIMIT", default = "100")]
@@ -109,6 +109,23 @@ pub struct EnvVarsStore {
     /// is 10_000 which corresponds to 10MB. Setting this to 0 disables
     /// write batching.
     pub write_batch_size: usize,
+    /// Whether to add the changes for a block to a write that is still
+    /// waiting in the queue once a subgraph is synced instead of queueing
+    /// a write for each block. This helps on chains that produce several
+    /// blocks per second, where the writer otherwise can't keep up with
+    /// one write per block. Set by `GRAPH_STORE_MERGE_QUEUED_WRITES`. The
+    /// default is `false`
+    pub merge_queued_writes: bool,
+    /// How many writes to entities to count for the write statistics of
+    /// a deployment, e.g., 10 for one in every ten writes. Set by
+    /// `GRAPH_STORE_WRITE_STATS_SAMPLE_RATE`. The default is 10, and 0
+    /// turns off tracking of individual entities
+    pub write_stats_sample_rate: usize,
+    /// How many rows of each table of a deployment to read to warm up
+    /// the database caches before a copy of the deployment starts serving
+    /// queries. Set by `GRAPH_STORE_WARMUP_ROWS`. The default is 0, which
+    /// turns warming up off
+    pub warmup_rows: usize,
     /// Whether to create GIN indexes for array attributes. Set by
     /// `GRAPH_STORE_CREATE_GIN_INDEXES`. The default is `false`
     pub create_gin_indexes: bool,
@@ -116,6 +133,25 @@ pub struct EnvVarsStore {
     pub use_brin_for_all_query_types: bool,
     /// Temporary env var to disable certain lookups in the chain store
     pub disable_block_cache_for_lookup: bool,
+    /// How often a standby instance of a deployment checks whether it can
+    /// take over writing to the deployment. Set by
+    /// `GRAPH_STORE_STANDBY_POLL_INTERVAL` in seconds. The default is 5s.
+    pub standby_poll_interval: Duration,
+    /// SQL queries for GraphQL queries that take longer than this are
+    /// run again with `EXPLAIN (ANALYZE, BUFFERS)` in the background, and
+    /// the query plan is logged. Set by
+    /// `GRAPH_STORE_EXPLAIN_SLOW_QUERIES_MS`. Off by default.
+    pub explain_slow_queries: Option<Duration>,
+    /// The fraction of slow queries that are explained. Set by
+    /// `GRAPH_STORE_EXPLAIN_SAMPLE_RATE`. The default is 0.1
+    pub explain_sample_rate: f64,
+    /// Explain at most one slow query per shard this often. Set by
+    /// `GRAPH_STORE_EXPLAIN_INTERVAL` in seconds. The default is 60s.
+    pub explain_interval: Duration,
+    /// Entries in the call cache of a chain that are more than this many
+    /// blocks behind the chain head are removed periodically. Set by
+    /// `GRAPH_STORE_CALL_CACHE_RETENTION_BLOCKS`. Off by default.
+    pub call_cache_retention_blocks: Option<i32>,
 }
 
 // This does not print any values avoid accidentally leaking any sensitive env vars
@@ -157,9 +193,17 @@ impl From<InnerStore> for EnvVarsStore {
             history_slack_factor: x.history_slack_factor.0,
             write_batch_duration: Duration::from_secs(x.write_batch_duration_in_secs),
             write_batch_size: x.write_batch_size * 1_000,
+            merge_queued_writes: x.merge_queued_writes,
+            write_stats_sample_rate: x.write_stats_sample_rate,
+            warmup_rows: x.warmup_rows,
             create_gin_indexes: x.create_gin_indexes,
             use_brin_for_all_query_types: x.use_brin_for_all_query_types,
             disable_block_cache_for_lookup: x.disable_block_cache_for_lookup,
+            standby_poll_interval: Duration::from_secs(x.standby_poll_interval_in_secs),
+            explain_slow_queries: x.explain_slow_queries_in_millis.map(Duration::from_millis),
+            explain_sample_rate: x.explain_sample_rate.0,
+            explain_interval: Duration::from_secs(x.explain_interval_in_secs),
+            call_cache_retention_blocks: x.call_cache_retention_blocks,
         }
     }
 }
@@ -213,12 +257,28 @@ pub struct InnerStore {
     write_batch_duration_in_secs: u64,
     #[envconfig(from = "GRAPH_STORE_WRITE_BATCH_SIZE", default = "10000")]
     write_batch_size: usize,
+    #[envconfig(from = "GRAPH_STORE_MERGE_QUEUED_WRITES", default = "false")]
+    merge_queued_writes: bool,
+    #[envconfig(from = "GRAPH_STORE_WRITE_STATS_SAMPLE_RATE", default = "10")]
+    write_stats_sample_rate: usize,
+    #[envconfig(from = "GRAPH_STORE_WARMUP_ROWS", default = "0")]
+    warmup_rows: usize,
     #[envconfig(from = "GRAPH_STORE_CREATE_GIN_INDEXES", default = "false")]
     create_gin_indexes: bool,
     #[envconfig(from = "GRAPH_STORE_USE_BRIN_FOR_ALL_QUERY_TYPES", default = "false")]
     use_brin_for_all_query_types: bool,
     #[envconfig(from = "GRAPH_STORE_DISABLE_BLOCK_CACHE_FOR_LOOKUP", default = "false")]
     disable_block_cache_for_lookup: bool,
+    #[envconfig(from = "GRAPH_STORE_STANDBY_POLL_INTERVAL", default = "5")]
+    standby_poll_interval_in_secs: u64,
+    #[envconfig(from = "GRAPH_STORE_EXPLAIN_SLOW_QUERIES_MS")]
+    explain_slow_queries_in_millis: Option<u64>,
+    #[envconfig(from = "GRAPH_STORE_EXPLAIN_SAMPLE_RATE", default = "0.1")]
+    explain_sample_rate: ZeroToOneF64,
+    #[envconfig(from = "GRAPH_STORE_EXPLAIN_INTERVAL", default = "60")]
+    explain_interval_in_secs: u64,
+    #[envconfig(from = "GRAPH_STORE_CALL_CACHE_RETENTION_BLOCKS")]
+    call_cache_retention_blocks: Option<i32>,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -178,12 +178,20 @@ impl IpfsClient {
             .bytes_stream())
     }
 
-    pub async fn get_block(&self, cid: String) -> Result<Bytes, reqwest::Error> {
+    pub async fn get_block(
+        &self,
+        cid: String,
+        timeout: Option<Duration>,
+    ) -> Result<Bytes, reqwest::Error> {
         let form = multipart::Form::new().part("arg", multipart::Part::text(cid));
-        self.call(format!("{}api/v0/block/get", self.base), Some(form), None)
-            .await?
-            .bytes()
-            .await
+        self.call(
+            format!("{}api/v0/block/get", self.base),
+            Some(form),
+            timeout,
+        )
+        .await?
+        .bytes()
+        .await
     }
 
     pub async fn test(&self) -> Result<(), reqwest::Error> {
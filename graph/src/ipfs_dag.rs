@@ -0,0 +1,389 @@
+//! Read files from IPFS block by block, checking that every block hashes to
+//! the CID under which it was requested.
+//!
+//! Fetching a file with `cat` trusts the IPFS node to return the contents
+//! that belong to the CID; a misbehaving node or gateway can return
+//! anything. CIDs identify the root block of a DAG whose blocks link to
+//! each other by their CIDs, so verifying every block on the way from the
+//! root to the leaves proves that the assembled file is the one the CID
+//! refers to, no matter how the file was chunked when it was added.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Error};
+use cid::Cid;
+use prost::Message;
+use sha2::{Digest, Sha256};
+use slog::Logger;
+
+use crate::components::link_resolver::LinkResolver;
+use crate::data::subgraph::Link;
+use crate::ipfs_client::CidFile;
+
+/// Multicodec for blocks in the dag-pb format that UnixFS uses
+const DAG_PB: u64 = 0x70;
+/// Multicodec for blocks that contain file data as is
+const RAW: u64 = 0x55;
+
+/// Multihash codes for the hash functions we can verify
+const IDENTITY: u64 = 0x00;
+const SHA2_256: u64 = 0x12;
+
+/// UnixFS node types, see the `DataType` enum in the UnixFS spec
+const UNIXFS_RAW: i32 = 0;
+const UNIXFS_DIRECTORY: i32 = 1;
+const UNIXFS_FILE: i32 = 2;
+
+/// A link from one dag-pb node to another
+#[derive(Clone, PartialEq, Message)]
+struct PbLink {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    hash: Option<Vec<u8>>,
+    #[prost(string, optional, tag = "2")]
+    name: Option<String>,
+    #[prost(uint64, optional, tag = "3")]
+    tsize: Option<u64>,
+}
+
+/// A dag-pb node; for UnixFS, `data` holds a `UnixFsData` message
+#[derive(Clone, PartialEq, Message)]
+struct PbNode {
+    #[prost(message, repeated, tag = "2")]
+    links: Vec<PbLink>,
+    #[prost(bytes = "vec", optional, tag = "1")]
+    data: Option<Vec<u8>>,
+}
+
+/// The UnixFS metadata of a dag-pb node. We only need the fields that say
+/// what kind of node it is and what file data it holds directly
+#[derive(Clone, PartialEq, Message)]
+struct UnixFsData {
+    #[prost(int32, optional, tag = "1")]
+    data_type: Option<i32>,
+    #[prost(bytes = "vec", optional, tag = "2")]
+    data: Option<Vec<u8>>,
+    #[prost(uint64, optional, tag = "3")]
+    filesize: Option<u64>,
+}
+
+/// Check that `data` hashes to the multihash in `cid`
+pub fn verify_block(cid: &Cid, data: &[u8]) -> Result<(), Error> {
+    let hash = cid.hash();
+    let matches = match hash.code() {
+        SHA2_256 => Sha256::digest(data).as_slice() == hash.digest(),
+        IDENTITY => data == hash.digest(),
+        code => bail!(
+            "the IPFS block {} uses the hash function 0x{:x} which can not be verified",
+            cid,
+            code
+        ),
+    };
+    if !matches {
+        bail!(
+            "the IPFS block {} has contents that do not match its CID",
+            cid
+        );
+    }
+    Ok(())
+}
+
+async fn get_block(
+    resolver: &dyn LinkResolver,
+    logger: &Logger,
+    cid: &Cid,
+) -> Result<Vec<u8>, Error> {
+    let data = resolver
+        .get_block(logger, &Link::from(cid.to_string()))
+        .await?;
+    verify_block(cid, &data)?;
+    Ok(data)
+}
+
+async fn get_node(
+    resolver: &dyn LinkResolver,
+    logger: &Logger,
+    cid: &Cid,
+) -> Result<(PbNode, UnixFsData), Error> {
+    if cid.codec() != DAG_PB {
+        bail!(
+            "the IPFS block {} has codec 0x{:x} but should be a dag-pb node",
+            cid,
+            cid.codec()
+        );
+    }
+    let data = get_block(resolver, logger, cid).await?;
+    let node = PbNode::decode(data.as_slice())
+        .map_err(|e| anyhow!("the IPFS block {} is not a valid dag-pb node: {}", cid, e))?;
+    let unixfs = UnixFsData::decode(node.data.as_deref().unwrap_or_default())
+        .map_err(|e| anyhow!("the IPFS block {} is not a valid UnixFS node: {}", cid, e))?;
+    Ok((node, unixfs))
+}
+
+fn link_cid(parent: &Cid, link: &PbLink) -> Result<Cid, Error> {
+    let hash = link
+        .hash
+        .as_deref()
+        .ok_or_else(|| anyhow!("the IPFS block {} has a link without a CID", parent))?;
+    Cid::try_from(hash).map_err(|e| anyhow!("the IPFS block {} has an invalid link: {}", parent, e))
+}
+
+/// Read the file that `link` points to, which has the form `/ipfs/<cid>`
+/// or `<cid>/<path>`, verifying every block that makes up the file and
+/// the directories on the way to it. Fails if the file is larger than
+/// `max_size` bytes
+pub async fn cat_verified(
+    resolver: &dyn LinkResolver,
+    logger: &Logger,
+    link: &Link,
+    max_size: usize,
+) -> Result<Vec<u8>, Error> {
+    let file = CidFile::from_str(link.link.trim_start_matches("/ipfs/"))?;
+
+    let mut cid = file.cid;
+    for name in file.path.iter().flat_map(|path| path.split('/')) {
+        if name.is_empty() {
+            continue;
+        }
+        let (node, unixfs) = get_node(resolver, logger, &cid).await?;
+        if unixfs.data_type != Some(UNIXFS_DIRECTORY) {
+            bail!(
+                "can not look up `{}` in the IPFS block {} since it is not a directory",
+                name,
+                cid
+            );
+        }
+        let entry = node
+            .links
+            .iter()
+            .find(|link| link.name.as_deref() == Some(name))
+            .ok_or_else(|| anyhow!("the IPFS directory {} has no entry `{}`", cid, name))?;
+        cid = link_cid(&cid, entry)?;
+    }
+
+    // Walk the DAG depth-first; a node's own data comes before the data
+    // of its children, and children are read in the order of their links
+    let mut contents = Vec::new();
+    let mut pending = vec![cid];
+    while let Some(cid) = pending.pop() {
+        match cid.codec() {
+            RAW => contents.extend(get_block(resolver, logger, &cid).await?),
+            DAG_PB => {
+                let (node, unixfs) = get_node(resolver, logger, &cid).await?;
+                match unixfs.data_type {
+                    Some(UNIXFS_RAW) | Some(UNIXFS_FILE) => {}
+                    _ => bail!("the IPFS block {} is not part of a file", cid),
+                }
+                contents.extend(unixfs.data.unwrap_or_default());
+                for link in node.links.iter().rev() {
+                    pending.push(link_cid(&cid, link)?);
+                }
+            }
+            codec => bail!(
+                "the IPFS block {} has codec 0x{:x} which is not supported",
+                cid,
+                codec
+            ),
+        }
+        if contents.len() > max_size {
+            bail!(
+                "IPFS file {} is too large. It can be at most {} bytes",
+                link.link,
+                max_size
+            );
+        }
+    }
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use cid::multihash::Multihash;
+
+    use std::sync::Arc;
+
+    use crate::components::link_resolver::{JsonValueStream, VerifyingLinkResolver};
+    use crate::log::discard;
+
+    use super::*;
+
+    /// A `LinkResolver` that only knows how to get the blocks it was given
+    #[derive(Clone, Debug, Default)]
+    struct Blocks(HashMap<String, Vec<u8>>);
+
+    impl Blocks {
+        fn add(&mut self, codec: u64, data: Vec<u8>) -> Cid {
+            let digest = Sha256::digest(&data);
+            let cid = Cid::new_v1(codec, Multihash::wrap(SHA2_256, &digest).unwrap());
+            self.0.insert(cid.to_string(), data);
+            cid
+        }
+
+        fn add_raw(&mut self, data: &[u8]) -> Cid {
+            self.add(RAW, data.to_vec())
+        }
+
+        /// Add a dag-pb node of UnixFS type `data_type` that links to the
+        /// `(name, cid)` in `links`
+        fn add_node(&mut self, data_type: i32, data: &[u8], links: &[(&str, Cid)]) -> Cid {
+            let unixfs = UnixFsData {
+                data_type: Some(data_type),
+                data: Some(data.to_vec()),
+                filesize: None,
+            };
+            let node = PbNode {
+                links: links
+                    .iter()
+                    .map(|(name, cid)| PbLink {
+                        hash: Some(cid.to_bytes()),
+                        name: Some(name.to_string()),
+                        tsize: None,
+                    })
+                    .collect(),
+                data: Some(unixfs.encode_to_vec()),
+            };
+            self.add(DAG_PB, node.encode_to_vec())
+        }
+
+        async fn read(&self, link: &str, max_size: usize) -> Result<Vec<u8>, Error> {
+            cat_verified(self, &discard(), &Link::from(link.to_string()), max_size).await
+        }
+    }
+
+    #[async_trait]
+    impl LinkResolver for Blocks {
+        fn with_timeout(&self, _timeout: Duration) -> Box<dyn LinkResolver> {
+            Box::new(self.clone())
+        }
+
+        fn with_retries(&self) -> Box<dyn LinkResolver> {
+            Box::new(self.clone())
+        }
+
+        async fn cat(&self, _logger: &Logger, link: &Link) -> Result<Vec<u8>, Error> {
+            bail!(
+                "files must be read block by block, not with cat({})",
+                link.link
+            )
+        }
+
+        async fn get_block(&self, _logger: &Logger, link: &Link) -> Result<Vec<u8>, Error> {
+            self.0
+                .get(&link.link)
+                .cloned()
+                .ok_or_else(|| anyhow!("unknown block {}", link.link))
+        }
+
+        async fn json_stream(
+            &self,
+            _logger: &Logger,
+            link: &Link,
+        ) -> Result<JsonValueStream, Error> {
+            bail!("json_stream({}) is not supported", link.link)
+        }
+    }
+
+    // The CIDv0 of the text `hello\n` as added by `ipfs add`, and the
+    // dag-pb block it refers to
+    const HELLO_CID: &str = "QmZULkCELmmk5XNfCgTnCyFgAVxBRBXyDHGGMVoLFLiXEN";
+    const HELLO_BLOCK: &[u8] = &[
+        0x0a, 0x0c, 0x08, 0x02, 0x12, 0x06, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x0a, 0x18, 0x06,
+    ];
+
+    #[test]
+    fn verifies_blocks() {
+        let cid = Cid::from_str(HELLO_CID).unwrap();
+        verify_block(&cid, HELLO_BLOCK).unwrap();
+
+        let mut tampered = HELLO_BLOCK.to_vec();
+        tampered[6] = b'j';
+        assert!(verify_block(&cid, &tampered).is_err());
+    }
+
+    #[test]
+    fn decodes_unixfs_file() {
+        let node = PbNode::decode(HELLO_BLOCK).unwrap();
+        assert!(node.links.is_empty());
+        let unixfs = UnixFsData::decode(node.data.as_deref().unwrap()).unwrap();
+        assert_eq!(Some(UNIXFS_FILE), unixfs.data_type);
+        assert_eq!(Some(b"hello\n".to_vec()), unixfs.data);
+        assert_eq!(Some(6), unixfs.filesize);
+    }
+
+    #[tokio::test]
+    async fn cat_verified_reads_file() {
+        let mut blocks = Blocks::default();
+        blocks.0.insert(HELLO_CID.to_string(), HELLO_BLOCK.to_vec());
+
+        let hello = format!("/ipfs/{}", HELLO_CID);
+        assert_eq!(
+            b"hello\n".to_vec(),
+            blocks.read(&hello, 1024).await.unwrap()
+        );
+
+        let err = blocks.read(&hello, 3).await.unwrap_err();
+        assert!(err.to_string().contains("too large"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn cat_verified_follows_links() {
+        let mut blocks = Blocks::default();
+        let first = blocks.add_raw(b"world");
+        let second = blocks.add_raw(b"!");
+        let file = blocks.add_node(UNIXFS_FILE, b"hello ", &[("", first), ("", second)]);
+        let dir = blocks.add_node(UNIXFS_DIRECTORY, b"", &[("hello.txt", file)]);
+
+        let contents = blocks.read(&format!("{}/hello.txt", dir), 1024).await;
+        assert_eq!(b"hello world!".to_vec(), contents.unwrap());
+        let contents = blocks.read(&file.to_string(), 1024).await;
+        assert_eq!(b"hello world!".to_vec(), contents.unwrap());
+
+        let err = blocks.read(&format!("{}/other.txt", dir), 1024).await;
+        assert!(err
+            .unwrap_err()
+            .to_string()
+            .contains("no entry `other.txt`"));
+        let err = blocks.read(&dir.to_string(), 1024).await;
+        assert!(err.unwrap_err().to_string().contains("not part of a file"));
+    }
+
+    #[tokio::test]
+    async fn cat_verified_rejects_mismatching_hash() {
+        let mut blocks = Blocks::default();
+        let mut tampered = HELLO_BLOCK.to_vec();
+        tampered[6] = b'j';
+        blocks.0.insert(HELLO_CID.to_string(), tampered);
+
+        let err = blocks.read(HELLO_CID, 1024).await.unwrap_err();
+        assert!(err.to_string().contains("do not match its CID"), "{}", err);
+
+        // A node that substitutes a leaf deeper in the DAG is caught, too
+        let mut blocks = Blocks::default();
+        let leaf = blocks.add_raw(b"world");
+        let file = blocks.add_node(UNIXFS_FILE, b"hello ", &[("", leaf)]);
+        blocks.0.insert(leaf.to_string(), b"there".to_vec());
+
+        let err = blocks.read(&file.to_string(), 1024).await.unwrap_err();
+        assert!(err.to_string().contains("do not match its CID"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn verifying_resolver_caches_files() {
+        let mut blocks = Blocks::default();
+        let file = blocks.add_raw(b"cached by the verifying resolver");
+        let link = Link::from(file.to_string());
+
+        let resolver = VerifyingLinkResolver::new(Arc::new(blocks));
+        let data = resolver.cat(&discard(), &link).await.unwrap();
+        assert_eq!(b"cached by the verifying resolver".to_vec(), data);
+
+        // Another resolver reads the file from the cache without asking
+        // for any blocks
+        let resolver = VerifyingLinkResolver::new(Arc::new(Blocks::default()));
+        assert_eq!(data, resolver.cat(&discard(), &link).await.unwrap());
+        assert_eq!(data.len() as u64, resolver.files()[0].size);
+    }
+}
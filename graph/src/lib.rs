@@ -18,6 +18,8 @@ pub mod cheap_clone;
 
 pub mod ipfs_client;
 
+pub mod ipfs_dag;
+
 pub mod data_source;
 
 pub mod blockchain;
@@ -159,10 +161,10 @@ pub mod prelude {
     };
     pub use crate::data::subgraph::schema::SubgraphDeploymentEntity;
     pub use crate::data::subgraph::{
-        CreateSubgraphResult, DataSourceContext, DeploymentHash, DeploymentState, Link,
-        SubgraphAssignmentProviderError, SubgraphManifest, SubgraphManifestResolveError,
-        SubgraphManifestValidationError, SubgraphName, SubgraphRegistrarError,
-        UnvalidatedSubgraphManifest,
+        CreateSubgraphResult, CreateSubgraphVersionResult, DataSourceContext, DeploymentHash,
+        DeploymentState, Link, SubgraphAssignmentProviderError, SubgraphManifest,
+        SubgraphManifestResolveError, SubgraphManifestValidationError, SubgraphName,
+        SubgraphRegistrarError, UnvalidatedSubgraphManifest,
     };
     pub use crate::data::subscription::{
         QueryResultStream, Subscription, SubscriptionError, SubscriptionResult,
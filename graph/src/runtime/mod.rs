@@ -261,6 +261,8 @@ pub enum IndexForAscTypeId {
     ArrayH256 = 1002,
     ArrayLog = 1003,
     ArrayTypedMapStringStoreValue = 1004,
+    ArrayEthereumEvent = 1005,
+    EthereumTokenUriCall = 1006,
     // Continue to add more Ethereum type IDs here.
     // e.g.:
     // NextEthereumType = 1004,
@@ -0,0 +1,544 @@
+//! Aggregations that roll up the data points of a timeseries into one
+//! entity per hour or per day.
+//!
+//! A timeseries is an entity type declared with `@entity(timeseries:
+//! true)`. Its entities are immutable, and it must have the fields `id:
+//! Int8!` and `timestamp: Int8!`, where the timestamp is in seconds since
+//! the epoch. An aggregation over it is declared as
+//!
+//! ```graphql
+//! type Stats @aggregation(intervals: ["hour", "day"], source: "Data") {
+//!   id: Int8!
+//!   timestamp: Int8!
+//!   volume: BigDecimal! @aggregate(fn: "sum", arg: "amount")
+//!   trades: Int8! @aggregate(fn: "count")
+//! }
+//! ```
+//!
+//! Parsing the schema replaces the aggregation with an immutable entity
+//! type for each interval, here `StatsHour` and `StatsDay`, which can be
+//! queried like any other entity type. The store writes the entity for an
+//! interval, with the start of the interval as its `id` and `timestamp`,
+//! once a data point in a later interval has been written, i.e., once the
+//! interval is complete. Mappings can not write these entities themselves.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error};
+
+use crate::data::graphql::ext::DirectiveFinder;
+use crate::data::graphql::{DirectiveExt, DocumentExt, ObjectTypeExt, TypeExt};
+use crate::data::value::Word;
+use crate::env::ENV_VARS;
+use crate::prelude::s;
+
+use super::SchemaValidationError;
+
+const TIMESTAMP: &str = "timestamp";
+
+/// The length of the intervals of an aggregation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AggregationInterval {
+    Hour,
+    Day,
+}
+
+impl AggregationInterval {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AggregationInterval::Hour => "hour",
+            AggregationInterval::Day => "day",
+        }
+    }
+
+    /// The length of the interval in seconds
+    pub fn as_secs(&self) -> i64 {
+        match self {
+            AggregationInterval::Hour => 3600,
+            AggregationInterval::Day => 86400,
+        }
+    }
+
+    /// The suffix of the name of the entity type for this interval
+    fn suffix(&self) -> &'static str {
+        match self {
+            AggregationInterval::Hour => "Hour",
+            AggregationInterval::Day => "Day",
+        }
+    }
+}
+
+impl FromStr for AggregationInterval {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hour" => Ok(AggregationInterval::Hour),
+            "day" => Ok(AggregationInterval::Day),
+            _ => Err(anyhow!(
+                "invalid interval `{}`; it must be one of `hour` and `day`",
+                s
+            )),
+        }
+    }
+}
+
+/// The function that computes an aggregate
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AggregateFn {
+    Sum,
+    Count,
+    Min,
+    Max,
+}
+
+impl AggregateFn {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AggregateFn::Sum => "sum",
+            AggregateFn::Count => "count",
+            AggregateFn::Min => "min",
+            AggregateFn::Max => "max",
+        }
+    }
+}
+
+impl FromStr for AggregateFn {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sum" => Ok(AggregateFn::Sum),
+            "count" => Ok(AggregateFn::Count),
+            "min" => Ok(AggregateFn::Min),
+            "max" => Ok(AggregateFn::Max),
+            _ => Err(anyhow!(
+                "invalid function `{}`; it must be one of `sum`, `count`, `min`, and `max`",
+                s
+            )),
+        }
+    }
+}
+
+/// A field of an aggregation that is computed with `func` from the field
+/// `arg` of the data points in an interval. Only `count` does not have an
+/// `arg`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Aggregate {
+    pub name: Word,
+    pub func: AggregateFn,
+    pub arg: Option<Word>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Aggregation {
+    /// The name of the aggregation in the schema, like `Stats`
+    pub name: Word,
+    /// The name of the timeseries entity type that is aggregated
+    pub source: Word,
+    /// The intervals and the name of the entity type for each of them,
+    /// like `StatsHour`
+    pub intervals: Vec<(AggregationInterval, Word)>,
+    pub aggregates: Vec<Aggregate>,
+}
+
+impl Aggregation {
+    /// Whether `entity_type` is the entity type for one of the intervals
+    /// of this aggregation
+    pub fn has_entity_type(&self, entity_type: &str) -> bool {
+        self.intervals
+            .iter()
+            .any(|(_, name)| name.as_str() == entity_type)
+    }
+}
+
+fn is_timeseries(obj_type: &s::ObjectType) -> bool {
+    obj_type
+        .find_directive("entity")
+        .and_then(|dir| dir.argument("timeseries"))
+        .map(|value| matches!(value, s::Value::Boolean(true)))
+        .unwrap_or(false)
+}
+
+fn is_int8(field_type: &s::Type) -> bool {
+    match field_type {
+        s::Type::NonNullType(inner) => {
+            matches!(&**inner, s::Type::NamedType(name) if name == "Int8")
+        }
+        _ => false,
+    }
+}
+
+fn is_list(field_type: &s::Type) -> bool {
+    match field_type {
+        s::Type::NonNullType(inner) => is_list(inner),
+        s::Type::ListType(_) => true,
+        s::Type::NamedType(_) => false,
+    }
+}
+
+/// Check the timeseries `obj_type` and make its entities immutable
+fn prepare_timeseries(obj_type: &mut s::ObjectType) -> Result<(), SchemaValidationError> {
+    let type_name = obj_type.name.clone();
+    let err =
+        |msg: &str| SchemaValidationError::InvalidTimeseries(type_name.clone(), msg.to_string());
+
+    for name in ["id", TIMESTAMP] {
+        match obj_type.field(name) {
+            Some(field) if is_int8(&field.field_type) => {}
+            _ => return Err(err(&format!("it must have a field `{}: Int8!`", name))),
+        }
+    }
+    // Unwrap: `is_timeseries` found the directive
+    let directive = obj_type
+        .directives
+        .iter_mut()
+        .find(|dir| dir.name == "entity")
+        .unwrap();
+    match directive.argument("immutable") {
+        Some(s::Value::Boolean(false)) => Err(err("a timeseries can not be mutable")),
+        Some(_) => Ok(()),
+        None => {
+            directive
+                .arguments
+                .push(("immutable".to_string(), s::Value::Boolean(true)));
+            Ok(())
+        }
+    }
+}
+
+fn aggregation(
+    document: &s::Document,
+    obj_type: &s::ObjectType,
+) -> Result<(Aggregation, Vec<s::ObjectType>), SchemaValidationError> {
+    let err = |msg: String| SchemaValidationError::InvalidAggregation(obj_type.name.clone(), msg);
+
+    if obj_type.find_directive("entity").is_some() {
+        return Err(err("it can not also be an @entity".to_string()));
+    }
+    if !obj_type.implements_interfaces.is_empty() {
+        return Err(err("it can not implement interfaces".to_string()));
+    }
+    // Unwrap: the caller found the directive
+    let directive = obj_type.find_directive("aggregation").unwrap();
+
+    let source = match directive.argument("source") {
+        Some(s::Value::String(source)) => source,
+        _ => return Err(err("@aggregation needs a `source` argument".to_string())),
+    };
+    let source_type = document
+        .get_object_type_definition(source)
+        .filter(|source_type| is_timeseries(source_type))
+        .ok_or_else(|| err(format!("the source `{}` must be a timeseries", source)))?;
+
+    let intervals = match directive.argument("intervals") {
+        Some(s::Value::List(intervals)) if !intervals.is_empty() => intervals,
+        _ => return Err(err("@aggregation needs a list of `intervals`".to_string())),
+    };
+    let mut aggregation_intervals = Vec::new();
+    for interval in intervals {
+        let interval = match interval {
+            s::Value::String(interval) => {
+                AggregationInterval::from_str(interval).map_err(|e| err(e.to_string()))?
+            }
+            _ => return Err(err("intervals must be strings".to_string())),
+        };
+        if aggregation_intervals
+            .iter()
+            .any(|(other, _)| *other == interval)
+        {
+            return Err(err(format!(
+                "the interval `{}` is listed twice",
+                interval.as_str()
+            )));
+        }
+        let name = format!("{}{}", obj_type.name, interval.suffix());
+        if document.get_named_type(&name).is_some() {
+            return Err(err(format!(
+                "the type `{}` for the interval `{}` is already defined",
+                name,
+                interval.as_str()
+            )));
+        }
+        aggregation_intervals.push((interval, Word::from(name)));
+    }
+
+    let mut aggregates = Vec::new();
+    for field in &obj_type.fields {
+        let name = field.name.as_str();
+        let directive = field.find_directive("aggregate");
+        if name == "id" || name == TIMESTAMP {
+            if !is_int8(&field.field_type) || directive.is_some() {
+                return Err(err(format!("the field `{}` must be `Int8!`", name)));
+            }
+            continue;
+        }
+        let directive = directive.ok_or_else(|| {
+            err(format!(
+                "the field `{}` needs an @aggregate directive",
+                name
+            ))
+        })?;
+        let func = match directive.argument("fn") {
+            Some(s::Value::String(func)) => {
+                AggregateFn::from_str(func).map_err(|e| err(format!("field `{}`: {}", name, e)))?
+            }
+            _ => {
+                return Err(err(format!(
+                    "the @aggregate directive of `{}` needs a `fn` argument",
+                    name
+                )))
+            }
+        };
+        let arg = match (func, directive.argument("arg")) {
+            (AggregateFn::Count, None) => {
+                if !is_int8(&field.field_type) {
+                    return Err(err(format!("the count `{}` must be `Int8!`", name)));
+                }
+                None
+            }
+            (AggregateFn::Count, Some(_)) => {
+                return Err(err(format!("the count `{}` can not have an `arg`", name)))
+            }
+            (_, Some(s::Value::String(arg))) => {
+                let arg_field = source_type
+                    .field(arg)
+                    .filter(|arg_field| !arg_field.is_derived())
+                    .ok_or_else(|| {
+                        err(format!(
+                            "the `arg` of `{}` must be a field of `{}`, but `{}` is not",
+                            name, source, arg
+                        ))
+                    })?;
+                let base_type = field.field_type.get_base_type();
+                if is_list(&field.field_type)
+                    || is_list(&arg_field.field_type)
+                    || !matches!(base_type, "Int" | "Int8" | "BigInt" | "BigDecimal")
+                {
+                    return Err(err(format!(
+                        "`{}` and its `arg` must have one of the types Int, Int8, BigInt, \
+                         and BigDecimal",
+                        name
+                    )));
+                }
+                if arg_field.field_type.get_base_type() != base_type {
+                    return Err(err(format!(
+                        "`{}` must have the same type as `{}.{}`",
+                        name, source, arg
+                    )));
+                }
+                if field.field_type.is_non_null() && !arg_field.field_type.is_non_null() {
+                    return Err(err(format!(
+                        "`{}` can only be non-null if `{}.{}` is non-null",
+                        name, source, arg
+                    )));
+                }
+                Some(Word::from(arg.as_str()))
+            }
+            (_, _) => {
+                return Err(err(format!(
+                    "the @aggregate directive of `{}` needs an `arg` argument",
+                    name
+                )))
+            }
+        };
+        aggregates.push(Aggregate {
+            name: Word::from(name),
+            func,
+            arg,
+        });
+    }
+    for name in ["id", TIMESTAMP] {
+        if obj_type.field(name).is_none() {
+            return Err(err(format!("it must have a field `{}: Int8!`", name)));
+        }
+    }
+
+    let fields: Vec<_> = obj_type
+        .fields
+        .iter()
+        .map(|field| {
+            let mut field = field.clone();
+            field.directives.retain(|dir| dir.name != "aggregate");
+            field
+        })
+        .collect();
+    let entity = s::Directive {
+        name: "entity".to_string(),
+        position: directive.position,
+        arguments: vec![("immutable".to_string(), s::Value::Boolean(true))],
+    };
+    let interval_types = aggregation_intervals
+        .iter()
+        .map(|(_, name)| s::ObjectType {
+            position: obj_type.position,
+            description: obj_type.description.clone(),
+            name: name.to_string(),
+            implements_interfaces: vec![],
+            directives: vec![entity.clone()],
+            fields: fields.clone(),
+        })
+        .collect();
+
+    let aggregation = Aggregation {
+        name: Word::from(obj_type.name.as_str()),
+        source: Word::from(source.as_str()),
+        intervals: aggregation_intervals,
+        aggregates,
+    };
+    Ok((aggregation, interval_types))
+}
+
+/// Make the entities of timeseries in `document` immutable and replace
+/// each aggregation with the entity types for its intervals. Return the
+/// aggregations that were found
+pub(crate) fn expand(
+    document: &mut s::Document,
+) -> Result<Vec<Aggregation>, SchemaValidationError> {
+    let uses_timeseries = document
+        .get_object_type_definitions()
+        .into_iter()
+        .any(|obj_type| {
+            is_timeseries(obj_type) || obj_type.find_directive("aggregation").is_some()
+        });
+    if !uses_timeseries {
+        return Ok(vec![]);
+    }
+    if !ENV_VARS.enable_timeseries {
+        return Err(SchemaValidationError::TimeseriesNotEnabled);
+    }
+
+    for def in document.definitions.iter_mut() {
+        if let s::Definition::TypeDefinition(s::TypeDefinition::Object(obj_type)) = def {
+            if is_timeseries(obj_type) {
+                prepare_timeseries(obj_type)?;
+            }
+        }
+    }
+
+    let mut aggregations = Vec::new();
+    let mut interval_types = Vec::new();
+    for obj_type in document.get_object_type_definitions() {
+        if obj_type.find_directive("aggregation").is_some() {
+            let (aggregation, types) = aggregation(document, obj_type)?;
+            aggregations.push(aggregation);
+            interval_types.extend(types);
+        }
+    }
+    document.definitions.retain(|def| match def {
+        s::Definition::TypeDefinition(s::TypeDefinition::Object(obj_type)) => {
+            obj_type.find_directive("aggregation").is_none()
+        }
+        _ => true,
+    });
+    document.definitions.extend(
+        interval_types
+            .into_iter()
+            .map(|obj_type| s::Definition::TypeDefinition(s::TypeDefinition::Object(obj_type))),
+    );
+    Ok(aggregations)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::graphql::{DocumentExt, ObjectTypeExt};
+
+    use super::*;
+
+    const DATA: &str = "type Data @entity(timeseries: true) { id: Int8!, timestamp: Int8!, \
+                        amount: BigDecimal!, count: Int }";
+
+    fn expand_schema(aggregation: &str) -> Result<(s::Document, Vec<Aggregation>), String> {
+        let raw = format!("{}\n{}", DATA, aggregation);
+        let mut document = graphql_parser::parse_schema(&raw).unwrap().into_static();
+        let aggregations = expand(&mut document).map_err(|e| e.to_string())?;
+        Ok((document, aggregations))
+    }
+
+    #[test]
+    fn expands_aggregations() {
+        let (document, aggregations) = expand_schema(
+            "type Stats @aggregation(intervals: [\"hour\", \"day\"], source: \"Data\") {
+               id: Int8!
+               timestamp: Int8!
+               volume: BigDecimal! @aggregate(fn: \"sum\", arg: \"amount\")
+               trades: Int8! @aggregate(fn: \"count\")
+             }",
+        )
+        .unwrap();
+
+        assert!(document.get_object_type_definition("Stats").is_none());
+        for name in ["StatsHour", "StatsDay"] {
+            let obj_type = document.get_object_type_definition(name).unwrap();
+            assert!(obj_type.is_immutable());
+            assert!(obj_type.field("volume").unwrap().directives.is_empty());
+        }
+        assert!(document
+            .get_object_type_definition("Data")
+            .unwrap()
+            .is_immutable());
+
+        assert_eq!(1, aggregations.len());
+        let stats = &aggregations[0];
+        assert_eq!("Data", stats.source.as_str());
+        assert_eq!(
+            vec![
+                (AggregationInterval::Hour, Word::from("StatsHour")),
+                (AggregationInterval::Day, Word::from("StatsDay"))
+            ],
+            stats.intervals
+        );
+        assert_eq!(
+            vec![
+                Aggregate {
+                    name: Word::from("volume"),
+                    func: AggregateFn::Sum,
+                    arg: Some(Word::from("amount"))
+                },
+                Aggregate {
+                    name: Word::from("trades"),
+                    func: AggregateFn::Count,
+                    arg: None
+                }
+            ],
+            stats.aggregates
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_aggregations() {
+        fn check(aggregation: &str, msg: &str) {
+            let err = expand_schema(aggregation).unwrap_err();
+            assert!(err.contains(msg), "`{}` does not contain `{}`", err, msg);
+        }
+
+        check(
+            "type S @aggregation(intervals: [\"week\"], source: \"Data\") { id: Int8!, timestamp: Int8! }",
+            "invalid interval `week`",
+        );
+        check(
+            "type S @aggregation(intervals: [\"hour\"], source: \"Other\") { id: Int8!, timestamp: Int8! }",
+            "the source `Other` must be a timeseries",
+        );
+        check(
+            "type S @aggregation(intervals: [\"hour\"], source: \"Data\") { id: Int8!, timestamp: Int8!, \
+             total: BigDecimal! }",
+            "the field `total` needs an @aggregate directive",
+        );
+        check(
+            "type S @aggregation(intervals: [\"hour\"], source: \"Data\") { id: Int8!, timestamp: Int8!, \
+             total: BigInt! @aggregate(fn: \"sum\", arg: \"amount\") }",
+            "`total` must have the same type as `Data.amount`",
+        );
+        check(
+            "type S @aggregation(intervals: [\"hour\"], source: \"Data\") { id: Int8!, timestamp: Int8!, \
+             total: Int! @aggregate(fn: \"max\", arg: \"count\") }",
+            "`total` can only be non-null if `Data.count` is non-null",
+        );
+        check(
+            "type S @aggregation(intervals: [\"hour\"], source: \"Data\") { id: Int8!, timestamp: Int8!, \
+             total: Int8! @aggregate(fn: \"avg\") }",
+            "invalid function `avg`",
+        );
+    }
+}
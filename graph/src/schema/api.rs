@@ -9,7 +9,9 @@ use crate::data::graphql::{ObjectOrInterface, ObjectTypeExt};
 use crate::data::store::IdType;
 use crate::schema::{ast, META_FIELD_NAME, META_FIELD_TYPE};
 
-use crate::data::graphql::ext::{DefinitionExt, DirectiveExt, DocumentExt, ValueExt};
+use crate::data::graphql::ext::{
+    DefinitionExt, DirectiveExt, DirectiveFinder, DocumentExt, ValueExt,
+};
 use crate::prelude::s::{Value, *};
 use crate::prelude::*;
 use thiserror::Error;
@@ -776,11 +778,12 @@ fn field_list_filter_input_values(
         TypeDefinition::InputObject(_) | TypeDefinition::Union(_) => (None, None),
     };
 
-    let mut input_values: Vec<InputValue> = match input_field_type {
-        None => {
-            vec![]
-        }
-        Some(input_field_type) => vec![
+    // Lists that are stored in a join table can only be filtered by
+    // membership
+    let filter_types = if field.find_directive("joinTable").is_some() {
+        vec!["contains", "not_contains"]
+    } else {
+        vec![
             "",
             "not",
             "contains",
@@ -788,17 +791,24 @@ fn field_list_filter_input_values(
             "not_contains",
             "not_contains_nocase",
         ]
-        .into_iter()
-        .map(|filter_type| {
-            input_value(
-                &field.name,
-                filter_type,
-                Type::ListType(Box::new(Type::NonNullType(Box::new(
-                    input_field_type.clone(),
-                )))),
-            )
-        })
-        .collect(),
+    };
+
+    let mut input_values: Vec<InputValue> = match input_field_type {
+        None => {
+            vec![]
+        }
+        Some(input_field_type) => filter_types
+            .into_iter()
+            .map(|filter_type| {
+                input_value(
+                    &field.name,
+                    filter_type,
+                    Type::ListType(Box::new(Type::NonNullType(Box::new(
+                        input_field_type.clone(),
+                    )))),
+                )
+            })
+            .collect(),
     };
 
     if let Some(parent) = parent_type_name {
@@ -969,6 +979,8 @@ fn block_argument() -> InputValue {
              or a `{ number_gte: Int }` containing the minimum block number. \
              In the case of `number_gte`, the query will be executed on the latest block only if \
              the subgraph has progressed to or past the minimum block number. \
+             `{ final: true }` executes the query on the latest block that can no longer \
+             be reverted. \
              Defaults to the latest block when omitted."
                 .to_owned(),
         ),
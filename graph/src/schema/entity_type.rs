@@ -1,4 +1,4 @@
-use std::{borrow::Borrow, fmt, sync::Arc};
+use std::{borrow::Borrow, collections::BTreeSet, fmt, sync::Arc};
 
 use anyhow::{Context, Error};
 use serde::Serialize;
@@ -51,6 +51,18 @@ impl EntityType {
         self.schema.is_immutable(self.atom)
     }
 
+    /// Whether this is the entity type for an interval of an aggregation;
+    /// the store maintains those entities, and mappings can not write them
+    pub fn is_aggregation(&self) -> bool {
+        self.schema.is_aggregation(self.atom)
+    }
+
+    /// The length in seconds of the shortest interval of the aggregations
+    /// of this timeseries, or `None` if it is not aggregated
+    pub fn shortest_interval(&self) -> Option<i64> {
+        self.schema.shortest_interval(self.atom)
+    }
+
     pub fn id_type(&self) -> Result<IdType, Error> {
         self.schema.id_type(self.atom)
     }
@@ -59,6 +71,12 @@ impl EntityType {
         self.schema.find_object_type(self.atom)
     }
 
+    /// Return the values of the enum type `name` if it is defined in the
+    /// schema this entity type belongs to
+    pub fn enum_values(&self, name: &str) -> Option<Arc<BTreeSet<String>>> {
+        self.schema.enum_values(name)
+    }
+
     /// Create a key from this type for an onchain entity
     pub fn key(&self, id: Id) -> EntityKey {
         self.key_in(id, CausalityRegion::ONCHAIN)
@@ -10,7 +10,8 @@ use crate::components::store::LoadRelatedRequest;
 use crate::data::graphql::ext::DirectiveFinder;
 use crate::data::graphql::{DirectiveExt, DocumentExt, ObjectTypeExt, TypeExt, ValueExt};
 use crate::data::store::{
-    self, EntityValidationError, IdType, IntoEntityIterator, TryIntoEntityIterator, ValueType, ID,
+    self, EntityValidationError, Id, IdType, IntoEntityIterator, TryIntoEntityIterator, ValueType,
+    ID,
 };
 use crate::data::value::Word;
 use crate::prelude::q::Value;
@@ -18,6 +19,7 @@ use crate::prelude::{s, DeploymentHash};
 use crate::schema::api::api_schema;
 use crate::util::intern::{Atom, AtomPool};
 
+use super::aggregation::{self, Aggregation};
 use super::fulltext::FulltextDefinition;
 use super::{ApiSchema, AsEntityTypeName, EntityType, Schema, SCHEMA_TYPE_NAME};
 
@@ -187,6 +189,14 @@ pub struct Field {
     pub field_type: s::Type,
     pub value_type: ValueType,
     pub is_derived: bool,
+    /// Whether the field is annotated with `@joinTable`, i.e., whether the
+    /// ids in the list are stored in a separate join table rather than in
+    /// an array column of the entity's table
+    pub is_join_table: bool,
+    /// Whether the field is annotated with `@compressed`, i.e., whether
+    /// large values are compressed and stored out of line so that they
+    /// are only read when they are actually needed
+    pub is_compressed: bool,
 }
 
 impl Field {
@@ -197,6 +207,8 @@ impl Field {
             field_type: field_type.clone(),
             value_type,
             is_derived,
+            is_join_table: false,
+            is_compressed: false,
         }
     }
 
@@ -244,11 +256,76 @@ impl Field {
     }
 }
 
+/// Restrictions on the ids of an entity type that come from an
+/// `@idFormat` directive on its `id` field. They are checked whenever an
+/// entity of that type is written
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct IdFormat {
+    /// Whether ids that start with `0x` may only contain lowercase hex
+    /// digits after that
+    pub lowercase_hex: bool,
+    /// The maximum number of characters in an id
+    pub max_length: Option<usize>,
+}
+
+impl IdFormat {
+    fn new(field: &s::Field) -> Self {
+        let directive = match field.find_directive("idFormat") {
+            Some(directive) => directive,
+            None => return Self::default(),
+        };
+        let lowercase_hex = matches!(
+            directive.argument("lowercaseHex"),
+            Some(Value::Boolean(true))
+        );
+        let max_length = match directive.argument("maxLength") {
+            Some(Value::Int(n)) => n.as_i64().map(|n| n as usize),
+            _ => None,
+        };
+        Self {
+            lowercase_hex,
+            max_length,
+        }
+    }
+
+    /// Check that `id` has this format and explain why not if it doesn't
+    pub fn check(&self, id: &Id) -> Result<(), String> {
+        let id = match id {
+            Id::String(id) => id.as_str(),
+            // Validation only allows `@idFormat` for string ids
+            Id::Bytes(_) | Id::Int8(_) => return Ok(()),
+        };
+        if let Some(max_length) = self.max_length {
+            let length = id.chars().count();
+            if length > max_length {
+                return Err(format!(
+                    "the id has {} characters but can have at most {}",
+                    length, max_length
+                ));
+            }
+        }
+        if self.lowercase_hex && (id.starts_with("0x") || id.starts_with("0X")) {
+            let lowercase = |c: char| matches!(c, '0'..='9' | 'a'..='f');
+            if id.starts_with("0X") || !id[2..].chars().all(lowercase) {
+                return Err(
+                    "an id that starts with `0x` must be followed by lowercase hex digits"
+                        .to_string(),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(PartialEq, Debug)]
 pub struct ObjectType {
     pub name: Atom,
     pub id_type: IdType,
+    pub id_format: IdFormat,
     pub fields: Box<[Field]>,
+    /// The fields of each `@index` directive on the type, for which the
+    /// store creates an index in addition to the ones it always creates
+    pub indexes: Box<[Box<[Word]>]>,
     interfaces: Box<[Word]>,
     shared_interfaces: Box<[Atom]>,
 }
@@ -261,12 +338,34 @@ impl ObjectType {
         shared_interfaces: Box<[Atom]>,
     ) -> Self {
         let id_type = IdType::try_from(object_type).expect("validation caught any issues here");
+        let id_format = object_type
+            .field(&*ID)
+            .map(IdFormat::new)
+            .unwrap_or_default();
         let fields = object_type
             .fields
             .iter()
             .map(|field| {
                 let is_derived = field.is_derived();
-                Field::new(schema, &field.name, &field.field_type, is_derived)
+                let mut f = Field::new(schema, &field.name, &field.field_type, is_derived);
+                f.is_join_table = field.find_directive("joinTable").is_some();
+                f.is_compressed = field.find_directive("compressed").is_some();
+                f
+            })
+            .collect();
+        let indexes = object_type
+            .directives
+            .iter()
+            .filter(|dir| dir.name == "index")
+            .map(|dir| match dir.argument("fields") {
+                Some(Value::List(fields)) => fields
+                    .iter()
+                    .filter_map(|field| match field {
+                        Value::String(field) => Some(Word::from(field.as_str())),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => Box::new([]) as Box<[Word]>,
             })
             .collect();
         let interfaces = object_type
@@ -280,7 +379,9 @@ impl ObjectType {
         Self {
             name,
             fields,
+            indexes,
             id_type,
+            id_format,
             interfaces,
             shared_interfaces,
         }
@@ -293,12 +394,16 @@ impl ObjectType {
                 field_type: s::Type::NamedType("ID".to_string()),
                 value_type: ValueType::String,
                 is_derived: false,
+                is_join_table: false,
+                is_compressed: false,
             },
             Field {
                 name: Word::from(POI_DIGEST),
                 field_type: s::Type::NamedType("String".to_string()),
                 value_type: ValueType::String,
                 is_derived: false,
+                is_join_table: false,
+                is_compressed: false,
             },
         ]
         .into_boxed_slice();
@@ -309,7 +414,9 @@ impl ObjectType {
             name,
             interfaces: Box::new([]),
             id_type: IdType::String,
+            id_format: IdFormat::default(),
             fields,
+            indexes: Box::new([]),
             shared_interfaces: Box::new([]),
         }
     }
@@ -404,6 +511,9 @@ pub struct Inner {
     type_infos: Box<[TypeInfo]>,
     enum_map: EnumMap,
     pool: Arc<AtomPool>,
+    /// The aggregations in the schema; the entity types for their
+    /// intervals are part of `schema` and `type_infos`
+    aggregations: Box<[Aggregation]>,
 }
 
 impl CheapClone for InputSchema {
@@ -419,7 +529,9 @@ impl InputSchema {
     /// representation of the subgraph's GraphQL schema `raw` and its
     /// deployment hash `id`. The returned schema is fully validated.
     pub fn parse(raw: &str, id: DeploymentHash) -> Result<Self, Error> {
-        let schema = Schema::parse(raw, id.clone())?;
+        let mut document = graphql_parser::parse_schema(raw)?.into_static();
+        let aggregations = aggregation::expand(&mut document)?;
+        let schema = Schema::new(id.clone(), document)?;
         validations::validate(&schema).map_err(|errors| {
             anyhow!(
                 "Validation errors in subgraph `{}`:\n{}",
@@ -461,6 +573,7 @@ impl InputSchema {
                 type_infos,
                 enum_map,
                 pool,
+                aggregations: aggregations.into_boxed_slice(),
             }),
         })
     }
@@ -577,6 +690,37 @@ impl InputSchema {
             .unwrap_or(false)
     }
 
+    /// Check if `entity_type` is the entity type for an interval of an
+    /// aggregation
+    pub(in crate::schema) fn is_aggregation(&self, entity_type: Atom) -> bool {
+        match self.inner.pool.get(entity_type) {
+            Some(name) => self
+                .inner
+                .aggregations
+                .iter()
+                .any(|aggregation| aggregation.has_entity_type(name)),
+            None => false,
+        }
+    }
+
+    /// The aggregations in this schema
+    pub fn aggregations(&self) -> &[Aggregation] {
+        &self.inner.aggregations
+    }
+
+    /// The length in seconds of the shortest interval of the aggregations
+    /// whose source is `entity_type`, or `None` if no aggregation uses it
+    pub(in crate::schema) fn shortest_interval(&self, entity_type: Atom) -> Option<i64> {
+        let name = self.inner.pool.get(entity_type)?;
+        self.inner
+            .aggregations
+            .iter()
+            .filter(|aggregation| aggregation.source.as_str() == name)
+            .flat_map(|aggregation| aggregation.intervals.iter())
+            .map(|(interval, _)| interval.as_secs())
+            .min()
+    }
+
     /// Return true if `type_name` is the name of an object or interface type
     pub fn is_reference(&self, type_name: &str) -> bool {
         self.inner
@@ -875,6 +1019,10 @@ mod validations {
 
         errors.append(&mut schema.validate_entity_type_ids());
         errors.append(&mut schema.validate_fields());
+        errors.append(&mut schema.validate_join_tables());
+        errors.append(&mut schema.validate_compressed_fields());
+        errors.append(&mut schema.validate_indexes());
+        errors.append(&mut schema.validate_id_formats());
         errors.append(&mut schema.validate_fulltext_directives());
 
         if errors.is_empty() {
@@ -1125,6 +1273,218 @@ mod validations {
                 })
         }
 
+        /// Fields with a `@joinTable` directive must be non-nullable lists
+        /// of non-nullable elements of an entity type, and can not also be
+        /// derived. The join table can not store `null` elements
+        fn validate_join_tables(&self) -> Vec<SchemaValidationError> {
+            let invalid = |object_type: &s::ObjectType, field: &s::Field, reason: &str| {
+                SchemaValidationError::InvalidJoinTable(
+                    object_type.name.clone(),
+                    field.name.clone(),
+                    reason.to_owned(),
+                )
+            };
+
+            let mut errors = vec![];
+            for object_type in &self.entity_types {
+                for field in &object_type.fields {
+                    if field.find_directive("joinTable").is_none() {
+                        continue;
+                    }
+                    if field.is_derived() {
+                        errors.push(invalid(
+                            object_type,
+                            field,
+                            "derived fields can not be stored in a join table",
+                        ));
+                        continue;
+                    }
+                    let is_non_null_list = match &field.field_type {
+                        s::Type::NonNullType(inner) => match &**inner {
+                            s::Type::ListType(elem) => matches!(**elem, s::Type::NonNullType(_)),
+                            _ => false,
+                        },
+                        _ => false,
+                    };
+                    if !is_non_null_list {
+                        errors.push(invalid(
+                            object_type,
+                            field,
+                            "the field must be a non-nullable list of non-nullable elements",
+                        ));
+                        continue;
+                    }
+                    let base = field.field_type.get_base_type();
+                    if !self.entity_types.iter().any(|typ| typ.name == base) {
+                        errors.push(invalid(
+                            object_type,
+                            field,
+                            "the elements of the list must be of an entity type",
+                        ));
+                    }
+                }
+            }
+            errors
+        }
+
+        /// Fields with a `@compressed` directive must be stored attributes
+        /// of type `String` or `Bytes` other than the id
+        fn validate_compressed_fields(&self) -> Vec<SchemaValidationError> {
+            let invalid = |object_type: &s::ObjectType, field: &s::Field, reason: &str| {
+                SchemaValidationError::InvalidCompressed(
+                    object_type.name.clone(),
+                    field.name.clone(),
+                    reason.to_owned(),
+                )
+            };
+
+            let mut errors = vec![];
+            for object_type in &self.entity_types {
+                for field in &object_type.fields {
+                    if field.find_directive("compressed").is_none() {
+                        continue;
+                    }
+                    if field.is_derived() {
+                        errors.push(invalid(
+                            object_type,
+                            field,
+                            "derived fields are not stored and can not be compressed",
+                        ));
+                    } else if field.name == ID.as_str() {
+                        errors.push(invalid(object_type, field, "the id can not be compressed"));
+                    } else if field.field_type.is_list()
+                        || !matches!(field.field_type.get_base_type(), "String" | "Bytes")
+                    {
+                        errors.push(invalid(
+                            object_type,
+                            field,
+                            "only fields of type String or Bytes can be compressed",
+                        ));
+                    }
+                }
+            }
+            errors
+        }
+
+        /// The `fields` of an `@index` directive must be a non-empty list
+        /// of distinct fields of the entity type that are stored in a
+        /// column of its table and are neither lists nor compressed
+        fn validate_indexes(&self) -> Vec<SchemaValidationError> {
+            let invalid = |object_type: &s::ObjectType, reason: String| {
+                SchemaValidationError::InvalidIndex(object_type.name.clone(), reason)
+            };
+
+            let mut errors = vec![];
+            for object_type in &self.entity_types {
+                for dir in object_type
+                    .directives
+                    .iter()
+                    .filter(|dir| dir.name == "index")
+                {
+                    let fields = match dir.argument("fields") {
+                        Some(Value::List(fields)) if !fields.is_empty() => fields,
+                        _ => {
+                            errors.push(invalid(
+                                object_type,
+                                "`fields` must be a non-empty list of field names".to_string(),
+                            ));
+                            continue;
+                        }
+                    };
+                    let mut seen = BTreeSet::new();
+                    for name in fields {
+                        let name = match name {
+                            Value::String(name) => name,
+                            _ => {
+                                errors.push(invalid(
+                                    object_type,
+                                    "`fields` must be a list of field names".to_string(),
+                                ));
+                                continue;
+                            }
+                        };
+                        if !seen.insert(name) {
+                            errors.push(invalid(
+                                object_type,
+                                format!("field `{}` is listed more than once", name),
+                            ));
+                            continue;
+                        }
+                        let reason = match object_type.field(name) {
+                            None => "does not exist",
+                            Some(field) if field.is_derived() => "is derived and not stored",
+                            Some(field) if field.find_directive("joinTable").is_some() => {
+                                "is stored in a join table"
+                            }
+                            Some(field) if field.find_directive("compressed").is_some() => {
+                                "is compressed"
+                            }
+                            Some(field) if field.field_type.is_list() => "is a list",
+                            Some(_) => continue,
+                        };
+                        errors.push(invalid(
+                            object_type,
+                            format!("field `{}` {} and can not be indexed", name, reason),
+                        ));
+                    }
+                }
+            }
+            errors
+        }
+
+        /// Only the `id` field can have an `@idFormat` directive, only if it
+        /// is of type `String` or `ID`, and with arguments of the right type
+        fn validate_id_formats(&self) -> Vec<SchemaValidationError> {
+            let invalid = |object_type: &s::ObjectType, field: &s::Field, reason: &str| {
+                SchemaValidationError::InvalidIdFormat(
+                    object_type.name.clone(),
+                    field.name.clone(),
+                    reason.to_owned(),
+                )
+            };
+
+            let mut errors = vec![];
+            for object_type in &self.entity_types {
+                for field in &object_type.fields {
+                    let directive = match field.find_directive("idFormat") {
+                        Some(directive) => directive,
+                        None => continue,
+                    };
+                    if field.name != ID.as_str() {
+                        errors.push(invalid(
+                            object_type,
+                            field,
+                            "only the id can have an id format",
+                        ));
+                        continue;
+                    }
+                    if !matches!(field.field_type.get_base_type(), "String" | "ID") {
+                        errors.push(invalid(
+                            object_type,
+                            field,
+                            "only ids of type String or ID can have an id format",
+                        ));
+                        continue;
+                    }
+                    for (name, value) in &directive.arguments {
+                        let reason = match (name.as_str(), value) {
+                            ("lowercaseHex", Value::Boolean(_)) => continue,
+                            ("lowercaseHex", _) => "`lowercaseHex` must be a Boolean".to_string(),
+                            ("maxLength", Value::Int(n))
+                                if n.as_i64().map(|n| n > 0).unwrap_or(false) =>
+                            {
+                                continue
+                            }
+                            ("maxLength", _) => "`maxLength` must be a positive Int".to_string(),
+                            (name, _) => format!("unknown argument `{}`", name),
+                        };
+                        errors.push(invalid(object_type, field, &reason));
+                    }
+                }
+            }
+            errors
+        }
+
         /// 1. All object types besides `_Schema_` must have an id field
         /// 2. The id field must be recognized by IdType
         fn validate_entity_type_ids(&self) -> Vec<SchemaValidationError> {
@@ -1530,6 +1890,220 @@ type Account implements Address @entity { id: ID!, txn: Transaction! @derivedFro
             validate("j: B @derivedFrom(field: \"id\")", "ok");
         }
 
+        #[test]
+        fn test_join_table_validation() {
+            const OTHER_TYPES: &str = "
+type B @entity { id: ID!, a: [A!]! @derivedFrom(field: \"b\") }
+interface I { id: ID! }
+enum E { x, y }";
+
+            fn validate(field: &str, errmsg: &str) {
+                let raw = format!("type A @entity {{ id: ID!\n {} }}\n{}", field, OTHER_TYPES);
+
+                let document = graphql_parser::parse_schema(&raw)
+                    .expect("Failed to parse raw schema")
+                    .into_static();
+                let schema = BaseSchema::new(DeploymentHash::new("id").unwrap(), document).unwrap();
+                let schema = Schema::new(&schema);
+                let errs = schema.validate_join_tables();
+                match errs.first() {
+                    Some(SchemaValidationError::InvalidJoinTable(_, _, msg)) => {
+                        assert_eq!(errmsg, msg)
+                    }
+                    Some(e) => panic!("unexpected validation error {}", e),
+                    None => {
+                        if errmsg != "ok" {
+                            panic!("expected validation for `{}` to fail", field)
+                        }
+                    }
+                }
+            }
+
+            validate("b: [B!]! @joinTable", "ok");
+            validate(
+                "b: [B]! @joinTable",
+                "the field must be a non-nullable list of non-nullable elements",
+            );
+            validate(
+                "b: [B!] @joinTable",
+                "the field must be a non-nullable list of non-nullable elements",
+            );
+            validate(
+                "b: B! @joinTable",
+                "the field must be a non-nullable list of non-nullable elements",
+            );
+            validate(
+                "b: [I!]! @joinTable",
+                "the elements of the list must be of an entity type",
+            );
+            validate(
+                "b: [E!]! @joinTable",
+                "the elements of the list must be of an entity type",
+            );
+            validate(
+                "b: [String!]! @joinTable",
+                "the elements of the list must be of an entity type",
+            );
+            validate(
+                "c: [B!]! @derivedFrom(field: \"a\") @joinTable",
+                "derived fields can not be stored in a join table",
+            );
+        }
+
+        #[test]
+        fn test_compressed_validation() {
+            const OTHER_TYPES: &str = "
+type B @entity { id: ID!, a: [A!]! @derivedFrom(field: \"b\") }";
+
+            fn validate(field: &str, errmsg: &str) {
+                let raw = format!("type A @entity {{ id: ID!\n {} }}\n{}", field, OTHER_TYPES);
+
+                let document = graphql_parser::parse_schema(&raw)
+                    .expect("Failed to parse raw schema")
+                    .into_static();
+                let schema = BaseSchema::new(DeploymentHash::new("id").unwrap(), document).unwrap();
+                let schema = Schema::new(&schema);
+                let errs = schema.validate_compressed_fields();
+                match errs.first() {
+                    Some(SchemaValidationError::InvalidCompressed(_, _, msg)) => {
+                        assert_eq!(errmsg, msg)
+                    }
+                    Some(e) => panic!("unexpected validation error {}", e),
+                    None => {
+                        if errmsg != "ok" {
+                            panic!("expected validation for `{}` to fail", field)
+                        }
+                    }
+                }
+            }
+
+            validate("data: String @compressed", "ok");
+            validate("data: Bytes! @compressed", "ok");
+            validate(
+                "data: [Bytes!]! @compressed",
+                "only fields of type String or Bytes can be compressed",
+            );
+            validate(
+                "data: BigInt @compressed",
+                "only fields of type String or Bytes can be compressed",
+            );
+            validate(
+                "b: B @compressed",
+                "only fields of type String or Bytes can be compressed",
+            );
+            validate(
+                "c: [B!]! @derivedFrom(field: \"a\") @compressed",
+                "derived fields are not stored and can not be compressed",
+            );
+        }
+
+        #[test]
+        fn test_index_validation() {
+            fn validate(index: &str, errmsg: &str) {
+                let raw = format!(
+                    "type A @entity {} {{ id: ID!, name: String!, tags: [String!]!, \
+                     data: Bytes! @compressed, b: B }}\n\
+                     type B @entity {{ id: ID!, a: [A!]! @derivedFrom(field: \"b\") }}",
+                    index
+                );
+
+                let document = graphql_parser::parse_schema(&raw)
+                    .expect("Failed to parse raw schema")
+                    .into_static();
+                let schema = BaseSchema::new(DeploymentHash::new("id").unwrap(), document).unwrap();
+                let schema = Schema::new(&schema);
+                let errs = schema.validate_indexes();
+                match errs.first() {
+                    Some(SchemaValidationError::InvalidIndex(_, msg)) => assert_eq!(errmsg, msg),
+                    Some(e) => panic!("unexpected validation error {}", e),
+                    None => {
+                        if errmsg != "ok" {
+                            panic!("expected validation for `{}` to fail", index)
+                        }
+                    }
+                }
+            }
+
+            validate("@index(fields: [\"name\", \"b\"])", "ok");
+            validate(
+                "@index(fields: [\"name\"]) @index(fields: [\"b\", \"id\"])",
+                "ok",
+            );
+            validate(
+                "@index(fields: [])",
+                "`fields` must be a non-empty list of field names",
+            );
+            validate(
+                "@index(fields: [\"name\", \"name\"])",
+                "field `name` is listed more than once",
+            );
+            validate(
+                "@index(fields: [\"nope\"])",
+                "field `nope` does not exist and can not be indexed",
+            );
+            validate(
+                "@index(fields: [\"tags\"])",
+                "field `tags` is a list and can not be indexed",
+            );
+            validate(
+                "@index(fields: [\"data\"])",
+                "field `data` is compressed and can not be indexed",
+            );
+        }
+
+        #[test]
+        fn test_id_format_validation() {
+            fn validate(id: &str, field: &str, errmsg: &str) {
+                let raw = format!("type A @entity {{ id: {}\n {} }}", id, field);
+
+                let document = graphql_parser::parse_schema(&raw)
+                    .expect("Failed to parse raw schema")
+                    .into_static();
+                let schema = BaseSchema::new(DeploymentHash::new("id").unwrap(), document).unwrap();
+                let schema = Schema::new(&schema);
+                let errs = schema.validate_id_formats();
+                match errs.first() {
+                    Some(SchemaValidationError::InvalidIdFormat(_, _, msg)) => {
+                        assert_eq!(errmsg, msg)
+                    }
+                    Some(e) => panic!("unexpected validation error {}", e),
+                    None => {
+                        if errmsg != "ok" {
+                            panic!("expected validation for `{}` to fail", id)
+                        }
+                    }
+                }
+            }
+
+            validate("ID! @idFormat(lowercaseHex: true, maxLength: 42)", "", "ok");
+            validate("String! @idFormat(maxLength: 42)", "", "ok");
+            validate(
+                "Bytes! @idFormat(lowercaseHex: true)",
+                "",
+                "only ids of type String or ID can have an id format",
+            );
+            validate(
+                "ID!",
+                "name: String @idFormat(maxLength: 42)",
+                "only the id can have an id format",
+            );
+            validate(
+                "ID! @idFormat(lowercaseHex: 1)",
+                "",
+                "`lowercaseHex` must be a Boolean",
+            );
+            validate(
+                "ID! @idFormat(maxLength: 0)",
+                "",
+                "`maxLength` must be a positive Int",
+            );
+            validate(
+                "ID! @idFormat(uppercase: true)",
+                "",
+                "unknown argument `uppercase`",
+            );
+        }
+
         #[test]
         fn test_reserved_type_with_fields() {
             const ROOT_SCHEMA: &str = "
@@ -14,6 +14,8 @@ use std::iter::FromIterator;
 /// Generate full-fledged API schemas from existing GraphQL schemas.
 mod api;
 
+mod aggregation;
+
 /// Utilities for working with GraphQL schema ASTs.
 pub mod ast;
 
@@ -24,11 +26,12 @@ mod input_schema;
 
 pub use api::{is_introspection_field, APISchemaError, INTROSPECTION_QUERY_TYPE};
 
+pub use aggregation::{Aggregate, AggregateFn, Aggregation, AggregationInterval};
 pub use api::{ApiSchema, ErrorPolicy};
 pub use entity_key::EntityKey;
 pub use entity_type::{AsEntityTypeName, EntityType};
 pub use fulltext::{FulltextAlgorithm, FulltextConfig, FulltextDefinition, FulltextLanguage};
-pub use input_schema::{Field, InputSchema, InterfaceType, ObjectType};
+pub use input_schema::{Field, IdFormat, InputSchema, InterfaceType, ObjectType};
 
 pub const SCHEMA_TYPE_NAME: &str = "_Schema_";
 pub const INTROSPECTION_SCHEMA_FIELD_NAME: &str = "__schema";
@@ -67,6 +70,14 @@ pub enum SchemaValidationError {
     InterfaceImplementorsMixId(String, String),
     #[error("Field `{1}` in type `{0}` has invalid @derivedFrom: {2}")]
     InvalidDerivedFrom(String, String, String), // (type, field, reason)
+    #[error("Field `{1}` in type `{0}` has invalid @joinTable: {2}")]
+    InvalidJoinTable(String, String, String), // (type, field, reason)
+    #[error("Field `{1}` in type `{0}` has invalid @compressed: {2}")]
+    InvalidCompressed(String, String, String), // (type, field, reason)
+    #[error("Type `{0}` has invalid @index: {1}")]
+    InvalidIndex(String, String), // (type, reason)
+    #[error("Field `{1}` in type `{0}` has invalid @idFormat: {2}")]
+    InvalidIdFormat(String, String, String), // (type, field, reason)
     #[error("The following type names are reserved: `{0}`")]
     UsageOfReservedTypes(Strings),
     #[error("_Schema_ type is only for @fulltext and must not have any fields")]
@@ -111,6 +122,15 @@ pub enum SchemaValidationError {
     IdFieldMissing(String),
     #[error("{0}")]
     IllegalIdType(String),
+    #[error("Timeseries `{0}` is invalid: {1}")]
+    InvalidTimeseries(String, String), // (type, reason)
+    #[error("Aggregation `{0}` is invalid: {1}")]
+    InvalidAggregation(String, String), // (type, reason)
+    #[error(
+        "Timeseries and aggregations are experimental and need to be enabled \
+         by setting `GRAPH_EXPERIMENTAL_TIMESERIES`"
+    )]
+    TimeseriesNotEnabled,
 }
 
 /// A validated and preprocessed GraphQL schema for a subgraph.
@@ -115,6 +115,12 @@ impl CacheWeight for usize {
     }
 }
 
+impl CacheWeight for u8 {
+    fn indirect_weight(&self) -> usize {
+        0
+    }
+}
+
 impl CacheWeight for EntityType {
     fn indirect_weight(&self) -> usize {
         0
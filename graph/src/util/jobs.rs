@@ -2,6 +2,12 @@
 //! at fixed intervals. This facility is not meant for work that needs
 //! to be done on a tight deadline, solely for work that needs to be done
 //! at reasonably long intervals (like a few hours)
+//!
+//! The runner can record when jobs run with a [`JobRecorder`] so that the
+//! status of the jobs of all nodes can be seen from outside the process
+//! that runs them. When it starts, the runner schedules each job from the
+//! latest recorded run so that restarting a node neither delays a job that
+//! is overdue nor runs one again that just ran.
 
 use slog::{debug, info, o, trace, warn, Logger};
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -9,6 +15,9 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::components::store::StoreError;
 
 /// An individual job to run. Each job should be written in a way that it
 /// doesn't take more than a few minutes.
@@ -18,6 +27,42 @@ pub trait Job: Send + Sync {
     async fn run(&self, logger: &Logger);
 }
 
+/// What is known about the runs of a job on a node
+#[derive(Clone, Debug)]
+pub struct JobStatus {
+    pub node: String,
+    pub name: String,
+    pub interval: Duration,
+    /// How many times the job has been started
+    pub runs: u64,
+    /// When the latest run started
+    pub started_at: Option<DateTime<Utc>>,
+    /// When the latest run that finished did so
+    pub finished_at: Option<DateTime<Utc>>,
+    /// How long the latest run that finished took
+    pub duration: Option<Duration>,
+}
+
+impl JobStatus {
+    /// Whether the job is running right now
+    pub fn is_running(&self) -> bool {
+        match (self.started_at, self.finished_at) {
+            (Some(started), Some(finished)) => started > finished,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+/// Records the runs of jobs somewhere where they can be seen from outside
+/// the process that runs them
+pub trait JobRecorder: Send + Sync {
+    fn started(&self, name: &str, interval: Duration) -> Result<(), StoreError>;
+    fn finished(&self, name: &str, duration: Duration) -> Result<(), StoreError>;
+    /// When the latest run of the job `name` finished, if it ever did
+    fn last_finished(&self, name: &str) -> Result<Option<DateTime<Utc>>, StoreError>;
+}
+
 struct Task {
     job: Arc<dyn Job>,
     logger: Logger,
@@ -28,6 +73,7 @@ struct Task {
 pub struct Runner {
     logger: Logger,
     tasks: Vec<Task>,
+    recorder: Option<Arc<dyn JobRecorder>>,
     pub stop: Arc<AtomicBool>,
 }
 
@@ -36,10 +82,16 @@ impl Runner {
         Runner {
             logger: logger.new(o!("component" => "JobRunner")),
             tasks: Vec::new(),
+            recorder: None,
             stop: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    /// Record every run of a job with `recorder`
+    pub fn record_with(&mut self, recorder: Arc<dyn JobRecorder>) {
+        self.recorder = Some(recorder);
+    }
+
     pub fn register(&mut self, job: Arc<dyn Job>, interval: Duration) {
         let logger = self.logger.new(o!("job" => job.name().to_owned()));
         // We want tasks to start running pretty soon after server start, but
@@ -63,6 +115,21 @@ impl Runner {
             self.tasks.len()
         );
 
+        if let Some(recorder) = &self.recorder {
+            for task in self.tasks.iter_mut() {
+                match recorder.last_finished(task.job.name()) {
+                    Ok(Some(finished)) => {
+                        let since = (Utc::now() - finished).to_std().unwrap_or_default();
+                        task.next_run = Instant::now() + task.interval.saturating_sub(since);
+                    }
+                    Ok(None) => { /* never ran, keep the default schedule */ }
+                    Err(e) => {
+                        warn!(task.logger, "Failed to look up the last run of job"; "error" => e.to_string());
+                    }
+                }
+            }
+        }
+
         for task in &self.tasks {
             let next = task.next_run.saturating_duration_since(Instant::now());
             debug!(self.logger, "Schedule for {}", task.job.name();
@@ -79,7 +146,18 @@ impl Runner {
                     trace!(self.logger, "Running job"; "name" => task.job.name());
                     // We only run one job at a time since we don't want to
                     // deal with the same job possibly starting twice.
+                    let start = Instant::now();
+                    if let Some(recorder) = &self.recorder {
+                        if let Err(e) = recorder.started(task.job.name(), task.interval) {
+                            warn!(task.logger, "Failed to record start of job"; "error" => e.to_string());
+                        }
+                    }
                     task.job.run(&task.logger).await;
+                    if let Some(recorder) = &self.recorder {
+                        if let Err(e) = recorder.finished(task.job.name(), start.elapsed()) {
+                            warn!(task.logger, "Failed to record end of job"; "error" => e.to_string());
+                        }
+                    }
                     task.next_run = Instant::now() + task.interval;
                 }
                 next = next.min(task.next_run);
@@ -152,4 +230,130 @@ mod tests {
             tokio::time::sleep(Duration::from_millis(10)).await;
         }
     }
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Mutex<Vec<&'static str>>,
+    }
+
+    impl JobRecorder for Recorder {
+        fn started(&self, _: &str, _: Duration) -> Result<(), StoreError> {
+            self.events.lock().unwrap().push("started");
+            Ok(())
+        }
+
+        fn finished(&self, _: &str, _: Duration) -> Result<(), StoreError> {
+            self.events.lock().unwrap().push("finished");
+            Ok(())
+        }
+
+        fn last_finished(&self, _: &str) -> Result<Option<DateTime<Utc>>, StoreError> {
+            Ok(None)
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn runs_are_recorded() {
+        let count = Arc::new(Mutex::new(0));
+        let job = CounterJob {
+            count: count.clone(),
+        };
+        let recorder = Arc::new(Recorder::default());
+        let mut runner = Runner::new(&LOGGER);
+        runner.register(Arc::new(job), Duration::from_millis(10));
+        runner.record_with(recorder.clone());
+        let stop = runner.stop.clone();
+
+        crate::spawn_blocking(runner.start());
+
+        let start = Instant::now();
+        while recorder.events.lock().unwrap().len() < 4 {
+            if start.elapsed() > Duration::from_secs(2) {
+                assert!(false, "Running the job twice took longer than 2 seconds");
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        stop.store(true, Ordering::SeqCst);
+        while stop.load(Ordering::SeqCst) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let events = recorder.events.lock().unwrap();
+        assert_eq!(vec!["started", "finished"], events[0..2]);
+        assert_eq!(vec!["started", "finished"], events[2..4]);
+    }
+
+    struct NamedJob {
+        name: &'static str,
+        runs: Arc<Mutex<usize>>,
+    }
+
+    #[async_trait]
+    impl Job for NamedJob {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        async fn run(&self, _: &Logger) {
+            *self.runs.lock().unwrap() += 1;
+        }
+    }
+
+    /// Pretends that the job `overdue` last finished two hours ago, and
+    /// every other job just now
+    struct History;
+
+    impl JobRecorder for History {
+        fn started(&self, _: &str, _: Duration) -> Result<(), StoreError> {
+            Ok(())
+        }
+
+        fn finished(&self, _: &str, _: Duration) -> Result<(), StoreError> {
+            Ok(())
+        }
+
+        fn last_finished(&self, name: &str) -> Result<Option<DateTime<Utc>>, StoreError> {
+            let ago = match name {
+                "overdue" => chrono::Duration::hours(2),
+                _ => chrono::Duration::zero(),
+            };
+            Ok(Some(Utc::now() - ago))
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn jobs_are_scheduled_from_recorded_runs() {
+        const ONE_HOUR: Duration = Duration::from_secs(60 * 60);
+
+        let overdue = Arc::new(Mutex::new(0));
+        let recent = Arc::new(Mutex::new(0));
+        let mut runner = Runner::new(&LOGGER);
+        runner.record_with(Arc::new(History));
+        let job = NamedJob {
+            name: "overdue",
+            runs: overdue.clone(),
+        };
+        runner.register(Arc::new(job), ONE_HOUR);
+        let job = NamedJob {
+            name: "recent",
+            runs: recent.clone(),
+        };
+        runner.register(Arc::new(job), ONE_HOUR);
+        // The runner sleeps for an hour after the first run; spawning it
+        // as a task lets the test runtime drop it while it sleeps
+        tokio::spawn(runner.start());
+
+        // Without the recorded runs, neither job would run for the first
+        // `ONE_HOUR / 91`, i.e., about 40s
+        let start = Instant::now();
+        while *overdue.lock().unwrap() == 0 {
+            if start.elapsed() > Duration::from_secs(2) {
+                assert!(false, "The overdue job did not run right away");
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(1, *overdue.lock().unwrap());
+        assert_eq!(0, *recent.lock().unwrap());
+    }
 }
@@ -33,3 +33,5 @@ pub mod monitored;
 pub mod intern;
 
 pub mod herd_cache;
+
+pub mod rate_limit;
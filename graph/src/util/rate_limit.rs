@@ -0,0 +1,95 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Limit how often an operation happens to `rate` times per second on
+/// average. Up to one second's worth of operations can happen in a burst
+/// after the operation has not happened for a while.
+///
+/// Callers reserve their turn before they wait for it, so that callers that
+/// wait at the same time go in the order in which they called `wait`
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    state: Mutex<State>,
+}
+
+struct State {
+    /// How many operations can happen right away; negative if earlier
+    /// callers have reserved turns that are still in the future
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// Create a limiter that allows `rate` operations per second. `rate`
+    /// must be positive
+    pub fn new(rate: f64) -> Self {
+        assert!(rate > 0.0, "the rate of a rate limiter must be positive");
+        let burst = rate.max(1.0);
+        RateLimiter {
+            rate,
+            burst,
+            state: Mutex::new(State {
+                tokens: burst,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Reserve a turn for one operation at `now` and return how long the
+    /// caller has to wait until it is their turn
+    fn reserve(&self, now: Instant) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let elapsed = now.saturating_duration_since(state.updated).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.rate).min(self.burst);
+        state.updated = now.max(state.updated);
+        state.tokens -= 1.0;
+        if state.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.tokens / self.rate)
+        }
+    }
+
+    /// Wait until the operation may happen and return how long that took
+    pub async fn wait(&self) -> Duration {
+        let delay = self.reserve(Instant::now());
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        delay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserves_turns_in_order() {
+        let limiter = RateLimiter::new(10.0);
+        let start = limiter.state.lock().unwrap().updated;
+
+        // The burst goes through right away
+        for _ in 0..10 {
+            assert_eq!(Duration::ZERO, limiter.reserve(start));
+        }
+        // After that, every caller waits 100ms longer than the one before
+        let delay = limiter.reserve(start);
+        assert_eq!(100, delay.as_millis());
+        let delay = limiter.reserve(start);
+        assert_eq!(200, delay.as_millis());
+
+        // A second later, the reserved turns have passed and there is room
+        // for 8 more operations
+        let later = start + Duration::from_secs(1);
+        for _ in 0..8 {
+            assert_eq!(Duration::ZERO, limiter.reserve(later));
+        }
+        assert_eq!(100, limiter.reserve(later).as_millis());
+    }
+}
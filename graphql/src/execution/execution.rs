@@ -16,7 +16,7 @@ use std::time::Instant;
 use std::{borrow::ToOwned, collections::HashSet};
 
 use graph::data::graphql::*;
-use graph::data::query::CacheStatus;
+use graph::data::query::{CacheStatus, RunningQuery};
 use graph::env::CachedSubgraphIds;
 use graph::prelude::*;
 use graph::schema::ast as sast;
@@ -203,6 +203,10 @@ where
     /// Time at which the query times out.
     pub deadline: Option<Instant>,
 
+    /// The entry for this query in the running queries, through which an
+    /// operator can cancel it
+    pub running: Option<Arc<RunningQuery>>,
+
     /// Max value for `first`.
     pub max_first: u32,
 
@@ -241,6 +245,7 @@ where
             resolver: introspection_resolver,
             query: self.query.cheap_clone(),
             deadline: self.deadline,
+            running: self.running.cheap_clone(),
             max_first: std::u32::MAX,
             max_skip: std::u32::MAX,
 
@@ -249,6 +254,20 @@ where
             trace: ENV_VARS.log_sql_timing(),
         }
     }
+
+    /// Return an error if the query has timed out or was cancelled and
+    /// should therefore stop
+    pub fn check_interrupted(&self) -> Result<(), QueryExecutionError> {
+        if let Some(deadline) = self.deadline {
+            if deadline < Instant::now() {
+                return Err(QueryExecutionError::Timeout);
+            }
+        }
+        match &self.running {
+            Some(running) if running.is_cancelled() => Err(QueryExecutionError::Cancelled),
+            _ => Ok(()),
+        }
+    }
 }
 
 pub(crate) async fn execute_root_selection_set_uncached(
@@ -505,12 +524,9 @@ async fn execute_selection_set_to_map<'a>(
 
     // Process all field groups in order
     for field in selection_set.fields_for(object_type)? {
-        match ctx.deadline {
-            Some(deadline) if deadline < Instant::now() => {
-                errors.push(QueryExecutionError::Timeout);
-                break;
-            }
-            _ => (),
+        if let Err(e) = ctx.check_interrupted() {
+            errors.push(e);
+            break;
         }
 
         let response_key = field.response_key();
@@ -13,8 +13,8 @@ use std::time::Instant;
 use std::{collections::hash_map::DefaultHasher, convert::TryFrom};
 
 use graph::data::graphql::{ext::TypeExt, ObjectOrInterface};
-use graph::data::query::QueryExecutionError;
 use graph::data::query::{Query as GraphDataQuery, QueryVariables};
+use graph::data::query::{QueryExecutionError, QueryTiming, SLOW_QUERIES};
 use graph::prelude::{
     info, o, q, r, s, warn, BlockNumber, CheapClone, DeploymentHash, EntityRange, GraphQLMetrics,
     Logger, TryFromValue, ENV_VARS,
@@ -128,6 +128,9 @@ pub struct Query {
 
     pub logger: Logger,
 
+    /// The complexity of the query as computed by `check_complexity`
+    pub complexity: u64,
+
     start: Instant,
 
     kind: Kind,
@@ -258,10 +261,8 @@ impl Query {
         };
 
         // It's important to check complexity first, so `validate_fields`
-        // doesn't risk a stack overflow from invalid queries. We don't
-        // really care about the resulting complexity, only that all the
-        // checks that `check_complexity` performs pass successfully
-        let _ = raw_query.check_complexity(max_complexity, max_depth)?;
+        // doesn't risk a stack overflow from invalid queries
+        let complexity = raw_query.check_complexity(max_complexity, max_depth)?;
         raw_query.validate_fields()?;
         let selection_set = raw_query.convert()?;
 
@@ -272,6 +273,7 @@ impl Query {
             kind,
             network,
             logger,
+            complexity,
             start,
             query_text: query.query_text.cheap_clone(),
             variables_text: query.variables_text.cheap_clone(),
@@ -367,6 +369,44 @@ impl Query {
         }
     }
 
+    /// Log the query and remember it in `SLOW_QUERIES` if it took longer
+    /// than `GRAPH_GRAPHQL_SLOW_QUERY_MS`
+    pub fn log_if_slow(&self, block: BlockNumber, timing: QueryTiming) {
+        let threshold = match ENV_VARS.graphql.slow_query_threshold {
+            Some(threshold) => threshold,
+            None => return,
+        };
+        if timing.total() < threshold {
+            return;
+        }
+
+        let variables_hash = {
+            let mut hasher = DefaultHasher::new();
+            self.variables_text.hash(&mut hasher);
+            hasher.finish()
+        };
+        warn!(
+            &self.logger,
+            "Slow query";
+            "query" => &self.query_text,
+            "shape_hash" => format!("{:x}", self.shape_hash),
+            "variables_hash" => format!("{:x}", variables_hash),
+            "block" => block,
+            "query_time_ms" => timing.total().as_millis(),
+            "prepare_ms" => timing.prepare.as_millis(),
+            "wait_ms" => timing.wait.as_millis(),
+            "execute_ms" => timing.execute.as_millis(),
+        );
+        SLOW_QUERIES.record(
+            self.schema.id(),
+            self.shape_hash,
+            &self.query_text,
+            variables_hash,
+            block,
+            timing,
+        );
+    }
+
     /// Log details about how the part of the query corresponding to
     /// `selection_set` was cached
     pub fn log_cache_status(
@@ -481,6 +521,27 @@ struct RawQuery<'s> {
     root_type: &'s s::ObjectType,
 }
 
+/// The number of conditions in the `where` argument `filter`, each counted
+/// with `weight`. Conditions on child entities count double since they
+/// need a join. Variables in `filter` must have been resolved so that a
+/// filter passed in a variable is weighed like one written out in the query
+fn filter_conditions(filter: &r::Value, weight: u64) -> u64 {
+    match filter {
+        r::Value::Object(conditions) => conditions
+            .iter()
+            .map(|(key, value)| match (key, value) {
+                ("and" | "or", r::Value::List(filters)) => filters
+                    .iter()
+                    .map(|filter| filter_conditions(filter, weight))
+                    .fold(0, u64::saturating_add),
+                (_, r::Value::Object(_)) => filter_conditions(value, weight.saturating_mul(2)),
+                _ => weight,
+            })
+            .fold(0, u64::saturating_add),
+        _ => weight,
+    }
+}
+
 impl<'s> RawQuery<'s> {
     fn check_complexity(
         &self,
@@ -556,15 +617,23 @@ impl<'s> RawQuery<'s> {
 
                         // For collection queries, check the `first` argument.
                         let max_entities = qast::get_argument_value(&field.arguments, "first")
+                            .map(|arg| interpolate_value(arg.clone(), &self.variables))
                             .and_then(|arg| match arg {
-                                q::Value::Int(n) => Some(n.as_i64()? as u64),
+                                r::Value::Int(n) => Some(n as u64),
                                 _ => None,
                             })
                             .unwrap_or(EntityRange::FIRST as u64);
+                        let filter_complexity = qast::get_argument_value(&field.arguments, "where")
+                            .map(|filter| interpolate_value(filter.clone(), &self.variables))
+                            .map(|filter| filter_conditions(&filter, 1))
+                            .unwrap_or(0)
+                            .checked_mul(ENV_VARS.graphql.filter_complexity)
+                            .ok_or(Overflow)?;
                         max_entities
                             .checked_add(
                                 max_entities.checked_mul(field_complexity).ok_or(Overflow)?,
                             )
+                            .and_then(|complexity| complexity.checked_add(filter_complexity))
                             .ok_or(Overflow)
                     }
                     q::Selection::FragmentSpread(fragment) => {
@@ -740,59 +809,50 @@ struct Transform {
     fragments: HashMap<String, q::FragmentDefinition>,
 }
 
-impl Transform {
-    /// Look up the value of the variable `name`. If the variable is not
-    /// defined, return `r::Value::Null`
-    // graphql-bug-compat: Once queries are fully validated, all variables
-    // will be defined
-    fn variable(&self, name: &str) -> r::Value {
-        self.variables.get(name).cloned().unwrap_or(r::Value::Null)
+/// Turn `value` into an `r::Value` by resolving variable references. A
+/// variable that is not defined resolves to `r::Value::Null`
+// graphql-bug-compat: Once queries are fully validated, all variables
+// will be defined
+fn interpolate_value(value: q::Value, variables: &HashMap<String, r::Value>) -> r::Value {
+    match value {
+        q::Value::Variable(var) => variables.get(&var).cloned().unwrap_or(r::Value::Null),
+        q::Value::Int(ref num) => {
+            r::Value::Int(num.as_i64().expect("q::Value::Int contains an i64"))
+        }
+        q::Value::Float(f) => r::Value::Float(f),
+        q::Value::String(s) => r::Value::String(s),
+        q::Value::Boolean(b) => r::Value::Boolean(b),
+        q::Value::Null => r::Value::Null,
+        q::Value::Enum(s) => r::Value::Enum(s),
+        q::Value::List(vals) => {
+            let vals = vals
+                .into_iter()
+                .map(|val| interpolate_value(val, variables))
+                .collect();
+            r::Value::List(vals)
+        }
+        q::Value::Object(map) => {
+            let mut rmap = BTreeMap::new();
+            for (key, value) in map.into_iter() {
+                let value = interpolate_value(value, variables);
+                rmap.insert(key.into(), value);
+            }
+            r::Value::object(rmap)
+        }
     }
+}
 
+impl Transform {
     /// Interpolate variable references in the arguments `args`
-    fn interpolate_arguments(
-        &self,
-        args: Vec<(String, q::Value)>,
-        pos: &Pos,
-    ) -> Vec<(String, r::Value)> {
+    fn interpolate_arguments(&self, args: Vec<(String, q::Value)>) -> Vec<(String, r::Value)> {
         args.into_iter()
             .map(|(name, val)| {
-                let val = self.interpolate_value(val, pos);
+                let val = interpolate_value(val, &self.variables);
                 (name, val)
             })
             .collect()
     }
 
-    /// Turn `value` into an `r::Value` by resolving variable references
-    fn interpolate_value(&self, value: q::Value, pos: &Pos) -> r::Value {
-        match value {
-            q::Value::Variable(var) => self.variable(&var),
-            q::Value::Int(ref num) => {
-                r::Value::Int(num.as_i64().expect("q::Value::Int contains an i64"))
-            }
-            q::Value::Float(f) => r::Value::Float(f),
-            q::Value::String(s) => r::Value::String(s),
-            q::Value::Boolean(b) => r::Value::Boolean(b),
-            q::Value::Null => r::Value::Null,
-            q::Value::Enum(s) => r::Value::Enum(s),
-            q::Value::List(vals) => {
-                let vals = vals
-                    .into_iter()
-                    .map(|val| self.interpolate_value(val, pos))
-                    .collect();
-                r::Value::List(vals)
-            }
-            q::Value::Object(map) => {
-                let mut rmap = BTreeMap::new();
-                for (key, value) in map.into_iter() {
-                    let value = self.interpolate_value(value, pos);
-                    rmap.insert(key.into(), value);
-                }
-                r::Value::object(rmap)
-            }
-        }
-    }
-
     /// Interpolate variable references in directives. Return the directives
     /// and a boolean indicating whether the element these directives are
     /// attached to should be skipped
@@ -808,7 +868,7 @@ impl Transform {
                     position,
                     arguments,
                 } = dir;
-                let arguments = self.interpolate_arguments(arguments, &position);
+                let arguments = self.interpolate_arguments(arguments);
                 a::Directive {
                     name,
                     position,
@@ -924,7 +984,7 @@ impl Transform {
             return Ok(None);
         }
 
-        let mut arguments = self.interpolate_arguments(arguments, &position);
+        let mut arguments = self.interpolate_arguments(arguments);
         self.coerce_argument_values(&mut arguments, parent_type, &name)?;
 
         let is_leaf_type = self.schema.document().is_leaf_type(&field_type.field_type);
@@ -1056,3 +1116,65 @@ impl Transform {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use graph::data::graphql::object;
+    use graph::prelude::{q, r};
+
+    use super::{filter_conditions, interpolate_value};
+
+    fn filter(conditions: Vec<(&str, q::Value)>) -> q::Value {
+        q::Value::Object(BTreeMap::from_iter(
+            conditions
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value)),
+        ))
+    }
+
+    #[test]
+    fn filters_in_variables_are_weighed_like_inline_filters() {
+        // `{ name: "x", owner_: { id: "1" }, or: [{ id: "2" }, { id: "3" }] }`
+        let inline = filter(vec![
+            ("name", q::Value::String("x".to_string())),
+            (
+                "owner_",
+                filter(vec![("id", q::Value::String("1".to_string()))]),
+            ),
+            (
+                "or",
+                q::Value::List(vec![
+                    filter(vec![("id", q::Value::String("2".to_string()))]),
+                    filter(vec![("id", q::Value::String("3".to_string()))]),
+                ]),
+            ),
+        ]);
+        let variables = HashMap::from([(
+            "filter".to_string(),
+            object! {
+                name: "x",
+                owner_: object! { id: "1" },
+                or: vec![object! { id: "2" }, object! { id: "3" }],
+            },
+        )]);
+        let no_variables = HashMap::new();
+
+        let weight = |filter: q::Value, variables: &HashMap<String, r::Value>| {
+            filter_conditions(&interpolate_value(filter, variables), 1)
+        };
+
+        assert_eq!(5, weight(inline.clone(), &no_variables));
+        assert_eq!(
+            5,
+            weight(q::Value::Variable("filter".to_string()), &variables)
+        );
+        // Variables nested inside a filter are resolved, too
+        let nested = filter(vec![(
+            "and",
+            q::Value::List(vec![inline, q::Value::Variable("filter".to_string())]),
+        )]);
+        assert_eq!(10, weight(nested, &variables));
+    }
+}
@@ -62,6 +62,9 @@ pub enum BlockConstraint {
     /// Execute the query on the latest block only if the the subgraph has progressed to or past the
     /// given block number.
     Min(BlockNumber),
+    /// Execute the query on the latest block that can no longer be
+    /// reverted by a reorg
+    Final,
     Latest,
 }
 
@@ -80,6 +83,23 @@ impl TryFromValue for BlockConstraint {
             _ => return Err(anyhow!("invalid `BlockConstraint`")),
         };
 
+        // Silently ignoring `final` would make a query look like it only
+        // returns data that can not be reverted while it does not
+        let is_final = map
+            .get("final")
+            .map(bool::try_from_value)
+            .transpose()?
+            .unwrap_or(false);
+        if is_final
+            && ["hash", "number", "number_gte"]
+                .iter()
+                .any(|key| map.get(key).is_some())
+        {
+            return Err(anyhow!(
+                "`final: true` can not be combined with `hash`, `number` or `number_gte`"
+            ));
+        }
+
         if let Some(hash) = map.get("hash") {
             Ok(BlockConstraint::Hash(TryFromValue::try_from_value(hash)?))
         } else if let Some(number_value) = map.get("number") {
@@ -90,6 +110,12 @@ impl TryFromValue for BlockConstraint {
             Ok(BlockConstraint::Min(BlockNumber::try_from_value(
                 number_value,
             )?))
+        } else if map.get("final").is_some() {
+            if is_final {
+                Ok(BlockConstraint::Final)
+            } else {
+                Ok(BlockConstraint::Latest)
+            }
         } else {
             Err(anyhow!("invalid `BlockConstraint`"))
         }
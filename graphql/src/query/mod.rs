@@ -1,3 +1,4 @@
+use graph::data::query::RunningQuery;
 use graph::prelude::{BlockPtr, CheapClone, QueryExecutionError, QueryResult};
 use std::sync::Arc;
 use std::time::Instant;
@@ -18,6 +19,10 @@ pub struct QueryExecutionOptions<R> {
     /// Time at which the query times out.
     pub deadline: Option<Instant>,
 
+    /// The entry for the query in the running queries, if it is
+    /// registered there
+    pub running: Option<Arc<RunningQuery>>,
+
     /// Maximum value for the `first` argument.
     pub max_first: u32,
 
@@ -45,6 +50,7 @@ where
         resolver: options.resolver,
         query: query.clone(),
         deadline: options.deadline,
+        running: options.running,
         max_first: options.max_first,
         max_skip: options.max_skip,
         cache_status: Default::default(),
@@ -1,25 +1,33 @@
+use std::future::Future;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use crate::metrics::GraphQLMetrics;
-use crate::prelude::{QueryExecutionOptions, StoreResolver, SubscriptionExecutionOptions};
+use crate::prelude::{
+    BlockConstraint, QueryExecutionOptions, StoreResolver, SubscriptionExecutionOptions,
+};
 use crate::query::execute_query;
 use crate::subscription::execute_prepared_subscription;
 use graph::prelude::MetricsRegistry;
 use graph::{
-    components::store::SubscriptionManager,
+    components::store::{SubscriptionManager, UnitStream},
     prelude::{
-        async_trait, o, CheapClone, DeploymentState, GraphQLMetrics as GraphQLMetricsTrait,
-        GraphQlRunner as GraphQlRunnerTrait, Logger, Query, QueryExecutionError, Subscription,
-        SubscriptionError, SubscriptionResult, ENV_VARS,
+        async_trait, futures03::StreamExt, o, tokio, CheapClone, DeploymentState,
+        GraphQLMetrics as GraphQLMetricsTrait, GraphQlRunner as GraphQlRunnerTrait, Logger, Query,
+        QueryExecutionError, Subscription, SubscriptionError, SubscriptionFilter,
+        SubscriptionResult, ENV_VARS,
     },
 };
 use graph::{data::graphql::load_manager::LoadManager, prelude::QueryStoreManager};
 use graph::{
-    data::query::{QueryResults, QueryTarget},
+    data::query::{QueryResults, QueryTarget, QueryTiming, RUNNING_QUERIES},
     prelude::QueryStore,
 };
 
+/// How often `wait_for_blocks` looks at the deployment between store
+/// events; blocks that do not change any entities do not send one
+const BLOCK_WAIT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
 /// GraphQL runner implementation for The Graph.
 pub struct GraphQlRunner<S, SM> {
     logger: Logger,
@@ -89,6 +97,80 @@ where
         Ok(())
     }
 
+    /// Wait until the deployment has indexed the blocks that the block
+    /// constraints of a query ask for, but no longer than
+    /// `GRAPH_GRAPHQL_BLOCK_WAIT_TIMEOUT`. Return the state of the
+    /// deployment that the query should use; it is only different from
+    /// `state` if we waited
+    ///
+    /// We look at the deployment again whenever the store sends an event
+    /// for it, which it does after every write that changes entities.
+    /// Blocks without changes do not send an event, and we therefore also
+    /// look every `BLOCK_WAIT_POLL_INTERVAL` and one last time when the
+    /// wait times out
+    async fn wait_for_blocks(
+        &self,
+        store: &dyn QueryStore,
+        constraints: Vec<&BlockConstraint>,
+        state: DeploymentState,
+    ) -> Result<DeploymentState, QueryExecutionError> {
+        async fn reached(
+            store: &dyn QueryStore,
+            constraints: &[&BlockConstraint],
+            state: &DeploymentState,
+        ) -> Result<bool, QueryExecutionError> {
+            let latest = state.latest_block.number;
+            for bc in constraints {
+                let reached = match bc {
+                    BlockConstraint::Number(number) | BlockConstraint::Min(number) => {
+                        latest >= *number
+                    }
+                    BlockConstraint::Hash(hash) => store
+                        .block_number_with_timestamp(hash)
+                        .await?
+                        .map_or(false, |(number, _)| latest >= number),
+                    BlockConstraint::Final | BlockConstraint::Latest => true,
+                };
+                if !reached {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+
+        let timeout = match ENV_VARS.graphql.block_wait_timeout {
+            Some(timeout) => timeout,
+            None => return Ok(state),
+        };
+        if reached(store, &constraints, &state).await? {
+            return Ok(state);
+        }
+        let deadline = Instant::now() + timeout;
+
+        let schema = store.input_schema()?;
+        let filters = schema
+            .entity_types()
+            .into_iter()
+            .map(|entity_type| SubscriptionFilter::Entities(schema.id().clone(), entity_type))
+            .collect();
+        // The stream starts out with a notification, which makes us look
+        // at the deployment once more right away; that way, we do not miss
+        // writes that happened before we subscribed
+        let mut changes = self.subscription_manager.subscribe_no_payload(filters);
+        let constraints = constraints.as_slice();
+        check_on_changes(
+            &mut changes,
+            deadline,
+            BLOCK_WAIT_POLL_INTERVAL,
+            move || async move {
+                let state = store.deployment_state().await?;
+                let done = reached(store, constraints, &state).await?;
+                Ok((state, done))
+            },
+        )
+        .await
+    }
+
     async fn execute(
         &self,
         query: Query,
@@ -124,6 +206,8 @@ where
 
         let max_depth = max_depth.unwrap_or(ENV_VARS.graphql.max_depth);
         let trace = query.trace;
+        let client = query.client.clone();
+        let start = Instant::now();
         let query = crate::execution::Query::new(
             &self.logger,
             schema,
@@ -133,6 +217,8 @@ where
             max_depth,
             metrics.cheap_clone(),
         )?;
+        let prepare = start.elapsed();
+        let start = Instant::now();
         self.load_manager
             .decide(
                 &store.wait_stats().map_err(QueryExecutionError::from)?,
@@ -142,7 +228,19 @@ where
                 query.query_text.as_ref(),
             )
             .to_result()?;
+        let running = RUNNING_QUERIES.register(query.schema.id().clone(), query.shape_hash, client);
         let by_block_constraint = query.block_constraint()?;
+        // All parts of the query are answered from the state that we have
+        // after waiting, so that they see the same blocks
+        let state = self
+            .wait_for_blocks(
+                store.as_ref(),
+                by_block_constraint.iter().map(|(bc, _)| bc).collect(),
+                state,
+            )
+            .await?;
+        let wait = start.elapsed();
+        let start = Instant::now();
         let mut max_block = 0;
         let mut result: QueryResults = QueryResults::empty();
 
@@ -169,6 +267,7 @@ where
                 QueryExecutionOptions {
                     resolver,
                     deadline: ENV_VARS.graphql.query_timeout.map(|t| Instant::now() + t),
+                    running: Some(running.query().cheap_clone()),
                     max_first: max_first.unwrap_or(ENV_VARS.graphql.max_first),
                     max_skip: max_skip.unwrap_or(ENV_VARS.graphql.max_skip),
                     trace,
@@ -179,7 +278,18 @@ where
             result.append(query_res);
         }
 
+        if trace {
+            result.set_complexity(query.complexity);
+        }
         query.log_execution(max_block);
+        query.log_if_slow(
+            max_block,
+            QueryTiming {
+                prepare,
+                wait,
+                execute: start.elapsed(),
+            },
+        );
         self.deployment_changed(store.as_ref(), state, max_block as u64)
             .await
             .map_err(QueryResults::from)
@@ -187,6 +297,40 @@ where
     }
 }
 
+/// Run `check` whenever `changes` reports a change, and at least every
+/// `poll_interval`, until `check` says that it found what it was looking
+/// for, and one last time once `deadline` passes. Return what the last run
+/// of `check` found
+async fn check_on_changes<T, F, Fut>(
+    changes: &mut UnitStream,
+    deadline: Instant,
+    poll_interval: Duration,
+    mut check: F,
+) -> Result<T, QueryExecutionError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<(T, bool), QueryExecutionError>>,
+{
+    let mut ended = false;
+    loop {
+        let wait = deadline
+            .saturating_duration_since(Instant::now())
+            .min(poll_interval);
+        if ended {
+            tokio::time::sleep(wait).await;
+        } else if let Ok(None) = tokio::time::timeout(wait, changes.next()).await {
+            // There will be no more notifications, and we only poll from
+            // now on
+            ended = true;
+            continue;
+        }
+        let (value, done) = check().await?;
+        if done || Instant::now() >= deadline {
+            return Ok(value);
+        }
+    }
+}
+
 #[async_trait]
 impl<S, SM> GraphQlRunnerTrait for GraphQlRunner<S, SM>
 where
@@ -281,3 +425,77 @@ where
         self.graphql_metrics.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    use graph::components::store::UnitStream;
+    use graph::prelude::futures03::{stream, StreamExt};
+    use graph::prelude::tokio;
+
+    use super::check_on_changes;
+
+    /// Count how often `changes` and polling every `poll_interval` make
+    /// us check, where the check is done once it ran `done_after` times
+    async fn checks(
+        mut changes: UnitStream,
+        poll_interval: Duration,
+        done_after: usize,
+    ) -> (usize, bool, Duration) {
+        let count = &AtomicUsize::new(0);
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(100);
+        let (checks, done) =
+            check_on_changes(&mut changes, deadline, poll_interval, move || async move {
+                let checks = count.fetch_add(1, Ordering::SeqCst) + 1;
+                let done = checks >= done_after;
+                Ok(((checks, done), done))
+            })
+            .await
+            .unwrap();
+        (checks, done, start.elapsed())
+    }
+
+    /// Longer than the wait, so that we never poll
+    const NO_POLL: Duration = Duration::from_secs(10);
+
+    #[tokio::test]
+    async fn checks_run_when_the_deployment_changes() {
+        let changes =
+            || -> UnitStream { Box::new(stream::iter(vec![(); 3]).chain(stream::pending())) };
+
+        // We stop as soon as the check is done
+        let (count, done, elapsed) = checks(changes(), NO_POLL, 2).await;
+        assert_eq!((2, true), (count, done));
+        assert!(elapsed < Duration::from_millis(100));
+
+        // Without further changes, we check one last time at the deadline
+        let (count, done, elapsed) = checks(changes(), NO_POLL, 10).await;
+        assert_eq!((4, false), (count, done));
+        assert!(elapsed >= Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn checks_wait_for_the_deadline_when_changes_end() {
+        let (count, done, elapsed) = checks(Box::new(stream::empty()), NO_POLL, 10).await;
+        assert_eq!((1, false), (count, done));
+        assert!(elapsed >= Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn checks_poll_without_changes() {
+        let poll_interval = Duration::from_millis(10);
+
+        // Blocks that change nothing send no events
+        let (count, done, elapsed) = checks(Box::new(stream::pending()), poll_interval, 3).await;
+        assert_eq!((3, true), (count, done));
+        assert!(elapsed >= Duration::from_millis(30));
+        assert!(elapsed < Duration::from_millis(100));
+
+        // We keep polling once the changes end
+        let (count, done, _) = checks(Box::new(stream::empty()), poll_interval, 3).await;
+        assert_eq!((3, true), (count, done));
+    }
+}
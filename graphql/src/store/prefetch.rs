@@ -13,7 +13,6 @@ use graph::slog::warn;
 use graph::util::cache_weight;
 use std::collections::{BTreeMap, HashMap};
 use std::rc::Rc;
-use std::time::Instant;
 
 use graph::data::graphql::*;
 use graph::schema::{ast as sast, EntityType, InputSchema};
@@ -232,12 +231,16 @@ impl Node {
 enum JoinField<'a> {
     List(&'a str),
     Scalar(&'a str),
+    /// A list whose ids are stored in a join table
+    Join(&'a str),
 }
 
 impl<'a> JoinField<'a> {
     fn new(field: &'a s::Field) -> Self {
         let name = field.name.as_str();
-        if sast::is_list_or_non_null_list_field(field) {
+        if field.find_directive("joinTable").is_some() {
+            JoinField::Join(name)
+        } else if sast::is_list_or_non_null_list_field(field) {
             JoinField::List(name)
         } else {
             JoinField::Scalar(name)
@@ -247,7 +250,9 @@ impl<'a> JoinField<'a> {
     fn window_attribute(&self) -> WindowAttribute {
         match self {
             JoinField::Scalar(name) => WindowAttribute::Scalar(name.to_string()),
-            JoinField::List(name) => WindowAttribute::List(name.to_string()),
+            JoinField::List(name) | JoinField::Join(name) => {
+                WindowAttribute::List(name.to_string())
+            }
         }
     }
 }
@@ -363,6 +368,15 @@ impl<'a> JoinCond<'a> {
                             .collect::<Result<Vec<_>, _>>()?;
                         (ids, ParentLink::List(child_ids))
                     }
+                    JoinField::Join(child_field) => {
+                        // The child ids are in a join table; all we need
+                        // are the parent ids
+                        let ids = IdList::try_from_iter(
+                            &self.parent_type,
+                            parents_by_id.into_iter().map(|(id, _)| id),
+                        )?;
+                        (ids, ParentLink::Join(Word::from(*child_field)))
+                    }
                 };
                 Ok((
                     ids,
@@ -610,11 +624,9 @@ fn execute_selection_set<'a>(
 
     // Process all field groups in order
     for (object_type, fields) in selection_set.interior_fields() {
-        if let Some(deadline) = ctx.deadline {
-            if deadline < Instant::now() {
-                errors.push(QueryExecutionError::Timeout);
-                break;
-            }
+        if let Err(e) = ctx.check_interrupted() {
+            errors.push(e);
+            break;
         }
 
         // Filter out parents that do not match the type condition.
@@ -762,6 +774,8 @@ fn fetch(
     )?;
     query.trace = ctx.trace;
     query.query_id = Some(ctx.query.query_id.clone());
+    query.deadline = ctx.deadline;
+    query.running = ctx.running.cheap_clone();
 
     if multiplicity == ChildMultiplicity::Single {
         // Suppress 'order by' in lookups of scalar values since
@@ -797,16 +811,20 @@ pub(crate) struct SelectedAttributes(BTreeMap<String, AttributeNames>);
 
 impl SelectedAttributes {
     /// Extract the attributes we should select from `selection_set`. In
-    /// particular, disregard derived fields since they are not stored
+    /// particular, disregard derived fields and fields stored in join
+    /// tables since they do not have a column
     fn for_field(field: &a::Field) -> Result<SelectedAttributes, Vec<QueryExecutionError>> {
         let mut map = BTreeMap::new();
         for (object_type, fields) in field.selection_set.fields() {
             let column_names = fields
                 .filter(|field| {
-                    // Keep fields that are not derived and for which we
-                    // can find the field type
+                    // Keep fields that are not derived or stored in a join
+                    // table and for which we can find the field type
                     sast::get_field(object_type, &field.name)
-                        .map(|field_type| !field_type.is_derived())
+                        .map(|field_type| {
+                            !field_type.is_derived()
+                                && field_type.find_directive("joinTable").is_none()
+                        })
                         .unwrap_or(false)
                 })
                 .filter_map(|field| {
@@ -29,6 +29,8 @@ pub struct StoreResolver {
     pub(crate) store: Arc<dyn QueryStore>,
     subscription_manager: Arc<dyn SubscriptionManager>,
     pub(crate) block_ptr: Option<BlockPtrTs>,
+    /// The latest block of the subgraph that can not be reverted anymore
+    final_block: Option<BlockNumber>,
     deployment: DeploymentHash,
     has_non_fatal_errors: bool,
     error_policy: ErrorPolicy,
@@ -77,6 +79,7 @@ impl StoreResolver {
             store,
             subscription_manager,
             block_ptr: None,
+            final_block: None,
             deployment,
 
             // Checking for non-fatal errors does not work with subscriptions.
@@ -118,6 +121,7 @@ impl StoreResolver {
             store,
             subscription_manager,
             block_ptr: Some(block_ptr),
+            final_block: state.final_block_number(),
             deployment,
             has_non_fatal_errors,
             error_policy,
@@ -212,6 +216,22 @@ impl StoreResolver {
 
                 Ok(BlockPtrTs { ptr, timestamp })
             }
+            BlockConstraint::Final => {
+                let number = state.final_block_number().ok_or_else(|| {
+                    QueryExecutionError::ValueParseError(
+                        "block.final".to_owned(),
+                        format!(
+                            "subgraph {} has not indexed any blocks yet that \
+                                can no longer be reverted",
+                            state.id
+                        ),
+                    )
+                })?;
+                // Like for `BlockConstraint::Number`, we do not know the
+                // hash of the block
+                // See 7a7b9708-adb7-4fc2-acec-88680cb07ec1
+                Ok(BlockPtr::from((web3::types::H256::zero(), number as u64)).into())
+            }
             BlockConstraint::Latest => {
                 let timestamp = get_block_ts(store, &state.latest_block).await?;
 
@@ -260,14 +280,36 @@ impl StoreResolver {
                     .unwrap_or(r::Value::Null)
             });
 
+            // Without a block pointer, the query runs against the latest
+            // block which might still get reverted
+            let is_final = match (&self.block_ptr, self.final_block) {
+                (Some(ptr), Some(final_block)) => ptr.ptr.number <= final_block,
+                _ => false,
+            };
+
             let mut map = BTreeMap::new();
             let block = object! {
                 hash: hash,
                 number: number,
                 timestamp: timestamp,
+                isFinal: is_final,
                 __typename: BLOCK_FIELD_TYPE
             };
             map.insert("prefetch:block".into(), r::Value::List(vec![block]));
+            let final_block = self
+                .final_block
+                .map(|number| {
+                    object! {
+                        hash: r::Value::Null,
+                        number: number,
+                        timestamp: r::Value::Null,
+                        isFinal: true,
+                        __typename: BLOCK_FIELD_TYPE
+                    }
+                })
+                .into_iter()
+                .collect();
+            map.insert("prefetch:finalBlock".into(), r::Value::List(final_block));
             map.insert(
                 "deployment".into(),
                 r::Value::String(self.deployment.to_string()),
@@ -98,6 +98,7 @@ fn create_source_event_stream(
         resolver,
         query,
         deadline: None,
+        running: None,
         max_first: options.max_first,
         max_skip: options.max_skip,
         cache_status: Default::default(),
@@ -237,6 +238,7 @@ async fn execute_subscription_event(
         resolver,
         query,
         deadline: timeout.map(|t| Instant::now() + t),
+        running: None,
         max_first,
         max_skip,
         cache_status: Default::default(),
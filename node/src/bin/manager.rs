@@ -32,6 +32,7 @@ use graph_store_postgres::{
 };
 use lazy_static::lazy_static;
 use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::{collections::HashMap, num::ParseIntError, sync::Arc, time::Duration};
 const VERSION_LABEL_KEY: &str = "version";
 
@@ -167,6 +168,19 @@ pub enum Command {
         /// The deployment (see `help info`)
         deployment: DeploymentSearch,
     },
+    /// Keep a standby instance of a deployment on another node
+    ///
+    /// The standby instance is fully built but does not index the
+    /// deployment until the node the deployment is assigned to stops
+    /// writing to it, for example, because that node crashed. The standby
+    /// instance then takes over, and the two nodes swap roles.
+    Standby {
+        /// The deployment (see `help info`)
+        deployment: DeploymentSearch,
+        /// The name of the node that should keep the standby instance.
+        /// Omit it to remove the standby instance
+        node: Option<String>,
+    },
     /// Pause a deployment
     Pause {
         /// The deployment (see `help info`)
@@ -248,6 +262,19 @@ pub enum Command {
         /// Prometheus push gateway endpoint.
         prometheus_host: Option<String>,
     },
+    /// Run the handlers of a deployment for one block without writing
+    ///
+    /// The handlers see the state of the deployment as of the block before
+    /// the given block. The entity operations they perform are printed
+    /// instead of being written to the store, and what the mappings log is
+    /// printed as usual. Only works for deployments on Ethereum networks,
+    /// and does not process offchain data sources
+    Replay {
+        /// The deployment (see `help info`)
+        deployment: DeploymentSearch,
+        /// The number of the block to replay
+        block: i32,
+    },
     /// Check and interrogate the configuration
     ///
     /// Print information about a configuration file without
@@ -281,6 +308,9 @@ pub enum Command {
     /// Get information about chains and manipulate them
     #[clap(subcommand)]
     Chain(ChainCommand),
+    /// Manage the names that `ens.nameByHash` resolves ENS label hashes to
+    #[clap(subcommand)]
+    Ens(EnsCommand),
     /// Manipulate internal subgraph statistics
     #[clap(subcommand)]
     Stats(StatsCommand),
@@ -289,6 +319,10 @@ pub enum Command {
     #[clap(subcommand)]
     Index(IndexCommand),
 
+    /// Manage read-only SQL views over the entity tables of a deployment
+    #[clap(subcommand)]
+    Views(ViewsCommand),
+
     /// Prune a deployment
     ///
     /// Keep only entity versions that are needed to respond to queries at
@@ -322,6 +356,10 @@ pub enum Command {
     #[clap(subcommand)]
     Database(DatabaseCommand),
 
+    /// Export and import the data of a deployment as a snapshot
+    #[clap(subcommand)]
+    Snapshot(SnapshotCommand),
+
     /// Delete a deployment and all it's indexed data
     ///
     /// The deployment can be specified as either a subgraph name, an IPFS
@@ -499,6 +537,73 @@ pub enum CopyCommand {
     },
 }
 
+#[derive(Clone, Debug, Subcommand)]
+pub enum SnapshotCommand {
+    /// Export the data of a deployment at a block into a directory
+    ///
+    /// The snapshot contains the entities and dynamic data sources of the
+    /// deployment as they were at the block, and the proof of indexing up
+    /// to that block. The block should be final since the snapshot is only
+    /// valid for the block hash that was current when it was taken
+    Export {
+        /// The deployment to export (see `help info`)
+        deployment: DeploymentSearch,
+        /// The directory for the snapshot. It must be empty or not exist
+        dir: PathBuf,
+        /// The block at which to export. Defaults to the subgraph head
+        #[clap(long, short)]
+        block: Option<i32>,
+    },
+    /// Import a snapshot into a deployment
+    ///
+    /// The deployment must be for the same subgraph as the snapshot, and
+    /// must not have indexed any blocks yet. It must not be assigned to a
+    /// node that is indexing it while the import runs
+    Import {
+        /// The deployment into which to import (see `help info`)
+        deployment: DeploymentSearch,
+        /// The directory that contains the snapshot
+        dir: PathBuf,
+    },
+    /// Write the entities of a deployment at a block in canonical form
+    ///
+    /// Every entity is written as one line of JSON with sorted keys and
+    /// normalized values, entity types in the order of their names and
+    /// entities in the order of their ids, so that the output can be
+    /// hashed and compared with the output of other indexers. The proof of
+    /// indexing is not part of the output
+    Canonical {
+        /// The deployment to export (see `help info`)
+        deployment: DeploymentSearch,
+        /// The file to write to. Defaults to stdout
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+        /// The block at which to export. Defaults to the subgraph head
+        #[clap(long, short)]
+        block: Option<i32>,
+    },
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum EnsCommand {
+    /// Import ENS names from a rainbow table
+    ///
+    /// The file contains one name per line. `graph-node` computes the
+    /// label hash for each name; names that are already known are skipped,
+    /// so importing a file again, or a file that overlaps with an earlier
+    /// one, is safe.
+    Import {
+        /// The file with the names
+        file: PathBuf,
+    },
+    /// Print the name for an ENS label hash
+    Lookup {
+        /// The label hash `0x..`
+        #[clap(empty_values = false)]
+        hash: String,
+    },
+}
+
 #[derive(Clone, Debug, Subcommand)]
 pub enum ChainCommand {
     /// List all chains that are in the database
@@ -645,6 +750,43 @@ pub enum StatsCommand {
     },
 }
 
+#[derive(Clone, Debug, Subcommand)]
+pub enum ViewsCommand {
+    /// Create a view, or replace the view with the same name
+    ///
+    /// The view is created in the schema `sgdNNN_views` next to the
+    /// deployment schema `sgdNNN`. Table names in the query refer to the
+    /// tables of the deployment; the query can only use those tables and
+    /// functions from `pg_catalog`. The node recreates the view when the
+    /// deployment is copied or grafted and when pruning rebuilds a table.
+    Create {
+        /// The deployment (see `help info`).
+        #[clap(empty_values = false)]
+        deployment: DeploymentSearch,
+        /// The name of the view
+        #[clap(empty_values = false)]
+        name: String,
+        /// The SQL query that defines the view
+        #[clap(empty_values = false)]
+        query: String,
+    },
+    /// List the views of a deployment
+    List {
+        /// The deployment (see `help info`).
+        #[clap(empty_values = false)]
+        deployment: DeploymentSearch,
+    },
+    /// Drop a view
+    Drop {
+        /// The deployment (see `help info`).
+        #[clap(empty_values = false)]
+        deployment: DeploymentSearch,
+        /// The name of the view
+        #[clap(empty_values = false)]
+        name: String,
+    },
+}
+
 #[derive(Clone, Debug, Subcommand)]
 pub enum IndexCommand {
     /// Creates a new database index.
@@ -1155,6 +1297,10 @@ async fn main() -> anyhow::Result<()> {
             let sender = ctx.notification_sender();
             commands::assign::reassign(ctx.primary_pool(), &sender, &deployment, node)
         }
+        Standby { deployment, node } => {
+            let sender = ctx.notification_sender();
+            commands::assign::standby(ctx.primary_pool(), &sender, &deployment, node)
+        }
         Pause { deployment } => {
             let sender = ctx.notification_sender();
             commands::assign::pause_or_resume(ctx.primary_pool(), &sender, &deployment, true)
@@ -1223,6 +1369,28 @@ async fn main() -> anyhow::Result<()> {
             )
             .await
         }
+        Replay { deployment, block } => {
+            let logger = ctx.logger.clone();
+            let config = ctx.config();
+            let registry = ctx.metrics_registry().clone();
+            let node_id = ctx.node_id().clone();
+            let store_builder = ctx.store_builder().await;
+            let ipfs_url = ctx.ipfs_url.clone();
+            let arweave_url = ctx.arweave_url.clone();
+
+            commands::replay::run(
+                logger,
+                store_builder,
+                ipfs_url,
+                arweave_url,
+                config,
+                registry,
+                node_id,
+                deployment,
+                block,
+            )
+            .await
+        }
         Listen(cmd) => {
             use ListenCommand::*;
             match cmd {
@@ -1268,6 +1436,14 @@ async fn main() -> anyhow::Result<()> {
             query,
             vars,
         } => commands::query::run(ctx.graphql_runner(), target, query, vars, output, trace).await,
+        Ens(cmd) => {
+            use EnsCommand::*;
+            let primary = ctx.primary_pool();
+            match cmd {
+                Import { file } => commands::ens::import(primary, &file),
+                Lookup { hash } => commands::ens::lookup(primary, &hash),
+            }
+        }
         Chain(cmd) => {
             use ChainCommand::*;
             match cmd {
@@ -1467,6 +1643,27 @@ async fn main() -> anyhow::Result<()> {
                 }
             }
         }
+        Views(cmd) => {
+            use ViewsCommand::*;
+            let (store, primary_pool) = ctx.store_and_primary();
+            let subgraph_store = store.subgraph_store();
+            match cmd {
+                Create {
+                    deployment,
+                    name,
+                    query,
+                } => {
+                    commands::views::create(subgraph_store, primary_pool, deployment, &name, &query)
+                        .await
+                }
+                List { deployment } => {
+                    commands::views::list(subgraph_store, primary_pool, deployment).await
+                }
+                Drop { deployment, name } => {
+                    commands::views::drop(subgraph_store, primary_pool, deployment, &name).await
+                }
+            }
+        }
         Database(cmd) => {
             match cmd {
                 DatabaseCommand::Migrate => {
@@ -1528,6 +1725,33 @@ async fn main() -> anyhow::Result<()> {
             .await
         }
 
+        Snapshot(cmd) => {
+            use SnapshotCommand::*;
+            match cmd {
+                Export {
+                    deployment,
+                    dir,
+                    block,
+                } => {
+                    let (store, primary) = ctx.store_and_primary();
+                    commands::snapshot::export(store, primary, deployment, dir, block).await
+                }
+                Import { deployment, dir } => {
+                    let logger = ctx.logger.clone();
+                    let (store, primary) = ctx.store_and_primary();
+                    commands::snapshot::import(&logger, store, primary, deployment, dir)
+                }
+                Canonical {
+                    deployment,
+                    output,
+                    block,
+                } => {
+                    let (store, primary) = ctx.store_and_primary();
+                    commands::snapshot::canonical(store, primary, deployment, output, block).await
+                }
+            }
+        }
+
         Deploy {
             deployment,
             name,
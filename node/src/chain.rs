@@ -14,7 +14,7 @@ use graph::slog::{debug, error, info, o, Logger};
 use graph::url::Url;
 use graph::util::futures::retry;
 use graph::util::security::SafeDisplay;
-use graph_chain_ethereum::{self as ethereum, EthereumAdapterTrait, Transport};
+use graph_chain_ethereum::{self as ethereum, EthereumAdapterTrait, RequestThrottle, Transport};
 use std::collections::{btree_map, BTreeMap};
 use std::sync::Arc;
 use std::time::Duration;
@@ -506,24 +506,41 @@ pub async fn create_ethereum_networks_for_chain(
             ),
             Ipc => Transport::new_ipc(&web3.url).await,
             Ws => Transport::new_ws(&web3.url).await,
+            Fixture => Transport::new_fixture(&web3.url)?,
+        };
+        let transport = match web3.limits.max_concurrent_requests {
+            Some(max_requests) => transport.with_max_concurrent_requests(max_requests),
+            None => transport,
+        };
+        let transport = match web3.limits.max_requests_per_second {
+            Some(max_requests) => transport.with_throttle(RequestThrottle::for_provider(
+                &eth_rpc_metrics,
+                &provider.label,
+                max_requests,
+            )),
+            None => transport,
         };
 
         let supports_eip_1898 = !web3.features.contains("no_eip1898");
 
+        let adapter = graph_chain_ethereum::EthereumAdapter::new(
+            logger,
+            provider.label.clone(),
+            transport,
+            eth_rpc_metrics.clone(),
+            supports_eip_1898,
+            call_only,
+            web3.limits,
+        )
+        .await
+        .with_debug_traces(
+            !web3.features.contains("traces") && web3.features.contains("debug_traces"),
+        );
+        adapter.start_health_checks();
         parsed_networks.insert(
             network_name.to_string(),
             capabilities,
-            Arc::new(
-                graph_chain_ethereum::EthereumAdapter::new(
-                    logger,
-                    provider.label.clone(),
-                    transport,
-                    eth_rpc_metrics.clone(),
-                    supports_eip_1898,
-                    call_only,
-                )
-                .await,
-            ),
+            Arc::new(adapter),
             web3.limit_for(&config.node),
         );
     }
@@ -15,7 +15,7 @@ use graph::{
         serde_json, serde_regex, toml, Logger, NodeId, StoreError,
     },
 };
-use graph_chain_ethereum::{self as ethereum, NodeCapabilities};
+use graph_chain_ethereum::{self as ethereum, NodeCapabilities, ProviderLimits};
 use graph_store_postgres::{DeploymentPlacer, Shard as ShardName, PRIMARY_SHARD};
 
 use http::{HeaderMap, Uri};
@@ -491,6 +491,7 @@ impl ChainSection {
                         features,
                         headers: Default::default(),
                         rules: vec![],
+                        limits: Default::default(),
                     }),
                 };
                 let entry = chains.entry(name.to_string()).or_insert_with(|| Chain {
@@ -693,13 +694,16 @@ pub struct Web3Provider {
 
     #[serde(default, rename = "match")]
     rules: Vec<Web3Rule>,
+
+    #[serde(default)]
+    pub limits: ProviderLimits,
 }
 
 impl Web3Provider {
     pub fn node_capabilities(&self) -> NodeCapabilities {
         NodeCapabilities {
             archive: self.features.contains("archive"),
-            traces: self.features.contains("traces"),
+            traces: self.features.contains("traces") || self.features.contains("debug_traces"),
         }
     }
 
@@ -708,7 +712,7 @@ impl Web3Provider {
     }
 }
 
-const PROVIDER_FEATURES: [&str; 3] = ["traces", "archive", "no_eip1898"];
+const PROVIDER_FEATURES: [&str; 4] = ["traces", "debug_traces", "archive", "no_eip1898"];
 const DEFAULT_PROVIDER_FEATURES: [&str; 2] = ["traces", "archive"];
 
 impl Provider {
@@ -769,15 +773,28 @@ impl Provider {
 
                 web3.url = shellexpand::env(&web3.url)?.into_owned();
 
-                let label = &self.label;
-                Url::parse(&web3.url).map_err(|e| {
-                    anyhow!(
-                        "the url `{}` for provider {} is not a legal URL: {}",
-                        web3.url,
-                        label,
-                        e
-                    )
-                })?;
+                let limits = &web3.limits;
+                if limits.max_block_range == Some(0)
+                    || limits.max_batch_size == Some(0)
+                    || limits.timeout_secs == Some(0)
+                    || limits.max_concurrent_requests == Some(0)
+                    || limits.max_requests_per_second == Some(0)
+                {
+                    bail!("the limits for provider {} must be positive", self.label);
+                }
+
+                // The url of a fixture transport is a file path
+                if web3.transport != Transport::Fixture {
+                    let label = &self.label;
+                    Url::parse(&web3.url).map_err(|e| {
+                        anyhow!(
+                            "the url `{}` for provider {} is not a legal URL: {}",
+                            web3.url,
+                            label,
+                            e
+                        )
+                    })?;
+                }
             }
         }
 
@@ -810,6 +827,7 @@ impl<'de> Deserialize<'de> for Provider {
                 let mut transport = None;
                 let mut features = None;
                 let mut headers = None;
+                let mut limits = None;
                 let mut nodes = Vec::new();
 
                 while let Some(key) = map.next_key()? {
@@ -852,6 +870,12 @@ impl<'de> Deserialize<'de> for Provider {
                             let raw_headers: BTreeMap<String, String> = map.next_value()?;
                             headers = Some(btree_map_to_http_headers(raw_headers));
                         }
+                        ProviderField::Limits => {
+                            if limits.is_some() {
+                                return Err(serde::de::Error::duplicate_field("limits"));
+                            }
+                            limits = Some(map.next_value()?);
+                        }
                         ProviderField::Match => {
                             nodes = map.next_value()?;
                         }
@@ -865,8 +889,9 @@ impl<'de> Deserialize<'de> for Provider {
                             || transport.is_some()
                             || features.is_some()
                             || headers.is_some()
+                            || limits.is_some()
                         {
-                            return Err(serde::de::Error::custom("when `details` field is provided, deprecated `url`, `transport`, `features` and `headers` cannot be specified"));
+                            return Err(serde::de::Error::custom("when `details` field is provided, deprecated `url`, `transport`, `features`, `headers` and `limits` cannot be specified"));
                         }
 
                         match v {
@@ -886,6 +911,7 @@ impl<'de> Deserialize<'de> for Provider {
                             .ok_or_else(|| serde::de::Error::missing_field("features"))?,
                         headers: headers.unwrap_or_else(HeaderMap::new),
                         rules: nodes,
+                        limits: limits.unwrap_or_default(),
                     }),
                 };
 
@@ -900,6 +926,7 @@ impl<'de> Deserialize<'de> for Provider {
             "url",
             "features",
             "headers",
+            "limits",
         ];
         deserializer.deserialize_struct("Provider", FIELDS, ProviderVisitor)
     }
@@ -917,6 +944,7 @@ enum ProviderField {
     Transport,
     Features,
     Headers,
+    Limits,
 }
 
 #[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq)]
@@ -927,6 +955,8 @@ pub enum Transport {
     Ws,
     #[serde(rename = "ipc")]
     Ipc,
+    #[serde(rename = "fixture")]
+    Fixture,
 }
 
 impl Default for Transport {
@@ -943,6 +973,7 @@ impl std::fmt::Display for Transport {
             Rpc => write!(f, "rpc"),
             Ws => write!(f, "ws"),
             Ipc => write!(f, "ipc"),
+            Fixture => write!(f, "fixture"),
         }
     }
 }
@@ -1207,6 +1238,7 @@ mod tests {
     use graph::firehose::SubgraphLimit;
     use graph::prelude::regex::Regex;
     use graph::prelude::{toml, NodeId};
+    use graph_chain_ethereum::{NodeCapabilities, ProviderLimits};
     use http::{HeaderMap, HeaderValue};
     use std::collections::BTreeSet;
     use std::fs::read_to_string;
@@ -1292,6 +1324,7 @@ mod tests {
                     features: BTreeSet::new(),
                     headers: HeaderMap::new(),
                     rules: Vec::new(),
+                    limits: Default::default(),
                 }),
             },
             actual
@@ -1318,6 +1351,7 @@ mod tests {
                     features: BTreeSet::new(),
                     headers: HeaderMap::new(),
                     rules: Vec::new(),
+                    limits: Default::default(),
                 }),
             },
             actual
@@ -1425,6 +1459,7 @@ mod tests {
                     features,
                     headers,
                     rules: Vec::new(),
+                    limits: Default::default(),
                 }),
             },
             actual
@@ -1450,12 +1485,87 @@ mod tests {
                     features: BTreeSet::new(),
                     headers: HeaderMap::new(),
                     rules: Vec::new(),
+                    limits: Default::default(),
                 }),
             },
             actual
         );
     }
 
+    #[test]
+    fn it_works_on_web3_provider_with_limits_from_toml() {
+        let mut actual: Provider = toml::from_str(
+            r#"
+            label = "peering"
+            details = { type = "web3", url = "http://localhost:8545", features = [], limits = { max_block_range = 2000, max_batch_size = 50, timeout_secs = 30, max_concurrent_requests = 8, max_requests_per_second = 25 } }
+        "#,
+        )
+        .unwrap();
+        actual.validate().unwrap();
+
+        assert_eq!(
+            Provider {
+                label: "peering".to_owned(),
+                details: ProviderDetails::Web3(Web3Provider {
+                    transport: Transport::Rpc,
+                    url: "http://localhost:8545".to_owned(),
+                    features: BTreeSet::new(),
+                    headers: HeaderMap::new(),
+                    rules: Vec::new(),
+                    limits: ProviderLimits {
+                        max_block_range: Some(2000),
+                        max_batch_size: Some(50),
+                        timeout_secs: Some(30),
+                        max_concurrent_requests: Some(8),
+                        max_requests_per_second: Some(25),
+                    },
+                }),
+            },
+            actual
+        );
+
+        let mut zero: Provider = toml::from_str(
+            r#"
+            label = "peering"
+            details = { type = "web3", url = "http://localhost:8545", features = [], limits = { max_batch_size = 0 } }
+        "#,
+        )
+        .unwrap();
+        assert!(zero.validate().is_err());
+
+        let mut zero_rate: Provider = toml::from_str(
+            r#"
+            label = "peering"
+            details = { type = "web3", url = "http://localhost:8545", features = [], limits = { max_requests_per_second = 0 } }
+        "#,
+        )
+        .unwrap();
+        assert!(zero_rate.validate().is_err());
+    }
+
+    #[test]
+    fn it_works_on_web3_provider_with_debug_traces_from_toml() {
+        let mut actual: Provider = toml::from_str(
+            r#"
+            label = "peering"
+            details = { type = "web3", url = "http://localhost:8545", features = ["archive", "debug_traces"] }
+        "#,
+        )
+        .unwrap();
+        actual.validate().unwrap();
+
+        match actual.details {
+            ProviderDetails::Web3(web3) => assert_eq!(
+                NodeCapabilities {
+                    archive: true,
+                    traces: true,
+                },
+                web3.node_capabilities()
+            ),
+            _ => panic!("expected a web3 provider"),
+        }
+    }
+
     #[test]
     fn it_errors_on_new_provider_with_deprecated_fields_from_toml() {
         let actual = toml::from_str::<Provider>(
@@ -1785,6 +1895,7 @@ mod tests {
                     features: BTreeSet::new(),
                     headers: HeaderMap::new(),
                     rules: Vec::new(),
+                    limits: Default::default(),
                 }),
             },
             actual
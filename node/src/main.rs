@@ -1,6 +1,6 @@
 use clap::Parser as _;
 use ethereum::chain::{EthereumAdapterSelector, EthereumBlockRefetcher, EthereumStreamBuilder};
-use ethereum::{BlockIngestor, EthereumNetworks, RuntimeAdapter};
+use ethereum::{BlockIngestor, DeploymentThrottles, EthereumNetworks, RuntimeAdapter};
 use git_testament::{git_testament, render_testament};
 use graph::blockchain::client::ChainClient;
 use graph_chain_ethereum::codec::HeaderOnlyBlock;
@@ -10,9 +10,11 @@ use graph::blockchain::{
     ChainIdentifier,
 };
 use graph::components::link_resolver::{ArweaveClient, FileSizeLimit};
+use graph::components::server::readiness::Readiness;
 use graph::components::store::BlockStore;
 use graph::components::subgraph::Settings;
 use graph::data::graphql::load_manager::LoadManager;
+use graph::data::subgraph::schema::{DeploymentMetadata, DeploymentPlacement};
 use graph::endpoint::EndpointMetrics;
 use graph::env::EnvVars;
 use graph::firehose::{FirehoseEndpoints, FirehoseNetworks};
@@ -115,6 +117,11 @@ async fn main() {
     env_logger::init();
 
     let env_vars = Arc::new(EnvVars::from_env().unwrap());
+    // Check the Ethereum settings now rather than when they are first used
+    if let Err(e) = ethereum::EnvVars::from_env() {
+        eprintln!("invalid environment: {}", e);
+        std::process::exit(1);
+    }
     let opt = opt::Opt::parse();
 
     // Set up logger
@@ -269,6 +276,7 @@ async fn main() {
         &config.chains.providers(),
         metrics_registry.cheap_clone(),
     ));
+    let readiness = Arc::new(Readiness::new(endpoint_metrics.cheap_clone()));
 
     // Ethereum clients; query nodes ignore all ethereum clients and never
     // connect to them directly
@@ -515,8 +523,13 @@ async fn main() {
             load_manager,
             graphql_metrics_registry,
         ));
-        let mut graphql_server =
-            GraphQLQueryServer::new(&logger_factory, graphql_runner.clone(), node_id.clone());
+        let mut graphql_server = GraphQLQueryServer::new(
+            &logger_factory,
+            graphql_runner.clone(),
+            network_store.clone(),
+            readiness.clone(),
+            node_id.clone(),
+        );
         let subscription_server =
             GraphQLSubscriptionServer::new(&logger, graphql_runner.clone(), network_store.clone());
 
@@ -556,6 +569,7 @@ async fn main() {
                 network_store.clone(),
                 primary_pool,
                 metrics_registry.clone(),
+                node_id.clone(),
             );
             graph::spawn_blocking(job_runner.start());
         }
@@ -577,12 +591,12 @@ async fn main() {
         );
 
         // Create IPFS-based subgraph provider
-        let subgraph_provider = IpfsSubgraphAssignmentProvider::new(
+        let subgraph_provider = Arc::new(IpfsSubgraphAssignmentProvider::new(
             &logger_factory,
             link_resolver.clone(),
             subgraph_instance_manager,
             sg_count,
-        );
+        ));
 
         // Check version switching mode environment variable
         let version_switching_mode = ENV_VARS.subgraph_version_switching_mode;
@@ -591,7 +605,7 @@ async fn main() {
         let subgraph_registrar = Arc::new(IpfsSubgraphRegistrar::new(
             &logger_factory,
             link_resolver,
-            Arc::new(subgraph_provider),
+            subgraph_provider.cheap_clone(),
             network_store.subgraph_store(),
             subscription_manager,
             blockchain_map,
@@ -664,6 +678,8 @@ async fn main() {
                             start_block,
                             None,
                             None,
+                            DeploymentMetadata::default(),
+                            DeploymentPlacement::default(),
                         )
                         .await
                 }
@@ -696,6 +712,24 @@ async fn main() {
                 .await
                 .expect("Failed to start metrics server")
         });
+
+        // All servers are up, start accepting traffic
+        readiness.mark_initialized();
+
+        // When we are asked to shut down, stop all subgraphs so that the
+        // blocks they have already processed are written before we exit
+        shutdown_signal().await;
+        info!(logger, "Shutting down, stopping all subgraphs";
+              "timeout_s" => ENV_VARS.shutdown_timeout.as_secs());
+        let stopped = tokio::time::timeout(ENV_VARS.shutdown_timeout, subgraph_provider.stop_all());
+        if stopped.await.is_err() {
+            warn!(
+                logger,
+                "Not all subgraphs stopped before the shutdown timeout"
+            );
+        }
+        info!(logger, "Shutdown complete");
+        std::process::exit(0);
     };
 
     graph::spawn(launch_services(logger.clone(), env_vars.cheap_clone()));
@@ -736,6 +770,18 @@ async fn main() {
     futures::future::pending::<()>().await;
 }
 
+/// Wait until the process receives `SIGTERM` or `SIGINT`
+async fn shutdown_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate()).expect("failed to listen for SIGTERM");
+    let mut interrupt = signal(SignalKind::interrupt()).expect("failed to listen for SIGINT");
+    tokio::select! {
+        _ = terminate.recv() => {},
+        _ = interrupt.recv() => {},
+    }
+}
+
 /// Return the hashmap of chains and also add them to `blockchain_map`.
 fn networks_as_chains<C>(
     config: &Arc<EnvVars>,
@@ -860,17 +906,20 @@ fn ethereum_networks_as_chains(
                 firehose_endpoints,
                 eth_adapters.clone(),
             ));
+            let throttles = Arc::new(DeploymentThrottles::new(registry.clone()));
             let adapter_selector = EthereumAdapterSelector::new(
                 logger_factory.clone(),
                 client.clone(),
                 registry.clone(),
                 chain_store.clone(),
+                throttles.cheap_clone(),
             );
 
             let runtime_adapter = Arc::new(RuntimeAdapter {
                 eth_adapters: Arc::new(eth_adapters.clone()),
                 call_cache: chain_store.cheap_clone(),
                 chain_identifier: Arc::new(chain_store.chain_identifier.clone()),
+                throttles,
             });
 
             let chain_config = config.chains.chains.get(network_name).unwrap();
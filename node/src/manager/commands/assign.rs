@@ -72,6 +72,58 @@ pub fn reassign(
     Ok(())
 }
 
+/// Make `node` keep a standby instance of the deployment that takes over
+/// when the node that indexes the deployment stops writing to it. Passing
+/// `None` for `node` removes the standby instance
+pub fn standby(
+    primary: ConnectionPool,
+    sender: &NotificationSender,
+    search: &DeploymentSearch,
+    node: Option<String>,
+) -> Result<(), Error> {
+    let node = node
+        .map(|node| NodeId::new(node.clone()).map_err(|()| anyhow!("illegal node id `{}`", node)))
+        .transpose()?;
+    let locator = search.locate_unique(&primary)?;
+
+    let conn = primary.get()?;
+    let conn = catalog::Connection::new(conn);
+
+    let site = conn
+        .locate_site(locator.clone())?
+        .ok_or_else(|| anyhow!("failed to locate site for {locator}"))?;
+    let assigned = conn
+        .assigned_node(&site)?
+        .ok_or_else(|| anyhow!("deployment {locator} is not assigned to any node"))?;
+    if node.as_ref() == Some(&assigned) {
+        return Err(anyhow!(
+            "deployment {locator} is assigned to {assigned}, which can not also keep its standby instance"
+        ));
+    }
+
+    let changes = match (conn.standby_node(&site)?, &node) {
+        (cur, node) if cur.as_ref() == node.as_ref() => {
+            match cur {
+                Some(cur) => {
+                    println!("deployment {locator} already has a standby instance on {cur}")
+                }
+                None => println!("deployment {locator} has no standby instance"),
+            }
+            vec![]
+        }
+        (_, Some(node)) => {
+            println!("keeping a standby instance of {locator} on {node}");
+            conn.set_standby(&site, Some(node))?
+        }
+        (_, None) => {
+            println!("removing the standby instance of {locator}");
+            conn.set_standby(&site, None)?
+        }
+    };
+    conn.send_store_event(sender, &StoreEvent::new(changes))?;
+    Ok(())
+}
+
 pub fn pause_or_resume(
     primary: ConnectionPool,
     sender: &NotificationSender,
@@ -0,0 +1,55 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use graph::prelude::{anyhow, hex, tiny_keccak};
+use graph_store_postgres::command_support::catalog::Connection;
+use graph_store_postgres::connection_pool::ConnectionPool;
+
+/// How many names to insert with one statement; each name uses two bind
+/// variables, and Postgres allows at most 65535 of them
+const IMPORT_BATCH_SIZE: usize = 10_000;
+
+/// The hash under which mappings look up `label` with `ens.nameByHash`
+fn label_hash(label: &str) -> String {
+    format!(
+        "0x{}",
+        hex::encode(tiny_keccak::keccak256(label.as_bytes()))
+    )
+}
+
+pub fn import(primary: ConnectionPool, file: &Path) -> Result<(), anyhow::Error> {
+    let reader = BufReader::new(File::open(file)?);
+    let conn = Connection::new(primary.get()?);
+
+    let mut read = 0;
+    let mut added = 0;
+    let mut batch = Vec::with_capacity(IMPORT_BATCH_SIZE);
+    for line in reader.lines() {
+        let line = line?;
+        let label = line.trim_end_matches('\r');
+        if label.is_empty() {
+            continue;
+        }
+        batch.push((label_hash(label), label.to_string()));
+        read += 1;
+        if batch.len() == IMPORT_BATCH_SIZE {
+            added += conn.insert_ens_names(&batch)?;
+            batch.clear();
+        }
+    }
+    if !batch.is_empty() {
+        added += conn.insert_ens_names(&batch)?;
+    }
+    println!("Read {read} names and added {added}; the others were already known",);
+    Ok(())
+}
+
+pub fn lookup(primary: ConnectionPool, hash: &str) -> Result<(), anyhow::Error> {
+    let conn = Connection::new(primary.get()?);
+    match conn.find_ens_name(&hash.to_lowercase())? {
+        Some(name) => println!("{name}"),
+        None => anyhow::bail!("no name is known for hash {hash}"),
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use std::sync::Arc;
+
+use graph::anyhow::bail;
+use graph::blockchain::BlockchainKind;
+use graph::components::store::write::EntityModification;
+use graph::data::subgraph::parse_raw_manifest;
+use graph::prelude::{anyhow, BlockNumber, LinkResolver as _, MetricsRegistry, NodeId};
+use graph::slog::Logger;
+use graph_chain_ethereum as ethereum;
+use graph_core::SubgraphTriggerProcessor;
+
+use crate::config::Config;
+use crate::manager::commands::run::{indexer, Indexer};
+use crate::manager::deployment::DeploymentSearch;
+use crate::store_builder::StoreBuilder;
+
+fn print_modification(modification: &EntityModification) {
+    let (op, key, data) = match modification {
+        EntityModification::Insert { key, data, .. } => ("insert", key, Some(data)),
+        EntityModification::Overwrite { key, data, .. } => ("overwrite", key, Some(data)),
+        EntityModification::Remove { key, .. } => ("remove", key, None),
+    };
+    println!("{:<10}{}[{}]", op, key.entity_type, key.entity_id);
+    if let Some(data) = data {
+        for (name, value) in data.sorted_ref() {
+            println!("{:<10}  {}: {}", "", name, value);
+        }
+    }
+}
+
+pub async fn run(
+    logger: Logger,
+    store_builder: StoreBuilder,
+    ipfs_url: Vec<String>,
+    arweave_url: String,
+    config: Config,
+    registry: Arc<MetricsRegistry>,
+    node_id: NodeId,
+    search: DeploymentSearch,
+    block: BlockNumber,
+) -> Result<(), anyhow::Error> {
+    let primary = store_builder.primary_pool();
+    let locator = search.locate_unique(&primary)?;
+    let network = match search.lookup(&primary)?.into_iter().next() {
+        Some(deployment) => deployment.chain,
+        None => bail!("no deployment found for {}", search),
+    };
+
+    let Indexer {
+        logger_factory,
+        env_vars,
+        link_resolver,
+        instance_manager,
+        ..
+    } = indexer(
+        &logger,
+        store_builder,
+        &network,
+        &ipfs_url,
+        arweave_url,
+        &config,
+        registry,
+        &node_id,
+    )
+    .await?;
+
+    let file_bytes = link_resolver
+        .cat(&logger, &locator.hash.to_ipfs_link())
+        .await?;
+    let manifest = parse_raw_manifest(&file_bytes)?;
+    match BlockchainKind::from_manifest(&manifest)? {
+        BlockchainKind::Ethereum => { /* ok */ }
+        kind => bail!(
+            "replaying blocks is only supported for Ethereum, not for {}",
+            kind
+        ),
+    }
+
+    println!("Replaying block {} of {}", block, locator);
+    // What the mappings log goes to the subgraph logger
+    let replay = instance_manager
+        .replay_block::<ethereum::Chain>(
+            logger_factory.subgraph_logger(&locator),
+            env_vars,
+            locator,
+            manifest,
+            block,
+            Box::new(SubgraphTriggerProcessor {}),
+        )
+        .await?;
+
+    match &replay.block_ptr {
+        Some(ptr) => println!("Block {} has hash {}", ptr.number, ptr.hash),
+        None => {
+            println!("The block has no triggers for the deployment");
+            return Ok(());
+        }
+    }
+
+    println!("Entity operations: {}", replay.modifications.len());
+    for modification in &replay.modifications {
+        print_modification(modification);
+    }
+
+    if !replay.errors.is_empty() {
+        println!("Errors: {}", replay.errors.len());
+        for error in &replay.errors {
+            println!("  {}", error);
+        }
+    }
+    Ok(())
+}
@@ -36,15 +36,7 @@ async fn block_ptr(
         None => bail!("can not find chain store for {}", chain),
         Some(store) => store,
     };
-    if let Some((_, number, _)) = chain_store.block_number(&block_ptr_to.hash).await? {
-        if number != block_ptr_to.number {
-            bail!(
-                "the given hash is for block number {} but the command specified block number {}",
-                number,
-                block_ptr_to.number
-            );
-        }
-    } else if !force {
+    if !chain_store.check_block_ptr(&block_ptr_to).await? && !force {
         bail!(
             "the chain {} does not have a block with hash {} \
                (run with --force to avoid this error)",
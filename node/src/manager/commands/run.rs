@@ -11,7 +11,9 @@ use crate::manager::PanicSubscriptionManager;
 use crate::store_builder::StoreBuilder;
 use crate::MetricsContext;
 use ethereum::chain::{EthereumAdapterSelector, EthereumBlockRefetcher, EthereumStreamBuilder};
-use ethereum::{ProviderEthRpcMetrics, RuntimeAdapter as EthereumRuntimeAdapter};
+use ethereum::{
+    DeploymentThrottles, ProviderEthRpcMetrics, RuntimeAdapter as EthereumRuntimeAdapter,
+};
 use graph::anyhow::{bail, format_err};
 use graph::blockchain::client::ChainClient;
 use graph::blockchain::{BlockchainKind, BlockchainMap};
@@ -19,13 +21,14 @@ use graph::cheap_clone::CheapClone;
 use graph::components::link_resolver::{ArweaveClient, FileSizeLimit};
 use graph::components::store::{BlockStore as _, DeploymentLocator};
 use graph::components::subgraph::Settings;
+use graph::data::subgraph::schema::{DeploymentMetadata, DeploymentPlacement};
 use graph::endpoint::EndpointMetrics;
 use graph::env::EnvVars;
 use graph::firehose::FirehoseEndpoints;
 use graph::prelude::{
-    anyhow, tokio, BlockNumber, DeploymentHash, LoggerFactory, NodeId, SubgraphAssignmentProvider,
-    SubgraphCountMetric, SubgraphName, SubgraphRegistrar, SubgraphStore,
-    SubgraphVersionSwitchingMode, ENV_VARS,
+    anyhow, tokio, BlockNumber, DeploymentHash, LoggerFactory, MetricsRegistry, NodeId,
+    SubgraphAssignmentProvider, SubgraphCountMetric, SubgraphName, SubgraphRegistrar,
+    SubgraphStore, SubgraphVersionSwitchingMode, ENV_VARS,
 };
 use graph::slog::{debug, info, Logger};
 use graph_chain_ethereum as ethereum;
@@ -44,29 +47,34 @@ fn locate(store: &dyn SubgraphStore, hash: &str) -> Result<DeploymentLocator, an
     }
 }
 
-pub async fn run(
-    logger: Logger,
+/// Everything needed to index subgraphs on one Ethereum network
+pub(crate) struct Indexer {
+    pub logger_factory: LoggerFactory,
+    pub env_vars: Arc<EnvVars>,
+    pub subgraph_store: Arc<graph_store_postgres::SubgraphStore>,
+    pub link_resolver: Arc<LinkResolver>,
+    pub blockchain_map: Arc<BlockchainMap>,
+    pub sg_metrics: Arc<SubgraphCountMetric>,
+    pub instance_manager: SubgraphInstanceManager<graph_store_postgres::SubgraphStore>,
+}
+
+/// Connect to the Ethereum network `network_name` and set up an instance
+/// manager that indexes subgraphs on it
+pub(crate) async fn indexer(
+    logger: &Logger,
     store_builder: StoreBuilder,
-    network_name: String,
-    ipfs_url: Vec<String>,
+    network_name: &str,
+    ipfs_url: &[String],
     arweave_url: String,
-    config: Config,
-    metrics_ctx: MetricsContext,
-    node_id: NodeId,
-    subgraph: String,
-    stop_block: BlockNumber,
-) -> Result<(), anyhow::Error> {
-    println!(
-        "Run command: starting subgraph => {}, stop_block = {}",
-        subgraph, stop_block
-    );
-
+    config: &Config,
+    metrics_registry: Arc<MetricsRegistry>,
+    node_id: &NodeId,
+) -> Result<Indexer, anyhow::Error> {
     let env_vars = Arc::new(EnvVars::from_env().unwrap());
-    let metrics_registry = metrics_ctx.registry.clone();
-    let logger_factory = LoggerFactory::new(logger.clone(), None, metrics_ctx.registry.clone());
+    let logger_factory = LoggerFactory::new(logger.clone(), None, metrics_registry.clone());
 
     // FIXME: Hard-coded IPFS config, take it from config file instead?
-    let ipfs_clients: Vec<_> = create_ipfs_clients(&logger, &ipfs_url);
+    let ipfs_clients: Vec<_> = create_ipfs_clients(logger, ipfs_url);
     let ipfs_client = ipfs_clients.first().cloned().expect("Missing IPFS client");
     let ipfs_service = ipfs_service(
         ipfs_client,
@@ -100,22 +108,22 @@ pub async fn run(
 
     let eth_rpc_metrics = Arc::new(ProviderEthRpcMetrics::new(metrics_registry.clone()));
     let eth_networks = create_ethereum_networks_for_chain(
-        &logger,
+        logger,
         eth_rpc_metrics,
-        &config,
-        &network_name,
+        config,
+        network_name,
         endpoint_metrics.cheap_clone(),
     )
     .await
     .expect("Failed to parse Ethereum networks");
     let firehose_networks_by_kind =
-        create_firehose_networks(logger.clone(), &config, endpoint_metrics);
+        create_firehose_networks(logger.clone(), config, endpoint_metrics);
     let firehose_networks = firehose_networks_by_kind.get(&BlockchainKind::Ethereum);
     let firehose_endpoints = firehose_networks
-        .and_then(|v| v.networks.get(&network_name))
+        .and_then(|v| v.networks.get(network_name))
         .map_or_else(FirehoseEndpoints::new, |v| v.clone());
 
-    let eth_adapters = match eth_networks.networks.get(&network_name) {
+    let eth_adapters = match eth_networks.networks.get(network_name) {
         Some(adapters) => adapters.clone(),
         None => {
             return Err(format_err!(
@@ -126,7 +134,7 @@ pub async fn run(
 
     let eth_adapters2 = eth_adapters.clone();
 
-    let (_, ethereum_idents) = connect_ethereum_networks(&logger, eth_networks).await?;
+    let (_, ethereum_idents) = connect_ethereum_networks(logger, eth_networks).await?;
     // let (near_networks, near_idents) = connect_firehose_networks::<NearFirehoseHeaderOnlyBlock>(
     //     &logger,
     //     firehose_networks_by_kind
@@ -142,15 +150,16 @@ pub async fn run(
     let subgraph_store = network_store.subgraph_store();
     let chain_store = network_store
         .block_store()
-        .chain_store(network_name.as_ref())
-        .unwrap_or_else(|| panic!("No chain store for {}", &network_name));
+        .chain_store(network_name)
+        .unwrap_or_else(|| panic!("No chain store for {}", network_name));
 
     let client = Arc::new(ChainClient::new(firehose_endpoints, eth_adapters));
 
-    let chain_config = config.chains.chains.get(&network_name).unwrap();
+    let chain_config = config.chains.chains.get(network_name).unwrap();
+    let throttles = Arc::new(DeploymentThrottles::new(metrics_registry.clone()));
     let chain = ethereum::Chain::new(
         logger_factory.clone(),
-        network_name.clone(),
+        network_name.to_string(),
         node_id.clone(),
         metrics_registry.clone(),
         chain_store.cheap_clone(),
@@ -164,11 +173,13 @@ pub async fn run(
             client,
             metrics_registry.clone(),
             chain_store.cheap_clone(),
+            throttles.cheap_clone(),
         )),
         Arc::new(EthereumRuntimeAdapter {
             call_cache: chain_store.cheap_clone(),
             eth_adapters: Arc::new(eth_adapters2),
             chain_identifier: Arc::new(chain_store.chain_identifier.clone()),
+            throttles,
         }),
         graph::env::ENV_VARS.reorg_threshold,
         chain_config.polling_interval,
@@ -177,14 +188,14 @@ pub async fn run(
     );
 
     let mut blockchain_map = BlockchainMap::new();
-    blockchain_map.insert(network_name.clone(), Arc::new(chain));
+    blockchain_map.insert(network_name.to_string(), Arc::new(chain));
 
     let static_filters = ENV_VARS.experimental_static_filters;
 
     let sg_metrics = Arc::new(SubgraphCountMetric::new(metrics_registry.clone()));
 
     let blockchain_map = Arc::new(blockchain_map);
-    let subgraph_instance_manager = SubgraphInstanceManager::new(
+    let instance_manager = SubgraphInstanceManager::new(
         &logger_factory,
         env_vars.cheap_clone(),
         subgraph_store.clone(),
@@ -197,6 +208,54 @@ pub async fn run(
         static_filters,
     );
 
+    Ok(Indexer {
+        logger_factory,
+        env_vars,
+        subgraph_store,
+        link_resolver,
+        blockchain_map,
+        sg_metrics,
+        instance_manager,
+    })
+}
+
+pub async fn run(
+    logger: Logger,
+    store_builder: StoreBuilder,
+    network_name: String,
+    ipfs_url: Vec<String>,
+    arweave_url: String,
+    config: Config,
+    metrics_ctx: MetricsContext,
+    node_id: NodeId,
+    subgraph: String,
+    stop_block: BlockNumber,
+) -> Result<(), anyhow::Error> {
+    println!(
+        "Run command: starting subgraph => {}, stop_block = {}",
+        subgraph, stop_block
+    );
+
+    let Indexer {
+        logger_factory,
+        subgraph_store,
+        link_resolver,
+        blockchain_map,
+        sg_metrics,
+        instance_manager: subgraph_instance_manager,
+        ..
+    } = indexer(
+        &logger,
+        store_builder,
+        &network_name,
+        &ipfs_url,
+        arweave_url,
+        &config,
+        metrics_ctx.registry.clone(),
+        &node_id,
+    )
+    .await?;
+
     // Create IPFS-based subgraph provider
     let subgraph_provider = Arc::new(IpfsSubgraphAssignmentProvider::new(
         &logger_factory,
@@ -253,6 +312,8 @@ pub async fn run(
         None,
         None,
         None,
+        DeploymentMetadata::default(),
+        DeploymentPlacement::default(),
     )
     .await?;
 
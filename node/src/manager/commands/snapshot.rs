@@ -0,0 +1,143 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use graph::anyhow::bail;
+use graph::components::store::{BlockStore as _, ChainStore as _, DeploymentLocator};
+use graph::data::store::canonical::CANONICAL_VERSION;
+use graph::prelude::{anyhow, BlockNumber, BlockPtr, Logger, SubgraphStore as _};
+use graph_store_postgres::{connection_pool::ConnectionPool, Snapshot, Store};
+
+use crate::manager::deployment::DeploymentSearch;
+
+fn print_snapshot(snapshot: &Snapshot) {
+    println!("deployment     | {}", snapshot.deployment);
+    println!("network        | {}", snapshot.network);
+    println!(
+        "block          | {} ({})",
+        snapshot.block_number, snapshot.block_hash
+    );
+    println!("data sources   | {}", snapshot.data_sources);
+    println!("entities       | {}", snapshot.entity_count());
+    for (entity_type, count) in &snapshot.entities {
+        println!("  {:<12} | {}", entity_type, count);
+    }
+}
+
+/// The pointer to the block with number `block` on the network of the
+/// deployment `search`, or to the subgraph head if `block` is `None`
+async fn block_ptr(
+    store: &Store,
+    primary: &ConnectionPool,
+    search: &DeploymentSearch,
+    locator: &DeploymentLocator,
+    block: Option<BlockNumber>,
+) -> Result<BlockPtr, anyhow::Error> {
+    let network = match search.lookup(primary)?.into_iter().next() {
+        Some(deployment) => deployment.chain,
+        None => bail!("no deployment found for {}", search),
+    };
+    let block_ptr = match block {
+        Some(number) => {
+            let chain_store = match store.block_store().chain_store(&network) {
+                Some(chain_store) => chain_store,
+                None => bail!("can not find chain store for {}", network),
+            };
+            let hashes = chain_store.block_hashes_by_block_number(number)?;
+            match hashes.as_slice() {
+                [hash] => BlockPtr::new(hash.clone(), number),
+                [] => bail!("the chain {} has no block with number {}", network, number),
+                _ => bail!(
+                    "the chain {} has {} blocks with number {}; use a final block",
+                    network,
+                    hashes.len(),
+                    number
+                ),
+            }
+        }
+        None => match store
+            .subgraph_store()
+            .least_block_ptr(&locator.hash)
+            .await?
+        {
+            Some(ptr) => ptr,
+            None => bail!("deployment {} has not indexed any blocks yet", locator),
+        },
+    };
+    Ok(block_ptr)
+}
+
+pub async fn export(
+    store: Arc<Store>,
+    primary: ConnectionPool,
+    search: DeploymentSearch,
+    dir: PathBuf,
+    block: Option<BlockNumber>,
+) -> Result<(), anyhow::Error> {
+    let locator = search.locate_unique(&primary)?;
+    let block_ptr = block_ptr(&store, &primary, &search, &locator, block).await?;
+
+    println!(
+        "Exporting {} at block {} into {}",
+        locator,
+        block_ptr.number,
+        dir.display()
+    );
+    let snapshot = store
+        .subgraph_store()
+        .export_snapshot(&locator, block_ptr, &dir)?;
+    print_snapshot(&snapshot);
+    Ok(())
+}
+
+/// Write the entities of a deployment at a block in canonical form to
+/// `output`, or to stdout if that is `None`. Everything else goes to
+/// stderr so that the output only contains the entities
+pub async fn canonical(
+    store: Arc<Store>,
+    primary: ConnectionPool,
+    search: DeploymentSearch,
+    output: Option<PathBuf>,
+    block: Option<BlockNumber>,
+) -> Result<(), anyhow::Error> {
+    let locator = search.locate_unique(&primary)?;
+    let block_ptr = block_ptr(&store, &primary, &search, &locator, block).await?;
+
+    eprintln!("deployment     | {}", locator.hash);
+    eprintln!(
+        "block          | {} ({})",
+        block_ptr.number,
+        block_ptr.hash_hex()
+    );
+    eprintln!("format version | {}", CANONICAL_VERSION);
+    let mut out: Box<dyn Write> = match &output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout())),
+    };
+    let counts = store
+        .subgraph_store()
+        .export_canonical(&locator, block_ptr.number, &mut out)?;
+    eprintln!("entities       | {}", counts.values().sum::<usize>());
+    for (entity_type, count) in &counts {
+        eprintln!("  {:<12} | {}", entity_type, count);
+    }
+    Ok(())
+}
+
+pub fn import(
+    logger: &Logger,
+    store: Arc<Store>,
+    primary: ConnectionPool,
+    search: DeploymentSearch,
+    dir: PathBuf,
+) -> Result<(), anyhow::Error> {
+    let locator = search.locate_unique(&primary)?;
+
+    println!("Importing {} into {}", dir.display(), locator);
+    let snapshot = store
+        .subgraph_store()
+        .import_snapshot(logger, &locator, &dir)?;
+    print_snapshot(&snapshot);
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use crate::manager::deployment::DeploymentSearch;
+use graph::prelude::anyhow;
+use graph_store_postgres::{connection_pool::ConnectionPool, SubgraphStore};
+use std::sync::Arc;
+
+pub async fn create(
+    store: Arc<SubgraphStore>,
+    pool: ConnectionPool,
+    search: DeploymentSearch,
+    name: &str,
+    query: &str,
+) -> Result<(), anyhow::Error> {
+    let deployment_locator = search.locate_unique(&pool)?;
+    store.create_view(&deployment_locator, name, query).await?;
+    println!(
+        "Created view sgd{}_views.{name} for {deployment_locator}",
+        deployment_locator.id
+    );
+    Ok(())
+}
+
+pub async fn list(
+    store: Arc<SubgraphStore>,
+    pool: ConnectionPool,
+    search: DeploymentSearch,
+) -> Result<(), anyhow::Error> {
+    let deployment_locator = search.locate_unique(&pool)?;
+    let views = store.views(&deployment_locator).await?;
+    if views.is_empty() {
+        println!("{deployment_locator} has no views");
+    }
+    for view in views {
+        println!("{:-^74}", format!(" {} ", view.name));
+        println!("{}", view.query);
+    }
+    Ok(())
+}
+
+pub async fn drop(
+    store: Arc<SubgraphStore>,
+    pool: ConnectionPool,
+    search: DeploymentSearch,
+    name: &str,
+) -> Result<(), anyhow::Error> {
+    let deployment_locator = search.locate_unique(&pool)?;
+    if !store.drop_view(&deployment_locator, name).await? {
+        anyhow::bail!("{deployment_locator} has no view {name}");
+    }
+    println!("Dropped view {name}");
+    Ok(())
+}
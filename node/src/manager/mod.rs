@@ -2,7 +2,7 @@ use std::collections::BTreeSet;
 
 use graph::{
     components::store::{SubscriptionManager, UnitStream},
-    prelude::{anyhow, StoreEventStreamBox, SubscriptionFilter},
+    prelude::{anyhow, futures03, StoreEventStreamBox, SubscriptionFilter},
 };
 
 pub mod catalog;
@@ -12,7 +12,9 @@ pub mod deployment;
 mod display;
 pub mod prompt;
 
-/// A dummy subscription manager that always panics
+/// A dummy subscription manager that panics when asked for store events.
+/// Queries that wait for blocks never hear about changes from it, and only
+/// look at the deployment again when their wait times out
 pub struct PanicSubscriptionManager;
 
 impl SubscriptionManager for PanicSubscriptionManager {
@@ -21,7 +23,7 @@ impl SubscriptionManager for PanicSubscriptionManager {
     }
 
     fn subscribe_no_payload(&self, _: BTreeSet<SubscriptionFilter>) -> UnitStream {
-        panic!("we were never meant to call `subscribe_no_payload`");
+        Box::new(futures03::stream::pending())
     }
 }
 
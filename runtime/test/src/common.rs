@@ -36,6 +36,7 @@ fn mock_host_exports(
             network: Some(String::from("mainnet")),
             source: TemplateSource {
                 abi: String::from("foo"),
+                abi_from_context: None,
             },
             mapping: Mapping {
                 kind: String::from("ethereum/events"),
@@ -46,6 +47,7 @@ fn mock_host_exports(
                 event_handlers: vec![],
                 call_handlers: vec![],
                 block_handlers: vec![],
+                contract_creation_handlers: vec![],
                 link: Link {
                     link: "link".to_owned(),
                 },
@@ -145,6 +147,7 @@ pub fn mock_data_source(path: &str, api_version: Version) -> DataSource {
             event_handlers: vec![],
             call_handlers: vec![],
             block_handlers: vec![],
+            contract_creation_handlers: vec![],
             link: Link {
                 link: "link".to_owned(),
             },
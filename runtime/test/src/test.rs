@@ -903,7 +903,11 @@ async fn run_data_source_create(
     )
     .await;
 
-    module.instance_ctx_mut().ctx.state.enter_handler();
+    module
+        .instance_ctx_mut()
+        .ctx
+        .state
+        .enter_handler("dataSourceCreate");
     module.invoke_export2_void("dataSourceCreate", &name, &params)?;
     module.instance_ctx_mut().ctx.state.exit_handler();
 
@@ -1300,6 +1304,32 @@ impl Host {
         )
     }
 
+    fn store_set_many(
+        &mut self,
+        entity_type: &str,
+        data: Vec<Vec<(&str, &str)>>,
+    ) -> Result<(), HostExportError> {
+        let data = data
+            .into_iter()
+            .map(|data| {
+                HashMap::from_iter(
+                    data.into_iter()
+                        .map(|(k, v)| (Word::from(k), Value::from(v))),
+                )
+            })
+            .collect();
+        self.host_exports.store_set_many(
+            &self.ctx.logger,
+            &mut self.ctx.state,
+            12, // Arbitrary block number
+            &self.ctx.proof_of_indexing,
+            entity_type.to_string(),
+            data,
+            &self.stopwatch,
+            &self.gas,
+        )
+    }
+
     fn store_get(
         &mut self,
         entity_type: &str,
@@ -1417,6 +1447,94 @@ async fn test_store_set_id() {
     );
 }
 
+/// Test that `bigDecimal.fromString` rejects exponents that could not be
+/// stored
+#[tokio::test]
+async fn test_big_decimal_from_string() {
+    let schema = "type User @entity {
+        id: ID!,
+    }";
+
+    let host = Host::new(schema, "hostBigDecimalFromString", "boolean.wasm", None).await;
+    let from_string = |s: &str| {
+        host.host_exports
+            .big_decimal_from_string(s.to_string(), &host.gas)
+    };
+
+    assert_eq!(
+        BigDecimal::from_str("1.5").unwrap(),
+        from_string("1.5").unwrap()
+    );
+    for s in ["1e6144", "1e-6143"] {
+        assert!(from_string(s).is_ok(), "`{s}` can be stored");
+    }
+    for s in ["1e6145", "1e-6144"] {
+        let err = from_string(s).expect_err("exponent is out of range");
+        assert!(matches!(err, HostExportError::Deterministic(_)));
+        assert!(err
+            .to_string()
+            .contains("is outside the `-6143` to `6144` range"));
+    }
+    let err = from_string("one").expect_err("not a number");
+    assert!(err.to_string().contains("string is not a BigDecimal"));
+}
+
+#[tokio::test]
+async fn test_data_source_name() {
+    let schema = "type User @entity {
+        id: ID!,
+    }";
+
+    let host = Host::new(schema, "hostDataSourceName", "boolean.wasm", None).await;
+
+    assert_eq!(
+        "example data source",
+        host.host_exports.data_source_name(&host.gas).unwrap()
+    );
+}
+
+/// Test that `store_set_many` sets entities like `store_set` does and
+/// insists on getting their ids
+#[tokio::test]
+async fn test_store_set_many() {
+    const USER: &str = "User";
+
+    let schema = "type User @entity {
+        id: ID!,
+        name: String,
+    }";
+
+    let mut host = Host::new(schema, "hostStoreSetMany", "boolean.wasm", None).await;
+
+    host.store_set_many(
+        USER,
+        vec![
+            vec![("id", "u1"), ("name", "user1")],
+            vec![("id", "u2"), ("name", "user2")],
+        ],
+    )
+    .expect("setting many entities works");
+    for (id, name) in [("u1", "user1"), ("u2", "user2")] {
+        let entity = host.store_get(USER, id).unwrap().unwrap();
+        assert_eq!(Some(&Value::from(name)), entity.get("name"));
+    }
+
+    // Entities without an id are rejected, and the ones before them are
+    // still set just as if `store_set` had been called for each of them
+    let err = host
+        .store_set_many(
+            USER,
+            vec![
+                vec![("id", "u3"), ("name", "user3")],
+                vec![("name", "user4")],
+            ],
+        )
+        .expect_err("entities without an id are rejected");
+    assert!(matches!(err, HostExportError::Deterministic(_)));
+    assert!(err.to_string().contains("must have an `id`"));
+    assert!(host.store_get(USER, "u3").unwrap().is_some());
+}
+
 /// Test setting fields that are not defined in the schema
 /// This should return an error
 #[tokio::test]
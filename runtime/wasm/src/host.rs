@@ -162,6 +162,7 @@ where
         instrument: bool,
     ) -> Result<BlockState<C>, MappingError> {
         let handler = trigger.handler_name().to_string();
+        let block = block_ptr.number;
 
         let extras = trigger.logging_extras();
         trace!(
@@ -186,7 +187,14 @@ where
                     proof_of_indexing,
                     host_fns: self.host_fns.cheap_clone(),
                     debug_fork: debug_fork.cheap_clone(),
-                    mapping_logger: Logger::new(&logger, o!("component" => "UserMapping")),
+                    mapping_logger: Logger::new(
+                        &logger,
+                        o!(
+                            "component" => "UserMapping",
+                            "data_source" => self.data_source.name().to_string(),
+                            "handler" => handler.clone(),
+                        ),
+                    ),
                     instrument,
                 },
                 trigger,
@@ -202,6 +210,9 @@ where
 
         let elapsed = start_time.elapsed();
         metrics.observe_handler_execution_time(elapsed.as_secs_f64(), &handler);
+        metrics
+            .handler_stats
+            .record(logger, self.data_source.name(), &handler, block, elapsed);
 
         // If there is an error, "gas_used" is incorrectly reported as 0.
         let gas_used = result.as_ref().map(|(_, gas)| gas).unwrap_or(&Gas::ZERO);
@@ -251,7 +262,14 @@ where
                     proof_of_indexing,
                     host_fns: self.host_fns.cheap_clone(),
                     debug_fork: debug_fork.cheap_clone(),
-                    mapping_logger: Logger::new(&logger, o!("component" => "UserBlockMapping")),
+                    mapping_logger: Logger::new(
+                        &logger,
+                        o!(
+                            "component" => "UserBlockMapping",
+                            "data_source" => self.data_source.name().to_string(),
+                            "handler" => handler.clone(),
+                        ),
+                    ),
                     instrument,
                 },
                 handler.clone(),
@@ -268,6 +286,13 @@ where
 
         let elapsed = start_time.elapsed();
         metrics.observe_handler_execution_time(elapsed.as_secs_f64(), &handler);
+        metrics.handler_stats.record(
+            logger,
+            self.data_source.name(),
+            &handler,
+            block_ptr.number,
+            elapsed,
+        );
 
         // If there is an error, "gas_used" is incorrectly reported as 0.
         let gas_used = result.as_ref().map(|(_, gas)| gas).unwrap_or(&Gas::ZERO);
@@ -18,16 +18,17 @@ use graph::components::store::{EnsLookup, GetScope, LoadRelatedRequest};
 use graph::components::subgraph::{
     PoICausalityRegion, ProofOfIndexingEvent, SharedProofOfIndexing,
 };
-use graph::data::store;
+use graph::data::store::{self, EntityValidationError};
 use graph::data_source::{CausalityRegion, DataSource, DataSourceTemplate, EntityTypeAccess};
 use graph::ensure;
 use graph::prelude::ethabi::param_type::Reader;
-use graph::prelude::ethabi::{decode, encode, Token};
+use graph::prelude::ethabi::{decode, encode, short_signature, ParamType, Token};
 use graph::prelude::serde_json;
 use graph::prelude::{slog::b, slog::record_static, *};
 use graph::runtime::gas::{self, complexity, Gas, GasCounter};
 pub use graph::runtime::{DeterministicHostError, HostExportError};
 
+use crate::ipfs_map_checkpoint::{Checkpoint, Progress};
 use crate::module::{WasmInstance, WasmInstanceContext};
 use crate::{error::DeterminismLevel, module::IntoTrap};
 
@@ -63,7 +64,7 @@ impl IntoTrap for HostExportError {
 pub struct HostExports<C: Blockchain> {
     pub(crate) subgraph_id: DeploymentHash,
     pub api_version: Version,
-    data_source_name: String,
+    pub(crate) data_source_name: String,
     data_source_address: Vec<u8>,
     subgraph_network: String,
     data_source_context: Arc<Option<DataSourceContext>>,
@@ -213,6 +214,13 @@ impl<C: Blockchain> HostExports<C> {
         gas: &GasCounter,
     ) -> Result<(), HostExportError> {
         let entity_type = state.entity_cache.schema.entity_type(&entity_type)?;
+        if entity_type.is_aggregation() {
+            return Err(HostExportError::Deterministic(anyhow!(
+                "entity type `{}` belongs to an aggregation; its entities are computed \
+                 from the timeseries it aggregates and can not be set",
+                entity_type
+            )));
+        }
 
         let entity_id = if entity_id == "auto" {
             if self.data_source_causality_region != CausalityRegion::ONCHAIN {
@@ -282,6 +290,32 @@ impl<C: Blockchain> HostExports<C> {
             .make_entity(filtered_entity_data)
             .map_err(|e| HostExportError::Deterministic(anyhow!(e)))?;
 
+        // The store rolls up an interval once the timeseries has a data
+        // point in a later interval. A data point for an interval that an
+        // earlier block already completed would be left out of the rollup,
+        // or not, depending on how the store batched its writes
+        if let Some(secs) = key.entity_type.shortest_interval() {
+            if let Some(Value::Int8(timestamp)) = entity.get("timestamp") {
+                let latest = state
+                    .entity_cache
+                    .max_timestamp(&key.entity_type)
+                    .map_err(anyhow::Error::from)?;
+                if let Some(latest) = latest {
+                    if timestamp.div_euclid(secs) < latest.div_euclid(secs) {
+                        return Err(HostExportError::Deterministic(anyhow!(
+                            "the timestamp {} of the data point with id {} of timeseries `{}` \
+                             lies in an interval that is already complete, since an earlier \
+                             block wrote a data point with timestamp {}",
+                            timestamp,
+                            key.entity_id,
+                            key.entity_type,
+                            latest
+                        )));
+                    }
+                }
+            }
+        }
+
         let poi_section = stopwatch.start_section("host_export_store_set__proof_of_indexing");
         write_poi_event(
             proof_of_indexing,
@@ -295,11 +329,69 @@ impl<C: Blockchain> HostExports<C> {
         );
         poi_section.end();
 
-        state.entity_cache.set(key, entity)?;
+        state.entity_cache.set(key, entity).map_err(|e| {
+            // Validation failures and ids that conflict with entities of
+            // other types depend only on the data the mapping wrote, the
+            // schema and the entities at this block, and are therefore
+            // deterministic
+            match e.downcast::<EntityValidationError>() {
+                Ok(e) => HostExportError::Deterministic(anyhow!(e)),
+                Err(e) => match e.downcast::<StoreError>() {
+                    Ok(e @ StoreError::ConflictingId(..)) => {
+                        HostExportError::Deterministic(anyhow!(e))
+                    }
+                    Ok(e) => HostExportError::Unknown(anyhow!(e)),
+                    Err(e) => HostExportError::Unknown(e),
+                },
+            }
+        })?;
 
         Ok(())
     }
 
+    /// Set all entities in `data`, which must all be of type `entity_type`
+    /// and have their `id` set. This behaves exactly like calling
+    /// `store_set` for each entity, but saves the mapping from crossing
+    /// the WASM boundary for each entity
+    pub(crate) fn store_set_many(
+        &self,
+        logger: &Logger,
+        state: &mut BlockState<C>,
+        block: BlockNumber,
+        proof_of_indexing: &SharedProofOfIndexing,
+        entity_type: String,
+        data: Vec<HashMap<Word, Value>>,
+        stopwatch: &StopwatchMetrics,
+        gas: &GasCounter,
+    ) -> Result<(), HostExportError> {
+        for data in data {
+            let entity_id = match data.get(&store::ID) {
+                Some(id) => store::Id::try_from(id.clone())
+                    .map_err(HostExportError::Deterministic)?
+                    .to_string(),
+                None => {
+                    return Err(HostExportError::Deterministic(anyhow!(
+                        "All entities passed to `store.setMany()` must have an `id`, \
+                         but an entity of type `{}` does not have one",
+                        entity_type
+                    )))
+                }
+            };
+            self.store_set(
+                logger,
+                state,
+                block,
+                proof_of_indexing,
+                entity_type.clone(),
+                entity_id,
+                data,
+                stopwatch,
+                gas,
+            )?;
+        }
+        Ok(())
+    }
+
     pub(crate) fn store_remove(
         &self,
         logger: &Logger,
@@ -357,6 +449,30 @@ impl<C: Blockchain> HostExports<C> {
         Ok(result)
     }
 
+    pub(crate) fn store_get_many(
+        &self,
+        state: &mut BlockState<C>,
+        entity_type: String,
+        entity_ids: Vec<String>,
+        gas: &GasCounter,
+    ) -> Result<Vec<Entity>, anyhow::Error> {
+        let entity_type = state.entity_cache.schema.entity_type(&entity_type)?;
+        self.check_entity_type_access(&entity_type)?;
+        let keys = entity_ids
+            .into_iter()
+            .map(|id| entity_type.parse_key_in(id, self.data_source_causality_region))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let result = state.entity_cache.get_many(&keys)?;
+
+        gas.consume_host_fn_with_metrics(
+            gas::STORE_GET.with_args(complexity::Linear, (&keys, &result)),
+            "store_get_many",
+        )?;
+
+        Ok(result)
+    }
+
     pub(crate) fn store_load_related(
         &self,
         state: &mut BlockState<C>,
@@ -430,11 +546,14 @@ impl<C: Blockchain> HostExports<C> {
 
     // Read the IPFS file `link`, split it into JSON objects, and invoke the
     // exported function `callback` on each JSON object. The successful return
-    // value contains the block state produced by each callback invocation. Each
-    // invocation of `callback` happens in its own instance of a WASM module,
-    // which is identical to `module` when it was first started. The signature
-    // of the callback must be `callback(JSONValue, Value)`, and the `userData`
-    // parameter is passed to the callback without any changes
+    // value contains the block state produced by all callback invocations
+    // together with the number of invocations. Each invocation of `callback`
+    // happens in its own instance of a WASM module, which is identical to
+    // `module` when it was first started. The signature of the callback must
+    // be `callback(JSONValue, Value)`, and the `userData` parameter is passed
+    // to the callback without any changes. If checkpoints are turned on, the
+    // progress is saved periodically so that a call that gets interrupted
+    // resumes where it left off
     pub(crate) fn ipfs_map(
         link_resolver: &Arc<dyn LinkResolver>,
         module: &mut WasmInstanceContext<C>,
@@ -442,7 +561,7 @@ impl<C: Blockchain> HostExports<C> {
         callback: &str,
         user_data: store::Value,
         flags: Vec<String>,
-    ) -> Result<Vec<BlockState<C>>, anyhow::Error> {
+    ) -> Result<(BlockState<C>, usize), anyhow::Error> {
         // Does not consume gas because this is not a part of deterministic APIs.
         // Ideally we would consume gas the same as ipfs_cat and then share
         // gas across the spawned modules for callbacks.
@@ -466,13 +585,47 @@ impl<C: Blockchain> HostExports<C> {
         let start = Instant::now();
         let mut last_log = start;
         let logger = ctx.logger.new(o!("ipfs_map" => link.clone()));
+        let checkpoint = Checkpoint::new(
+            &ctx.host_exports.subgraph_id,
+            &ctx.block_ptr,
+            &link,
+            &callback,
+            &user_data,
+        );
 
         let result = {
+            let mut state = ctx.derive_with_empty_block_state().state;
+            let mut calls = 0;
+            let mut resume_after = 0;
+            let progress = match &checkpoint {
+                Some(checkpoint) => {
+                    if let Err(e) = checkpoint.remove_superseded() {
+                        warn!(logger, "Failed to remove superseded ipfs.map checkpoints"; "error" => e.to_string());
+                    }
+                    // A checkpoint we can not read is no use; the next
+                    // one we save replaces it
+                    checkpoint.load().unwrap_or_else(|e| {
+                        warn!(logger, "Ignoring unreadable ipfs.map checkpoint"; "error" => e.to_string());
+                        None
+                    })
+                }
+                None => None,
+            };
+            if let Some(progress) = progress {
+                info!(logger, "Resuming ipfs.map from checkpoint";
+                      "line" => progress.line, "calls" => progress.calls);
+                state.entity_cache.restore_updates(progress.updates)?;
+                resume_after = progress.line;
+                calls = progress.calls;
+            }
+
             let mut stream: JsonValueStream =
                 graph::block_on(link_resolver.json_stream(&logger, &Link { link }))?;
-            let mut v = Vec::new();
             while let Some(sv) = graph::block_on(stream.next()) {
                 let sv = sv?;
+                if sv.line <= resume_after {
+                    continue;
+                }
                 let module = WasmInstance::from_valid_module_with_ctx(
                     valid_module.clone(),
                     ctx.derive_with_empty_block_state(),
@@ -491,9 +644,33 @@ impl<C: Blockchain> HostExports<C> {
                     );
                     last_log = Instant::now();
                 }
-                v.push(result)
+                state.extend(result);
+                calls += 1;
+
+                if let Some(checkpoint) = &checkpoint {
+                    if calls % ENV_VARS.mappings.ipfs_map_checkpoint_interval == 0
+                        && !state.has_errors()
+                        && !state.has_created_data_sources()
+                    {
+                        let progress = Progress {
+                            line: sv.line,
+                            calls,
+                            updates: state.entity_cache.saved_updates(),
+                        };
+                        // Failing to save progress only makes a restart
+                        // slower, it does not affect the outcome
+                        if let Err(e) = checkpoint.save(&progress) {
+                            warn!(logger, "Failed to save ipfs.map checkpoint"; "error" => e.to_string());
+                        }
+                    }
+                }
+            }
+            if let Some(checkpoint) = &checkpoint {
+                if let Err(e) = checkpoint.remove() {
+                    warn!(logger, "Failed to remove ipfs.map checkpoint"; "error" => e.to_string());
+                }
             }
-            Ok(v)
+            Ok((state, calls))
         };
         result.map_err(move |e: Error| anyhow::anyhow!("{}: {}", errmsg, e.to_string()))
     }
@@ -575,6 +752,23 @@ impl<C: Blockchain> HostExports<C> {
         Ok(tiny_keccak::keccak256(data))
     }
 
+    /// Derive an id from `components`, for example the hash of a
+    /// transaction and the index of a log in it. The id is the keccak256
+    /// hash of an encoding of the components that can not be ambiguous, so
+    /// that different lists of components produce different ids even if
+    /// their values would concatenate to the same string
+    pub(crate) fn id_from_components(
+        &self,
+        components: Vec<store::Value>,
+        gas: &GasCounter,
+    ) -> Result<[u8; 32], DeterministicHostError> {
+        gas.consume_host_fn_with_metrics(
+            gas::DEFAULT_GAS_OP.with_args(complexity::Size, &components),
+            "id_from_components",
+        )?;
+        Ok(tiny_keccak::keccak256(&encode_id_components(&components)?))
+    }
+
     pub(crate) fn big_int_plus(
         &self,
         x: BigInt,
@@ -839,9 +1033,26 @@ impl<C: Blockchain> HostExports<C> {
             gas::DEFAULT_GAS_OP.with_args(complexity::Size, &s),
             "big_decimal_from_string",
         )?;
-        BigDecimal::from_str(&s)
-            .with_context(|| format!("string  is not a BigDecimal: '{}'", s))
-            .map_err(DeterministicHostError::from)
+        let big_decimal = BigDecimal::from_str(&s)
+            .with_context(|| format!("string is not a BigDecimal: `{}`", s))
+            .map_err(DeterministicHostError::from)?;
+
+        // Reject the same exponents that we would reject for a `BigDecimal`
+        // passed in from the mapping, so that a value parsed from a string
+        // can't fail later when it is stored.
+        let exp = -big_decimal.as_bigint_and_exponent().1;
+        let min_exp: i64 = BigDecimal::MIN_EXP.into();
+        let max_exp: i64 = BigDecimal::MAX_EXP.into();
+        if exp < min_exp || max_exp < exp {
+            return Err(DeterministicHostError::from(anyhow!(
+                "big decimal exponent `{}` of `{}` is outside the `{}` to `{}` range",
+                exp,
+                s,
+                min_exp,
+                max_exp
+            )));
+        }
+        Ok(big_decimal)
     }
 
     pub(crate) fn data_source_create(
@@ -949,6 +1160,14 @@ impl<C: Blockchain> HostExports<C> {
         Ok(self.subgraph_network.clone())
     }
 
+    pub(crate) fn data_source_name(
+        &self,
+        gas: &GasCounter,
+    ) -> Result<String, DeterministicHostError> {
+        gas.consume_host_fn_with_metrics(Gas::new(gas::DEFAULT_BASE_COST), "data_source_name")?;
+        Ok(self.data_source_name.clone())
+    }
+
     pub(crate) fn data_source_context(
         &self,
         gas: &GasCounter,
@@ -1021,6 +1240,21 @@ impl<C: Blockchain> HostExports<C> {
         Ok(encoded)
     }
 
+    /// Compute the 4-byte selector for a function `signature` like
+    /// `transfer(address,uint256)`, see `function_selector`
+    pub(crate) fn ethereum_selector(
+        &self,
+        signature: String,
+        gas: &GasCounter,
+    ) -> Result<[u8; 4], DeterministicHostError> {
+        gas.consume_host_fn_with_metrics(
+            gas::DEFAULT_GAS_OP.with_args(complexity::Size, &signature),
+            "ethereum_selector",
+        )?;
+
+        function_selector(&signature)
+    }
+
     pub(crate) fn ethereum_decode(
         &self,
         types: String,
@@ -1044,6 +1278,93 @@ impl<C: Blockchain> HostExports<C> {
     }
 }
 
+/// Encode `components` for `id.fromComponents`. Each component is a tag
+/// byte for its type followed by its value: `Int` and `Int8` values as 4
+/// and 8 bytes big-endian, `Boolean` values as one byte, and `String`,
+/// `Bytes` and `BigInt` values as their length as 4 bytes big-endian
+/// followed by the UTF-8 bytes, the bytes, and the big-endian two's
+/// complement of the value respectively. Any change to this encoding
+/// changes the ids of existing entities
+fn encode_id_components(components: &[store::Value]) -> Result<Vec<u8>, DeterministicHostError> {
+    use store::Value;
+
+    fn with_length(out: &mut Vec<u8>, bytes: &[u8]) {
+        out.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        out.extend_from_slice(bytes);
+    }
+
+    if components.is_empty() {
+        return Err(DeterministicHostError::from(anyhow!(
+            "id.fromComponents needs at least one component"
+        )));
+    }
+    let mut out = Vec::new();
+    for (index, component) in components.iter().enumerate() {
+        match component {
+            Value::String(s) => {
+                out.push(1);
+                with_length(&mut out, s.as_bytes());
+            }
+            Value::Int(i) => {
+                out.push(2);
+                out.extend_from_slice(&i.to_be_bytes());
+            }
+            Value::Int8(i) => {
+                out.push(3);
+                out.extend_from_slice(&i.to_be_bytes());
+            }
+            Value::BigInt(i) => {
+                out.push(4);
+                let mut bytes = i.to_signed_bytes_le();
+                bytes.reverse();
+                with_length(&mut out, &bytes);
+            }
+            Value::Bytes(b) => {
+                out.push(5);
+                with_length(&mut out, b.as_slice());
+            }
+            Value::Bool(b) => {
+                out.push(6);
+                out.push(*b as u8);
+            }
+            Value::BigDecimal(_) | Value::List(_) | Value::Null => {
+                return Err(DeterministicHostError::from(anyhow!(
+                    "component {} of id.fromComponents is a {}, but only String, Int, Int8, \
+                     BigInt, Bytes and Boolean values can be used",
+                    index,
+                    component.type_name()
+                )))
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// The 4-byte selector for a function `signature` like
+/// `transfer(address,uint256)`. The parameter types are normalized before
+/// hashing, so that, e.g., `uint` and `uint256` produce the same selector
+fn function_selector(signature: &str) -> Result<[u8; 4], DeterministicHostError> {
+    let signature: String = signature.chars().filter(|c| !c.is_whitespace()).collect();
+    let invalid = || {
+        DeterministicHostError::from(anyhow!(
+            "Invalid function signature `{}`, expected something like `transfer(address,uint256)`",
+            signature
+        ))
+    };
+    let (name, params) = match signature.find('(') {
+        Some(0) | None => return Err(invalid()),
+        Some(pos) => signature.split_at(pos),
+    };
+    // `Reader` does not accept an empty tuple
+    if params == "()" {
+        return Ok(short_signature(name, &[]));
+    }
+    match Reader::read(params) {
+        Ok(ParamType::Tuple(params)) => Ok(short_signature(name, &params)),
+        _ => Err(invalid()),
+    }
+}
+
 fn string_to_h160(string: &str) -> Result<H160, DeterministicHostError> {
     // `H160::from_str` takes a hex string with no leading `0x`.
     let s = string.trim_start_matches("0x");
@@ -1083,7 +1404,7 @@ pub mod test_support {
             subgraph::SharedProofOfIndexing,
         },
         data::value::Word,
-        prelude::{BlockState, Entity, StopwatchMetrics, Value},
+        prelude::{BigDecimal, BlockState, Entity, StopwatchMetrics, Value},
         runtime::{gas::GasCounter, HostExportError},
         slog::Logger,
     };
@@ -1122,6 +1443,41 @@ pub mod test_support {
             )
         }
 
+        pub fn store_set_many(
+            &self,
+            logger: &Logger,
+            state: &mut BlockState<C>,
+            block: BlockNumber,
+            proof_of_indexing: &SharedProofOfIndexing,
+            entity_type: String,
+            data: Vec<HashMap<Word, Value>>,
+            stopwatch: &StopwatchMetrics,
+            gas: &GasCounter,
+        ) -> Result<(), HostExportError> {
+            self.0.store_set_many(
+                logger,
+                state,
+                block,
+                proof_of_indexing,
+                entity_type,
+                data,
+                stopwatch,
+                gas,
+            )
+        }
+
+        pub fn big_decimal_from_string(
+            &self,
+            s: String,
+            gas: &GasCounter,
+        ) -> Result<BigDecimal, HostExportError> {
+            Ok(self.0.big_decimal_from_string(s, gas)?)
+        }
+
+        pub fn data_source_name(&self, gas: &GasCounter) -> Result<String, HostExportError> {
+            Ok(self.0.data_source_name(gas)?)
+        }
+
         pub fn store_get<'a>(
             &self,
             state: &'a mut BlockState<C>,
@@ -1163,3 +1519,51 @@ fn bytes_to_string_is_lossy() {
         )
     )
 }
+
+#[test]
+fn id_components_are_unambiguous() {
+    use graph::data::store::scalar::{BigInt, Bytes};
+    use store::Value;
+
+    let encode = |components: Vec<Value>| encode_id_components(&components).unwrap();
+
+    // Concatenating the values would produce the same string
+    assert_ne!(
+        encode(vec![Value::from("ab"), Value::from("c")]),
+        encode(vec![Value::from("a"), Value::from("bc")])
+    );
+    assert_ne!(
+        encode(vec![Value::Int(1)]),
+        encode(vec![Value::BigInt(BigInt::from(1i32))])
+    );
+    assert_eq!(
+        vec![5, 0, 0, 0, 2, 0xca, 0xfe, 2, 0, 0, 0, 7],
+        encode(vec![
+            Value::Bytes(Bytes::from([0xca, 0xfe].as_slice())),
+            Value::Int(7)
+        ])
+    );
+    assert_eq!(
+        vec![4, 0, 0, 0, 1, 0xff],
+        encode(vec![Value::BigInt(BigInt::from(-1i32))])
+    );
+    assert!(encode_id_components(&[]).is_err());
+    assert!(encode_id_components(&[Value::Null]).is_err());
+}
+
+#[test]
+fn function_selectors_normalize_parameter_types() {
+    let selector = |signature: &str| hex::encode(function_selector(signature).unwrap());
+
+    assert_eq!("a9059cbb", selector("transfer(address,uint256)"));
+    assert_eq!("a9059cbb", selector("transfer(address, uint)"));
+    assert_eq!("70a08231", selector("balanceOf(address)"));
+    assert_eq!("18160ddd", selector("totalSupply()"));
+
+    for signature in ["transfer", "(address)", "transfer(address", "transfer(foo)"] {
+        assert!(
+            function_selector(signature).is_err(),
+            "`{signature}` is not a valid signature"
+        );
+    }
+}
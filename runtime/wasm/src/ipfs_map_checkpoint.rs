@@ -0,0 +1,204 @@
+//! Saving the progress of `ipfs.map` so that processing a large file can
+//! pick up where it left off after the node restarts or fetching the file
+//! fails part of the way through.
+//!
+//! A checkpoint records the number of the last line of the file for which
+//! the callback ran and the entity changes that all callbacks up to that
+//! line made. It is identified by everything that determines the outcome of
+//! the `ipfs.map` call: the deployment, the block, the file, the callback
+//! and the user data, so that a checkpoint is only ever used to resume the
+//! exact same call. Checkpoints only hold entity changes; while callbacks
+//! have created data sources or caused errors, progress is not saved.
+//!
+//! Checkpoints are files in a directory per deployment in
+//! `GRAPH_IPFS_MAP_CHECKPOINT_DIR`. A checkpoint is removed once the whole
+//! file has been processed. A deployment only ever processes one block at a
+//! time, and once it calls `ipfs.map` for another block, the checkpoints it
+//! saved for earlier blocks can never be resumed and are removed, too.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use graph::components::store::SavedEntityOp;
+use graph::data::store;
+use graph::prelude::{
+    serde_json, BlockPtr, DeploymentHash, Deserialize, Error, Serialize, ENV_VARS,
+};
+use graph::stable_hash::crypto_stable_hash;
+
+/// What an `ipfs.map` call had done when its checkpoint was saved
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Progress {
+    /// The last line of the file for which the callback ran
+    pub line: usize,
+    /// How many times the callback ran
+    pub calls: usize,
+    /// The entity changes made by all callbacks so far
+    pub updates: Vec<SavedEntityOp>,
+}
+
+pub(crate) struct Checkpoint {
+    /// The directory with all checkpoints of the deployment
+    dir: PathBuf,
+    /// The start of the file names of all checkpoints for the block
+    block_prefix: String,
+    path: PathBuf,
+}
+
+impl Checkpoint {
+    /// The checkpoint for calling `callback` for each value in the file
+    /// `link` while processing `block`, or `None` if checkpoints are turned
+    /// off
+    pub fn new(
+        deployment: &DeploymentHash,
+        block: &BlockPtr,
+        link: &str,
+        callback: &str,
+        user_data: &store::Value,
+    ) -> Option<Self> {
+        let dir = ENV_VARS.mappings.ipfs_map_checkpoint_dir.as_ref()?;
+        Some(Self::in_dir(
+            dir, deployment, block, link, callback, user_data,
+        ))
+    }
+
+    fn in_dir(
+        dir: &Path,
+        deployment: &DeploymentHash,
+        block: &BlockPtr,
+        link: &str,
+        callback: &str,
+        user_data: &store::Value,
+    ) -> Self {
+        let key = crypto_stable_hash(&format!(
+            "{}:{}:{}:{}:{}",
+            deployment,
+            block.hash_hex(),
+            link,
+            callback,
+            serde_json::to_string(user_data).unwrap_or_default()
+        ));
+        let dir = dir.join(deployment.as_str());
+        let block_prefix = format!("{}-", block.hash_hex());
+        let path = dir.join(format!("{}{}.json", block_prefix, hex::encode(key)));
+        Checkpoint {
+            dir,
+            block_prefix,
+            path,
+        }
+    }
+
+    /// Remove the checkpoints that the deployment saved for other blocks
+    pub fn remove_superseded(&self) -> Result<(), Error> {
+        if !self.dir.exists() {
+            return Ok(());
+        }
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let superseded = entry
+                .file_name()
+                .to_str()
+                .map_or(true, |name| !name.starts_with(&self.block_prefix));
+            if superseded {
+                fs::remove_file(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The progress that was saved for this checkpoint, if any
+    pub fn load(&self) -> Result<Option<Progress>, Error> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let data = fs::read(&self.path)?;
+        Ok(Some(serde_json::from_slice(&data)?))
+    }
+
+    /// Save `progress`; the previous checkpoint stays in place until the
+    /// new one has been written completely
+    pub fn save(&self, progress: &Progress) -> Result<(), Error> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.tmp_path(), serde_json::to_vec(progress)?)?;
+        fs::rename(self.tmp_path(), &self.path)?;
+        Ok(())
+    }
+
+    /// Remove the checkpoint, including one that was only partially
+    /// written
+    pub fn remove(&self) -> Result<(), Error> {
+        for path in [self.path.clone(), self.tmp_path()] {
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn tmp_path(&self) -> PathBuf {
+        self.path.with_extension("json.tmp")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use graph::data::store;
+    use graph::prelude::{web3::types::H256, BlockPtr, DeploymentHash};
+
+    use super::{Checkpoint, Progress};
+
+    #[test]
+    fn checkpoints_are_removed_when_done_or_superseded() {
+        let dir = std::env::temp_dir().join(format!("ipfs-map-{}", uuid::Uuid::new_v4()));
+        let deployment = DeploymentHash::new("checkpoints").unwrap();
+        let other_deployment = DeploymentHash::new("otherCheckpoints").unwrap();
+        let block = |number: u8| BlockPtr::from((H256::from([number; 32]), number as i32));
+        let checkpoint = |deployment: &DeploymentHash, block: BlockPtr, link: &str| {
+            Checkpoint::in_dir(
+                &dir,
+                deployment,
+                &block,
+                link,
+                "callback",
+                &store::Value::Null,
+            )
+        };
+        let progress = || Progress {
+            line: 7,
+            calls: 3,
+            updates: vec![],
+        };
+        let saved = |checkpoint: &Checkpoint| checkpoint.load().unwrap().map(|p| p.line);
+
+        let first = checkpoint(&deployment, block(1), "QmFirst");
+        let second = checkpoint(&deployment, block(1), "QmSecond");
+        let other = checkpoint(&other_deployment, block(1), "QmFirst");
+        for checkpoint in [&first, &second, &other] {
+            assert_eq!(None, saved(checkpoint));
+            checkpoint.save(&progress()).unwrap();
+            assert_eq!(Some(7), saved(checkpoint));
+        }
+
+        // A call that is done only removes its own checkpoint
+        first.remove().unwrap();
+        assert_eq!(None, saved(&first));
+        assert_eq!(Some(7), saved(&second));
+
+        // Calls for the same block leave the checkpoints of that block
+        // alone, calls for another block remove them, but only for the
+        // same deployment
+        checkpoint(&deployment, block(1), "QmThird")
+            .remove_superseded()
+            .unwrap();
+        assert_eq!(Some(7), saved(&second));
+        checkpoint(&deployment, block(2), "QmFirst")
+            .remove_superseded()
+            .unwrap();
+        assert_eq!(None, saved(&second));
+        assert_eq!(Some(7), saved(&other));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -217,7 +217,7 @@ impl<C: Blockchain> MappingContext<C> {
             proof_of_indexing: self.proof_of_indexing.cheap_clone(),
             host_fns: self.host_fns.cheap_clone(),
             debug_fork: self.debug_fork.cheap_clone(),
-            mapping_logger: Logger::new(&self.logger, o!("component" => "UserMapping")),
+            mapping_logger: self.mapping_logger.cheap_clone(),
             instrument: self.instrument,
         }
     }
@@ -9,7 +9,7 @@ use std::time::Instant;
 
 use anyhow::anyhow;
 use anyhow::Error;
-use graph::components::store::GetScope;
+use graph::components::store::{GetScope, WriteConflictMode};
 use graph::data::value::Word;
 use graph::slog::SendSyncRefUnwindSafeKV;
 use never::Never;
@@ -181,7 +181,10 @@ impl<C: Blockchain> WasmInstance<C> {
         let value = asc_new(self.instance_ctx_mut().deref_mut(), value, &gas)?;
         let user_data = asc_new(self.instance_ctx_mut().deref_mut(), user_data, &gas)?;
 
-        self.instance_ctx_mut().ctx.state.enter_handler();
+        self.instance_ctx_mut()
+            .ctx
+            .state
+            .enter_handler(handler_name);
 
         // Invoke the callback
         self.instance
@@ -208,7 +211,7 @@ impl<C: Blockchain> WasmInstance<C> {
 
         let obj = AscPtr::alloc_obj(obj, self.instance_ctx_mut().deref_mut(), &self.gas)?;
 
-        self.invoke_handler(handler_name, obj, Arc::new(o!()), None)
+        self.invoke_handler(handler_name, obj, Arc::new(o!()), None, None)
     }
 
     pub(crate) fn handle_trigger(
@@ -222,9 +225,16 @@ impl<C: Blockchain> WasmInstance<C> {
         let gas = self.gas.clone();
         let logging_extras = trigger.logging_extras().cheap_clone();
         let error_context = trigger.trigger.error_context();
+        let transaction_hash = trigger.trigger.transaction_hash();
         let asc_trigger = trigger.to_asc_ptr(self.instance_ctx_mut().deref_mut(), &gas)?;
 
-        self.invoke_handler(&handler_name, asc_trigger, logging_extras, error_context)
+        self.invoke_handler(
+            &handler_name,
+            asc_trigger,
+            logging_extras,
+            error_context,
+            transaction_hash,
+        )
     }
 
     pub fn take_ctx(&mut self) -> WasmInstanceContext<C> {
@@ -249,12 +259,39 @@ impl<C: Blockchain> WasmInstance<C> {
         self.gas.get().value()
     }
 
+    /// Look for writes of the current handler that conflict with writes
+    /// from earlier handlers in the block. Depending on the configured
+    /// `WriteConflictMode`, conflicts are logged, or turned into an error
+    /// that fails the handler non-deterministically
+    fn check_write_conflicts(&self) -> Option<Error> {
+        let ctx = self.instance_ctx();
+        let entity_cache = &ctx.ctx.state.entity_cache;
+        let conflicts = entity_cache.write_conflicts();
+        match (entity_cache.write_conflict_mode(), conflicts.first()) {
+            (_, None) | (WriteConflictMode::Off, _) => None,
+            (WriteConflictMode::Warn, Some(_)) => {
+                for conflict in &conflicts {
+                    warn!(ctx.ctx.logger, "Order-dependent entity write";
+                        "entity_type" => conflict.key.entity_type.as_str(),
+                        "entity_id" => conflict.key.entity_id.to_string(),
+                        "field" => conflict.field.as_str(),
+                        "handler" => conflict.handler.as_str(),
+                        "previous_handler" => conflict.previous_handler.as_str()
+                    );
+                }
+                None
+            }
+            (WriteConflictMode::Strict, Some(conflict)) => Some(anyhow!("{}", conflict)),
+        }
+    }
+
     fn invoke_handler<T>(
         &mut self,
         handler: &str,
         arg: AscPtr<T>,
         logging_extras: Arc<dyn SendSyncRefUnwindSafeKV>,
         error_context: Option<String>,
+        transaction_hash: Option<String>,
     ) -> Result<(BlockState<C>, Gas), MappingError> {
         let func = self
             .instance
@@ -262,7 +299,7 @@ impl<C: Blockchain> WasmInstance<C> {
             .with_context(|| format!("function {} not found", handler));
 
         // Caution: Make sure all exit paths from this function call `exit_handler`.
-        self.instance_ctx_mut().ctx.state.enter_handler();
+        self.instance_ctx_mut().ctx.state.enter_handler(handler);
 
         // `handle_func_call` evaluates the outcome of a WASM function call:
         // - For non-deterministic traps, it terminates early with a `MappingError`.
@@ -327,6 +364,15 @@ impl<C: Blockchain> WasmInstance<C> {
             },
             Err(e) => Some(e),
         };
+        if deterministic_error.is_none() {
+            if let Some(e) = self.check_write_conflicts() {
+                // Whether conflicts fail a handler depends on the
+                // configuration of this node, and other nodes must still
+                // arrive at the same state for the deployment
+                self.instance_ctx_mut().ctx.state.exit_handler();
+                return Err(MappingError::Unknown(e));
+            }
+        }
 
         if let Some(deterministic_error) = deterministic_error {
             let deterministic_error = match error_context {
@@ -344,9 +390,17 @@ impl<C: Blockchain> WasmInstance<C> {
             );
             let subgraph_error = SubgraphError {
                 subgraph_id: self.instance_ctx().ctx.host_exports.subgraph_id.clone(),
-                message,
+                message: SubgraphError::truncate_message(message),
                 block_ptr: Some(self.instance_ctx().ctx.block_ptr.cheap_clone()),
                 handler: Some(handler.to_string()),
+                data_source: Some(
+                    self.instance_ctx()
+                        .ctx
+                        .host_exports
+                        .data_source_name
+                        .clone(),
+                ),
+                transaction_hash,
                 deterministic: true,
             };
             self.instance_ctx_mut()
@@ -562,8 +616,10 @@ impl<C: Blockchain> WasmInstance<C> {
                     let _section =
                         stopwatch.start_section(&format!("host_export_{}", name_for_metrics));
                     let metrics = instance.host_metrics.cheap_clone();
+                    let host_exports = instance.ctx.host_exports.cheap_clone();
 
                     let ctx = HostFnCtx {
+                        deployment: &host_exports.subgraph_id,
                         logger: instance.ctx.logger.cheap_clone(),
                         block_ptr: instance.ctx.block_ptr.cheap_clone(),
                         heap: instance,
@@ -592,10 +648,18 @@ impl<C: Blockchain> WasmInstance<C> {
 
         link!("ethereum.encode", ethereum_encode, params_ptr);
         link!("ethereum.decode", ethereum_decode, params_ptr, data_ptr);
+        link!("ethereum.selector", ethereum_selector, signature_ptr);
 
         link!("abort", abort, message_ptr, file_name_ptr, line, column);
 
         link!("store.get", store_get, "host_export_store_get", entity, id);
+        link!(
+            "store.getMany",
+            store_get_many,
+            "host_export_store_get_many",
+            entity,
+            ids
+        );
         link!(
             "store.loadRelated",
             store_load_related,
@@ -620,6 +684,14 @@ impl<C: Blockchain> WasmInstance<C> {
             data
         );
 
+        link!(
+            "store.setMany",
+            store_set_many,
+            "host_export_store_set_many",
+            entity,
+            data
+        );
+
         // All IPFS-related functions exported by the host WASM runtime should be listed in the
         // graph::data::subgraph::features::IPFS_ON_ETHEREUM_CONTRACTS_FUNCTION_NAMES array for
         // automatic feature detection to work.
@@ -663,6 +735,8 @@ impl<C: Blockchain> WasmInstance<C> {
 
         link!("crypto.keccak256", crypto_keccak_256, ptr);
 
+        link!("id.fromComponents", id_from_components, components_ptr);
+
         link!("bigInt.plus", big_int_plus, x_ptr, y_ptr);
         link!("bigInt.minus", big_int_minus, x_ptr, y_ptr);
         link!("bigInt.times", big_int_times, x_ptr, y_ptr);
@@ -694,6 +768,7 @@ impl<C: Blockchain> WasmInstance<C> {
         );
         link!("dataSource.address", data_source_address,);
         link!("dataSource.network", data_source_network,);
+        link!("dataSource.name", data_source_name,);
         link!("dataSource.context", data_source_context,);
 
         link!("ens.nameByHash", ens_name_by_hash, ptr);
@@ -1164,6 +1239,41 @@ impl<C: Blockchain> WasmInstanceContext<C> {
         Ok(())
     }
 
+    /// function store.setMany(entity: string, data: Array<Entity>): void
+    ///
+    /// Every entity in `data` must have its `id` set
+    pub fn store_set_many(
+        &mut self,
+        gas: &GasCounter,
+        entity_ptr: AscPtr<AscString>,
+        data_ptr: AscPtr<Array<AscPtr<AscEntity>>>,
+    ) -> Result<(), HostExportError> {
+        let stopwatch = &self.host_metrics.stopwatch;
+        stopwatch.start_section("host_export_store_set_many__wasm_instance_context_store_set_many");
+
+        let entity: String = asc_get(self, entity_ptr, gas)?;
+        let data: Vec<HashMap<Word, Value>> = asc_get(self, data_ptr, gas)?;
+
+        if self.ctx.instrument {
+            debug!(self.ctx.logger, "store_set_many";
+                    "type" => &entity,
+                    "count" => data.len());
+        }
+
+        self.ctx.host_exports.store_set_many(
+            &self.ctx.logger,
+            &mut self.ctx.state,
+            self.ctx.block_ptr.number,
+            &self.ctx.proof_of_indexing,
+            entity,
+            data,
+            stopwatch,
+            gas,
+        )?;
+
+        Ok(())
+    }
+
     /// function store.remove(entity: string, id: string): void
     pub fn store_remove(
         &mut self,
@@ -1208,6 +1318,35 @@ impl<C: Blockchain> WasmInstanceContext<C> {
         self.store_get_scoped(gas, entity_ptr, id_ptr, GetScope::InBlock)
     }
 
+    /// function store.getMany(entity: string, ids: Array<string>): Array<Entity>
+    ///
+    /// Entities that do not exist are left out of the result
+    pub fn store_get_many(
+        &mut self,
+        gas: &GasCounter,
+        entity_type_ptr: AscPtr<AscString>,
+        ids_ptr: AscPtr<Array<AscPtr<AscString>>>,
+    ) -> Result<AscPtr<Array<AscPtr<AscEntity>>>, HostExportError> {
+        let entity_type: String = asc_get(self, entity_type_ptr, gas)?;
+        let ids: Vec<String> = asc_get(self, ids_ptr, gas)?;
+
+        if self.ctx.instrument {
+            debug!(self.ctx.logger, "store_get_many";
+                    "type" => &entity_type,
+                    "ids" => ids.len());
+        }
+
+        let entities =
+            self.ctx
+                .host_exports
+                .store_get_many(&mut self.ctx.state, entity_type, ids, gas)?;
+
+        let entities: Vec<Vec<(Word, Value)>> =
+            entities.into_iter().map(|entity| entity.sorted()).collect();
+        let ret = asc_new(self, &entities, gas)?;
+        Ok(ret)
+    }
+
     /// function store.loadRelated(entity_type: string, id: string, field: string): Array<Entity>
     pub fn store_load_related(
         &mut self,
@@ -1456,7 +1595,7 @@ impl<C: Blockchain> WasmInstanceContext<C> {
         let _stopwatch_guard = defer::defer(|| defer_stopwatch.lock().unwrap().start());
 
         let start_time = Instant::now();
-        let output_states = HostExports::ipfs_map(
+        let (output_state, n_calls) = HostExports::ipfs_map(
             &self.ctx.host_exports.link_resolver.clone(),
             self,
             link.clone(),
@@ -1470,12 +1609,10 @@ impl<C: Blockchain> WasmInstanceContext<C> {
             "Successfully processed file with ipfs.map";
             "link" => &link,
             "callback" => &*callback,
-            "n_calls" => output_states.len(),
+            "n_calls" => n_calls,
             "time" => format!("{}ms", start_time.elapsed().as_millis())
         );
-        for output_state in output_states {
-            self.ctx.state.extend(output_state);
-        }
+        self.ctx.state.extend(output_state);
 
         Ok(())
     }
@@ -1543,6 +1680,19 @@ impl<C: Blockchain> WasmInstanceContext<C> {
         asc_new(self, input.as_ref(), gas)
     }
 
+    /// function id.fromComponents(components: Array<Value>): Bytes
+    pub fn id_from_components(
+        &mut self,
+        gas: &GasCounter,
+        components_ptr: AscPtr<Array<AscPtr<AscEnum<StoreValueKind>>>>,
+    ) -> Result<AscPtr<Uint8Array>, HostExportError> {
+        let id = self
+            .ctx
+            .host_exports
+            .id_from_components(asc_get(self, components_ptr, gas)?, gas)?;
+        asc_new(self, id.as_ref(), gas)
+    }
+
     /// function bigInt.plus(x: BigInt, y: BigInt): BigInt
     pub fn big_int_plus(
         &mut self,
@@ -1885,6 +2035,14 @@ impl<C: Blockchain> WasmInstanceContext<C> {
         asc_new(self, &self.ctx.host_exports.data_source_network(gas)?, gas)
     }
 
+    /// function dataSource.name(): String
+    pub fn data_source_name(
+        &mut self,
+        gas: &GasCounter,
+    ) -> Result<AscPtr<AscString>, HostExportError> {
+        asc_new(self, &self.ctx.host_exports.data_source_name(gas)?, gas)
+    }
+
     /// function dataSource.context(): DataSourceContext
     pub fn data_source_context(
         &mut self,
@@ -1969,6 +2127,19 @@ impl<C: Blockchain> WasmInstanceContext<C> {
             .unwrap_or(Ok(AscPtr::null()))
     }
 
+    /// function ethereum.selector(signature: string): Bytes
+    pub fn ethereum_selector(
+        &mut self,
+        gas: &GasCounter,
+        signature_ptr: AscPtr<AscString>,
+    ) -> Result<AscPtr<Uint8Array>, HostExportError> {
+        let selector = self
+            .ctx
+            .host_exports
+            .ethereum_selector(asc_get(self, signature_ptr, gas)?, gas)?;
+        asc_new(self, selector.as_ref(), gas)
+    }
+
     /// function arweave.transactionData(txId: string): Bytes | null
     pub fn arweave_transaction_data(
         &mut self,
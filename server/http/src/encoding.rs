@@ -0,0 +1,247 @@
+//! Sending query results to clients.
+//!
+//! Results are serialized on a blocking thread and sent to the client in
+//! chunks as they are produced, so that large results never have to be
+//! turned into one big JSON string first. A client that stops reading the
+//! response only holds on to the blocking thread for `SEND_TIMEOUT`.
+//! Clients that send `Accept-Encoding: gzip` get results compressed with
+//! gzip on the fly, unless `GRAPH_GRAPHQL_DISABLE_RESPONSE_COMPRESSION` is
+//! set.
+
+use std::io::{self, Write};
+use std::time::Duration;
+
+use flate2::{write::GzEncoder, Compression};
+use graph::data::query::QueryResults;
+use graph::prelude::{serde_json, tokio, ENV_VARS};
+use http::header::{HeaderMap, ACCEPT_ENCODING, CONTENT_ENCODING, VARY};
+use hyper::body::{Bytes, Sender};
+use hyper::{Body, Response};
+
+/// The size of the chunks in which results are sent
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// How long we wait for the client to accept a chunk before giving up on
+/// the response
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How the body of a response is encoded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Encoding {
+    Identity,
+    Gzip,
+}
+
+impl Encoding {
+    /// The encoding for the response to a request with `headers`
+    pub(crate) fn negotiate(headers: &HeaderMap) -> Encoding {
+        if ENV_VARS.graphql.disable_response_compression {
+            return Encoding::Identity;
+        }
+        Self::from_accept_encoding(headers)
+    }
+
+    /// Use gzip if the `Accept-Encoding` headers allow it, either by
+    /// listing `gzip` or with a wildcard, and do not give it a quality
+    /// of 0
+    fn from_accept_encoding(headers: &HeaderMap) -> Encoding {
+        let mut gzip = None;
+        let mut wildcard = None;
+        for coding in headers
+            .get_all(ACCEPT_ENCODING)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+        {
+            let mut params = coding.split(';');
+            let name = params.next().unwrap_or_default().trim();
+            let quality = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .next()
+                .map(|q| q.trim().parse::<f32>().unwrap_or(0.0))
+                .unwrap_or(1.0);
+            if name.eq_ignore_ascii_case("gzip") {
+                gzip = Some(quality);
+            } else if name == "*" {
+                wildcard = Some(quality);
+            }
+        }
+        match gzip.or(wildcard) {
+            Some(quality) if quality > 0.0 => Encoding::Gzip,
+            _ => Encoding::Identity,
+        }
+    }
+}
+
+/// Collects the serialized results and sends them to the client whenever
+/// a chunk is full
+struct BodyWriter {
+    handle: tokio::runtime::Handle,
+    sender: Sender,
+    buf: Vec<u8>,
+    timeout: Duration,
+}
+
+impl BodyWriter {
+    fn send(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::replace(&mut self.buf, Vec::with_capacity(CHUNK_SIZE));
+        let (sender, timeout) = (&mut self.sender, self.timeout);
+        let send = async move {
+            tokio::time::timeout(timeout, sender.send_data(Bytes::from(chunk))).await
+        };
+        match self.handle.block_on(send) {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(e)) => Err(io::Error::new(io::ErrorKind::BrokenPipe, e)),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "client did not read the response in time",
+            )),
+        }
+    }
+}
+
+impl Write for BodyWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        if self.buf.len() >= CHUNK_SIZE {
+            self.send()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send()
+    }
+}
+
+fn write_results(
+    writer: &mut BodyWriter,
+    results: &QueryResults,
+    encoding: Encoding,
+) -> io::Result<()> {
+    match encoding {
+        Encoding::Identity => serde_json::to_writer(&mut *writer, results)?,
+        Encoding::Gzip => {
+            let mut encoder = GzEncoder::new(&mut *writer, Compression::fast());
+            serde_json::to_writer(&mut encoder, results)?;
+            encoder.finish()?;
+        }
+    }
+    writer.flush()
+}
+
+/// The HTTP response for `results`. The body is produced in the background
+/// while the response is sent; if that fails, for example because the
+/// client went away, the body is aborted. Must be called from within the
+/// tokio runtime
+pub(crate) fn query_response(results: QueryResults, encoding: Encoding) -> Response<Body> {
+    let (sender, body) = Body::channel();
+    let mut builder = results
+        .http_response_builder()
+        .header(VARY, ACCEPT_ENCODING.as_str());
+    if encoding == Encoding::Gzip {
+        builder = builder.header(CONTENT_ENCODING, "gzip");
+    }
+
+    let mut writer = BodyWriter {
+        handle: tokio::runtime::Handle::current(),
+        sender,
+        buf: Vec::with_capacity(CHUNK_SIZE),
+        timeout: SEND_TIMEOUT,
+    };
+    graph::spawn_blocking_allow_panic(move || {
+        if write_results(&mut writer, &results, encoding).is_err() {
+            writer.sender.abort();
+        }
+    });
+
+    builder.body(body).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+    use graph::data::graphql::object;
+    use graph::data::value::{Object, Word};
+    use graph::prelude::r;
+    use http::HeaderValue;
+
+    use super::*;
+
+    fn negotiate(accept: &[&str]) -> Encoding {
+        let mut headers = HeaderMap::new();
+        for value in accept {
+            headers.append(ACCEPT_ENCODING, HeaderValue::from_str(value).unwrap());
+        }
+        Encoding::from_accept_encoding(&headers)
+    }
+
+    #[test]
+    fn negotiates_encoding() {
+        use Encoding::*;
+
+        assert_eq!(Identity, negotiate(&[]));
+        assert_eq!(Identity, negotiate(&["identity"]));
+        assert_eq!(Identity, negotiate(&["br, deflate"]));
+        assert_eq!(Gzip, negotiate(&["gzip"]));
+        assert_eq!(Gzip, negotiate(&["deflate, GZip;q=0.5"]));
+        assert_eq!(Gzip, negotiate(&["br", "gzip, deflate"]));
+        assert_eq!(Gzip, negotiate(&["*"]));
+        assert_eq!(Identity, negotiate(&["gzip;q=0"]));
+        assert_eq!(Identity, negotiate(&["gzip; q=0.0, *"]));
+        assert_eq!(Identity, negotiate(&["*;q=0"]));
+    }
+
+    #[tokio::test]
+    async fn streams_large_results() {
+        let results = || {
+            let rows: Vec<r::Value> = (0..10_000)
+                .map(|i: i32| object! { id: format!("row-{}", i), value: i })
+                .collect();
+            let data = Object::from_iter([(Word::from("rows"), r::Value::List(rows))]);
+            QueryResults::from(data)
+        };
+        let expected = serde_json::to_vec(&results()).unwrap();
+        assert!(expected.len() > CHUNK_SIZE);
+
+        let response = query_response(results(), Encoding::Identity);
+        assert!(response.headers().get(CONTENT_ENCODING).is_none());
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(expected, body.to_vec());
+
+        let response = query_response(results(), Encoding::Gzip);
+        assert_eq!("gzip", response.headers().get(CONTENT_ENCODING).unwrap());
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(body.len() < expected.len());
+        let mut json = Vec::new();
+        GzDecoder::new(body.as_ref())
+            .read_to_end(&mut json)
+            .unwrap();
+        assert_eq!(expected, json);
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_clients_that_do_not_read() {
+        let (sender, body) = Body::channel();
+        let mut writer = BodyWriter {
+            handle: tokio::runtime::Handle::current(),
+            sender,
+            buf: Vec::with_capacity(CHUNK_SIZE),
+            timeout: Duration::from_millis(10),
+        };
+        let err = tokio::task::spawn_blocking(move || {
+            let chunk = vec![b'x'; CHUNK_SIZE];
+            (0..4).try_for_each(|_| writer.write_all(&chunk))
+        })
+        .await
+        .unwrap()
+        .expect_err("writing to a body nobody reads should time out");
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+        drop(body);
+    }
+}
@@ -6,6 +6,7 @@ extern crate http;
 extern crate hyper;
 extern crate serde;
 
+mod encoding;
 mod request;
 mod server;
 mod service;
@@ -6,6 +6,7 @@ use hyper::Server;
 use thiserror::Error;
 
 use crate::service::GraphQLService;
+use graph::components::{server::readiness::Readiness, store::StatusStore};
 use graph::prelude::{GraphQLServer as GraphQLServerTrait, GraphQlRunner, *};
 
 /// Errors that may occur when starting the server.
@@ -19,12 +20,20 @@ pub enum GraphQLServeError {
 pub struct GraphQLServer<Q> {
     logger: Logger,
     graphql_runner: Arc<Q>,
+    status_store: Arc<dyn StatusStore>,
+    readiness: Arc<Readiness>,
     node_id: NodeId,
 }
 
 impl<Q> GraphQLServer<Q> {
     /// Creates a new GraphQL server.
-    pub fn new(logger_factory: &LoggerFactory, graphql_runner: Arc<Q>, node_id: NodeId) -> Self {
+    pub fn new(
+        logger_factory: &LoggerFactory,
+        graphql_runner: Arc<Q>,
+        status_store: Arc<dyn StatusStore>,
+        readiness: Arc<Readiness>,
+        node_id: NodeId,
+    ) -> Self {
         let logger = logger_factory.component_logger(
             "GraphQLServer",
             Some(ComponentLoggerConfig {
@@ -36,6 +45,8 @@ impl<Q> GraphQLServer<Q> {
         GraphQLServer {
             logger,
             graphql_runner,
+            status_store,
+            readiness,
             node_id,
         }
     }
@@ -65,11 +76,15 @@ where
         // incoming queries to the query sink.
         let logger_for_service = self.logger.clone();
         let graphql_runner = self.graphql_runner.clone();
+        let status_store = self.status_store.clone();
+        let readiness = self.readiness.clone();
         let node_id = self.node_id.clone();
         let new_service = make_service_fn(move |_| {
             let graphql_service = GraphQLService::new(
                 logger_for_service.clone(),
                 graphql_runner.clone(),
+                status_store.clone(),
+                readiness.clone(),
                 ws_port,
                 node_id.clone(),
             );
@@ -77,7 +92,8 @@ where
             futures03::future::ok::<_, Error>(graphql_service)
         });
 
-        // Create a task to run the server and handle HTTP requests
+        // Create a task to run the server and handle HTTP requests; clients
+        // can use HTTP/1 or talk HTTP/2 over the plain connection
         let task = Server::try_bind(&addr.into())?
             .serve(new_service)
             .map_err(move |e| error!(logger, "Server error"; "error" => format!("{}", e)));
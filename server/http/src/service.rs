@@ -10,7 +10,13 @@ use graph::prelude::serde_json::json;
 use graph::prelude::*;
 use graph::semver::VersionReq;
 use graph::url::form_urlencoded;
-use graph::{components::server::query::GraphQLServerError, data::query::QueryTarget};
+use graph::{
+    components::{
+        server::{query::GraphQLServerError, readiness::Readiness},
+        store::StatusStore,
+    },
+    data::query::QueryTarget,
+};
 use http::header;
 use http::header::{
     ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
@@ -19,6 +25,7 @@ use http::header::{
 use hyper::service::Service;
 use hyper::{Body, Method, Request, Response, StatusCode};
 
+use crate::encoding::{query_response, Encoding};
 use crate::request::parse_graphql_request;
 
 pub type GraphQLServiceResult = Result<Response<Body>, GraphQLServerError>;
@@ -31,8 +38,12 @@ pub type GraphQLServiceResponse =
 pub struct GraphQLService<Q> {
     logger: Logger,
     graphql_runner: Arc<Q>,
+    status_store: Arc<dyn StatusStore>,
+    readiness: Arc<Readiness>,
     ws_port: u16,
     node_id: NodeId,
+    /// Whether to serve the list of subgraphs at `/subgraphs`
+    list_subgraphs: bool,
 }
 
 impl<Q> Clone for GraphQLService<Q> {
@@ -40,8 +51,11 @@ impl<Q> Clone for GraphQLService<Q> {
         Self {
             logger: self.logger.clone(),
             graphql_runner: self.graphql_runner.clone(),
+            status_store: self.status_store.clone(),
+            readiness: self.readiness.clone(),
             ws_port: self.ws_port,
             node_id: self.node_id.clone(),
+            list_subgraphs: self.list_subgraphs,
         }
     }
 }
@@ -51,25 +65,48 @@ where
     Q: GraphQlRunner,
 {
     /// Creates a new GraphQL service.
-    pub fn new(logger: Logger, graphql_runner: Arc<Q>, ws_port: u16, node_id: NodeId) -> Self {
+    pub fn new(
+        logger: Logger,
+        graphql_runner: Arc<Q>,
+        status_store: Arc<dyn StatusStore>,
+        readiness: Arc<Readiness>,
+        ws_port: u16,
+        node_id: NodeId,
+    ) -> Self {
         GraphQLService {
             logger,
             graphql_runner,
+            status_store,
+            readiness,
             ws_port,
             node_id,
+            list_subgraphs: ENV_VARS.graphql.list_subgraphs,
         }
     }
 
+    /// Serve the list of subgraphs at `/subgraphs` if `list_subgraphs` is
+    /// set. By default, that is controlled by `GRAPH_GRAPHQL_LIST_SUBGRAPHS`
+    pub fn with_subgraph_list(mut self, list_subgraphs: bool) -> Self {
+        self.list_subgraphs = list_subgraphs;
+        self
+    }
+
     fn graphiql_html(&self) -> String {
         include_str!("../assets/index.html")
             .replace("__WS_PORT__", format!("{}", self.ws_port).as_str())
     }
 
     async fn index(self) -> GraphQLServiceResult {
-        let response_obj = json!({
-            "message": "Access deployed subgraphs by deployment ID at \
-                        /subgraphs/id/<ID> or by name at /subgraphs/name/<NAME>"
-        });
+        let message = if self.list_subgraphs {
+            "Access deployed subgraphs by deployment ID at \
+             /subgraphs/id/<ID> or by name at /subgraphs/name/<NAME>. \
+             Deployed subgraphs are listed at /subgraphs and can be \
+             explored with GraphiQL at /subgraphs/graphql"
+        } else {
+            "Access deployed subgraphs by deployment ID at \
+             /subgraphs/id/<ID> or by name at /subgraphs/name/<NAME>"
+        };
+        let response_obj = json!({ "message": message });
         let response_str = serde_json::to_string(&response_obj).unwrap();
 
         Ok(Response::builder()
@@ -80,6 +117,83 @@ where
             .unwrap())
     }
 
+    /// Lists the current and pending versions of all subgraphs; this is
+    /// what the deployment selector in GraphiQL is populated from
+    async fn handle_subgraph_list(self) -> GraphQLServiceResult {
+        let versions = self
+            .status_store
+            .subgraph_versions()
+            .map_err(|e| GraphQLServerError::InternalError(e.to_string()))?;
+        let subgraphs: Vec<_> = versions
+            .into_iter()
+            .map(|(name, deployment, version)| {
+                json!({
+                    "name": name,
+                    "deployment": deployment,
+                    "version": version,
+                })
+            })
+            .collect();
+        let response_str = serde_json::to_string(&json!({ "subgraphs": subgraphs })).unwrap();
+
+        Ok(Response::builder()
+            .status(200)
+            .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(response_str))
+            .unwrap())
+    }
+
+    /// Liveness probe: the node is live as long as it can answer HTTP
+    /// requests
+    async fn handle_live(self) -> GraphQLServiceResult {
+        Ok(Response::builder()
+            .status(200)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(json!({ "live": true }).to_string()))
+            .unwrap())
+    }
+
+    /// Readiness probe: the node is ready to receive traffic once it has
+    /// finished starting up, can reach the store, and has at least one
+    /// usable provider. Responds with `503 Service Unavailable` otherwise
+    async fn handle_ready(self) -> GraphQLServiceResult {
+        let initialized = self.readiness.is_initialized();
+        // Checking the connection blocks until the database answers
+        let status_store = self.status_store.clone();
+        let check = graph::spawn_blocking_allow_panic(move || status_store.check_connection())
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|res| res.map_err(|e| e.to_string()));
+        let store = match check {
+            Ok(()) => true,
+            Err(e) => {
+                warn!(self.logger, "Readiness check could not reach the store";
+                      "error" => e);
+                false
+            }
+        };
+        let providers = self.readiness.providers_healthy();
+        let ready = initialized && store && providers;
+
+        let response_obj = json!({
+            "ready": ready,
+            "initialized": initialized,
+            "store": store,
+            "providers": providers,
+        });
+
+        Ok(Response::builder()
+            .status(if ready {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            })
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(response_obj.to_string()))
+            .unwrap())
+    }
+
     /// Serves a dynamically created file.
     fn serve_dynamic_file(&self, contents: String) -> GraphQLServiceResponse {
         async {
@@ -179,10 +293,16 @@ where
                     })
                     .unwrap_or(false)
         };
+        let client = request
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_owned);
+        let encoding = Encoding::negotiate(request.headers());
         let body = hyper::body::to_bytes(request.into_body())
             .map_err(|_| GraphQLServerError::InternalError("Failed to read request body".into()))
             .await?;
-        let query = parse_graphql_request(&body, trace);
+        let query = parse_graphql_request(&body, trace).map(|query| query.with_client(client));
         let query_parsing_time = start.elapsed();
 
         let result = match query {
@@ -198,7 +318,7 @@ where
             .metrics()
             .observe_query_execution(start.elapsed(), &result);
 
-        Ok(result.as_http_response())
+        Ok(query_response(result, encoding))
     }
 
     // Handles OPTIONS requests
@@ -354,6 +474,11 @@ where
 
         match (method, path_segments.as_slice()) {
             (Method::GET, [""]) => self.index().boxed(),
+            (Method::GET, ["live"]) => self.handle_live().boxed(),
+            (Method::GET, ["ready"]) => self.handle_ready().boxed(),
+            (Method::GET, ["subgraphs"]) if self.list_subgraphs => {
+                self.handle_subgraph_list().boxed()
+            }
             (Method::GET, &["subgraphs", "id", _, "graphql"])
             | (Method::GET, &["subgraphs", "name", _, "graphql"])
             | (Method::GET, &["subgraphs", "name", _, _, "graphql"])
@@ -530,7 +655,14 @@ mod tests {
         let graphql_runner = Arc::new(TestGraphQlRunner);
 
         let node_id = NodeId::new("test").unwrap();
-        let mut service = GraphQLService::new(logger, graphql_runner, 8001, node_id);
+        let mut service = GraphQLService::new(
+            logger,
+            graphql_runner,
+            Arc::new(test_utils::TestStatusStore),
+            test_utils::readiness(),
+            8001,
+            node_id,
+        );
 
         let request = Request::builder()
             .method(Method::GET)
@@ -557,6 +689,96 @@ mod tests {
         assert_eq!(json.unwrap(), serde_json::json!({"message": "Not found"}));
     }
 
+    #[tokio::test]
+    async fn lists_subgraph_versions() {
+        async fn list_subgraphs(list_subgraphs: bool) -> serde_json::Value {
+            let logger = Logger::root(slog::Discard, o!());
+            let graphql_runner = Arc::new(TestGraphQlRunner);
+
+            let node_id = NodeId::new("test").unwrap();
+            let mut service = GraphQLService::new(
+                logger,
+                graphql_runner,
+                Arc::new(test_utils::TestStatusStore),
+                test_utils::readiness(),
+                8001,
+                node_id,
+            )
+            .with_subgraph_list(list_subgraphs);
+
+            let request = Request::builder()
+                .method(Method::GET)
+                .uri("http://localhost:8000/subgraphs".to_string())
+                .body(Body::empty())
+                .unwrap();
+
+            let response = service
+                .call(request)
+                .await
+                .expect("Should return a response");
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body_bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            serde_json::from_slice(&body_bytes).unwrap()
+        }
+
+        assert_eq!(
+            list_subgraphs(true).await,
+            serde_json::json!({
+                "subgraphs": [{ "name": "users", "deployment": "users", "version": "current" }]
+            })
+        );
+
+        // Unless the list is turned on, subgraph names are not revealed
+        assert_eq!(
+            list_subgraphs(false).await,
+            serde_json::json!({"message": "Not found"})
+        );
+    }
+
+    #[tokio::test]
+    async fn readiness_and_liveness() {
+        let logger = Logger::root(slog::Discard, o!());
+        let graphql_runner = Arc::new(TestGraphQlRunner);
+        let readiness = test_utils::readiness();
+
+        let node_id = NodeId::new("test").unwrap();
+        let mut service = GraphQLService::new(
+            logger,
+            graphql_runner,
+            Arc::new(test_utils::TestStatusStore),
+            readiness.clone(),
+            8001,
+            node_id,
+        );
+
+        let get = |path: &str| {
+            Request::builder()
+                .method(Method::GET)
+                .uri(format!("http://localhost:8000/{}", path))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = service.call(get("live")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = service.call(get("ready")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body_bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body_bytes).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "ready": false, "initialized": false, "store": true, "providers": true
+            })
+        );
+
+        readiness.mark_initialized();
+        let response = service.call(get("ready")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
     #[test]
     fn posting_invalid_query_yields_error_response() {
         let logger = Logger::root(slog::Discard, o!());
@@ -564,7 +786,14 @@ mod tests {
         let graphql_runner = Arc::new(TestGraphQlRunner);
 
         let node_id = NodeId::new("test").unwrap();
-        let mut service = GraphQLService::new(logger, graphql_runner, 8001, node_id);
+        let mut service = GraphQLService::new(
+            logger,
+            graphql_runner,
+            Arc::new(test_utils::TestStatusStore),
+            test_utils::readiness(),
+            8001,
+            node_id,
+        );
 
         let request = Request::builder()
             .method(Method::POST)
@@ -597,7 +826,14 @@ mod tests {
         let graphql_runner = Arc::new(TestGraphQlRunner);
 
         let node_id = NodeId::new("test").unwrap();
-        let mut service = GraphQLService::new(logger, graphql_runner, 8001, node_id);
+        let mut service = GraphQLService::new(
+            logger,
+            graphql_runner,
+            Arc::new(test_utils::TestStatusStore),
+            test_utils::readiness(),
+            8001,
+            node_id,
+        );
 
         let request = Request::builder()
             .method(Method::POST)
@@ -1,5 +1,12 @@
+use graph::components::server::index_node::VersionInfo;
+use graph::components::server::readiness::Readiness;
+use graph::components::store::{BlockPtrForNumber, QueryPermit, StatusStore};
+use graph::data::subgraph::status;
+use graph::endpoint::EndpointMetrics;
 use graph::prelude::serde_json;
+use graph::prelude::web3::types::Address;
 use graph::prelude::*;
+use graph::util::jobs::JobStatus;
 use http::StatusCode;
 use hyper::{header::ACCESS_CONTROL_ALLOW_ORIGIN, Body, Response};
 
@@ -69,3 +76,75 @@ pub fn assert_expected_headers(response: &Response<Body>) {
         &"*"
     );
 }
+
+/// Readiness state for tests; it has no providers and is not initialized
+pub fn readiness() -> Arc<Readiness> {
+    Arc::new(Readiness::new(Arc::new(EndpointMetrics::mock())))
+}
+
+/// A status store for tests that knows about a single subgraph `users`
+/// whose current version is the deployment `users`
+pub struct TestStatusStore;
+
+#[async_trait]
+impl StatusStore for TestStatusStore {
+    async fn query_permit(&self) -> Result<QueryPermit, StoreError> {
+        unimplemented!()
+    }
+
+    fn status(&self, _filter: status::Filter) -> Result<Vec<status::Info>, StoreError> {
+        unimplemented!()
+    }
+
+    fn version_info(&self, _version_id: &str) -> Result<VersionInfo, StoreError> {
+        unimplemented!()
+    }
+
+    fn versions_for_subgraph_id(
+        &self,
+        _subgraph_id: &str,
+    ) -> Result<(Option<String>, Option<String>), StoreError> {
+        unimplemented!()
+    }
+
+    fn subgraphs_for_deployment_hash(
+        &self,
+        _deployment_hash: &str,
+    ) -> Result<Vec<(String, String)>, StoreError> {
+        unimplemented!()
+    }
+
+    fn subgraph_versions(&self) -> Result<Vec<(String, String, String)>, StoreError> {
+        Ok(vec![(
+            "users".to_string(),
+            "users".to_string(),
+            "current".to_string(),
+        )])
+    }
+
+    fn check_connection(&self) -> Result<(), StoreError> {
+        Ok(())
+    }
+
+    fn job_statuses(&self) -> Result<Vec<JobStatus>, StoreError> {
+        Ok(vec![])
+    }
+
+    async fn get_proof_of_indexing(
+        &self,
+        _subgraph_id: &DeploymentHash,
+        _indexer: &Option<Address>,
+        _block: BlockPtr,
+    ) -> Result<Option<[u8; 32]>, StoreError> {
+        unimplemented!()
+    }
+
+    async fn get_public_proof_of_indexing(
+        &self,
+        _subgraph_id: &DeploymentHash,
+        _block_number: BlockNumber,
+        _fetch_block_ptr: &dyn BlockPtrForNumber,
+    ) -> Result<Option<(PartialBlockPtr, [u8; 32])>, StoreError> {
+        unimplemented!()
+    }
+}
@@ -99,7 +99,13 @@ mod test {
                 let id = USERS.clone();
                 let query_runner = Arc::new(TestGraphQlRunner);
                 let node_id = NodeId::new("test").unwrap();
-                let mut server = HyperGraphQLServer::new(&logger_factory, query_runner, node_id);
+                let mut server = HyperGraphQLServer::new(
+                    &logger_factory,
+                    query_runner,
+                    Arc::new(test_utils::TestStatusStore),
+                    test_utils::readiness(),
+                    node_id,
+                );
                 let http_server = server
                     .serve(8007, 8008)
                     .expect("Failed to start GraphQL server");
@@ -141,7 +147,13 @@ mod test {
             let id = USERS.clone();
             let query_runner = Arc::new(TestGraphQlRunner);
             let node_id = NodeId::new("test").unwrap();
-            let mut server = HyperGraphQLServer::new(&logger_factory, query_runner, node_id);
+            let mut server = HyperGraphQLServer::new(
+                &logger_factory,
+                query_runner,
+                Arc::new(test_utils::TestStatusStore),
+                test_utils::readiness(),
+                node_id,
+            );
             let http_server = server
                 .serve(8002, 8003)
                 .expect("Failed to start GraphQL server");
@@ -223,7 +235,13 @@ mod test {
             let id = USERS.clone();
             let query_runner = Arc::new(TestGraphQlRunner);
             let node_id = NodeId::new("test").unwrap();
-            let mut server = HyperGraphQLServer::new(&logger_factory, query_runner, node_id);
+            let mut server = HyperGraphQLServer::new(
+                &logger_factory,
+                query_runner,
+                Arc::new(test_utils::TestStatusStore),
+                test_utils::readiness(),
+                node_id,
+            );
             let http_server = server
                 .serve(8003, 8004)
                 .expect("Failed to start GraphQL server");
@@ -270,7 +288,13 @@ mod test {
             let id = USERS.clone();
             let query_runner = Arc::new(TestGraphQlRunner);
             let node_id = NodeId::new("test").unwrap();
-            let mut server = HyperGraphQLServer::new(&logger_factory, query_runner, node_id);
+            let mut server = HyperGraphQLServer::new(
+                &logger_factory,
+                query_runner,
+                Arc::new(test_utils::TestStatusStore),
+                test_utils::readiness(),
+                node_id,
+            );
             let http_server = server
                 .serve(8005, 8006)
                 .expect("Failed to start GraphQL server");
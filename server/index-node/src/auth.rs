@@ -1,6 +1,7 @@
 use hyper::header::AUTHORIZATION;
 
 use graph::env::EnvVars;
+use graph::prelude::QueryExecutionError;
 
 /// Validation logic for access tokens required to access POI results.
 pub struct PoiProtection {
@@ -46,9 +47,79 @@ impl PoiProtection {
     pub fn is_active(&self) -> bool {
         self.reqd_access_token.is_some()
     }
+
+    /// Returns `true` iff the given access token allows access to fields
+    /// that reveal details about the node or about other clients. Unlike
+    /// POI results, these are never accessible when no access token is
+    /// configured.
+    pub fn validate_restricted_access(&self, access_token: Option<&str>) -> bool {
+        self.is_active() && self.validate_access_token(access_token)
+    }
+
+    /// Fail with `AccessDenied` for `field` unless `access_token` allows
+    /// access to restricted fields
+    pub fn check_restricted_access(
+        &self,
+        field: &str,
+        access_token: Option<&str>,
+    ) -> Result<(), QueryExecutionError> {
+        if self.validate_restricted_access(access_token) {
+            Ok(())
+        } else {
+            Err(QueryExecutionError::AccessDenied(field.to_string()))
+        }
+    }
 }
 
 pub fn bearer_token(headers: &hyper::HeaderMap) -> Option<&[u8]> {
     let header = headers.get(AUTHORIZATION)?.as_bytes();
     header.strip_prefix(b"Bearer ")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restricted_access_requires_token() {
+        let unprotected = PoiProtection {
+            reqd_access_token: None,
+        };
+        assert!(unprotected.validate_access_token(None));
+        assert!(!unprotected.validate_restricted_access(None));
+        assert!(!unprotected.validate_restricted_access(Some("token")));
+
+        let protected = PoiProtection {
+            reqd_access_token: Some("token".to_string()),
+        };
+        assert!(!protected.validate_restricted_access(None));
+        assert!(!protected.validate_restricted_access(Some("other")));
+        assert!(protected.validate_restricted_access(Some("token")));
+    }
+
+    #[test]
+    fn rejects_unauthenticated_running_queries() {
+        fn denied(protection: &PoiProtection, token: Option<&str>) -> bool {
+            match protection.check_restricted_access("runningQueries", token) {
+                Ok(()) => false,
+                Err(QueryExecutionError::AccessDenied(field)) => {
+                    assert_eq!("runningQueries", field);
+                    true
+                }
+                Err(e) => panic!("unexpected error {}", e),
+            }
+        }
+
+        let unprotected = PoiProtection {
+            reqd_access_token: None,
+        };
+        assert!(denied(&unprotected, None));
+
+        let protected = PoiProtection {
+            reqd_access_token: Some("token".to_string()),
+        };
+        assert!(denied(&protected, None));
+        assert!(denied(&protected, Some("other")));
+        assert!(!denied(&protected, Some("token")));
+    }
+}
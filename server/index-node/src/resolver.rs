@@ -1,23 +1,42 @@
 use std::collections::BTreeMap;
 use std::convert::TryInto;
 
-use graph::data::query::Trace;
+use graph::data::query::{Trace, RUNNING_QUERIES, SLOW_QUERIES};
 use graph::data::store::Id;
 use graph::schema::EntityType;
 use web3::types::Address;
 
 use git_testament::{git_testament, CommitKind};
 use graph::blockchain::{Blockchain, BlockchainKind, BlockchainMap};
-use graph::components::store::{BlockPtrForNumber, BlockStore, QueryPermit, Store};
+use graph::components::store::{
+    BlockPtrForNumber, BlockStore, CachedEthereumCall, EthereumCallCache, QueryPermit, StatusStore,
+    Store, WRITE_STATS,
+};
+use graph::components::subgraph::HANDLER_STATS;
 use graph::components::versions::VERSIONS;
 use graph::data::graphql::{object, IntoValue, ObjectOrInterface, ValueMap};
-use graph::data::subgraph::{status, DeploymentFeatures};
-use graph::data::value::Object;
+use graph::data::store::scalar;
+use graph::data::subgraph::{parse_raw_manifest, status, DeploymentFeatures};
+use graph::data::value::{Object, Word};
 use graph::prelude::*;
 use graph_graphql::prelude::{a, ExecutionContext, Resolver};
 
 use crate::auth::PoiProtection;
 
+/// The largest number of statuses `indexingStatuses` returns at once when
+/// it is paginated
+const MAX_STATUS_PAGE_SIZE: usize = 1000;
+
+/// The maximum number of proofs of indexing that `indexingStatuses` looks
+/// up at the same time
+const MAX_CONCURRENT_POIS: usize = 10;
+
+/// How many entities `entityWrites` lists as the hottest ones by default
+const DEFAULT_HOT_ENTITIES: usize = 10;
+
+/// How many groups of slow queries `slowQueries` lists by default
+const DEFAULT_SLOW_QUERIES: usize = 20;
+
 /// Timeout for calls to fetch the block from JSON-RPC or Firehose.
 const BLOCK_HASH_FROM_NUMBER_TIMEOUT: Duration = Duration::from_secs(10);
 
@@ -121,7 +140,10 @@ impl<S: Store> IndexNodeResolver<S> {
         }
     }
 
-    fn resolve_indexing_statuses(&self, field: &a::Field) -> Result<r::Value, QueryExecutionError> {
+    async fn resolve_indexing_statuses(
+        &self,
+        field: &a::Field,
+    ) -> Result<r::Value, QueryExecutionError> {
         let deployments = field
             .argument_value("subgraphs")
             .map(|value| match value {
@@ -136,10 +158,54 @@ impl<S: Store> IndexNodeResolver<S> {
             })
             .unwrap_or_else(Vec::new);
 
-        let infos = self
-            .store
-            .status(status::Filter::Deployments(deployments))?;
-        Ok(infos.into_value())
+        let first = field
+            .get_optional::<i32>("first")
+            .expect("first is an Int")
+            .map(|first| first.max(0) as usize);
+        let skip = field
+            .get_optional::<i32>("skip")
+            .expect("skip is an Int")
+            .map(|skip| skip.max(0) as usize)
+            .unwrap_or(0);
+        if first.map_or(false, |first| first > MAX_STATUS_PAGE_SIZE) {
+            return Err(QueryExecutionError::TooExpensive);
+        }
+
+        // Paginate in the order of deployment hashes. An empty list of
+        // deployments means all deployments, and the store paginates them
+        let filter = if deployments.is_empty() {
+            status::Filter::Page { skip, first }
+        } else {
+            let mut deployments = deployments;
+            deployments.sort();
+            deployments.dedup();
+            let deployments = deployments.into_iter().skip(skip);
+            let deployments: Vec<_> = match first {
+                Some(first) => deployments.take(first).collect(),
+                None => deployments.collect(),
+            };
+            if deployments.is_empty() {
+                return Ok(r::Value::List(vec![]));
+            }
+            status::Filter::Deployments(deployments)
+        };
+        let mut infos = self.store.status(filter)?;
+        infos.sort_by(|a, b| a.subgraph.cmp(&b.subgraph));
+
+        // Only look up proofs of indexing when the query asks for them
+        let wants_poi = field
+            .selection_set
+            .fields()
+            .any(|(_, mut fields)| fields.any(|field| field.name == "latestProofOfIndexing"));
+        if !wants_poi {
+            return Ok(infos.into_value());
+        }
+
+        let indexer = self.poi_indexer(None);
+        let values =
+            with_latest_proofs_of_indexing(self.store.as_ref(), &self.logger, &indexer, infos)
+                .await;
+        Ok(r::Value::List(values))
     }
 
     fn resolve_indexing_statuses_for_subgraph_name(
@@ -252,7 +318,7 @@ impl<S: Store> IndexNodeResolver<S> {
         }
     }
 
-    async fn resolve_cached_ethereum_calls(
+    async fn resolve_ancestor_block_data(
         &self,
         field: &a::Field,
     ) -> Result<r::Value, QueryExecutionError> {
@@ -264,9 +330,107 @@ impl<S: Store> IndexNodeResolver<S> {
             .get_required::<BlockHash>("blockHash")
             .expect("Valid blockHash required");
 
+        let offset = field
+            .get_required::<BlockNumber>("offset")
+            .expect("Valid offset required");
+
+        let chain_store = if let Some(cs) = self.store.block_store().chain_store(&network) {
+            cs
+        } else {
+            error!(
+                self.logger,
+                "Failed to fetch ancestor block data; nonexistent network";
+                "network" => network,
+                "block_hash" => format!("{}", block_hash),
+            );
+            return Ok(r::Value::Null);
+        };
+
+        let block_number = match chain_store.block_number(&block_hash).await {
+            Ok(Some((_, number, _))) => number,
+            Ok(None) => return Ok(r::Value::Null),
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Failed to fetch ancestor block data; storage error";
+                    "network" => network.as_str(),
+                    "block_hash" => format!("{}", block_hash),
+                    "error" => e.to_string(),
+                );
+                return Ok(r::Value::Null);
+            }
+        };
+        let block_ptr = BlockPtr::new(block_hash.cheap_clone(), block_number);
+
+        match chain_store.ancestor_block(block_ptr, offset).await {
+            Ok(Some(block)) => Ok(block.into()),
+            Ok(None) => Ok(r::Value::Null),
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Failed to fetch ancestor block data";
+                    "network" => network.as_str(),
+                    "block_hash" => format!("{}", block_hash),
+                    "offset" => offset,
+                    "error" => e.to_string(),
+                );
+                Ok(r::Value::Null)
+            }
+        }
+    }
+
+    fn resolve_cached_blocks_from_number(
+        &self,
+        field: &a::Field,
+    ) -> Result<r::Value, QueryExecutionError> {
+        let network = field
+            .get_required::<String>("network")
+            .expect("Valid network required");
+
+        let block_number = field
+            .get_required::<BlockNumber>("blockNumber")
+            .expect("Valid blockNumber required");
+
+        let chain_store = if let Some(cs) = self.store.block_store().chain_store(&network) {
+            cs
+        } else {
+            error!(
+                self.logger,
+                "Failed to fetch cached blocks; nonexistent network";
+                "network" => network,
+                "block_number" => block_number,
+            );
+            return Ok(r::Value::Null);
+        };
+
+        let hashes = chain_store
+            .block_hashes_by_block_number(block_number)
+            .map_err(|e| QueryExecutionError::StoreError(e.into()))?;
+
+        Ok(r::Value::List(
+            hashes
+                .into_iter()
+                .map(|hash| {
+                    object! {
+                        hash: hash.hash_hex(),
+                        number: format!("{}", block_number),
+                    }
+                })
+                .collect(),
+        ))
+    }
+
+    /// Finds the call cache for `network` and the pointer and timestamp
+    /// for the block with the given hash. Returns `None` and logs why if
+    /// either of them can not be found
+    async fn call_cache_at_block(
+        &self,
+        network: &str,
+        block_hash: &BlockHash,
+    ) -> Option<(Arc<dyn EthereumCallCache>, BlockPtr, Option<u64>)> {
         let chain = if let Ok(c) = self
             .blockchain_map
-            .get::<graph_chain_ethereum::Chain>(network.clone())
+            .get::<graph_chain_ethereum::Chain>(network.to_string())
         {
             c
         } else {
@@ -276,12 +440,12 @@ impl<S: Store> IndexNodeResolver<S> {
                 "network" => network,
                 "block_hash" => format!("{}", block_hash),
             );
-            return Ok(r::Value::Null);
+            return None;
         };
         let chain_store = chain.chain_store();
         let call_cache = chain.call_cache();
 
-        let (block_number, timestamp) = match chain_store.block_number(&block_hash).await {
+        let (block_number, timestamp) = match chain_store.block_number(block_hash).await {
             Ok(Some((_, n, timestamp))) => (n, timestamp),
             Ok(None) => {
                 error!(
@@ -290,21 +454,42 @@ impl<S: Store> IndexNodeResolver<S> {
                     "network" => network,
                     "block_hash" => format!("{}", block_hash),
                 );
-                return Ok(r::Value::Null);
+                return None;
             }
             Err(e) => {
                 error!(
                     self.logger,
                     "Failed to fetch cached Ethereum calls; storage error";
-                    "network" => network.as_str(),
+                    "network" => network,
                     "block_hash" => format!("{}", block_hash),
                     "error" => e.to_string(),
                 );
-                return Ok(r::Value::Null);
+                return None;
             }
         };
         let block_ptr = BlockPtr::new(block_hash.cheap_clone(), block_number);
 
+        Some((call_cache, block_ptr, timestamp))
+    }
+
+    async fn resolve_cached_ethereum_calls(
+        &self,
+        field: &a::Field,
+    ) -> Result<r::Value, QueryExecutionError> {
+        let network = field
+            .get_required::<String>("network")
+            .expect("Valid network required");
+
+        let block_hash = field
+            .get_required::<BlockHash>("blockHash")
+            .expect("Valid blockHash required");
+
+        let (call_cache, block_ptr, timestamp) =
+            match self.call_cache_at_block(&network, &block_hash).await {
+                Some(found) => found,
+                None => return Ok(r::Value::Null),
+            };
+
         let calls = match call_cache.get_calls_in_block(block_ptr) {
             Ok(c) => c,
             Err(e) => {
@@ -322,23 +507,58 @@ impl<S: Store> IndexNodeResolver<S> {
         Ok(r::Value::List(
             calls
                 .into_iter()
-                .map(|cached_call| {
-                    object! {
-                        idHash: &cached_call.blake3_id[..],
-                        block: object! {
-                            hash: cached_call.block_ptr.hash.hash_hex(),
-                            number: cached_call.block_ptr.number,
-                            timestamp: timestamp,
-                        },
-                        contractAddress: &cached_call.contract_address[..],
-                        returnValue: &cached_call.return_value[..],
-                    }
-                })
+                .map(|cached_call| cached_call_to_graphql(cached_call, timestamp))
                 .collect::<Vec<r::Value>>(),
         ))
     }
 
-    fn resolve_proof_of_indexing(&self, field: &a::Field) -> Result<r::Value, QueryExecutionError> {
+    async fn resolve_cached_ethereum_call(
+        &self,
+        field: &a::Field,
+    ) -> Result<r::Value, QueryExecutionError> {
+        let network = field
+            .get_required::<String>("network")
+            .expect("Valid network required");
+
+        let block_hash = field
+            .get_required::<BlockHash>("blockHash")
+            .expect("Valid blockHash required");
+
+        let contract_address = field
+            .get_required::<Address>("contractAddress")
+            .expect("Valid contractAddress required");
+
+        let calldata = field
+            .get_required::<scalar::Bytes>("calldata")
+            .expect("Valid calldata required");
+
+        let (call_cache, block_ptr, timestamp) =
+            match self.call_cache_at_block(&network, &block_hash).await {
+                Some(found) => found,
+                None => return Ok(r::Value::Null),
+            };
+
+        match call_cache.get_cached_call(contract_address, calldata.as_slice(), block_ptr) {
+            Ok(Some(cached_call)) => Ok(cached_call_to_graphql(cached_call, timestamp)),
+            Ok(None) => Ok(r::Value::Null),
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Failed to fetch cached Ethereum call; storage error";
+                    "network" => network.as_str(),
+                    "block_hash" => format!("{}", block_hash),
+                    "contract_address" => format!("{:x}", contract_address),
+                    "error" => e.to_string(),
+                );
+                Err(QueryExecutionError::StoreError(e.into()))
+            }
+        }
+    }
+
+    async fn resolve_proof_of_indexing(
+        &self,
+        field: &a::Field,
+    ) -> Result<r::Value, QueryExecutionError> {
         let deployment_id = field
             .get_required::<DeploymentHash>("subgraph")
             .expect("Valid subgraphId required");
@@ -355,36 +575,30 @@ impl<S: Store> IndexNodeResolver<S> {
 
         let block = BlockPtr::new(block_hash, block_number);
 
-        let mut indexer = field
+        let indexer = field
             .get_optional::<Address>("indexer")
             .expect("Invalid indexer");
+        let indexer = self.poi_indexer(indexer);
+
+        Ok(proof_of_indexing(
+            self.store.as_ref(),
+            &self.logger,
+            &deployment_id,
+            &indexer,
+            block,
+        )
+        .await)
+    }
 
+    /// The indexer to sign proofs of indexing with. When the access token
+    /// is invalid, proofs are signed with a zero'd address
+    fn poi_indexer(&self, indexer: Option<Address>) -> Option<Address> {
         let poi_protection = PoiProtection::from_env(&ENV_VARS);
-        if !poi_protection.validate_access_token(self.bearer_token.as_deref()) {
-            // Let's sign the POI with a zero'd address when the access token is
-            // invalid.
-            indexer = Some(Address::zero());
+        if poi_protection.validate_access_token(self.bearer_token.as_deref()) {
+            indexer
+        } else {
+            Some(Address::zero())
         }
-
-        let poi_fut = self
-            .store
-            .get_proof_of_indexing(&deployment_id, &indexer, block.clone());
-        let poi = match futures::executor::block_on(poi_fut) {
-            Ok(Some(poi)) => r::Value::String(format!("0x{}", hex::encode(poi))),
-            Ok(None) => r::Value::Null,
-            Err(e) => {
-                error!(
-                    self.logger,
-                    "Failed to query proof of indexing";
-                    "subgraph" => deployment_id,
-                    "block" => format!("{}", block),
-                    "error" => format!("{:?}", e)
-                );
-                r::Value::Null
-            }
-        };
-
-        Ok(poi)
     }
 
     async fn resolve_public_proofs_of_indexing(
@@ -496,7 +710,7 @@ impl<S: Store> IndexNodeResolver<S> {
                 .await
                 .map_err(SubgraphManifestResolveError::ResolveError)?;
 
-            serde_yaml::from_slice(&file_bytes).map_err(SubgraphManifestResolveError::ParseError)?
+            parse_raw_manifest(&file_bytes).map_err(SubgraphManifestResolveError::ParseError)?
         };
 
         let kind = BlockchainKind::from_manifest(&raw_yaml)
@@ -647,6 +861,220 @@ impl<S: Store> IndexNodeResolver<S> {
         ))
     }
 
+    fn resolve_running_queries(&self) -> Result<r::Value, QueryExecutionError> {
+        // The list shows what other clients are doing
+        self.check_restricted_access("runningQueries")?;
+        Ok(r::Value::List(
+            RUNNING_QUERIES
+                .list()
+                .iter()
+                .map(|query| {
+                    object! {
+                        __typename: "RunningQuery",
+                        id: query.id.to_string(),
+                        deployment: query.deployment.to_string(),
+                        shapeHash: format!("{:x}", query.shape_hash),
+                        elapsedMs: query.elapsed().as_millis() as u64,
+                        client: query.client.clone(),
+                    }
+                })
+                .collect(),
+        ))
+    }
+
+    fn resolve_slow_queries(&self, field: &a::Field) -> Result<r::Value, QueryExecutionError> {
+        let first = field
+            .get_optional::<i32>("first")
+            .expect("first is an Int")
+            .map(|first| first.max(0) as usize)
+            .unwrap_or(DEFAULT_SLOW_QUERIES);
+
+        Ok(r::Value::List(
+            SLOW_QUERIES
+                .top(first)
+                .into_iter()
+                .map(|query| {
+                    object! {
+                        __typename: "SlowQuery",
+                        deployment: query.deployment.to_string(),
+                        shapeHash: format!("{:x}", query.shape_hash),
+                        query: query.query.as_str(),
+                        variablesHash: format!("{:x}", query.variables_hash),
+                        block: query.block,
+                        count: query.count,
+                        totalMs: query.total.as_millis() as u64,
+                        slowest: object! {
+                            __typename: "QueryTiming",
+                            prepareMs: query.max.prepare.as_millis() as u64,
+                            waitMs: query.max.wait.as_millis() as u64,
+                            executeMs: query.max.execute.as_millis() as u64,
+                        },
+                    }
+                })
+                .collect(),
+        ))
+    }
+
+    /// Fail unless the request has the access token that fields which
+    /// reveal details about the node or about other clients require
+    fn check_restricted_access(&self, field: &str) -> Result<(), QueryExecutionError> {
+        PoiProtection::from_env(&ENV_VARS)
+            .check_restricted_access(field, self.bearer_token.as_deref())
+    }
+
+    fn resolve_environment(&self) -> Result<r::Value, QueryExecutionError> {
+        self.check_restricted_access("environment")?;
+        Ok(r::Value::List(
+            graph::env::settings_from_env()
+                .into_iter()
+                .map(|setting| {
+                    object! {
+                        __typename: "EnvironmentVariable",
+                        name: setting.name,
+                        value: setting.value,
+                        source: setting.source.as_str(),
+                    }
+                })
+                .collect(),
+        ))
+    }
+
+    fn resolve_jobs(&self) -> Result<r::Value, QueryExecutionError> {
+        Ok(r::Value::List(
+            self.store
+                .job_statuses()?
+                .into_iter()
+                .map(|job| {
+                    object! {
+                        __typename: "Job",
+                        node: job.node.clone(),
+                        name: job.name.clone(),
+                        intervalSecs: job.interval.as_secs() as i32,
+                        runs: job.runs,
+                        running: job.is_running(),
+                        startedAt: job.started_at.map(|at| at.to_rfc3339()),
+                        finishedAt: job.finished_at.map(|at| at.to_rfc3339()),
+                        durationMs: job.duration.map(|duration| duration.as_millis() as u64),
+                    }
+                })
+                .collect(),
+        ))
+    }
+
+    fn resolve_provider_health(&self, field: &a::Field) -> Result<r::Value, QueryExecutionError> {
+        let network = field
+            .get_required::<String>("network")
+            .expect("Valid network required");
+
+        let chain = match self
+            .blockchain_map
+            .get::<graph_chain_ethereum::Chain>(network.clone())
+        {
+            Ok(chain) => chain,
+            Err(_) => return Ok(r::Value::List(vec![])),
+        };
+        Ok(r::Value::List(
+            chain
+                .provider_health()
+                .into_iter()
+                .map(|(provider, health)| {
+                    let health = health.as_ref();
+                    object! {
+                        __typename: "ProviderHealth",
+                        provider: provider,
+                        checked: health.is_some(),
+                        clientVersion: health.and_then(|health| health.client_version.clone()),
+                        syncing: health.map_or(false, |health| health.syncing),
+                        brokenVersion: health.map_or(false, |health| health.broken_version),
+                        checkedAt: health.map(|health| health.checked_at.to_rfc3339()),
+                    }
+                })
+                .collect(),
+        ))
+    }
+
+    fn resolve_entity_writes(&self, field: &a::Field) -> Result<r::Value, QueryExecutionError> {
+        let subgraph_id = field
+            .get_required::<DeploymentHash>("subgraphId")
+            .expect("Valid subgraphId required");
+        let first = field
+            .get_optional::<i32>("first")
+            .expect("first is an Int")
+            .map(|first| first.max(0) as usize)
+            .unwrap_or(DEFAULT_HOT_ENTITIES);
+
+        let stats = match WRITE_STATS.get(&subgraph_id) {
+            Some(stats) => stats,
+            None => return Ok(r::Value::Null),
+        };
+        let entity_types: Vec<_> = stats
+            .entity_types()
+            .into_iter()
+            .map(|(entity_type, writes)| {
+                object! {
+                    __typename: "EntityTypeWrites",
+                    entityType: entity_type.as_str(),
+                    writes: writes,
+                }
+            })
+            .collect();
+        let hottest: Vec<_> = stats
+            .hottest(first)
+            .into_iter()
+            .map(|entity| {
+                object! {
+                    __typename: "HotEntity",
+                    entityType: entity.entity_type.as_str(),
+                    id: entity.id.to_string(),
+                    writes: entity.writes,
+                }
+            })
+            .collect();
+        Ok(object! {
+            __typename: "EntityWrites",
+            entityTypes: entity_types,
+            hottest: hottest,
+            sampleRate: stats.sample_rate() as i32,
+        })
+    }
+
+    fn resolve_handler_stats(&self, field: &a::Field) -> Result<r::Value, QueryExecutionError> {
+        let subgraph_id = field
+            .get_required::<DeploymentHash>("subgraphId")
+            .expect("Valid subgraphId required");
+
+        let stats = match HANDLER_STATS.get(subgraph_id.as_str()) {
+            Some(stats) => stats,
+            None => return Ok(r::Value::Null),
+        };
+        let handlers: Vec<_> = stats
+            .handlers()
+            .into_iter()
+            .map(|stat| {
+                let slowest: Vec<_> = stat
+                    .slowest
+                    .iter()
+                    .map(|invocation| {
+                        object! {
+                            __typename: "HandlerInvocation",
+                            blockNumber: invocation.block,
+                            durationMs: invocation.elapsed.as_millis() as u64,
+                        }
+                    })
+                    .collect();
+                object! {
+                    __typename: "HandlerStats",
+                    dataSource: stat.data_source,
+                    handler: stat.handler,
+                    invocations: stat.invocations,
+                    totalMs: stat.total.as_millis() as u64,
+                    slowest: slowest,
+                }
+            })
+            .collect();
+        Ok(r::Value::List(handlers))
+    }
+
     fn version(&self) -> Result<r::Value, QueryExecutionError> {
         Ok(VERSION.clone().into_value())
     }
@@ -733,6 +1161,86 @@ impl<S: Store> BlockPtrForNumber for IndexNodeResolver<S> {
     }
 }
 
+/// The proof of indexing of `deployment_id` at `block`, or `null` if there
+/// is none or looking it up failed
+async fn proof_of_indexing<S: StatusStore + ?Sized>(
+    store: &S,
+    logger: &Logger,
+    deployment_id: &DeploymentHash,
+    indexer: &Option<Address>,
+    block: BlockPtr,
+) -> r::Value {
+    match store
+        .get_proof_of_indexing(deployment_id, indexer, block.clone())
+        .await
+    {
+        Ok(Some(poi)) => r::Value::String(format!("0x{}", hex::encode(poi))),
+        Ok(None) => r::Value::Null,
+        Err(e) => {
+            error!(
+                logger,
+                "Failed to query proof of indexing";
+                "subgraph" => deployment_id,
+                "block" => format!("{}", block),
+                "error" => format!("{:?}", e)
+            );
+            r::Value::Null
+        }
+    }
+}
+
+/// Turn `infos` into values that also have the `latestProofOfIndexing` of
+/// each deployment. The proofs of all deployments are looked up at the
+/// same time
+async fn with_latest_proofs_of_indexing<S: StatusStore + ?Sized>(
+    store: &S,
+    logger: &Logger,
+    indexer: &Option<Address>,
+    infos: Vec<status::Info>,
+) -> Vec<r::Value> {
+    let pois: Vec<_> = futures::stream::iter(infos.iter().map(|info| async move {
+        let latest = info
+            .chains
+            .first()
+            .and_then(|chain| chain.latest_block.as_ref())
+            .map(|block| block.ptr().clone());
+        match (DeploymentHash::new(info.subgraph.clone()), latest) {
+            (Ok(deployment), Some(block)) => {
+                proof_of_indexing(store, logger, &deployment, indexer, block).await
+            }
+            _ => r::Value::Null,
+        }
+    }))
+    .buffered(MAX_CONCURRENT_POIS)
+    .collect()
+    .await;
+
+    infos
+        .into_iter()
+        .zip(pois)
+        .map(|(info, poi)| {
+            let mut value = info.into_value();
+            if let r::Value::Object(object) = &mut value {
+                object.extend(Some((Word::from("latestProofOfIndexing"), poi)));
+            }
+            value
+        })
+        .collect()
+}
+
+fn cached_call_to_graphql(cached_call: CachedEthereumCall, timestamp: Option<u64>) -> r::Value {
+    object! {
+        idHash: &cached_call.blake3_id[..],
+        block: object! {
+            hash: cached_call.block_ptr.hash.hash_hex(),
+            number: cached_call.block_ptr.number,
+            timestamp: timestamp,
+        },
+        contractAddress: &cached_call.contract_address[..],
+        returnValue: &cached_call.return_value[..],
+    }
+}
+
 fn entity_changes_to_graphql(entity_changes: Vec<EntityOperation>) -> r::Value {
     // Results are sorted first alphabetically by entity type, then by entity
     // ID, and then aphabetically by field name.
@@ -822,11 +1330,14 @@ impl<S: Store> Resolver for IndexNodeResolver<S> {
             field.name.as_str(),
             scalar_type.name.as_str(),
         ) {
-            ("Query", "proofOfIndexing", "Bytes") => self.resolve_proof_of_indexing(field),
+            ("Query", "proofOfIndexing", "Bytes") => self.resolve_proof_of_indexing(field).await,
             ("Query", "blockData", "JSONObject") => self.resolve_block_data(field).await,
             ("Query", "blockHashFromNumber", "Bytes") => {
                 self.resolve_block_hash_from_number(field).await
             }
+            ("Query", "ancestorBlockData", "JSONObject") => {
+                self.resolve_ancestor_block_data(field).await
+            }
 
             // Fallback to the same as is in the default trait implementation. There
             // is no way to call back into the default implementation for the trait.
@@ -846,7 +1357,7 @@ impl<S: Store> Resolver for IndexNodeResolver<S> {
         // Resolves the `field.name` top-level field.
         match (prefetched_objects, object_type.name(), field.name.as_str()) {
             (None, "SubgraphIndexingStatus", "indexingStatuses") => {
-                self.resolve_indexing_statuses(field)
+                self.resolve_indexing_statuses(field).await
             }
             (None, "SubgraphIndexingStatus", "indexingStatusesForSubgraphName") => {
                 self.resolve_indexing_statuses_for_subgraph_name(field)
@@ -854,6 +1365,15 @@ impl<S: Store> Resolver for IndexNodeResolver<S> {
             (None, "CachedEthereumCall", "cachedEthereumCalls") => {
                 self.resolve_cached_ethereum_calls(field).await
             }
+            (None, "Block", "cachedBlocksFromNumber") => {
+                self.resolve_cached_blocks_from_number(field)
+            }
+            (None, "RunningQuery", "runningQueries") => self.resolve_running_queries(),
+            (None, "SlowQuery", "slowQueries") => self.resolve_slow_queries(field),
+            (None, "EnvironmentVariable", "environment") => self.resolve_environment(),
+            (None, "Job", "jobs") => self.resolve_jobs(),
+            (None, "ProviderHealth", "providerHealth") => self.resolve_provider_health(field),
+            (None, "HandlerStats", "handlerStats") => self.resolve_handler_stats(field),
 
             // The top-level `publicProofsOfIndexing` field
             (None, "PublicProofOfIndexingResult", "publicProofsOfIndexing") => {
@@ -882,6 +1402,8 @@ impl<S: Store> Resolver for IndexNodeResolver<S> {
             }
             (None, "subgraphFeatures") => self.resolve_subgraph_features(field).await,
             (None, "entityChangesInBlock") => self.resolve_entity_changes_in_block(field),
+            (None, "entityWrites") => self.resolve_entity_writes(field),
+            (None, "cachedEthereumCall") => self.resolve_cached_ethereum_call(field).await,
             // The top-level `subgraphVersions` field
             (None, "apiVersions") => self.resolve_api_versions(field),
             (None, "version") => self.version(),
@@ -891,3 +1413,151 @@ impl<S: Store> Resolver for IndexNodeResolver<S> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use graph::components::server::index_node::VersionInfo;
+    use graph::components::store::{DeploymentId, PartialBlockPtr};
+    use graph::data::subgraph::schema::SubgraphHealth;
+    use graph::util::jobs::JobStatus;
+    use tokio::sync::Barrier;
+
+    use super::*;
+
+    /// A status store whose proof of indexing lookups only finish once
+    /// two of them are waiting at the same time
+    struct MockStatusStore {
+        barrier: Barrier,
+    }
+
+    #[async_trait]
+    impl StatusStore for MockStatusStore {
+        async fn query_permit(&self) -> Result<QueryPermit, StoreError> {
+            unimplemented!()
+        }
+
+        fn status(&self, _filter: status::Filter) -> Result<Vec<status::Info>, StoreError> {
+            unimplemented!()
+        }
+
+        fn version_info(&self, _version_id: &str) -> Result<VersionInfo, StoreError> {
+            unimplemented!()
+        }
+
+        fn versions_for_subgraph_id(
+            &self,
+            _subgraph_id: &str,
+        ) -> Result<(Option<String>, Option<String>), StoreError> {
+            unimplemented!()
+        }
+
+        fn subgraphs_for_deployment_hash(
+            &self,
+            _deployment_hash: &str,
+        ) -> Result<Vec<(String, String)>, StoreError> {
+            unimplemented!()
+        }
+
+        fn subgraph_versions(&self) -> Result<Vec<(String, String, String)>, StoreError> {
+            unimplemented!()
+        }
+
+        fn check_connection(&self) -> Result<(), StoreError> {
+            unimplemented!()
+        }
+
+        fn job_statuses(&self) -> Result<Vec<JobStatus>, StoreError> {
+            unimplemented!()
+        }
+
+        async fn get_proof_of_indexing(
+            &self,
+            subgraph_id: &DeploymentHash,
+            _indexer: &Option<Address>,
+            block: BlockPtr,
+        ) -> Result<Option<[u8; 32]>, StoreError> {
+            self.barrier.wait().await;
+            if subgraph_id.as_str() == "broken" {
+                return Err(StoreError::Unknown(anyhow!("no poi for {}", subgraph_id)));
+            }
+            Ok(Some([block.number as u8; 32]))
+        }
+
+        async fn get_public_proof_of_indexing(
+            &self,
+            _subgraph_id: &DeploymentHash,
+            _block_number: BlockNumber,
+            _fetch_block_ptr: &dyn BlockPtrForNumber,
+        ) -> Result<Option<(PartialBlockPtr, [u8; 32])>, StoreError> {
+            unimplemented!()
+        }
+    }
+
+    fn info(id: i32, subgraph: &str, latest: Option<BlockNumber>) -> status::Info {
+        let latest_block = latest.map(|number| {
+            status::EthereumBlock::from(BlockPtr::new(
+                BlockHash::from(vec![number as u8; 32]),
+                number,
+            ))
+        });
+        status::Info {
+            id: DeploymentId(id),
+            subgraph: subgraph.to_string(),
+            synced: true,
+            health: SubgraphHealth::Healthy,
+            fatal_error: None,
+            non_fatal_errors: vec![],
+            paused: None,
+            chains: vec![status::ChainInfo {
+                network: "mainnet".to_string(),
+                chain_head_block: None,
+                earliest_block_number: 0,
+                latest_block,
+            }],
+            entity_count: 0,
+            storage_bytes: None,
+            node: None,
+            history_blocks: i32::MAX,
+            version_label: None,
+            description: None,
+            repository: None,
+        }
+    }
+
+    fn latest_poi(value: &r::Value) -> &r::Value {
+        match value {
+            r::Value::Object(object) => object.get("latestProofOfIndexing").unwrap(),
+            _ => panic!("status is not an object: {:?}", value),
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn looks_up_latest_proofs_of_indexing_concurrently() {
+        let logger = Logger::root(slog::Discard, o!());
+        let store = MockStatusStore {
+            barrier: Barrier::new(2),
+        };
+        let infos = vec![
+            info(1, "QmFirst", Some(7)),
+            info(2, "QmNoBlock", None),
+            info(3, "broken", Some(9)),
+        ];
+
+        // The lookups wait for each other and would never finish if they
+        // ran one after the other or blocked the only worker thread
+        let values = tokio::time::timeout(
+            Duration::from_secs(10),
+            with_latest_proofs_of_indexing(&store, &logger, &None, infos),
+        )
+        .await
+        .expect("proofs of indexing are looked up concurrently");
+
+        assert_eq!(3, values.len());
+        assert_eq!(
+            &r::Value::String(format!("0x{}", hex::encode([7u8; 32]))),
+            latest_poi(&values[0])
+        );
+        assert_eq!(&r::Value::Null, latest_poi(&values[1]));
+        assert_eq!(&r::Value::Null, latest_poi(&values[2]));
+    }
+}
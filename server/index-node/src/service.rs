@@ -156,6 +156,7 @@ where
             let options = QueryExecutionOptions {
                 resolver,
                 deadline: None,
+                running: None,
                 max_first: std::u32::MAX,
                 max_skip: std::u32::MAX,
                 trace: false,
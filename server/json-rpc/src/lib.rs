@@ -1,3 +1,5 @@
+use graph::data::query::RUNNING_QUERIES;
+use graph::data::subgraph::schema::{DeploymentMetadata, DeploymentPlacement};
 use graph::prelude::{Value as GraphValue, *};
 use jsonrpsee::core::Error as JsonRpcError;
 use jsonrpsee::http_server::{HttpServerBuilder, HttpServerHandle};
@@ -68,6 +70,31 @@ impl JsonRpcServer {
                 state.reassign_handler(params.parse()?).await
             })
             .unwrap();
+        rpc_module
+            .register_async_method("subgraph_pause", |params, state| async move {
+                state.pause_handler(params.parse()?).await
+            })
+            .unwrap();
+        rpc_module
+            .register_async_method("subgraph_resume", |params, state| async move {
+                state.resume_handler(params.parse()?).await
+            })
+            .unwrap();
+        rpc_module
+            .register_async_method("subgraph_prune", |params, state| async move {
+                state.prune_handler(params.parse()?).await
+            })
+            .unwrap();
+        rpc_module
+            .register_async_method("subgraph_rewind", |params, state| async move {
+                state.rewind_handler(params.parse()?).await
+            })
+            .unwrap();
+        rpc_module
+            .register_async_method("query_cancel", |params, state| async move {
+                state.cancel_query_handler(params.parse()?).await
+            })
+            .unwrap();
 
         let _handle = http_server.start(rpc_module)?;
         Ok(Self { _handle })
@@ -87,6 +114,10 @@ impl<R: SubgraphRegistrar> ServerState<R> {
     const REMOVE_ERROR: i64 = 1;
     const CREATE_ERROR: i64 = 2;
     const REASSIGN_ERROR: i64 = 3;
+    const PAUSE_ERROR: i64 = 4;
+    const RESUME_ERROR: i64 = 5;
+    const PRUNE_ERROR: i64 = 6;
+    const REWIND_ERROR: i64 = 7;
 
     /// Handler for the `subgraph_create` endpoint.
     async fn create_handler(&self, params: SubgraphCreateParams) -> JsonRpcResult<JsonValue> {
@@ -111,7 +142,7 @@ impl<R: SubgraphRegistrar> ServerState<R> {
         info!(&self.logger, "Received subgraph_deploy request"; "params" => format!("{:?}", params));
 
         let node_id = params.node_id.clone().unwrap_or(self.node_id.clone());
-        let routes = subgraph_routes(&params.name, self.http_port, self.ws_port);
+        let mut routes = subgraph_routes(&params.name, self.http_port, self.ws_port);
         match self
             .registrar
             .create_subgraph_version(
@@ -124,10 +155,28 @@ impl<R: SubgraphRegistrar> ServerState<R> {
                 None,
                 None,
                 params.history_blocks,
+                DeploymentMetadata {
+                    version_label: params.version_label.clone(),
+                    description: params.description.clone(),
+                    repository: params.repository.clone(),
+                },
+                DeploymentPlacement {
+                    shard: params.shard.clone(),
+                    node: params.node_id.clone(),
+                },
             )
             .await
         {
-            Ok(_) => Ok(routes),
+            Ok(result) => {
+                // Manifest warnings are only included in the response if
+                // there are any so that the response stays the same for
+                // clients that don't know about them
+                if !result.warnings.is_empty() {
+                    routes["warnings"] = serde_json::to_value(&result.warnings)
+                        .expect("invalid subgraph manifest warnings");
+                }
+                Ok(routes)
+            }
             Err(e) => Err(json_rpc_error(
                 &self.logger,
                 "subgraph_deploy",
@@ -173,6 +222,88 @@ impl<R: SubgraphRegistrar> ServerState<R> {
             )),
         }
     }
+
+    /// Handler for the `subgraph_pause` endpoint.
+    async fn pause_handler(&self, params: SubgraphPauseParams) -> JsonRpcResult<GraphValue> {
+        info!(&self.logger, "Received subgraph_pause request"; "params" => format!("{:?}", params));
+
+        match self.registrar.pause_subgraph(&params.deployment).await {
+            Ok(_) => Ok(Value::Null),
+            Err(e) => Err(json_rpc_error(
+                &self.logger,
+                "subgraph_pause",
+                e,
+                Self::PAUSE_ERROR,
+                params,
+            )),
+        }
+    }
+
+    /// Handler for the `subgraph_resume` endpoint.
+    async fn resume_handler(&self, params: SubgraphPauseParams) -> JsonRpcResult<GraphValue> {
+        info!(&self.logger, "Received subgraph_resume request"; "params" => format!("{:?}", params));
+
+        match self.registrar.resume_subgraph(&params.deployment).await {
+            Ok(_) => Ok(Value::Null),
+            Err(e) => Err(json_rpc_error(
+                &self.logger,
+                "subgraph_resume",
+                e,
+                Self::RESUME_ERROR,
+                params,
+            )),
+        }
+    }
+
+    /// Handler for the `subgraph_prune` endpoint.
+    async fn prune_handler(&self, params: SubgraphPruneParams) -> JsonRpcResult<GraphValue> {
+        info!(&self.logger, "Received subgraph_prune request"; "params" => format!("{:?}", params));
+
+        match self
+            .registrar
+            .prune_subgraph(&params.deployment, params.history_blocks)
+            .await
+        {
+            Ok(_) => Ok(Value::Null),
+            Err(e) => Err(json_rpc_error(
+                &self.logger,
+                "subgraph_prune",
+                e,
+                Self::PRUNE_ERROR,
+                params,
+            )),
+        }
+    }
+
+    /// Handler for the `subgraph_rewind` endpoint.
+    async fn rewind_handler(&self, params: SubgraphRewindParams) -> JsonRpcResult<GraphValue> {
+        info!(&self.logger, "Received subgraph_rewind request"; "params" => format!("{:?}", params));
+
+        let block_ptr = BlockPtr::new(params.block_hash.into(), params.block_number);
+        match self
+            .registrar
+            .rewind_subgraph(&params.deployment, block_ptr)
+            .await
+        {
+            Ok(_) => Ok(Value::Null),
+            Err(e) => Err(json_rpc_error(
+                &self.logger,
+                "subgraph_rewind",
+                e,
+                Self::REWIND_ERROR,
+                params,
+            )),
+        }
+    }
+
+    /// Handler for the `query_cancel` endpoint. The query fails with an
+    /// error the next time it checks whether it should stop. Returns
+    /// `false` if no query with that id is running
+    async fn cancel_query_handler(&self, params: QueryCancelParams) -> JsonRpcResult<bool> {
+        info!(&self.logger, "Received query_cancel request"; "params" => format!("{:?}", params));
+
+        Ok(RUNNING_QUERIES.cancel(params.id))
+    }
 }
 
 fn json_rpc_error(
@@ -238,6 +369,10 @@ struct SubgraphDeployParams {
     node_id: Option<NodeId>,
     debug_fork: Option<DeploymentHash>,
     history_blocks: Option<i32>,
+    version_label: Option<String>,
+    description: Option<String>,
+    repository: Option<String>,
+    shard: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -250,3 +385,26 @@ struct SubgraphReassignParams {
     ipfs_hash: DeploymentHash,
     node_id: NodeId,
 }
+
+#[derive(Debug, Deserialize)]
+struct SubgraphPauseParams {
+    deployment: DeploymentHash,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubgraphPruneParams {
+    deployment: DeploymentHash,
+    history_blocks: Option<BlockNumber>,
+}
+
+#[derive(Debug, Deserialize)]
+struct QueryCancelParams {
+    id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubgraphRewindParams {
+    deployment: DeploymentHash,
+    block_hash: web3::types::H256,
+    block_number: BlockNumber,
+}
@@ -0,0 +1,3 @@
+alter table subgraphs.subgraph_error
+    drop column data_source,
+    drop column transaction_hash;
@@ -0,0 +1,3 @@
+alter table subgraphs.subgraph_error
+    add column data_source text,
+    add column transaction_hash text;
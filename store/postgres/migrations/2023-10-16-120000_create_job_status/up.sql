@@ -0,0 +1,10 @@
+create table public.job_status(
+    node_id       text        not null,
+    name          text        not null,
+    interval_secs int8        not null,
+    runs          int8        not null default 0,
+    started_at    timestamptz,
+    finished_at   timestamptz,
+    duration_ms   int8,
+    primary key(node_id, name)
+);
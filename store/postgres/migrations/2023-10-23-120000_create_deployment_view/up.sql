@@ -0,0 +1,7 @@
+create table subgraphs.deployment_view(
+    deployment int  not null
+               references subgraphs.subgraph_deployment(id) on delete cascade,
+    name       text not null,
+    query      text not null,
+    primary key(deployment, name)
+);
@@ -13,6 +13,9 @@
 //!           shard
 //!   * 2, n: to lock the deployment with id n to make sure only one write
 //!           happens to it
+//!   * 3, n: to lock pruning of the deployment with id n
+//!   * 4, n: held by the graph-node instance that writes to the deployment
+//!           with id n for as long as it does so
 
 use diesel::sql_types::Bool;
 use diesel::{sql_query, PgConnection, RunQueryDsl};
@@ -56,6 +59,27 @@ impl Scope {
             .map_err(StoreError::from)
     }
 
+    /// Whether `conn` holds the lock for the deployment in this scope with
+    /// the given id
+    fn holds(&self, conn: &PgConnection, id: DeploymentId) -> Result<bool, StoreError> {
+        #[derive(QueryableByName)]
+        struct Held {
+            #[sql_type = "Bool"]
+            held: bool,
+        }
+
+        sql_query(format!(
+            "select exists (select 1 from pg_locks \
+                             where locktype = 'advisory' and pid = pg_backend_pid() \
+                               and classid = {} and objid = {id} and objsubid = 2 \
+                               and granted) as held",
+            self.id
+        ))
+        .get_result::<Held>(conn)
+        .map(|res| res.held)
+        .map_err(StoreError::from)
+    }
+
     /// Unlock the deployment in this scope with the given id.
     fn unlock(&self, conn: &PgConnection, id: DeploymentId) -> Result<(), StoreError> {
         sql_query(format!("select pg_advisory_unlock({}, {id})", self.id))
@@ -68,6 +92,7 @@ impl Scope {
 const COPY: Scope = Scope { id: 1 };
 const WRITE: Scope = Scope { id: 2 };
 const PRUNE: Scope = Scope { id: 3 };
+const WRITER: Scope = Scope { id: 4 };
 
 /// Get a lock for running migrations. Blocks until we get the lock.
 pub(crate) fn lock_migration(conn: &PgConnection) -> Result<(), StoreError> {
@@ -121,3 +146,28 @@ pub(crate) fn try_lock_pruning(conn: &PgConnection, site: &Site) -> Result<bool,
 pub(crate) fn unlock_pruning(conn: &PgConnection, site: &Site) -> Result<(), StoreError> {
     PRUNE.unlock(conn, site.id)
 }
+
+/// Try to take the lock that marks this graph-node instance as the only
+/// writer of the deployment. Return `true` if we got the lock, and `false`
+/// if another instance holds it. The lock is held on `conn` until it is
+/// released with `unlock_writer` or `conn` is closed
+pub(crate) fn try_lock_writer(conn: &PgConnection, site: &Site) -> Result<bool, StoreError> {
+    WRITER.try_lock(conn, site.id)
+}
+
+/// Whether `conn` still holds the lock acquired with `try_lock_writer`
+pub(crate) fn holds_writer(conn: &PgConnection, site: &Site) -> Result<bool, StoreError> {
+    WRITER.holds(conn, site.id)
+}
+
+/// Release the lock acquired with `try_lock_writer`.
+pub(crate) fn unlock_writer(conn: &PgConnection, site: &Site) -> Result<(), StoreError> {
+    WRITER.unlock(conn, site.id)
+}
+
+/// Release all advisory locks held on `conn`. Only use this on connections
+/// that are set aside for writer locks with `try_lock_writer`
+pub(crate) fn unlock_all_writers(conn: &PgConnection) -> Result<(), StoreError> {
+    sql_query("select pg_advisory_unlock_all()").execute(conn)?;
+    Ok(())
+}
@@ -5,8 +5,8 @@ use std::{
 };
 
 use graph::{
-    blockchain::ChainIdentifier,
-    components::store::{BlockStore as BlockStoreTrait, QueryPermit},
+    blockchain::{BlockHash, ChainIdentifier},
+    components::store::{BlockStore as BlockStoreTrait, ChainStore as _, QueryPermit},
     prelude::{error, info, warn, BlockNumber, BlockPtr, Logger, ENV_VARS},
     slog::o,
 };
@@ -387,6 +387,25 @@ impl BlockStore {
         Ok(map)
     }
 
+    /// How far `block` is behind `head` on `chain` in time, or `None` if
+    /// the timestamp of either of them is not known
+    pub fn time_behind(
+        &self,
+        chain: &str,
+        head: &BlockHash,
+        block: &BlockHash,
+    ) -> Result<Option<Duration>, StoreError> {
+        let store = match self.store(chain) {
+            Some(store) => store,
+            None => return Ok(None),
+        };
+        let head = store.block_timestamp(head)?;
+        let block = store.block_timestamp(block)?;
+        Ok(head
+            .zip(block)
+            .map(|(head, block)| Duration::from_secs(head.saturating_sub(block))))
+    }
+
     pub fn chain_head_block(&self, chain: &str) -> Result<Option<BlockNumber>, StoreError> {
         let store = self
             .store(chain)
@@ -480,6 +499,26 @@ impl BlockStore {
         Ok(())
     }
 
+    /// Remove the entries in the call cache of every chain that are more
+    /// than `retention` blocks behind the head of the chain
+    pub async fn clear_stale_call_caches(&self, retention: BlockNumber) -> Result<(), StoreError> {
+        let stores: Vec<_> = self.stores.read().unwrap().values().cloned().collect();
+        for store in stores {
+            let head = match store.chain_head_block(&store.chain)? {
+                Some(head) => head,
+                None => continue,
+            };
+            let to = head - retention;
+            if to < 0 {
+                continue;
+            }
+            store.clear_call_cache(0, to).await?;
+            info!(&self.logger, "Removed stale call cache entries";
+                  "network" => &store.chain, "up_to_block" => to);
+        }
+        Ok(())
+    }
+
     fn truncate_block_caches(&self) -> Result<(), StoreError> {
         for store in self.stores.read().unwrap().values() {
             store.truncate_block_cache()?
@@ -186,6 +186,10 @@ pub struct Catalog {
     /// Whether the database supports `int4_minmax_multi_ops` etc.
     /// See the [Postgres docs](https://www.postgresql.org/docs/15/brin-builtin-opclasses.html)
     has_minmax_multi_ops: bool,
+
+    /// Whether the database can compress column values with `lz4`, which
+    /// requires Postgres 14 or later built with `lz4` support
+    has_lz4_compression: bool,
 }
 
 impl Catalog {
@@ -199,6 +203,7 @@ impl Catalog {
         let text_columns = get_text_columns(conn, &site.namespace)?;
         let use_poi = supports_proof_of_indexing(conn, &site.namespace)?;
         let has_minmax_multi_ops = has_minmax_multi_ops(conn)?;
+        let has_lz4_compression = has_lz4_compression(conn)?;
 
         Ok(Catalog {
             site,
@@ -207,6 +212,7 @@ impl Catalog {
             use_bytea_prefix,
             entities_with_causality_region: entities_with_causality_region.into_iter().collect(),
             has_minmax_multi_ops,
+            has_lz4_compression,
         })
     }
 
@@ -217,6 +223,7 @@ impl Catalog {
         entities_with_causality_region: BTreeSet<EntityType>,
     ) -> Result<Self, StoreError> {
         let has_minmax_multi_ops = has_minmax_multi_ops(conn)?;
+        let has_lz4_compression = has_lz4_compression(conn)?;
 
         Ok(Catalog {
             site,
@@ -228,6 +235,7 @@ impl Catalog {
             use_bytea_prefix: true,
             entities_with_causality_region,
             has_minmax_multi_ops,
+            has_lz4_compression,
         })
     }
 
@@ -245,6 +253,7 @@ impl Catalog {
             use_bytea_prefix: true,
             entities_with_causality_region,
             has_minmax_multi_ops: false,
+            has_lz4_compression: false,
         })
     }
 
@@ -269,6 +278,16 @@ impl Catalog {
             MINMAX_OPS
         }
     }
+
+    /// The compression method to use for `@compressed` columns, or `None`
+    /// if the database's default method should be used
+    pub fn compression_method(&self) -> Option<&str> {
+        if self.has_lz4_compression {
+            Some("lz4")
+        } else {
+            None
+        }
+    }
 }
 
 fn get_text_columns(
@@ -800,6 +819,27 @@ pub(crate) fn cancel_vacuum(conn: &PgConnection, namespace: &Namespace) -> Resul
     Ok(())
 }
 
+/// The process id of the database backend that serves `conn`
+pub(crate) fn backend_pid(conn: &PgConnection) -> Result<i32, StoreError> {
+    #[derive(Queryable, QueryableByName)]
+    struct Pid {
+        #[sql_type = "Integer"]
+        pid: i32,
+    }
+
+    let pid = sql_query("select pg_backend_pid() as pid").get_result::<Pid>(conn)?;
+    Ok(pid.pid)
+}
+
+/// Cancel the statement that the database backend with process id `pid`
+/// is running
+pub(crate) fn cancel_backend(conn: &PgConnection, pid: i32) -> Result<(), StoreError> {
+    sql_query("select pg_cancel_backend($1)")
+        .bind::<Integer, _>(pid)
+        .execute(conn)?;
+    Ok(())
+}
+
 pub(crate) fn default_stats_target(conn: &PgConnection) -> Result<i32, StoreError> {
     #[derive(Queryable, QueryableByName)]
     struct Target {
@@ -904,3 +944,20 @@ fn has_minmax_multi_ops(conn: &PgConnection) -> Result<bool, StoreError> {
 
     Ok(sql_query(QUERY).get_result::<Ops>(conn)?.has_ops)
 }
+
+/// Check whether the database for `conn` can compress values with `lz4`,
+/// which Postgres 14 introduced as an option at build time
+fn has_lz4_compression(conn: &PgConnection) -> Result<bool, StoreError> {
+    const QUERY: &str = "select count(*) > 0 as has_lz4 \
+                           from pg_settings \
+                          where name = 'default_toast_compression' \
+                            and 'lz4' = any(enumvals)";
+
+    #[derive(Queryable, QueryableByName)]
+    struct Compression {
+        #[sql_type = "Bool"]
+        has_lz4: bool,
+    }
+
+    Ok(sql_query(QUERY).get_result::<Compression>(conn)?.has_lz4)
+}
@@ -81,6 +81,10 @@ struct ChainHeadUpdate {
     pub head_block_number: u64,
 }
 
+/// Listens for chain head updates from the block ingestors and passes them
+/// on to block streams. A node has a single listener, and all block streams
+/// for a network share one watch channel, so that block streams never have
+/// to poll providers for the chain head themselves.
 pub struct ChainHeadUpdateListener {
     /// Update watchers keyed by network.
     watchers: Arc<TimedRwLock<BTreeMap<String, Watcher>>>,
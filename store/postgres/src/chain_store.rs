@@ -7,7 +7,7 @@ use graph::env::ENV_VARS;
 use graph::parking_lot::RwLock;
 use graph::prelude::MetricsRegistry;
 use graph::prometheus::{CounterVec, GaugeVec};
-use graph::slog::Logger;
+use graph::slog::{warn, Logger};
 use graph::stable_hash::crypto_stable_hash;
 use graph::util::herd_cache::HerdCache;
 
@@ -16,6 +16,7 @@ use std::{
     convert::{TryFrom, TryInto},
     iter::FromIterator,
     sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use graph::blockchain::{Block, BlockHash, ChainIdentifier};
@@ -82,7 +83,7 @@ mod data {
         types::{FromSql, ToSql},
     };
     use diesel::{
-        sql_types::{BigInt, Bytea, Integer, Jsonb},
+        sql_types::{BigInt, Bytea, Integer, Jsonb, Nullable},
         update,
     };
     use graph::blockchain::{Block, BlockHash};
@@ -625,6 +626,47 @@ mod data {
                 .collect())
         }
 
+        /// Return all blocks with numbers from `from` to `to`, including
+        /// blocks that are not on the main chain. Unlike `blocks`, the data
+        /// of each block is returned as it was stored, i.e., including
+        /// transaction receipts if the store has them
+        pub(super) fn blocks_in_range(
+            &self,
+            conn: &PgConnection,
+            chain: &str,
+            from: BlockNumber,
+            to: BlockNumber,
+        ) -> Result<Vec<JsonBlock>, StoreError> {
+            let x = match self {
+                Storage::Shared => {
+                    use public::ethereum_blocks as b;
+
+                    b::table
+                        .select((b::hash, b::number, b::parent_hash, b::data))
+                        .filter(b::network_name.eq(chain))
+                        .filter(b::number.ge(from as i64))
+                        .filter(b::number.le(to as i64))
+                        .load::<(BlockHash, i64, BlockHash, json::Value)>(conn)
+                }
+                Storage::Private(Schema { blocks, .. }) => blocks
+                    .table()
+                    .select((
+                        blocks.hash(),
+                        blocks.number(),
+                        blocks.parent_hash(),
+                        blocks.data(),
+                    ))
+                    .filter(blocks.number().ge(from as i64))
+                    .filter(blocks.number().le(to as i64))
+                    .load::<(BlockHash, i64, BlockHash, json::Value)>(conn),
+            }?;
+            Ok(x.into_iter()
+                .map(|(hash, nr, parent, data)| {
+                    JsonBlock::new(BlockPtr::new(hash, nr as i32), parent, Some(data))
+                })
+                .collect())
+        }
+
         pub(super) fn block_hashes_by_block_number(
             &self,
             conn: &PgConnection,
@@ -881,13 +923,16 @@ mod data {
             }
         }
 
-        pub(super) fn ancestor_block(
+        /// The hash of the block that is `offset` blocks before `block_ptr`,
+        /// or `None` if the chain store is missing one of the blocks in
+        /// between
+        pub(super) fn ancestor_hash(
             &self,
             conn: &PgConnection,
-            block_ptr: BlockPtr,
+            block_ptr: &BlockPtr,
             offset: BlockNumber,
-        ) -> Result<Option<(json::Value, BlockPtr)>, Error> {
-            let data_and_hash = match self {
+        ) -> Result<Option<BlockHash>, Error> {
+            match self {
                 Storage::Shared => {
                     const ANCESTOR_SQL: &str = "
         with recursive ancestors(block_hash, block_offset) as (
@@ -902,24 +947,13 @@ mod data {
           from ancestors a
          where a.block_offset = $2;";
 
-                    let hash = sql_query(ANCESTOR_SQL)
+                    sql_query(ANCESTOR_SQL)
                         .bind::<Text, _>(block_ptr.hash_hex())
                         .bind::<BigInt, _>(offset as i64)
                         .get_result::<BlockHashText>(conn)
-                        .optional()?;
-
-                    use public::ethereum_blocks as b;
-
-                    match hash {
-                        None => None,
-                        Some(hash) => Some((
-                            b::table
-                                .filter(b::hash.eq(&hash.hash))
-                                .select(b::data)
-                                .first::<json::Value>(conn)?,
-                            BlockHash::from_str(&hash.hash)?,
-                        )),
-                    }
+                        .optional()?
+                        .map(|hash| BlockHash::from_str(&hash.hash))
+                        .transpose()
                 }
                 Storage::Private(Schema { blocks, .. }) => {
                     // Same as ANCESTOR_SQL except for the table name
@@ -939,22 +973,115 @@ mod data {
                         blocks.qname
                     );
 
-                    let hash = sql_query(query)
+                    Ok(sql_query(query)
                         .bind::<Bytea, _>(block_ptr.hash_slice())
                         .bind::<BigInt, _>(offset as i64)
                         .get_result::<BlockHashBytea>(conn)
-                        .optional()?;
-                    match hash {
-                        None => None,
-                        Some(hash) => Some((
-                            blocks
-                                .table()
-                                .filter(blocks.hash().eq(&hash.hash))
-                                .select(blocks.data())
-                                .first::<json::Value>(conn)?,
-                            BlockHash::from(hash.hash),
-                        )),
-                    }
+                        .optional()?
+                        .map(|hash| BlockHash::from(hash.hash)))
+                }
+            }
+        }
+
+        /// How many blocks of the chain that ends in `old` are not part of
+        /// the chain that ends in `new`. Looks for the common ancestor of
+        /// the two at most `limit` blocks below the lower of them; returns
+        /// `None` if it can not be found since it is deeper than that or
+        /// blocks are missing from the chain store
+        pub(super) fn reorg_depth(
+            &self,
+            conn: &PgConnection,
+            old: &BlockPtr,
+            new: &BlockPtr,
+            limit: BlockNumber,
+        ) -> Result<Option<BlockNumber>, Error> {
+            #[derive(QueryableByName)]
+            struct Ancestor {
+                #[sql_type = "Nullable<BigInt>"]
+                number: Option<i64>,
+            }
+
+            // Walk down both chains to `bottom` and find the highest block
+            // that is on both of them
+            const REORG_DEPTH_SQL: &str = "
+        with recursive old_chain(block_hash, block_number) as (
+            values ($1::HASH, $2::int8)
+            union all
+            select b.parent_hash, c.block_number - 1
+              from old_chain c, BLOCKS b
+             where c.block_hash = b.hash
+               and c.block_number > $5
+        ), new_chain(block_hash, block_number) as (
+            values ($3::HASH, $4::int8)
+            union all
+            select b.parent_hash, c.block_number - 1
+              from new_chain c, BLOCKS b
+             where c.block_hash = b.hash
+               and c.block_number > $5
+        )
+        select max(o.block_number) as number
+          from old_chain o, new_chain n
+         where o.block_hash = n.block_hash
+           and o.block_number = n.block_number";
+
+            let top = old.number.min(new.number);
+            let bottom = (top - limit.min(top)) as i64;
+            let ancestor = match self {
+                Storage::Shared => {
+                    let query = REORG_DEPTH_SQL
+                        .replace("HASH", "text")
+                        .replace("BLOCKS", "ethereum_blocks");
+                    sql_query(query)
+                        .bind::<Text, _>(old.hash_hex())
+                        .bind::<BigInt, _>(old.number as i64)
+                        .bind::<Text, _>(new.hash_hex())
+                        .bind::<BigInt, _>(new.number as i64)
+                        .bind::<BigInt, _>(bottom)
+                        .get_result::<Ancestor>(conn)?
+                }
+                Storage::Private(Schema { blocks, .. }) => {
+                    let query = REORG_DEPTH_SQL
+                        .replace("HASH", "bytea")
+                        .replace("BLOCKS", blocks.qname.as_str());
+                    sql_query(query)
+                        .bind::<Bytea, _>(old.hash_slice())
+                        .bind::<BigInt, _>(old.number as i64)
+                        .bind::<Bytea, _>(new.hash_slice())
+                        .bind::<BigInt, _>(new.number as i64)
+                        .bind::<BigInt, _>(bottom)
+                        .get_result::<Ancestor>(conn)?
+                }
+            };
+            Ok(ancestor
+                .number
+                .map(|number| old.number - number as BlockNumber))
+        }
+
+        pub(super) fn ancestor_block(
+            &self,
+            conn: &PgConnection,
+            block_ptr: BlockPtr,
+            offset: BlockNumber,
+        ) -> Result<Option<(json::Value, BlockPtr)>, Error> {
+            let data_and_hash = match self.ancestor_hash(conn, &block_ptr, offset)? {
+                None => None,
+                Some(hash) => {
+                    let data = match self {
+                        Storage::Shared => {
+                            use public::ethereum_blocks as b;
+
+                            b::table
+                                .filter(b::hash.eq(hash.hash_hex()))
+                                .select(b::data)
+                                .first::<json::Value>(conn)?
+                        }
+                        Storage::Private(Schema { blocks, .. }) => blocks
+                            .table()
+                            .filter(blocks.hash().eq(hash.as_slice()))
+                            .select(blocks.data())
+                            .first::<json::Value>(conn)?,
+                    };
+                    Some((data, hash))
                 }
             };
 
@@ -1398,6 +1525,13 @@ pub struct ChainStoreMetrics {
     chain_head_cache_latest_block_num: Box<GaugeVec>,
     chain_head_cache_hits: Box<CounterVec>,
     chain_head_cache_misses: Box<CounterVec>,
+    blocks_ingested: Box<CounterVec>,
+    chain_head_updated_at: Box<GaugeVec>,
+    ingestion_latency: Box<GaugeVec>,
+    gaps: Box<CounterVec>,
+    gap_size: Box<GaugeVec>,
+    reorgs: Box<CounterVec>,
+    reorg_depth: Box<GaugeVec>,
 }
 
 impl ChainStoreMetrics {
@@ -1439,12 +1573,119 @@ impl ChainStoreMetrics {
             )
             .expect("Can't register the counter");
 
+        let blocks_ingested = registry
+            .new_counter_vec(
+                "chain_head_blocks_ingested",
+                "Number of blocks the block ingestor wrote to the chain store",
+                vec!["network".to_string()],
+            )
+            .expect("Can't register the counter");
+        let chain_head_updated_at = registry
+            .new_gauge_vec(
+                "chain_head_updated_at",
+                "Unix timestamp of the last time the chain head moved",
+                vec!["network".to_string()],
+            )
+            .expect("Can't register the gauge");
+        let ingestion_latency = registry
+            .new_gauge_vec(
+                "chain_head_ingestion_latency",
+                "Seconds between the timestamp of the chain head block and the time it became the chain head",
+                vec!["network".to_string()],
+            )
+            .expect("Can't register the gauge");
+        let gaps = registry
+            .new_counter_vec(
+                "chain_head_gaps",
+                "Number of times the chain head skipped over blocks when it moved",
+                vec!["network".to_string()],
+            )
+            .expect("Can't register the counter");
+        let gap_size = registry
+            .new_gauge_vec(
+                "chain_head_gap_size",
+                "Number of blocks the chain head skipped over the last time it moved without a reorg",
+                vec!["network".to_string()],
+            )
+            .expect("Can't register the gauge");
+        let reorgs = registry
+            .new_counter_vec(
+                "chain_head_reorgs",
+                "Number of reorgs of the chain head",
+                vec!["network".to_string()],
+            )
+            .expect("Can't register the counter");
+        let reorg_depth = registry
+            .new_gauge_vec(
+                "chain_head_reorg_depth",
+                "Number of blocks that the most recent reorg of the chain head replaced",
+                vec!["network".to_string()],
+            )
+            .expect("Can't register the gauge");
+
         Self {
             chain_head_cache_size,
             chain_head_cache_oldest_block_num,
             chain_head_cache_latest_block_num,
             chain_head_cache_hits,
             chain_head_cache_misses,
+            blocks_ingested,
+            chain_head_updated_at,
+            ingestion_latency,
+            gaps,
+            gap_size,
+            reorgs,
+            reorg_depth,
+        }
+    }
+
+    pub fn record_block_ingested(&self, network: &str) {
+        self.blocks_ingested.with_label_values(&[network]).inc();
+    }
+
+    /// Record that the chain head moved from `old` to `new`. `timestamp`
+    /// is the timestamp of the `new` block, and `reorg_depth` the number
+    /// of blocks of the old chain that are not on the new one, if known
+    pub fn record_chain_head_update(
+        &self,
+        network: &str,
+        old: Option<&BlockPtr>,
+        new: &BlockPtr,
+        timestamp: Option<u64>,
+        reorg_depth: Option<BlockNumber>,
+    ) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.as_secs_f64())
+            .unwrap_or_default();
+        self.chain_head_updated_at
+            .with_label_values(&[network])
+            .set(now);
+        if let Some(timestamp) = timestamp {
+            self.ingestion_latency
+                .with_label_values(&[network])
+                .set((now - timestamp as f64).max(0.0));
+        }
+
+        let old = match old {
+            Some(old) => old,
+            None => return,
+        };
+        match reorg_depth {
+            Some(0) => {
+                let gap = (new.number - old.number - 1).max(0);
+                if gap > 0 {
+                    self.gaps.with_label_values(&[network]).inc();
+                }
+                self.gap_size.with_label_values(&[network]).set(gap as f64);
+            }
+            Some(depth) => {
+                self.reorgs.with_label_values(&[network]).inc();
+                self.reorg_depth
+                    .with_label_values(&[network])
+                    .set(depth as f64);
+            }
+            None => { /* we don't know how the chains relate */ }
         }
     }
 
@@ -1508,6 +1749,7 @@ pub struct ChainStore {
     // conservative approach is acceptable.
     recent_blocks_cache: RecentBlocksCache,
     lookup_herd: HerdCache<BlocksLookupResult>,
+    metrics: Arc<ChainStoreMetrics>,
 }
 
 impl ChainStore {
@@ -1523,7 +1765,7 @@ impl ChainStore {
         metrics: Arc<ChainStoreMetrics>,
     ) -> Self {
         let recent_blocks_cache =
-            RecentBlocksCache::new(recent_blocks_cache_capacity, chain.clone(), metrics);
+            RecentBlocksCache::new(recent_blocks_cache_capacity, chain.clone(), metrics.clone());
         let lookup_herd = HerdCache::new(format!("chain_{}_herd_cache", chain));
         ChainStore {
             logger,
@@ -1536,6 +1778,7 @@ impl ChainStore {
             chain_identifier: net_identifier.clone(),
             recent_blocks_cache,
             lookup_herd,
+            metrics,
         }
     }
 
@@ -1543,6 +1786,55 @@ impl ChainStore {
         matches!(self.status, ChainStatus::Ingestible)
     }
 
+    fn head_ptr(&self, conn: &PgConnection) -> Result<Option<BlockPtr>, Error> {
+        use public::ethereum_networks as n;
+
+        match n::table
+            .filter(n::name.eq(&self.chain))
+            .select((n::head_block_hash, n::head_block_number))
+            .first::<(Option<String>, Option<i64>)>(conn)
+            .optional()?
+        {
+            Some((Some(hash), Some(number))) => {
+                Ok(Some(BlockPtr::try_from((hash.as_str(), number))?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Update the ingestion metrics after the chain head moved from `old`
+    /// to `new`. Not being able to do that does not fail the update
+    fn record_chain_head_update(&self, conn: &PgConnection, old: Option<BlockPtr>, new: &BlockPtr) {
+        if old.as_ref() == Some(new) {
+            // The head did not move
+            return;
+        }
+        let timestamp = match self.storage.block_number(conn, &new.hash) {
+            Ok(number) => number.and_then(|(_, timestamp)| timestamp),
+            Err(e) => {
+                warn!(self.logger, "Failed to get the timestamp of the chain head"; "error" => e.to_string());
+                None
+            }
+        };
+        let reorg_depth = match &old {
+            Some(old) => self
+                .storage
+                .reorg_depth(conn, old, new, ENV_VARS.reorg_threshold)
+                .unwrap_or_else(|e| {
+                    warn!(self.logger, "Failed to determine the depth of a reorg"; "error" => e.to_string());
+                    None
+                }),
+            None => None,
+        };
+        self.metrics.record_chain_head_update(
+            &self.chain,
+            old.as_ref(),
+            new,
+            timestamp,
+            reorg_depth,
+        );
+    }
+
     fn get_conn(&self) -> Result<PooledConnection<ConnectionManager<PgConnection>>, Error> {
         self.pool.get().map_err(Error::from)
     }
@@ -1603,6 +1895,16 @@ impl ChainStore {
         Ok(HashMap::from_iter(pointers))
     }
 
+    /// The timestamp of the block with the given hash, if that block is
+    /// in the store and its timestamp is known
+    pub(crate) fn block_timestamp(&self, hash: &BlockHash) -> Result<Option<u64>, StoreError> {
+        let conn = self.get_conn()?;
+        Ok(self
+            .storage
+            .block_number(&conn, hash)?
+            .and_then(|(_, timestamp)| timestamp))
+    }
+
     pub fn chain_head_block(&self, chain: &str) -> Result<Option<BlockNumber>, StoreError> {
         use public::ethereum_networks as n;
 
@@ -1625,6 +1927,19 @@ impl ChainStore {
         )
     }
 
+    /// How many blocks of the chain that ends in `old` are not part of
+    /// the chain that ends in `new`, or `None` if that is not known within
+    /// the reorg threshold
+    pub fn reorg_depth(
+        &self,
+        old: &BlockPtr,
+        new: &BlockPtr,
+    ) -> Result<Option<BlockNumber>, Error> {
+        let conn = self.get_conn()?;
+        self.storage
+            .reorg_depth(&conn, old, new, ENV_VARS.reorg_threshold)
+    }
+
     /// Store the given chain as the blocks for the `network` set the
     /// network's genesis block to `genesis_hash`, and head block to
     /// `null`
@@ -1721,7 +2036,9 @@ impl ChainStoreTrait for ChainStore {
             })
         })
         .await
-        .map_err(Error::from)
+        .map_err(Error::from)?;
+        self.metrics.record_block_ingested(&self.chain);
+        Ok(())
     }
 
     fn upsert_light_blocks(&self, blocks: &[&dyn Block]) -> Result<(), Error> {
@@ -1772,18 +2089,22 @@ impl ChainStoreTrait for ChainStore {
                     let hash = ptr.hash_hex();
                     let number = ptr.number as i64;
 
-                    conn.transaction(
-                        || -> Result<(Option<H256>, Option<(String, i64)>), StoreError> {
-                            update(n::table.filter(n::name.eq(&chain_store.chain)))
-                                .set((
-                                    n::head_block_hash.eq(&hash),
-                                    n::head_block_number.eq(number),
-                                ))
-                                .execute(conn)?;
-                            Ok((None, Some((hash, number))))
-                        },
-                    )
-                    .map_err(CancelableError::from)
+                    let old = chain_store.head_ptr(conn).map_err(CancelableError::from)?;
+                    let updated = conn
+                        .transaction(
+                            || -> Result<(Option<H256>, Option<(String, i64)>), StoreError> {
+                                update(n::table.filter(n::name.eq(&chain_store.chain)))
+                                    .set((
+                                        n::head_block_hash.eq(&hash),
+                                        n::head_block_number.eq(number),
+                                    ))
+                                    .execute(conn)?;
+                                Ok((None, Some((hash, number))))
+                            },
+                        )
+                        .map_err(CancelableError::from)?;
+                    chain_store.record_chain_head_update(conn, old, ptr);
+                    Ok(updated)
                 })
                 .await?
         };
@@ -1862,6 +2183,7 @@ impl ChainStoreTrait for ChainStore {
         self.chain_head_update_sender.send(&hash, number)?;
 
         pool.with_conn(move |conn, _| {
+            let old = self.head_ptr(conn).map_err(CancelableError::from)?;
             conn.transaction(|| -> Result<(), StoreError> {
                 storage
                     .upsert_block(conn, &network, block.as_ref(), true)
@@ -1877,7 +2199,10 @@ impl ChainStoreTrait for ChainStore {
 
                 Ok(())
             })
-            .map_err(CancelableError::from)
+            .map_err(CancelableError::from)?;
+            self.metrics.record_block_ingested(&self.chain);
+            self.record_chain_head_update(conn, old, &ptr);
+            Ok(())
         })
         .await?;
 
@@ -1979,6 +2304,63 @@ impl ChainStoreTrait for ChainStore {
             .map(|b| b.0))
     }
 
+    async fn cached_blocks_in_range(
+        self: Arc<Self>,
+        from: BlockNumber,
+        to: BlockNumber,
+    ) -> Result<Option<Vec<json::Value>>, Error> {
+        let head = match self.cheap_clone().chain_head_ptr().await? {
+            Some(head) if head.number >= to => head,
+            _ => return Ok(None),
+        };
+
+        // Find the block `to` on the chain that ends in the chain head;
+        // other blocks with that number that are still cached are on
+        // chains that were reorged away
+        let chain_store = self.cheap_clone();
+        let (top, blocks) = self
+            .pool
+            .with_conn(move |conn, _| {
+                let top = chain_store
+                    .storage
+                    .ancestor_hash(conn, &head, head.number - to)
+                    .map_err(StoreError::from)?;
+                let blocks =
+                    chain_store
+                        .storage
+                        .blocks_in_range(conn, &chain_store.chain, from, to)?;
+                Ok((top, blocks))
+            })
+            .await?;
+
+        // Walk from the block `to` back to the block `from` along parent
+        // hashes so that we only return blocks on the main chain
+        let by_hash: HashMap<_, _> = blocks
+            .iter()
+            .map(|block| (&block.ptr.hash, block))
+            .collect();
+        let mut block = match top.as_ref().and_then(|top| by_hash.get(top)) {
+            Some(block) => block,
+            None => return Ok(None),
+        };
+        let mut chain = vec![];
+        loop {
+            match &block.data {
+                Some(data) => chain.push(data.clone()),
+                None => return Ok(None),
+            }
+            if block.ptr.number == from {
+                break;
+            }
+            block = match by_hash.get(&block.parent_hash) {
+                Some(parent) => parent,
+                None => return Ok(None),
+            };
+        }
+        chain.reverse();
+        Ok(Some(chain))
+    }
+
     fn cleanup_cached_blocks(
         &self,
         ancestor_count: BlockNumber,
@@ -2322,6 +2704,23 @@ impl EthereumCallCache for ChainStore {
         conn.transaction::<_, Error, _>(|| self.storage.get_calls_in_block(conn, block))
     }
 
+    fn get_cached_call(
+        &self,
+        contract_address: ethabi::Address,
+        encoded_call: &[u8],
+        block: BlockPtr,
+    ) -> Result<Option<CachedEthereumCall>, Error> {
+        let id = contract_call_id(&contract_address, encoded_call, &block);
+        let conn = &*self.get_conn()?;
+        let call = self.storage.get_call_and_access(conn, id.as_ref())?;
+        Ok(call.map(|(return_value, _)| CachedEthereumCall {
+            blake3_id: id.to_vec(),
+            block_ptr: block,
+            contract_address,
+            return_value,
+        }))
+    }
+
     fn set_call(
         &self,
         contract_address: ethabi::Address,
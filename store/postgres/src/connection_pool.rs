@@ -217,6 +217,7 @@ impl ForeignServer {
             "subgraph_version",
             "subgraph_deployment",
             "subgraph_manifest",
+            "deployment_view",
         ] {
             let create_stmt =
                 catalog::create_foreign_table(conn, "subgraphs", table_name, &nsp, &self.name)?;
@@ -758,6 +758,25 @@ impl Connection {
         Ok(())
     }
 
+    /// Copy the join tables of all tables. Since join tables are narrow
+    /// and we start from scratch each time, this happens in one go
+    fn copy_join_tables(&self, state: &CopyState) -> Result<(), StoreError> {
+        for dst in state.dst.tables.values() {
+            let src = match state.src.table(&dst.name) {
+                Some(src) => src,
+                None => continue,
+            };
+            for djoin in &dst.join_tables {
+                if let Some(sjoin) = src.join_table(djoin.column.as_str()) {
+                    self.transaction(|conn| {
+                        djoin.copy_from(conn, sjoin, state.target_block.number)
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn copy_data_internal(&self) -> Result<Status, StoreError> {
         let mut state = self.transaction(|conn| {
             CopyState::new(
@@ -807,6 +826,7 @@ impl Connection {
         }
 
         self.copy_private_data_sources(&state)?;
+        self.copy_join_tables(&state)?;
 
         self.transaction(|conn| state.finished(conn))?;
         progress.finished();
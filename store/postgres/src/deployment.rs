@@ -26,8 +26,8 @@ use graph::{
 };
 use graph::{
     prelude::{
-        anyhow, bigdecimal::ToPrimitive, hex, web3::types::H256, BigDecimal, BlockNumber, BlockPtr,
-        DeploymentHash, DeploymentState, StoreError,
+        anyhow, bigdecimal::ToPrimitive, hex, serde_json, web3::types::H256, BigDecimal,
+        BlockNumber, BlockPtr, DeploymentHash, DeploymentState, StoreError,
     },
     schema::InputSchema,
 };
@@ -160,6 +160,8 @@ table! {
         handler -> Nullable<Text>,
         deterministic -> Bool,
         block_range -> Range<Integer>,
+        data_source -> Nullable<Text>,
+        transaction_hash -> Nullable<Text>,
     }
 }
 
@@ -185,6 +187,10 @@ table! {
         // How many blocks of history to keep, defaults to `i32::max` for
         // unlimited history
         history_blocks -> Integer,
+        version_label -> Nullable<Text>,
+        // The files that were fetched when the manifest was resolved, as
+        // a JSON array of `{ link, size }` objects
+        files -> Nullable<Jsonb>,
     }
 }
 
@@ -308,6 +314,20 @@ pub fn schema(conn: &PgConnection, site: &Site) -> Result<(InputSchema, bool), S
         .map(|schema| (schema, use_bytea_prefix))
 }
 
+/// The GraphQL schema and the raw manifest of the deployment `site` as
+/// they were stored when the deployment was created
+pub fn schema_and_raw_yaml(
+    conn: &PgConnection,
+    site: &Site,
+) -> Result<(String, Option<String>), StoreError> {
+    use subgraph_manifest as sm;
+    sm::table
+        .select((sm::schema, sm::raw_yaml))
+        .filter(sm::id.eq(site.id))
+        .first::<(String, Option<String>)>(conn)
+        .map_err(StoreError::from)
+}
+
 pub struct ManifestInfo {
     pub input_schema: InputSchema,
     pub description: Option<String>,
@@ -614,6 +634,19 @@ pub fn initialize_block_ptr(conn: &PgConnection, site: &Site) -> Result<(), Stor
     }
 }
 
+/// Return the number of the block before the first block the deployment
+/// processes, or `None` if it starts at the genesis block
+pub fn start_block_number(
+    conn: &PgConnection,
+    site: &Site,
+) -> Result<Option<BlockNumber>, StoreError> {
+    m::table
+        .filter(m::id.eq(site.id))
+        .select(m::start_block_number)
+        .first::<Option<BlockNumber>>(conn)
+        .map_err(StoreError::from)
+}
+
 fn convert_to_u32(number: Option<i32>, field: &str, subgraph: &str) -> Result<u32, StoreError> {
     number
         .ok_or_else(|| constraint_violation!("missing {} for subgraph `{}`", field, subgraph))
@@ -743,6 +776,8 @@ fn insert_subgraph_error(conn: &PgConnection, error: &SubgraphError) -> anyhow::
         message,
         handler,
         block_ptr,
+        data_source,
+        transaction_hash,
         deterministic,
     } = error;
 
@@ -760,6 +795,8 @@ fn insert_subgraph_error(conn: &PgConnection, error: &SubgraphError) -> anyhow::
             e::subgraph_id.eq(subgraph_id.as_str()),
             e::message.eq(message),
             e::handler.eq(handler),
+            e::data_source.eq(data_source),
+            e::transaction_hash.eq(transaction_hash),
             e::deterministic.eq(deterministic),
             e::block_hash.eq(block_ptr.as_ref().map(|ptr| ptr.hash_slice())),
             e::block_range.eq((Bound::Included(block_num), Bound::Unbounded)),
@@ -1006,10 +1043,12 @@ pub(crate) fn copy_errors(
     let query = format!(
         "\
       insert into subgraphs.subgraph_error(id,
-             subgraph_id, message, block_hash, handler, deterministic, block_range)
+             subgraph_id, message, block_hash, handler, deterministic, block_range,
+             data_source, transaction_hash)
       select md5($2 || e.message || coalesce(e.block_hash, 'nohash') || coalesce(e.handler, 'nohandler') || e.deterministic) as id,
              $2 as subgraph_id, e.message, e.block_hash,
-             e.handler, e.deterministic, e.block_range
+             e.handler, e.deterministic, e.block_range,
+             e.data_source, e.transaction_hash
         from {src_nsp}.subgraph_error e
        where e.subgraph_id = $1
          and lower(e.block_range) <= $3",
@@ -1035,9 +1074,13 @@ pub fn drop_schema(
     conn: &diesel::pg::PgConnection,
     namespace: &crate::primary::Namespace,
 ) -> Result<(), StoreError> {
+    // The schema with the operator-defined views of the deployment goes
+    // with it
     let query = format!(
-        "set local lock_timeout=2000; drop schema if exists {} cascade",
-        namespace
+        "set local lock_timeout=2000; \
+         drop schema if exists {namespace}_views cascade; \
+         drop schema if exists {namespace} cascade",
+        namespace = namespace
     );
     Ok(conn.batch_execute(&query)?)
 }
@@ -1078,17 +1121,20 @@ pub fn create_deployment(
                 spec_version,
                 description,
                 repository,
+                version_label,
                 features,
                 schema,
                 raw_yaml,
                 entities_with_causality_region,
                 history_blocks,
+                files,
             },
         start_block,
         graft_base,
         graft_block,
         debug_fork,
         history_blocks: history_blocks_override,
+        placement: _,
     } = deployment;
     let earliest_block_number = start_block.as_ref().map(|ptr| ptr.number).unwrap_or(0);
     let entities_with_causality_region = Vec::from_iter(
@@ -1115,6 +1161,8 @@ pub fn create_deployment(
         d::debug_fork.eq(debug_fork.as_ref().map(|s| s.as_str())),
     );
 
+    let files = serde_json::to_value(files)?;
+
     let graph_node_version_id = GraphNodeVersion::create_or_get(conn)?;
 
     let manifest_values = (
@@ -1122,6 +1170,7 @@ pub fn create_deployment(
         m::spec_version.eq(spec_version),
         m::description.eq(description),
         m::repository.eq(repository),
+        m::version_label.eq(version_label),
         m::features.eq(features),
         m::schema.eq(schema),
         m::graph_node_version_id.eq(graph_node_version_id),
@@ -1133,6 +1182,7 @@ pub fn create_deployment(
         m::raw_yaml.eq(raw_yaml),
         m::entities_with_causality_region.eq(entities_with_causality_region),
         m::history_blocks.eq(history_blocks_override.unwrap_or(history_blocks)),
+        m::files.eq(files),
     );
 
     if exists && replace {
@@ -1173,6 +1223,22 @@ pub fn update_entity_count(conn: &PgConnection, site: &Site, count: i32) -> Resu
     Ok(())
 }
 
+/// The number of entities of the deployment
+pub fn entity_count(conn: &PgConnection, site: &Site) -> Result<u64, StoreError> {
+    use subgraph_deployment as d;
+
+    let count = d::table
+        .filter(d::id.eq(site.id))
+        .select(d::entity_count)
+        .get_result::<BigDecimal>(conn)?;
+    count.to_u64().ok_or_else(|| {
+        constraint_violation!(
+            "the entity count for {} is not representable as a u64",
+            site.deployment
+        )
+    })
+}
+
 /// Set the deployment's entity count to whatever `full_count_query` produces
 pub fn set_entity_count(
     conn: &PgConnection,
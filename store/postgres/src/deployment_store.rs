@@ -11,7 +11,7 @@ use graph::components::store::{
     QueryPermit, StoredDynamicDataSource, VersionStats,
 };
 use graph::components::versions::VERSIONS;
-use graph::data::query::Trace;
+use graph::data::query::{CancelBackend, Trace};
 use graph::data::store::{Id, IdList};
 use graph::data::subgraph::{status, SPEC_VERSION_0_0_6};
 use graph::data_source::CausalityRegion;
@@ -24,11 +24,13 @@ use graph::semver::Version;
 use graph::tokio::task::JoinHandle;
 use itertools::Itertools;
 use lru_time_cache::LruCache;
-use rand::{seq::SliceRandom, thread_rng};
-use std::collections::{BTreeMap, HashMap};
+use rand::{seq::SliceRandom, thread_rng, Rng};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::Into;
+use std::io::Write;
 use std::ops::Bound;
 use std::ops::Deref;
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::{atomic::AtomicUsize, Arc, Mutex};
 use std::time::{Duration, Instant};
@@ -40,7 +42,7 @@ use graph::data::subgraph::schema::{DeploymentCreate, SubgraphError};
 use graph::prelude::{
     anyhow, debug, info, o, warn, web3, AttributeNames, BlockNumber, BlockPtr, CheapClone,
     DeploymentHash, DeploymentState, Entity, EntityQuery, Error, Logger, QueryExecutionError,
-    StopwatchMetrics, StoreError, StoreEvent, UnfailOutcome, Value, ENV_VARS,
+    StopwatchMetrics, StoreError, StoreEvent, UnfailOutcome, Value, BLOCK_NUMBER_MAX, ENV_VARS,
 };
 use graph::schema::{ApiSchema, EntityKey, EntityType, InputSchema};
 use web3::types::Address;
@@ -53,7 +55,8 @@ use crate::primary::DeploymentId;
 use crate::relational::index::{CreateIndex, Method};
 use crate::relational::{Layout, LayoutCache, SqlName, Table};
 use crate::relational_queries::FromEntityData;
-use crate::{advisory_lock, catalog, retry};
+use crate::snapshot::{self, Snapshot};
+use crate::{advisory_lock, catalog, retry, views, ChainStore};
 use crate::{connection_pool::ConnectionPool, detail};
 use crate::{dynds, primary::Site};
 
@@ -90,6 +93,15 @@ pub(crate) struct SubgraphInfo {
 
 type PruneHandle = JoinHandle<Result<(), StoreError>>;
 
+/// The writer locks this node holds for deployments in this shard. All of
+/// them are held on `conn`, which is taken out of the pool when the first
+/// lock is taken and returned to it when the last one is released
+#[derive(Default)]
+struct WriterLocks {
+    conn: Option<PooledConnection<ConnectionManager<PgConnection>>>,
+    locked: HashSet<DeploymentId>,
+}
+
 pub struct StoreInner {
     logger: Logger,
 
@@ -104,6 +116,9 @@ pub struct StoreInner {
     /// pick next
     conn_round_robin_counter: AtomicUsize,
 
+    /// When we last explained a slow query, see `explain_slow_query`
+    last_explain: Mutex<Option<Instant>>,
+
     /// A cache of commonly needed data about a subgraph.
     subgraph_cache: Mutex<LruCache<DeploymentHash, SubgraphInfo>>,
 
@@ -113,6 +128,8 @@ pub struct StoreInner {
     pub(crate) layout_cache: LayoutCache,
 
     prune_handles: Mutex<HashMap<DeploymentId, PruneHandle>>,
+
+    writer_locks: Mutex<WriterLocks>,
 }
 
 /// Storage of the data for individual deployments. Each `DeploymentStore`
@@ -166,9 +183,11 @@ impl DeploymentStore {
             read_only_pools,
             replica_order,
             conn_round_robin_counter: AtomicUsize::new(0),
+            last_explain: Mutex::new(None),
             subgraph_cache: Mutex::new(LruCache::with_capacity(100)),
             layout_cache: LayoutCache::new(ENV_VARS.store.query_stats_refresh_interval),
             prune_handles: Mutex::new(HashMap::new()),
+            writer_locks: Mutex::new(WriterLocks::default()),
         };
 
         DeploymentStore(Arc::new(store))
@@ -271,6 +290,65 @@ impl DeploymentStore {
         layout.query(&logger, conn, query)
     }
 
+    /// Explain `query`, which took `elapsed` to run on `replica`, in the
+    /// background and log its query plan if it took longer than
+    /// `GRAPH_STORE_EXPLAIN_SLOW_QUERIES_MS`. Explaining a query runs it
+    /// again, and we therefore only explain a sample of slow queries, and
+    /// at most one every `GRAPH_STORE_EXPLAIN_INTERVAL`
+    pub(crate) fn explain_slow_query(
+        &self,
+        replica: ReplicaId,
+        site: Arc<Site>,
+        query: EntityQuery,
+        elapsed: Duration,
+    ) {
+        let threshold = match ENV_VARS.store.explain_slow_queries {
+            Some(threshold) => threshold,
+            None => return,
+        };
+        if elapsed < threshold || !thread_rng().gen_bool(ENV_VARS.store.explain_sample_rate) {
+            return;
+        }
+        {
+            let mut last_explain = self.last_explain.lock().unwrap();
+            if let Some(last) = *last_explain {
+                if last.elapsed() < ENV_VARS.store.explain_interval {
+                    return;
+                }
+            }
+            *last_explain = Some(Instant::now());
+        }
+
+        let store = self.cheap_clone();
+        let logger = query
+            .logger
+            .cheap_clone()
+            .unwrap_or_else(|| self.logger.cheap_clone());
+        graph::spawn_blocking_allow_panic(move || {
+            let plan = store
+                .get_replica_conn(replica)
+                .map_err(|e| QueryExecutionError::StoreError(e.into()))
+                .and_then(|conn| {
+                    let layout = store.layout(&conn, site)?;
+                    layout.explain(&conn, query)
+                });
+            match plan {
+                Ok(plan) => info!(
+                    logger,
+                    "Query plan (SQL)";
+                    "time_ms" => elapsed.as_millis(),
+                    "plan" => plan.join("\n")
+                ),
+                Err(e) => warn!(
+                    logger,
+                    "Failed to explain slow query";
+                    "time_ms" => elapsed.as_millis(),
+                    "error" => e.to_string()
+                ),
+            }
+        });
+    }
+
     fn check_interface_entity_uniqueness(
         &self,
         conn: &PgConnection,
@@ -390,6 +468,105 @@ impl DeploymentStore {
         self.pool.with_conn(f).await
     }
 
+    /// Take the advisory lock that makes this node the only writer of the
+    /// deployment `site` so that misconfigured clusters where several
+    /// nodes index the same deployment fail instead of corrupting data.
+    /// Taking the lock again while this node holds it is a no-op. Fails
+    /// with `StoreError::DeploymentLocked` if another node holds the lock
+    pub(crate) fn lock_writer(&self, site: &Site) -> Result<(), StoreError> {
+        let mut locks = self.writer_locks.lock().unwrap();
+        if locks.locked.contains(&site.id) {
+            return Ok(());
+        }
+        let conn = match locks.conn.take() {
+            Some(conn) => conn,
+            None => self.get_conn()?,
+        };
+        match advisory_lock::try_lock_writer(&conn, site) {
+            Ok(true) => {
+                locks.conn = Some(conn);
+                locks.locked.insert(site.id);
+                Ok(())
+            }
+            Ok(false) => {
+                if !locks.locked.is_empty() {
+                    locks.conn = Some(conn);
+                }
+                Err(StoreError::DeploymentLocked(site.deployment.clone()))
+            }
+            Err(e) => {
+                // Something is wrong with the connection. Make sure it
+                // does not go back to the pool with locks still held on
+                // it; if it is broken, Postgres has released them already
+                advisory_lock::unlock_all_writers(&conn).ok();
+                if !locks.locked.is_empty() {
+                    warn!(self.logger, "Lost the writer locks for deployments";
+                        "deployments" => locks.locked.iter().join(", "),
+                        "error" => e.to_string());
+                }
+                locks.locked.clear();
+                Err(e)
+            }
+        }
+    }
+
+    /// Check that this node still holds the lock taken with `lock_writer`.
+    /// The lock lives on a connection that is idle between writes; if that
+    /// connection broke, Postgres released the lock and another node might
+    /// have started writing to the deployment. Fails with
+    /// `StoreError::WriterLockLost` in that case so that the writes of the
+    /// two nodes do not get interleaved
+    pub(crate) fn check_writer_lock(&self, site: &Site) -> Result<(), StoreError> {
+        let mut locks = self.writer_locks.lock().unwrap();
+        if !locks.locked.contains(&site.id) {
+            return Err(StoreError::WriterLockLost(site.deployment.clone()));
+        }
+        let error = match &locks.conn {
+            Some(conn) => match advisory_lock::holds_writer(conn, site) {
+                Ok(true) => return Ok(()),
+                Ok(false) => "the lock is not held anymore".to_string(),
+                Err(e) => e.to_string(),
+            },
+            None => "there is no connection for the lock".to_string(),
+        };
+        // If one lock is gone, the connection is most likely broken and
+        // all the others are gone, too
+        if let Some(conn) = locks.conn.take() {
+            advisory_lock::unlock_all_writers(&conn).ok();
+        }
+        warn!(self.logger, "Lost the writer locks for deployments";
+            "deployments" => locks.locked.iter().join(", "),
+            "error" => error);
+        locks.locked.clear();
+        Err(StoreError::WriterLockLost(site.deployment.clone()))
+    }
+
+    /// Release the lock taken with `lock_writer`
+    pub(crate) fn unlock_writer(&self, site: &Site) -> Result<(), StoreError> {
+        let mut locks = self.writer_locks.lock().unwrap();
+        if !locks.locked.remove(&site.id) {
+            return Ok(());
+        }
+        let res = match &locks.conn {
+            Some(conn) => advisory_lock::unlock_writer(conn, site),
+            None => Ok(()),
+        };
+        if res.is_err() || locks.locked.is_empty() {
+            if let Some(conn) = locks.conn.take() {
+                advisory_lock::unlock_all_writers(&conn).ok();
+            }
+            locks.locked.clear();
+        }
+        res
+    }
+
+    /// The chain head pointers of the chains whose blocks are stored in
+    /// this store's shard
+    pub(crate) fn chain_head_pointers(&self) -> Result<HashMap<String, BlockPtr>, StoreError> {
+        let conn = self.get_conn()?;
+        ChainStore::chain_head_pointers(&conn)
+    }
+
     /// Deprecated. Use `with_conn` instead.
     fn get_conn(&self) -> Result<PooledConnection<ConnectionManager<PgConnection>>, StoreError> {
         self.pool.get()
@@ -414,6 +591,21 @@ impl DeploymentStore {
         Ok(conn)
     }
 
+    /// A function that cancels the statement a backend of `replica` is
+    /// running, for cancelling running GraphQL queries
+    pub(crate) fn backend_canceller(&self, replica: ReplicaId) -> CancelBackend {
+        let store = self.cheap_clone();
+        Arc::new(move |pid| {
+            let res = store
+                .get_replica_conn(replica)
+                .map_err(StoreError::from)
+                .and_then(|conn| crate::catalog::cancel_backend(&conn, pid));
+            if let Err(e) = res {
+                warn!(store.logger, "Failed to cancel query"; "pid" => pid, "error" => e.to_string());
+            }
+        })
+    }
+
     pub(crate) async fn query_permit(&self, replica: ReplicaId) -> Result<QueryPermit, StoreError> {
         let pool = match replica {
             ReplicaId::Main => &self.pool,
@@ -624,6 +816,38 @@ impl DeploymentStore {
         Ok(())
     }
 
+    /// Warm up the database caches for the deployment `site` on the main
+    /// database and all replicas by running queries that read
+    /// `ENV_VARS.store.warmup_rows` rows from each table. Warming up is
+    /// best effort: errors are logged but not returned
+    pub(crate) fn warm_up(&self, site: Arc<Site>) {
+        let rows = ENV_VARS.store.warmup_rows;
+        if rows == 0 {
+            return;
+        }
+
+        let logger = self
+            .logger
+            .new(o!("deployment" => site.deployment.to_string()));
+        let start = Instant::now();
+        let mut read = 0;
+        for pool in std::iter::once(&self.pool).chain(self.read_only_pools.iter()) {
+            let res = pool.get().and_then(|conn| {
+                let layout = self.layout(&conn, site.cheap_clone())?;
+                for table in layout.tables.values() {
+                    read += table.warm_up(&conn, rows)?;
+                }
+                Ok(())
+            });
+            if let Err(e) = res {
+                warn!(logger, "Failed to warm up database caches";
+                      "shard" => pool.shard.as_str(), "error" => e.to_string());
+            }
+        }
+        info!(logger, "Warmed up database caches"; "rows" => read,
+              "time_ms" => start.elapsed().as_millis());
+    }
+
     pub(crate) fn stats_targets(
         &self,
         site: Arc<Site>,
@@ -794,6 +1018,29 @@ impl DeploymentStore {
         .await
     }
 
+    pub(crate) async fn create_view(
+        &self,
+        site: Arc<Site>,
+        name: &str,
+        query: &str,
+    ) -> Result<(), StoreError> {
+        let name = name.to_string();
+        let query = query.to_string();
+        self.with_conn(move |conn, _| views::add(conn, &site, &name, &query).map_err(Into::into))
+            .await
+    }
+
+    pub(crate) async fn drop_view(&self, site: Arc<Site>, name: &str) -> Result<bool, StoreError> {
+        let name = name.to_string();
+        self.with_conn(move |conn, _| views::remove(conn, &site, &name).map_err(Into::into))
+            .await
+    }
+
+    pub(crate) async fn views(&self, site: Arc<Site>) -> Result<Vec<views::View>, StoreError> {
+        self.with_conn(move |conn, _| views::list(conn, &site).map_err(Into::into))
+            .await
+    }
+
     pub(crate) fn set_history_blocks(
         &self,
         site: &Site,
@@ -1058,6 +1305,17 @@ impl DeploymentStore {
         layout.find_derived(&conn, derived_query, block, excluded_keys)
     }
 
+    pub(crate) fn max_timestamp(
+        &self,
+        site: Arc<Site>,
+        entity_type: &EntityType,
+        block: BlockNumber,
+    ) -> Result<Option<i64>, StoreError> {
+        let conn = self.get_conn()?;
+        let layout = self.layout(&conn, site)?;
+        layout.max_timestamp(&conn, entity_type, block)
+    }
+
     pub(crate) fn get_changes(
         &self,
         site: Arc<Site>,
@@ -1089,7 +1347,7 @@ impl DeploymentStore {
         batch: &Batch,
         stopwatch: &StopwatchMetrics,
         manifest_idx_and_name: &[(u32, String)],
-    ) -> Result<StoreEvent, StoreError> {
+    ) -> Result<(StoreEvent, i32), StoreError> {
         let conn = {
             let _section = stopwatch.start_section("transact_blocks_get_conn");
             self.get_conn()?
@@ -1101,7 +1359,7 @@ impl DeploymentStore {
         // for longer than we have to
         let event: StoreEvent = batch.store_event(&site.deployment);
 
-        let (layout, earliest_block) = deployment::with_lock(&conn, &site, || {
+        let (layout, earliest_block, count) = deployment::with_lock(&conn, &site, || {
             conn.transaction(|| -> Result<_, StoreError> {
                 // Make the changes
                 let layout = self.layout(&conn, site.clone())?;
@@ -1115,6 +1373,10 @@ impl DeploymentStore {
                 )?;
                 section.end();
 
+                let section = stopwatch.start_section("rollup");
+                let count = count + layout.rollup(&conn)?;
+                section.end();
+
                 dynds::insert(&conn, &site, &batch.data_sources, manifest_idx_and_name)?;
 
                 dynds::update_offchain_status(&conn, &site, &batch.offchain_to_remove)?;
@@ -1145,7 +1407,7 @@ impl DeploymentStore {
                     count,
                 )?;
 
-                Ok((layout, earliest_block))
+                Ok((layout, earliest_block, count))
             })
         })?;
 
@@ -1166,7 +1428,33 @@ impl DeploymentStore {
             )?;
         }
 
-        Ok(event)
+        Ok((event, count))
+    }
+
+    /// Prune the deployment in the background, independently of any
+    /// writes to it. If `history_blocks` is given, it becomes the amount
+    /// of history the deployment keeps. Does nothing if this process is
+    /// already pruning the deployment
+    pub(crate) fn start_pruning(
+        self: &Arc<Self>,
+        site: Arc<Site>,
+        history_blocks: Option<BlockNumber>,
+    ) -> Result<(), StoreError> {
+        if let Some(history_blocks) = history_blocks {
+            self.set_history_blocks(&site, history_blocks, ENV_VARS.reorg_threshold)?;
+        }
+
+        let conn = self.get_conn()?;
+        let history_blocks = deployment::history_blocks(&conn, &site)?;
+        let state = deployment::state(&conn, site.deployment.clone())?;
+        let logger = self.logger.new(o!("sgd" => site.id.to_string()));
+        self.spawn_prune(
+            &logger,
+            site,
+            history_blocks,
+            state.earliest_block_number,
+            state.latest_block.number,
+        )
     }
 
     fn spawn_prune(
@@ -1248,6 +1536,9 @@ impl DeploymentStore {
         Ok(())
     }
 
+    /// Return the event for the changes, and by how much they changed the
+    /// entity count. The change is `None` when truncating since the count
+    /// is recomputed from the tables then
     fn rewind_or_truncate_with_conn(
         &self,
         conn: &PgConnection,
@@ -1255,8 +1546,8 @@ impl DeploymentStore {
         block_ptr_to: BlockPtr,
         firehose_cursor: &FirehoseCursor,
         truncate: bool,
-    ) -> Result<StoreEvent, StoreError> {
-        let event = deployment::with_lock(conn, &site, || {
+    ) -> Result<(StoreEvent, Option<i32>), StoreError> {
+        let res = deployment::with_lock(conn, &site, || {
             conn.transaction(|| -> Result<_, StoreError> {
                 // Don't revert past a graft point
                 let info = self.subgraph_info_with_conn(conn, site.as_ref())?;
@@ -1287,14 +1578,14 @@ impl DeploymentStore {
                 // Revert the data
                 let layout = self.layout(conn, site.clone())?;
 
-                let event = if truncate {
+                let (event, count) = if truncate {
                     let event = layout.truncate_tables(conn)?;
                     deployment::set_entity_count(conn, site.as_ref(), layout.count_query.as_str())?;
-                    event
+                    (event, None)
                 } else {
                     let (event, count) = layout.revert_block(conn, block)?;
                     deployment::update_entity_count(conn, site.as_ref(), count)?;
-                    event
+                    (event, Some(count))
                 };
 
                 // Revert the meta data changes that correspond to this subgraph.
@@ -1304,11 +1595,11 @@ impl DeploymentStore {
                 // changes that might need to be reverted
                 Layout::revert_metadata(conn, &site, block)?;
 
-                Ok(event)
+                Ok((event, count))
             })
         })?;
 
-        Ok(event)
+        Ok(res)
     }
 
     pub(crate) fn truncate(
@@ -1333,6 +1624,7 @@ impl DeploymentStore {
         // When rewinding, we reset the firehose cursor. That way, on resume, Firehose will start
         // from the block_ptr instead (with sanity check to ensure it's resume at the exact block).
         self.rewind_or_truncate_with_conn(&conn, site, block_ptr_to, &FirehoseCursor::None, true)
+            .map(|(event, _)| event)
     }
 
     pub(crate) fn rewind(
@@ -1342,21 +1634,131 @@ impl DeploymentStore {
     ) -> Result<StoreEvent, StoreError> {
         let conn = self.get_conn()?;
 
-        // Unwrap: If we are reverting then the block ptr is not `None`.
-        let block_ptr_from = Self::block_ptr_with_conn(&conn, site.cheap_clone())?.unwrap();
+        let block_ptr_from =
+            Self::block_ptr_with_conn(&conn, site.cheap_clone())?.ok_or_else(|| {
+                constraint_violation!(
+                    "can not rewind {} since it has not processed any blocks",
+                    site.deployment
+                )
+            })?;
 
         // Sanity check on block numbers
         if block_ptr_from.number <= block_ptr_to.number {
-            constraint_violation!(
+            return Err(constraint_violation!(
                 "rewind must go backwards, but would go from block {} to block {}",
                 block_ptr_from.number,
                 block_ptr_to.number
-            );
+            ));
+        }
+
+        // Data before the earliest block has been pruned, and the
+        // deployment has no data at all before its start block
+        let state = deployment::state(&conn, site.deployment.clone())?;
+        if block_ptr_to.number < state.earliest_block_number {
+            return Err(constraint_violation!(
+                "deployment {} has been pruned up to block {} and can not be rewound to block {}",
+                site.deployment,
+                state.earliest_block_number,
+                block_ptr_to.number
+            ));
+        }
+        if let Some(start_block) = deployment::start_block_number(&conn, &site)? {
+            if block_ptr_to.number < start_block {
+                return Err(constraint_violation!(
+                    "deployment {} starts at block {} and can not be rewound to block {}",
+                    site.deployment,
+                    start_block,
+                    block_ptr_to.number
+                ));
+            }
         }
 
         // When rewinding, we reset the firehose cursor. That way, on resume, Firehose will start
         // from the block_ptr instead (with sanity check to ensure it's resume at the exact block).
         self.rewind_or_truncate_with_conn(&conn, site, block_ptr_to, &FirehoseCursor::None, false)
+            .map(|(event, _)| event)
+    }
+
+    /// Export the data of `site` as it was at `block` into a snapshot in
+    /// `dir`. The block must not have been pruned and must have been
+    /// indexed already
+    pub(crate) fn export_snapshot(
+        &self,
+        site: Arc<Site>,
+        block: &BlockPtr,
+        dir: &Path,
+    ) -> Result<Snapshot, StoreError> {
+        let conn = self.get_conn()?;
+        let layout = self.layout(&conn, site.cheap_clone())?;
+
+        conn.transaction(|| {
+            Self::check_exportable(&conn, &site, block.number)?;
+            snapshot::export(&conn, &site, &layout, block, dir)
+        })
+    }
+
+    /// Write the entities of `site` as they were at `block` to `out` in
+    /// canonical form. The block must not have been pruned and must have
+    /// been indexed already
+    pub(crate) fn export_canonical(
+        &self,
+        site: Arc<Site>,
+        block: BlockNumber,
+        out: &mut dyn Write,
+    ) -> Result<BTreeMap<String, usize>, StoreError> {
+        let conn = self.get_conn()?;
+        let layout = self.layout(&conn, site.cheap_clone())?;
+
+        conn.transaction(|| {
+            Self::check_exportable(&conn, &site, block)?;
+            snapshot::export_canonical(&conn, &layout, block, out)
+        })
+    }
+
+    /// Check that the data of `site` at `block` is available for export
+    fn check_exportable(
+        conn: &PgConnection,
+        site: &Site,
+        block: BlockNumber,
+    ) -> Result<(), StoreError> {
+        let state = deployment::state(conn, site.deployment.clone())?;
+        if block > state.latest_block.number {
+            return Err(constraint_violation!(
+                "deployment {} has only indexed up to block {} and can not export block {}",
+                site.deployment,
+                state.latest_block.number,
+                block
+            ));
+        }
+        if block < state.earliest_block_number {
+            return Err(constraint_violation!(
+                "deployment {} has been pruned up to block {} and can not export block {}",
+                site.deployment,
+                state.earliest_block_number,
+                block
+            ));
+        }
+        Ok(())
+    }
+
+    /// Import the snapshot in `dir` into `site`, which must not have
+    /// indexed any blocks yet. Takes the writer lock for `site` while
+    /// importing so that the import fails if the deployment is being
+    /// indexed somewhere
+    pub(crate) fn import_snapshot(
+        &self,
+        site: Arc<Site>,
+        dir: &Path,
+        stopwatch: &StopwatchMetrics,
+    ) -> Result<Snapshot, StoreError> {
+        self.lock_writer(&site)?;
+        let res = (|| {
+            let conn = self.get_conn()?;
+            let layout = self.layout(&conn, site.cheap_clone())?;
+            conn.transaction(|| snapshot::import(&conn, &site, &layout, dir, stopwatch))
+        })();
+        self.unlock_writer(&site)?;
+        res
     }
 
     pub(crate) fn revert_block_operations(
@@ -1364,7 +1766,7 @@ impl DeploymentStore {
         site: Arc<Site>,
         block_ptr_to: BlockPtr,
         firehose_cursor: &FirehoseCursor,
-    ) -> Result<StoreEvent, StoreError> {
+    ) -> Result<(StoreEvent, i32), StoreError> {
         let conn = self.get_conn()?;
         // Unwrap: If we are reverting then the block ptr is not `None`.
         let deployment_head = Self::block_ptr_with_conn(&conn, site.cheap_clone())?.unwrap();
@@ -1374,7 +1776,10 @@ impl DeploymentStore {
             panic!("revert_block_operations must revert only backward, you are trying to revert forward going from subgraph block {} to new block {}", deployment_head, block_ptr_to);
         }
 
-        self.rewind_or_truncate_with_conn(&conn, site, block_ptr_to, firehose_cursor, false)
+        let (event, count) =
+            self.rewind_or_truncate_with_conn(&conn, site, block_ptr_to, firehose_cursor, false)?;
+        // Rewinding always reports the change in the entity count
+        Ok((event, count.unwrap_or_default()))
     }
 
     pub(crate) async fn deployment_state_from_id(
@@ -1420,6 +1825,44 @@ impl DeploymentStore {
         Ok(replica_id)
     }
 
+    /// Check whether `replica`, to which `conn` is connected, has all
+    /// changes that the deployment at `site` made up to and including
+    /// `block`. Read-only replicas lag behind the main database, and a
+    /// query for the latest block of a deployment that runs on a replica
+    /// that does not have that block yet would return data that is older
+    /// than the block that `_meta` reports for the query. The main
+    /// database always has all blocks, and so does any replica for
+    /// queries at `BLOCK_NUMBER_MAX`
+    pub(crate) fn replica_has_block(
+        &self,
+        conn: &PgConnection,
+        replica: ReplicaId,
+        site: &Site,
+        block: BlockNumber,
+        known_head: &mut Option<BlockNumber>,
+    ) -> Result<bool, StoreError> {
+        match replica {
+            ReplicaId::Main => return Ok(true),
+            ReplicaId::ReadOnly(_) if block == BLOCK_NUMBER_MAX => return Ok(true),
+            ReplicaId::ReadOnly(_) => (),
+        }
+
+        // `known_head` is the head we read from the replica earlier, and
+        // it only lives as long as one GraphQL query. A head that the
+        // replica had stays there unless the deployment is reverted, which
+        // can happen on any node while the query runs, exactly like for
+        // all other data the query reads
+        if known_head.map_or(false, |head| head >= block) {
+            return Ok(true);
+        }
+        // The head is written in the same transaction as the changes of
+        // the block, and the replica therefore has all of them as soon as
+        // it has the head
+        let head = deployment::block_ptr(conn, &site.deployment)?.map(|ptr| ptr.number);
+        *known_head = head;
+        Ok(head.map_or(false, |head| head >= block))
+    }
+
     pub(crate) async fn load_dynamic_data_sources(
         &self,
         site: Arc<Site>,
@@ -1531,6 +1974,11 @@ impl DeploymentStore {
 
                 catalog::copy_account_like(&conn, &src.site, &dst.site)?;
 
+                let start = Instant::now();
+                let count = views::copy(&logger, &conn, &src.site, &dst.site)?;
+                info!(logger, "Copied {} views", count;
+                      "time_ms" => start.elapsed().as_millis());
+
                 // Rewind the subgraph so that entity versions that are
                 // clamped in the future (beyond `block`) become valid for
                 // all blocks after `block`. `revert_block` gets rid of
@@ -1765,6 +2213,11 @@ impl DeploymentStore {
         deployment::error_count(&conn, id)
     }
 
+    pub(crate) fn entity_count(&self, site: &Site) -> Result<u64, StoreError> {
+        let conn = self.get_conn()?;
+        deployment::entity_count(&conn, site)
+    }
+
     pub(crate) async fn mirror_primary_tables(&self, logger: &Logger) {
         self.pool.mirror_primary_tables().await.unwrap_or_else(|e| {
             warn!(logger, "Mirroring primary tables failed. We will try again in a few minutes";
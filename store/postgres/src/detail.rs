@@ -7,12 +7,14 @@ use diesel::prelude::{
     ExpressionMethods, JoinOnDsl, NullableExpressionMethods, OptionalExtension, PgConnection,
     QueryDsl, RunQueryDsl,
 };
+use diesel::sql_query;
+use diesel::sql_types::{Array, BigInt, Text};
 use diesel_derives::Associations;
 use git_testament::{git_testament, git_testament_macros};
 use graph::blockchain::BlockHash;
 use graph::data::subgraph::schema::{SubgraphError, SubgraphManifestEntity};
 use graph::prelude::{
-    bigdecimal::ToPrimitive, BigDecimal, BlockPtr, DeploymentHash, StoreError,
+    bigdecimal::ToPrimitive, serde_json, BigDecimal, BlockPtr, DeploymentHash, StoreError,
     SubgraphDeploymentEntity,
 };
 use graph::schema::InputSchema;
@@ -82,6 +84,8 @@ pub(crate) struct ErrorDetail {
     handler: Option<String>,
     pub deterministic: bool,
     pub block_range: (Bound<i32>, Bound<i32>),
+    data_source: Option<String>,
+    transaction_hash: Option<String>,
 }
 
 impl ErrorDetail {
@@ -117,6 +121,8 @@ impl TryFrom<ErrorDetail> for SubgraphError {
             handler,
             deterministic,
             block_range,
+            data_source,
+            transaction_hash,
         } = value;
         let block_number = crate::block_range::first_block_in_range(&block_range);
         // FIXME:
@@ -139,6 +145,8 @@ impl TryFrom<ErrorDetail> for SubgraphError {
             message,
             block_ptr,
             handler,
+            data_source,
+            transaction_hash,
             deterministic,
         })
     }
@@ -183,7 +191,8 @@ pub(crate) fn info_from_details(
     fatal: Option<ErrorDetail>,
     non_fatal: Vec<ErrorDetail>,
     sites: &[Arc<Site>],
-    subgraph_history_blocks: i32,
+    manifest: ManifestDetail,
+    sizes: &HashMap<String, i64>,
 ) -> Result<status::Info, StoreError> {
     let DeploymentDetail {
         id,
@@ -247,8 +256,14 @@ pub(crate) fn info_from_details(
         non_fatal_errors,
         chains: vec![chain],
         entity_count,
+        storage_bytes: sizes
+            .get(site.namespace.as_str())
+            .map(|bytes| (*bytes).max(0) as u64),
         node: None,
-        history_blocks: subgraph_history_blocks,
+        history_blocks: manifest.history_blocks,
+        version_label: manifest.version_label,
+        description: manifest.description,
+        repository: manifest.repository,
     })
 }
 
@@ -270,6 +285,44 @@ pub(crate) fn deployment_details(
     Ok(details)
 }
 
+/// The total size in bytes of the tables and indexes of each of `sites`,
+/// or of all deployments if `sites` is empty, keyed by namespace. The
+/// sizes come from the materialized view `info.subgraph_sizes`, which is
+/// refreshed periodically, and are therefore only approximate; deployments
+/// that were created since the last refresh are missing
+fn storage_sizes(
+    conn: &PgConnection,
+    sites: &[Arc<Site>],
+) -> Result<HashMap<String, i64>, StoreError> {
+    #[derive(QueryableByName)]
+    struct Size {
+        #[sql_type = "Text"]
+        name: String,
+        #[sql_type = "BigInt"]
+        total_bytes: i64,
+    }
+
+    let sizes = if sites.is_empty() {
+        sql_query("select name, total_bytes::int8 as total_bytes from info.subgraph_sizes")
+            .get_results::<Size>(conn)?
+    } else {
+        let namespaces: Vec<_> = sites
+            .iter()
+            .map(|site| site.namespace.to_string())
+            .collect();
+        sql_query(
+            "select name, total_bytes::int8 as total_bytes from info.subgraph_sizes \
+             where name = any($1)",
+        )
+        .bind::<Array<Text>, _>(namespaces)
+        .get_results::<Size>(conn)?
+    };
+    Ok(sizes
+        .into_iter()
+        .map(|size| (size.name, size.total_bytes))
+        .collect())
+}
+
 pub(crate) fn deployment_statuses(
     conn: &PgConnection,
     sites: &[Arc<Site>],
@@ -317,31 +370,51 @@ pub(crate) fn deployment_statuses(
         .into_group_map()
     };
 
-    let mut history_blocks_map: HashMap<_, _> = {
+    let mut manifest_map: HashMap<_, _> = {
+        let columns = (
+            sm::id,
+            (
+                sm::history_blocks,
+                sm::version_label,
+                sm::description,
+                sm::repository,
+            ),
+        );
         if sites.is_empty() {
             sm::table
-                .select((sm::id, sm::history_blocks))
-                .load::<(DeploymentId, i32)>(conn)?
+                .select(columns)
+                .load::<(DeploymentId, ManifestDetail)>(conn)?
         } else {
             sm::table
                 .filter(sm::id.eq_any(sites.iter().map(|site| site.id)))
-                .select((sm::id, sm::history_blocks))
-                .load::<(DeploymentId, i32)>(conn)?
+                .select(columns)
+                .load::<(DeploymentId, ManifestDetail)>(conn)?
         }
         .into_iter()
         .collect()
     };
 
+    let sizes = storage_sizes(conn, sites)?;
+
     details_with_fatal_error
         .into_iter()
         .map(|(detail, fatal)| {
             let non_fatal = non_fatal_errors.remove(&detail.id).unwrap_or_default();
-            let subgraph_history_blocks = history_blocks_map.remove(&detail.id).unwrap_or_default();
-            info_from_details(detail, fatal, non_fatal, sites, subgraph_history_blocks)
+            let manifest = manifest_map.remove(&detail.id).unwrap_or_default();
+            info_from_details(detail, fatal, non_fatal, sites, manifest, &sizes)
         })
         .collect()
 }
 
+/// The parts of `subgraph_manifest` that are needed for the indexing status
+#[derive(Queryable, Default)]
+pub(crate) struct ManifestDetail {
+    history_blocks: i32,
+    version_label: Option<String>,
+    description: Option<String>,
+    repository: Option<String>,
+}
+
 #[derive(Queryable, QueryableByName, Identifiable, Associations)]
 #[table_name = "subgraph_manifest"]
 #[belongs_to(GraphNodeVersion)]
@@ -363,6 +436,8 @@ struct StoredSubgraphManifest {
     entities_with_causality_region: Vec<String>,
     on_sync: Option<String>,
     history_blocks: i32,
+    version_label: Option<String>,
+    files: Option<serde_json::Value>,
 }
 
 impl StoredSubgraphManifest {
@@ -376,11 +451,16 @@ impl StoredSubgraphManifest {
             spec_version: self.spec_version,
             description: self.description,
             repository: self.repository,
+            version_label: self.version_label,
             features: self.features,
             schema: self.schema,
             raw_yaml: self.raw_yaml,
             entities_with_causality_region: e,
             history_blocks: self.history_blocks,
+            files: self
+                .files
+                .and_then(|files| serde_json::from_value(files).ok())
+                .unwrap_or_default(),
         }
     }
 }
@@ -36,6 +36,23 @@ pub(crate) fn insert(
     }
 }
 
+/// Insert `data_sources` into the empty deployment `site` when importing a
+/// snapshot. Only deployments that store their data sources in their own
+/// schema support this
+pub(crate) fn import(
+    conn: &PgConnection,
+    site: &Site,
+    data_sources: &[StoredDynamicDataSource],
+) -> Result<usize, StoreError> {
+    match site.schema_version.private_data_sources() {
+        true => DataSourcesTable::new(site.namespace.clone()).import(conn, data_sources),
+        false => Err(constraint_violation!(
+            "deployment {} uses the shared data sources table and can not import data sources",
+            site.deployment
+        )),
+    }
+}
+
 pub(crate) fn revert(
     conn: &PgConnection,
     site: &Site,
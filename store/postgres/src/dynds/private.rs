@@ -151,45 +151,74 @@ impl DataSourcesTable {
         for (block_ptr, dss) in &data_sources.entries {
             let block = block_ptr.number;
             for ds in dss {
-                let StoredDynamicDataSource {
-                    manifest_idx,
-                    param,
-                    context,
-                    creation_block,
-                    done_at,
-                    causality_region,
-                } = ds;
-
-                if creation_block != &Some(block) {
+                if ds.creation_block != Some(block) {
                     return Err(constraint_violation!(
                         "mismatching creation blocks `{:?}` and `{}`",
-                        creation_block,
+                        ds.creation_block,
                         block
                     ));
                 }
 
-                // Offchain data sources have a unique causality region assigned from a sequence in the
-                // database, while onchain data sources always have causality region 0.
-                let query = format!(
-                "insert into {}(block_range, manifest_idx, param, context, causality_region, done_at) \
-                            values (int4range($1, null), $2, $3, $4, $5, $6)",
-                self.qname
-            );
-
-                let query = sql_query(query)
-                    .bind::<Nullable<Integer>, _>(creation_block)
-                    .bind::<Integer, _>(*manifest_idx as i32)
-                    .bind::<Nullable<Binary>, _>(param.as_ref().map(|p| &**p))
-                    .bind::<Nullable<Jsonb>, _>(context)
-                    .bind::<Integer, _>(causality_region)
-                    .bind::<Nullable<Integer>, _>(done_at);
+                inserted_total += self.insert_one(conn, ds)?;
+            }
+        }
+        Ok(inserted_total)
+    }
 
-                inserted_total += query.execute(conn)?;
+    /// Insert `data_sources` as they are, keeping their creation blocks and
+    /// causality regions. This is used when importing a snapshot into an
+    /// empty deployment
+    pub(crate) fn import(
+        &self,
+        conn: &PgConnection,
+        data_sources: &[StoredDynamicDataSource],
+    ) -> Result<usize, StoreError> {
+        let mut inserted_total = 0;
+        for ds in data_sources {
+            if ds.creation_block.is_none() {
+                return Err(constraint_violation!(
+                    "data source {:?} has no creation block",
+                    ds
+                ));
             }
+            inserted_total += self.insert_one(conn, ds)?;
         }
         Ok(inserted_total)
     }
 
+    fn insert_one(
+        &self,
+        conn: &PgConnection,
+        ds: &StoredDynamicDataSource,
+    ) -> Result<usize, StoreError> {
+        let StoredDynamicDataSource {
+            manifest_idx,
+            param,
+            context,
+            creation_block,
+            done_at,
+            causality_region,
+        } = ds;
+
+        // Offchain data sources have a unique causality region assigned from a sequence in the
+        // database, while onchain data sources always have causality region 0.
+        let query = format!(
+            "insert into {}(block_range, manifest_idx, param, context, causality_region, done_at) \
+                        values (int4range($1, null), $2, $3, $4, $5, $6)",
+            self.qname
+        );
+
+        let query = sql_query(query)
+            .bind::<Nullable<Integer>, _>(creation_block)
+            .bind::<Integer, _>(*manifest_idx as i32)
+            .bind::<Nullable<Binary>, _>(param.as_ref().map(|p| &**p))
+            .bind::<Nullable<Jsonb>, _>(context)
+            .bind::<Integer, _>(causality_region)
+            .bind::<Nullable<Integer>, _>(done_at);
+
+        Ok(query.execute(conn)?)
+    }
+
     pub(crate) fn revert(&self, conn: &PgConnection, block: BlockNumber) -> Result<(), StoreError> {
         // Use the 'does not extend to the left of' operator `&>` to leverage the gist index, this
         // is equivalent to lower(block_range) >= $1.
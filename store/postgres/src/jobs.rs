@@ -6,22 +6,30 @@ use std::time::{Duration, Instant};
 use async_trait::async_trait;
 use diesel::{prelude::RunQueryDsl, sql_query, sql_types::Double};
 
-use graph::prelude::{error, Logger, MetricsRegistry, StoreError, ENV_VARS};
+use graph::prelude::chrono::{DateTime, Utc};
+use graph::prelude::{error, BlockNumber, Logger, MetricsRegistry, NodeId, StoreError, ENV_VARS};
 use graph::prometheus::Gauge;
-use graph::util::jobs::{Job, Runner};
+use graph::util::jobs::{Job, JobRecorder, Runner};
 
 use crate::connection_pool::ConnectionPool;
-use crate::{unused, Store, SubgraphStore};
+use crate::primary;
+use crate::{unused, BlockStore, Store, SubgraphStore};
 
 pub fn register(
     runner: &mut Runner,
     store: Arc<Store>,
     primary_pool: ConnectionPool,
     registry: Arc<MetricsRegistry>,
+    node_id: NodeId,
 ) {
     const ONE_MINUTE: Duration = Duration::from_secs(60);
     const ONE_HOUR: Duration = Duration::from_secs(60 * 60);
 
+    runner.record_with(Arc::new(JobStatusRecorder::new(
+        primary_pool.clone(),
+        node_id,
+    )));
+
     runner.register(
         Arc::new(VacuumDeploymentsJob::new(store.subgraph_store())),
         ONE_MINUTE,
@@ -47,6 +55,44 @@ pub fn register(
         Arc::new(RefreshMaterializedView::new(store.subgraph_store())),
         6 * ONE_HOUR,
     );
+
+    if let Some(retention) = ENV_VARS.store.call_cache_retention_blocks {
+        runner.register(
+            Arc::new(CallCacheCleanup::new(store.block_store(), retention)),
+            ONE_HOUR,
+        );
+    }
+}
+
+/// Records the runs of the jobs of a node in `public.job_status` in the
+/// primary so that they can be seen through the index node, and so that a
+/// restarted node picks up the schedule of its jobs where it left off
+struct JobStatusRecorder {
+    primary: ConnectionPool,
+    node_id: NodeId,
+}
+
+impl JobStatusRecorder {
+    fn new(primary: ConnectionPool, node_id: NodeId) -> Self {
+        JobStatusRecorder { primary, node_id }
+    }
+}
+
+impl JobRecorder for JobStatusRecorder {
+    fn started(&self, name: &str, interval: Duration) -> Result<(), StoreError> {
+        let conn = primary::Connection::new(self.primary.get()?);
+        conn.record_job_started(&self.node_id, name, interval)
+    }
+
+    fn finished(&self, name: &str, duration: Duration) -> Result<(), StoreError> {
+        let conn = primary::Connection::new(self.primary.get()?);
+        conn.record_job_finished(&self.node_id, name, duration)
+    }
+
+    fn last_finished(&self, name: &str) -> Result<Option<DateTime<Utc>>, StoreError> {
+        let conn = primary::Connection::new(self.primary.get()?);
+        conn.last_job_finished(&self.node_id, name)
+    }
 }
 
 /// A job that vacuums `subgraphs.subgraph_deployment`. With a large number
@@ -236,3 +282,29 @@ impl Job for UnusedJob {
         }
     }
 }
+
+/// Removes entries from the call caches that are too far behind the chain
+/// head to still be useful
+struct CallCacheCleanup {
+    store: Arc<BlockStore>,
+    retention: BlockNumber,
+}
+
+impl CallCacheCleanup {
+    fn new(store: Arc<BlockStore>, retention: BlockNumber) -> Self {
+        Self { store, retention }
+    }
+}
+
+#[async_trait]
+impl Job for CallCacheCleanup {
+    fn name(&self) -> &str {
+        "Remove stale call cache entries"
+    }
+
+    async fn run(&self, logger: &Logger) {
+        if let Err(e) = self.store.clear_stale_call_caches(self.retention).await {
+            error!(logger, "Removing stale call cache entries failed"; "error" => e.to_string());
+        }
+    }
+}
@@ -33,10 +33,12 @@ pub mod query_store;
 mod relational;
 mod relational_queries;
 mod retry;
+mod snapshot;
 mod store;
 mod store_events;
 mod subgraph_store;
 pub mod transaction_receipt;
+mod views;
 mod writable;
 
 #[cfg(debug_assertions)]
@@ -60,6 +62,7 @@ pub use self::detail::DeploymentDetail;
 pub use self::jobs::register as register_jobs;
 pub use self::notification_listener::NotificationSender;
 pub use self::primary::{db_version, UnusedDeployment};
+pub use self::snapshot::Snapshot;
 pub use self::store::Store;
 pub use self::store_events::SubscriptionManager;
 pub use self::subgraph_store::{unused, DeploymentPlacer, Shard, SubgraphStore, PRIMARY_SHARD};
@@ -83,4 +86,5 @@ pub mod command_support {
     pub use crate::deployment::{on_sync, OnSync};
     pub use crate::primary::Namespace;
     pub use crate::relational::{Catalog, Column, ColumnType, Layout, SqlName};
+    pub use crate::views::View;
 }
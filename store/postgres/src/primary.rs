@@ -36,6 +36,10 @@ use graph::{
     prelude::{chrono, CancelHandle, CancelToken},
 };
 use graph::{data::subgraph::schema::generate_entity_id, prelude::StoreEvent};
+use graph::{
+    prelude::chrono::{DateTime, Utc},
+    util::jobs::JobStatus,
+};
 use itertools::Itertools;
 use maybe_owned::MaybeOwned;
 use std::{
@@ -45,7 +49,7 @@ use std::{
     convert::TryInto,
     fmt,
     io::Write,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use crate::{
@@ -107,6 +111,9 @@ table! {
         node_id -> Text,
         paused_at -> Nullable<Timestamptz>,
         assigned_at -> Nullable<Timestamptz>,
+        // The node that keeps a standby instance of the deployment that
+        // takes over when the node in `node_id` stops writing to it
+        standby_node -> Nullable<Text>,
     }
 }
 
@@ -122,6 +129,21 @@ table! {
     }
 }
 
+table! {
+    /// When the background jobs of each node ran last
+    public.job_status(node_id, name) {
+        node_id -> Text,
+        name -> Text,
+        interval_secs -> BigInt,
+        // How many times the job has been started
+        runs -> BigInt,
+        started_at -> Nullable<Timestamptz>,
+        finished_at -> Nullable<Timestamptz>,
+        // How long the last run that finished took
+        duration_ms -> Nullable<BigInt>,
+    }
+}
+
 table! {
     public.ens_names(hash) {
         hash -> Varchar,
@@ -563,6 +585,27 @@ mod queries {
             .collect()
     }
 
+    /// Find active sites in the order of their deployment hashes, skipping
+    /// the first `skip` and returning at most `first`
+    pub(super) fn find_sites_page(
+        conn: &PgConnection,
+        skip: usize,
+        first: Option<usize>,
+    ) -> Result<Vec<Site>, StoreError> {
+        let query = ds::table
+            .filter(ds::active)
+            .order_by(ds::subgraph)
+            .offset(skip as i64);
+        let schemas = match first {
+            Some(first) => query.limit(first as i64).load::<Schema>(conn)?,
+            None => query.load::<Schema>(conn)?,
+        };
+        schemas
+            .into_iter()
+            .map(|schema| schema.try_into())
+            .collect()
+    }
+
     /// Find sites by their subgraph deployment ids. If `ids` is empty,
     /// return no sites
     pub(super) fn find_sites_by_id(
@@ -616,6 +659,24 @@ mod queries {
             .collect::<Result<Vec<Site>, _>>()
     }
 
+    // All deployments for which `node` keeps a standby instance and that
+    // are currently not paused
+    pub(super) fn standby_assignments(
+        conn: &PgConnection,
+        node: &NodeId,
+    ) -> Result<Vec<Site>, StoreError> {
+        ds::table
+            .inner_join(a::table.on(a::id.eq(ds::id)))
+            .filter(a::standby_node.eq(node.as_str()))
+            .filter(a::node_id.ne(node.as_str()))
+            .filter(a::paused_at.is_null())
+            .select(ds::all_columns)
+            .load::<Schema>(conn)?
+            .into_iter()
+            .map(Site::try_from)
+            .collect::<Result<Vec<Site>, _>>()
+    }
+
     pub(super) fn fill_assignments(
         conn: &PgConnection,
         infos: &mut [status::Info],
@@ -657,6 +718,28 @@ mod queries {
             .transpose()
     }
 
+    pub(super) fn standby_node(
+        conn: &PgConnection,
+        site: &Site,
+    ) -> Result<Option<NodeId>, StoreError> {
+        a::table
+            .filter(a::id.eq(site.id))
+            .select(a::standby_node)
+            .first::<Option<String>>(conn)
+            .optional()?
+            .flatten()
+            .map(|node| {
+                NodeId::new(&node).map_err(|()| {
+                    constraint_violation!(
+                        "invalid standby node id `{}` in assignment for `{}`",
+                        node,
+                        site.deployment
+                    )
+                })
+            })
+            .transpose()
+    }
+
     /// Returns Option<(node_id,is_paused)> where `node_id` is the node that
     /// the subgraph is assigned to, and `is_paused` is true if the
     /// subgraph is paused.
@@ -735,6 +818,32 @@ mod queries {
                 .get_results(conn)
                 .map_err(Into::into)
     }
+
+    /// Returns all (subgraph_name, deployment_hash, version) triples for
+    /// the current and pending versions of all subgraphs
+    pub fn subgraph_versions(
+        conn: &PgConnection,
+    ) -> Result<Vec<(String, String, String)>, StoreError> {
+        s::table
+            .inner_join(
+                v::table.on(v::id
+                    .nullable()
+                    .eq(s::current_version)
+                    .or(v::id.nullable().eq(s::pending_version))),
+            )
+            .select((
+                s::name,
+                v::deployment,
+                sql::<Text>(
+                    "(case when subgraphs.subgraph.pending_version = subgraphs.subgraph_version.id then 'pending'
+                           else 'current'
+                     end) as version",
+                ),
+            ))
+            .order_by((s::name, v::created_at))
+            .get_results(conn)
+            .map_err(Into::into)
+    }
 }
 
 /// A wrapper for a database connection that provides access to functionality
@@ -1112,6 +1221,71 @@ impl<'a> Connection<'a> {
         }
     }
 
+    /// Make `node` keep a standby instance of the deployment, or stop
+    /// keeping one anywhere if `node` is `None`
+    pub fn set_standby(
+        &self,
+        site: &Site,
+        node: Option<&NodeId>,
+    ) -> Result<Vec<EntityChange>, StoreError> {
+        use subgraph_deployment_assignment as a;
+
+        let conn = self.conn.as_ref();
+        let updates = update(a::table.filter(a::id.eq(site.id)))
+            .set(a::standby_node.eq(node.map(|node| node.as_str())))
+            .execute(conn)?;
+        match updates {
+            0 => Err(StoreError::DeploymentNotFound(site.deployment.to_string())),
+            1 => {
+                let change = EntityChange::for_assignment(site.into(), EntityChangeOperation::Set);
+                Ok(vec![change])
+            }
+            _ => {
+                // `id` is the primary key of the subgraph_deployment_assignment table,
+                // and we can therefore only update no or one entry
+                unreachable!()
+            }
+        }
+    }
+
+    /// Assign the deployment to `node`, which kept a standby instance of
+    /// it, and make the node that the deployment was assigned to the new
+    /// standby node. Nothing is changed if the deployment is paused or if
+    /// `node` is not its standby node anymore, and the returned list of
+    /// changes is empty
+    pub fn take_over_assignment(
+        &self,
+        site: &Site,
+        node: &NodeId,
+    ) -> Result<Vec<EntityChange>, StoreError> {
+        use subgraph_deployment_assignment as a;
+
+        let conn = self.conn.as_ref();
+        let updates = update(
+            a::table
+                .filter(a::id.eq(site.id))
+                .filter(a::standby_node.eq(node.as_str()))
+                .filter(a::paused_at.is_null()),
+        )
+        .set((
+            a::standby_node.eq(a::node_id.nullable()),
+            a::node_id.eq(node.as_str()),
+        ))
+        .execute(conn)?;
+        match updates {
+            0 => Ok(vec![]),
+            1 => {
+                let change = EntityChange::for_assignment(site.into(), EntityChangeOperation::Set);
+                Ok(vec![change])
+            }
+            _ => {
+                // `id` is the primary key of the subgraph_deployment_assignment table,
+                // and we can therefore only update no or one entry
+                unreachable!()
+            }
+        }
+    }
+
     pub fn get_subgraph_features(
         &self,
         id: String,
@@ -1308,6 +1482,10 @@ impl<'a> Connection<'a> {
         queries::assigned_node(self.conn.as_ref(), site)
     }
 
+    pub fn standby_node(&self, site: &Site) -> Result<Option<NodeId>, StoreError> {
+        queries::standby_node(self.conn.as_ref(), site)
+    }
+
     /// Returns Option<(node_id,is_paused)> where `node_id` is the node that
     /// the subgraph is assigned to, and `is_paused` is true if the
     /// subgraph is paused.
@@ -1711,6 +1889,22 @@ impl<'a> Connection<'a> {
             .map_err(|e| anyhow!("error looking up ens_name for hash {}: {}", hash, e).into())
     }
 
+    /// Add the `(hash, name)` pairs in `names` to the ENS names, skipping
+    /// hashes that are already there. Return how many names were added
+    pub fn insert_ens_names(&self, names: &[(String, String)]) -> Result<usize, StoreError> {
+        use ens_names as dsl;
+
+        let rows: Vec<_> = names
+            .iter()
+            .map(|(hash, name)| (dsl::hash.eq(hash), dsl::name.eq(name)))
+            .collect();
+        insert_into(dsl::table)
+            .values(&rows)
+            .on_conflict_do_nothing()
+            .execute(self.conn.as_ref())
+            .map_err(StoreError::from)
+    }
+
     pub fn is_ens_table_empty(&self) -> Result<bool, StoreError> {
         use ens_names as dsl;
 
@@ -1745,6 +1939,112 @@ impl<'a> Connection<'a> {
 
         Ok(())
     }
+
+    pub fn record_job_started(
+        &self,
+        node: &NodeId,
+        name: &str,
+        interval: Duration,
+    ) -> Result<(), StoreError> {
+        use job_status as j;
+
+        let interval = interval.as_secs() as i64;
+        insert_into(j::table)
+            .values((
+                j::node_id.eq(node.as_str()),
+                j::name.eq(name),
+                j::interval_secs.eq(interval),
+                j::runs.eq(1),
+                j::started_at.eq(sql("now()")),
+            ))
+            .on_conflict((j::node_id, j::name))
+            .do_update()
+            .set((
+                j::interval_secs.eq(interval),
+                j::runs.eq(j::runs + 1),
+                j::started_at.eq(sql("now()")),
+            ))
+            .execute(self.conn.as_ref())?;
+        Ok(())
+    }
+
+    pub fn record_job_finished(
+        &self,
+        node: &NodeId,
+        name: &str,
+        duration: Duration,
+    ) -> Result<(), StoreError> {
+        use job_status as j;
+
+        update(
+            j::table
+                .filter(j::node_id.eq(node.as_str()))
+                .filter(j::name.eq(name)),
+        )
+        .set((
+            j::finished_at.eq(sql("now()")),
+            j::duration_ms.eq(duration.as_millis() as i64),
+        ))
+        .execute(self.conn.as_ref())?;
+        Ok(())
+    }
+
+    /// When the latest run of the job `name` on `node` finished
+    pub fn last_job_finished(
+        &self,
+        node: &NodeId,
+        name: &str,
+    ) -> Result<Option<DateTime<Utc>>, StoreError> {
+        use job_status as j;
+
+        let finished = j::table
+            .filter(j::node_id.eq(node.as_str()))
+            .filter(j::name.eq(name))
+            .select(j::finished_at)
+            .first::<Option<DateTime<Utc>>>(self.conn.as_ref())
+            .optional()?;
+        Ok(finished.flatten())
+    }
+
+    /// The status of the jobs of all nodes, ordered by node and job name
+    pub fn job_statuses(&self) -> Result<Vec<JobStatus>, StoreError> {
+        use job_status as j;
+
+        let rows = j::table
+            .select((
+                j::node_id,
+                j::name,
+                j::interval_secs,
+                j::runs,
+                j::started_at,
+                j::finished_at,
+                j::duration_ms,
+            ))
+            .order_by((j::node_id, j::name))
+            .load::<(
+                String,
+                String,
+                i64,
+                i64,
+                Option<DateTime<Utc>>,
+                Option<DateTime<Utc>>,
+                Option<i64>,
+            )>(self.conn.as_ref())?;
+        Ok(rows
+            .into_iter()
+            .map(
+                |(node, name, interval, runs, started_at, finished_at, duration)| JobStatus {
+                    node,
+                    name,
+                    interval: Duration::from_secs(interval as u64),
+                    runs: runs as u64,
+                    started_at,
+                    finished_at,
+                    duration: duration.map(|ms| Duration::from_millis(ms as u64)),
+                },
+            )
+            .collect())
+    }
 }
 
 /// Return `true` if we deem this installation to be empty, defined as
@@ -1937,6 +2237,14 @@ impl Mirror {
         self.read(|conn| queries::assigned_node(conn, site))
     }
 
+    pub fn standby_assignments(&self, node: &NodeId) -> Result<Vec<Site>, StoreError> {
+        self.read(|conn| queries::standby_assignments(conn, node))
+    }
+
+    pub fn standby_node(&self, site: &Site) -> Result<Option<NodeId>, StoreError> {
+        self.read(|conn| queries::standby_node(conn, site))
+    }
+
     /// Returns Option<(node_id,is_paused)> where `node_id` is the node that
     /// the subgraph is assigned to, and `is_paused` is true if the
     /// subgraph is paused.
@@ -1982,6 +2290,16 @@ impl Mirror {
         self.read(|conn| queries::find_sites(conn, ids, only_active))
     }
 
+    /// Find active sites in the order of their deployment hashes, skipping
+    /// the first `skip` and returning at most `first`
+    pub fn find_sites_page(
+        &self,
+        skip: usize,
+        first: Option<usize>,
+    ) -> Result<Vec<Site>, StoreError> {
+        self.read(|conn| queries::find_sites_page(conn, skip, first))
+    }
+
     /// Find sites by their subgraph deployment ids. If `ids` is empty,
     /// return no sites
     pub fn find_sites_by_id(&self, ids: &[DeploymentId]) -> Result<Vec<Site>, StoreError> {
@@ -2011,6 +2329,12 @@ impl Mirror {
         self.read(|conn| queries::subgraphs_by_deployment_hash(conn, deployment_hash))
     }
 
+    /// Returns all (subgraph_name, deployment_hash, version) triples for
+    /// the current and pending versions of all subgraphs
+    pub fn subgraph_versions(&self) -> Result<Vec<(String, String, String)>, StoreError> {
+        self.read(|conn| queries::subgraph_versions(conn))
+    }
+
     pub fn find_site_in_shard(
         &self,
         subgraph: &DeploymentHash,
@@ -1,3 +1,4 @@
+use std::sync::Mutex;
 use std::time::Instant;
 
 use crate::deployment_store::{DeploymentStore, ReplicaId};
@@ -7,6 +8,7 @@ use graph::data::store::QueryObject;
 use graph::prelude::*;
 use graph::schema::{ApiSchema, InputSchema};
 
+use crate::catalog;
 use crate::primary::Site;
 
 pub(crate) struct QueryStore {
@@ -15,6 +17,9 @@ pub(crate) struct QueryStore {
     store: Arc<DeploymentStore>,
     chain_store: Arc<crate::ChainStore>,
     api_version: Arc<ApiVersion>,
+    /// The latest block of the deployment that the replica had when we
+    /// last checked; see `DeploymentStore::replica_has_block`
+    replica_head: Mutex<Option<BlockNumber>>,
 }
 
 impl QueryStore {
@@ -31,6 +36,7 @@ impl QueryStore {
             store,
             chain_store,
             api_version,
+            replica_head: Mutex::new(None),
         }
     }
 }
@@ -43,17 +49,52 @@ impl QueryStoreTrait for QueryStore {
     ) -> Result<(Vec<QueryObject>, Trace), graph::prelude::QueryExecutionError> {
         assert_eq!(&self.site.deployment, &query.subgraph_id);
         let start = Instant::now();
-        let conn = self
+        let mut replica = self.replica_id;
+        let mut conn = self
             .store
-            .get_replica_conn(self.replica_id)
+            .get_replica_conn(replica)
             .map_err(|e| QueryExecutionError::StoreError(e.into()))?;
+        // Queries must see data at least as new as the block for which
+        // they run, which a lagging replica might not have yet
+        if !self.store.replica_has_block(
+            &conn,
+            replica,
+            &self.site,
+            query.block,
+            &mut self.replica_head.lock().unwrap(),
+        )? {
+            replica = ReplicaId::Main;
+            conn = self
+                .store
+                .get_replica_conn(replica)
+                .map_err(|e| QueryExecutionError::StoreError(e.into()))?;
+        }
         let wait = start.elapsed();
-        self.store
+
+        // Cancelling the GraphQL query also stops the database from
+        // working on this query
+        let running = query.running.cheap_clone();
+        let _backend = match &running {
+            Some(running) => {
+                let pid = catalog::backend_pid(&conn)?;
+                Some(running.in_backend(pid, self.store.backend_canceller(replica)))
+            }
+            None => None,
+        };
+        let explain = ENV_VARS.store.explain_slow_queries.map(|_| query.clone());
+        let start = Instant::now();
+        let result = self
+            .store
             .execute_query(&conn, self.site.clone(), query)
             .map(|(entities, mut trace)| {
                 trace.conn_wait(wait);
                 (entities, trace)
-            })
+            });
+        if let (Some(query), Ok(_)) = (explain, &result) {
+            self.store
+                .explain_slow_query(replica, self.site.clone(), query, start.elapsed());
+        }
+        result
     }
 
     /// Return true if the deployment with the given id is fully synced,
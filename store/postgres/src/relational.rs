@@ -15,7 +15,9 @@ mod ddl_tests;
 mod query_tests;
 
 pub(crate) mod index;
+mod join_table;
 mod prune;
+mod rollup;
 
 use diesel::pg::Pg;
 use diesel::serialize::Output;
@@ -37,7 +39,6 @@ use graph::schema::{
 use graph::slog::warn;
 use inflector::Inflector;
 use itertools::Itertools;
-use lazy_static::lazy_static;
 use std::borrow::Borrow;
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::{From, TryFrom};
@@ -50,8 +51,9 @@ use crate::relational_queries::{FindChangesQuery, FindDerivedQuery, FindPossible
 use crate::{
     primary::{Namespace, Site},
     relational_queries::{
-        ClampRangeQuery, ConflictingEntityQuery, EntityData, EntityDeletion, FilterCollection,
-        FilterQuery, FindManyQuery, FindQuery, InsertQuery, RevertClampQuery, RevertRemoveQuery,
+        ClampRangeQuery, ConflictingEntityQuery, EntityData, EntityDeletion, ExplainQuery,
+        FilterCollection, FilterQuery, FindManyQuery, FindQuery, InsertQuery, PlanLine,
+        RevertClampQuery, RevertRemoveQuery,
     },
 };
 use graph::components::store::DerivedEntityQuery;
@@ -67,6 +69,8 @@ pub use crate::catalog::Catalog;
 use crate::connection_pool::ForeignServer;
 use crate::{catalog, deployment};
 
+pub use join_table::JoinTable;
+
 const DELETE_OPERATION_CHUNK_SIZE: usize = 1_000;
 
 /// The size of string prefixes that we index. This is chosen so that we
@@ -78,11 +82,39 @@ const DELETE_OPERATION_CHUNK_SIZE: usize = 1_000;
 pub const STRING_PREFIX_SIZE: usize = 256;
 pub const BYTE_ARRAY_PREFIX_SIZE: usize = 64;
 
-lazy_static! {
-    static ref STATEMENT_TIMEOUT: Option<String> = ENV_VARS
-        .graphql
-        .sql_statement_timeout
-        .map(|duration| format!("set local statement_timeout={}", duration.as_millis()));
+/// The SQL to limit how long the statements of a query that has to be done
+/// by `deadline` may run. The limit is the smaller of
+/// `GRAPH_SQL_STATEMENT_TIMEOUT` and the time that is left until the
+/// deadline. Fails with a timeout if the deadline has already passed
+fn statement_timeout(deadline: Option<Instant>) -> Result<Option<String>, QueryExecutionError> {
+    let remaining = match deadline {
+        Some(deadline) => {
+            let now = Instant::now();
+            if deadline <= now {
+                return Err(QueryExecutionError::Timeout);
+            }
+            Some(deadline - now)
+        }
+        None => None,
+    };
+    let timeout = match (ENV_VARS.graphql.sql_statement_timeout, remaining) {
+        (Some(timeout), Some(remaining)) => Some(timeout.min(remaining)),
+        (timeout, remaining) => timeout.or(remaining),
+    };
+    // Round up so that a statement that gets canceled has really run into
+    // the deadline; that also avoids a timeout of 0, which Postgres treats
+    // as no timeout
+    let sql = timeout.map(|timeout| {
+        let millis = (timeout.as_micros() + 999) / 1000;
+        format!("set local statement_timeout={}", millis)
+    });
+    Ok(sql)
+}
+
+/// Whether the error `message` says that Postgres canceled a statement
+/// because it ran longer than `statement_timeout`
+fn is_statement_timeout(message: &str) -> bool {
+    message.starts_with("canceling statement due to statement timeout")
 }
 
 /// A string we use as a SQL name for a table or column. The important thing
@@ -299,6 +331,7 @@ impl Layout {
                     fulltext_fields: None,
                     is_reference: false,
                     use_prefix_comparison: false,
+                    is_compressed: false,
                 },
                 Column {
                     name: SqlName::from(PRIMARY_KEY_COLUMN),
@@ -310,11 +343,14 @@ impl Layout {
                     fulltext_fields: None,
                     is_reference: false,
                     use_prefix_comparison: false,
+                    is_compressed: false,
                 },
             ],
             /// The position of this table in all the tables for this layout; this
             /// is really only needed for the tests to make the names of indexes
             /// predictable
+            join_tables: vec![],
+            indexes: vec![],
             position: position as u32,
             is_account_like: false,
             immutable: false,
@@ -550,6 +586,7 @@ impl Layout {
                 InsertQuery::new(table, &chunk)?.execute(conn)?;
             }
         }
+        table.write_join_tables(conn, group)?;
         Ok(())
     }
 
@@ -614,6 +651,9 @@ impl Layout {
         }
 
         let trace = query.trace;
+        let deadline = query.deadline;
+        let running = query.running.cheap_clone();
+        let timeout_sql = statement_timeout(deadline)?;
 
         let filter_collection =
             FilterCollection::new(self, query.collection, query.filter.as_ref(), query.block)?;
@@ -633,7 +673,7 @@ impl Layout {
         let start = Instant::now();
         let values = conn
             .transaction(|| {
-                if let Some(ref timeout_sql) = *STATEMENT_TIMEOUT {
+                if let Some(ref timeout_sql) = timeout_sql {
                     conn.batch_execute(timeout_sql)?;
                 }
                 query.load::<EntityData>(conn)
@@ -659,6 +699,22 @@ impl Layout {
                     {
                         QueryExecutionError::FulltextQueryInvalidSyntax(info.message().to_string())
                     }
+                    // The statement ran into the deadline of the query
+                    DatabaseError(_, ref info)
+                        if is_statement_timeout(info.message())
+                            && deadline.map_or(false, |deadline| deadline <= Instant::now()) =>
+                    {
+                        QueryExecutionError::Timeout
+                    }
+                    // An operator cancelled the query while the statement
+                    // was running
+                    DatabaseError(_, _)
+                        if running
+                            .as_ref()
+                            .map_or(false, |running| running.is_cancelled()) =>
+                    {
+                        QueryExecutionError::Cancelled
+                    }
                     _ => QueryExecutionError::ResolveEntitiesError(format!(
                         "{e}, query = {query_text}",
                     )),
@@ -678,6 +734,38 @@ impl Layout {
             .map(|values| (values, trace))
     }
 
+    /// Run `query` with `EXPLAIN (ANALYZE, BUFFERS)` and return the lines
+    /// of the query plan
+    pub fn explain(
+        &self,
+        conn: &PgConnection,
+        query: EntityQuery,
+    ) -> Result<Vec<String>, QueryExecutionError> {
+        let timeout_sql = statement_timeout(query.deadline)?;
+        let filter_collection =
+            FilterCollection::new(self, query.collection, query.filter.as_ref(), query.block)?;
+        let query = FilterQuery::new(
+            &filter_collection,
+            self,
+            query.filter.as_ref(),
+            query.order,
+            query.range,
+            query.block,
+            query.query_id,
+            &self.site,
+        )?;
+
+        let lines = conn
+            .transaction(|| {
+                if let Some(ref timeout_sql) = timeout_sql {
+                    conn.batch_execute(timeout_sql)?;
+                }
+                ExplainQuery::new(&query).load::<PlanLine>(conn)
+            })
+            .map_err(|e| QueryExecutionError::ResolveEntitiesError(e.to_string()))?;
+        Ok(lines.into_iter().map(|PlanLine(line)| line).collect())
+    }
+
     pub fn update<'a>(
         &'a self,
         conn: &PgConnection,
@@ -719,6 +807,7 @@ impl Layout {
         for chunk in group.write_chunks(chunk_size) {
             count += InsertQuery::new(table, &chunk)?.execute(conn)?;
         }
+        table.write_join_tables(conn, group)?;
 
         Ok(count)
     }
@@ -761,6 +850,9 @@ impl Layout {
     pub fn truncate_tables(&self, conn: &PgConnection) -> Result<StoreEvent, StoreError> {
         for table in self.tables.values() {
             conn.execute(&format!("TRUNCATE TABLE {}", table.qualified_name))?;
+            for join in &table.join_tables {
+                conn.execute(&format!("TRUNCATE TABLE {}", join.qualified_name))?;
+            }
         }
         Ok(StoreEvent::new(vec![]))
     }
@@ -818,6 +910,10 @@ impl Layout {
                 entity_type: table.object.to_string(),
             });
             changes.extend(set);
+
+            for join in &table.join_tables {
+                join.revert(conn, block)?;
+            }
         }
         Ok((StoreEvent::new(changes), count))
     }
@@ -1027,6 +1123,10 @@ pub struct Column {
     /// Whether to use a prefix of the column for comparisons and index
     /// creation, or column values in their entirety
     pub use_prefix_comparison: bool,
+    /// Whether the field has a `@compressed` directive. Values of such a
+    /// column are compressed and moved out of the table's rows, and the
+    /// column is not indexed
+    pub is_compressed: bool,
 }
 
 impl Column {
@@ -1080,6 +1180,7 @@ impl Column {
             fulltext_fields: None,
             is_reference,
             use_prefix_comparison,
+            is_compressed: field.is_compressed,
         })
     }
 
@@ -1095,6 +1196,7 @@ impl Column {
             fulltext_fields: Some(def.included_fields.clone()),
             is_reference: false,
             use_prefix_comparison: false,
+            is_compressed: false,
         })
     }
 
@@ -1182,6 +1284,14 @@ pub struct Table {
 
     pub columns: Vec<Column>,
 
+    /// The join tables for the `@joinTable` list fields of this type;
+    /// those fields do not have a column in `columns`
+    pub join_tables: Vec<JoinTable>,
+
+    /// The columns of the indexes that the `@index` directives of the
+    /// type ask for
+    pub(crate) indexes: Vec<Vec<SqlName>>,
+
     /// This kind of entity behaves like an account in that it has a low
     /// ratio of distinct entities to overall number of rows because
     /// entities are updated frequently on average
@@ -1220,10 +1330,47 @@ impl Table {
         let columns = object_type
             .fields
             .into_iter()
-            .filter(|field| !field.is_derived)
+            .filter(|field| !field.is_derived && !field.is_join_table)
             .map(|field| Column::new(schema, &table_name, field, catalog))
             .chain(fulltexts.iter().map(Column::new_fulltext))
             .collect::<Result<Vec<Column>, StoreError>>()?;
+        let id_type = ColumnType::from(defn.id_type()?);
+        let join_tables = object_type
+            .fields
+            .into_iter()
+            .filter(|field| field.is_join_table)
+            .map(|field| {
+                JoinTable::new(
+                    schema,
+                    &catalog.site.namespace,
+                    &table_name,
+                    id_type.clone(),
+                    field,
+                )
+            })
+            .collect::<Result<Vec<JoinTable>, StoreError>>()?;
+        let indexes = object_type
+            .indexes
+            .iter()
+            .map(|fields| {
+                fields
+                    .iter()
+                    .map(|field| {
+                        columns
+                            .iter()
+                            .find(|column| &column.field == field)
+                            .map(|column| column.name.clone())
+                            .ok_or_else(|| {
+                                constraint_violation!(
+                                    "the @index of `{}` uses `{}` which has no column",
+                                    defn.as_str(),
+                                    field
+                                )
+                            })
+                    })
+                    .collect::<Result<Vec<_>, StoreError>>()
+            })
+            .collect::<Result<Vec<_>, StoreError>>()?;
         let qualified_name = SqlName::qualified_name(&catalog.site.namespace, &table_name);
         let immutable = defn.is_immutable();
 
@@ -1236,6 +1383,8 @@ impl Table {
             // db connection, which we don't have at this point.
             is_account_like: false,
             columns,
+            join_tables,
+            indexes,
             position,
             immutable,
             has_causality_region,
@@ -1251,6 +1400,8 @@ impl Table {
             name: name.clone(),
             qualified_name: SqlName::qualified_name(namespace, name),
             columns: self.columns.clone(),
+            join_tables: self.join_tables.clone(),
+            indexes: self.indexes.clone(),
             is_account_like: self.is_account_like,
             position: self.position,
             immutable: self.immutable,
@@ -1282,12 +1433,18 @@ impl Table {
     }
 
     fn can_copy_from(&self, source: &Self) -> Vec<String> {
-        self.columns
+        let mut errors: Vec<_> = self
+            .columns
             .iter()
             .filter_map(|dcol| match source.column(&dcol.name) {
                 Some(scol) => dcol.is_assignable_from(scol, &self.object),
                 None => {
-                    if !dcol.is_nullable() {
+                    if source.join_table(dcol.name.as_str()).is_some() {
+                        Some(format!(
+                            "The attribute {}.{} is stored in a join table in the source",
+                            self.object, dcol.field
+                        ))
+                    } else if !dcol.is_nullable() {
                         Some(format!(
                             "The attribute {}.{} is non-nullable, \
                          but there is no such attribute in the source",
@@ -1298,7 +1455,19 @@ impl Table {
                     }
                 }
             })
-            .collect()
+            .collect();
+        errors.extend(self.join_tables.iter().filter_map(|djoin| {
+            match source.join_table(djoin.column.as_str()) {
+                Some(sjoin) => djoin.is_assignable_from(sjoin, &self.object),
+                None if source.column(&djoin.column).is_some() => Some(format!(
+                    "The attribute {}.{} is stored in a join table, \
+                     but in an array column in the source",
+                    self.object, djoin.field
+                )),
+                None => None,
+            }
+        }));
+        errors
     }
 
     pub fn primary_key(&self) -> &Column {
@@ -1315,6 +1484,38 @@ impl Table {
         Ok(())
     }
 
+    /// Run the queries that GraphQL queries for the first `rows` current
+    /// entities and for the `rows` most recently written versions turn
+    /// into so that the rows and the index pages they need end up in the
+    /// database caches. Return how many rows the queries read
+    pub fn warm_up(&self, conn: &PgConnection, rows: usize) -> Result<usize, StoreError> {
+        #[derive(QueryableByName)]
+        struct Rows {
+            #[sql_type = "diesel::sql_types::BigInt"]
+            rows: i64,
+        }
+
+        let table_name = &self.qualified_name;
+        let primary_key = &self.primary_key().name;
+        let current = if self.immutable {
+            format!("{BLOCK_COLUMN} <= {BLOCK_NUMBER_MAX}")
+        } else {
+            format!("{BLOCK_RANGE_COLUMN} @> {BLOCK_NUMBER_MAX}")
+        };
+        let queries = [
+            format!(
+                "select * from {table_name} where {current} order by {primary_key} limit {rows}"
+            ),
+            format!("select * from {table_name} order by {VID_COLUMN} desc limit {rows}"),
+        ];
+        let mut read = 0;
+        for query in queries {
+            let sql = format!("select count(*) as rows from ({query}) w");
+            read += diesel::sql_query(sql).get_result::<Rows>(conn)?.rows as usize;
+        }
+        Ok(read)
+    }
+
     pub(crate) fn block_column(&self) -> &SqlName {
         if self.immutable {
             &crate::block_range::BLOCK_COLUMN_SQL
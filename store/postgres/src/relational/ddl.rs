@@ -20,6 +20,10 @@ const CREATE_EXCLUSION_CONSTRAINT: bool = true;
 #[cfg(not(debug_assertions))]
 const CREATE_EXCLUSION_CONSTRAINT: bool = false;
 
+/// The `toast_tuple_target` for tables with `@compressed` columns; this is
+/// the smallest value Postgres accepts
+const COMPRESSED_TUPLE_TARGET: usize = 128;
+
 impl Layout {
     /// Generate the DDL for the entire layout, i.e., all `create table`
     /// and `create index` etc. statements needed in the database schema
@@ -37,9 +41,18 @@ impl Layout {
         let mut tables = self.tables.values().collect::<Vec<_>>();
         tables.sort_by_key(|table| table.position);
         // Output 'create table' statements for all tables
-        for table in tables {
+        for table in &tables {
             table.as_ddl(&self.catalog, &mut out)?;
         }
+        // Join tables are not part of `Table::as_ddl` since that is also
+        // used to create tables during pruning, which leaves join tables
+        // alone
+        for table in tables {
+            let as_constraint = table.has_causality_region || CREATE_EXCLUSION_CONSTRAINT;
+            for join in &table.join_tables {
+                join.as_ddl(&self.catalog, as_constraint, &mut out)?;
+            }
+        }
 
         Ok(out)
     }
@@ -143,6 +156,43 @@ impl Table {
         }
     }
 
+    /// Set up the storage for tables with `@compressed` columns. Postgres
+    /// compresses large `text` and `bytea` values and moves them into the
+    /// table's TOAST table, and only reads them from there when a query
+    /// selects the column, but by default only once a row does not fit
+    /// into 2kB. Lowering `toast_tuple_target` makes that happen for rows
+    /// of a few hundred bytes already. The setting is for the whole table,
+    /// and therefore also applies to the other `text` and `bytea` columns
+    /// of such a table. What is particular to `@compressed` columns is
+    /// that they use lz4 where the database supports it, and that they are
+    /// not indexed
+    fn create_compressed_storage(&self, catalog: &Catalog, out: &mut String) -> fmt::Result {
+        let mut compressed = self
+            .columns
+            .iter()
+            .filter(|col| col.is_compressed)
+            .peekable();
+        if compressed.peek().is_none() {
+            return Ok(());
+        }
+        for column in compressed {
+            if let Some(method) = catalog.compression_method() {
+                write!(
+                    out,
+                    "alter table {qname} alter column {column} set compression {method};\n",
+                    qname = self.qualified_name,
+                    column = column.name.quoted()
+                )?;
+            }
+        }
+        write!(
+            out,
+            "alter table {qname} set (toast_tuple_target = {target});\n",
+            qname = self.qualified_name,
+            target = COMPRESSED_TUPLE_TARGET
+        )
+    }
+
     fn create_time_travel_indexes(&self, catalog: &Catalog, out: &mut String) -> fmt::Result {
         let (int4, int8) = catalog.minmax_ops();
 
@@ -222,22 +272,7 @@ impl Table {
                 ("gist".to_string(), index_expr)
             }
         } else {
-            let index_expr = if column.use_prefix_comparison {
-                match column.column_type {
-                    ColumnType::String => {
-                        format!("left({}, {})", column.name.quoted(), STRING_PREFIX_SIZE)
-                    }
-                    ColumnType::Bytes => format!(
-                        "substring({}, 1, {})",
-                        column.name.quoted(),
-                        BYTE_ARRAY_PREFIX_SIZE
-                    ),
-                    // Handle other types if necessary, or maintain the unreachable statement
-                    _ => unreachable!("only String and Bytes can have arbitrary size"),
-                }
-            } else {
-                column.name.quoted()
-            };
+            let index_expr = Self::column_index_expr(column);
 
             let method = if column.is_list() || column.is_fulltext() {
                 "gin".to_string()
@@ -249,6 +284,49 @@ impl Table {
         }
     }
 
+    /// The expression with which `column` appears in a btree index;
+    /// columns that can hold arbitrarily large values are indexed by a
+    /// prefix of their value
+    fn column_index_expr(column: &Column) -> String {
+        if column.use_prefix_comparison {
+            match column.column_type {
+                ColumnType::String => {
+                    format!("left({}, {})", column.name.quoted(), STRING_PREFIX_SIZE)
+                }
+                ColumnType::Bytes => format!(
+                    "substring({}, 1, {})",
+                    column.name.quoted(),
+                    BYTE_ARRAY_PREFIX_SIZE
+                ),
+                // Handle other types if necessary, or maintain the unreachable statement
+                _ => unreachable!("only String and Bytes can have arbitrary size"),
+            }
+        } else {
+            column.name.quoted()
+        }
+    }
+
+    /// Create the indexes that the `@index` directives of the entity type
+    /// ask for
+    fn create_declared_indexes(&self, out: &mut String) -> fmt::Result {
+        for (index, columns) in self.indexes.iter().enumerate() {
+            let exprs = columns
+                .iter()
+                .filter_map(|name| self.column(name))
+                .map(Self::column_index_expr)
+                .collect::<Vec<_>>()
+                .join(", ");
+            write!(
+                out,
+                "create index idx_{table_index}_{index}_{table_name}\n    on {qname} using btree({exprs});\n",
+                table_index = self.position,
+                table_name = self.name,
+                qname = self.qualified_name,
+            )?;
+        }
+        Ok(())
+    }
+
     fn create_attribute_indexes(&self, out: &mut String) -> fmt::Result {
         // Create indexes.
 
@@ -269,12 +347,17 @@ impl Table {
                 && [ColumnType::BigDecimal, ColumnType::BigInt, ColumnType::Int]
                     .contains(&col.column_type))
         };
+
+        // Compressed columns hold large blobs that are only ever read,
+        // never searched
+        let not_compressed = |col: &&Column| !col.is_compressed;
         let columns = self
             .columns
             .iter()
             .filter(not_enum_list)
             .filter(not_immutable_pk)
-            .filter(not_numeric_list);
+            .filter(not_numeric_list)
+            .filter(not_compressed);
 
         for (column_index, column) in columns.enumerate() {
             let (method, index_expr) =
@@ -295,6 +378,7 @@ impl Table {
                 )?;
             }
         }
+        self.create_declared_indexes(out)?;
         writeln!(out)
     }
 
@@ -305,6 +389,7 @@ impl Table {
     /// gets generated
     pub(crate) fn as_ddl(&self, catalog: &Catalog, out: &mut String) -> fmt::Result {
         self.create_table(out)?;
+        self.create_compressed_storage(catalog, out)?;
         self.create_time_travel_indexes(catalog, out)?;
         self.create_attribute_indexes(out)
     }
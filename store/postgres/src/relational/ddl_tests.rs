@@ -123,6 +123,86 @@ fn forward_enum() {
     assert!(column.is_enum());
 }
 
+#[test]
+fn join_table() {
+    let layout = test_layout(JOIN_TABLE_GQL);
+    let table = layout
+        .table(&SqlName::from("group"))
+        .expect("group table exists");
+    // The list is not stored in the table itself
+    assert!(table.column(&SqlName::from("members")).is_none());
+    assert!(table.column(&SqlName::from("admins")).is_some());
+
+    let join = table
+        .join_table_for_field("members")
+        .expect("members has a join table");
+    assert_eq!("group$members", join.name.as_str());
+    assert_eq!(ColumnType::String, join.parent_type);
+    assert_eq!(ColumnType::Bytes, join.child_id_type);
+    assert!(table.join_table_for_field("admins").is_none());
+
+    let sql = layout.as_ddl().expect("Failed to generate DDL");
+    let sql = sql.split_whitespace().join(" ");
+    for stmt in [
+        r#"create table "sgd0815"."group$members" ( vid bigserial primary key, parent text not null, child bytea not null, block_range int4range not null );"#,
+        r#"alter table "sgd0815"."group$members" add constraint "group$members_excl" exclude using gist (parent with =, child with =, block_range with &&);"#,
+        r#"create index "group$members_parent_child" on "sgd0815"."group$members" using btree(parent, child);"#,
+        r#"create index "group$members_child_parent" on "sgd0815"."group$members" using btree(child, parent);"#,
+    ] {
+        assert!(sql.contains(stmt), "missing `{}` in\n{}", stmt, sql);
+    }
+}
+
+#[test]
+fn compressed_column() {
+    let layout = test_layout(COMPRESSED_GQL);
+    let table = layout
+        .table(&SqlName::from("call"))
+        .expect("call table exists");
+    let input = table
+        .column(&SqlName::from("input"))
+        .expect("input column exists");
+    assert!(input.is_compressed);
+    assert!(!table.column(&SqlName::from("to")).unwrap().is_compressed);
+
+    let sql = layout.as_ddl().expect("Failed to generate DDL");
+    let sql = sql.split_whitespace().join(" ");
+    for stmt in [
+        r#"alter table "sgd0815"."call" set (toast_tuple_target = 128);"#,
+        r#"create index attr_0_1_call_to on "sgd0815"."call" using btree("to");"#,
+    ] {
+        assert!(sql.contains(stmt), "missing `{}` in\n{}", stmt, sql);
+    }
+    // Compressed columns are not indexed, and the test catalog does not
+    // support lz4
+    assert!(!sql.contains("_call_input"));
+    assert!(!sql.contains("set compression"));
+}
+
+#[test]
+fn declared_indexes() {
+    let layout = test_layout(INDEX_GQL);
+    let table = layout
+        .table(&SqlName::from("swap"))
+        .expect("swap table exists");
+    assert_eq!(
+        vec![
+            vec![SqlName::from("pair"), SqlName::from("timestamp")],
+            vec![SqlName::from("amount")]
+        ],
+        table.indexes
+    );
+
+    let sql = layout.as_ddl().expect("Failed to generate DDL");
+    let sql = sql.split_whitespace().join(" ");
+    for stmt in [
+        r#"create index idx_0_0_swap on "sgd0815"."swap" using btree(left("pair", 256), "timestamp");"#,
+        r#"create index idx_0_1_swap on "sgd0815"."swap" using btree("amount");"#,
+    ] {
+        assert!(sql.contains(stmt), "missing `{}` in\n{}", stmt, sql);
+    }
+}
+
 #[test]
 fn can_copy_from() {
     let source = test_layout(THING_GQL);
@@ -614,3 +694,33 @@ create index attr_0_1_thing_orientation
     on "sgd0815"."thing" using btree("orientation");
 
 "#;
+
+const COMPRESSED_GQL: &str = r#"
+type Call @entity {
+    id: ID!,
+    to: Bytes!,
+    input: Bytes! @compressed
+}
+"#;
+
+const INDEX_GQL: &str = r#"
+type Swap @entity @index(fields: ["pair", "timestamp"]) @index(fields: ["amount"]) {
+    id: ID!,
+    pair: String!,
+    timestamp: Int!,
+    amount: BigInt!
+}
+"#;
+
+const JOIN_TABLE_GQL: &str = r#"
+type Group @entity {
+    id: ID!,
+    admins: [User!]!,
+    members: [User!]! @joinTable
+}
+
+type User @entity {
+    id: Bytes!,
+    name: String!
+}
+"#;
@@ -0,0 +1,308 @@
+//! Support for list fields whose ids are stored in a join table instead of
+//! an array column of the entity's table.
+//!
+//! A field `members: [User!]! @joinTable` on `Group` is stored in a table
+//! `group$members` with columns `parent` (the id of the `Group`), `child`
+//! (the id of the `User`) and `block_range`. A row records that `child` was
+//! in the list of `parent` for all blocks in `block_range`. Changing the
+//! list of a group therefore only touches the rows for the ids that were
+//! added or removed, no matter how long the list is.
+//!
+//! The list behaves like a set: the `EntityCache` sorts and dedupes the
+//! list when it is set, and when entities are loaded for indexing the list
+//! is assembled from the join table ordered by the id of the child, so
+//! that mappings see the same list either way. GraphQL queries never
+//! assemble the list but join through the join table instead.
+
+use diesel::{sql_query, sql_types::Integer, PgConnection, RunQueryDsl};
+use graph::{
+    components::store::write::{EntityModification, EntityWrite, RowGroup},
+    constraint_violation,
+    data::{
+        store::{Id, IdList},
+        value::Word,
+    },
+    prelude::{BlockNumber, Entity, StoreError, Value, BLOCK_NUMBER_MAX},
+    schema::{EntityType, Field, InputSchema},
+};
+use std::fmt::{self, Write};
+
+use crate::primary::Namespace;
+use crate::relational_queries::{JoinClampQuery, JoinInsertQuery};
+
+use super::{Catalog, ColumnType, SqlName, Table};
+
+/// The longest name Postgres accepts for an identifier
+const MAX_IDENTIFIER_LENGTH: usize = 63;
+
+/// The join table for one `@joinTable` field of an entity type
+#[derive(Debug, Clone)]
+pub struct JoinTable {
+    /// The GraphQL name of the list field ('members')
+    pub field: Word,
+    /// The key under which the list appears in entity data, i.e., the
+    /// snakecased version of `field`
+    pub column: SqlName,
+    /// The name of the database table ('group$members')
+    pub name: SqlName,
+    /// The table name qualified with the schema in which the table lives
+    pub qualified_name: SqlName,
+    /// The type of the `parent` column, which is the type of the id of the
+    /// entity type that has the list field
+    pub parent_type: ColumnType,
+    /// The entity type of the elements of the list
+    pub child_type: EntityType,
+    /// The type of the `child` column
+    pub child_id_type: ColumnType,
+}
+
+impl JoinTable {
+    pub(crate) fn new(
+        schema: &InputSchema,
+        namespace: &Namespace,
+        table_name: &SqlName,
+        parent_type: ColumnType,
+        field: &Field,
+    ) -> Result<JoinTable, StoreError> {
+        SqlName::check_valid_identifier(&field.name, "attribute")?;
+
+        let column = SqlName::from(&*field.name);
+        let name = SqlName::verbatim(format!("{}${}", table_name, column));
+        if name.as_str().len() > MAX_IDENTIFIER_LENGTH {
+            return Err(StoreError::InvalidIdentifier(format!(
+                "the join table `{}` for field `{}` has a name that is longer than {} characters",
+                name, field.name, MAX_IDENTIFIER_LENGTH
+            )));
+        }
+        let child_type = schema.entity_type(field.field_type.get_base_type())?;
+        let child_id_type = child_type.id_type()?.into();
+
+        Ok(JoinTable {
+            field: field.name.clone(),
+            column,
+            qualified_name: SqlName::qualified_name(namespace, &name),
+            name,
+            parent_type,
+            child_type,
+            child_id_type,
+        })
+    }
+
+    /// Generate the `create table` and `create index` statements for this
+    /// join table
+    pub(crate) fn as_ddl(
+        &self,
+        catalog: &Catalog,
+        as_constraint: bool,
+        out: &mut String,
+    ) -> fmt::Result {
+        let (int4, int8) = catalog.minmax_ops();
+
+        writeln!(
+            out,
+            r#"
+    create table {qname} (
+        vid                  bigserial primary key,
+        parent               {parent_type} not null,
+        child                {child_type} not null,
+        block_range          int4range not null
+    );"#,
+            qname = self.qualified_name,
+            parent_type = self.parent_type.sql_type(),
+            child_type = self.child_id_type.sql_type(),
+        )?;
+        if as_constraint {
+            writeln!(
+                out,
+                "    alter table {qname}\n        \
+                 add constraint \"{name}_excl\" exclude using gist (parent with =, child with =, block_range with &&);",
+                qname = self.qualified_name,
+                name = self.name,
+            )?;
+        } else {
+            writeln!(
+                out,
+                "    create index \"{name}_excl\" on {qname}\n     \
+                 using gist (parent, child, block_range);",
+                qname = self.qualified_name,
+                name = self.name,
+            )?;
+        }
+        write!(
+            out,
+            "create index \"brin_{name}\"\n    \
+             on {qname}\n \
+                using brin(lower(block_range) {int4}, coalesce(upper(block_range), {block_max}) {int4}, vid {int8});\n\
+             create index \"{name}_parent_child\"\n    \
+             on {qname} using btree(parent, child);\n\
+             create index \"{name}_child_parent\"\n    \
+             on {qname} using btree(child, parent);\n\n",
+            qname = self.qualified_name,
+            name = self.name,
+            block_max = BLOCK_NUMBER_MAX
+        )
+    }
+
+    /// The ids of the children stored in `entity`. Since the field is
+    /// non-nullable, a missing value is treated like an empty list
+    fn children(&self, entity: &Entity) -> Result<IdList, StoreError> {
+        let ids = match entity.get(self.field.as_str()) {
+            None | Some(Value::Null) => vec![],
+            Some(Value::List(values)) => values
+                .iter()
+                .cloned()
+                .map(Id::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(value) => {
+                return Err(constraint_violation!(
+                    "the value `{}` for field `{}` is not a list of ids",
+                    value,
+                    self.field
+                ))
+            }
+        };
+        let ids = IdList::try_from_iter(&self.child_type, ids.into_iter())?;
+        Ok(ids.as_unique())
+    }
+
+    /// Make the list of `write.id` equal to the children of `write.entity`
+    /// starting at `write.block`
+    fn write(&self, conn: &PgConnection, write: &EntityWrite) -> Result<(), StoreError> {
+        let children = self.children(write.entity)?;
+        JoinClampQuery::new(self, write.id, write.block, Some(&children)).execute(conn)?;
+        if !children.is_empty() {
+            JoinInsertQuery::new(self, write.id, &children, write.block).execute(conn)?;
+        }
+        if let Some(end) = write.end {
+            JoinClampQuery::new(self, write.id, end, None).execute(conn)?;
+        }
+        Ok(())
+    }
+
+    /// Remove all versions whose entire block range lies beyond `block`
+    /// and make the ones current again that were current at `block - 1`
+    pub(crate) fn revert(&self, conn: &PgConnection, block: BlockNumber) -> Result<(), StoreError> {
+        sql_query(format!(
+            "delete from {qname} where lower(block_range) >= $1",
+            qname = self.qualified_name
+        ))
+        .bind::<Integer, _>(block)
+        .execute(conn)?;
+        sql_query(format!(
+            "update {qname} set block_range = int4range(lower(block_range), null) \
+              where coalesce(upper(block_range), {block_max}) >= $1 \
+                and coalesce(upper(block_range), {block_max}) < {block_max}",
+            qname = self.qualified_name,
+            block_max = BLOCK_NUMBER_MAX
+        ))
+        .bind::<Integer, _>(block)
+        .execute(conn)?;
+        Ok(())
+    }
+
+    /// Delete all rows whose block range was closed before
+    /// `earliest_block`
+    pub(crate) fn prune(
+        &self,
+        conn: &PgConnection,
+        earliest_block: BlockNumber,
+    ) -> Result<usize, StoreError> {
+        let rows = sql_query(format!(
+            "delete from {qname} where coalesce(upper(block_range), {block_max}) <= $1",
+            qname = self.qualified_name,
+            block_max = BLOCK_NUMBER_MAX
+        ))
+        .bind::<Integer, _>(earliest_block)
+        .execute(conn)?;
+        Ok(rows)
+    }
+
+    /// Replace the contents of `self` with the rows of `src` that are
+    /// visible at `target_block`, making them current if they were closed
+    /// after `target_block`
+    pub(crate) fn copy_from(
+        &self,
+        conn: &PgConnection,
+        src: &JoinTable,
+        target_block: BlockNumber,
+    ) -> Result<usize, StoreError> {
+        sql_query(format!("delete from {qname}", qname = self.qualified_name)).execute(conn)?;
+        let rows = sql_query(format!(
+            "insert into {dst}(parent, child, block_range) \
+             select parent, child, \
+                    case when coalesce(upper(block_range), {block_max}) > $1 \
+                         then int4range(lower(block_range), null) \
+                         else block_range end \
+               from {src} where lower(block_range) <= $1",
+            dst = self.qualified_name,
+            src = src.qualified_name,
+            block_max = BLOCK_NUMBER_MAX
+        ))
+        .bind::<Integer, _>(target_block)
+        .execute(conn)?;
+        Ok(rows)
+    }
+
+    /// Check that the data of `source`, which stores the same field, can be
+    /// copied into `self`
+    pub(crate) fn is_assignable_from(&self, source: &Self, object: &EntityType) -> Option<String> {
+        if self.child_id_type != source.child_id_type || self.parent_type != source.parent_type {
+            Some(format!(
+                "The join table for {}.{} stores ids of a different type than the source",
+                object, self.field
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+// Additions to `Table` for maintaining its join tables
+impl Table {
+    /// Find the join table for `field`. The name must be the GraphQL name
+    /// of an entity field
+    pub fn join_table_for_field(&self, field: &str) -> Option<&JoinTable> {
+        self.join_tables.iter().find(|join| join.field == field)
+    }
+
+    /// Find the join table whose list appears under `name` in entity data
+    pub(crate) fn join_table(&self, name: &str) -> Option<&JoinTable> {
+        self.join_tables
+            .iter()
+            .find(|join| join.column.as_str() == name)
+    }
+
+    /// Apply the changes in `group` to the join tables of this table. The
+    /// entity versions in the table itself must already have been clamped
+    /// and inserted
+    pub(crate) fn write_join_tables(
+        &self,
+        conn: &PgConnection,
+        group: &RowGroup,
+    ) -> Result<(), StoreError> {
+        if self.join_tables.is_empty() {
+            return Ok(());
+        }
+
+        // A removal of an entity always precedes any writes for the same
+        // entity in `group`, so handling all removals first is safe
+        for (block, rows) in group.clamps_by_block() {
+            for row in rows {
+                if let EntityModification::Remove { key, .. } = row {
+                    for join in &self.join_tables {
+                        JoinClampQuery::new(join, &key.entity_id, block, None).execute(conn)?;
+                    }
+                }
+            }
+        }
+
+        for row in group.writes() {
+            let write = EntityWrite::try_from(row)
+                .map_err(|_| constraint_violation!("write {:?} has no data", row))?;
+            for join in &self.join_tables {
+                join.write(conn, &write)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -21,6 +21,7 @@ use crate::{
     copy::AdaptiveBatchSize,
     deployment,
     relational::{Table, VID_COLUMN},
+    views,
 };
 
 use super::{Catalog, Layout, Namespace};
@@ -443,7 +444,13 @@ impl Layout {
                         pair.copy_nonfinal_entities(conn, reporter, req.final_block)?;
                         cancel.check_cancel().map_err(CancelableError::from)?;
 
-                        conn.transaction(|| pair.switch(logger, conn))?;
+                        // Views over the table would keep us from dropping
+                        // it; they get recreated over the new table
+                        conn.transaction(|| -> Result<_, StoreError> {
+                            views::drop_all(conn, &self.site)?;
+                            pair.switch(logger, conn)?;
+                            views::recreate(logger, conn, &self.site)
+                        })?;
                         cancel.check_cancel().map_err(CancelableError::from)?;
 
                         Ok(())
@@ -486,6 +493,15 @@ impl Layout {
             }
             reporter.finish_table(table.name.as_str());
         }
+        // Join tables are always pruned by deleting rows; they are narrow
+        // and only change for entities whose lists change
+        for table in self.tables.values() {
+            for join in &table.join_tables {
+                join.prune(conn, req.earliest_block)?;
+                cancel.check_cancel()?;
+            }
+        }
+
         // Get rid of the temporary prune schema if we actually created it
         if !recreate_dst_nsp {
             catalog::drop_schema(conn, dst_nsp.as_str())?;
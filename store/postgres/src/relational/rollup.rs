@@ -0,0 +1,148 @@
+//! Maintain the entities of aggregations by rolling up the data points of
+//! the timeseries they aggregate, see `graph::schema::Aggregation`.
+//!
+//! The entity for an interval is written once the timeseries has a data
+//! point in a later interval. Rollups are immutable entities that are
+//! written at the block that completed their interval, i.e., the block of
+//! the first data point in a later interval, even if the store writes
+//! several blocks at once. Reverting that block removes them, and they are
+//! computed again once the interval is complete again.
+
+use std::fmt::Write;
+
+use diesel::sql_types::{BigInt, Integer, Nullable};
+use diesel::{sql_query, PgConnection, RunQueryDsl};
+use graph::{
+    prelude::{BlockNumber, StoreError},
+    schema::{Aggregation, AggregationInterval, EntityType},
+};
+
+use crate::block_range::{BLOCK_COLUMN, CAUSALITY_REGION_COLUMN};
+
+use super::{Layout, Table};
+
+/// The SQL expression for the start of the interval of length `secs`
+/// that contains the timestamp `ts`. Postgres' `/` and `%` round towards
+/// zero, which would put negative timestamps into the wrong interval; this
+/// rounds down like `i64::div_euclid`
+fn bucket(ts: &str, secs: i64) -> String {
+    format!("({ts} - (({ts} % {secs}) + {secs}) % {secs})")
+}
+
+/// The query that writes the entities for all complete intervals of
+/// `interval` that have not been written yet into `rollup`
+fn rollup_query(
+    aggregation: &Aggregation,
+    interval: AggregationInterval,
+    src: &Table,
+    rollup: &Table,
+) -> Result<String, StoreError> {
+    let secs = interval.as_secs();
+    let timestamp = src.column_for_field("timestamp")?.name.quoted();
+    let rollup_id = rollup.primary_key().name.quoted();
+    let rollup_timestamp = rollup.column_for_field("timestamp")?.name.quoted();
+
+    // The interval was completed by the first data point after it
+    let block = format!(
+        "(select min(n.{BLOCK_COLUMN}) from {src_table} n \
+          where n.{timestamp} >= d.bucket + {secs})",
+        src_table = src.qualified_name
+    );
+
+    let mut columns = format!("{rollup_id}, {rollup_timestamp}, {BLOCK_COLUMN}");
+    let mut values = format!("d.bucket, d.bucket, {block}");
+    if rollup.has_causality_region {
+        // Aggregations are only maintained for onchain data
+        write!(columns, ", {CAUSALITY_REGION_COLUMN}")?;
+        write!(values, ", 0")?;
+    }
+    for aggregate in &aggregation.aggregates {
+        let column = rollup.column_for_field(aggregate.name.as_str())?;
+        let arg = match &aggregate.arg {
+            Some(arg) => format!("d.{}", src.column_for_field(arg.as_str())?.name.quoted()),
+            None => "*".to_string(),
+        };
+        write!(columns, ", {}", column.name.quoted())?;
+        write!(
+            values,
+            ", {}({})::{}",
+            aggregate.func.as_str(),
+            arg,
+            column.column_type.sql_type()
+        )?;
+    }
+    // `start` is the first interval without a rollup, and `end` the
+    // interval of the latest data point which is not complete yet. The
+    // bounds take the interval of `min` and `max` of the timestamps rather
+    // than the other way around so that they can use the index on the
+    // timestamp instead of scanning the whole timeseries
+    let query = format!(
+        "with ts as (
+           select min({timestamp}) as first_ts, max({timestamp}) as last_ts from {src_table}),
+         bounds as (
+           select coalesce((select max({rollup_timestamp}) + {secs} from {rollup_table}),
+                           {first_bucket}) as start,
+                  {last_bucket} as \"end\"
+             from ts)
+         insert into {rollup_table}({columns})
+         select {values}
+           from (select s.*, {bucket} as bucket
+                   from {src_table} s, bounds b
+                  where s.{timestamp} >= b.start
+                    and s.{timestamp} < b.\"end\") d
+          group by d.bucket
+          order by d.bucket",
+        rollup_table = rollup.qualified_name,
+        src_table = src.qualified_name,
+        first_bucket = bucket("ts.first_ts", secs),
+        last_bucket = bucket("ts.last_ts", secs),
+        bucket = bucket(&format!("s.{timestamp}"), secs),
+    );
+    Ok(query)
+}
+
+impl Layout {
+    /// Write the entities for all intervals of the aggregations of this
+    /// layout that are complete and have not been written yet. Return the
+    /// number of entities that were written
+    pub fn rollup(&self, conn: &PgConnection) -> Result<i32, StoreError> {
+        let mut count = 0;
+        for aggregation in self.input_schema.aggregations() {
+            let src = self
+                .table_for_entity(&self.input_schema.entity_type(aggregation.source.as_str())?)?;
+            for (interval, name) in &aggregation.intervals {
+                let rollup =
+                    self.table_for_entity(&self.input_schema.entity_type(name.as_str())?)?;
+                let query = rollup_query(aggregation, *interval, src, rollup)?;
+                count += sql_query(query).execute(conn)? as i32;
+            }
+        }
+        Ok(count)
+    }
+
+    /// The largest timestamp of the data points of the timeseries
+    /// `entity_type` that were written at or before `block`
+    pub fn max_timestamp(
+        &self,
+        conn: &PgConnection,
+        entity_type: &EntityType,
+        block: BlockNumber,
+    ) -> Result<Option<i64>, StoreError> {
+        #[derive(QueryableByName)]
+        struct MaxTimestamp {
+            #[sql_type = "Nullable<BigInt>"]
+            timestamp: Option<i64>,
+        }
+
+        let table = self.table_for_entity(entity_type)?;
+        let query = format!(
+            "select max({timestamp}) as timestamp from {table} where {BLOCK_COLUMN} <= $1",
+            timestamp = table.column_for_field("timestamp")?.name.quoted(),
+            table = table.qualified_name,
+        );
+        let max = sql_query(query)
+            .bind::<Integer, _>(block)
+            .get_result::<MaxTimestamp>(conn)?;
+        Ok(max.timestamp)
+    }
+}
@@ -9,6 +9,7 @@ use diesel::pg::{Pg, PgConnection};
 use diesel::query_builder::{AstPass, QueryFragment, QueryId};
 use diesel::query_dsl::{LoadQuery, RunQueryDsl};
 use diesel::result::{Error as DieselError, QueryResult};
+use diesel::row::NamedRow;
 use diesel::sql_types::{Array, BigInt, Binary, Bool, Int8, Integer, Jsonb, Range, Text};
 use diesel::Connection;
 
@@ -35,8 +36,8 @@ use std::str::FromStr;
 
 use crate::block_range::BlockRange;
 use crate::relational::{
-    Column, ColumnType, Layout, SqlName, Table, BYTE_ARRAY_PREFIX_SIZE, PRIMARY_KEY_COLUMN,
-    STRING_PREFIX_SIZE,
+    Column, ColumnType, JoinTable, Layout, SqlName, Table, BYTE_ARRAY_PREFIX_SIZE,
+    PRIMARY_KEY_COLUMN, STRING_PREFIX_SIZE,
 };
 use crate::{
     block_range::{
@@ -471,10 +472,19 @@ pub struct EntityData {
 }
 
 impl EntityData {
+    pub(crate) fn new(entity: String, data: serde_json::Value) -> Self {
+        EntityData { entity, data }
+    }
+
     pub fn entity_type(&self, schema: &InputSchema) -> EntityType {
         schema.entity_type(&self.entity).unwrap()
     }
 
+    /// The raw data of the row, including internal columns like `vid`
+    pub(crate) fn into_data(self) -> serde_json::Value {
+        self.data
+    }
+
     /// Map the `EntityData` using the schema information in `Layout`
     pub fn deserialize_with_layout<T: FromEntityData>(
         self,
@@ -521,17 +531,20 @@ impl EntityData {
                 });
                 let entries = map.into_iter().filter_map(move |(key, json)| {
                     // Simply ignore keys that do not have an underlying
-                    // table column; those will be things like the
-                    // block_range that `select *` pulls in but that we
-                    // don't care about here
-                    if let Some(column) = table.column(&SqlName::verbatim(key)) {
-                        match T::Value::from_column_value(&column.column_type, json) {
-                            Ok(value) if value.is_null() => None,
-                            Ok(value) => Some(Ok((Word::from(column.field.to_string()), value))),
-                            Err(e) => Some(Err(e)),
-                        }
+                    // table column or join table; those will be things
+                    // like the block_range that `select *` pulls in but
+                    // that we don't care about here
+                    let (field, column_type) = if let Some(join) = table.join_table(&key) {
+                        (&join.field, &join.child_id_type)
+                    } else if let Some(column) = table.column(&SqlName::verbatim(key)) {
+                        (&column.field, &column.column_type)
                     } else {
-                        None
+                        return None;
+                    };
+                    match T::Value::from_column_value(column_type, json) {
+                        Ok(value) if value.is_null() => None,
+                        Ok(value) => Some(Ok((Word::from(field.to_string()), value))),
+                        Err(e) => Some(Err(e)),
                     }
                 });
                 T::from_data(&layout.input_schema, parent_id, typname.chain(entries))
@@ -947,12 +960,19 @@ impl<'a> QueryFilter<'a> {
                 if child.derived {
                     let derived_table = layout.table_for_entity(&child.entity_type)?;
                     // Make sure that the attribute name is valid for the given table
-                    derived_table.column_for_field(child.attr.as_str())?;
+                    if derived_table
+                        .join_table_for_field(child.attr.as_str())
+                        .is_none()
+                    {
+                        derived_table.column_for_field(child.attr.as_str())?;
+                    }
 
                     Self::valid_attributes(&child.filter, derived_table, layout, true)?;
                 } else {
                     // Make sure that the attribute name is valid for the given table
-                    table.column_for_field(child.attr.as_str())?;
+                    if table.join_table_for_field(child.attr.as_str()).is_none() {
+                        table.column_for_field(child.attr.as_str())?;
+                    }
 
                     Self::valid_attributes(
                         &child.filter,
@@ -965,6 +985,8 @@ impl<'a> QueryFilter<'a> {
             // This is a special case since we want to allow passing "block" column filter, but we dont
             // want to fail/error when this is passed here, since this column is not really an entity column.
             ChangeBlockGte(..) => {}
+            Contains(attr, _) | NotContains(attr, _)
+                if table.join_table_for_field(attr).is_some() => {}
             Contains(attr, _)
             | ContainsNoCase(attr, _)
             | NotContains(attr, _)
@@ -1026,8 +1048,23 @@ impl<'a> QueryFilter<'a> {
 
         let mut is_type_c_or_d = false;
 
+        let join = if derived {
+            child_table.join_table_for_field(attribute)
+        } else {
+            self.table.join_table_for_field(attribute)
+        };
+
         // Join tables
-        if derived {
+        if let Some(join) = join {
+            if derived {
+                // The child stores the ids of its parents in a join table
+                join_exists(join, "i.id", "c.id", self.block, &mut out)?;
+            } else {
+                // The parent stores the ids of its children in a join table
+                is_type_c_or_d = true;
+                join_exists(join, "c.id", "i.id", self.block, &mut out)?;
+            }
+        } else if derived {
             // If the parent is derived,
             // the child column is picked based on the provided attribute
             // and the parent column is the primary key of the parent table
@@ -1105,6 +1142,43 @@ impl<'a> QueryFilter<'a> {
         Ok(())
     }
 
+    /// Generate the SQL for `contains` and `not_contains` filters on a list
+    /// that is stored in `join`. The list contains a value if every element
+    /// of `value` is in the join table for the current entity; it does not
+    /// contain `value` if none of its elements are in the join table
+    fn join_contains(
+        &self,
+        join: &JoinTable,
+        value: &Value,
+        negated: bool,
+        mut out: AstPass<Pg>,
+    ) -> QueryResult<()> {
+        let parent = format!("{}id", self.table_prefix);
+        if negated {
+            // not exists (select 1 from {join} j
+            //              where j.parent = c.id and j.block_range @> $block
+            //                and j.child = any($value))
+            out.push_sql("not exists (select 1 from ");
+            out.push_sql(join.qualified_name.as_str());
+            out.push_sql(" j where j.parent = ");
+            out.push_sql(&parent);
+            out.push_sql(" and j.block_range @> ");
+            out.push_bind_param::<Integer, _>(&self.block)?;
+            out.push_sql(" and j.child = any(");
+            QueryValue(value, &join.child_id_type).walk_ast(out.reborrow())?;
+            out.push_sql("))");
+        } else {
+            // not exists (select 1 from unnest($value) as l(child)
+            //              where not {join_exists(c.id, l.child)})
+            out.push_sql("not exists (select 1 from unnest(");
+            QueryValue(value, &join.child_id_type).walk_ast(out.reborrow())?;
+            out.push_sql(") as l(child) where not ");
+            join_exists(join, &parent, "l.child", self.block, &mut out)?;
+            out.push_sql(")");
+        }
+        Ok(())
+    }
+
     fn column(&self, attribute: &Attribute) -> &'a Column {
         self.table
             .column_for_field(attribute)
@@ -1141,6 +1215,17 @@ impl<'a> QueryFilter<'a> {
         strict: bool,
         mut out: AstPass<Pg>,
     ) -> QueryResult<()> {
+        if let Some(join) = self.table.join_table_for_field(attribute) {
+            return match value {
+                Value::List(_) if strict => self.join_contains(join, value, negated, out),
+                _ => Err(UnsupportedFilter {
+                    filter: if negated { "not_contains" } else { "contains" }.to_owned(),
+                    value: value.clone(),
+                }
+                .into()),
+            };
+        }
+
         let column = self.column(attribute);
         let operation = match (strict, negated) {
             (true, true) => " not like ",
@@ -1490,11 +1575,13 @@ impl<'a> QueryFragment<Pg> for FindQuery<'a> {
         out.unsafe_to_cache_prepared();
 
         // Generate
-        //    select '..' as entity, to_jsonb(e.*) as data
+        //    select '..' as entity, to_jsonb(e.*) {join_values} as data
         //      from schema.table e where id = $1
         out.push_sql("select ");
         out.push_bind_param::<Text, _>(&self.table.object.as_str())?;
-        out.push_sql(" as entity, to_jsonb(e.*) as data\n");
+        out.push_sql(" as entity, to_jsonb(e.*)");
+        join_table_values(self.table, "e.", self.block, &mut out)?;
+        out.push_sql(" as data\n");
         out.push_sql("  from ");
         out.push_sql(self.table.qualified_name.as_str());
         out.push_sql(" e\n where ");
@@ -1523,6 +1610,139 @@ impl<'a> LoadQuery<PgConnection, EntityData> for FindQuery<'a> {
 
 impl<'a, Conn> RunQueryDsl<Conn> for FindQuery<'a> {}
 
+/// A query that returns the versions of entities in `table` that are
+/// visible at `block`, ordered by `vid`. The results are paginated with
+/// `after_vid` and `limit` so that large tables can be exported in batches
+#[derive(Debug, Clone, Constructor)]
+pub struct ExportQuery<'a> {
+    table: &'a Table,
+    block: BlockNumber,
+    after_vid: i64,
+    limit: i64,
+}
+
+impl<'a> QueryFragment<Pg> for ExportQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        // Generate
+        //    select '..' as entity, to_jsonb(e.*) {join_values} as data
+        //      from schema.table e
+        //     where e.vid > $after and {block_range contains $block}
+        //     order by e.vid limit $limit
+        out.push_sql("select ");
+        out.push_bind_param::<Text, _>(&self.table.object.as_str())?;
+        out.push_sql(" as entity, to_jsonb(e.*)");
+        join_table_values(self.table, "e.", self.block, &mut out)?;
+        out.push_sql(" as data\n");
+        out.push_sql("  from ");
+        out.push_sql(self.table.qualified_name.as_str());
+        out.push_sql(" e\n where e.vid > ");
+        out.push_bind_param::<BigInt, _>(&self.after_vid)?;
+        out.push_sql(" and ");
+        BlockRangeColumn::new(self.table, "e.", self.block).contains(&mut out, false)?;
+        out.push_sql("\n order by e.vid limit ");
+        out.push_bind_param::<BigInt, _>(&self.limit)
+    }
+}
+
+impl<'a> QueryId for ExportQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a> LoadQuery<PgConnection, EntityData> for ExportQuery<'a> {
+    fn internal_load(self, conn: &PgConnection) -> QueryResult<Vec<EntityData>> {
+        conn.query_by_name(&self)
+    }
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for ExportQuery<'a> {}
+
+/// A query that returns the versions of entities in `table` that are
+/// visible at `block`, ordered by `id` and then `causality_region`. Ids
+/// are compared bytewise regardless of the collation of the database. The
+/// results are paginated with `after`, the id and causality region of
+/// the last entity of the previous page, and `limit`
+#[derive(Debug, Clone, Constructor)]
+pub struct OrderedExportQuery<'a> {
+    table: &'a Table,
+    block: BlockNumber,
+    after: Option<(Id, CausalityRegion)>,
+    limit: i64,
+}
+
+impl<'a> OrderedExportQuery<'a> {
+    fn push_id(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.push_sql("e.id");
+        if matches!(
+            self.table.primary_key().column_type.id_type(),
+            Ok(IdType::String)
+        ) {
+            out.push_sql(" collate \"C\"");
+        }
+        Ok(())
+    }
+}
+
+impl<'a> QueryFragment<Pg> for OrderedExportQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        // Generate
+        //    select '..' as entity, to_jsonb(e.*) {join_values} as data
+        //      from schema.table e
+        //     where (e.id, e.causality_region) > ($id, $cr)
+        //       and {block_range contains $block}
+        //     order by e.id, e.causality_region limit $limit
+        out.push_sql("select ");
+        out.push_bind_param::<Text, _>(&self.table.object.as_str())?;
+        out.push_sql(" as entity, to_jsonb(e.*)");
+        join_table_values(self.table, "e.", self.block, &mut out)?;
+        out.push_sql(" as data\n");
+        out.push_sql("  from ");
+        out.push_sql(self.table.qualified_name.as_str());
+        out.push_sql(" e\n where ");
+        if let Some((id, causality_region)) = &self.after {
+            out.push_sql("(");
+            self.push_id(out.reborrow())?;
+            if self.table.has_causality_region {
+                out.push_sql(", e.causality_region");
+            }
+            out.push_sql(") > (");
+            id.push_bind_param(&mut out)?;
+            if self.table.has_causality_region {
+                out.push_sql(", ");
+                out.push_bind_param::<Integer, _>(causality_region)?;
+            }
+            out.push_sql(") and ");
+        }
+        BlockRangeColumn::new(self.table, "e.", self.block).contains(&mut out, false)?;
+        out.push_sql("\n order by ");
+        self.push_id(out.reborrow())?;
+        if self.table.has_causality_region {
+            out.push_sql(", e.causality_region");
+        }
+        out.push_sql(" limit ");
+        out.push_bind_param::<BigInt, _>(&self.limit)
+    }
+}
+
+impl<'a> QueryId for OrderedExportQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a> LoadQuery<PgConnection, EntityData> for OrderedExportQuery<'a> {
+    fn internal_load(self, conn: &PgConnection) -> QueryResult<Vec<EntityData>> {
+        conn.query_by_name(&self)
+    }
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for OrderedExportQuery<'a> {}
+
 /// Builds a query over a given set of [`Table`]s in an attempt to find updated
 /// and/or newly inserted entities at a given block number; i.e. such that the
 /// block range's lower bound is equal to said block number.
@@ -1543,7 +1763,9 @@ impl<'a> QueryFragment<Pg> for FindChangesQuery<'a> {
             }
             out.push_sql("select ");
             out.push_bind_param::<Text, _>(&table.object.as_str())?;
-            out.push_sql(" as entity, to_jsonb(e.*) as data\n");
+            out.push_sql(" as entity, to_jsonb(e.*)");
+            join_table_values(table, "e.", self.block, &mut out)?;
+            out.push_sql(" as data\n");
             out.push_sql("  from ");
             out.push_sql(table.qualified_name.as_str());
             out.push_sql(" e\n where ");
@@ -1651,7 +1873,9 @@ impl<'a> QueryFragment<Pg> for FindManyQuery<'a> {
             }
             out.push_sql("select ");
             out.push_bind_param::<Text, _>(&table.object.as_str())?;
-            out.push_sql(" as entity, to_jsonb(e.*) as data\n");
+            out.push_sql(" as entity, to_jsonb(e.*)");
+            join_table_values(table, "e.", self.block, &mut out)?;
+            out.push_sql(" as data\n");
             out.push_sql("  from ");
             out.push_sql(table.qualified_name.as_str());
             out.push_sql(" e\n where ");
@@ -1706,11 +1930,13 @@ impl<'a> QueryFragment<Pg> for FindDerivedQuery<'a> {
         } = self.derived_query;
 
         // Generate
-        //    select '..' as entity, to_jsonb(e.*) as data
+        //    select '..' as entity, to_jsonb(e.*) {join_values} as data
         //      from schema.table e where field = $1
         out.push_sql("select ");
         out.push_bind_param::<Text, _>(&self.table.object.as_str())?;
-        out.push_sql(" as entity, to_jsonb(e.*) as data\n");
+        out.push_sql(" as entity, to_jsonb(e.*)");
+        join_table_values(self.table, "e.", self.block, &mut out)?;
+        out.push_sql(" as data\n");
         out.push_sql("  from ");
         out.push_sql(self.table.qualified_name.as_str());
         out.push_sql(" e\n where ");
@@ -1728,9 +1954,22 @@ impl<'a> QueryFragment<Pg> for FindDerivedQuery<'a> {
             }
             out.push_sql(") and ");
         }
-        out.push_identifier(entity_field.to_snake_case().as_str())?;
-        out.push_sql(" = ");
-        entity_id.push_bind_param(&mut out)?;
+        if let Some(join) = self.table.join_table_for_field(entity_field.as_str()) {
+            // Generate
+            //    e.id in (select j.parent from {join} j
+            //              where j.child = $1 and j.block_range @> $block)
+            out.push_sql("e.id in (select j.parent from ");
+            out.push_sql(join.qualified_name.as_str());
+            out.push_sql(" j where j.child = ");
+            entity_id.push_bind_param(&mut out)?;
+            out.push_sql(" and j.block_range @> ");
+            out.push_bind_param::<Integer, _>(&self.block)?;
+            out.push_sql(")");
+        } else {
+            out.push_identifier(entity_field.to_snake_case().as_str())?;
+            out.push_sql(" = ");
+            entity_id.push_bind_param(&mut out)?;
+        }
         out.push_sql(" and ");
         if self.table.has_causality_region {
             out.push_sql("causality_region = ");
@@ -2039,6 +2278,13 @@ impl ParentIds {
         let link = match link {
             ParentLink::Scalar(child_ids) => ParentIds::Scalar(child_ids),
             ParentLink::List(child_ids) => ParentIds::List(child_ids),
+            ParentLink::Join(field) => {
+                return Err(graph::constraint_violation!(
+                    "the field `{}` is stored in a join table and has no parent ids",
+                    field
+                )
+                .into())
+            }
         };
         Ok(link)
     }
@@ -2051,6 +2297,10 @@ enum TableLink<'a> {
     Direct(&'a Column, ChildMultiplicity),
     /// The `Table` is the parent table
     Parent(&'a Table, ParentIds),
+    /// The parent stores the ids of the children in the join table
+    JoinParent(&'a JoinTable),
+    /// The child stores the ids of the parents in the join table
+    JoinChild(&'a JoinTable, ChildMultiplicity),
 }
 
 impl<'a> TableLink<'a> {
@@ -2061,9 +2311,19 @@ impl<'a> TableLink<'a> {
     ) -> Result<Self, QueryExecutionError> {
         match link {
             EntityLink::Direct(attribute, multiplicity) => {
+                if let Some(join) = child_table.join_table_for_field(attribute.name()) {
+                    return Ok(TableLink::JoinChild(join, multiplicity));
+                }
                 let column = child_table.column_for_field(attribute.name())?;
                 Ok(TableLink::Direct(column, multiplicity))
             }
+            EntityLink::Parent(parent_type, ParentLink::Join(field)) => {
+                let parent_table = layout.table_for_entity(&parent_type)?;
+                let join = parent_table
+                    .join_table_for_field(field.as_str())
+                    .ok_or_else(|| StoreError::UnknownField(field.to_string()))?;
+                Ok(TableLink::JoinParent(join))
+            }
             EntityLink::Parent(parent_type, parent_link) => {
                 let parent_table = layout.table_for_entity(&parent_type)?;
                 Ok(TableLink::Parent(
@@ -2179,6 +2439,8 @@ impl<'a> FilterWindow<'a> {
         match &self.link {
             TableLink::Direct(column, _) => column.column_type.id_type(),
             TableLink::Parent(parent_table, _) => parent_table.primary_key().column_type.id_type(),
+            TableLink::JoinParent(join) => join.parent_type.id_type(),
+            TableLink::JoinChild(join, _) => join.child_id_type.id_type(),
         }
     }
 
@@ -2445,6 +2707,87 @@ impl<'a> FilterWindow<'a> {
         Ok(())
     }
 
+    /// Generate the condition that child `c` is linked to parent `p`
+    /// through `join`. If the child stores the ids of its parents in
+    /// `join`, the roles of `p` and `c` are swapped
+    fn join_exists(
+        &self,
+        join: &JoinTable,
+        parent_stores_ids: bool,
+        block: BlockNumber,
+        out: &mut AstPass<Pg>,
+    ) -> QueryResult<()> {
+        if parent_stores_ids {
+            join_exists(join, "p.id", "c.id", block, out)
+        } else {
+            join_exists(join, "c.id", "p.id", block, out)
+        }
+    }
+
+    fn children_join(
+        &self,
+        join: &JoinTable,
+        parent_stores_ids: bool,
+        limit: ParentLimit<'_>,
+        block: BlockNumber,
+        out: &mut AstPass<Pg>,
+    ) -> QueryResult<()> {
+        // Generate
+        //      from unnest({parent_ids}) as p(id)
+        //           cross join lateral
+        //           (select {column names}
+        //              from children c
+        //             where {join_exists}
+        //               and .. other conditions on c ..
+        //             order by c.{sort_key}
+        //             limit {first} offset {skip}) c
+        //     order by c.{sort_key}
+
+        out.push_sql("\n/* children_join */  from unnest(");
+        self.ids.push_bind_param(out)?;
+        out.push_sql(") as p(id) cross join lateral (select ");
+        write_column_names(&self.column_names, self.table, None, out)?;
+        out.push_sql(" from ");
+        out.push_sql(self.table.qualified_name.as_str());
+        out.push_sql(" c where ");
+        BlockRangeColumn::new(self.table, "c.", block).contains(out, false)?;
+        limit.filter(out);
+        out.push_sql(" and ");
+        self.join_exists(join, parent_stores_ids, block, out)?;
+        self.and_filter(out.reborrow())?;
+        limit.restrict(out)?;
+        out.push_sql(") c");
+        Ok(())
+    }
+
+    fn child_join(
+        &self,
+        join: &JoinTable,
+        parent_stores_ids: bool,
+        limit: ParentLimit<'_>,
+        block: BlockNumber,
+        out: &mut AstPass<Pg>,
+    ) -> QueryResult<()> {
+        // Generate
+        //      from unnest({parent_ids}) as p(id), children c
+        //     where {join_exists}
+        //       and .. other conditions on c ..
+        //     limit {parent_ids.len} + 1
+
+        out.push_sql("\n/* child_join */  from unnest(");
+        self.ids.push_bind_param(out)?;
+        out.push_sql(") as p(id), ");
+        out.push_sql(self.table.qualified_name.as_str());
+        out.push_sql(" c where ");
+        BlockRangeColumn::new(self.table, "c.", block).contains(out, false)?;
+        limit.filter(out);
+        out.push_sql(" and ");
+        self.join_exists(join, parent_stores_ids, block, out)?;
+        self.and_filter(out.reborrow())?;
+        limit.single_limit(self.ids.len(), out);
+        Ok(())
+    }
+
     fn children(
         &self,
         limit: ParentLimit<'_>,
@@ -2472,6 +2815,13 @@ impl<'a> FilterWindow<'a> {
             TableLink::Parent(_, ParentIds::Scalar(child_ids)) => {
                 self.child_type_d(child_ids, limit, block, &mut out)
             }
+            TableLink::JoinParent(join) => self.children_join(join, true, limit, block, &mut out),
+            TableLink::JoinChild(join, ChildMultiplicity::Many) => {
+                self.children_join(join, false, limit, block, &mut out)
+            }
+            TableLink::JoinChild(join, ChildMultiplicity::Single) => {
+                self.child_join(join, false, limit, block, &mut out)
+            }
         }
     }
 
@@ -2565,6 +2915,9 @@ impl<'a> fmt::Display for FilterCollection<'a> {
                     TableLink::Parent(_, ParentIds::Scalar(cs)) => {
                         write!(f, "uniq:id={}", cs.iter().map(|c| c.to_string()).join(","))?
                     }
+                    TableLink::JoinParent(join) => write!(f, "join:{}", join.name)?,
+                    TableLink::JoinChild(join, Single) => write!(f, "uniq:join:{}", join.name)?,
+                    TableLink::JoinChild(join, Many) => write!(f, "many:join:{}", join.name)?,
                 };
                 write!(f, " for {}>", ids.join(","))?;
             }
@@ -4166,6 +4519,50 @@ impl<'a> LoadQuery<PgConnection, EntityData> for FilterQuery<'a> {
 
 impl<'a, Conn> RunQueryDsl<Conn> for FilterQuery<'a> {}
 
+/// Show how Postgres executes a `FilterQuery` with `EXPLAIN (ANALYZE,
+/// BUFFERS)`. Note that this runs the query
+#[derive(Debug)]
+pub struct ExplainQuery<'a> {
+    query: &'a FilterQuery<'a>,
+}
+
+impl<'a> ExplainQuery<'a> {
+    pub fn new(query: &'a FilterQuery<'a>) -> Self {
+        ExplainQuery { query }
+    }
+}
+
+impl<'a> QueryFragment<Pg> for ExplainQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+        out.push_sql("explain (analyze, buffers) ");
+        self.query.walk_ast(out.reborrow())
+    }
+}
+
+impl<'a> QueryId for ExplainQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+/// One line of the output of `EXPLAIN`
+pub struct PlanLine(pub String);
+
+impl diesel::deserialize::QueryableByName<Pg> for PlanLine {
+    fn build<R: NamedRow<Pg>>(row: &R) -> diesel::deserialize::Result<Self> {
+        row.get::<Text, String>("QUERY PLAN").map(PlanLine)
+    }
+}
+
+impl<'a> LoadQuery<PgConnection, PlanLine> for ExplainQuery<'a> {
+    fn internal_load(self, conn: &PgConnection) -> QueryResult<Vec<PlanLine>> {
+        conn.query_by_name(&self)
+    }
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for ExplainQuery<'a> {}
+
 /// Reduce the upper bound of the current entry's block range to `block` as
 /// long as that does not result in an empty block range
 #[derive(Debug)]
@@ -4227,6 +4624,112 @@ impl<'a> QueryId for ClampRangeQuery<'a> {
 
 impl<'a, Conn> RunQueryDsl<Conn> for ClampRangeQuery<'a> {}
 
+/// Take the children of `parent` out of its list in a join table at
+/// `block`, except for the ones in `keep`. Children that were only added
+/// at `block` or later are deleted; the rows of all other children are
+/// clamped to end at `block`
+#[derive(Debug, Constructor)]
+pub struct JoinClampQuery<'a> {
+    join: &'a JoinTable,
+    parent: &'a Id,
+    block: BlockNumber,
+    keep: Option<&'a IdList>,
+}
+
+impl<'a> JoinClampQuery<'a> {
+    fn filter(&self, lower: &str, out: &mut AstPass<Pg>) -> QueryResult<()> {
+        out.push_sql(" where parent = ");
+        self.parent.push_bind_param(out)?;
+        out.push_sql(" and upper_inf(block_range) and lower(block_range) ");
+        out.push_sql(lower);
+        out.push_sql(" ");
+        out.push_bind_param::<Integer, _>(&self.block)?;
+        if let Some(keep) = self.keep {
+            out.push_sql(" and not child = any(");
+            keep.push_bind_param(out)?;
+            out.push_sql(")");
+        }
+        Ok(())
+    }
+}
+
+impl<'a> QueryFragment<Pg> for JoinClampQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        // with deleted as (
+        //   delete from {join}
+        //    where parent = $parent and upper_inf(block_range)
+        //      and lower(block_range) >= $block
+        //      and not child = any($keep))
+        // update {join}
+        //    set block_range = int4range(lower(block_range), $block)
+        //  where parent = $parent and upper_inf(block_range)
+        //    and lower(block_range) < $block
+        //    and not child = any($keep)
+        out.unsafe_to_cache_prepared();
+        out.push_sql("with deleted as (delete from ");
+        out.push_sql(self.join.qualified_name.as_str());
+        self.filter(">=", &mut out)?;
+        out.push_sql(")\nupdate ");
+        out.push_sql(self.join.qualified_name.as_str());
+        out.push_sql("\n   set block_range = int4range(lower(block_range), ");
+        out.push_bind_param::<Integer, _>(&self.block)?;
+        out.push_sql(")\n");
+        self.filter("<", &mut out)
+    }
+}
+
+impl<'a> QueryId for JoinClampQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for JoinClampQuery<'a> {}
+
+/// Add the `children` that are not in the current list of `parent` in a
+/// join table to that list, starting at `block`
+#[derive(Debug, Constructor)]
+pub struct JoinInsertQuery<'a> {
+    join: &'a JoinTable,
+    parent: &'a Id,
+    children: &'a IdList,
+    block: BlockNumber,
+}
+
+impl<'a> QueryFragment<Pg> for JoinInsertQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        // insert into {join}(parent, child, block_range)
+        // select $parent, l.child, int4range($block, null)
+        //   from (select distinct child from unnest($children) as l(child)) l
+        //  where not exists (select 1 from {join} j
+        //                     where j.parent = $parent and j.child = l.child
+        //                       and upper_inf(j.block_range))
+        out.unsafe_to_cache_prepared();
+        out.push_sql("insert into ");
+        out.push_sql(self.join.qualified_name.as_str());
+        out.push_sql("(parent, child, block_range)\nselect ");
+        self.parent.push_bind_param(&mut out)?;
+        out.push_sql(", l.child, int4range(");
+        out.push_bind_param::<Integer, _>(&self.block)?;
+        out.push_sql(", null)\n  from (select distinct child from unnest(");
+        self.children.push_bind_param(&mut out)?;
+        out.push_sql(") as l(child)) l\n where not exists (select 1 from ");
+        out.push_sql(self.join.qualified_name.as_str());
+        out.push_sql(" j where j.parent = ");
+        self.parent.push_bind_param(&mut out)?;
+        out.push_sql(" and j.child = l.child and upper_inf(j.block_range))");
+        Ok(())
+    }
+}
+
+impl<'a> QueryId for JoinInsertQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for JoinInsertQuery<'a> {}
+
 /// Helper struct for returning the id's touched by the RevertRemove and
 /// RevertExtend queries
 #[derive(QueryableByName, PartialEq, Eq, Hash)]
@@ -4583,6 +5086,64 @@ fn write_column_names(
     Ok(())
 }
 
+/// Generate the condition that `parent` stores `child` in `join`:
+///
+///   exists (select 1 from {join} j
+///            where j.parent = {parent} and j.child = {child}
+///              and j.block_range @> $block)
+fn join_exists(
+    join: &JoinTable,
+    parent: &str,
+    child: &str,
+    block: BlockNumber,
+    out: &mut AstPass<Pg>,
+) -> QueryResult<()> {
+    out.push_sql("exists (select 1 from ");
+    out.push_sql(join.qualified_name.as_str());
+    out.push_sql(" j where j.parent = ");
+    out.push_sql(parent);
+    out.push_sql(" and j.child = ");
+    out.push_sql(child);
+    out.push_sql(" and j.block_range @> ");
+    out.push_bind_param::<Integer, _>(&block)?;
+    out.push_sql(")");
+    Ok(())
+}
+
+/// Add the lists stored in the join tables of `table` to the entity data
+/// for the row `{prefix}id` by generating
+///
+///   || jsonb_build_object('{column}',
+///          array(select j.child from {join} j
+///                 where j.parent = {prefix}id and j.block_range @> $block
+///                 order by j.child))
+///
+/// for each join table. String ids are ordered with the `C` collation so
+/// that the order matches the order of the ids in the `EntityCache`
+fn join_table_values(
+    table: &Table,
+    prefix: &str,
+    block: BlockNumber,
+    out: &mut AstPass<Pg>,
+) -> QueryResult<()> {
+    for join in &table.join_tables {
+        out.push_sql(" || jsonb_build_object('");
+        out.push_sql(join.column.as_str());
+        out.push_sql("', array(select j.child from ");
+        out.push_sql(join.qualified_name.as_str());
+        out.push_sql(" j where j.parent = ");
+        out.push_sql(prefix);
+        out.push_sql("id and j.block_range @> ");
+        out.push_bind_param::<Integer, _>(&block)?;
+        out.push_sql(" order by j.child");
+        if join.child_id_type == ColumnType::String {
+            out.push_sql(" collate \"C\"");
+        }
+        out.push_sql("))");
+    }
+    Ok(())
+}
+
 fn jsonb_build_object(
     column_names: &AttributeNames,
     table_identifier: &str,
@@ -0,0 +1,509 @@
+//! Export the data of a deployment as it was at a certain block into a
+//! portable snapshot, and import such a snapshot into another, empty
+//! deployment of the same subgraph. That makes it possible to move a
+//! deployment to another installation without indexing it from scratch.
+//!
+//! A snapshot is a directory with the following files:
+//!
+//! - `snapshot.json`: the [`Snapshot`] metadata that describes the
+//!   deployment and the block at which the snapshot was taken
+//! - `data_sources.jsonl`: the dynamic data sources that were live at the
+//!   block, one per line
+//! - `entities/<EntityType>.jsonl`: the entities of each type that were
+//!   visible at the block, one per line, in the JSON format that
+//!   `to_jsonb` produces for the entity table. The proof of indexing is
+//!   stored like any other entity type
+//!
+//! Entities lose their history when they are exported: on import, every
+//! entity starts its life at the block of the snapshot, and the imported
+//! deployment's earliest block is set to that block.
+//!
+//! Independently of snapshots, the entities of a deployment at a block can
+//! also be exported in the canonical form from
+//! [`graph::data::store::canonical`] so that they can be hashed and
+//! compared with the data of other indexers.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use diesel::{PgConnection, RunQueryDsl};
+use graph::anyhow::Context;
+use graph::blockchain::block_stream::FirehoseCursor;
+use graph::blockchain::{BlockHash, BlockPtr};
+use graph::components::store::write::{EntityModification, RowGroup};
+use graph::components::store::StoredDynamicDataSource;
+use graph::constraint_violation;
+use graph::data::store::canonical::canonical_json;
+use graph::data::store::scalar::Bytes;
+use graph::data_source::CausalityRegion;
+use graph::prelude::{anyhow, serde_json, BlockNumber, Entity, StopwatchMetrics, StoreError};
+use graph::schema::EntityType;
+use serde::{Deserialize, Serialize};
+
+use crate::relational::{Layout, Table, VID_COLUMN};
+use crate::relational_queries::{EntityData, ExportQuery, OrderedExportQuery};
+use crate::{deployment, dynds, primary::Site};
+
+/// The version of the snapshot format. Imports refuse snapshots with a
+/// different version
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+const METADATA_FILE: &str = "snapshot.json";
+const DATA_SOURCES_FILE: &str = "data_sources.jsonl";
+const ENTITIES_DIR: &str = "entities";
+
+/// How many entities to read from or write to the database at once
+const BATCH_SIZE: usize = 10_000;
+
+/// Internal columns of entity tables that only have meaning in the
+/// database the snapshot was taken from
+const INTERNAL_COLUMNS: [&str; 3] = [VID_COLUMN, "block_range", "block$"];
+
+/// The description of a snapshot that is stored in `snapshot.json`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    /// The IPFS hash of the deployment
+    pub deployment: String,
+    pub network: String,
+    pub block_number: BlockNumber,
+    pub block_hash: String,
+    /// The GraphQL schema of the deployment
+    pub schema: String,
+    /// The manifest of the deployment, if it is known
+    pub raw_yaml: Option<String>,
+    /// The number of entities of each type in the snapshot
+    pub entities: BTreeMap<String, usize>,
+    /// The number of dynamic data sources in the snapshot
+    pub data_sources: usize,
+}
+
+impl Snapshot {
+    /// Read the metadata of the snapshot in `dir`
+    pub fn read(dir: &Path) -> Result<Snapshot, StoreError> {
+        let path = dir.join(METADATA_FILE);
+        let file = File::open(&path)
+            .with_context(|| format!("can not open snapshot file {}", path.display()))?;
+        let snapshot: Snapshot = serde_json::from_reader(BufReader::new(file))?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(StoreError::Unknown(anyhow!(
+                "the snapshot in {} has version {} but only version {} is supported",
+                dir.display(),
+                snapshot.version,
+                SNAPSHOT_VERSION
+            )));
+        }
+        Ok(snapshot)
+    }
+
+    fn write(&self, dir: &Path) -> Result<(), StoreError> {
+        let path = dir.join(METADATA_FILE);
+        let file = File::create(&path)
+            .with_context(|| format!("can not create snapshot file {}", path.display()))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    pub fn block_ptr(&self) -> Result<BlockPtr, StoreError> {
+        let hash = BlockHash::from_str(&self.block_hash)?;
+        Ok(BlockPtr::new(hash, self.block_number))
+    }
+
+    /// The total number of entities in the snapshot
+    pub fn entity_count(&self) -> usize {
+        self.entities.values().sum()
+    }
+}
+
+/// A dynamic data source as it is stored in `data_sources.jsonl`
+#[derive(Serialize, Deserialize)]
+struct DataSourceRecord {
+    manifest_idx: u32,
+    param: Option<Bytes>,
+    context: Option<serde_json::Value>,
+    creation_block: Option<BlockNumber>,
+    done_at: Option<i32>,
+    causality_region: CausalityRegion,
+}
+
+impl DataSourceRecord {
+    /// Turn `ds` into a record as it was at `block`; a data source that was
+    /// marked as done after `block` was still live then
+    fn new(ds: StoredDynamicDataSource, block: BlockNumber) -> Self {
+        let StoredDynamicDataSource {
+            manifest_idx,
+            param,
+            context,
+            creation_block,
+            done_at,
+            causality_region,
+        } = ds;
+        DataSourceRecord {
+            manifest_idx,
+            param,
+            context,
+            creation_block,
+            done_at: done_at.filter(|done_at| *done_at <= block),
+            causality_region,
+        }
+    }
+}
+
+impl From<DataSourceRecord> for StoredDynamicDataSource {
+    fn from(record: DataSourceRecord) -> Self {
+        let DataSourceRecord {
+            manifest_idx,
+            param,
+            context,
+            creation_block,
+            done_at,
+            causality_region,
+        } = record;
+        StoredDynamicDataSource {
+            manifest_idx,
+            param,
+            context,
+            creation_block,
+            done_at,
+            causality_region,
+        }
+    }
+}
+
+fn create_file(path: &Path) -> Result<BufWriter<File>, StoreError> {
+    let file = File::create(path)
+        .with_context(|| format!("can not create snapshot file {}", path.display()))?;
+    Ok(BufWriter::new(file))
+}
+
+fn write_line<T: Serialize>(out: &mut BufWriter<File>, value: &T) -> Result<(), StoreError> {
+    serde_json::to_writer(&mut *out, value)?;
+    out.write_all(b"\n")
+        .context("failed to write to snapshot file")?;
+    Ok(())
+}
+
+fn entities_file(dir: &Path, entity_type: &EntityType) -> PathBuf {
+    dir.join(ENTITIES_DIR)
+        .join(format!("{}.jsonl", entity_type.as_str()))
+}
+
+/// Write all entities in `table` that are visible at `block` into the
+/// snapshot in `dir` and return how many entities were written
+fn export_table(
+    conn: &PgConnection,
+    table: &Table,
+    block: BlockNumber,
+    dir: &Path,
+) -> Result<usize, StoreError> {
+    let mut out = create_file(&entities_file(dir, &table.object))?;
+    let mut count = 0;
+    let mut after_vid = -1;
+    loop {
+        let batch = ExportQuery::new(table, block, after_vid, BATCH_SIZE as i64)
+            .get_results::<EntityData>(conn)?;
+        let done = batch.len() < BATCH_SIZE;
+        for data in batch {
+            let mut data = match data.into_data() {
+                serde_json::Value::Object(map) => map,
+                data => {
+                    return Err(constraint_violation!(
+                        "entity data for {} is not an object: {}",
+                        table.object,
+                        data
+                    ))
+                }
+            };
+            after_vid = data
+                .get(VID_COLUMN)
+                .and_then(|vid| vid.as_i64())
+                .ok_or_else(|| {
+                    constraint_violation!("entity data for {} has no vid", table.object)
+                })?;
+            for column in INTERNAL_COLUMNS {
+                data.remove(column);
+            }
+            write_line(&mut out, &data)?;
+            count += 1;
+        }
+        if done {
+            break;
+        }
+    }
+    out.flush().context("failed to write to snapshot file")?;
+    Ok(count)
+}
+
+/// Export the deployment `site` as it was at `block` into `dir`, which
+/// must either not exist or be empty
+pub(crate) fn export(
+    conn: &PgConnection,
+    site: &Site,
+    layout: &Layout,
+    block: &BlockPtr,
+    dir: &Path,
+) -> Result<Snapshot, StoreError> {
+    if !site.schema_version.private_data_sources() {
+        return Err(constraint_violation!(
+            "deployment {} uses the shared data sources table and can not be exported",
+            site.deployment
+        ));
+    }
+    if dir.exists()
+        && fs::read_dir(dir)
+            .with_context(|| format!("can not read directory {}", dir.display()))?
+            .next()
+            .is_some()
+    {
+        return Err(StoreError::Unknown(anyhow!(
+            "the snapshot directory {} is not empty",
+            dir.display()
+        )));
+    }
+    fs::create_dir_all(dir.join(ENTITIES_DIR))
+        .with_context(|| format!("can not create snapshot directory {}", dir.display()))?;
+
+    let (schema, raw_yaml) = deployment::schema_and_raw_yaml(conn, site)?;
+
+    // Export tables in a fixed order so that snapshots of the same
+    // deployment are easy to compare
+    let mut tables: Vec<_> = layout.tables.values().collect();
+    tables.sort_by(|a, b| a.object.as_str().cmp(b.object.as_str()));
+    let mut entities = BTreeMap::new();
+    for table in tables {
+        let count = export_table(conn, table, block.number, dir)?;
+        entities.insert(table.object.to_string(), count);
+    }
+
+    let mut out = create_file(&dir.join(DATA_SOURCES_FILE))?;
+    let data_sources = dynds::load(conn, site, block.number, vec![])?;
+    let data_source_count = data_sources.len();
+    for ds in data_sources {
+        write_line(&mut out, &DataSourceRecord::new(ds, block.number))?;
+    }
+    out.flush().context("failed to write to snapshot file")?;
+
+    let snapshot = Snapshot {
+        version: SNAPSHOT_VERSION,
+        deployment: site.deployment.to_string(),
+        network: site.network.clone(),
+        block_number: block.number,
+        block_hash: block.hash.to_string(),
+        schema,
+        raw_yaml,
+        entities,
+        data_sources: data_source_count,
+    };
+    snapshot.write(dir)?;
+    Ok(snapshot)
+}
+
+/// Write the canonical form of all entities in `table` that are visible
+/// at `block` to `out`, in the order of their ids, and return how many
+/// entities were written
+fn export_canonical_table(
+    conn: &PgConnection,
+    layout: &Layout,
+    table: &Table,
+    block: BlockNumber,
+    out: &mut dyn Write,
+) -> Result<usize, StoreError> {
+    let mut count = 0;
+    let mut after = None;
+    loop {
+        let batch = OrderedExportQuery::new(table, block, after.take(), BATCH_SIZE as i64)
+            .get_results::<EntityData>(conn)?;
+        let done = batch.len() < BATCH_SIZE;
+        for data in batch {
+            let data = data.into_data();
+            let causality_region = data
+                .get("causality_region")
+                .map(|cr| serde_json::from_value::<CausalityRegion>(cr.clone()))
+                .transpose()?
+                .unwrap_or(CausalityRegion::ONCHAIN);
+            let entity: Entity = EntityData::new(table.object.to_string(), data)
+                .deserialize_with_layout(layout, None)?;
+            writeln!(out, "{}", canonical_json(table.object.as_str(), &entity))
+                .context("failed to write canonical entities")?;
+            after = Some((entity.id(), causality_region));
+            count += 1;
+        }
+        if done {
+            break;
+        }
+    }
+    Ok(count)
+}
+
+/// Write the canonical form of all entities of the deployment with
+/// `layout` that are visible at `block` to `out`, one entity per line.
+/// Entity types appear in the order of their names, and the entities of
+/// each type in the order of their ids. The proof of indexing is left out.
+/// Return how many entities of each type were written
+pub(crate) fn export_canonical(
+    conn: &PgConnection,
+    layout: &Layout,
+    block: BlockNumber,
+    out: &mut dyn Write,
+) -> Result<BTreeMap<String, usize>, StoreError> {
+    let mut tables: Vec<_> = layout
+        .tables
+        .values()
+        .filter(|table| !table.object.is_poi())
+        .collect();
+    tables.sort_by(|a, b| a.object.as_str().cmp(b.object.as_str()));
+    let mut counts = BTreeMap::new();
+    for table in tables {
+        let count = export_canonical_table(conn, layout, table, block, out)?;
+        counts.insert(table.object.to_string(), count);
+    }
+    out.flush().context("failed to write canonical entities")?;
+    Ok(counts)
+}
+
+/// Read the lines of the file at `path` one by one, deserializing each
+/// of them into a `T`
+fn read_lines<T>(path: &Path) -> Result<impl Iterator<Item = Result<T, StoreError>>, StoreError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let file = File::open(path)
+        .with_context(|| format!("can not open snapshot file {}", path.display()))?;
+    let path = path.to_path_buf();
+    Ok(BufReader::new(file).lines().map(move |line| {
+        let line =
+            line.with_context(|| format!("failed to read snapshot file {}", path.display()))?;
+        Ok(serde_json::from_str(&line)?)
+    }))
+}
+
+/// Insert the entities of `table` from the snapshot in `dir` and return
+/// how many entities were inserted
+fn import_table(
+    conn: &PgConnection,
+    layout: &Layout,
+    table: &Table,
+    block: BlockNumber,
+    dir: &Path,
+    stopwatch: &StopwatchMetrics,
+) -> Result<usize, StoreError> {
+    let path = entities_file(dir, &table.object);
+    if !path.exists() {
+        return Ok(0);
+    }
+
+    let mut count = 0;
+    let mut group = RowGroup::new(table.object.clone(), table.immutable);
+    for data in read_lines::<serde_json::Value>(&path)? {
+        let data = data?;
+        let causality_region = data
+            .get("causality_region")
+            .map(|cr| serde_json::from_value::<CausalityRegion>(cr.clone()))
+            .transpose()?
+            .unwrap_or(CausalityRegion::ONCHAIN);
+        let entity: Entity = EntityData::new(table.object.to_string(), data)
+            .deserialize_with_layout(layout, None)?;
+        let key = table.object.key_in(entity.id(), causality_region);
+        group.push(
+            EntityModification::Insert {
+                key,
+                data: entity,
+                block,
+                end: None,
+            },
+            block,
+        )?;
+        count += 1;
+
+        if count % BATCH_SIZE == 0 {
+            let full = std::mem::replace(
+                &mut group,
+                RowGroup::new(table.object.clone(), table.immutable),
+            );
+            layout.insert(conn, &full, stopwatch)?;
+        }
+    }
+    layout.insert(conn, &group, stopwatch)?;
+    Ok(count)
+}
+
+/// Import the snapshot in `dir` into the deployment `site`. The deployment
+/// must be for the same subgraph as the snapshot and must not have
+/// indexed any blocks yet. The caller is responsible for running this in
+/// a transaction and making sure that nothing else writes to the
+/// deployment
+pub(crate) fn import(
+    conn: &PgConnection,
+    site: &Site,
+    layout: &Layout,
+    dir: &Path,
+    stopwatch: &StopwatchMetrics,
+) -> Result<Snapshot, StoreError> {
+    let snapshot = Snapshot::read(dir)?;
+
+    if snapshot.deployment != site.deployment.as_str() {
+        return Err(constraint_violation!(
+            "the snapshot is for deployment {} and can not be imported into {}",
+            snapshot.deployment,
+            site.deployment
+        ));
+    }
+    if snapshot.network != site.network {
+        return Err(constraint_violation!(
+            "the snapshot is for network {} but deployment {} indexes {}",
+            snapshot.network,
+            site.deployment,
+            site.network
+        ));
+    }
+    let (schema, _) = deployment::schema_and_raw_yaml(conn, site)?;
+    if snapshot.schema != schema {
+        return Err(constraint_violation!(
+            "the schema of the snapshot differs from the schema of deployment {}",
+            site.deployment
+        ));
+    }
+    if let Some(ptr) = deployment::block_ptr(conn, &site.deployment)? {
+        return Err(constraint_violation!(
+            "deployment {} has already indexed up to block {} and can not import a snapshot",
+            site.deployment,
+            ptr.number
+        ));
+    }
+
+    let block_ptr = snapshot.block_ptr()?;
+    let block = block_ptr.number;
+
+    let mut count = 0;
+    for table in layout.tables.values() {
+        let imported = import_table(conn, layout, table, block, dir, stopwatch)?;
+        let expected = snapshot
+            .entities
+            .get(table.object.as_str())
+            .copied()
+            .unwrap_or(0);
+        if imported != expected {
+            return Err(constraint_violation!(
+                "the snapshot should contain {} entities of type {} but contains {}",
+                expected,
+                table.object,
+                imported
+            ));
+        }
+        count += imported;
+    }
+
+    let path = dir.join(DATA_SOURCES_FILE);
+    let data_sources = read_lines::<DataSourceRecord>(&path)?
+        .map(|record| record.map(StoredDynamicDataSource::from))
+        .collect::<Result<Vec<_>, _>>()?;
+    dynds::import(conn, site, &data_sources)?;
+
+    deployment::transact_block(conn, site, &block_ptr, &FirehoseCursor::None, count as i32)?;
+    deployment::set_earliest_block(conn, site, block)?;
+
+    Ok(snapshot)
+}
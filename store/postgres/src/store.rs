@@ -1,5 +1,6 @@
 use async_trait::async_trait;
 use std::sync::Arc;
+use std::time::Duration;
 
 use graph::{
     components::{
@@ -10,11 +11,13 @@ use graph::{
         },
     },
     constraint_violation,
-    data::subgraph::status,
+    data::subgraph::{schema::SubgraphHealth, status},
+    env::ENV_VARS,
     prelude::{
         web3::types::Address, BlockNumber, BlockPtr, CheapClone, DeploymentHash, PartialBlockPtr,
         QueryExecutionError, StoreError,
     },
+    util::jobs::JobStatus,
 };
 
 use crate::{block_store::BlockStore, query_store::QueryStore, SubgraphStore};
@@ -110,10 +113,33 @@ impl StatusStore for Store {
     fn status(&self, filter: status::Filter) -> Result<Vec<status::Info>, StoreError> {
         let mut infos = self.subgraph_store.status(filter)?;
         let ptrs = self.block_store.chain_head_pointers()?;
+        let max_blocks = ENV_VARS.unhealthy_blocks_behind;
+        let max_time = ENV_VARS.unhealthy_time_behind;
         for info in &mut infos {
             for chain in &mut info.chains {
                 chain.chain_head_block = ptrs.get(&chain.network).map(|ptr| ptr.clone().into());
             }
+            // A synced deployment that has fallen too far behind is not
+            // healthy even if it hasn't encountered any errors
+            if !info.synced || info.health != SubgraphHealth::Healthy {
+                continue;
+            }
+            for chain in &info.chains {
+                // Looking up the timestamps costs two queries, only do
+                // that when we need them
+                let time_behind = match (max_time, &chain.chain_head_block, &chain.latest_block) {
+                    (Some(_), Some(head), Some(latest)) => self.block_store.time_behind(
+                        &chain.network,
+                        &head.ptr().hash,
+                        &latest.ptr().hash,
+                    )?,
+                    _ => None,
+                };
+                if lagging(chain, max_blocks, max_time, time_behind) {
+                    info.health = SubgraphHealth::Unhealthy;
+                    break;
+                }
+            }
         }
         Ok(infos)
     }
@@ -141,6 +167,18 @@ impl StatusStore for Store {
             .subgraphs_for_deployment_hash(deployment_hash)
     }
 
+    fn subgraph_versions(&self) -> Result<Vec<(String, String, String)>, StoreError> {
+        self.subgraph_store.subgraph_versions()
+    }
+
+    fn check_connection(&self) -> Result<(), StoreError> {
+        self.subgraph_store.check_connection()
+    }
+
+    fn job_statuses(&self) -> Result<Vec<JobStatus>, StoreError> {
+        self.subgraph_store.job_statuses()
+    }
+
     async fn get_proof_of_indexing(
         &self,
         subgraph_id: &DeploymentHash,
@@ -173,3 +211,81 @@ impl StatusStore for Store {
         Ok(self.block_store.query_permit_primary().await)
     }
 }
+
+/// Whether a deployment is further behind the head of `chain` than
+/// `max_blocks` blocks or `max_time` allow. `time_behind` is how far the
+/// latest block of the deployment is behind the chain head in time, if
+/// that is known
+fn lagging(
+    chain: &status::ChainInfo,
+    max_blocks: Option<BlockNumber>,
+    max_time: Option<Duration>,
+    time_behind: Option<Duration>,
+) -> bool {
+    let blocks = matches!(
+        (max_blocks, chain.blocks_behind()),
+        (Some(max), Some(behind)) if behind > max
+    );
+    let time = matches!(
+        (max_time, time_behind),
+        (Some(max), Some(behind)) if behind > max
+    );
+    blocks || time
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use graph::{blockchain::BlockHash, data::subgraph::status};
+
+    use super::lagging;
+
+    fn chain(head: Option<i32>, latest: Option<i32>) -> status::ChainInfo {
+        let block = |number| status::EthereumBlock::new(BlockHash::zero(), number);
+        status::ChainInfo {
+            network: "mainnet".to_string(),
+            chain_head_block: head.map(block),
+            earliest_block_number: 0,
+            latest_block: latest.map(block),
+        }
+    }
+
+    #[test]
+    fn lagging_by_blocks() {
+        let behind_ten = chain(Some(110), Some(100));
+
+        assert!(lagging(&behind_ten, Some(9), None, None));
+        assert!(!lagging(&behind_ten, Some(10), None, None));
+        assert!(!lagging(&behind_ten, None, None, None));
+        // A deployment that is ahead of a stale chain head is not behind
+        assert!(!lagging(&chain(Some(100), Some(110)), Some(0), None, None));
+        // Without a chain head or latest block there is nothing to compare
+        assert!(!lagging(&chain(None, Some(100)), Some(0), None, None));
+        assert!(!lagging(&chain(Some(100), None), Some(0), None, None));
+    }
+
+    #[test]
+    fn lagging_by_time() {
+        const MINUTE: Duration = Duration::from_secs(60);
+        let synced = chain(Some(100), Some(100));
+
+        assert!(lagging(&synced, None, Some(MINUTE * 5), Some(MINUTE * 6)));
+        assert!(!lagging(&synced, None, Some(MINUTE * 5), Some(MINUTE * 5)));
+        assert!(!lagging(&synced, None, Some(MINUTE * 5), None));
+        assert!(!lagging(&synced, None, None, Some(MINUTE * 6)));
+        // Either limit makes a deployment lag
+        assert!(lagging(
+            &chain(Some(110), Some(100)),
+            Some(5),
+            Some(MINUTE * 5),
+            Some(MINUTE)
+        ));
+        assert!(lagging(
+            &synced,
+            Some(5),
+            Some(MINUTE * 5),
+            Some(MINUTE * 6)
+        ));
+    }
+}
@@ -1,14 +1,16 @@
 use diesel::{
     pg::Pg,
     serialize::Output,
+    sql_query,
     sql_types::Text,
     types::{FromSql, ToSql},
+    RunQueryDsl,
 };
 use std::{
     collections::{BTreeMap, HashMap},
     sync::{atomic::AtomicU8, Arc, Mutex},
 };
-use std::{fmt, io::Write};
+use std::{fmt, io::Write, path::Path};
 use std::{iter::FromIterator, time::Duration};
 
 use graph::{
@@ -17,22 +19,26 @@ use graph::{
         server::index_node::VersionInfo,
         store::{
             self, BlockPtrForNumber, BlockStore, DeploymentLocator, EnsLookup as EnsLookupTrait,
-            PruneReporter, PruneRequest, SubgraphFork,
+            PruneReporter, PruneRequest, SubgraphFork, WRITE_STATS,
         },
     },
     constraint_violation,
     data::query::QueryTarget,
-    data::subgraph::{schema::DeploymentCreate, status, DeploymentFeatures},
+    data::subgraph::{
+        schema::{DeploymentCreate, DeploymentPlacement},
+        status, DeploymentFeatures,
+    },
     prelude::{
         anyhow, futures03::future::join_all, lazy_static, o, web3::types::Address, ApiVersion,
         BlockNumber, BlockPtr, ChainStore, DeploymentHash, EntityOperation, Logger,
-        MetricsRegistry, NodeId, PartialBlockPtr, StoreError, SubgraphDeploymentEntity,
-        SubgraphName, SubgraphStore as SubgraphStoreTrait, SubgraphVersionSwitchingMode,
+        MetricsRegistry, NodeId, PartialBlockPtr, StopwatchMetrics, StoreError,
+        SubgraphDeploymentEntity, SubgraphName, SubgraphStore as SubgraphStoreTrait,
+        SubgraphVersionSwitchingMode,
     },
-    prelude::{CancelableError, StoreEvent},
+    prelude::{warn, CancelableError, StoreEvent},
     schema::{ApiSchema, InputSchema},
     url::Url,
-    util::timed_cache::TimedCache,
+    util::{jobs::JobStatus, timed_cache::TimedCache},
 };
 
 use crate::{
@@ -41,7 +47,8 @@ use crate::{
     primary,
     primary::{DeploymentId, Mirror as PrimaryMirror, Site},
     relational::{index::Method, Layout},
-    writable::WritableStore,
+    snapshot::Snapshot,
+    writable::{Mode, WritableStore},
     NotificationSender,
 };
 use crate::{
@@ -49,7 +56,7 @@ use crate::{
     detail::DeploymentDetail,
     primary::UnusedDeployment,
 };
-use crate::{fork, relational::index::CreateIndex, relational::SqlName};
+use crate::{fork, relational::index::CreateIndex, relational::SqlName, views::View};
 
 /// The name of a database shard; valid names must match `[a-z0-9_]+`
 #[derive(Clone, Debug, Eq, PartialEq, Hash, AsExpression, FromSqlRow)]
@@ -123,6 +130,43 @@ pub trait DeploymentPlacer {
         -> Result<Option<(Vec<Shard>, Vec<NodeId>)>, String>;
 }
 
+/// Check that the `shard` and `node` that were requested for subgraph
+/// `name` are among the `shards` and `nodes` of the deployment rule that
+/// matches it. A rule without shards only allows the primary, and a rule
+/// without nodes allows any node
+fn check_requested_placement(
+    name: &SubgraphName,
+    shards: &[Shard],
+    nodes: &[NodeId],
+    shard: Option<&Shard>,
+    node: Option<&NodeId>,
+) -> Result<(), StoreError> {
+    if let Some(shard) = shard {
+        let allowed = if shards.is_empty() {
+            shard == &*PRIMARY_SHARD
+        } else {
+            shards.contains(shard)
+        };
+        if !allowed {
+            return Err(StoreError::Unknown(anyhow!(
+                "the deployment rules do not allow placing subgraph `{}` in shard `{}`",
+                name,
+                shard
+            )));
+        }
+    }
+    if let Some(node) = node {
+        if !nodes.is_empty() && !nodes.contains(node) {
+            return Err(StoreError::Unknown(anyhow!(
+                "the deployment rules do not allow subgraph `{}` to be indexed by node `{}`",
+                name,
+                node
+            )));
+        }
+    }
+    Ok(())
+}
+
 /// Tools for managing unused deployments
 pub mod unused {
     use graph::prelude::chrono::Duration;
@@ -253,6 +297,35 @@ impl SubgraphStore {
     pub fn notification_sender(&self) -> Arc<NotificationSender> {
         self.sender.clone()
     }
+
+    /// Create a `WritableStore` that never writes, for example for a
+    /// standby instance or for replaying a block. Such a store neither
+    /// takes the writer lock nor goes into the cache of writables; the node
+    /// that indexes the deployment might be writing to it while we read
+    /// from it
+    async fn read_only_writable(
+        self: Arc<Self>,
+        logger: Logger,
+        deployment: DeploymentId,
+        manifest_idx_and_name: Arc<Vec<(u32, String)>>,
+        mode: Mode,
+    ) -> Result<Arc<dyn store::WritableStore>, StoreError> {
+        let this = self.clone();
+        let site = graph::spawn_blocking_allow_panic(move || this.find_site(deployment))
+            .await
+            .unwrap()?; // Propagate panics, there shouldn't be any.
+
+        let writable = WritableStore::new(
+            self.as_ref().clone(),
+            logger,
+            site,
+            manifest_idx_and_name,
+            self.registry.clone(),
+            mode,
+        )
+        .await?;
+        Ok(Arc::new(writable))
+    }
 }
 
 impl std::ops::Deref for SubgraphStore {
@@ -465,6 +538,7 @@ impl SubgraphStoreInner {
         name: &SubgraphName,
         network_name: &str,
         default_node: NodeId,
+        requested: &DeploymentPlacement,
     ) -> Result<(Shard, NodeId), StoreError> {
         // We try to place the deployment according to the configured rules.
         // If they don't yield a match, place into the primary and have
@@ -478,11 +552,43 @@ impl SubgraphStoreInner {
                 constraint_violation!("illegal indexer name in deployment rule: {}", msg)
             })?;
 
+        let requested_shard = requested
+            .shard
+            .as_ref()
+            .map(|shard| Shard::new(shard.clone()))
+            .transpose()?;
+
         match placement {
-            None => Ok((PRIMARY_SHARD.clone(), default_node)),
+            None => {
+                let shard = match requested_shard {
+                    Some(shard) if !self.stores.contains_key(&shard) => {
+                        return Err(StoreError::UnknownShard(shard.to_string()))
+                    }
+                    Some(shard) => shard,
+                    None => PRIMARY_SHARD.clone(),
+                };
+                let node = requested.node.clone().unwrap_or(default_node);
+                Ok((shard, node))
+            }
             Some((shards, nodes)) => {
-                let node = self.place_on_node(nodes, default_node)?;
-                let shard = self.place_in_shard(shards)?;
+                // An explicitly requested shard or node overrides the
+                // choice among the candidates of the matching rule, but
+                // must be one of them
+                check_requested_placement(
+                    name,
+                    &shards,
+                    &nodes,
+                    requested_shard.as_ref(),
+                    requested.node.as_ref(),
+                )?;
+                let shard = match requested_shard {
+                    Some(shard) => shard,
+                    None => self.place_in_shard(shards)?,
+                };
+                let node = match &requested.node {
+                    Some(node) => node.clone(),
+                    None => self.place_on_node(nodes, default_node)?,
+                };
 
                 Ok((shard, node))
             }
@@ -528,7 +634,8 @@ impl SubgraphStoreInner {
             //       In that case, we need to use the shard and node
             //       assignment that we used last time to avoid creating
             //       the same deployment in another shard
-            let (shard, node_id) = self.place(&name, &network_name, node_id)?;
+            let (shard, node_id) =
+                self.place(&name, &network_name, node_id, &deployment.placement)?;
             let conn = self.primary_conn()?;
             let (site, site_was_created) =
                 conn.allocate_site(shard, schema.id(), network_name, graft_base)?;
@@ -679,6 +786,8 @@ impl SubgraphStoreInner {
     /// with the same deployment hash. Activating this specific deployment
     /// will make queries use that instead of whatever was active before
     pub fn activate(&self, deployment: &DeploymentLocator) -> Result<(), StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        self.for_site(&site)?.warm_up(site);
         self.primary_conn()?.activate(deployment)?;
         // As a side-effect, this will update the `self.sites` cache with
         // the new active site
@@ -706,6 +815,25 @@ impl SubgraphStoreInner {
         conn.send_store_event(&self.sender, event)
     }
 
+    /// Assign the deployment to `node`, which keeps a standby instance of
+    /// it. Return `false` if the assignment could not be changed because
+    /// the deployment is paused or `node` is not its standby node anymore
+    pub(crate) fn take_over_assignment(
+        &self,
+        site: &Site,
+        node: &NodeId,
+    ) -> Result<bool, StoreError> {
+        let pconn = self.primary_conn()?;
+        pconn.transaction(|| -> Result<_, StoreError> {
+            let changes = pconn.take_over_assignment(site, node)?;
+            if changes.is_empty() {
+                return Ok(false);
+            }
+            pconn.send_store_event(&self.sender, &StoreEvent::new(changes))?;
+            Ok(true)
+        })
+    }
+
     /// Get a connection to the primary shard. Code must never hold one of these
     /// connections while also accessing a `DeploymentStore`, since both
     /// might draw connections from the same pool, and trying to get two
@@ -886,6 +1014,7 @@ impl SubgraphStoreInner {
                 let ids: Vec<_> = ids.into_iter().map(|id| id.into()).collect();
                 self.mirror.find_sites_by_id(&ids)?
             }
+            status::Filter::Page { skip, first } => self.mirror.find_sites_page(skip, first)?,
         };
 
         let by_shard: HashMap<Shard, Vec<Arc<Site>>> = self.deployments_by_shard(sites)?;
@@ -953,6 +1082,22 @@ impl SubgraphStoreInner {
         self.mirror.subgraphs_by_deployment_hash(deployment_hash)
     }
 
+    pub(crate) fn subgraph_versions(&self) -> Result<Vec<(String, String, String)>, StoreError> {
+        self.mirror.subgraph_versions()
+    }
+
+    /// Check that we can run a query against the primary
+    pub(crate) fn check_connection(&self) -> Result<(), StoreError> {
+        let conn = self.mirror.primary().get()?;
+        sql_query("select 1").execute(&conn)?;
+        Ok(())
+    }
+
+    /// The status of the background jobs of all nodes
+    pub(crate) fn job_statuses(&self) -> Result<Vec<JobStatus>, StoreError> {
+        self.primary_conn()?.job_statuses()
+    }
+
     #[cfg(debug_assertions)]
     pub fn error_count(&self, id: &DeploymentHash) -> Result<usize, StoreError> {
         let (store, _) = self.store(id)?;
@@ -976,6 +1121,57 @@ impl SubgraphStoreInner {
         self.send_store_event(&event)
     }
 
+    /// Export the data of `deployment` as it was at `block` into a
+    /// snapshot in the directory `dir`
+    pub fn export_snapshot(
+        &self,
+        deployment: &DeploymentLocator,
+        block: BlockPtr,
+        dir: &Path,
+    ) -> Result<Snapshot, StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        let store = self.for_site(&site)?;
+
+        store.export_snapshot(site, &block, dir)
+    }
+
+    /// Write the entities of `deployment` as they were at `block` to `out`
+    /// in the canonical form from [`graph::data::store::canonical`] and
+    /// return how many entities of each type were written
+    pub fn export_canonical(
+        &self,
+        deployment: &DeploymentLocator,
+        block: BlockNumber,
+        out: &mut dyn Write,
+    ) -> Result<BTreeMap<String, usize>, StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        let store = self.for_site(&site)?;
+
+        store.export_canonical(site, block, out)
+    }
+
+    /// Import the snapshot in the directory `dir` into `deployment`. The
+    /// deployment must be for the same subgraph as the snapshot, and must
+    /// not have indexed any blocks yet
+    pub fn import_snapshot(
+        &self,
+        logger: &Logger,
+        deployment: &DeploymentLocator,
+        dir: &Path,
+    ) -> Result<Snapshot, StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        let store = self.for_site(&site)?;
+        let stopwatch = StopwatchMetrics::new(
+            logger.clone(),
+            site.deployment.clone(),
+            "snapshot",
+            self.registry.clone(),
+            site.shard.to_string(),
+        );
+
+        store.import_snapshot(site, dir, &stopwatch)
+    }
+
     pub(crate) async fn get_proof_of_indexing(
         &self,
         id: &DeploymentHash,
@@ -1153,6 +1349,34 @@ impl SubgraphStoreInner {
         store.set_account_like(site, table, is_account_like).await
     }
 
+    /// Define the SQL view `name` over the entity tables of `deployment`,
+    /// replacing any view with that name
+    pub async fn create_view(
+        &self,
+        deployment: &DeploymentLocator,
+        name: &str,
+        query: &str,
+    ) -> Result<(), StoreError> {
+        let (store, site) = self.store(&deployment.hash)?;
+        store.create_view(site, name, query).await
+    }
+
+    /// Remove the SQL view `name` of `deployment`. Return `false` if there
+    /// is no such view
+    pub async fn drop_view(
+        &self,
+        deployment: &DeploymentLocator,
+        name: &str,
+    ) -> Result<bool, StoreError> {
+        let (store, site) = self.store(&deployment.hash)?;
+        store.drop_view(site, name).await
+    }
+
+    pub async fn views(&self, deployment: &DeploymentLocator) -> Result<Vec<View>, StoreError> {
+        let (store, site) = self.store(&deployment.hash)?;
+        store.views(site).await
+    }
+
     /// Prune the history according to the parameters in `req`.
     ///
     /// Pruning can take a long time, and is structured into multiple
@@ -1316,6 +1540,57 @@ impl SubgraphStoreTrait for SubgraphStore {
         })
     }
 
+    fn pause_subgraph(&self, deployment: &DeploymentLocator) -> Result<(), StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        let pconn = self.primary_conn()?;
+        pconn.transaction(|| -> Result<_, StoreError> {
+            let changes = pconn.pause_subgraph(site.as_ref())?;
+            pconn.send_store_event(&self.sender, &StoreEvent::new(changes))
+        })
+    }
+
+    fn resume_subgraph(&self, deployment: &DeploymentLocator) -> Result<(), StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        let pconn = self.primary_conn()?;
+        pconn.transaction(|| -> Result<_, StoreError> {
+            let changes = pconn.resume_subgraph(site.as_ref())?;
+            pconn.send_store_event(&self.sender, &StoreEvent::new(changes))
+        })
+    }
+
+    fn prune_subgraph(
+        &self,
+        deployment: &DeploymentLocator,
+        history_blocks: Option<BlockNumber>,
+    ) -> Result<(), StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        let store = self.for_site(&site)?;
+        store.start_pruning(site, history_blocks)
+    }
+
+    fn rewind_subgraph(
+        &self,
+        deployment: &DeploymentLocator,
+        block_ptr: BlockPtr,
+    ) -> Result<(), StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        // Having a writable means that this node is indexing the
+        // deployment, and it therefore already holds the writer lock
+        if self.writables.lock().unwrap().contains_key(&site.id) {
+            return Err(StoreError::DeploymentLocked(site.deployment.clone()));
+        }
+        let store = self.for_site(&site)?;
+        store.lock_writer(&site)?;
+        let res = store.rewind(site.cheap_clone(), block_ptr);
+        store.unlock_writer(&site)?;
+        self.send_store_event(&res?)
+    }
+
+    fn network_name(&self, deployment: &DeploymentLocator) -> Result<String, StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        Ok(site.network.clone())
+    }
+
     fn assigned_node(&self, deployment: &DeploymentLocator) -> Result<Option<NodeId>, StoreError> {
         let site = self.find_site(deployment.id.into())?;
         self.mirror.assigned_node(site.as_ref())
@@ -1345,6 +1620,17 @@ impl SubgraphStoreTrait for SubgraphStore {
             .map(|sites| sites.iter().map(|site| site.into()).collect())
     }
 
+    fn standby_assignments(&self, node: &NodeId) -> Result<Vec<DeploymentLocator>, StoreError> {
+        self.mirror
+            .standby_assignments(node)
+            .map(|sites| sites.iter().map(|site| site.into()).collect())
+    }
+
+    fn standby_node(&self, deployment: &DeploymentLocator) -> Result<Option<NodeId>, StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        self.mirror.standby_node(site.as_ref())
+    }
+
     fn subgraph_exists(&self, name: &SubgraphName) -> Result<bool, StoreError> {
         self.mirror.subgraph_exists(name)
     }
@@ -1429,21 +1715,37 @@ impl SubgraphStoreTrait for SubgraphStore {
         // Ideally the lower level functions would be asyncified.
         let this = self.clone();
         let site = graph::spawn_blocking_allow_panic(move || -> Result<_, StoreError> {
-            this.find_site(deployment)
+            let site = this.find_site(deployment)?;
+            // Make sure no other graph-node instance writes to this
+            // deployment while we do
+            this.for_site(&site)?.lock_writer(&site)?;
+            Ok(site)
         })
         .await
         .unwrap()?; // Propagate panics, there shouldn't be any.
 
-        let writable = Arc::new(
-            WritableStore::new(
-                self.as_ref().clone(),
-                logger,
-                site,
-                manifest_idx_and_name,
-                self.registry.clone(),
-            )
-            .await?,
-        );
+        let writable = match WritableStore::new(
+            self.as_ref().clone(),
+            logger.cheap_clone(),
+            site.cheap_clone(),
+            manifest_idx_and_name,
+            self.registry.clone(),
+            Mode::Write,
+        )
+        .await
+        {
+            Ok(writable) => Arc::new(writable),
+            Err(e) => {
+                // Nobody will write to the deployment; let other nodes
+                // have it
+                if let Err(unlock_err) = self.for_site(&site)?.unlock_writer(&site) {
+                    warn!(logger, "Failed to release the writer lock";
+                        "deployment" => site.deployment.as_str(),
+                        "error" => unlock_err.to_string());
+                }
+                return Err(e);
+            }
+        };
         self.writables
             .lock()
             .unwrap()
@@ -1451,14 +1753,76 @@ impl SubgraphStoreTrait for SubgraphStore {
         Ok(writable)
     }
 
+    async fn standby_writable(
+        self: Arc<Self>,
+        logger: Logger,
+        deployment: graph::components::store::DeploymentId,
+        manifest_idx_and_name: Arc<Vec<(u32, String)>>,
+        node: NodeId,
+    ) -> Result<Arc<dyn store::WritableStore>, StoreError> {
+        self.read_only_writable(
+            logger,
+            deployment.into(),
+            manifest_idx_and_name,
+            Mode::Standby(node),
+        )
+        .await
+    }
+
+    async fn replay_writable(
+        self: Arc<Self>,
+        logger: Logger,
+        deployment: graph::components::store::DeploymentId,
+        manifest_idx_and_name: Arc<Vec<(u32, String)>>,
+        block: BlockNumber,
+    ) -> Result<Arc<dyn store::WritableStore>, StoreError> {
+        let deployment = deployment.into();
+        let site = self.find_site(deployment)?;
+        let state = self
+            .for_site(&site)?
+            .deployment_state_from_id(site.deployment.clone())
+            .await?;
+        if block > state.latest_block.number {
+            return Err(StoreError::Unknown(anyhow!(
+                "deployment {} has only processed blocks up to {} and can not replay block {}",
+                site.deployment,
+                state.latest_block.number,
+                block
+            )));
+        }
+        if block < state.earliest_block_number {
+            return Err(StoreError::Unknown(anyhow!(
+                "deployment {} only has data from block {} on and can not replay block {}",
+                site.deployment,
+                state.earliest_block_number,
+                block
+            )));
+        }
+
+        self.read_only_writable(
+            logger,
+            deployment,
+            manifest_idx_and_name,
+            Mode::Replay(block),
+        )
+        .await
+    }
+
     async fn stop_subgraph(&self, loc: &DeploymentLocator) -> Result<(), StoreError> {
         self.evict(&loc.hash)?;
+        WRITE_STATS.remove(&loc.hash);
 
         // Remove the writable from the cache and stop it
         let deployment = loc.id.into();
         let writable = self.writables.lock().unwrap().remove(&deployment);
         match writable {
-            Some(writable) => writable.stop().await,
+            Some(writable) => {
+                // Write everything that is queued before we let go of the
+                // deployment
+                writable.stop().await;
+                let site = self.find_site(deployment)?;
+                self.for_site(&site)?.unlock_writer(&site)
+            }
             None => Ok(()),
         }
     }
@@ -1482,6 +1846,29 @@ impl SubgraphStoreTrait for SubgraphStore {
         store.block_ptr(site.cheap_clone()).await
     }
 
+    fn blocks_behind(
+        &self,
+        deployments: &[DeploymentLocator],
+    ) -> Result<HashMap<store::DeploymentId, BlockNumber>, StoreError> {
+        // The deployment statuses do not contain the chain heads; chains
+        // can be stored in any shard
+        let mut heads = HashMap::new();
+        for store in self.stores.values() {
+            heads.extend(store.chain_head_pointers()?);
+        }
+        let ids = deployments.iter().map(|loc| loc.id).collect();
+        Ok(self
+            .status(status::Filter::DeploymentIds(ids))?
+            .into_iter()
+            .filter_map(|mut info| {
+                let chain = info.chains.first_mut()?;
+                chain.chain_head_block = heads.get(&chain.network).map(|ptr| ptr.clone().into());
+                let behind = chain.blocks_behind()?;
+                Some((info.id, behind))
+            })
+            .collect())
+    }
+
     async fn is_healthy(&self, id: &DeploymentHash) -> Result<bool, StoreError> {
         let (store, site) = self.store(id)?;
         let health = store.health(&site).await?;
@@ -1526,3 +1913,43 @@ impl SubgraphStoreTrait for SubgraphStore {
         Ok(info.instrument)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use graph::prelude::{NodeId, SubgraphName};
+
+    use super::{check_requested_placement, Shard, PRIMARY_SHARD};
+
+    #[test]
+    fn requested_placement_must_be_allowed_by_the_rule() {
+        let name = SubgraphName::new("test/placement").unwrap();
+        let shard = |name: &str| Shard::new(name.to_string()).unwrap();
+        let node = |name: &str| NodeId::new(name).unwrap();
+        let check = |shards: &[Shard], nodes: &[NodeId], s: Option<&Shard>, n: Option<&NodeId>| {
+            check_requested_placement(&name, shards, nodes, s, n).is_ok()
+        };
+
+        let shards = [shard("sharda"), shard("shardb")];
+        let nodes = [node("index_1"), node("index_2")];
+
+        assert!(check(&shards, &nodes, None, None));
+        assert!(check(
+            &shards,
+            &nodes,
+            Some(&shard("shardb")),
+            Some(&node("index_1"))
+        ));
+        assert!(!check(&shards, &nodes, Some(&shard("shardc")), None));
+        assert!(!check(&shards, &nodes, None, Some(&node("index_3"))));
+
+        // Without shards, only the primary is allowed; without nodes, any
+        // node is
+        assert!(check(
+            &[],
+            &[],
+            Some(&*PRIMARY_SHARD),
+            Some(&node("index_3"))
+        ));
+        assert!(!check(&[], &[], Some(&shard("sharda")), None));
+    }
+}
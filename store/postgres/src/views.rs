@@ -0,0 +1,258 @@
+//! Read-only SQL views that operators define over the entity tables of a
+//! deployment so that tools that speak SQL, like BI tools, can use the
+//! data of a subgraph without access to the deployment schema itself.
+//!
+//! The views of the deployment in namespace `sgdNNN` live in the schema
+//! `sgdNNN_views`; giving a database user `usage` on that schema and
+//! `select` on its views gives it access to exactly those views. Views may
+//! only use the tables of their deployment and functions from
+//! `pg_catalog`, which keeps them away from the metadata schemas.
+//!
+//! The definition of each view is stored in `subgraphs.deployment_view`,
+//! and the node recreates views from it whenever the tables they use are
+//! replaced: when a deployment is copied or grafted, and when pruning
+//! rebuilds a table.
+
+use diesel::connection::SimpleConnection;
+use diesel::pg::PgConnection;
+use diesel::prelude::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use diesel::sql_types::{Integer, Text};
+use diesel::{delete, insert_into, sql_query, Connection};
+use graph::prelude::{anyhow::anyhow, warn, Logger, StoreError};
+
+use crate::connection_pool::ForeignServer;
+use crate::primary::{Namespace, Site};
+use crate::relational::SqlName;
+
+table! {
+    subgraphs.deployment_view(deployment, name) {
+        deployment -> Integer,
+        name -> Text,
+        query -> Text,
+    }
+}
+
+/// A view that an operator defined for a deployment
+#[derive(Clone, Debug, Queryable)]
+pub struct View {
+    pub name: String,
+    pub query: String,
+}
+
+/// The schema that holds the views for the deployment in `namespace`
+fn views_schema(namespace: &Namespace) -> String {
+    format!("{}_views", namespace)
+}
+
+/// Create the view `name` for `site` in the database, replacing any
+/// existing view with that name. Unqualified table names in `query` refer
+/// to the tables of the deployment. Fails if the view uses anything other
+/// than the tables of the deployment and functions from `pg_catalog`, or
+/// if `query` is more than one statement.
+///
+/// The view is created and checked in one transaction so that a view that
+/// fails the check is never visible
+fn create_view(
+    conn: &PgConnection,
+    site: &Site,
+    name: &str,
+    query: &str,
+) -> Result<(), StoreError> {
+    #[derive(QueryableByName)]
+    struct Path {
+        #[sql_type = "Text"]
+        path: String,
+    }
+
+    let nsp = views_schema(&site.namespace);
+    let qname = format!("{}.\"{}\"", nsp, name);
+
+    conn.transaction(|| {
+        conn.batch_execute(&format!(
+            "create schema if not exists {nsp};
+             drop view if exists {qname};",
+            nsp = nsp,
+            qname = qname,
+        ))?;
+
+        let path = sql_query("select current_setting('search_path') as path")
+            .get_result::<Path>(conn)?
+            .path;
+        sql_query("select set_config('search_path', $1, true)")
+            .bind::<Text, _>(site.namespace.as_str())
+            .execute(conn)?;
+        // `sql_query` runs a prepared statement, and Postgres refuses to
+        // prepare more than one statement at a time
+        sql_query(format!("create view {} as {}", qname, query)).execute(conn)?;
+        sql_query("select set_config('search_path', $1, true)")
+            .bind::<Text, _>(&path)
+            .execute(conn)?;
+
+        check_dependencies(conn, site, name, &qname)
+    })
+}
+
+/// Check that the view `qname` only uses the tables of `site` and
+/// functions from `pg_catalog`
+fn check_dependencies(
+    conn: &PgConnection,
+    site: &Site,
+    name: &str,
+    qname: &str,
+) -> Result<(), StoreError> {
+    #[derive(QueryableByName)]
+    struct Dependency {
+        #[sql_type = "Text"]
+        kind: String,
+        #[sql_type = "Text"]
+        nsp: String,
+    }
+
+    // Everything the view uses; the rewrite rule of the view also depends
+    // on the view itself, which we skip
+    let deps = sql_query(
+        "select distinct d.kind, d.nsp
+           from (select 'relation' as kind, cn.nspname as nsp
+                   from pg_rewrite r
+                   join pg_depend d on d.objid = r.oid
+                                   and d.classid = 'pg_rewrite'::regclass
+                                   and d.refclassid = 'pg_class'::regclass
+                   join pg_class c on c.oid = d.refobjid and c.oid <> r.ev_class
+                   join pg_namespace cn on cn.oid = c.relnamespace
+                  where r.ev_class = $1::regclass
+                 union all
+                 select 'function' as kind, pn.nspname as nsp
+                   from pg_rewrite r
+                   join pg_depend d on d.objid = r.oid
+                                   and d.classid = 'pg_rewrite'::regclass
+                                   and d.refclassid = 'pg_proc'::regclass
+                   join pg_proc p on p.oid = d.refobjid
+                   join pg_namespace pn on pn.oid = p.pronamespace
+                  where r.ev_class = $1::regclass) d",
+    )
+    .bind::<Text, _>(qname)
+    .get_results::<Dependency>(conn)?;
+    for dep in deps {
+        let allowed = match dep.kind.as_str() {
+            "relation" => dep.nsp == site.namespace.as_str(),
+            _ => dep.nsp == "pg_catalog",
+        };
+        if !allowed {
+            return Err(StoreError::Unknown(anyhow!(
+                "view `{}` uses a {} in schema `{}`; views can only use the tables \
+                 of their deployment and functions from `pg_catalog`",
+                name,
+                dep.kind,
+                dep.nsp
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Define the view `name` for `site` with the given `query`, replacing
+/// any view with that name
+pub fn add(conn: &PgConnection, site: &Site, name: &str, query: &str) -> Result<(), StoreError> {
+    use deployment_view as v;
+
+    SqlName::check_valid_identifier(name, "view")?;
+    let query = query.trim().trim_end_matches(';');
+
+    conn.transaction(|| {
+        create_view(conn, site, name, query)?;
+        insert_into(v::table)
+            .values((
+                v::deployment.eq(site.id),
+                v::name.eq(name),
+                v::query.eq(query),
+            ))
+            .on_conflict((v::deployment, v::name))
+            .do_update()
+            .set(v::query.eq(query))
+            .execute(conn)?;
+        Ok(())
+    })
+}
+
+/// Remove the view `name` of `site`. Return `false` if there is no such
+/// view
+pub fn remove(conn: &PgConnection, site: &Site, name: &str) -> Result<bool, StoreError> {
+    use deployment_view as v;
+
+    conn.transaction(|| {
+        let count = delete(
+            v::table
+                .filter(v::deployment.eq(site.id))
+                .filter(v::name.eq(name)),
+        )
+        .execute(conn)?;
+        if count > 0 {
+            conn.batch_execute(&format!(
+                "drop view if exists {}.\"{}\"",
+                views_schema(&site.namespace),
+                name
+            ))?;
+        }
+        Ok(count > 0)
+    })
+}
+
+/// The views of `site`, ordered by name
+pub fn list(conn: &PgConnection, site: &Site) -> Result<Vec<View>, StoreError> {
+    use deployment_view as v;
+
+    v::table
+        .filter(v::deployment.eq(site.id))
+        .select((v::name, v::query))
+        .order_by(v::name)
+        .load::<View>(conn)
+        .map_err(StoreError::from)
+}
+
+/// Drop the views of `site` from the database while keeping their
+/// definitions, so that the tables they use can be replaced. Views are
+/// restored with `recreate`
+pub fn drop_all(conn: &PgConnection, site: &Site) -> Result<(), StoreError> {
+    Ok(conn.batch_execute(&format!(
+        "drop schema if exists {} cascade",
+        views_schema(&site.namespace)
+    ))?)
+}
+
+/// Create all views of `site` from their definitions. A view that can not
+/// be created, for example because the schema of the subgraph changed in
+/// a way that the view does not work with anymore, is skipped with a
+/// warning
+pub fn recreate(logger: &Logger, conn: &PgConnection, site: &Site) -> Result<(), StoreError> {
+    for view in list(conn, site)? {
+        if let Err(e) = create_view(conn, site, &view.name, &view.query) {
+            warn!(logger, "Failed to create view; skipping it";
+                  "sgd" => site.id.to_string(), "view" => &view.name, "error" => e.to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Copy the definitions of the views of `src` to `dst` and create them
+/// over the tables of `dst`. Return the number of views that were copied
+pub fn copy(
+    logger: &Logger,
+    conn: &PgConnection,
+    src: &Site,
+    dst: &Site,
+) -> Result<usize, StoreError> {
+    let src_nsp = ForeignServer::metadata_schema_in(&src.shard, &dst.shard);
+    let query = format!(
+        "insert into subgraphs.deployment_view(deployment, name, query)
+         select $2 as deployment, v.name, v.query
+           from {src_nsp}.deployment_view v
+          where v.deployment = $1",
+        src_nsp = src_nsp
+    );
+    let count = sql_query(query)
+        .bind::<Integer, _>(src.id)
+        .bind::<Integer, _>(dst.id)
+        .execute(conn)?;
+    recreate(logger, conn, dst)?;
+    Ok(count)
+}
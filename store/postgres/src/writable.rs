@@ -6,15 +6,19 @@ use std::time::{Duration, Instant};
 use std::{collections::BTreeMap, sync::Arc};
 
 use graph::blockchain::block_stream::FirehoseCursor;
-use graph::components::store::{Batch, DeploymentCursorTracker, DerivedEntityQuery, ReadStore};
+use graph::components::store::{
+    Batch, DeploymentCursorTracker, DeploymentWriteStats, DerivedEntityQuery, ReadStore,
+    WRITE_STATS,
+};
 use graph::constraint_violation;
 use graph::data::store::IdList;
 use graph::data::subgraph::schema;
 use graph::data_source::CausalityRegion;
 use graph::prelude::{
-    BlockNumber, CacheWeight, Entity, MetricsRegistry, SubgraphDeploymentEntity,
-    SubgraphStore as _, BLOCK_NUMBER_MAX,
+    BlockNumber, CacheWeight, Entity, MetricsRegistry, NodeId, SubgraphDeploymentEntity,
+    SubgraphStore as _, Value, BLOCK_NUMBER_MAX,
 };
+use graph::prometheus::Gauge;
 use graph::schema::{EntityKey, EntityType, InputSchema};
 use graph::slog::{info, warn};
 use graph::tokio::select;
@@ -65,6 +69,10 @@ impl WritableSubgraphStore {
     fn find_site(&self, id: DeploymentId) -> Result<Arc<Site>, StoreError> {
         self.0.find_site(id)
     }
+
+    fn take_over_assignment(&self, site: &Site, node: &NodeId) -> Result<bool, StoreError> {
+        self.0.take_over_assignment(site, node)
+    }
 }
 
 /// Write synchronously to the actual store, i.e., once a method returns,
@@ -77,6 +85,9 @@ struct SyncStore {
     site: Arc<Site>,
     input_schema: InputSchema,
     manifest_idx_and_name: Arc<Vec<(u32, String)>>,
+    registry: Arc<MetricsRegistry>,
+    /// The number of entities of the deployment, updated after each write
+    entity_count: Gauge,
 }
 
 impl SyncStore {
@@ -85,18 +96,52 @@ impl SyncStore {
         logger: Logger,
         site: Arc<Site>,
         manifest_idx_and_name: Arc<Vec<(u32, String)>>,
+        registry: Arc<MetricsRegistry>,
     ) -> Result<Self, StoreError> {
         let store = WritableSubgraphStore(subgraph_store.clone());
         let writable = subgraph_store.for_site(site.as_ref())?.clone();
         let input_schema = subgraph_store.input_schema(&site.deployment)?;
-        Ok(Self {
+        let entity_count = registry
+            .new_deployment_gauge(
+                "deployment_entity_count",
+                "The number of entities of a subgraph deployment",
+                site.deployment.as_str(),
+            )
+            .expect("Failed to create `deployment_entity_count` gauge");
+        let store = Self {
             logger,
             store,
             writable,
             site,
             input_schema,
             manifest_idx_and_name,
-        })
+            registry,
+            entity_count,
+        };
+        store.observe_entity_count();
+        Ok(store)
+    }
+
+    /// Set the `deployment_entity_count` gauge from the count in the
+    /// database. Writes and reverts change the gauge by the change in the
+    /// count they made, so that we only need to read the count when we
+    /// start and after changes that do not report it. Not being able to
+    /// read the count only affects the metric, and is therefore not
+    /// treated as an error
+    fn observe_entity_count(&self) {
+        match self.writable.entity_count(&self.site) {
+            Ok(count) => self.entity_count.set(count as f64),
+            Err(e) => {
+                warn!(self.logger, "Failed to read entity count"; "error" => e.to_string())
+            }
+        }
+    }
+
+    /// Remove the metrics for this deployment from the registry so that
+    /// the next writable for it can register them again
+    fn unregister_metrics(&self) {
+        self.registry
+            .unregister(Box::new(self.entity_count.clone()));
     }
 
     /// Try to send a `StoreEvent`; if sending fails, log the error but
@@ -143,7 +188,10 @@ impl SyncStore {
             self.writable
                 .start_subgraph(logger, self.site.clone(), graft_base)?;
             self.store.primary_conn()?.copy_finished(self.site.as_ref())
-        })
+        })?;
+        // Grafting and copying set the entity count
+        self.observe_entity_count();
+        Ok(())
     }
 
     fn revert_block_operations(
@@ -151,15 +199,19 @@ impl SyncStore {
         block_ptr_to: BlockPtr,
         firehose_cursor: &FirehoseCursor,
     ) -> Result<(), StoreError> {
-        retry::forever(&self.logger, "revert_block_operations", || {
-            let event = self.writable.revert_block_operations(
+        self.writable.check_writer_lock(&self.site)?;
+        let count = retry::forever(&self.logger, "revert_block_operations", || {
+            let (event, count) = self.writable.revert_block_operations(
                 self.site.clone(),
                 block_ptr_to.clone(),
                 firehose_cursor,
             )?;
 
-            self.try_send_store_event(event)
-        })
+            self.try_send_store_event(event)?;
+            Ok(count)
+        })?;
+        self.entity_count.add(count as f64);
+        Ok(())
     }
 
     fn unfail_deterministic_error(
@@ -167,10 +219,15 @@ impl SyncStore {
         current_ptr: &BlockPtr,
         parent_ptr: &BlockPtr,
     ) -> Result<UnfailOutcome, StoreError> {
-        retry::forever(&self.logger, "unfail_deterministic_error", || {
+        let outcome = retry::forever(&self.logger, "unfail_deterministic_error", || {
             self.writable
                 .unfail_deterministic_error(self.site.clone(), current_ptr, parent_ptr)
-        })
+        })?;
+        // Unfailing might have reverted a block
+        if let UnfailOutcome::Unfailed = outcome {
+            self.observe_entity_count();
+        }
+        Ok(outcome)
     }
 
     fn unfail_non_deterministic_error(
@@ -216,8 +273,9 @@ impl SyncStore {
         batch: &Batch,
         stopwatch: &StopwatchMetrics,
     ) -> Result<(), StoreError> {
-        retry::forever(&self.logger, "transact_block_operations", move || {
-            let event = self.writable.transact_block_operations(
+        self.writable.check_writer_lock(&self.site)?;
+        let count = retry::forever(&self.logger, "transact_block_operations", move || {
+            let (event, count) = self.writable.transact_block_operations(
                 &self.logger,
                 self.site.clone(),
                 batch,
@@ -227,8 +285,10 @@ impl SyncStore {
 
             let _section = stopwatch.start_section("send_store_event");
             self.try_send_store_event(event)?;
-            Ok(())
-        })
+            Ok(count)
+        })?;
+        self.entity_count.add(count as f64);
+        Ok(())
     }
 
     fn get_many(
@@ -263,6 +323,17 @@ impl SyncStore {
         })
     }
 
+    fn max_timestamp(
+        &self,
+        entity_type: &EntityType,
+        block: BlockNumber,
+    ) -> Result<Option<i64>, StoreError> {
+        retry::forever(&self.logger, "max_timestamp", || {
+            self.writable
+                .max_timestamp(self.site.cheap_clone(), entity_type, block)
+        })
+    }
+
     async fn is_deployment_synced(&self) -> Result<bool, StoreError> {
         retry::forever_async(&self.logger, "is_deployment_synced", || async {
             self.writable
@@ -282,6 +353,32 @@ impl SyncStore {
         })
     }
 
+    /// Warm up the database caches for this copy, activate it, and then
+    /// unassign `replaced` if it is given. Warming up can take a while, and
+    /// is therefore done in the background so that the writer does not have
+    /// to wait for it
+    fn activate_in_background(&self, replaced: Option<Arc<Site>>) {
+        let store = self.store.0.clone();
+        let site = self.site.cheap_clone();
+        let logger = self.logger.cheap_clone();
+        graph::spawn_blocking_allow_panic(move || {
+            let res = store.activate(&site.as_ref().into()).and_then(|()| {
+                let replaced = match replaced {
+                    Some(replaced) => replaced,
+                    None => return Ok(()),
+                };
+                let pconn = store.primary_conn()?;
+                pconn.transaction(|| -> Result<_, StoreError> {
+                    let changes = pconn.unassign_subgraph(&replaced)?;
+                    store.send_store_event(&StoreEvent::new(changes))
+                })
+            });
+            if let Err(e) = res {
+                error!(logger, "Failed to activate copy"; "error" => e.to_string());
+            }
+        });
+    }
+
     async fn load_dynamic_data_sources(
         &self,
         block: BlockNumber,
@@ -338,12 +435,18 @@ impl SyncStore {
                 if let Some(src) = self.maybe_find_site(src)? {
                     if src.deployment == self.site.deployment {
                         let on_sync = self.writable.on_sync(&self.site)?;
-                        if on_sync.activate() {
-                            let pconn = self.store.primary_conn()?;
-                            pconn.activate(&self.site.as_ref().into())?;
-                        }
-                        if on_sync.replace() {
-                            self.unassign_subgraph(&src)?;
+                        if on_sync.activate() && ENV_VARS.store.warmup_rows > 0 {
+                            // The source keeps serving queries until the
+                            // copy is active
+                            self.activate_in_background(on_sync.replace().then_some(src));
+                        } else {
+                            if on_sync.activate() {
+                                let pconn = self.store.primary_conn()?;
+                                pconn.activate(&self.site.as_ref().into())?;
+                            }
+                            if on_sync.replace() {
+                                self.unassign_subgraph(&src)?;
+                            }
                         }
                     }
                 }
@@ -677,6 +780,9 @@ struct Queue {
 
     stopwatch: StopwatchMetrics,
 
+    /// The number of requests in `queue`
+    queue_size: Gauge,
+
     /// Wether we should attempt to combine writes into large batches
     /// spanning multiple blocks. This is initially `true` and gets set to
     /// `false` when the subgraph is marked as synced.
@@ -827,11 +933,13 @@ impl Queue {
                         // The request has been handled. It's now safe to remove it
                         // from the queue
                         queue.queue.pop().await;
+                        queue.queue_size.set(queue.queue.len() as f64);
                     }
                     Ok(Ok(Stop)) => {
                         // Graceful shutdown. We also handled the request
                         // successfully
                         queue.queue.pop().await;
+                        queue.queue_size.set(queue.queue.len() as f64);
                         return;
                     }
                     Ok(Err(e)) => {
@@ -850,6 +958,13 @@ impl Queue {
 
         let queue = BoundedQueue::with_capacity(capacity);
         let write_err = Mutex::new(None);
+        let queue_size = registry
+            .new_deployment_gauge(
+                "deployment_write_queue_size",
+                "The number of requests waiting to be written for a subgraph deployment",
+                store.site.deployment.as_str(),
+            )
+            .expect("Failed to create `deployment_write_queue_size` gauge");
 
         // Use a separate instance of the `StopwatchMetrics` for background
         // work since that has its own call hierarchy, and using the
@@ -869,6 +984,7 @@ impl Queue {
             write_err,
             poisoned: AtomicBool::new(false),
             stopwatch,
+            queue_size,
             batch_writes: AtomicBool::new(true),
             batch_ready_notify: batch_ready_notify.clone(),
         };
@@ -893,6 +1009,7 @@ impl Queue {
             self.stop_batching();
         }
         self.queue.push(Arc::new(req)).await;
+        self.queue_size.set(self.queue.len() as f64);
         Ok(())
     }
 
@@ -900,7 +1017,8 @@ impl Queue {
     /// is a write request. We will only append if several conditions are
     /// true:
     ///
-    ///   1. The subgraph is not synced
+    ///   1. The subgraph is not synced, or
+    ///      `GRAPH_STORE_MERGE_QUEUED_WRITES` is set
     ///   2. The newest request (back of the queue) is a write
     ///   3. The newest request is not already being processed by the
     ///      writing thread
@@ -917,11 +1035,13 @@ impl Queue {
     /// This strategy is closely tied to how start_writer waits for writes
     /// to fill up before writing them to maximize the chances that we build
     /// a 'full' write batch, i.e., one that is either big enough or old
-    /// enough
+    /// enough. Once the subgraph is synced, the writer does not wait, and
+    /// merging only adds a block to a write that would have had to wait
+    /// for the writer anyway
     async fn push_write(&self, batch: Batch) -> Result<(), StoreError> {
         let batch = if ENV_VARS.store.write_batch_size == 0
             || ENV_VARS.store.write_batch_duration.is_zero()
-            || !self.batch_writes()
+            || !(self.batch_writes() || ENV_VARS.store.merge_queued_writes)
         {
             Some(batch)
         } else {
@@ -1022,6 +1142,12 @@ impl Queue {
         self.push(Request::Stop).await
     }
 
+    fn unregister_metrics(&self) {
+        self.store
+            .registry
+            .unregister(Box::new(self.queue_size.clone()));
+    }
+
     fn check_err(&self) -> Result<(), StoreError> {
         if let Some(err) = self.write_err.lock().unwrap().take() {
             return Err(err);
@@ -1176,6 +1302,26 @@ impl Queue {
         Ok(items_from_database)
     }
 
+    /// The largest timestamp of the timeseries `entity_type` by looking at
+    /// both the queue and the store
+    fn max_timestamp(&self, entity_type: &EntityType) -> Result<Option<i64>, StoreError> {
+        let (max_in_queue, query_block) =
+            BlockTracker::fold(&self.queue, None, |max: Option<i64>, batch, at| {
+                batch
+                    .effective_ops(entity_type, at)
+                    .filter_map(|op| match op {
+                        EntityOp::Write { key: _, entity } => match entity.get("timestamp") {
+                            Some(Value::Int8(timestamp)) => Some(*timestamp),
+                            _ => None,
+                        },
+                        EntityOp::Remove { .. } => None,
+                    })
+                    .fold(max, |max, timestamp| max.max(Some(timestamp)))
+            });
+        let max_in_store = self.store.max_timestamp(entity_type, query_block)?;
+        Ok(max_in_queue.max(max_in_store))
+    }
+
     /// Load dynamic data sources by looking at both the queue and the store
     async fn load_dynamic_data_sources(
         &self,
@@ -1333,6 +1479,13 @@ impl Writer {
         }
     }
 
+    fn max_timestamp(&self, entity_type: &EntityType) -> Result<Option<i64>, StoreError> {
+        match self {
+            Writer::Sync(store) => store.max_timestamp(entity_type, BLOCK_NUMBER_MAX),
+            Writer::Async { queue, .. } => queue.max_timestamp(entity_type),
+        }
+    }
+
     async fn load_dynamic_data_sources(
         &self,
         manifest_idx_and_name: Vec<(u32, String)>,
@@ -1363,6 +1516,13 @@ impl Writer {
         }
     }
 
+    fn unregister_metrics(&self) {
+        match self {
+            Writer::Sync(_) => {}
+            Writer::Async { queue, .. } => queue.unregister_metrics(),
+        }
+    }
+
     fn deployment_synced(&self) {
         match self {
             Writer::Sync(_) => {}
@@ -1371,11 +1531,26 @@ impl Writer {
     }
 }
 
+/// What a `WritableStore` is used for
+#[derive(Clone, Debug)]
+pub(crate) enum Mode {
+    /// Index the deployment; the store writes everything it is handed
+    Write,
+    /// The store of a standby instance that runs on the given node. Such a
+    /// store never writes anything
+    Standby(NodeId),
+    /// The store for replaying the given block. It reads the state of the
+    /// deployment as of the block before it and never writes anything
+    Replay(BlockNumber),
+}
+
 pub struct WritableStore {
     store: Arc<SyncStore>,
     block_ptr: Mutex<Option<BlockPtr>>,
     block_cursor: Mutex<FirehoseCursor>,
     writer: Writer,
+    mode: Mode,
+    write_stats: Arc<DeploymentWriteStats>,
 }
 
 impl WritableStore {
@@ -1385,56 +1560,116 @@ impl WritableStore {
         site: Arc<Site>,
         manifest_idx_and_name: Arc<Vec<(u32, String)>>,
         registry: Arc<MetricsRegistry>,
+        mode: Mode,
     ) -> Result<Self, StoreError> {
+        let write_stats = WRITE_STATS.for_deployment(&site.deployment);
         let store = Arc::new(SyncStore::new(
             subgraph_store,
             logger.clone(),
             site,
             manifest_idx_and_name,
+            registry.clone(),
         )?);
         let block_ptr = Mutex::new(store.block_ptr().await?);
         let block_cursor = Mutex::new(store.block_cursor().await?);
-        let writer = Writer::new(
-            logger,
-            store.clone(),
-            ENV_VARS.store.write_queue_size,
-            registry,
-        );
+        // Only a store that writes needs a background writer
+        let queue_size = match mode {
+            Mode::Write => ENV_VARS.store.write_queue_size,
+            Mode::Standby(_) | Mode::Replay(_) => 0,
+        };
+        let writer = Writer::new(logger, store.clone(), queue_size, registry);
 
         Ok(Self {
             store,
             block_ptr,
             block_cursor,
             writer,
+            mode,
+            write_stats,
         })
     }
 
+    /// Fail if this store is not allowed to write
+    fn check_writable(&self) -> Result<(), StoreError> {
+        match &self.mode {
+            Mode::Write => Ok(()),
+            Mode::Standby(node) => Err(constraint_violation!(
+                "the standby instance of {} on node {} can not write to it",
+                self.store.site,
+                node
+            )),
+            Mode::Replay(block) => Err(constraint_violation!(
+                "replaying block {} of {} can not write to it",
+                block,
+                self.store.site
+            )),
+        }
+    }
+
+    /// The block at which a replay reads the state of the deployment, or
+    /// `None` if this store reads the latest state
+    fn replay_block(&self) -> Option<BlockNumber> {
+        match self.mode {
+            Mode::Replay(block) => Some(block - 1),
+            Mode::Write | Mode::Standby(_) => None,
+        }
+    }
+
     pub(crate) fn poisoned(&self) -> bool {
         self.writer.poisoned()
     }
 
-    pub(crate) async fn stop(&self) -> Result<(), StoreError> {
-        self.writer.stop().await
+    /// Write everything that is queued and stop the writer. If the writer
+    /// has failed, nothing can be written anymore and the writer has
+    /// already stopped; we only log the error so that the caller can still
+    /// let go of the deployment
+    pub(crate) async fn stop(&self) {
+        let res = match self.writer.flush().await {
+            Ok(()) => self.writer.stop().await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            warn!(self.store.logger, "Failed to write queued changes when stopping the writer";
+                  "error" => e.to_string());
+        }
+        self.writer.unregister_metrics();
+        self.store.unregister_metrics();
     }
 }
 
 impl ReadStore for WritableStore {
     fn get(&self, key: &EntityKey) -> Result<Option<Entity>, StoreError> {
-        self.writer.get(key)
+        match self.replay_block() {
+            Some(block) => self.store.get(key, block),
+            None => self.writer.get(key),
+        }
     }
 
     fn get_many(
         &self,
         keys: BTreeSet<EntityKey>,
     ) -> Result<BTreeMap<EntityKey, Entity>, StoreError> {
-        self.writer.get_many(keys)
+        match self.replay_block() {
+            Some(block) => self.store.get_many(keys, block),
+            None => self.writer.get_many(keys),
+        }
     }
 
     fn get_derived(
         &self,
         key: &DerivedEntityQuery,
     ) -> Result<BTreeMap<EntityKey, Entity>, StoreError> {
-        self.writer.get_derived(key)
+        match self.replay_block() {
+            Some(block) => self.store.get_derived(key, block, vec![]),
+            None => self.writer.get_derived(key),
+        }
+    }
+
+    fn max_timestamp(&self, entity_type: &EntityType) -> Result<Option<i64>, StoreError> {
+        match self.replay_block() {
+            Some(block) => self.store.max_timestamp(entity_type, block),
+            None => self.writer.max_timestamp(entity_type),
+        }
     }
 
     fn input_schema(&self) -> InputSchema {
@@ -1459,6 +1694,8 @@ impl DeploymentCursorTracker for WritableStore {
 #[async_trait::async_trait]
 impl WritableStoreTrait for WritableStore {
     async fn start_subgraph_deployment(&self, logger: &Logger) -> Result<(), StoreError> {
+        self.check_writable()?;
+
         let store = self.store.cheap_clone();
         let logger = logger.cheap_clone();
         graph::spawn_blocking_allow_panic(move || store.start_subgraph_deployment(&logger))
@@ -1477,6 +1714,8 @@ impl WritableStoreTrait for WritableStore {
         block_ptr_to: BlockPtr,
         firehose_cursor: FirehoseCursor,
     ) -> Result<(), StoreError> {
+        self.check_writable()?;
+
         *self.block_ptr.lock().unwrap() = Some(block_ptr_to.clone());
         *self.block_cursor.lock().unwrap() = firehose_cursor.clone();
 
@@ -1488,6 +1727,8 @@ impl WritableStoreTrait for WritableStore {
         current_ptr: &BlockPtr,
         parent_ptr: &BlockPtr,
     ) -> Result<UnfailOutcome, StoreError> {
+        self.check_writable()?;
+
         let outcome = self
             .store
             .unfail_deterministic_error(current_ptr, parent_ptr)?;
@@ -1507,10 +1748,12 @@ impl WritableStoreTrait for WritableStore {
         // We don't have to update in memory self.block_ptr
         // because the method call below doesn't rewind/revert
         // any block.
+        self.check_writable()?;
         self.store.unfail_non_deterministic_error(current_ptr)
     }
 
     async fn fail_subgraph(&self, error: SubgraphError) -> Result<(), StoreError> {
+        self.check_writable()?;
         self.store.fail_subgraph(error).await
     }
 
@@ -1529,6 +1772,9 @@ impl WritableStoreTrait for WritableStore {
         processed_data_sources: Vec<StoredDynamicDataSource>,
         is_non_fatal_errors_active: bool,
     ) -> Result<(), StoreError> {
+        self.check_writable()?;
+
+        self.write_stats.record(&mods);
         let batch = Batch::new(
             block_ptr_to.clone(),
             firehose_cursor.clone(),
@@ -1547,6 +1793,7 @@ impl WritableStoreTrait for WritableStore {
     }
 
     fn deployment_synced(&self) -> Result<(), StoreError> {
+        self.check_writable()?;
         self.writer.deployment_synced();
         self.store.deployment_synced()
     }
@@ -1556,6 +1803,7 @@ impl WritableStoreTrait for WritableStore {
     }
 
     fn unassign_subgraph(&self) -> Result<(), StoreError> {
+        self.check_writable()?;
         self.store.unassign_subgraph(&self.store.site)
     }
 
@@ -1563,9 +1811,18 @@ impl WritableStoreTrait for WritableStore {
         &self,
         manifest_idx_and_name: Vec<(u32, String)>,
     ) -> Result<Vec<StoredDynamicDataSource>, StoreError> {
-        self.writer
-            .load_dynamic_data_sources(manifest_idx_and_name)
-            .await
+        match self.replay_block() {
+            Some(block) => {
+                self.store
+                    .load_dynamic_data_sources(block, manifest_idx_and_name)
+                    .await
+            }
+            None => {
+                self.writer
+                    .load_dynamic_data_sources(manifest_idx_and_name)
+                    .await
+            }
+        }
     }
 
     async fn causality_region_curr_val(&self) -> Result<Option<CausalityRegion>, StoreError> {
@@ -1615,4 +1872,47 @@ impl WritableStoreTrait for WritableStore {
             Ok(None)
         }
     }
+
+    async fn take_over(self: Arc<Self>) -> Result<Option<Arc<dyn WritableStoreTrait>>, StoreError> {
+        let node = match &self.mode {
+            Mode::Standby(node) => node.clone(),
+            Mode::Write | Mode::Replay(_) => return Ok(None),
+        };
+
+        let logger = self.store.logger.clone();
+        let site = self.store.site.cheap_clone();
+        let store = Arc::new(self.store.store.0.clone());
+        let manifest_idx_and_name = self.store.manifest_idx_and_name.cheap_clone();
+        loop {
+            // The node that indexes the deployment holds the writer lock
+            // for it as long as its connection to the database is alive. We
+            // can only get a writable once it has released the lock or its
+            // connection has gone away
+            match store
+                .cheap_clone()
+                .writable(
+                    logger.clone(),
+                    site.id.into(),
+                    manifest_idx_and_name.cheap_clone(),
+                )
+                .await
+            {
+                Ok(writable) => {
+                    if self.store.store.take_over_assignment(&site, &node)? {
+                        info!(logger, "Standby instance took over the deployment";
+                            "node_id" => node.to_string());
+                        return Ok(Some(writable));
+                    }
+                    // The deployment was paused or is not supposed to have
+                    // a standby on this node anymore. Let go of the lock
+                    // and leave it to the assignment events to stop us if
+                    // necessary
+                    store.stop_subgraph(&site.as_ref().into()).await?;
+                }
+                Err(StoreError::DeploymentLocked(_)) => { /* keep waiting */ }
+                Err(e) => return Err(e),
+            }
+            graph::tokio::time::sleep(ENV_VARS.store.standby_poll_interval).await;
+        }
+    }
 }
@@ -549,6 +549,7 @@ async fn execute_subgraph_query_internal(
                 QueryExecutionOptions {
                     resolver,
                     deadline,
+                    running: None,
                     max_first: std::u32::MAX,
                     max_skip: std::u32::MAX,
                     trace,
@@ -642,6 +643,12 @@ pub fn primary_connection() -> graph_store_postgres::layout_for_tests::Connectio
     graph_store_postgres::layout_for_tests::Connection::new(conn)
 }
 
+/// The pool for the primary database, for tests that need to act like
+/// another graph-node instance
+pub fn primary_pool() -> ConnectionPool {
+    PRIMARY_POOL.clone()
+}
+
 pub fn primary_mirror() -> graph_store_postgres::layout_for_tests::Mirror {
     let pool = PRIMARY_POOL.clone();
     let map = HashMap::from_iter(Some((PRIMARY_SHARD.clone(), pool)));
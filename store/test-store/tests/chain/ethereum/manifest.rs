@@ -10,14 +10,15 @@ use graph::data::store::Value;
 use graph::data::subgraph::schema::SubgraphError;
 use graph::data::subgraph::{
     SPEC_VERSION_0_0_4, SPEC_VERSION_0_0_7, SPEC_VERSION_0_0_8, SPEC_VERSION_0_0_9,
-    SPEC_VERSION_0_1_0,
+    SPEC_VERSION_0_1_0, SPEC_VERSION_1_1_0,
 };
 use graph::data_source::offchain::OffchainDataSourceKind;
 use graph::data_source::DataSourceTemplate;
 use graph::entity;
 use graph::prelude::{
     anyhow, async_trait, serde_yaml, tokio, BigDecimal, BigInt, DeploymentHash, Link, Logger,
-    SubgraphManifest, SubgraphManifestValidationError, SubgraphStore, UnvalidatedSubgraphManifest,
+    SubgraphManifest, SubgraphManifestResolveError, SubgraphManifestValidationError, SubgraphStore,
+    UnvalidatedSubgraphManifest, BLOCK_NUMBER_MAX, ENV_VARS,
 };
 use graph::{
     blockchain::NodeCapabilities as _,
@@ -84,6 +85,17 @@ async fn resolve_manifest(
     text: &str,
     max_spec_version: Version,
 ) -> SubgraphManifest<graph_chain_ethereum::Chain> {
+    try_resolve_manifest(text, max_spec_version)
+        .await
+        .expect("Parsing simple manifest works")
+}
+
+/// Like `resolve_manifest`, but return the error if the manifest can not
+/// be resolved
+async fn try_resolve_manifest(
+    text: &str,
+    max_spec_version: Version,
+) -> Result<SubgraphManifest<graph_chain_ethereum::Chain>, SubgraphManifestResolveError> {
     let mut resolver = TextResolver::default();
     let id = DeploymentHash::new("Qmmanifest").unwrap();
 
@@ -96,17 +108,23 @@ async fn resolve_manifest(
     let resolver: Arc<dyn LinkResolverTrait> = Arc::new(resolver);
 
     let raw = serde_yaml::from_str(text).unwrap();
-    SubgraphManifest::resolve_from_raw(id, raw, &resolver, &LOGGER, max_spec_version)
-        .await
-        .expect("Parsing simple manifest works")
+    SubgraphManifest::resolve_from_raw(id, raw, &resolver, &LOGGER, max_spec_version).await
 }
 
 async fn resolve_unvalidated(text: &str) -> UnvalidatedSubgraphManifest<Chain> {
+    resolve_unvalidated_with_abi(text, ABI).await
+}
+
+/// Like `resolve_unvalidated`, but with `abi` as the contents of
+/// `/ipfs/Qmabi`
+async fn resolve_unvalidated_with_abi(text: &str, abi: &str) -> UnvalidatedSubgraphManifest<Chain> {
     let mut resolver = TextResolver::default();
     let id = DeploymentHash::new("Qmmanifest").unwrap();
 
     resolver.add(id.as_str(), &text);
     resolver.add("/ipfs/Qmschema", &GQL_SCHEMA);
+    resolver.add("/ipfs/Qmabi", &abi);
+    resolver.add("/ipfs/Qmmapping", &MAPPING_WITH_IPFS_FUNC_WASM);
 
     let resolver: Arc<dyn LinkResolverTrait> = Arc::new(resolver);
 
@@ -208,6 +226,43 @@ indexerHints:
     assert_eq!(manifest.history_blocks().unwrap(), 100);
 }
 
+#[tokio::test]
+async fn parse_indexer_hints_prune() {
+    fn yaml(prune: &str) -> String {
+        format!(
+            "
+dataSources: []
+schema:
+  file:
+    /: /ipfs/Qmschema
+specVersion: 0.1.0
+indexerHints:
+  prune: {prune}
+"
+        )
+    }
+
+    let manifest = resolve_manifest(&yaml("auto"), SPEC_VERSION_0_1_0).await;
+    assert_eq!(
+        manifest.history_blocks().unwrap(),
+        ENV_VARS.min_history_blocks
+    );
+
+    let manifest = resolve_manifest(&yaml("never"), SPEC_VERSION_0_1_0).await;
+    assert_eq!(manifest.history_blocks().unwrap(), BLOCK_NUMBER_MAX);
+
+    let blocks = ENV_VARS.min_history_blocks + 1000;
+    let manifest = resolve_manifest(&yaml(&blocks.to_string()), SPEC_VERSION_0_1_0).await;
+    assert_eq!(manifest.history_blocks().unwrap(), blocks);
+
+    // Asking for less history than the node allows gets the minimum
+    let manifest = resolve_manifest(&yaml("1"), SPEC_VERSION_0_1_0).await;
+    assert_eq!(
+        manifest.history_blocks().unwrap(),
+        ENV_VARS.min_history_blocks
+    );
+}
+
 #[test]
 fn graft_failed_subgraph() {
     const YAML: &str = "
@@ -248,6 +303,8 @@ specVersion: 0.0.2
             message: "deterministic error".to_string(),
             block_ptr: Some(test_store::BLOCKS[1].clone()),
             handler: None,
+            data_source: None,
+            transaction_hash: None,
             deterministic: true,
         };
 
@@ -364,6 +421,8 @@ specVersion: 0.0.2
             message: "deterministic error".to_string(),
             block_ptr: Some(test_store::BLOCKS[1].clone()),
             handler: None,
+            data_source: None,
+            transaction_hash: None,
             deterministic: true,
         };
 
@@ -570,6 +629,150 @@ specVersion: 0.0.8
     assert_eq!("Qmmanifest", manifest.id.as_str());
 }
 
+/// A manifest with one data source whose mapping has the given
+/// `handlers`, which must be indented by six spaces
+fn handlers_manifest(handlers: &str, spec_version: &str) -> String {
+    format!(
+        "
+dataSources:
+  - kind: ethereum/contract
+    name: Factory
+    network: mainnet
+    source:
+      address: \"0x0000000000000000000000000000000000000000\"
+      abi: Factory
+    mapping:
+      kind: ethereum/events
+      apiVersion: 0.0.7
+      language: wasm/assemblyscript
+      entities:
+        - TestEntity
+      file:
+        /: /ipfs/Qmmapping
+      abis:
+        - name: Factory
+          file:
+            /: /ipfs/Qmabi
+{handlers}
+schema:
+  file:
+    /: /ipfs/Qmschema
+specVersion: {spec_version}
+"
+    )
+}
+
+/// Check that a manifest with `handlers` can not be resolved with spec
+/// version 1.0.0, and return the manifest resolved with spec version 1.1.0
+async fn requires_spec_version_1_1_0(handlers: &str) -> SubgraphManifest<Chain> {
+    let err = try_resolve_manifest(&handlers_manifest(handlers, "1.0.0"), SPEC_VERSION_1_1_0)
+        .await
+        .expect_err("the handlers are not supported before spec version 1.1.0");
+    assert!(
+        err.to_string()
+            .contains("data source `Factory` requires at least version 1.1.0"),
+        "{}",
+        err
+    );
+
+    resolve_manifest(&handlers_manifest(handlers, "1.1.0"), SPEC_VERSION_1_1_0).await
+}
+
+#[tokio::test]
+async fn handler_start_requires_spec_version_1_1_0() {
+    let manifest = requires_spec_version_1_1_0(
+        "
+      blockHandlers:
+        - handler: handleBlock
+          startBlock: 100",
+    )
+    .await;
+    let data_source = manifest.data_sources[0].as_onchain().unwrap();
+    assert_eq!(
+        Some(100),
+        data_source.mapping.block_handlers[0].start.start_block
+    );
+}
+
+#[tokio::test]
+async fn event_filter_requires_spec_version_1_1_0() {
+    let manifest = requires_spec_version_1_1_0(
+        "
+      eventHandlers:
+        - event: Transfer(uint256)
+          handler: handleTransfer
+          filter: \"amount > 0\"",
+    )
+    .await;
+    let data_source = manifest.data_sources[0].as_onchain().unwrap();
+    assert!(data_source.mapping.event_handlers[0].filter.is_some());
+}
+
+#[tokio::test]
+async fn topic_filter_requires_spec_version_1_1_0() {
+    let manifest = requires_spec_version_1_1_0(
+        "
+      eventHandlers:
+        - event: Transfer(indexed address)
+          handler: handleTransfer
+          topic1:
+            - \"0x0000000000000000000000000000000000000000000000000000000000000001\"",
+    )
+    .await;
+    let data_source = manifest.data_sources[0].as_onchain().unwrap();
+    assert!(data_source.mapping.event_handlers[0].topics.is_set());
+}
+
+#[tokio::test]
+async fn abi_from_context_requires_spec_version_1_1_0() {
+    let yaml = |spec_version: &str| {
+        format!(
+            "
+dataSources: []
+templates:
+  - kind: ethereum/contract
+    name: Pool
+    network: mainnet
+    source:
+      abi: Factory
+      abiFromContext: poolAbi
+    mapping:
+      kind: ethereum/events
+      apiVersion: 0.0.7
+      language: wasm/assemblyscript
+      entities:
+        - TestEntity
+      file:
+        /: /ipfs/Qmmapping
+      abis:
+        - name: Factory
+          file:
+            /: /ipfs/Qmabi
+      blockHandlers:
+        - handler: handleBlock
+schema:
+  file:
+    /: /ipfs/Qmschema
+specVersion: {spec_version}
+"
+        )
+    };
+
+    let err = try_resolve_manifest(&yaml("1.0.0"), SPEC_VERSION_1_1_0)
+        .await
+        .expect_err("templates can not use abiFromContext before spec version 1.1.0");
+    assert!(
+        err.to_string()
+            .contains("data source template `Pool` requires at least version 1.1.0"),
+        "{}",
+        err
+    );
+
+    let manifest = resolve_manifest(&yaml("1.1.0"), SPEC_VERSION_1_1_0).await;
+    let template = manifest.templates[0].as_onchain().unwrap();
+    assert_eq!(Some("poolAbi"), template.source.abi_from_context.as_deref());
+}
+
 #[tokio::test]
 async fn parse_data_source_with_end_block() {
     const YAML: &str = "
@@ -878,6 +1081,149 @@ specVersion: 0.0.2
     assert_eq!(true, required_capabilities.traces);
 }
 
+#[test]
+fn lint_manifest() {
+    const YAML: &str = "
+dataSources:
+  - kind: ethereum/contract
+    name: Factory
+    network: mainnet
+    source:
+      address: \"0x0000000000000000000000000000000000000000\"
+      abi: Factory
+    mapping:
+      kind: ethereum/events
+      apiVersion: 0.0.4
+      language: wasm/assemblyscript
+      entities:
+        - TestEntity
+      file:
+        /: /ipfs/Qmmapping
+      abis:
+        - name: Factory
+          file:
+            /: /ipfs/Qmabi
+        - name: Unused
+          file:
+            /: /ipfs/Qmabi
+      eventHandlers:
+        - event: Transfer(address)
+          handler: handleTransfer
+      callHandlers:
+        - function: set(uint256)
+          handler: handleSet
+schema:
+  file:
+    /: /ipfs/Qmschema
+specVersion: 0.0.2
+";
+
+    test_store::run_test_sequentially(|store| async move {
+        let manifest = resolve_manifest(YAML, SPEC_VERSION_0_0_4).await;
+        let warnings: Vec<_> = manifest
+            .lint()
+            .into_iter()
+            .map(|warning| warning.to_string())
+            .collect();
+
+        assert_eq!(
+            vec![
+                "specVersion 0.0.2 is deprecated, use specVersion 0.0.4 or later",
+                "data source Factory: ABI `Unused` is not used since the mapping does not make any contract calls",
+            ],
+            warnings
+        );
+
+        // Handlers whose event or function is not in the ABI are
+        // validation errors, not warnings
+        let errors: Vec<_> = resolve_unvalidated(YAML)
+            .await
+            .validate(store.subgraph_store(), true)
+            .await
+            .expect_err("Validation must fail")
+            .into_iter()
+            .map(|e| e.to_string())
+            .collect();
+        for expected in [
+            "data source Factory is invalid: event `Transfer(address)` of handler `handleTransfer` not found in ABI `Factory`",
+            "data source Factory is invalid: function `set(uint256)` of handler `handleSet` not found in ABI `Factory`",
+        ] {
+            assert!(
+                errors.iter().any(|e| e.starts_with(expected)),
+                "missing `{}` in {:?}",
+                expected,
+                errors
+            );
+        }
+    })
+}
+
+#[test]
+fn handlers_not_in_abi_fail_validation() {
+    const FACTORY_ABI: &str = "[{\"type\":\"event\",\"name\":\"Transfer\",\"anonymous\":false,\"inputs\":[{\"name\":\"to\",\"type\":\"address\",\"indexed\":true}]},\
+        {\"type\":\"function\",\"name\":\"transfer\",\"stateMutability\":\"nonpayable\",\"inputs\":[{\"name\":\"to\",\"type\":\"address\"}],\"outputs\":[]},\
+        {\"type\":\"function\",\"name\":\"balanceOf\",\"stateMutability\":\"view\",\"inputs\":[{\"name\":\"owner\",\"type\":\"address\"}],\"outputs\":[{\"name\":\"\",\"type\":\"uint256\"}]}]";
+    const YAML: &str = "
+dataSources:
+  - kind: ethereum/contract
+    name: Factory
+    network: mainnet
+    source:
+      address: \"0x0000000000000000000000000000000000000000\"
+      abi: Factory
+      startBlock: 1
+    mapping:
+      kind: ethereum/events
+      apiVersion: 0.0.4
+      language: wasm/assemblyscript
+      entities:
+        - TestEntity
+      file:
+        /: /ipfs/Qmmapping
+      abis:
+        - name: Factory
+          file:
+            /: /ipfs/Qmabi
+      eventHandlers:
+        - event: Transfer(indexed address)
+          handler: handleTransfer
+        - event: Transfer(uint256)
+          handler: handleTransferAmount
+      callHandlers:
+        - function: transfer(address)
+          handler: handleTransferCall
+        - function: balanceOf(address)
+          handler: handleBalanceOf
+schema:
+  file:
+    /: /ipfs/Qmschema
+features:
+  - ipfsOnEthereumContracts
+specVersion: 0.0.4
+";
+
+    test_store::run_test_sequentially(|store| async move {
+        let errors: Vec<_> = resolve_unvalidated_with_abi(YAML, FACTORY_ABI)
+            .await
+            .validate(store.subgraph_store(), true)
+            .await
+            .expect_err("Validation must fail")
+            .into_iter()
+            .map(|e| e.to_string())
+            .collect();
+
+        // Only the handlers whose event or function is missing from the
+        // ABI are errors; view functions are never called in transactions
+        assert_eq!(
+            vec![
+                "data source Factory is invalid: event `Transfer(uint256)` of handler `handleTransferAmount` not found in ABI `Factory`, did you mean `Transfer(indexed address)`?",
+                "data source Factory is invalid: function `balanceOf(address)` of handler `handleBalanceOf` not found in ABI `Factory`, did you mean `transfer(address)`?",
+            ],
+            errors
+        );
+    })
+}
+
 #[test]
 fn undeclared_grafting_feature_causes_feature_validation_error() {
     const YAML: &str = "
@@ -1,12 +1,13 @@
 use graph::blockchain::block_stream::FirehoseCursor;
 use graph::components::store::{
     DeploymentCursorTracker, DerivedEntityQuery, GetScope, LoadRelatedRequest, ReadStore,
-    StoredDynamicDataSource, WritableStore,
+    StoredDynamicDataSource, WritableStore, WriteConflictMode,
 };
 use graph::data::store::Id;
 use graph::data::subgraph::schema::{DeploymentCreate, SubgraphError, SubgraphHealth};
 use graph::data_source::CausalityRegion;
 use graph::schema::{EntityKey, EntityType, InputSchema};
+use graph::util::lfu_cache::LfuCache;
 use graph::{
     components::store::{DeploymentId, DeploymentLocator},
     prelude::{DeploymentHash, Entity, EntityCache, EntityModification, Value},
@@ -37,6 +38,18 @@ lazy_static! {
                 founded: Int
                 label: String
             }
+
+            interface Musician {
+                id: ID!
+            }
+
+            type Soloist implements Musician @entity {
+                id: ID!
+            }
+
+            type Singer implements Musician @entity {
+                id: ID!
+            }
             ",
         SUBGRAPH_ID.clone(),
     )
@@ -72,6 +85,10 @@ impl ReadStore for MockStore {
         Ok(self.get_many_res.clone())
     }
 
+    fn max_timestamp(&self, _entity_type: &EntityType) -> Result<Option<i64>, StoreError> {
+        Ok(None)
+    }
+
     fn input_schema(&self) -> InputSchema {
         SCHEMA.clone()
     }
@@ -176,6 +193,10 @@ impl WritableStore for MockStore {
     async fn restart(self: Arc<Self>) -> Result<Option<Arc<dyn WritableStore>>, StoreError> {
         unimplemented!()
     }
+
+    async fn take_over(self: Arc<Self>) -> Result<Option<Arc<dyn WritableStore>>, StoreError> {
+        unimplemented!()
+    }
 }
 
 fn make_band_key(id: &'static str) -> EntityKey {
@@ -233,6 +254,45 @@ fn entity_version_map(entity_type: &str, entities: Vec<Entity>) -> BTreeMap<Enti
     map
 }
 
+#[test]
+fn conflicting_interface_ids() {
+    let soloist = SCHEMA.entity_type("Soloist").unwrap();
+    let singer = SCHEMA.entity_type("Singer").unwrap();
+    let nina = entity! { SCHEMA => id: "nina" };
+    let store = MockStore::new(entity_version_map("Singer", vec![nina.clone()]));
+    let mut cache = EntityCache::new(Arc::new(store));
+
+    // New ids and updates of existing entities are fine
+    let bob = entity! { SCHEMA => id: "bob" };
+    cache
+        .set(soloist.parse_key("bob").unwrap(), bob.clone())
+        .unwrap();
+    cache
+        .set(singer.parse_key("nina").unwrap(), nina.clone())
+        .unwrap();
+    cache
+        .set(soloist.parse_key("bob").unwrap(), bob.clone())
+        .unwrap();
+
+    // Ids that are used by another type with the same interface are not,
+    // no matter whether that entity is in the store or was created in
+    // this block
+    let conflict = |err: anyhow::Error| {
+        matches!(
+            err.downcast_ref::<StoreError>(),
+            Some(StoreError::ConflictingId(..))
+        )
+    };
+    let err = cache
+        .set(soloist.parse_key("nina").unwrap(), nina)
+        .unwrap_err();
+    assert!(conflict(err));
+    let err = cache
+        .set(singer.parse_key("bob").unwrap(), bob)
+        .unwrap_err();
+    assert!(conflict(err));
+}
+
 #[test]
 fn overwrite_modifications() {
     // Pre-populate the store with entities so that the cache treats
@@ -306,6 +366,116 @@ fn consecutive_modifications() {
     );
 }
 
+#[test]
+fn get_many() {
+    let sigurros = entity! { SCHEMA => id: "sigurros", name: "Sigur Ros" };
+    let store = {
+        let entities = vec![
+            entity! { SCHEMA => id: "mogwai", name: "Mogwai" },
+            sigurros.clone(),
+        ];
+        MockStore::new(entity_version_map("Band", entities))
+    };
+
+    let store = Arc::new(store);
+    let mut cache = EntityCache::new(store);
+
+    // Changes made in the cache are reflected in the result
+    let mogwai = entity! { SCHEMA => id: "mogwai", name: "Mogwai", founded: 1995 };
+    cache.set(make_band_key("mogwai"), mogwai.clone()).unwrap();
+
+    // Entities are returned in the order of the keys, and missing ones
+    // are left out
+    let keys = vec![
+        make_band_key("sigurros"),
+        make_band_key("daftpunk"),
+        make_band_key("mogwai"),
+    ];
+    let entities = cache.get_many(&keys).unwrap();
+    assert_eq!(entities, vec![sigurros, mogwai]);
+}
+
+#[test]
+fn saved_updates_roundtrip() {
+    let store = {
+        let entities = vec![
+            entity! { SCHEMA => id: "mogwai", name: "Mogwai", label: "Chemikal Underground" },
+            entity! { SCHEMA => id: "sigurros", name: "Sigur Ros" },
+        ];
+        MockStore::new(entity_version_map("Band", entities))
+    };
+    let store = Arc::new(store);
+
+    // An update, an overwrite, a removal and an insert
+    let mut cache = EntityCache::new(store.clone());
+    let update = entity! { SCHEMA => id: "mogwai", founded: 1995, label: Value::Null };
+    cache.set(make_band_key("mogwai"), update).unwrap();
+    cache.remove(make_band_key("sigurros"));
+    let sigurros = entity! { SCHEMA => id: "sigurros", name: "Sigur Rós" };
+    cache.set(make_band_key("sigurros"), sigurros).unwrap();
+    let daftpunk = entity! { SCHEMA => id: "daftpunk", name: "Daft Punk" };
+    cache.set(make_band_key("daftpunk"), daftpunk).unwrap();
+    cache.remove(make_band_key("daftpunk"));
+    let tortoise = entity! { SCHEMA => id: "tortoise", name: "Tortoise" };
+    cache.set(make_band_key("tortoise"), tortoise).unwrap();
+
+    let saved = serde_json::to_string(&cache.saved_updates()).unwrap();
+    let mut restored = EntityCache::new(store);
+    restored
+        .restore_updates(serde_json::from_str(&saved).unwrap())
+        .unwrap();
+
+    assert_eq!(
+        sort_by_entity_key(cache.as_modifications(0).unwrap().modifications),
+        sort_by_entity_key(restored.as_modifications(0).unwrap().modifications)
+    );
+}
+
+#[test]
+fn write_conflicts() {
+    let store = MockStore::new(BTreeMap::new());
+    let mut state = BlockState::<graph_chain_ethereum::Chain>::new(store, LfuCache::new());
+    state
+        .entity_cache
+        .set_write_conflict_mode(WriteConflictMode::Warn);
+
+    let key = make_band_key("mogwai");
+
+    state.enter_handler("handleFounded");
+    let data = entity! { SCHEMA => id: "mogwai", name: "Mogwai", founded: 1995 };
+    state.entity_cache.set(key.clone(), data).unwrap();
+    assert_eq!(state.entity_cache.write_conflicts(), vec![]);
+    state.exit_handler();
+
+    // Changing a field after reading the entity is not order-dependent
+    state.enter_handler("handleReissue");
+    state.entity_cache.get(&key, GetScope::Store).unwrap();
+    let data = entity! { SCHEMA => id: "mogwai", founded: 1996 };
+    state.entity_cache.set(key.clone(), data).unwrap();
+    assert_eq!(state.entity_cache.write_conflicts(), vec![]);
+    state.exit_handler();
+
+    // Setting `name` to the same value is fine, but overwriting `founded`
+    // without looking at it first is a conflict
+    state.enter_handler("handleRename");
+    let data = entity! { SCHEMA => id: "mogwai", name: "Mogwai", founded: 1997 };
+    state.entity_cache.set(key.clone(), data).unwrap();
+    let conflicts = state.entity_cache.write_conflicts();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].key, key);
+    assert_eq!(conflicts[0].field.as_str(), "founded");
+    assert_eq!(conflicts[0].handler.as_str(), "handleRename");
+    assert_eq!(conflicts[0].previous_handler.as_str(), "handleReissue");
+    state.exit_handler();
+
+    // The same handler running again is not considered a conflict
+    state.enter_handler("handleRename");
+    let data = entity! { SCHEMA => id: "mogwai", founded: 1998 };
+    state.entity_cache.set(key.clone(), data).unwrap();
+    assert_eq!(state.entity_cache.write_conflicts(), vec![]);
+    state.exit_handler();
+}
+
 const ACCOUNT_GQL: &str = "
     type Account @entity {
         id: ID!
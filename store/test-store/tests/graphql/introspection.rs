@@ -120,6 +120,7 @@ async fn introspection_query(schema: Arc<ApiSchema>, query: &str) -> QueryResult
     let options = QueryExecutionOptions {
         resolver: MockResolver,
         deadline: None,
+        running: None,
         max_first: std::u32::MAX,
         max_skip: std::u32::MAX,
         trace: false,
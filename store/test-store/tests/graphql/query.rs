@@ -2547,6 +2547,33 @@ fn can_query_meta() {
     run_query(QUERY4, |result, _| {
         assert!(result.has_errors());
     });
+
+    // the subgraph has not indexed enough blocks for any of them to be
+    // final
+    const QUERY5: &str = "query { _meta { block { number isFinal } finalBlock { number } } }";
+    run_query(QUERY5, |result, _| {
+        let exp = object! {
+            _meta: object! {
+                block: object! {
+                    number: 1,
+                    isFinal: false
+                },
+                finalBlock: r::Value::Null
+            },
+        };
+        assert_eq!(extract_data!(result), Some(exp));
+    });
+
+    const QUERY6: &str = "query { _meta(block: { final: true }) { block { number } } }";
+    run_query(QUERY6, |result, _| {
+        assert!(result.has_errors());
+    });
+
+    // `final` can not be combined with a specific block
+    const QUERY7: &str = "query { _meta(block: { number: 1, final: true }) { block { number } } }";
+    run_query(QUERY7, |result, _| {
+        assert!(result.has_errors());
+    });
 }
 
 #[test]
@@ -2568,6 +2595,8 @@ fn non_fatal_errors() {
             message: "cow template handler could not moo event transaction".to_string(),
             block_ptr: Some(BLOCK_TWO.block_ptr()),
             handler: Some("handleMoo".to_string()),
+            data_source: None,
+            transaction_hash: None,
             deterministic: true,
         };
 
@@ -2713,6 +2742,8 @@ fn deterministic_error() {
             message: "cow template handler could not moo event transaction".to_string(),
             block_ptr: Some(BLOCK_TWO.block_ptr()),
             handler: Some("handleMoo".to_string()),
+            data_source: None,
+            transaction_hash: None,
             deterministic: true,
         };
 
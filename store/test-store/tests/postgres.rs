@@ -1,6 +1,8 @@
 pub mod postgres {
+    pub mod aggregation;
     pub mod chain_head;
     pub mod graft;
+    pub mod join_table;
     pub mod relational;
     pub mod relational_bytes;
     pub mod store;
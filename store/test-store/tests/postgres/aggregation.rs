@@ -0,0 +1,193 @@
+//! Tests for the rollups of aggregations that the store maintains
+use graph::components::store::ReadStore;
+use graph::data::graphql::object;
+use graph::data::query::QueryTarget;
+use graph::entity;
+use graph::prelude::web3::types::H256;
+use graph::prelude::*;
+use graph::schema::InputSchema;
+use graph_store_postgres::layout_for_tests::writable;
+use lazy_static::lazy_static;
+use test_store::*;
+
+const SCHEMA_GQL: &str = r#"
+    type Data @entity(timeseries: true) {
+        id: Int8!
+        timestamp: Int8!
+        amount: Int8!
+    }
+
+    type Stats @aggregation(intervals: ["hour"], source: "Data") {
+        id: Int8!
+        timestamp: Int8!
+        total: Int8! @aggregate(fn: "sum", arg: "amount")
+        points: Int8! @aggregate(fn: "count")
+    }
+"#;
+
+lazy_static! {
+    static ref TEST_SUBGRAPH_ID: DeploymentHash = DeploymentHash::new("aggregation").unwrap();
+    static ref TEST_SUBGRAPH_SCHEMA: InputSchema =
+        InputSchema::parse(SCHEMA_GQL, TEST_SUBGRAPH_ID.clone())
+            .expect("Failed to parse user schema");
+}
+
+fn block_pointer(number: u8) -> BlockPtr {
+    let hash = H256::from([number; 32]);
+    BlockPtr::from((hash, number as BlockNumber))
+}
+
+/// Write a data point with id `block`, `timestamp` and `amount` at `block`
+async fn insert_data(deployment: &DeploymentLocator, block: u8, timestamp: i64, amount: i64) {
+    let data_type = TEST_SUBGRAPH_SCHEMA.entity_type("Data").unwrap();
+    let data = entity! { TEST_SUBGRAPH_SCHEMA =>
+        id: block as i64,
+        timestamp: timestamp,
+        amount: amount
+    };
+    let entity_op = EntityOperation::Set {
+        key: data_type.key(data.id()),
+        data,
+    };
+    transact_entity_operations(
+        &SUBGRAPH_STORE,
+        deployment,
+        block_pointer(block),
+        vec![entity_op],
+    )
+    .await
+    .unwrap();
+}
+
+/// The hourly rollups as of `block`, or as of the latest block if `block`
+/// is `None`
+async fn hourly_stats(block: Option<u8>) -> r::Value {
+    let block = block
+        .map(|block| format!("block: {{ number: {block} }}, "))
+        .unwrap_or_default();
+    let query = format!(
+        "query {{ statsHours({block}orderBy: timestamp) {{ id timestamp total points }} }}"
+    );
+    let query = Query::new(
+        graphql_parser::parse_query(&query).unwrap().into_static(),
+        None,
+        false,
+    );
+    let target = QueryTarget::Deployment(TEST_SUBGRAPH_ID.clone(), Default::default());
+    let result = execute_subgraph_query(query, target)
+        .await
+        .first()
+        .unwrap()
+        .duplicate();
+    match result.to_result() {
+        Err(errors) => panic!("Unexpected errors return for query: {:#?}", errors),
+        Ok(data) => data.unwrap(),
+    }
+}
+
+fn stats(timestamp: i64, total: i64, points: i64) -> r::Value {
+    object! {
+        id: timestamp.to_string(),
+        timestamp: timestamp.to_string(),
+        total: total.to_string(),
+        points: points.to_string(),
+    }
+}
+
+#[test]
+fn rollups_are_written_at_the_block_that_completes_them() {
+    const HOUR: i64 = 3600;
+
+    run_test_sequentially(|store| async move {
+        remove_subgraphs();
+        let deployment = create_test_subgraph(&TEST_SUBGRAPH_ID, SCHEMA_GQL).await;
+
+        // Hold the writer back so that all blocks are written in one batch
+        flush(&deployment).await.unwrap();
+        writable::allow_steps(&deployment, 0).await;
+
+        // The first two data points lie before the epoch and belong to
+        // the hour starting at `-HOUR`
+        insert_data(&deployment, 1, -HOUR / 2, 1).await;
+        insert_data(&deployment, 2, -HOUR / 6, 2).await;
+        insert_data(&deployment, 3, HOUR / 2, 3).await;
+        insert_data(&deployment, 4, HOUR * 2 / 3, 4).await;
+        insert_data(&deployment, 5, HOUR + 100, 5).await;
+        flush(&deployment).await.unwrap();
+
+        let first = stats(-HOUR, 3, 2);
+        let second = stats(0, 7, 2);
+
+        // Each rollup becomes visible at the block that completed its
+        // hour; the hour of the latest data point is not complete yet
+        let exp = object! { statsHours: Vec::<r::Value>::new() };
+        assert_eq!(exp, hourly_stats(Some(2)).await);
+        let exp = object! { statsHours: vec![first.clone()] };
+        assert_eq!(exp, hourly_stats(Some(3)).await);
+        assert_eq!(exp, hourly_stats(Some(4)).await);
+        let exp = object! { statsHours: vec![first.clone(), second.clone()] };
+        assert_eq!(exp, hourly_stats(Some(5)).await);
+        assert_eq!(exp, hourly_stats(None).await);
+
+        // Reverting the block that completed an hour removes its rollup
+        revert_block(&store, &deployment, &block_pointer(4)).await;
+        let exp = object! { statsHours: vec![first.clone()] };
+        assert_eq!(exp, hourly_stats(None).await);
+
+        // It is computed again once the hour is complete again
+        insert_data(&deployment, 5, HOUR * 2 + 100, 10).await;
+        flush(&deployment).await.unwrap();
+        let exp = object! { statsHours: vec![first.clone(), second.clone()] };
+        assert_eq!(exp, hourly_stats(None).await);
+        assert_eq!(exp, hourly_stats(Some(5)).await);
+        let exp = object! { statsHours: vec![first] };
+        assert_eq!(exp, hourly_stats(Some(4)).await);
+    })
+}
+
+#[test]
+fn data_points_for_complete_intervals_do_not_change_rollups() {
+    const HOUR: i64 = 3600;
+
+    run_test_sequentially(|_| async move {
+        remove_subgraphs();
+        let deployment = create_test_subgraph(&TEST_SUBGRAPH_ID, SCHEMA_GQL).await;
+        let data_type = TEST_SUBGRAPH_SCHEMA.entity_type("Data").unwrap();
+        let writable = SUBGRAPH_STORE
+            .cheap_clone()
+            .writable(LOGGER.clone(), deployment.id, Arc::new(Vec::new()))
+            .await
+            .unwrap();
+        assert_eq!(None, writable.max_timestamp(&data_type).unwrap());
+
+        // The latest timestamp includes data points that are still queued
+        flush(&deployment).await.unwrap();
+        writable::allow_steps(&deployment, 0).await;
+        insert_data(&deployment, 1, HOUR / 2, 1).await;
+        insert_data(&deployment, 2, HOUR + 100, 2).await;
+        assert_eq!(
+            Some(HOUR + 100),
+            writable.max_timestamp(&data_type).unwrap()
+        );
+        flush(&deployment).await.unwrap();
+        assert_eq!(
+            Some(HOUR + 100),
+            writable.max_timestamp(&data_type).unwrap()
+        );
+        let exp = object! { statsHours: vec![stats(0, 1, 1)] };
+        assert_eq!(exp, hourly_stats(None).await);
+
+        // The host exports reject a data point for the first hour since it
+        // is complete; if one reaches the store anyway, the rollup that an
+        // earlier write produced does not change
+        insert_data(&deployment, 3, HOUR / 4, 10).await;
+        insert_data(&deployment, 4, 2 * HOUR + 1, 4).await;
+        flush(&deployment).await.unwrap();
+        let exp = object! { statsHours: vec![stats(0, 1, 1), stats(HOUR, 2, 1)] };
+        assert_eq!(exp, hourly_stats(None).await);
+        assert_eq!(
+            Some(2 * HOUR + 1),
+            writable.max_timestamp(&data_type).unwrap()
+        );
+    })
+}
@@ -1,8 +1,9 @@
 //! Test ChainStore implementation of Store, in particular, how
 //! the chain head pointer gets updated in various situations
 
-use graph::blockchain::{BlockHash, BlockPtr};
-use graph::env::ENV_VARS;
+use graph::blockchain::{BlockHash, BlockPtr, BlockchainMap};
+use graph::env::{EnvVars, ENV_VARS};
+use graph::ipfs_client::IpfsClient;
 use graph::prelude::futures03::executor;
 use std::future::Future;
 use std::sync::Arc;
@@ -14,6 +15,9 @@ use graph::prelude::{BlockNumber, QueryStoreManager, QueryTarget};
 use graph::{cheap_clone::CheapClone, prelude::web3::types::H160};
 use graph::{components::store::BlockStore as _, prelude::DeploymentHash};
 use graph::{components::store::ChainStore as _, prelude::EthereumCallCache as _};
+use graph_core::LinkResolver;
+use graph_graphql::prelude::GraphQlRunner;
+use graph_server_index_node::IndexNodeService;
 use graph_store_postgres::Store as DieselStore;
 use graph_store_postgres::{layout_for_tests::FAKE_NETWORK_SHARED, ChainStore as DieselChainStore};
 
@@ -241,6 +245,81 @@ fn test_get_block_number() {
     })
 }
 
+#[test]
+fn check_block_ptr() {
+    let chain = vec![&*GENESIS_BLOCK, &*BLOCK_ONE, &*BLOCK_TWO];
+
+    run_test_async(chain, move |chain_store, _, _| async move {
+        let known = chain_store
+            .check_block_ptr(&BLOCK_ONE.block_ptr())
+            .await
+            .unwrap();
+        assert!(known);
+
+        let unknown = chain_store
+            .check_block_ptr(&BLOCK_THREE.block_ptr())
+            .await
+            .unwrap();
+        assert!(!unknown);
+
+        // The hash of block one with the number of block two
+        let mismatched = BlockPtr::new(BLOCK_ONE.block_hash(), BLOCK_TWO.number);
+        chain_store
+            .check_block_ptr(&mismatched)
+            .await
+            .expect_err("block number does not match the hash");
+    })
+}
+
+#[test]
+fn cached_blocks_in_range() {
+    fn hashes(blocks: Option<Vec<json::Value>>) -> Option<Vec<BlockHash>> {
+        blocks.map(|blocks| {
+            blocks
+                .into_iter()
+                .map(|data| {
+                    let block: EthereumBlock = json::from_value(data).unwrap();
+                    BlockHash::from(block.block.hash.unwrap())
+                })
+                .collect()
+        })
+    }
+
+    // Block one is missing, but a sibling of it that is not on the main
+    // chain is in the store
+    let chain = vec![&*GENESIS_BLOCK, &*BLOCK_ONE_SIBLING, &*BLOCK_TWO];
+
+    run_test_async(chain, move |chain_store, _, _| async move {
+        chain_store
+            .cheap_clone()
+            .set_chain_head(Arc::new(BLOCK_THREE.clone()), String::new())
+            .await
+            .unwrap();
+        let range = |from, to| chain_store.cheap_clone().cached_blocks_in_range(from, to);
+
+        let exp = vec![BLOCK_TWO.block_hash(), BLOCK_THREE.block_hash()];
+        assert_eq!(Some(exp), hashes(range(2, 3).await.unwrap()));
+        assert_eq!(None, hashes(range(1, 1).await.unwrap()));
+        assert_eq!(None, hashes(range(0, 3).await.unwrap()));
+        // Blocks beyond the chain head are never complete
+        assert_eq!(None, hashes(range(2, 4).await.unwrap()));
+
+        chain_store
+            .upsert_block(Arc::new(BLOCK_ONE.clone()))
+            .await
+            .unwrap();
+        let exp = vec![BLOCK_ONE.block_hash()];
+        assert_eq!(Some(exp), hashes(range(1, 1).await.unwrap()));
+        let exp = vec![
+            GENESIS_BLOCK.block_hash(),
+            BLOCK_ONE.block_hash(),
+            BLOCK_TWO.block_hash(),
+            BLOCK_THREE.block_hash(),
+        ];
+        assert_eq!(Some(exp), hashes(range(0, 3).await.unwrap()));
+    })
+}
+
 #[test]
 fn block_hashes_by_number() {
     let chain = vec![
@@ -286,6 +365,111 @@ fn block_hashes_by_number() {
     })
 }
 
+/// Run `query` against the index node API and return its data as JSON
+async fn query_index_node(store: Arc<DieselStore>, query: &str) -> json::Value {
+    let graphql_runner = Arc::new(GraphQlRunner::new(
+        &LOGGER,
+        store.cheap_clone(),
+        SUBSCRIPTION_MANAGER.cheap_clone(),
+        LOAD_MANAGER.cheap_clone(),
+        METRICS_REGISTRY.cheap_clone(),
+    ));
+    let link_resolver = Arc::new(LinkResolver::new(
+        vec![IpfsClient::localhost()],
+        Arc::new(EnvVars::default()),
+    ));
+    let service = IndexNodeService::new(
+        LOGGER.clone(),
+        Arc::new(BlockchainMap::new()),
+        graphql_runner,
+        store,
+        link_resolver,
+    );
+
+    let body = json::json!({ "query": query }).to_string();
+    let results = service
+        .handle_graphql_query(hyper::Request::new(body.into()))
+        .await
+        .unwrap();
+    let data = results
+        .first()
+        .unwrap()
+        .duplicate()
+        .to_result()
+        .unwrap()
+        .unwrap();
+    json::to_value(data).unwrap()
+}
+
+#[test]
+fn index_node_block_lookups() {
+    let chain = vec![
+        &*GENESIS_BLOCK,
+        &*BLOCK_ONE,
+        &*BLOCK_TWO,
+        &*BLOCK_TWO_NO_PARENT,
+        &*BLOCK_THREE,
+    ];
+    run_test_async(chain, |chain_store, store, _| async move {
+        let network = chain_store.chain.clone();
+
+        let ancestor_hash = |block: &FakeBlock, offset: BlockNumber| {
+            let query = format!(
+                r#"{{ ancestorBlockData(network: "{}", blockHash: "0x{}", offset: {}) }}"#,
+                network, block.hash, offset
+            );
+            let store = store.cheap_clone();
+            async move {
+                let data = query_index_node(store, &query).await;
+                data["ancestorBlockData"]["block"]["hash"]
+                    .as_str()
+                    .map(str::to_string)
+            }
+        };
+        let hash_of = |block: &FakeBlock| Some(format!("0x{}", block.hash));
+
+        assert_eq!(hash_of(&BLOCK_THREE), ancestor_hash(&BLOCK_THREE, 0).await);
+        assert_eq!(hash_of(&BLOCK_ONE), ancestor_hash(&BLOCK_THREE, 2).await);
+        assert_eq!(
+            hash_of(&GENESIS_BLOCK),
+            ancestor_hash(&BLOCK_THREE, 3).await
+        );
+        // Offsets before genesis and blocks that are not in the chain
+        // store have no ancestor
+        assert_eq!(None, ancestor_hash(&BLOCK_THREE, 4).await);
+        assert_eq!(None, ancestor_hash(&BLOCK_FIVE, 1).await);
+        assert_eq!(None, ancestor_hash(&BLOCK_TWO_NO_PARENT, 1).await);
+
+        let cached_blocks = |number: BlockNumber| {
+            let query = format!(
+                r#"{{ cachedBlocksFromNumber(network: "{}", blockNumber: {}) {{ hash number }} }}"#,
+                network, number
+            );
+            let store = store.cheap_clone();
+            async move {
+                let data = query_index_node(store, &query).await;
+                let mut blocks = data["cachedBlocksFromNumber"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|block| {
+                        assert_eq!(Some(number.to_string().as_str()), block["number"].as_str());
+                        block["hash"].as_str().unwrap().to_string()
+                    })
+                    .collect::<Vec<_>>();
+                blocks.sort();
+                blocks
+            }
+        };
+
+        assert_eq!(vec![BLOCK_ONE.hash.clone()], cached_blocks(1).await);
+        let mut exp = vec![BLOCK_TWO.hash.clone(), BLOCK_TWO_NO_PARENT.hash.clone()];
+        exp.sort();
+        assert_eq!(exp, cached_blocks(2).await);
+        assert!(cached_blocks(127).await.is_empty());
+    })
+}
+
 #[track_caller]
 fn check_ancestor(
     store: &Arc<DieselChainStore>,
@@ -350,6 +534,43 @@ fn ancestor_block_simple() {
     });
 }
 
+#[test]
+fn reorg_depth() {
+    let sibling_two = BLOCK_ONE_SIBLING.make_child(
+        "2ce1bfb7fd3bb3b04ba9505e6b92d8a5d0a40596ad44aa8ed24c83f6b2d3a4b6",
+        None,
+    );
+    let chain = vec![
+        &*GENESIS_BLOCK,
+        &*BLOCK_ONE,
+        &*BLOCK_ONE_SIBLING,
+        &*BLOCK_TWO,
+        &*BLOCK_THREE,
+        &*BLOCK_THREE_NO_PARENT,
+    ];
+
+    run_test(chain, move |store, _| -> Result<(), Error> {
+        let depth = |old: &FakeBlock, new: &FakeBlock| {
+            store
+                .reorg_depth(&old.block_ptr(), &new.block_ptr())
+                .unwrap()
+        };
+
+        // Moving forward or staying put is not a reorg
+        assert_eq!(Some(0), depth(&BLOCK_TWO, &BLOCK_THREE));
+        assert_eq!(Some(0), depth(&BLOCK_ONE, &BLOCK_THREE));
+        assert_eq!(Some(0), depth(&BLOCK_THREE, &BLOCK_THREE));
+        // Blocks one to three are not on the new chain
+        assert_eq!(Some(3), depth(&BLOCK_THREE, &BLOCK_ONE_SIBLING));
+        assert_eq!(Some(1), depth(&BLOCK_ONE, &BLOCK_ONE_SIBLING));
+        // An ancestor that is not in the store stops the search
+        assert_eq!(None, depth(&BLOCK_THREE, &BLOCK_THREE_NO_PARENT));
+        // So does a block that is not in the store
+        assert_eq!(None, depth(&BLOCK_TWO, &sibling_two));
+        Ok(())
+    });
+}
+
 #[test]
 fn ancestor_block_ommers() {
     let chain = vec![
@@ -407,6 +628,27 @@ fn eth_call_cache() {
             .unwrap();
         assert_eq!(&new_return_value, ret.as_slice());
 
+        let cached = store
+            .get_cached_call(address, &call, BLOCK_ONE.block_ptr())
+            .unwrap()
+            .unwrap();
+        assert_eq!(address, cached.contract_address);
+        assert_eq!(BLOCK_ONE.block_ptr(), cached.block_ptr);
+        assert_eq!(&return_value, cached.return_value.as_slice());
+        let in_block = store.get_calls_in_block(BLOCK_ONE.block_ptr()).unwrap();
+        assert_eq!(
+            vec![cached.blake3_id],
+            in_block
+                .into_iter()
+                .map(|c| c.blake3_id)
+                .collect::<Vec<_>>()
+        );
+
+        let cached = store
+            .get_cached_call(address, &call, GENESIS_BLOCK.block_ptr())
+            .unwrap();
+        assert!(cached.is_none());
+
         Ok(())
     })
 }
@@ -1,3 +1,6 @@
+use diesel::dsl::sql;
+use diesel::sql_types::BigInt;
+use diesel::RunQueryDsl;
 use graph::blockchain::block_stream::FirehoseCursor;
 use graph::schema::{EntityType, InputSchema};
 use graph_store_postgres::command_support::OnSync;
@@ -661,3 +664,89 @@ fn prune() {
         })
     }
 }
+
+#[test]
+fn views() {
+    struct Progress;
+    impl PruneReporter for Progress {}
+
+    /// The number of rows in the view `name` of `deployment`, or an error
+    /// if the view does not exist
+    fn count(deployment: &DeploymentLocator, name: &str) -> Result<i64, diesel::result::Error> {
+        let conn = primary_pool().get().unwrap();
+        diesel::select(sql::<BigInt>(&format!(
+            "(select count(*) from sgd{}_views.\"{}\")",
+            deployment.id, name
+        )))
+        .get_result::<i64>(&conn)
+    }
+
+    async fn view_names(
+        store: &DieselSubgraphStore,
+        deployment: &DeploymentLocator,
+    ) -> Vec<String> {
+        let views = store.views(deployment).await.unwrap();
+        views.into_iter().map(|view| view.name).collect()
+    }
+
+    run_test(|store, src| async move {
+        // Users with an age of at least 40: 1 and 2
+        store
+            .create_view(
+                &src,
+                "elders",
+                "select id, name from \"user\" where age >= 40",
+            )
+            .await?;
+        assert_eq!(2, count(&src, "elders").unwrap());
+        assert_eq!(vec!["elders"], view_names(&store, &src).await);
+
+        // Views can only use the tables of the deployment
+        store
+            .create_view(&src, "meta", "select * from subgraphs.subgraph")
+            .await
+            .expect_err("views can not use metadata tables");
+        assert!(count(&src, "meta").is_err());
+
+        // A query can not sneak in a second statement
+        store
+            .create_view(&src, "evil", "select 1; drop table \"user\"")
+            .await
+            .expect_err("views must be a single statement");
+        assert!(count(&src, "evil").is_err());
+        assert_eq!(2, count(&src, "elders").unwrap());
+        assert_eq!(vec!["elders"], view_names(&store, &src).await);
+
+        // Grafting copies the views over the tables of the graft
+        let graft = create_grafted_subgraph(
+            &DeploymentHash::new("grafted").unwrap(),
+            GRAFT_GQL,
+            TEST_SUBGRAPH_ID.as_str(),
+            BLOCKS[1].clone(),
+        )
+        .await?;
+        assert_eq!(2, count(&graft, "elders").unwrap());
+        assert_eq!(vec!["elders"], view_names(&store, &graft).await);
+
+        // Pruning by rebuilding the `user` table recreates the view over
+        // the new table
+        transact_and_wait(&store, &src, BLOCKS[6].clone(), vec![])
+            .await
+            .unwrap();
+        let mut req = PruneRequest::new(&src, 3, 1, 0, 6)?;
+        req.rebuild_threshold = 0.0;
+        req.delete_threshold = 0.0;
+        store
+            .prune(Box::new(Progress), &src, req)
+            .await
+            .expect("pruning works");
+        assert_eq!(2, count(&src, "elders").unwrap());
+
+        // Dropping a view removes it and its definition
+        assert!(store.drop_view(&src, "elders").await?);
+        assert!(!store.drop_view(&src, "elders").await?);
+        assert!(count(&src, "elders").is_err());
+        assert!(view_names(&store, &src).await.is_empty());
+        Ok(())
+    })
+}
@@ -0,0 +1,318 @@
+//! Tests for list fields that are stored in join tables with `@joinTable`
+use diesel::dsl::sql;
+use diesel::sql_types::{Array, Text};
+use diesel::RunQueryDsl;
+use graph::components::store::write::EntityModification;
+use graph::components::store::{EntityCache, GetScope, PruneReporter, PruneRequest, ReadStore};
+use graph::entity;
+use graph::prelude::web3::types::H256;
+use graph::prelude::*;
+use graph::schema::{EntityType, InputSchema};
+use graph_store_postgres::layout_for_tests::writable;
+use lazy_static::lazy_static;
+use test_store::*;
+
+const SCHEMA_GQL: &str = r#"
+    type User @entity {
+        id: ID!
+        name: String!
+    }
+
+    type Group @entity {
+        id: ID!
+        members: [User!]! @joinTable
+    }
+"#;
+
+lazy_static! {
+    static ref TEST_SUBGRAPH_ID: DeploymentHash = DeploymentHash::new("joinTable").unwrap();
+    static ref TEST_SUBGRAPH_SCHEMA: InputSchema =
+        InputSchema::parse(SCHEMA_GQL, TEST_SUBGRAPH_ID.clone())
+            .expect("Failed to parse user schema");
+    static ref GROUP_TYPE: EntityType = TEST_SUBGRAPH_SCHEMA.entity_type("Group").unwrap();
+}
+
+fn block_pointer(number: u8) -> BlockPtr {
+    let hash = H256::from([number; 32]);
+    BlockPtr::from((hash, number as BlockNumber))
+}
+
+fn set_group(id: &str, members: Vec<&str>) -> EntityOperation {
+    let data = entity! { TEST_SUBGRAPH_SCHEMA =>
+        id: id,
+        members: members
+    };
+    EntityOperation::Set {
+        key: GROUP_TYPE.parse_key(id).unwrap(),
+        data,
+    }
+}
+
+fn remove_group(id: &str) -> EntityOperation {
+    EntityOperation::Remove {
+        key: GROUP_TYPE.parse_key(id).unwrap(),
+    }
+}
+
+/// The operations for blocks 0 to 3 that all tests start from
+fn group_changes() -> Vec<Vec<EntityOperation>> {
+    vec![
+        vec![
+            set_group("g1", vec!["u1", "u2"]),
+            set_group("g2", vec!["u3"]),
+        ],
+        // Duplicate ids are only stored once
+        vec![set_group("g1", vec!["u2", "u3", "u3"])],
+        vec![remove_group("g2")],
+        vec![set_group("g1", vec!["u3"])],
+    ]
+}
+
+/// Write `group_changes` with one write per block
+async fn write_groups(deployment: &DeploymentLocator) {
+    for (block, ops) in group_changes().into_iter().enumerate() {
+        transact_and_wait(&SUBGRAPH_STORE, deployment, block_pointer(block as u8), ops)
+            .await
+            .unwrap();
+    }
+}
+
+/// The rows of the join table for `Group.members` formatted as
+/// `parent:child[block_range)` in a predictable order
+fn rows(deployment: &DeploymentLocator) -> Vec<String> {
+    let conn = primary_pool().get().unwrap();
+    diesel::select(sql::<Array<Text>>(&format!(
+        "array(select format('%s:%s%s', parent, child, block_range) \
+                 from sgd{}.\"group$members\" \
+                order by parent, child, lower(block_range))",
+        deployment.id
+    )))
+    .get_result::<Vec<String>>(&conn)
+    .unwrap()
+}
+
+/// The members of group `id` in the join table as of `block`
+fn members_at(deployment: &DeploymentLocator, id: &str, block: BlockNumber) -> Vec<String> {
+    let conn = primary_pool().get().unwrap();
+    diesel::select(sql::<Array<Text>>(&format!(
+        "array(select child from sgd{}.\"group$members\" \
+                where parent = '{}' and block_range @> {} \
+                order by child)",
+        deployment.id, id, block
+    )))
+    .get_result::<Vec<String>>(&conn)
+    .unwrap()
+}
+
+/// The members of group `id` as they are loaded for indexing, or `None`
+/// if there is no such group
+async fn members(deployment: &DeploymentLocator, id: &str) -> Option<Vec<String>> {
+    let writable = SUBGRAPH_STORE
+        .cheap_clone()
+        .writable(LOGGER.clone(), deployment.id, Arc::new(Vec::new()))
+        .await
+        .unwrap();
+    writable
+        .get(&GROUP_TYPE.parse_key(id).unwrap())
+        .unwrap()
+        .map(|group| member_ids(&group))
+}
+
+fn member_ids(group: &Entity) -> Vec<String> {
+    match group.get("members") {
+        Some(Value::List(members)) => members
+            .iter()
+            .map(|member| member.as_str().unwrap().to_string())
+            .collect(),
+        members => panic!("unexpected members {:?}", members),
+    }
+}
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+#[test]
+fn updates_clamp_join_table_rows() {
+    run_test_sequentially(|_| async move {
+        remove_subgraphs();
+        let deployment = create_test_subgraph(&TEST_SUBGRAPH_ID, SCHEMA_GQL).await;
+
+        write_groups(&deployment).await;
+
+        // Only the ids that were added or removed change rows
+        assert_eq!(
+            strings(&["g1:u1[0,1)", "g1:u2[0,3)", "g1:u3[1,)", "g2:u3[0,2)"]),
+            rows(&deployment)
+        );
+        assert_eq!(Some(strings(&["u3"])), members(&deployment, "g1").await);
+        assert_eq!(None, members(&deployment, "g2").await);
+    })
+}
+
+#[test]
+fn batched_updates_clamp_join_table_rows() {
+    run_test_sequentially(|_| async move {
+        remove_subgraphs();
+        let deployment = create_test_subgraph(&TEST_SUBGRAPH_ID, SCHEMA_GQL).await;
+
+        // Hold the writer back so that all blocks are written in one batch
+        flush(&deployment).await.unwrap();
+        writable::allow_steps(&deployment, 0).await;
+        for (block, ops) in group_changes().into_iter().enumerate() {
+            transact_entity_operations(
+                &SUBGRAPH_STORE,
+                &deployment,
+                block_pointer(block as u8),
+                ops,
+            )
+            .await
+            .unwrap();
+        }
+        flush(&deployment).await.unwrap();
+
+        // How the rows are split up depends on batching, but the lists
+        // at each block must not
+        assert_eq!(strings(&["u1", "u2"]), members_at(&deployment, "g1", 0));
+        assert_eq!(strings(&["u2", "u3"]), members_at(&deployment, "g1", 1));
+        assert_eq!(strings(&["u2", "u3"]), members_at(&deployment, "g1", 2));
+        assert_eq!(strings(&["u3"]), members_at(&deployment, "g1", 3));
+        assert_eq!(strings(&["u3"]), members_at(&deployment, "g2", 1));
+        assert!(members_at(&deployment, "g2", 2).is_empty());
+        assert_eq!(Some(strings(&["u3"])), members(&deployment, "g1").await);
+        assert_eq!(None, members(&deployment, "g2").await);
+    })
+}
+
+#[test]
+fn revert_restores_join_table_rows() {
+    run_test_sequentially(|store| async move {
+        remove_subgraphs();
+        let deployment = create_test_subgraph(&TEST_SUBGRAPH_ID, SCHEMA_GQL).await;
+
+        write_groups(&deployment).await;
+
+        // Undo blocks 2 and 3
+        revert_block(&store, &deployment, &block_pointer(1)).await;
+        assert_eq!(
+            strings(&["g1:u1[0,1)", "g1:u2[0,)", "g1:u3[1,)", "g2:u3[0,)"]),
+            rows(&deployment)
+        );
+        assert_eq!(
+            Some(strings(&["u2", "u3"])),
+            members(&deployment, "g1").await
+        );
+        assert_eq!(Some(strings(&["u3"])), members(&deployment, "g2").await);
+
+        // Undo block 1; rows added at that block disappear
+        revert_block(&store, &deployment, &block_pointer(0)).await;
+        assert_eq!(
+            strings(&["g1:u1[0,)", "g1:u2[0,)", "g2:u3[0,)"]),
+            rows(&deployment)
+        );
+        assert_eq!(
+            Some(strings(&["u1", "u2"])),
+            members(&deployment, "g1").await
+        );
+    })
+}
+
+#[test]
+fn prune_removes_old_join_table_rows() {
+    struct Progress;
+    impl PruneReporter for Progress {}
+
+    run_test_sequentially(|_| async move {
+        remove_subgraphs();
+        let deployment = create_test_subgraph(&TEST_SUBGRAPH_ID, SCHEMA_GQL).await;
+
+        write_groups(&deployment).await;
+        transact_and_wait(&SUBGRAPH_STORE, &deployment, block_pointer(6), vec![])
+            .await
+            .unwrap();
+
+        // Keep 4 blocks of history, i.e., everything from block 2 on
+        let req = PruneRequest::new(&deployment, 4, 1, 0, 6).unwrap();
+        SUBGRAPH_STORE
+            .prune(Box::new(Progress), &deployment, req)
+            .await
+            .expect("pruning works");
+
+        assert_eq!(strings(&["g1:u2[0,3)", "g1:u3[1,)"]), rows(&deployment));
+        assert_eq!(Some(strings(&["u3"])), members(&deployment, "g1").await);
+    })
+}
+
+#[test]
+fn graft_copies_join_table_rows() {
+    run_test_sequentially(|_| async move {
+        remove_subgraphs();
+        let deployment = create_test_subgraph(&TEST_SUBGRAPH_ID, SCHEMA_GQL).await;
+
+        write_groups(&deployment).await;
+
+        // Rows that were visible at the graft block become current again
+        let graft_id = DeploymentHash::new("joinTableGraft").unwrap();
+        let base = Some((TEST_SUBGRAPH_ID.clone(), block_pointer(2)));
+        let graft = create_subgraph(&graft_id, SCHEMA_GQL, base)
+            .await
+            .expect("can create grafted subgraph");
+        flush(&graft).await.unwrap();
+
+        assert_eq!(
+            strings(&["g1:u1[0,1)", "g1:u2[0,)", "g1:u3[1,)", "g2:u3[0,2)"]),
+            rows(&graft)
+        );
+        assert_eq!(Some(strings(&["u2", "u3"])), members(&graft, "g1").await);
+        assert_eq!(None, members(&graft, "g2").await);
+
+        // The base is unchanged
+        assert_eq!(
+            strings(&["g1:u1[0,1)", "g1:u2[0,3)", "g1:u3[1,)", "g2:u3[0,2)"]),
+            rows(&deployment)
+        );
+    })
+}
+
+#[test]
+fn cache_and_store_agree_on_join_table_lists() {
+    run_test_sequentially(|_| async move {
+        remove_subgraphs();
+        let deployment = create_test_subgraph(&TEST_SUBGRAPH_ID, SCHEMA_GQL).await;
+        let writable = SUBGRAPH_STORE
+            .cheap_clone()
+            .writable(LOGGER.clone(), deployment.id, Arc::new(Vec::new()))
+            .await
+            .unwrap();
+
+        // The cache sorts and dedupes the list as soon as it is set, in
+        // the same order in which the store reads it back
+        let key = GROUP_TYPE.parse_key("g1").unwrap();
+        let data = entity! { TEST_SUBGRAPH_SCHEMA =>
+            id: "g1",
+            members: vec!["u1", "U2", "u1"]
+        };
+        let mut cache = EntityCache::new(Arc::new(writable));
+        cache.set(key.clone(), data).unwrap();
+        let group = cache.get(&key, GetScope::Store).unwrap().unwrap();
+        assert_eq!(strings(&["U2", "u1"]), member_ids(&group));
+
+        let ops = cache
+            .as_modifications(0)
+            .unwrap()
+            .modifications
+            .into_iter()
+            .map(|modification| match modification {
+                EntityModification::Insert { key, data, .. } => EntityOperation::Set { key, data },
+                modification => panic!("unexpected modification {:?}", modification),
+            })
+            .collect();
+        transact_and_wait(&SUBGRAPH_STORE, &deployment, block_pointer(0), ops)
+            .await
+            .unwrap();
+        assert_eq!(
+            Some(strings(&["U2", "u1"])),
+            members(&deployment, "g1").await
+        );
+    })
+}
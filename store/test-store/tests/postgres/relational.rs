@@ -751,6 +751,36 @@ fn count_scalar_entities(conn: &PgConnection, layout: &Layout) -> usize {
         .len()
 }
 
+#[test]
+fn warm_up() {
+    run_test(|conn, layout| {
+        insert_users(conn, layout);
+        // User 1 now has an old and a current version
+        update_user_entity(
+            conn,
+            layout,
+            "1",
+            &*USER_TYPE,
+            "Johnton",
+            "tonofjohn@email.com",
+            68_i32,
+            184.4,
+            false,
+            Some("yellow"),
+            None,
+            61,
+            1,
+        );
+        let table = layout.table_for_entity(&USER_TYPE).unwrap();
+
+        // Both queries read as many rows as they are allowed to
+        assert_eq!(4, table.warm_up(conn, 2).unwrap());
+        // Only current versions count for the first query, but all
+        // versions for the second one
+        assert_eq!(7, table.warm_up(conn, 10).unwrap());
+    });
+}
+
 #[test]
 fn delete() {
     run_test(|conn, layout| {
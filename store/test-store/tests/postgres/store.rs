@@ -1067,6 +1067,67 @@ fn revert_block_with_delete() {
     })
 }
 
+#[test]
+fn entity_changes_in_block() {
+    fn ids(changes: &[EntityOperation]) -> Vec<(String, bool)> {
+        let mut ids: Vec<_> = changes
+            .iter()
+            .map(|op| match op {
+                EntityOperation::Set { key, .. } => (key.entity_id.to_string(), true),
+                EntityOperation::Remove { key } => (key.entity_id.to_string(), false),
+            })
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    run_test(|store, _, deployment| async move {
+        let subgraph_store = store.subgraph_store();
+
+        transact_and_wait(
+            &subgraph_store,
+            &deployment,
+            TEST_BLOCK_3_PTR.clone(),
+            vec![EntityOperation::Remove {
+                key: USER_TYPE.parse_key("2").unwrap(),
+            }],
+        )
+        .await
+        .unwrap();
+
+        let changes = |block| {
+            subgraph_store
+                .entity_changes_in_block(&deployment.hash, block)
+                .unwrap()
+        };
+
+        // Users 2 and 3 were inserted in block 1
+        let block1 = changes(TEST_BLOCK_1_PTR.number);
+        assert_eq!(
+            vec![("2".to_string(), true), ("3".to_string(), true)],
+            ids(&block1)
+        );
+
+        // User 3 was updated in block 2, and the change carries the new data
+        let block2 = changes(TEST_BLOCK_2_PTR.number);
+        assert_eq!(vec![("3".to_string(), true)], ids(&block2));
+        match &block2[0] {
+            EntityOperation::Set { data, .. } => assert_eq!(
+                Some(&Value::String("teeko@email.com".to_owned())),
+                data.get("email")
+            ),
+            EntityOperation::Remove { .. } => unreachable!("user 3 was updated"),
+        }
+
+        // User 2 was removed in block 3
+        let block3 = changes(TEST_BLOCK_3_PTR.number);
+        assert_eq!(vec![("2".to_string(), false)], ids(&block3));
+
+        // Nothing happened in block 4
+        assert!(changes(TEST_BLOCK_4_PTR.number).is_empty());
+    })
+}
+
 #[test]
 fn revert_block_with_partial_update() {
     run_test(|store, writable, deployment| async move {
@@ -1128,6 +1189,7 @@ fn mock_data_source() -> graph_chain_ethereum::DataSource {
             event_handlers: vec![],
             call_handlers: vec![],
             block_handlers: vec![],
+            contract_creation_handlers: vec![],
             link: Link {
                 link: "link".to_owned(),
             },
@@ -115,6 +115,97 @@ fn reassign_subgraph() {
     })
 }
 
+#[test]
+fn pause_and_resume_subgraph() {
+    async fn setup() -> DeploymentLocator {
+        let id = DeploymentHash::new("pauseSubgraph").unwrap();
+        remove_subgraphs();
+        create_test_subgraph(&id, SUBGRAPH_GQL).await
+    }
+
+    fn is_paused(store: &SubgraphStore, deployment: &DeploymentLocator) -> Option<bool> {
+        store
+            .assignment_status(deployment)
+            .unwrap()
+            .map(|(_, paused)| paused)
+    }
+
+    fn is_active(store: &SubgraphStore, node: &NodeId, deployment: &DeploymentLocator) -> bool {
+        store.active_assignments(node).unwrap().contains(deployment)
+    }
+
+    run_test_sequentially(|store| async move {
+        let deployment = setup().await;
+        let store = store.subgraph_store();
+        let node = store.assigned_node(&deployment).unwrap().unwrap();
+        assert_eq!(Some(false), is_paused(&store, &deployment));
+        assert!(is_active(&store, &node, &deployment));
+
+        // A paused deployment stays assigned to its node, but the node
+        // stops indexing it
+        for _ in 0..2 {
+            let (_, events) = tap_store_events(|| store.pause_subgraph(&deployment).unwrap());
+            assert_eq!(vec![StoreEvent::new(vec![unassigned(&deployment)])], events);
+            assert_eq!(Some(true), is_paused(&store, &deployment));
+            assert_eq!(
+                Some(&node),
+                store.assigned_node(&deployment).unwrap().as_ref()
+            );
+            assert!(!is_active(&store, &node, &deployment));
+        }
+
+        let (_, events) = tap_store_events(|| store.resume_subgraph(&deployment).unwrap());
+        assert_eq!(vec![StoreEvent::new(vec![assigned(&deployment)])], events);
+        assert_eq!(Some(false), is_paused(&store, &deployment));
+        assert!(is_active(&store, &node, &deployment));
+    })
+}
+
+#[test]
+fn prune_subgraph() {
+    const NAME: &str = "pruneSubgraph";
+
+    run_test_sequentially(|store| async move {
+        use graph::data::subgraph::status;
+        use graph::env::ENV_VARS;
+
+        remove_subgraphs();
+        let deployment =
+            create_test_subgraph(&DeploymentHash::new(NAME).unwrap(), SUBGRAPH_GQL).await;
+        let subgraph_store = store.subgraph_store();
+        transact_and_wait(&subgraph_store, &deployment, BLOCK_ONE.clone(), vec![])
+            .await
+            .unwrap();
+
+        let history_blocks = || {
+            store
+                .status(status::Filter::DeploymentIds(vec![deployment.id]))
+                .unwrap()
+                .first()
+                .unwrap()
+                .history_blocks
+        };
+        let reorg_threshold = ENV_VARS.reorg_threshold;
+        let before = history_blocks();
+
+        // A deployment must keep more history than the reorg threshold
+        let err = subgraph_store
+            .prune_subgraph(&deployment, Some(reorg_threshold))
+            .unwrap_err();
+        assert!(err.to_string().contains("reorg threshold"));
+        assert_eq!(before, history_blocks());
+
+        // Pruning with the configured amount of history leaves it alone
+        subgraph_store.prune_subgraph(&deployment, None).unwrap();
+        assert_eq!(before, history_blocks());
+
+        subgraph_store
+            .prune_subgraph(&deployment, Some(reorg_threshold + 10))
+            .unwrap();
+        assert_eq!(reorg_threshold + 10, history_blocks());
+    })
+}
+
 #[test]
 fn create_subgraph() {
     const SUBGRAPH_NAME: &str = "create/subgraph";
@@ -379,6 +470,19 @@ fn status() {
         assert_eq!(NAME, info.subgraph);
         assert!(!info.synced);
 
+        let page = |skip, first| {
+            store
+                .status(status::Filter::Page { skip, first })
+                .unwrap()
+                .into_iter()
+                .map(|info| info.subgraph)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![NAME], page(0, Some(1)));
+        assert_eq!(vec![OTHER], page(1, Some(1)));
+        assert_eq!(vec![OTHER], page(1, None));
+        assert!(page(2, None).is_empty());
+
         let infos = store
             .status(status::Filter::SubgraphName(NAME.to_string()))
             .unwrap();
@@ -423,7 +527,9 @@ fn status() {
             subgraph_id: deployment.hash.clone(),
             message: MSG.to_string(),
             block_ptr: Some(GENESIS_PTR.clone()),
-            handler: None,
+            handler: Some("handleSwallow".to_string()),
+            data_source: Some("Coconut".to_string()),
+            transaction_hash: Some(format!("0x{}", "ab".repeat(32))),
             deterministic: true,
         };
 
@@ -447,10 +553,53 @@ fn status() {
         assert_eq!(SubgraphHealth::Failed, info.health);
         let error = info.fatal_error.as_ref().unwrap();
         assert_eq!(MSG, error.message.as_str());
+        assert_eq!(Some("handleSwallow"), error.handler.as_deref());
+        assert_eq!(Some("Coconut"), error.data_source.as_deref());
+        assert_eq!(
+            Some(format!("0x{}", "ab".repeat(32))),
+            error.transaction_hash
+        );
         assert!(error.deterministic);
     })
 }
 
+#[test]
+fn blocks_behind() {
+    const NAME: &str = "blocksBehindSubgraph";
+
+    run_test_sequentially(|store| async move {
+        use graph::components::store::{BlockStore as _, ChainStore as _};
+
+        remove_subgraphs();
+        let chain = vec![
+            &*block_store::GENESIS_BLOCK,
+            &*block_store::BLOCK_ONE,
+            &*block_store::BLOCK_TWO,
+            &*block_store::BLOCK_THREE,
+        ];
+        block_store::set_chain(chain, NETWORK_NAME).await;
+        let deployment =
+            create_test_subgraph(&DeploymentHash::new(NAME).unwrap(), SUBGRAPH_GQL).await;
+        let subgraph_store = store.subgraph_store();
+        transact_and_wait(&subgraph_store, &deployment, BLOCK_ONE.clone(), vec![])
+            .await
+            .unwrap();
+
+        // Without a chain head it is not known how far behind we are
+        let behind = subgraph_store.blocks_behind(&[deployment.clone()]).unwrap();
+        assert!(behind.is_empty());
+
+        let chain_store = store.block_store().chain_store(NETWORK_NAME).unwrap();
+        chain_store
+            .cheap_clone()
+            .attempt_chain_head_update(10)
+            .await
+            .unwrap();
+        let behind = subgraph_store.blocks_behind(&[deployment.clone()]).unwrap();
+        assert_eq!(Some(&2), behind.get(&deployment.id));
+    })
+}
+
 #[test]
 fn version_info() {
     const NAME: &str = "versionInfoSubgraph";
@@ -528,6 +677,10 @@ fn subgraph_features() {
         assert_eq!(handler_kinds.len(), 2);
         assert!(handler_kinds.contains(&"mock_handler_1".to_string()));
         assert!(handler_kinds.contains(&"mock_handler_2".to_string()));
+        assert_eq!(
+            vec!["mock_handler_1".to_string(), "mock_handler_2".to_string()],
+            handler_kinds
+        );
 
         test_store::remove_subgraph(&id);
         let features = get_subgraph_features(id.to_string());
@@ -553,6 +706,8 @@ fn subgraph_error() {
             message: "test".to_string(),
             block_ptr: None,
             handler: None,
+            data_source: None,
+            transaction_hash: None,
             deterministic: false,
         };
 
@@ -568,6 +723,8 @@ fn subgraph_error() {
             message: "test".to_string(),
             block_ptr: None,
             handler: None,
+            data_source: None,
+            transaction_hash: None,
             deterministic: false,
         };
 
@@ -582,6 +739,8 @@ fn subgraph_error() {
             message: "test2".to_string(),
             block_ptr: None,
             handler: None,
+            data_source: None,
+            transaction_hash: None,
             deterministic: false,
         };
 
@@ -614,6 +773,8 @@ fn subgraph_non_fatal_error() {
             message: "test".to_string(),
             block_ptr: Some(BLOCKS[1].clone()),
             handler: None,
+            data_source: None,
+            transaction_hash: None,
             deterministic: true,
         };
 
@@ -634,6 +795,8 @@ fn subgraph_non_fatal_error() {
             message: "test2".to_string(),
             block_ptr: None,
             handler: None,
+            data_source: None,
+            transaction_hash: None,
             deterministic: false,
         };
 
@@ -685,6 +848,8 @@ fn fatal_vs_non_fatal() {
             message: "test".to_string(),
             block_ptr: Some(BLOCKS[1].clone()),
             handler: None,
+            data_source: None,
+            transaction_hash: None,
             deterministic: true,
         };
 
@@ -779,6 +944,8 @@ fn fail_unfail_deterministic_error() {
             message: "test".to_string(),
             block_ptr: Some(BLOCKS[1].clone()),
             handler: None,
+            data_source: None,
+            transaction_hash: None,
             deterministic: true,
         };
 
@@ -899,6 +1066,8 @@ fn fail_unfail_deterministic_error_noop() {
             message: "test".to_string(),
             block_ptr: Some(BLOCKS[1].clone()),
             handler: None,
+            data_source: None,
+            transaction_hash: None,
             deterministic: false, // wrong determinism
         };
 
@@ -932,6 +1101,8 @@ fn fail_unfail_deterministic_error_noop() {
             message: "test".to_string(),
             block_ptr: Some(BLOCKS[2].clone()), // wrong block
             handler: None,
+            data_source: None,
+            transaction_hash: None,
             deterministic: true, // right determinism
         };
 
@@ -998,6 +1169,8 @@ fn fail_unfail_non_deterministic_error() {
             message: "test".to_string(),
             block_ptr: Some(BLOCKS[1].clone()),
             handler: None,
+            data_source: None,
+            transaction_hash: None,
             deterministic: false,
         };
 
@@ -1123,6 +1296,8 @@ fn fail_unfail_non_deterministic_error_noop() {
             message: "test".to_string(),
             block_ptr: Some(BLOCKS[1].clone()),
             handler: None,
+            data_source: None,
+            transaction_hash: None,
             deterministic: true, // wrong determinism
         };
 
@@ -1152,6 +1327,8 @@ fn fail_unfail_non_deterministic_error_noop() {
             message: "test".to_string(),
             block_ptr: Some(BLOCKS[2].clone()), // wrong block
             handler: None,
+            data_source: None,
+            transaction_hash: None,
             deterministic: false, // right determinism
         };
 
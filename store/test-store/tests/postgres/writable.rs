@@ -1,3 +1,4 @@
+use diesel::connection::SimpleConnection as _;
 use graph::blockchain::block_stream::FirehoseCursor;
 use graph::data::subgraph::schema::DeploymentCreate;
 use graph::data::value::Word;
@@ -6,12 +7,14 @@ use graph::schema::{EntityKey, EntityType, InputSchema};
 use lazy_static::lazy_static;
 use std::collections::BTreeSet;
 use std::marker::PhantomData;
+use std::time::Duration;
 use test_store::*;
 
 use graph::components::store::{DeploymentLocator, DerivedEntityQuery, WritableStore};
 use graph::data::subgraph::*;
 use graph::semver::Version;
 use graph::{entity, prelude::*};
+use graph_store_postgres::command_support::catalog;
 use graph_store_postgres::layout_for_tests::writable;
 use graph_store_postgres::{Store as DieselStore, SubgraphStore as DieselSubgraphStore};
 use web3::types::H256;
@@ -102,6 +105,164 @@ where
     });
 }
 
+#[test]
+fn refuses_second_writer() {
+    run_test_sequentially(|store| async move {
+        let subgraph_store = store.subgraph_store();
+        remove_test_data(subgraph_store.clone());
+        let deployment = insert_test_data(subgraph_store.clone()).await;
+
+        // Act like another node that writes to the deployment by taking
+        // its writer lock on a connection of our own
+        let conn = primary_pool().get().unwrap();
+        conn.batch_execute(&format!("select pg_advisory_lock(4, {})", deployment.id))
+            .unwrap();
+
+        let res = store
+            .subgraph_store()
+            .writable(LOGGER.clone(), deployment.id, Arc::new(Vec::new()))
+            .await;
+        assert!(matches!(res, Err(StoreError::DeploymentLocked(_))));
+
+        // Once the other node is gone, we can write
+        conn.batch_execute(&format!("select pg_advisory_unlock(4, {})", deployment.id))
+            .unwrap();
+        store
+            .subgraph_store()
+            .writable(LOGGER.clone(), deployment.id, Arc::new(Vec::new()))
+            .await
+            .expect("we can get a writable store");
+    });
+}
+
+#[test]
+fn standby_takes_over() {
+    run_test_sequentially(|store| async move {
+        let subgraph_store = store.subgraph_store();
+        remove_test_data(subgraph_store.clone());
+        let deployment = insert_test_data(subgraph_store.clone()).await;
+        let standby = NodeId::new("standby").unwrap();
+
+        let pconn = catalog::Connection::new(primary_pool().get().unwrap());
+        let site = pconn.locate_site(deployment.clone()).unwrap().unwrap();
+        pconn.set_standby(&site, Some(&standby)).unwrap();
+        assert_eq!(
+            vec![deployment.clone()],
+            subgraph_store.standby_assignments(&standby).unwrap()
+        );
+        assert!(subgraph_store
+            .standby_assignments(&NODE_ID)
+            .unwrap()
+            .is_empty());
+
+        // Act like the node that writes to the deployment
+        let conn = primary_pool().get().unwrap();
+        conn.batch_execute(&format!("select pg_advisory_lock(4, {})", deployment.id))
+            .unwrap();
+
+        let writable = subgraph_store
+            .clone()
+            .standby_writable(
+                LOGGER.clone(),
+                deployment.id,
+                Arc::new(Vec::new()),
+                standby.clone(),
+            )
+            .await
+            .unwrap();
+        assert!(writable.deployment_synced().is_err());
+
+        let take_over = graph::spawn(writable.take_over());
+        graph::tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!take_over.is_finished());
+
+        // Once the other node is gone, the standby takes over and the
+        // nodes swap roles
+        conn.batch_execute(&format!("select pg_advisory_unlock(4, {})", deployment.id))
+            .unwrap();
+        let writable = take_over.await.unwrap().unwrap();
+        assert!(writable.is_some());
+        assert_eq!(Some(standby), pconn.assigned_node(&site).unwrap());
+        assert_eq!(Some(NODE_ID.clone()), pconn.standby_node(&site).unwrap());
+
+        subgraph_store.stop_subgraph(&deployment).await.unwrap();
+    });
+}
+
+#[test]
+fn replay_reads_previous_block() {
+    run_test(|store, writable, deployment| async move {
+        let subgraph_store = store.subgraph_store();
+        for count in 1..6 {
+            insert_count(&subgraph_store, &deployment, count).await;
+        }
+        writable.flush().await.unwrap();
+
+        let replay = subgraph_store
+            .clone()
+            .replay_writable(LOGGER.clone(), deployment.id, Arc::new(Vec::new()), 4)
+            .await
+            .unwrap();
+        assert_eq!(3, count_get(replay.as_ref()));
+        assert_eq!(3, count_get_many(replay.as_ref()));
+        assert_eq!(3, count_get_derived(replay.as_ref()));
+        assert!(replay.deployment_synced().is_err());
+
+        // The deployment has not processed block 6 yet
+        let res = subgraph_store
+            .clone()
+            .replay_writable(LOGGER.clone(), deployment.id, Arc::new(Vec::new()), 6)
+            .await;
+        assert!(res.is_err());
+    })
+}
+
+#[test]
+fn snapshot_roundtrip() {
+    run_test(|store, writable, deployment| async move {
+        let subgraph_store = store.subgraph_store();
+        for count in 1..6 {
+            insert_count(&subgraph_store, &deployment, count).await;
+        }
+        writable.flush().await.unwrap();
+
+        let dir = std::env::temp_dir().join(format!("snapshot-{}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        let snapshot = subgraph_store
+            .export_snapshot(&deployment, block_pointer(3), &dir)
+            .unwrap();
+        assert_eq!(3, snapshot.block_number);
+        assert_eq!(1, snapshot.entities[COUNTER]);
+
+        // A snapshot can only be exported into an empty directory
+        assert!(subgraph_store
+            .export_snapshot(&deployment, block_pointer(3), &dir)
+            .is_err());
+
+        // Import into a fresh deployment of the same subgraph
+        remove_test_data(subgraph_store.clone());
+        let deployment = insert_test_data(subgraph_store.clone()).await;
+        subgraph_store
+            .import_snapshot(&LOGGER, &deployment, &dir)
+            .unwrap();
+
+        let writable = subgraph_store
+            .clone()
+            .writable(LOGGER.clone(), deployment.id, Arc::new(Vec::new()))
+            .await
+            .unwrap();
+        assert_eq!(Some(block_pointer(3)), writable.block_ptr());
+        assert_eq!(3, count_get(writable.as_ref()));
+
+        // The deployment now has data and can not import again
+        assert!(subgraph_store
+            .import_snapshot(&LOGGER, &deployment, &dir)
+            .is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+    })
+}
+
 fn block_pointer(number: u8) -> BlockPtr {
     let hash = H256::from([number; 32]);
     BlockPtr::from((hash, number as BlockNumber))
@@ -286,3 +447,120 @@ fn restart() {
         writable.flush().await.unwrap();
     })
 }
+
+#[test]
+fn stop_poisoned_writer() {
+    run_test(|store, writable, deployment| async move {
+        let subgraph_store = store.subgraph_store();
+        let schema = subgraph_store.input_schema(&deployment.hash).unwrap();
+
+        // Poison the writer by leaving out the non-nullable `count` attribute
+        let entity_ops = vec![EntityOperation::Set {
+            key: count_key("1"),
+            data: entity! { schema => id: "1" },
+        }];
+        transact_entity_operations(&subgraph_store, &deployment, block_pointer(1), entity_ops)
+            .await
+            .unwrap();
+        writable
+            .flush()
+            .await
+            .expect_err("writing with missing non-nullable field should fail");
+
+        // Stopping the deployment still releases the writer lock so that
+        // another node can take over
+        subgraph_store.stop_subgraph(&deployment).await.unwrap();
+        let conn = primary_pool().get().unwrap();
+        conn.batch_execute(&format!(
+            "do $$ begin
+               if not pg_try_advisory_lock(4, {id}) then
+                 raise 'the writer lock is still held';
+               end if;
+             end $$;
+             select pg_advisory_unlock(4, {id})",
+            id = deployment.id
+        ))
+        .unwrap();
+    })
+}
+
+#[test]
+fn fail_writes_when_writer_lock_is_lost() {
+    run_test(|store, writable, deployment| async move {
+        let subgraph_store = store.subgraph_store();
+        insert_count(&subgraph_store, &deployment, 1).await;
+        writable.flush().await.unwrap();
+
+        // Break the connection that holds the writer lock; Postgres
+        // releases the lock and another node could take it now
+        let conn = primary_pool().get().unwrap();
+        conn.batch_execute(&format!(
+            "select pg_terminate_backend(pid) from pg_locks
+              where locktype = 'advisory' and classid = 4 and objid = {} and objsubid = 2",
+            deployment.id
+        ))
+        .unwrap();
+
+        insert_count(&subgraph_store, &deployment, 2).await;
+        let err = writable
+            .flush()
+            .await
+            .expect_err("writing without the writer lock should fail");
+        assert!(matches!(err, StoreError::WriterLockLost(_)), "{}", err);
+
+        subgraph_store.stop_subgraph(&deployment).await.unwrap();
+    })
+}
+
+#[test]
+fn rewind_subgraph() {
+    run_test_sequentially(|store| async move {
+        let subgraph_store = store.subgraph_store();
+        remove_test_data(subgraph_store.clone());
+        let deployment = insert_test_data(subgraph_store.clone()).await;
+
+        // A deployment that has not processed any blocks can not be rewound
+        let res = subgraph_store.rewind_subgraph(&deployment, block_pointer(1));
+        assert!(matches!(res, Err(StoreError::ConstraintViolation(_))));
+
+        for count in 1..4 {
+            insert_count(&subgraph_store, &deployment, count).await;
+        }
+        // The deployment can only be rewound once this node has stopped
+        // writing to it
+        let res = subgraph_store.rewind_subgraph(&deployment, block_pointer(2));
+        assert!(matches!(res, Err(StoreError::DeploymentLocked(_))));
+        subgraph_store.stop_subgraph(&deployment).await.unwrap();
+
+        // Rewinding can not go forward
+        let res = subgraph_store.rewind_subgraph(&deployment, block_pointer(5));
+        assert!(matches!(res, Err(StoreError::ConstraintViolation(_))));
+
+        // Rewinding can not go below the start block or into pruned data
+        let conn = primary_pool().get().unwrap();
+        conn.batch_execute(&format!(
+            "update subgraphs.subgraph_manifest set start_block_number = 2 where id = {}",
+            deployment.id
+        ))
+        .unwrap();
+        let res = subgraph_store.rewind_subgraph(&deployment, block_pointer(1));
+        assert!(matches!(res, Err(StoreError::ConstraintViolation(_))));
+        conn.batch_execute(&format!(
+            "update subgraphs.subgraph_manifest set start_block_number = null where id = {id};
+             update subgraphs.subgraph_deployment set earliest_block_number = 2 where id = {id}",
+            id = deployment.id
+        ))
+        .unwrap();
+        let res = subgraph_store.rewind_subgraph(&deployment, block_pointer(1));
+        assert!(matches!(res, Err(StoreError::ConstraintViolation(_))));
+
+        subgraph_store
+            .rewind_subgraph(&deployment, block_pointer(2))
+            .unwrap();
+        let head = subgraph_store
+            .least_block_ptr(&deployment.hash)
+            .await
+            .unwrap();
+        assert_eq!(Some(block_pointer(2)), head);
+    })
+}
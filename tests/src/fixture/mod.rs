@@ -24,7 +24,10 @@ use graph::components::store::{BlockStore, DeploymentLocator};
 use graph::components::subgraph::Settings;
 use graph::data::graphql::load_manager::LoadManager;
 use graph::data::query::{Query, QueryTarget};
-use graph::data::subgraph::schema::{SubgraphError, SubgraphHealth};
+use graph::data::subgraph::parse_raw_manifest;
+use graph::data::subgraph::schema::{
+    DeploymentMetadata, DeploymentPlacement, SubgraphError, SubgraphHealth,
+};
 use graph::endpoint::EndpointMetrics;
 use graph::env::EnvVars;
 use graph::firehose::{FirehoseEndpoint, FirehoseEndpoints, SubgraphLimit};
@@ -249,7 +252,7 @@ impl TestContext {
             .await
             .unwrap();
 
-        let raw: serde_yaml::Mapping = serde_yaml::from_slice(&file_bytes).unwrap();
+        let raw: serde_yaml::Mapping = parse_raw_manifest(&file_bytes).unwrap();
 
         (logger, deployment, raw)
     }
@@ -539,9 +542,12 @@ pub async fn setup<C: Blockchain>(
         None,
         graft_block,
         None,
+        DeploymentMetadata::default(),
+        DeploymentPlacement::default(),
     )
     .await
-    .expect("failed to create subgraph version");
+    .expect("failed to create subgraph version")
+    .deployment;
 
     let arweave_resolver = Arc::new(ArweaveClient::default());
 
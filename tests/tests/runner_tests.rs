@@ -384,6 +384,8 @@ async fn api_version_0_0_8() {
         message,
         block_ptr: Some(stop_block),
         handler: None,
+        data_source: None,
+        transaction_hash: None,
         deterministic: true,
     };
     assert_eq_ignore_backtrace(&err, &expected_err);
@@ -869,6 +871,8 @@ async fn file_data_sources() {
         message,
         block_ptr: Some(stop_block),
         handler: None,
+        data_source: None,
+        transaction_hash: None,
         deterministic: false,
     };
     assert_eq_ignore_backtrace(&err, &expected_err);